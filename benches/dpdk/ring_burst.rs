@@ -0,0 +1,156 @@
+// The throughput of the ring enqueue/dequeue path.
+//
+// Run with `cargo bench -p benches --bench ring_burst`, a subset is selected
+// by name, e.g. `cargo bench -p benches --bench ring_burst -- spsc_thread`.
+// The EAL runs in the `--no-huge` mode, so no hugepages are required.
+//
+// - `ring_single_thread` enqueues and then dequeues a burst on one thread, for
+//   each sync mode of the ring.
+// - `ring_spsc_thread` enqueues the bursts to a single-producer
+//   single-consumer ring, whose mbufs are dequeued by another thread and sent
+//   back through a second ring.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use arrayvec::*;
+use criterion::measurement::WallTime;
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkGroup, Criterion, Throughput,
+};
+use rpkt_dpdk::*;
+
+const RING_SIZE: u32 = 1024;
+
+const SYNC_MODES: [(&str, RingFlags); 4] = [
+    ("mp_mc", RingFlags::empty()),
+    ("sp_sc", RingFlags::SP_ENQ.union(RingFlags::SC_DEQ)),
+    ("rts", RingFlags::MP_RTS_ENQ.union(RingFlags::MC_RTS_DEQ)),
+    ("hts", RingFlags::MP_HTS_ENQ.union(RingFlags::MC_HTS_DEQ)),
+];
+
+fn init() -> Mempool {
+    let mut eal_config = EalConfig::new();
+    eal_config.set_no_huge(true);
+    let mut option = DpdkOption::new();
+    option.set_eal_config(eal_config);
+    option.init().unwrap();
+
+    let mut config = MempoolConf::default();
+    config.nb_mbufs = 2048;
+    service().mempool_create("wtf", &config).unwrap()
+}
+
+fn create_ring(name: String, flag: RingFlags) -> Ring {
+    Ring::try_create(
+        name,
+        &RingConf {
+            count: RING_SIZE,
+            socket_id: 0,
+            flag,
+        },
+    )
+    .unwrap()
+}
+
+fn single_thread<const N: usize>(
+    group: &mut BenchmarkGroup<WallTime>,
+    mp: &Mempool,
+    mode: &str,
+    flag: RingFlags,
+) {
+    let ring = create_ring(format!("bench_{mode}_{N}"), flag);
+    let mut batch = ArrayVec::<Mbuf, N>::new();
+    mp.fill_batch(&mut batch);
+    assert_eq!(batch.len(), N);
+
+    group.throughput(Throughput::Elements(N as u64));
+    group.bench_function(format!("{mode}/{N}"), |b| {
+        b.iter(|| {
+            ring.enqueue_burst(black_box(&mut batch));
+            ring.dequeue_burst(black_box(&mut batch));
+        })
+    });
+}
+
+fn spsc_thread<const N: usize>(group: &mut BenchmarkGroup<WallTime>, mp: &Mempool) {
+    let flag = RingFlags::SP_ENQ | RingFlags::SC_DEQ;
+    let fwd = create_ring(format!("bench_fwd_{N}"), flag.clone());
+    let back = create_ring(format!("bench_back_{N}"), flag);
+
+    // Seed the rings with 4 bursts of mbufs that circulate between the threads.
+    for _ in 0..4 {
+        let mut batch = ArrayVec::<Mbuf, N>::new();
+        mp.fill_batch(&mut batch);
+        assert_eq!(batch.len(), N);
+        back.enqueue_burst(&mut batch);
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let consumer = {
+        let fwd = fwd.clone();
+        let back = back.clone();
+        let stop = stop.clone();
+        std::thread::spawn(move || {
+            let mut batch = ArrayVec::<Mbuf, N>::new();
+            while !stop.load(Ordering::Relaxed) {
+                fwd.dequeue_burst(&mut batch);
+                back.enqueue_burst(&mut batch);
+            }
+            while !batch.is_empty() {
+                back.enqueue_burst(&mut batch);
+            }
+        })
+    };
+
+    group.throughput(Throughput::Elements(N as u64));
+    group.bench_function(format!("{N}"), |b| {
+        b.iter_custom(|iters| {
+            let mut batch = ArrayVec::<Mbuf, N>::new();
+            let start = Instant::now();
+            for _ in 0..iters {
+                while batch.len() < N {
+                    back.dequeue_burst(&mut batch);
+                }
+                while !batch.is_empty() {
+                    fwd.enqueue_burst(&mut batch);
+                }
+            }
+            start.elapsed()
+        })
+    });
+
+    stop.store(true, Ordering::Relaxed);
+    consumer.join().unwrap();
+    // Leave no mbuf in the rings, so that the mempool can be freed.
+    let mut batch = ArrayVec::<Mbuf, N>::new();
+    while fwd.dequeue_burst(&mut batch) > 0 || back.dequeue_burst(&mut batch) > 0 {
+        batch.clear();
+    }
+}
+
+pub fn b1(c: &mut Criterion) {
+    let mp = init();
+
+    let mut group = c.benchmark_group("ring_single_thread");
+    for (mode, flag) in SYNC_MODES {
+        single_thread::<8>(&mut group, &mp, mode, flag.clone());
+        single_thread::<32>(&mut group, &mp, mode, flag.clone());
+        single_thread::<128>(&mut group, &mp, mode, flag);
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("ring_spsc_thread");
+    group.measurement_time(Duration::from_secs(3));
+    spsc_thread::<8>(&mut group, &mp);
+    spsc_thread::<32>(&mut group, &mp);
+    spsc_thread::<128>(&mut group, &mp);
+    group.finish();
+
+    drop(mp);
+    service().mempool_free("wtf").unwrap();
+}
+
+criterion_group!(benches, b1);
+criterion_main!(benches);
@@ -48,16 +48,36 @@ fn build_dpdk_ffi() {
         // generate useful dpdk functions
         .allowlist_function("rte_thread_set_affinity")
         .allowlist_function("rte_thread_register")
+        .allowlist_function("rte_thread_unregister")
+        .allowlist_function("rte_socket_count")
+        .allowlist_function("rte_socket_id_by_idx")
+        .allowlist_function("rte_socket_id")
         .allowlist_function("rte_pktmbuf_pool_create")
+        .allowlist_function("rte_pktmbuf_pool_create_by_ops")
         .allowlist_function("rte_mempool_free")
         .allowlist_function("rte_pktmbuf_free_bulk")
+        .allowlist_function("rte_pktmbuf_copy")
+        .allowlist_function("rte_mbuf_check")
         .allowlist_function("rte_mempool_avail_count") // this can be removed
         .allowlist_function("rte_eth_dev_info_get")
         .allowlist_function("rte_eth_dev_count_avail")
+        .allowlist_function("rte_eth_find_next_owned_by")
+        .allowlist_function("rte_eth_dev_get_name_by_port")
+        .allowlist_function("rte_eth_dev_get_port_by_name")
+        .allowlist_function("rte_eth_dev_fw_version_get")
+        .allowlist_function("rte_dev_name")
+        .allowlist_function("rte_dev_bus")
+        .allowlist_function("rte_dev_bus_info")
+        .allowlist_function("rte_bus_name")
         .allowlist_function("rte_eth_macaddr_get")
         .allowlist_function("rte_eth_stats_get")
+        .allowlist_function("rte_eth_dev_rss_reta_query")
+        .allowlist_function("rte_eth_dev_set_rx_queue_stats_mapping")
+        .allowlist_function("rte_eth_dev_set_tx_queue_stats_mapping")
+        .allowlist_function("rte_eth_set_queue_rate_limit")
         .allowlist_function("rte_eth_dev_socket_id")
         .allowlist_function("rte_eth_dev_configure")
+        .allowlist_function("rte_eth_dev_adjust_nb_rx_tx_desc")
         .allowlist_function("rte_eth_dev_start")
         .allowlist_function("rte_eth_dev_stop")
         .allowlist_function("rte_eth_dev_close")
@@ -65,19 +85,91 @@ fn build_dpdk_ffi() {
         .allowlist_function("rte_eth_tx_queue_setup")
         .allowlist_function("rte_eth_promiscuous_enable")
         .allowlist_function("rte_eth_promiscuous_disable")
+        .allowlist_function("rte_eth_dev_set_vlan_offload")
+        .allowlist_function("rte_eth_dev_get_vlan_offload")
+        .allowlist_function("rte_eth_dev_vlan_filter")
+        .allowlist_function("rte_eth_dev_flow_ctrl_get")
+        .allowlist_function("rte_eth_dev_flow_ctrl_set")
+        .allowlist_function("rte_eth_add_rx_callback")
+        .allowlist_function("rte_eth_remove_rx_callback")
+        .allowlist_function("rte_eth_dev_rx_intr_enable")
+        .allowlist_function("rte_eth_dev_rx_intr_disable")
+        .allowlist_function("rte_eth_dev_rx_intr_ctl_q_get_fd")
+        .allowlist_function("rte_eth_tx_done_cleanup")
         .allowlist_function("rte_eal_init")
         .allowlist_function("rte_eal_cleanup")
+        .allowlist_function("rte_eal_process_type")
+        .allowlist_function("rte_eal_hotplug_add")
+        .allowlist_function("rte_eal_hotplug_remove")
+        .allowlist_function("rte_mempool_lookup")
+        .allowlist_function("rte_mempool_create")
         // generate dpdk ring
         .allowlist_function("rte_ring_create")
         .allowlist_function("rte_ring_enqueue_burst")
         .allowlist_function("rte_ring_dequeue_burst")
         .allowlist_function("rte_ring_free")
+        .allowlist_function("rte_ring_lookup")
+        .allowlist_function("rte_ring_create_elem")
+        .allowlist_function("rte_ring_init")
+        .allowlist_function("rte_ring_get_memsize")
+        .allowlist_function("rte_ring_get_memsize_elem")
+        // generate dpdk memzone
+        .allowlist_function("rte_memzone_reserve")
+        .allowlist_function("rte_memzone_lookup")
+        .allowlist_function("rte_memzone_free")
+        // generate dpdk hash
+        .allowlist_function("rte_hash_create")
+        .allowlist_function("rte_hash_free")
+        .allowlist_function("rte_hash_add_key")
+        .allowlist_function("rte_hash_lookup")
+        .allowlist_function("rte_hash_del_key")
+        .allowlist_function("rte_hash_iterate")
+        .allowlist_function("rte_hash_count")
+        // generate dpdk lpm
+        .allowlist_function("rte_lpm_create")
+        .allowlist_function("rte_lpm_free")
+        .allowlist_function("rte_lpm_add")
+        .allowlist_function("rte_lpm_delete")
+        .allowlist_function("rte_lpm6_create")
+        .allowlist_function("rte_lpm6_free")
+        .allowlist_function("rte_lpm6_add")
+        .allowlist_function("rte_lpm6_delete")
+        .allowlist_function("rte_lpm6_lookup")
         // generate dpdk distributor
         .allowlist_function("rte_distributor_create")
         .allowlist_function("rte_distributor_process")
         .allowlist_function("rte_distributor_returned_pkts")
         .allowlist_function("rte_distributor_flush")
         .allowlist_function("rte_distributor_clear_returns")
+        // generate dpdk reorder
+        .allowlist_function("rte_reorder_create")
+        .allowlist_function("rte_reorder_free")
+        .allowlist_function("rte_reorder_insert")
+        .allowlist_function("rte_reorder_drain")
+        .allowlist_function("rte_reorder_min_seqn_set")
+        // generate dpdk eventdev
+        .allowlist_function("rte_event_dev_get_dev_id")
+        .allowlist_function("rte_event_dev_info_get")
+        .allowlist_function("rte_event_dev_configure")
+        .allowlist_function("rte_event_queue_setup")
+        .allowlist_function("rte_event_port_setup")
+        .allowlist_function("rte_event_port_link")
+        .allowlist_function("rte_event_dev_start")
+        .allowlist_function("rte_event_dev_stop")
+        .allowlist_function("rte_event_dev_close")
+        .allowlist_function("rte_event_dev_service_id_get")
+        .allowlist_function("rte_service_runstate_set")
+        .allowlist_function("rte_service_set_runstate_mapped_check")
+        .allowlist_function("rte_service_run_iter_on_app_lcore")
+        .allowlist_function("rte_vdev_init")
+        // generate dpdk malloc
+        .allowlist_function("rte_zmalloc_socket")
+        .allowlist_function("rte_free")
+        // generate mbuf dynamic fields
+        .allowlist_function("rte_mbuf_dynfield_register")
+        .allowlist_function("rte_mbuf_dyn_rx_timestamp_register")
+        .allowlist_function("rte_mbuf_dyn_tx_timestamp_register")
+        .allowlist_function("rte_get_tsc_hz")
         // generate useful dpdk types
         .allowlist_type("rte_eth_conf")
         .allowlist_type("rte_eth_dev_info")
@@ -85,10 +177,28 @@ fn build_dpdk_ffi() {
         .allowlist_type("rte_mempool")
         .allowlist_type("rte_mbuf")
         .allowlist_type("rte_eth_stats")
+        .allowlist_type("rte_eth_fc_conf")
+        .allowlist_type("rte_eth_rss_reta_entry64")
         // generate dpdk ring
         .allowlist_type("rte_ring")
+        // generate dpdk memzone
+        .allowlist_type("rte_memzone")
+        // generate dpdk hash
+        .allowlist_type("rte_hash_parameters")
+        // generate dpdk lpm types
+        .allowlist_type("rte_lpm_config")
+        .allowlist_type("rte_lpm6_config")
         // generate dpdk distributor type
         .allowlist_type("rte_distributor")
+        // generate dpdk reorder type
+        .allowlist_type("rte_reorder_buffer")
+        // generate dpdk eventdev types
+        .allowlist_type("rte_event")
+        .allowlist_type("rte_event_dev_config")
+        .allowlist_type("rte_event_dev_info")
+        .allowlist_type("rte_event_queue_conf")
+        .allowlist_type("rte_event_port_conf")
+        .allowlist_type("rte_mbuf_dynfield")
         // generate useful dpdk macros defined in rte_build_config.h.
         .allowlist_var("RTE_MAX_LCORE")
         .allowlist_var("RTE_MAX_NUMA_NODES")
@@ -96,6 +206,10 @@ fn build_dpdk_ffi() {
         .allowlist_var("RTE_MBUF_DEFAULT_DATAROOM")
         .allowlist_var("RTE_PKTMBUF_HEADROOM")
         .allowlist_var("RTE_ETHDEV_QUEUE_STAT_CNTRS")
+        .allowlist_var("RTE_MAX_ETHPORTS")
+        .allowlist_var("RTE_ETH_NAME_MAX_LEN")
+        .allowlist_var("RTE_ETH_DEV_NO_OWNER")
+        .allowlist_var("RTE_ETH_RETA_GROUP_SIZE")
         .header("csrc/header.h");
     for cflag in cflags_iter {
         bgbuilder = bgbuilder.clang_arg(cflag);
@@ -2,8 +2,21 @@
 #define _GNU_SOURCE
 #include <rte_eal.h>
 #include <rte_ring.h>
+#include <rte_memzone.h>
+#include <rte_hash.h>
+#include <rte_lpm.h>
+#include <rte_lpm6.h>
 #include <rte_ethdev.h>
+#include <rte_bus.h>
 #include <rte_distributor.h>
+#include <rte_reorder.h>
+#include <rte_eventdev.h>
+#include <rte_malloc.h>
+#include <rte_service.h>
+#include <rte_bus_vdev.h>
+#include <rte_cycles.h>
+#include <rte_mbuf_dyn.h>
+#include <rte_version.h>
 
 // Add wrapper definitions for functions that bindgen can not generate.
 //
@@ -24,6 +37,32 @@ unsigned rte_lcore_id_();
 
 int rte_mempool_full_(const struct rte_mempool *mp);
 
+// Return 1 if a mempool ops named `name` is registered, 0 otherwise.
+int rte_mempool_ops_registered_(const char *name);
+
+int rte_mempool_get_(struct rte_mempool *mp, void **obj_p);
+
+void rte_mempool_put_(struct rte_mempool *mp, void *obj);
+
+struct rte_mempool_cache *rte_mempool_default_cache_(struct rte_mempool *mp,
+													 unsigned lcore_id);
+
+void rte_mempool_cache_flush_(struct rte_mempool_cache *cache,
+							  struct rte_mempool *mp);
+
+// Return the number of the objects in the common pool of the mempool, the
+// objects in the per-lcore caches are not counted.
+unsigned int rte_mempool_common_count_(const struct rte_mempool *mp);
+
+void rte_prefetch0_(const void *p);
+
+// Allocate an mbuf without the per-lcore cache, return NULL if the mempool is
+// exhausted.
+struct rte_mbuf *rte_pktmbuf_alloc_nocache_(struct rte_mempool *mp);
+
+// Free an mbuf chain without the per-lcore cache.
+void rte_pktmbuf_free_nocache_(struct rte_mbuf *m);
+
 struct rte_mbuf *rte_pktmbuf_alloc_(struct rte_mempool *mp);
 
 int rte_pktmbuf_alloc_bulk_(struct rte_mempool *pool,
@@ -31,12 +70,20 @@ int rte_pktmbuf_alloc_bulk_(struct rte_mempool *pool,
 
 void rte_pktmbuf_free_(struct rte_mbuf *m);
 
+void rte_pktmbuf_refcnt_update_(struct rte_mbuf *m, int16_t v);
+
+uint16_t rte_mbuf_refcnt_update_(struct rte_mbuf *m, int16_t value);
+
 uint16_t rte_eth_rx_burst_(uint16_t port_id, uint16_t queue_id,
 						   struct rte_mbuf **rx_pkts, const uint16_t nb_pkts);
 
 uint16_t rte_eth_tx_burst_(uint16_t port_id, uint16_t queue_id,
 						   struct rte_mbuf **tx_pkts, uint16_t nb_pkts);
 
+// Return the link status of the port, or a negative error number. This hides
+// the layout of `struct rte_eth_link`, which changes across dpdk versions.
+int rte_eth_link_status_(uint16_t port_id);
+
 int rte_errno_();
 
 // ring
@@ -48,3 +95,61 @@ unsigned int
 rte_ring_dequeue_burst_(struct rte_ring *r, void **obj_table,
 		unsigned int n, unsigned int *available);
 
+unsigned int
+rte_ring_enqueue_burst_elem_(struct rte_ring *r, const void *obj_table,
+		unsigned int esize, unsigned int n, unsigned int *free_space);
+
+unsigned int
+rte_ring_dequeue_burst_elem_(struct rte_ring *r, void *obj_table,
+		unsigned int esize, unsigned int n, unsigned int *available);
+
+unsigned int rte_ring_free_count_(const struct rte_ring *r);
+
+// mbuf headroom and tailroom
+uint16_t rte_pktmbuf_headroom_(const struct rte_mbuf *m);
+
+uint16_t rte_pktmbuf_tailroom_(const struct rte_mbuf *m);
+
+struct rte_mbuf *rte_pktmbuf_lastseg_(struct rte_mbuf *m);
+
+char *rte_pktmbuf_adj_(struct rte_mbuf *m, uint16_t len);
+
+int rte_pktmbuf_trim_(struct rte_mbuf *m, uint16_t len);
+
+int rte_pktmbuf_linearize_(struct rte_mbuf *mbuf);
+
+void rte_pktmbuf_dump_stderr_(const struct rte_mbuf *m, unsigned dump_len);
+
+// indirect mbuf
+int rte_pktmbuf_attach_(struct rte_mbuf *mi, struct rte_mbuf *m);
+
+// external buffer
+int rte_pktmbuf_attach_extbuf_(struct rte_mbuf *m, void *buf_addr, uint64_t buf_iova,
+            uint16_t buf_len, struct rte_mbuf_ext_shared_info *shinfo);
+
+int rte_pktmbuf_detach_extbuf_(struct rte_mbuf *m);
+
+void rte_mbuf_ext_refcnt_set_(struct rte_mbuf_ext_shared_info *shinfo, uint16_t new_value);
+
+uint16_t rte_mbuf_ext_refcnt_update_(struct rte_mbuf_ext_shared_info *shinfo, int16_t value);
+
+// lpm
+int rte_lpm_lookup_(struct rte_lpm *lpm, uint32_t ip, uint32_t *next_hop);
+
+// reorder
+uint32_t *rte_reorder_seqn_(struct rte_mbuf *mbuf);
+
+// eventdev
+uint16_t
+rte_event_enqueue_burst_(uint8_t dev_id, uint8_t port_id,
+			const struct rte_event ev[], uint16_t nb_events);
+
+uint16_t
+rte_event_dequeue_burst_(uint8_t dev_id, uint8_t port_id, struct rte_event ev[],
+			uint16_t nb_events, uint64_t timeout_ticks);
+
+// tsc
+uint64_t rte_rdtsc_(void);
+
+// version
+const char *rte_version_(void);
@@ -1,3 +1,5 @@
+#include <string.h>
+
 #include "header.h"
 
 // wrapper function implementations
@@ -13,6 +15,71 @@ int rte_mempool_full_(const struct rte_mempool *mp)
     return rte_mempool_full(mp);
 }
 
+int rte_mempool_ops_registered_(const char *name)
+{
+    for (unsigned i = 0; i < rte_mempool_ops_table.num_ops; i++) {
+        if (strcmp(name, rte_mempool_ops_table.ops[i].name) == 0) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+int rte_mempool_get_(struct rte_mempool *mp, void **obj_p)
+{
+    return rte_mempool_get(mp, obj_p);
+}
+
+void rte_mempool_put_(struct rte_mempool *mp, void *obj)
+{
+    rte_mempool_put(mp, obj);
+}
+
+struct rte_mempool_cache *rte_mempool_default_cache_(struct rte_mempool *mp,
+                                                     unsigned lcore_id)
+{
+    return rte_mempool_default_cache(mp, lcore_id);
+}
+
+void rte_mempool_cache_flush_(struct rte_mempool_cache *cache,
+                              struct rte_mempool *mp)
+{
+    rte_mempool_cache_flush(cache, mp);
+}
+
+unsigned int rte_mempool_common_count_(const struct rte_mempool *mp)
+{
+    return rte_mempool_ops_get_count(mp);
+}
+
+void rte_prefetch0_(const void *p)
+{
+    rte_prefetch0(p);
+}
+
+struct rte_mbuf *rte_pktmbuf_alloc_nocache_(struct rte_mempool *mp)
+{
+    struct rte_mbuf *m;
+    if (rte_mempool_generic_get(mp, (void **)&m, 1, NULL) < 0) {
+        return NULL;
+    }
+    rte_pktmbuf_reset(m);
+    return m;
+}
+
+void rte_pktmbuf_free_nocache_(struct rte_mbuf *m)
+{
+    struct rte_mbuf *next;
+    while (m != NULL) {
+        next = m->next;
+        m = rte_pktmbuf_prefree_seg(m);
+        if (m != NULL) {
+            rte_mempool_generic_put(m->pool, (void **)&m, 1, NULL);
+        }
+        m = next;
+    }
+}
+
 struct rte_mbuf *rte_pktmbuf_alloc_(struct rte_mempool *mp)
 {
     return rte_pktmbuf_alloc(mp);
@@ -29,6 +96,16 @@ void rte_pktmbuf_free_(struct rte_mbuf *m)
     rte_pktmbuf_free(m);
 }
 
+void rte_pktmbuf_refcnt_update_(struct rte_mbuf *m, int16_t v)
+{
+    rte_pktmbuf_refcnt_update(m, v);
+}
+
+uint16_t rte_mbuf_refcnt_update_(struct rte_mbuf *m, int16_t value)
+{
+    return rte_mbuf_refcnt_update(m, value);
+}
+
 uint16_t rte_eth_rx_burst_(uint16_t port_id, uint16_t queue_id,
                            struct rte_mbuf **rx_pkts, const uint16_t nb_pkts)
 {
@@ -41,6 +118,16 @@ uint16_t rte_eth_tx_burst_(uint16_t port_id, uint16_t queue_id,
     return rte_eth_tx_burst(port_id, queue_id, tx_pkts, nb_pkts);
 }
 
+int rte_eth_link_status_(uint16_t port_id)
+{
+    struct rte_eth_link link;
+    int ret = rte_eth_link_get_nowait(port_id, &link);
+    if (ret < 0) {
+        return ret;
+    }
+    return link.link_status;
+}
+
 int rte_errno_()
 {
     return rte_errno;
@@ -58,3 +145,134 @@ rte_ring_dequeue_burst_(struct rte_ring *r, void **obj_table,
 		unsigned int n, unsigned int *available) {
     return rte_ring_dequeue_burst(r, obj_table, n, available);
 }
+
+unsigned int
+rte_ring_enqueue_burst_elem_(struct rte_ring *r, const void *obj_table,
+		unsigned int esize, unsigned int n, unsigned int *free_space) {
+    return rte_ring_enqueue_burst_elem(r, obj_table, esize, n, free_space);
+}
+
+unsigned int
+rte_ring_dequeue_burst_elem_(struct rte_ring *r, void *obj_table,
+		unsigned int esize, unsigned int n, unsigned int *available) {
+    return rte_ring_dequeue_burst_elem(r, obj_table, esize, n, available);
+}
+
+unsigned int rte_ring_free_count_(const struct rte_ring *r) {
+    return rte_ring_free_count(r);
+}
+
+// mbuf headroom and tailroom
+uint16_t rte_pktmbuf_headroom_(const struct rte_mbuf *m)
+{
+    return rte_pktmbuf_headroom(m);
+}
+
+uint16_t rte_pktmbuf_tailroom_(const struct rte_mbuf *m)
+{
+    return rte_pktmbuf_tailroom(m);
+}
+
+struct rte_mbuf *rte_pktmbuf_lastseg_(struct rte_mbuf *m)
+{
+    return rte_pktmbuf_lastseg(m);
+}
+
+char *rte_pktmbuf_adj_(struct rte_mbuf *m, uint16_t len)
+{
+    return rte_pktmbuf_adj(m, len);
+}
+
+int rte_pktmbuf_trim_(struct rte_mbuf *m, uint16_t len)
+{
+    return rte_pktmbuf_trim(m, len);
+}
+
+int rte_pktmbuf_linearize_(struct rte_mbuf *mbuf)
+{
+    return rte_pktmbuf_linearize(mbuf);
+}
+
+void rte_pktmbuf_dump_stderr_(const struct rte_mbuf *m, unsigned dump_len)
+{
+    rte_pktmbuf_dump(stderr, m, dump_len);
+}
+
+// `rte_pktmbuf_attach` only checks its preconditions with `RTE_ASSERT`.
+int rte_pktmbuf_attach_(struct rte_mbuf *mi, struct rte_mbuf *m)
+{
+    if (!RTE_MBUF_DIRECT(mi) || rte_mbuf_refcnt_read(mi) != 1 || mi->next != NULL) {
+        return -1;
+    }
+    rte_pktmbuf_attach(mi, m);
+    return 0;
+}
+
+// The attached mbuf holds a reference of `shinfo`, which is released when the
+// mbuf is detached or freed.
+int rte_pktmbuf_attach_extbuf_(struct rte_mbuf *m, void *buf_addr, uint64_t buf_iova,
+            uint16_t buf_len, struct rte_mbuf_ext_shared_info *shinfo)
+{
+    if (!RTE_MBUF_DIRECT(m) || rte_mbuf_refcnt_read(m) != 1 || m->next != NULL) {
+        return -1;
+    }
+    rte_mbuf_ext_refcnt_update(shinfo, 1);
+    rte_pktmbuf_attach_extbuf(m, buf_addr, buf_iova, buf_len, shinfo);
+    return 0;
+}
+
+int rte_pktmbuf_detach_extbuf_(struct rte_mbuf *m)
+{
+    if (!RTE_MBUF_HAS_EXTBUF(m) || rte_mbuf_refcnt_read(m) != 1) {
+        return -1;
+    }
+    rte_pktmbuf_detach_extbuf(m);
+    return 0;
+}
+
+void rte_mbuf_ext_refcnt_set_(struct rte_mbuf_ext_shared_info *shinfo, uint16_t new_value)
+{
+    rte_mbuf_ext_refcnt_set(shinfo, new_value);
+}
+
+uint16_t rte_mbuf_ext_refcnt_update_(struct rte_mbuf_ext_shared_info *shinfo, int16_t value)
+{
+    return rte_mbuf_ext_refcnt_update(shinfo, value);
+}
+
+// lpm
+int rte_lpm_lookup_(struct rte_lpm *lpm, uint32_t ip, uint32_t *next_hop)
+{
+    return rte_lpm_lookup(lpm, ip, next_hop);
+}
+
+// reorder
+uint32_t *rte_reorder_seqn_(struct rte_mbuf *mbuf)
+{
+    return rte_reorder_seqn(mbuf);
+}
+
+// eventdev
+uint16_t
+rte_event_enqueue_burst_(uint8_t dev_id, uint8_t port_id,
+			const struct rte_event ev[], uint16_t nb_events) {
+    return rte_event_enqueue_burst(dev_id, port_id, ev, nb_events);
+}
+
+uint16_t
+rte_event_dequeue_burst_(uint8_t dev_id, uint8_t port_id, struct rte_event ev[],
+			uint16_t nb_events, uint64_t timeout_ticks) {
+    return rte_event_dequeue_burst(dev_id, port_id, ev, nb_events, timeout_ticks);
+}
+
+// tsc
+uint64_t rte_rdtsc_(void)
+{
+    return rte_rdtsc();
+}
+
+// version
+const char *rte_version_(void)
+{
+    return rte_version();
+}
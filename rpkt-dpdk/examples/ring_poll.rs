@@ -0,0 +1,58 @@
+use std::time::Instant;
+
+use arrayvec::ArrayVec;
+use rpkt_dpdk::*;
+
+const BATCH_SIZE: usize = 32;
+const NB_POLLS: u32 = 10_000_000;
+
+// Measure the cost of polling a ring, both empty and with a burst of mbufs in
+// each poll, with a single batch that is reused for all the polls.
+fn main() {
+    DpdkOption::new().init().unwrap();
+
+    {
+        let mut mpconf = MempoolConf::default();
+        mpconf.nb_mbufs = 1024;
+        let mp = service().mempool_create("wtf", &mpconf).unwrap();
+        let ring = Ring::try_create(
+            "poll".to_string(),
+            &RingConf {
+                count: 1024,
+                socket_id: 0,
+                flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ,
+            },
+        )
+        .unwrap();
+
+        let mut batch = ArrayVec::<_, BATCH_SIZE>::new();
+        let start = Instant::now();
+        let mut nb_deq = 0;
+        for _ in 0..NB_POLLS {
+            nb_deq += ring.dequeue_burst(&mut batch);
+        }
+        let elapsed = start.elapsed();
+        assert_eq!(nb_deq, 0);
+        println!(
+            "empty poll: {:.2} ns per poll",
+            elapsed.as_nanos() as f64 / f64::from(NB_POLLS)
+        );
+
+        let start = Instant::now();
+        for _ in 0..NB_POLLS / 10 {
+            mp.fill_batch(&mut batch);
+            ring.enqueue_burst(&mut batch);
+            ring.dequeue_burst(&mut batch);
+            Mempool::free_batch(&mut batch);
+        }
+        let elapsed = start.elapsed();
+        println!(
+            "full poll: {:.2} ns per burst of {} mbufs",
+            elapsed.as_nanos() as f64 / f64::from(NB_POLLS / 10),
+            BATCH_SIZE
+        );
+    }
+
+    service().mempool_free("wtf").unwrap();
+    service().service_close().unwrap();
+}
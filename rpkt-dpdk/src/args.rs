@@ -0,0 +1,145 @@
+use std::str::FromStr;
+
+use crate::error::*;
+
+/// The application arguments that follow the EAL arguments.
+///
+/// DPDK applications are launched as `prog <eal args> -- <app args>`.
+/// `AppArgs::split` separates the two parts: the EAL part can be passed to
+/// `EalConfig::set_extra_args`, and the application part is queried by name,
+/// e.g. `--rings 4` or `--rings=4`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AppArgs {
+    args: Vec<String>,
+}
+
+impl AppArgs {
+    /// Split `args` at the first "--".
+    ///
+    /// The first argument is the program name, which is skipped. Return the EAL
+    /// arguments and the application arguments, all the arguments are EAL
+    /// arguments if there is no "--".
+    pub fn split<I, S>(args: I) -> (Vec<String>, AppArgs)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut args = args.into_iter().skip(1).map(Into::into);
+        let eal_args = args.by_ref().take_while(|arg| arg != "--").collect();
+        (
+            eal_args,
+            AppArgs {
+                args: args.collect(),
+            },
+        )
+    }
+
+    /// Split the arguments of the current process, see `AppArgs::split`.
+    pub fn from_env() -> (Vec<String>, AppArgs) {
+        Self::split(std::env::args())
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Check whether the flag `--<name>` is present.
+    pub fn flag(&self, name: &str) -> bool {
+        let flag = format!("--{name}");
+        self.args.contains(&flag)
+    }
+
+    /// Parse the value of `--<name>`, which is given as `--<name> <value>` or
+    /// `--<name>=<value>`.
+    ///
+    /// Return `None` if the argument is absent, and an error if the value is
+    /// missing or can not be parsed. The last occurrence wins.
+    pub fn get<T: FromStr>(&self, name: &str) -> Result<Option<T>> {
+        let flag = format!("--{name}");
+        let err = Error::service_err("invalid app argument");
+
+        let mut value = None;
+        let mut args = self.args.iter();
+        while let Some(arg) = args.next() {
+            if *arg == flag {
+                value = Some(args.next().ok_or(err)?.as_str());
+            } else if let Some(val) = arg
+                .strip_prefix(flag.as_str())
+                .and_then(|rest| rest.strip_prefix('='))
+            {
+                value = Some(val);
+            }
+        }
+
+        value
+            .map(|val| val.parse::<T>().map_err(|_| err))
+            .transpose()
+    }
+
+    /// Parse the value of `--<name>`, or return `default` if it is absent.
+    pub fn get_or<T: FromStr>(&self, name: &str, default: T) -> Result<T> {
+        Ok(self.get(name)?.unwrap_or(default))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EalConfig;
+
+    #[test]
+    fn split_eal_and_app_args() {
+        let (eal_args, app_args) = AppArgs::split(["prog", "-l", "0-3", "--", "--rings", "4"]);
+        assert_eq!(eal_args, ["-l", "0-3"]);
+        assert_eq!(app_args.args(), ["--rings", "4"]);
+        assert_eq!(app_args.get::<u32>("rings").unwrap(), Some(4));
+        assert_eq!(app_args.get::<u32>("queues").unwrap(), None);
+        assert_eq!(app_args.get_or("burst", 32_usize).unwrap(), 32);
+
+        // the eal part is rendered after the typed eal parameters
+        let mut config = EalConfig::default();
+        config.set_extra_args(eal_args);
+        let rendered: Vec<_> = config
+            .into_args()
+            .unwrap()
+            .into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect();
+        assert_eq!(&rendered[rendered.len() - 2..], ["-l", "0-3"]);
+
+        let (eal_args, app_args) = AppArgs::split(["prog", "-l", "0-3"]);
+        assert_eq!(eal_args, ["-l", "0-3"]);
+        assert!(app_args.args().is_empty());
+    }
+
+    #[test]
+    fn parse_app_args() {
+        let (_, app_args) = AppArgs::split([
+            "prog",
+            "--",
+            "--rings=4",
+            "--ring-size",
+            "1024",
+            "--promisc",
+            "--rings",
+            "8",
+            "--burst",
+        ]);
+        assert_eq!(app_args.get::<u32>("rings").unwrap(), Some(8));
+        assert_eq!(app_args.get::<u32>("ring-size").unwrap(), Some(1024));
+        assert!(app_args.flag("promisc"));
+        assert!(!app_args.flag("ring"));
+        // `--ring` is not a prefix match of `--ring-size`
+        assert_eq!(app_args.get::<u32>("ring").unwrap(), None);
+        // the value is missing
+        assert!(app_args.get::<u32>("burst").is_err());
+
+        // the value is not a number
+        let (_, app_args) = AppArgs::split(["prog", "--", "--rings", "four"]);
+        assert!(app_args.get::<u32>("rings").is_err());
+        assert_eq!(
+            app_args.get::<String>("rings").unwrap(),
+            Some("four".to_string())
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use arrayvec::ArrayVec;
+use tokio::time::Sleep;
+
+use crate::{Mbuf, Ring};
+
+/// An async consumer of a `Ring`.
+///
+/// DPDK rings have no wake-up mechanism, so `AsyncRing` polls the ring and
+/// sleeps on a tokio timer for `poll_interval` whenever the ring is empty.
+/// Each dequeue has a latency of up to one poll interval, which makes it
+/// suitable for low-rate control traffic handed from the data-plane lcores to
+/// an async control-plane task, not for the data-plane packet path.
+///
+/// The timer requires a tokio runtime with the time driver enabled.
+pub struct AsyncRing {
+    ring: Ring,
+    poll_interval: Duration,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl AsyncRing {
+    /// The default poll interval.
+    pub const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+    pub fn new(ring: Ring) -> Self {
+        Self {
+            ring,
+            poll_interval: Self::POLL_INTERVAL,
+            sleep: None,
+        }
+    }
+
+    pub fn set_poll_interval(&mut self, val: Duration) {
+        self.poll_interval = val;
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        self.poll_interval
+    }
+
+    /// Return the wrapped ring.
+    pub fn ring(&self) -> &Ring {
+        &self.ring
+    }
+
+    /// Dequeue a single mbuf without waiting.
+    pub fn try_dequeue(&self) -> Option<Mbuf> {
+        let mut batch = ArrayVec::<Mbuf, 1>::new();
+        self.ring.dequeue_burst(&mut batch);
+        batch.pop()
+    }
+
+    /// Poll the ring for a single mbuf.
+    ///
+    /// If the ring is empty, a timer is registered with the current task and the
+    /// ring is checked again when the timer fires.
+    pub fn poll_dequeue(&mut self, cx: &mut Context<'_>) -> Poll<Mbuf> {
+        loop {
+            if let Some(mbuf) = self.try_dequeue() {
+                self.sleep = None;
+                return Poll::Ready(mbuf);
+            }
+
+            let poll_interval = self.poll_interval;
+            let sleep = self
+                .sleep
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep(poll_interval)));
+            match sleep.as_mut().poll(cx) {
+                Poll::Ready(_) => {
+                    // the timer has fired, re-arm it and check the ring again
+                    self.sleep = None;
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    /// Wait for a single mbuf from the ring.
+    pub async fn recv(&self) -> Mbuf {
+        loop {
+            if let Some(mbuf) = self.try_dequeue() {
+                return mbuf;
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[tokio::test]
+    async fn recv_from_producer_thread() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let ring = Ring::try_create(
+                "async".to_string(),
+                &RingConf {
+                    count: 64,
+                    socket_id: 0,
+                    flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ,
+                },
+            )
+            .unwrap();
+
+            let producer = {
+                let ring = ring.clone();
+                let mp = mp.clone();
+                std::thread::spawn(move || {
+                    for i in 0..16 {
+                        let mut batch = ArrayVec::<_, 1>::new();
+                        let mut mbuf = mp.try_alloc().unwrap();
+                        mbuf.extend_from_slice(&[i as u8; 1][..]);
+                        batch.push(mbuf);
+                        assert_eq!(ring.enqueue_burst(&mut batch), 1);
+                        std::thread::sleep(Duration::from_millis(2));
+                    }
+                })
+            };
+
+            let mut async_ring = AsyncRing::new(ring);
+            async_ring.set_poll_interval(Duration::from_micros(500));
+            for i in 0..8 {
+                let mbuf = async_ring.recv().await;
+                assert_eq!(mbuf.data()[0], i as u8);
+            }
+            for i in 8..16 {
+                let mbuf = std::future::poll_fn(|cx| async_ring.poll_dequeue(cx)).await;
+                assert_eq!(mbuf.data()[0], i as u8);
+            }
+            assert!(async_ring.try_dequeue().is_none());
+
+            producer.join().unwrap();
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+}
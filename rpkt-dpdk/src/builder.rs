@@ -0,0 +1,335 @@
+use std::net::IpAddr;
+
+use rpkt::untrusted::{parse_untrusted, ParsedPacket};
+
+use crate::checksum::{verify, ChecksumResult};
+use crate::error::*;
+use crate::proto::{
+    write_ether_header, ETHERTYPE_IPV4, ETHERTYPE_IPV6, ETHER_HEADER_LEN, IPPROTO_UDP,
+    IPV4_FRAG_OFFSET_MASK, IPV6_HEADER_LEN,
+};
+use crate::Mbuf;
+
+/// A builder that writes a packet directly into the data room of an mbuf.
+///
+/// The builder is handed out by `Mempool::build_packet`. Each `append` writes
+/// to the tailroom of the mbuf and extends its `data_len` and `pkt_len`, so no
+/// intermediate buffer is needed.
+pub struct PacketBuilder<'a> {
+    mbuf: &'a mut Mbuf,
+}
+
+impl<'a> PacketBuilder<'a> {
+    // The builder writes to the data room, so the mbuf must own its buffer.
+    pub(crate) fn new(mbuf: &'a mut Mbuf) -> Self {
+        assert!(mbuf.is_direct(), "building a packet in an attached mbuf");
+        Self { mbuf }
+    }
+
+    /// Return the number of bytes written so far.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.mbuf.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return the number of bytes that can still be appended.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.mbuf.capacity()
+    }
+
+    /// Append `bytes` to the end of the packet.
+    ///
+    /// # Panic:
+    /// This function panics if the length of `bytes` exceeds the capacity.
+    #[inline]
+    pub fn append(&mut self, bytes: &[u8]) {
+        self.mbuf.extend_from_slice(bytes);
+    }
+
+    /// Append `len` zeroed bytes to the end of the packet, and return them for
+    /// the caller to fill in, e.g. with a typed header of `rpkt`.
+    ///
+    /// # Panic:
+    /// This function panics if `len` exceeds the capacity.
+    #[inline]
+    pub fn append_zeroed(&mut self, len: usize) -> &mut [u8] {
+        let old_len = self.len();
+        unsafe { self.mbuf.extend(len) };
+        let appended = &mut self.mbuf.data_mut()[old_len..];
+        appended.fill(0);
+        appended
+    }
+
+    /// Return the bytes written so far, e.g. to fix up the length fields and
+    /// the checksums once the payload is appended.
+    #[inline]
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        self.mbuf.data_mut()
+    }
+
+    /// Append an ethernet header from `src` to the broadcast address, e.g. for
+    /// an arp request.
+    ///
+    /// # Panic:
+    /// This function panics if the capacity is below 14 bytes.
+    #[inline]
+    pub fn ethernet_broadcast(&mut self, src: [u8; 6], ethertype: u16) {
+        self.append_ethernet([0xff; 6], src, ethertype);
+    }
+
+    /// Append an ethernet header from `src` to the multicast mac address of
+    /// the ip multicast `group`, with the ethertype of its ip version.
+    ///
+    /// The ipv4 groups map to 01:00:5e and the low 23 bits of the group, the
+    /// ipv6 groups map to 33:33 and the low 32 bits of the group.
+    ///
+    /// # Panic:
+    /// This function panics if `group` is not a multicast address, or if the
+    /// capacity is below 14 bytes.
+    #[inline]
+    pub fn ethernet_multicast(&mut self, src: [u8; 6], group: IpAddr) {
+        let dst = multicast_mac(group).expect("not an ip multicast group");
+        let ethertype = match group {
+            IpAddr::V4(_) => ETHERTYPE_IPV4,
+            IpAddr::V6(_) => ETHERTYPE_IPV6,
+        };
+        self.append_ethernet(dst, src, ethertype);
+    }
+
+    #[inline]
+    fn append_ethernet(&mut self, dst: [u8; 6], src: [u8; 6], ethertype: u16) {
+        let header = self.append_zeroed(ETHER_HEADER_LEN);
+        write_ether_header(header, dst, src, ethertype);
+    }
+
+    /// Parse the bytes written so far back with `rpkt::parse_untrusted` and
+    /// check them for the common builder bugs, such as a header written at a
+    /// wrong offset or a stale length field.
+    ///
+    /// The ipv4 total length, the ipv6 payload length and the udp length are
+    /// checked against the packet, allowing for the padding of a minimum-sized
+    /// frame, and the ipv4 and tcp/udp checksums are checked with
+    /// `checksum::verify`, so they must not be left to the tx offloads. The
+    /// parsed layers are returned for the caller to compare the addresses and
+    /// the ports with the ones it wrote.
+    ///
+    /// This is a debug helper that reads the whole packet, it does not belong
+    /// in the hot path.
+    pub fn verify(&self) -> Result<ParsedPacket> {
+        // the minimum ethernet frame without the crc, a shorter ip packet is
+        // padded to it
+        const MIN_FRAME_LEN: usize = 60;
+
+        let data = self.mbuf.data();
+        let parsed =
+            parse_untrusted(data).map_err(|_| Error::service_err("built packet is malformed"))?;
+
+        let frame_len = data.len();
+        let l3 = ETHER_HEADER_LEN;
+        let read_u16 = |off: usize| u16::from_be_bytes([data[off], data[off + 1]]) as usize;
+        let lens_match = |ip_end: usize| {
+            ip_end == frame_len || (frame_len == MIN_FRAME_LEN && ip_end < frame_len)
+        };
+        let (ip_end, udp_off) = match read_u16(12) as u16 {
+            ETHERTYPE_IPV4 => {
+                let ip_end = l3 + read_u16(l3 + 2);
+                if !lens_match(ip_end) {
+                    return Error::service_err("ipv4 total length mismatches the packet").to_err();
+                }
+                let frag_off = read_u16(l3 + 6) & usize::from(IPV4_FRAG_OFFSET_MASK);
+                let udp = data[l3 + 9] == IPPROTO_UDP && frag_off == 0;
+                (ip_end, udp.then_some(l3 + usize::from(data[l3] & 0x0f) * 4))
+            }
+            ETHERTYPE_IPV6 => {
+                let ip_end = l3 + IPV6_HEADER_LEN + read_u16(l3 + 4);
+                if !lens_match(ip_end) {
+                    return Error::service_err("ipv6 payload length mismatches the packet")
+                        .to_err();
+                }
+                // the udp header directly follows the fixed header
+                let udp = data[l3 + 6] == IPPROTO_UDP;
+                (ip_end, udp.then_some(l3 + IPV6_HEADER_LEN))
+            }
+            _ => (frame_len, None),
+        };
+        if let Some(udp_off) = udp_off {
+            if udp_off + read_u16(udp_off + 4) != ip_end {
+                return Error::service_err("udp length mismatches the packet").to_err();
+            }
+        }
+
+        match verify(&*self.mbuf) {
+            ChecksumResult::BadIp => Error::service_err("bad ipv4 header checksum").to_err(),
+            ChecksumResult::BadL4 => Error::service_err("bad tcp or udp checksum").to_err(),
+            ChecksumResult::Ok | ChecksumResult::NotApplicable => Ok(parsed),
+        }
+    }
+}
+
+// Map the ip multicast `group` to its multicast mac address, see
+// `PacketBuilder::ethernet_multicast`.
+fn multicast_mac(group: IpAddr) -> Option<[u8; 6]> {
+    match group {
+        IpAddr::V4(addr) if addr.is_multicast() => {
+            let octets = addr.octets();
+            Some([0x01, 0x00, 0x5e, octets[1] & 0x7f, octets[2], octets[3]])
+        }
+        IpAddr::V6(addr) if addr.is_multicast() => {
+            let octets = addr.octets();
+            Some([0x33, 0x33, octets[12], octets[13], octets[14], octets[15]])
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn derive_multicast_macs() {
+        let mac = |group: &str| multicast_mac(group.parse().unwrap());
+        assert_eq!(mac("224.0.0.1"), Some([0x01, 0x00, 0x5e, 0x00, 0x00, 0x01]));
+        assert_eq!(
+            mac("239.255.255.250"),
+            Some([0x01, 0x00, 0x5e, 0x7f, 0xff, 0xfa])
+        );
+        // the high bit of the second octet is not mapped
+        assert_eq!(mac("224.128.0.1"), mac("224.0.0.1"));
+        assert_eq!(mac("ff02::1"), Some([0x33, 0x33, 0x00, 0x00, 0x00, 0x01]));
+        assert_eq!(
+            mac("ff02::1:ff00:1234"),
+            Some([0x33, 0x33, 0xff, 0x00, 0x12, 0x34])
+        );
+        assert_eq!(mac("10.0.0.1"), None);
+        assert_eq!(mac("fe80::1"), None);
+    }
+
+    #[test]
+    fn build_broadcast_and_multicast() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+            let src = [0x02, 0, 0, 0, 0, 0x01];
+
+            let arp = mp
+                .build_packet(|builder| {
+                    builder.ethernet_broadcast(src, 0x0806);
+                    builder.append_zeroed(28);
+                })
+                .unwrap();
+            assert_eq!(arp.len(), 42);
+            assert_eq!(&arp.data()[..6], &[0xff; 6][..]);
+            assert_eq!(&arp.data()[6..12], &src[..]);
+            assert_eq!(&arp.data()[12..14], &[0x08, 0x06][..]);
+
+            let mld = mp
+                .build_packet(|builder| {
+                    builder.ethernet_multicast(src, "ff02::16".parse().unwrap())
+                })
+                .unwrap();
+            assert_eq!(
+                mld.data(),
+                &[0x33, 0x33, 0, 0, 0, 0x16, 0x02, 0, 0, 0, 0, 0x01, 0x86, 0xdd][..]
+            );
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn verify_built_packets() {
+        use rpkt::ipv4::IpProtocol;
+        use rpkt::untrusted::{Network, Transport};
+
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut conf = FlowGenConf::new();
+            conf.set_nb_flows(1);
+            conf.set_tcp_percent(0);
+            conf.set_pkt_lens(vec![60, 200]);
+            let mut flowgen = FlowGenerator::new(42, &conf, &mp);
+            let flow = flowgen.flows()[0];
+            let (IpAddr::V4(src), IpAddr::V4(dst)) = (flow.src_ip, flow.dst_ip) else {
+                unreachable!("the flows are ipv4");
+            };
+            let reference: Vec<_> = (0..8).map(|_| flowgen.next().unwrap()).collect();
+
+            for mbuf in reference.iter() {
+                let frame = mbuf.data();
+                let mut res = None;
+                let built = mp
+                    .build_packet(|builder| {
+                        builder.append(frame);
+                        res = Some(builder.verify());
+                    })
+                    .unwrap();
+                assert!(built.data_eq(mbuf));
+                let parsed = res.unwrap().unwrap();
+                assert_eq!(
+                    parsed.network,
+                    Network::Ipv4 {
+                        src,
+                        dst,
+                        protocol: IpProtocol::UDP,
+                    }
+                );
+                assert_eq!(
+                    parsed.transport,
+                    Some(Transport::Udp {
+                        src_port: flow.src_port,
+                        dst_port: flow.dst_port,
+                    })
+                );
+            }
+
+            let frame = reference
+                .iter()
+                .find(|mbuf| mbuf.len() == 200)
+                .unwrap()
+                .data();
+            let broken = |f: &dyn Fn(&mut [u8])| {
+                let mut res = None;
+                mp.build_packet(|builder| {
+                    builder.append(frame);
+                    f(builder.data_mut());
+                    res = Some(builder.verify());
+                })
+                .unwrap();
+                res.unwrap().unwrap_err().to_string()
+            };
+
+            // a stale ip total length
+            let msg = broken(&|data| data[17] -= 4);
+            assert!(msg.contains("ipv4 total length mismatches the packet"));
+            // a udp length that is not updated with the payload
+            let msg = broken(&|data| data[39] -= 4);
+            assert!(msg.contains("udp length mismatches the packet"));
+            // a payload byte changed after the checksums are computed
+            let msg = broken(&|data| data[100] ^= 0xff);
+            assert!(msg.contains("bad tcp or udp checksum"));
+            let msg = broken(&|data| data[22] -= 1);
+            assert!(msg.contains("bad ipv4 header checksum"));
+            // an ipv4 header length below the minimum
+            let msg = broken(&|data| data[14] = 0x44);
+            assert!(msg.contains("built packet is malformed"));
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+}
@@ -0,0 +1,249 @@
+use arrayvec::ArrayVec;
+
+use crate::{Backoff, Mbuf};
+
+/// Split a burst into the mbufs that match `pred` and the ones that do not.
+///
+/// The input burst is consumed, each mbuf is moved into exactly one of the
+/// returned bursts, and the relative order of the mbufs is preserved.
+pub fn partition<const N: usize>(
+    batch: ArrayVec<Mbuf, N>,
+    pred: impl Fn(&Mbuf) -> bool,
+) -> (ArrayVec<Mbuf, N>, ArrayVec<Mbuf, N>) {
+    let mut matched = ArrayVec::new();
+    let mut unmatched = ArrayVec::new();
+    for mbuf in batch {
+        // both outputs have the capacity of the input, so the pushes never fail
+        if pred(&mbuf) {
+            matched.push(mbuf);
+        } else {
+            unmatched.push(mbuf);
+        }
+    }
+    (matched, unmatched)
+}
+
+/// Return the number of bytes in a burst, summed over all the segments of each
+/// mbuf with `Mbuf::total_len`.
+#[inline]
+pub fn total_bytes<const N: usize>(batch: &ArrayVec<Mbuf, N>) -> usize {
+    batch.iter().map(|mbuf| mbuf.total_len()).sum()
+}
+
+/// A controller of the rx burst size, which adapts the size to the load.
+///
+/// The controller keeps a moving average of the fill ratios of the recent
+/// bursts, i.e. the received mbufs over the requested ones. The burst size is
+/// doubled when most of the bursts are full, and halved when most of them are
+/// nearly empty, within `min_size` and `max_size`. After a number of empty
+/// polls in a row, the queue is considered idle and `backoff` suggests a pause
+/// before the next poll.
+///
+/// A polling loop receives `next_size` mbufs with `RxQueue::rx_at_most`, and
+/// reports the result with `observe`.
+#[derive(Clone, Debug)]
+pub struct AdaptiveBurst {
+    min_size: usize,
+    max_size: usize,
+    size: usize,
+    // The moving average of the fill ratios, in 1/256.
+    fill: u32,
+    idle_polls: u32,
+    max_idle_polls: u32,
+    idle_backoff: Backoff,
+}
+
+impl AdaptiveBurst {
+    /// The default number of the empty polls in a row, after which the queue is
+    /// considered idle.
+    pub const MAX_IDLE_POLLS: u32 = 16;
+    /// The default backoff of an idle queue.
+    pub const IDLE_BACKOFF: Backoff = Backoff::Pause(64);
+
+    const GROW_FILL: u32 = 192;
+    const SHRINK_FILL: u32 = 64;
+
+    /// Create a controller that starts with bursts of `max_size`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `min_size` is 0 or larger than `max_size`.
+    pub fn new(min_size: usize, max_size: usize) -> Self {
+        assert!(
+            min_size > 0 && min_size <= max_size,
+            "invalid burst size range"
+        );
+        Self {
+            min_size,
+            max_size,
+            size: max_size,
+            fill: 128,
+            idle_polls: 0,
+            max_idle_polls: Self::MAX_IDLE_POLLS,
+            idle_backoff: Self::IDLE_BACKOFF,
+        }
+    }
+
+    pub fn set_max_idle_polls(&mut self, val: u32) {
+        self.max_idle_polls = val;
+    }
+
+    pub fn set_idle_backoff(&mut self, val: Backoff) {
+        self.idle_backoff = val;
+    }
+
+    /// Return the suggested size of the next burst.
+    #[inline]
+    pub fn next_size(&self) -> usize {
+        self.size
+    }
+
+    /// Record a burst that receives `received` of the `requested` mbufs.
+    #[inline]
+    pub fn observe(&mut self, received: usize, requested: usize) {
+        let ratio = if requested == 0 {
+            0
+        } else {
+            (received.min(requested) * 256 / requested) as u32
+        };
+        self.fill = (self.fill * 3 + ratio) / 4;
+        if self.fill >= Self::GROW_FILL {
+            self.size = (self.size * 2).min(self.max_size);
+        } else if self.fill < Self::SHRINK_FILL {
+            self.size = (self.size / 2).max(self.min_size);
+        }
+
+        if received == 0 {
+            self.idle_polls = self.idle_polls.saturating_add(1);
+        } else {
+            self.idle_polls = 0;
+        }
+    }
+
+    /// Whether the queue is idle, i.e. the recent polls are all empty.
+    #[inline]
+    pub fn is_idle(&self) -> bool {
+        self.idle_polls >= self.max_idle_polls
+    }
+
+    /// Return the backoff before the next poll, which is the idle backoff if
+    /// the queue is idle, or `Backoff::None` otherwise.
+    #[inline]
+    pub fn backoff(&self) -> Backoff {
+        if self.is_idle() {
+            self.idle_backoff
+        } else {
+            Backoff::None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn partition_mixed_burst() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut batch = ArrayVec::<_, 32>::new();
+            mp.fill_batch(&mut batch);
+            for (i, mbuf) in batch.iter_mut().enumerate() {
+                mbuf.extend_from_slice(&[i as u8]);
+            }
+
+            let (odd, even) = partition(batch, |mbuf| mbuf.data()[0] % 2 == 1);
+            assert_eq!(odd.len(), 16);
+            assert_eq!(even.len(), 16);
+            assert!(odd.iter().all(|mbuf| mbuf.data()[0] % 2 == 1));
+            assert!(even.iter().all(|mbuf| mbuf.data()[0] % 2 == 0));
+
+            let mut seen: Vec<u8> = odd.iter().chain(even.iter()).map(|m| m.data()[0]).collect();
+            seen.sort_unstable();
+            assert_eq!(seen, (0..32).collect::<Vec<u8>>());
+
+            drop(odd);
+            drop(even);
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn total_bytes_of_burst() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut batch = ArrayVec::<_, 32>::new();
+            assert_eq!(total_bytes(&batch), 0);
+            mp.fill_batch(&mut batch);
+            for (i, mbuf) in batch.iter_mut().enumerate() {
+                mbuf.extend_from_slice(&vec![0; 60 + i][..]);
+                assert_eq!(mbuf.total_len(), mbuf.len());
+            }
+            assert_eq!(total_bytes(&batch), (60..92).sum::<usize>());
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn adaptive_burst_size() {
+        let mut ctl = AdaptiveBurst::new(4, 64);
+        assert_eq!(ctl.next_size(), 64);
+
+        // the bursts are shrunk when the queue is idle
+        for _ in 0..AdaptiveBurst::MAX_IDLE_POLLS - 1 {
+            let size = ctl.next_size();
+            ctl.observe(0, size);
+            assert!(!ctl.is_idle());
+            assert_eq!(ctl.backoff(), Backoff::None);
+        }
+        assert_eq!(ctl.next_size(), 4);
+        ctl.observe(0, 4);
+        assert!(ctl.is_idle());
+        assert_eq!(ctl.backoff(), AdaptiveBurst::IDLE_BACKOFF);
+
+        // and grown under load
+        let mut sizes = Vec::new();
+        for _ in 0..8 {
+            let size = ctl.next_size();
+            ctl.observe(size, size);
+            assert!(!ctl.is_idle());
+            sizes.push(ctl.next_size());
+        }
+        assert!(sizes.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(ctl.next_size(), 64);
+
+        // a half-full burst keeps the size
+        for _ in 0..8 {
+            ctl.observe(32, 64);
+        }
+        assert_eq!(ctl.next_size(), 64);
+        for _ in 0..4 {
+            ctl.observe(4, 64);
+        }
+        assert!(ctl.next_size() < 64);
+        assert!(!ctl.is_idle());
+
+        let mut ctl = AdaptiveBurst::new(8, 8);
+        ctl.set_max_idle_polls(1);
+        ctl.set_idle_backoff(Backoff::Yield);
+        ctl.observe(0, 8);
+        assert_eq!(ctl.next_size(), 8);
+        assert_eq!(ctl.backoff(), Backoff::Yield);
+        ctl.observe(1, 8);
+        assert_eq!(ctl.backoff(), Backoff::None);
+    }
+}
@@ -0,0 +1,267 @@
+use arrayvec::ArrayVec;
+use rpkt_dpdk_sys as ffi;
+
+use crate::error::*;
+use crate::{Mbuf, Mempool, MempoolConf, Ring, RingConf, RingFlags};
+
+// Each message starts with its length as a big-endian u32.
+const LEN_PREFIX: usize = 4;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ByteRingConf {
+    /// The maximum number of the chunks in the ring, which is also the size of
+    /// the mempool of the chunks.
+    pub nb_chunks: u32,
+    /// The number of bytes in each chunk.
+    pub chunk_size: u16,
+    pub socket_id: u32,
+}
+
+impl ByteRingConf {
+    pub const NB_CHUNKS: u32 = 1024;
+    pub const CHUNK_SIZE: u16 = 256;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_nb_chunks(&mut self, val: u32) {
+        self.nb_chunks = val;
+    }
+
+    pub fn set_chunk_size(&mut self, val: u16) {
+        self.chunk_size = val;
+    }
+
+    pub fn set_socket_id(&mut self, val: u32) {
+        self.socket_id = val;
+    }
+}
+
+impl Default for ByteRingConf {
+    fn default() -> Self {
+        Self {
+            nb_chunks: Self::NB_CHUNKS,
+            chunk_size: Self::CHUNK_SIZE,
+            socket_id: 0,
+        }
+    }
+}
+
+/// A single-producer single-consumer stream of byte messages between two
+/// lcores.
+///
+/// Each message is copied into the mbufs of a dedicated mempool, which are
+/// passed through a ring as fixed-size chunks. The first chunk of a message
+/// starts with the message length, so the message boundaries are preserved.
+///
+/// The two ends are the clones of a `ByteRing`, one of them only writes and
+/// the other one only reads.
+pub struct ByteRing {
+    name: String,
+    ring: Ring,
+    mp: Mempool,
+    chunk_size: u16,
+    // The length and the received bytes of the message that is being read.
+    partial: Option<(usize, Vec<u8>)>,
+}
+
+impl ByteRing {
+    /// Create a new byte ring named `name`, the mempool of the chunks is
+    /// created with the service under the same name.
+    pub fn try_create(name: String, conf: &ByteRingConf) -> Result<Self> {
+        if usize::from(conf.chunk_size) < LEN_PREFIX || conf.nb_chunks == 0 {
+            return Error::service_err("invalid byte ring config").to_err();
+        }
+
+        let mut mp_conf = MempoolConf::new();
+        mp_conf.set_nb_mbufs(conf.nb_chunks);
+        mp_conf.set_dataroom(conf.chunk_size);
+        mp_conf.set_socket_id(conf.socket_id);
+        let mp = crate::service().mempool_create(&name, &mp_conf)?;
+
+        let ring_conf = RingConf {
+            count: conf.nb_chunks,
+            socket_id: conf.socket_id,
+            flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ | RingFlags::EXACT_SZ,
+        };
+        let ring = match Ring::try_create(name.clone(), &ring_conf) {
+            Ok(ring) => ring,
+            Err(err) => {
+                drop(mp);
+                crate::service().mempool_free(&name)?;
+                return Err(err);
+            }
+        };
+
+        Ok(Self {
+            name,
+            ring,
+            mp,
+            chunk_size: conf.chunk_size,
+            partial: None,
+        })
+    }
+
+    /// Write `bytes` as a single message.
+    ///
+    /// The message is either written as a whole or not at all. This function
+    /// fails if the ring has no room for the chunks of the message, in which
+    /// case the write can be retried after the reader catches up.
+    pub fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        let len = u32::try_from(bytes.len())
+            .map_err(|_| Error::service_err("message exceeds the byte ring"))?;
+        let free_count = unsafe { ffi::rte_ring_free_count_(self.ring.as_ptr()) };
+        let chunk_size = self.chunk_size();
+        let nb_chunks = (LEN_PREFIX + bytes.len() + chunk_size - 1) / chunk_size;
+        if nb_chunks > free_count as usize {
+            return Error::service_err("byte ring is full").to_err();
+        }
+
+        // All the chunks are allocated before the first one is enqueued, so
+        // that the reader never sees a partial message.
+        let mut chunks = Vec::new();
+        let mut rest = bytes;
+        let mut header = Some(len.to_be_bytes());
+        while header.is_some() || !rest.is_empty() {
+            let mut chunk = self
+                .mp
+                .try_alloc()
+                .ok_or(Error::service_err("byte ring mempool is exhausted"))?;
+            if let Some(header) = header.take() {
+                chunk.extend_from_slice(&header[..]);
+            }
+            let (data, remaining) = rest.split_at(rest.len().min(chunk.capacity()));
+            chunk.extend_from_slice(data);
+            rest = remaining;
+            chunks.push(chunk);
+        }
+
+        // There is a single producer, so the free space of the ring can only
+        // grow after it is checked.
+        let mut batch = ArrayVec::<Mbuf, 32>::new();
+        for chunk in chunks {
+            batch.push(chunk);
+            if batch.is_full() {
+                self.ring.enqueue_burst(&mut batch);
+                debug_assert!(batch.is_empty());
+            }
+        }
+        self.ring.enqueue_burst(&mut batch);
+        debug_assert!(batch.is_empty());
+
+        Ok(())
+    }
+
+    /// Read the next message, return `None` if no complete message is in the
+    /// ring yet.
+    ///
+    /// The chunks of an incomplete message are consumed and kept by this
+    /// `ByteRing`, the message is returned by a later `read`.
+    pub fn read(&mut self) -> Option<Vec<u8>> {
+        loop {
+            let mut batch = ArrayVec::<Mbuf, 1>::new();
+            self.ring.dequeue_burst(&mut batch);
+            let chunk = batch.pop()?;
+
+            let mut data = chunk.data();
+            if self.partial.is_none() {
+                let mut len = [0; LEN_PREFIX];
+                len.copy_from_slice(&data[..LEN_PREFIX]);
+                let len = u32::from_be_bytes(len) as usize;
+                self.partial = Some((len, Vec::with_capacity(len)));
+                data = &data[LEN_PREFIX..];
+            }
+            let (len, buf) = self.partial.as_mut().unwrap();
+            buf.extend_from_slice(data);
+
+            if buf.len() >= *len {
+                return self.partial.take().map(|(_, buf)| buf);
+            }
+        }
+    }
+
+    /// Return the number of message bytes that each chunk can hold.
+    pub fn chunk_size(&self) -> usize {
+        usize::from(self.chunk_size)
+    }
+
+    /// Return the wrapped ring.
+    pub fn ring(&self) -> &Ring {
+        &self.ring
+    }
+
+    /// Drop the chunks left in the ring, and free the mempool of the chunks
+    /// from the service.
+    ///
+    /// This function fails if the other end of the ring is still alive.
+    pub fn free(self) -> Result<()> {
+        let mut batch = ArrayVec::<Mbuf, 32>::new();
+        while self.ring.dequeue_burst(&mut batch) > 0 {
+            Mempool::free_batch(&mut batch);
+        }
+        let Self { name, ring, mp, .. } = self;
+        drop(ring);
+        drop(mp);
+        crate::service().mempool_free(&name)
+    }
+}
+
+impl Clone for ByteRing {
+    // The partial message belongs to the reader, it is not cloned.
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            ring: self.ring.clone(),
+            mp: self.mp.clone(),
+            chunk_size: self.chunk_size,
+            partial: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn message_across_chunks() {
+        DpdkOption::new().init().unwrap();
+
+        let mut conf = ByteRingConf::new();
+        conf.set_nb_chunks(32);
+        conf.set_chunk_size(512);
+        let mut writer = ByteRing::try_create("ctrl".to_string(), &conf).unwrap();
+        let mut reader = writer.clone();
+        assert_eq!(reader.read(), None);
+
+        // 5 KB spans 11 chunks with the length prefix
+        let msg: Vec<u8> = (0..5 * 1024).map(|i| (i % 251) as u8).collect();
+        writer.write(&msg[..]).unwrap();
+        writer.write(&[]).unwrap();
+        writer.write(b"ping").unwrap();
+        assert_eq!(reader.read().unwrap(), msg);
+        assert_eq!(reader.read().unwrap(), b"");
+        assert_eq!(reader.read().unwrap(), b"ping");
+        assert_eq!(reader.read(), None);
+
+        // the message is rejected as a whole if the ring has no room for it
+        for _ in 0..2 {
+            writer.write(&msg[..]).unwrap();
+        }
+        assert!(writer.write(&msg[..]).is_err());
+        writer.write(&msg[..512 * 9]).unwrap();
+        assert!(writer.write(&[0]).is_err());
+        for _ in 0..2 {
+            assert_eq!(reader.read().unwrap(), msg);
+        }
+        assert_eq!(reader.read().unwrap(), &msg[..512 * 9]);
+        assert_eq!(reader.read(), None);
+
+        // the chunks left in the ring are released
+        writer.write(&msg[..]).unwrap();
+        drop(reader);
+        writer.free().unwrap();
+    }
+}
@@ -0,0 +1,316 @@
+use arrayvec::ArrayVec;
+
+use crate::proto::{
+    checksum, ethertype, fold, ipv4_is_fragment, packet_data, pseudo_header_checksum,
+    ETHERTYPE_IPV4, ETHER_HEADER_LEN, IPPROTO_ICMP, IPPROTO_TCP, IPPROTO_UDP, IPV4_HEADER_LEN,
+    TCP_HEADER_LEN, UDP_HEADER_LEN,
+};
+use crate::Mbuf;
+
+/// The result of the checksum verification of a packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumResult {
+    /// All the verifiable checksums are correct.
+    Ok,
+    /// The ipv4 header checksum is wrong.
+    BadIp,
+    /// The tcp or udp checksum is wrong.
+    BadL4,
+    /// The packet is not a well-formed ipv4 packet.
+    NotApplicable,
+}
+
+// The checksum status reported by the rx offload flags, the checksum is
+// unknown if neither of the good and bad flags is set. Both flags set is
+// `RTE_MBUF_F_RX_{IP,L4}_CKSUM_NONE`: the checksum in the packet data is not
+// correct, e.g. it is left to be filled in, but the integrity of the data is
+// verified by the device, so the packet is valid.
+fn offload_status(good: bool, bad: bool) -> Option<bool> {
+    match (good, bad) {
+        (true, _) => Some(true),
+        (false, true) => Some(false),
+        (false, false) => None,
+    }
+}
+
+// Return the header length and the total length of the ipv4 packet of an
+// ethernet frame, which are checked against the frame.
+fn ipv4_lens(data: &[u8]) -> Option<(usize, usize)> {
+    let l3 = ETHER_HEADER_LEN;
+    if data.len() < l3 + IPV4_HEADER_LEN
+        || ethertype(data) != Some(ETHERTYPE_IPV4)
+        || data[l3] >> 4 != 4
+    {
+        return None;
+    }
+    let ihl = usize::from(data[l3] & 0x0f) * 4;
+    let total_len = usize::from(u16::from_be_bytes([data[l3 + 2], data[l3 + 3]]));
+    if ihl < IPV4_HEADER_LEN || total_len < ihl || data.len() < l3 + total_len {
+        return None;
+    }
+    Some((ihl, total_len))
+}
+
+/// Verify the ipv4 and the tcp/udp checksums of `mbuf`.
+///
+/// The checksums that are reported by the rx offload flags of the mbuf are not
+/// computed again. The l4 checksum of a fragment, or a protocol other than tcp
+/// and udp, is not verified.
+pub fn verify(mbuf: &Mbuf) -> ChecksumResult {
+    let rx_offload = mbuf.rx_offload();
+    let ip_status = offload_status(rx_offload.ip_cksum_good(), rx_offload.ip_cksum_bad());
+    let l4_status = offload_status(rx_offload.l4_cksum_good(), rx_offload.l4_cksum_bad());
+    match (ip_status, l4_status) {
+        (Some(false), _) => return ChecksumResult::BadIp,
+        (Some(true), Some(false)) => return ChecksumResult::BadL4,
+        (Some(true), Some(true)) => return ChecksumResult::Ok,
+        _ => {}
+    }
+
+    let data = packet_data(mbuf);
+    let l3 = ETHER_HEADER_LEN;
+    let Some((ihl, total_len)) = ipv4_lens(&data) else {
+        return ChecksumResult::NotApplicable;
+    };
+    let ip_packet = &data[l3..l3 + total_len];
+
+    if ip_status.is_none() && fold(checksum(&ip_packet[..ihl], 0)) != 0xffff {
+        return ChecksumResult::BadIp;
+    }
+    if l4_status == Some(false) {
+        return ChecksumResult::BadL4;
+    }
+    // the l4 checksum of a fragment covers the reassembled packet
+    if l4_status.is_some() || ipv4_is_fragment(ip_packet) {
+        return ChecksumResult::Ok;
+    }
+
+    let protocol = ip_packet[9];
+    let l4_packet = &ip_packet[ihl..];
+    let l4_len = match protocol {
+        IPPROTO_TCP if l4_packet.len() >= TCP_HEADER_LEN => l4_packet.len(),
+        IPPROTO_UDP if l4_packet.len() >= UDP_HEADER_LEN => {
+            let udp_len = usize::from(u16::from_be_bytes([l4_packet[4], l4_packet[5]]));
+            if udp_len < UDP_HEADER_LEN || udp_len > l4_packet.len() {
+                return ChecksumResult::NotApplicable;
+            }
+            // a zero udp checksum means that there is no checksum
+            if l4_packet[6..8] == [0, 0] {
+                return ChecksumResult::Ok;
+            }
+            udp_len
+        }
+        IPPROTO_TCP | IPPROTO_UDP => return ChecksumResult::NotApplicable,
+        _ => return ChecksumResult::Ok,
+    };
+
+    let accum = pseudo_header_checksum(ip_packet, protocol, l4_len);
+    let accum = checksum(&l4_packet[..l4_len], accum);
+    if fold(accum) == 0xffff {
+        ChecksumResult::Ok
+    } else {
+        ChecksumResult::BadL4
+    }
+}
+
+/// Verify the checksums of each mbuf in `batch` with `verify`.
+///
+/// The i-th result is the result of the i-th mbuf.
+pub fn verify_burst<const N: usize>(batch: &ArrayVec<Mbuf, N>) -> ArrayVec<ChecksumResult, N> {
+    batch.iter().map(verify).collect()
+}
+
+impl Mbuf {
+    /// Compute and set the ipv4 header checksum, and then the tcp, udp or icmp
+    /// checksum of the packet, so that a packet built by hand is valid on the
+    /// wire.
+    ///
+    /// The l4 checksum is computed over the whole packet, but the headers must
+    /// be in the first segment. The l4 checksum of a fragment is left
+    /// untouched, as it covers the reassembled packet. A packet that is not a
+    /// well-formed ipv4 packet is left untouched, see `verify`.
+    pub fn finalize_checksums(&mut self) {
+        let l3 = ETHER_HEADER_LEN;
+        let Some((ihl, total_len)) = ipv4_lens(&packet_data(self)) else {
+            return;
+        };
+        let l4 = l3 + ihl;
+        if self.data().len() < l4 {
+            return;
+        }
+
+        // ipv4 header
+        let data = self.data_mut();
+        data[l3 + 10..l3 + 12].fill(0);
+        let cksum = !fold(checksum(&data[l3..l4], 0));
+        data[l3 + 10..l3 + 12].copy_from_slice(&cksum.to_be_bytes());
+
+        let fragment = ipv4_is_fragment(&data[l3..l4]);
+        let protocol = data[l3 + 9];
+        let (cksum_offset, l4_len) = match protocol {
+            IPPROTO_TCP if total_len >= ihl + TCP_HEADER_LEN => (16, total_len - ihl),
+            IPPROTO_UDP if total_len >= ihl + UDP_HEADER_LEN => {
+                let udp_len = usize::from(u16::from_be_bytes([data[l4 + 4], data[l4 + 5]]));
+                if udp_len < UDP_HEADER_LEN || udp_len > total_len - ihl {
+                    return;
+                }
+                (6, udp_len)
+            }
+            IPPROTO_ICMP if total_len >= ihl + 8 => (2, total_len - ihl),
+            _ => return,
+        };
+        if fragment || data.len() < l4 + cksum_offset + 2 {
+            return;
+        }
+
+        // l4 header, the icmp checksum has no pseudo header
+        data[l4 + cksum_offset..l4 + cksum_offset + 2].fill(0);
+        let whole = packet_data(self);
+        let accum = if protocol == IPPROTO_ICMP {
+            0
+        } else {
+            pseudo_header_checksum(&whole[l3..l4], protocol, l4_len)
+        };
+        let mut cksum = !fold(checksum(&whole[l4..l4 + l4_len], accum));
+        drop(whole);
+        // a zero udp checksum means that there is no checksum
+        if protocol == IPPROTO_UDP && cksum == 0 {
+            cksum = 0xffff;
+        }
+        self.data_mut()[l4 + cksum_offset..l4 + cksum_offset + 2]
+            .copy_from_slice(&cksum.to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use rpkt::ipv4::IpProtocol;
+
+    use super::*;
+    use crate::proto::l4_frame;
+    use crate::*;
+
+    fn set_rx_offload(mbuf: &mut Mbuf, ol_flags: u64) {
+        unsafe { (*(mbuf.as_ptr() as *mut rpkt_dpdk_sys::rte_mbuf)).ol_flags = ol_flags };
+    }
+
+    #[test]
+    fn verify_mixed_burst() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let (src, dst) = (Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2));
+            let tcp_frame = l4_frame(IpProtocol::TCP, src, dst, 0, 80, 0, &[0xab; 32]);
+            let udp_frame = l4_frame(IpProtocol::UDP, src, dst, 0, 53, 0, &[0xab; 32]);
+            let l3 = ETHER_HEADER_LEN;
+            let l4 = ETHER_HEADER_LEN + IPV4_HEADER_LEN;
+
+            let mut bad_ip = tcp_frame.clone();
+            bad_ip[l3 + 10] ^= 0xff;
+            let mut bad_tcp = tcp_frame.clone();
+            bad_tcp[l4 + 16] ^= 0xff;
+            let mut bad_udp = udp_frame.clone();
+            bad_udp[l4 + UDP_HEADER_LEN] ^= 0xff;
+            let mut arp = tcp_frame.clone();
+            arp[12..14].copy_from_slice(&[0x08, 0x06]);
+
+            // (frame, rx offload flags, expected result)
+            let ip_good = 1 << 7;
+            let l4_good = 1 << 8;
+            let l4_bad = 1 << 3;
+            let cases = [
+                (&tcp_frame, 0, ChecksumResult::Ok),
+                (&udp_frame, 0, ChecksumResult::Ok),
+                (&bad_ip, 0, ChecksumResult::BadIp),
+                (&bad_tcp, 0, ChecksumResult::BadL4),
+                (&bad_udp, 0, ChecksumResult::BadL4),
+                (&arp, 0, ChecksumResult::NotApplicable),
+                // the offload flags are trusted over the packet data
+                (&bad_tcp, ip_good | l4_good, ChecksumResult::Ok),
+                (&tcp_frame, ip_good | l4_bad, ChecksumResult::BadL4),
+                (&tcp_frame, 1 << 4, ChecksumResult::BadIp),
+                // only the ip checksum is reported
+                (&bad_udp, ip_good, ChecksumResult::BadL4),
+                (&bad_ip, l4_good, ChecksumResult::BadIp),
+                // both flags set: the checksums in the data are wrong, but the
+                // data is verified
+                (&bad_ip, ip_good | 1 << 4 | l4_good, ChecksumResult::Ok),
+                (&bad_tcp, ip_good | l4_good | l4_bad, ChecksumResult::Ok),
+            ];
+
+            let mut batch = ArrayVec::<_, 32>::new();
+            for (frame, ol_flags, _) in cases.iter() {
+                let mut mbuf = mp.try_alloc().unwrap();
+                mbuf.extend_from_slice(&frame[..]);
+                set_rx_offload(&mut mbuf, *ol_flags);
+                batch.push(mbuf);
+            }
+
+            let results = verify_burst(&batch);
+            assert_eq!(results.len(), cases.len());
+            for (result, (_, _, expected)) in results.iter().zip(cases.iter()) {
+                assert_eq!(result, expected);
+            }
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn finalize_zeroed_checksums() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let l3 = ETHER_HEADER_LEN;
+            let l4 = ETHER_HEADER_LEN + IPV4_HEADER_LEN;
+            // (frame, offset of the l4 checksum)
+            let (src, dst) = (Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2));
+            let tcp_frame = l4_frame(IpProtocol::TCP, src, dst, 0, 80, 0, &[0xab; 32]);
+            let udp_frame = l4_frame(IpProtocol::UDP, src, dst, 0, 53, 0, &[0xab; 32]);
+            for (frame, cksum_offset) in [(&tcp_frame, 16), (&udp_frame, 6)] {
+                let mut bytes = frame.clone();
+                bytes[l3 + 10..l3 + 12].fill(0);
+                bytes[l4 + cksum_offset..l4 + cksum_offset + 2].fill(0);
+
+                let mut mbuf = mp.try_alloc().unwrap();
+                mbuf.extend_from_slice(&bytes[..]);
+                assert_eq!(verify(&mbuf), ChecksumResult::BadIp);
+                mbuf.finalize_checksums();
+                assert_eq!(verify(&mbuf), ChecksumResult::Ok);
+                assert_eq!(mbuf.data(), &frame[..]);
+            }
+
+            // an icmp echo request, whose checksum has no pseudo header
+            let mut bytes = tcp_frame.clone();
+            bytes[l3 + 9] = IPPROTO_ICMP;
+            bytes[l3 + 10..l3 + 12].fill(0);
+            bytes[l4..l4 + 8].copy_from_slice(&[8, 0, 0, 0, 0x12, 0x34, 0, 1]);
+            let mut mbuf = mp.try_alloc().unwrap();
+            mbuf.extend_from_slice(&bytes[..]);
+            mbuf.finalize_checksums();
+            assert_eq!(verify(&mbuf), ChecksumResult::Ok);
+            assert_eq!(fold(checksum(&mbuf.data()[l4..], 0)), 0xffff);
+
+            // a non-ipv4 frame is left untouched
+            let mut arp = tcp_frame.clone();
+            arp[12..14].copy_from_slice(&[0x08, 0x06]);
+            arp[l3 + 10..l3 + 12].fill(0);
+            let mut mbuf = mp.try_alloc().unwrap();
+            mbuf.extend_from_slice(&arp[..]);
+            mbuf.finalize_checksums();
+            assert_eq!(mbuf.data(), &arp[..]);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+}
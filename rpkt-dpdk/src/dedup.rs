@@ -0,0 +1,207 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::proto::{
+    ethertype, packet_data, ETHERTYPE_IPV4, ETHERTYPE_IPV6, ETHER_HEADER_LEN, IPV4_HEADER_LEN,
+    IPV6_HEADER_LEN,
+};
+use crate::Mbuf;
+
+#[derive(Clone, Copy, Debug)]
+pub struct DedupConf {
+    /// The number of the recent unique packets that are remembered.
+    pub window: u32,
+    /// The number of the bytes past the ip header that are hashed, which cover
+    /// the l4 header and a prefix of the payload.
+    pub payload_prefix: u16,
+}
+
+impl DedupConf {
+    pub const WINDOW: u32 = 4096;
+    pub const PAYLOAD_PREFIX: u16 = 64;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_window(&mut self, val: u32) {
+        self.window = val;
+    }
+
+    pub fn set_payload_prefix(&mut self, val: u16) {
+        self.payload_prefix = val;
+    }
+}
+
+impl Default for DedupConf {
+    fn default() -> Self {
+        Self {
+            window: Self::WINDOW,
+            payload_prefix: Self::PAYLOAD_PREFIX,
+        }
+    }
+}
+
+/// A detector of the duplicate packets among the recent ones, e.g. for a tap
+/// that sees the same packet at two capture points.
+///
+/// Each packet is reduced to a 64-bit hash of its ip header and a prefix of
+/// the ip payload, so the hash covers the 5-tuple, the ipv4 identification
+/// and the start of the payload. The ethernet header, the ttl or hop limit,
+/// and the ipv4 checksum are left out, as they change on the way between the
+/// capture points. Only the first `payload_prefix` bytes of a non-ip frame
+/// are hashed.
+///
+/// The hashes of the last `window` unique packets are kept in a fifo, with an
+/// index for the O(1) lookup. Two different packets may share a hash, so a
+/// duplicate is only likely.
+pub struct DedupWindow {
+    fifo: VecDeque<u64>,
+    index: HashSet<u64>,
+    window: usize,
+    payload_prefix: usize,
+}
+
+impl DedupWindow {
+    /// # Panics
+    ///
+    /// This function panics if `conf.window` is 0.
+    pub fn new(conf: &DedupConf) -> Self {
+        assert!(conf.window > 0, "invalid dedup window");
+        let window = conf.window as usize;
+        Self {
+            fifo: VecDeque::with_capacity(window),
+            index: HashSet::with_capacity(window),
+            window,
+            payload_prefix: usize::from(conf.payload_prefix),
+        }
+    }
+
+    /// Check whether `mbuf` is a likely duplicate of a recent packet.
+    ///
+    /// A unique packet is remembered, evicting the oldest one if the window is
+    /// full, while a duplicate is not.
+    #[inline]
+    pub fn check(&mut self, mbuf: &Mbuf) -> bool {
+        self.check_frame(&packet_data(mbuf))
+    }
+
+    /// Check whether the ethernet frame in `frame` is a likely duplicate, see
+    /// `DedupWindow::check`.
+    pub fn check_frame(&mut self, frame: &[u8]) -> bool {
+        let hash = frame_hash(frame, self.payload_prefix);
+        if self.index.contains(&hash) {
+            return true;
+        }
+
+        // the hashes in the fifo are unique, as a duplicate is never pushed
+        if self.fifo.len() == self.window {
+            let oldest = self.fifo.pop_front().unwrap();
+            self.index.remove(&oldest);
+        }
+        self.fifo.push_back(hash);
+        self.index.insert(hash);
+        false
+    }
+
+    /// Return the number of the remembered packets.
+    pub fn len(&self) -> usize {
+        self.fifo.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fifo.is_empty()
+    }
+
+    /// Forget all the remembered packets.
+    pub fn clear(&mut self) {
+        self.fifo.clear();
+        self.index.clear();
+    }
+}
+
+// Hash the ip packet of `frame` with the fields that change on the way masked
+// out, see `DedupWindow`.
+fn frame_hash(frame: &[u8], payload_prefix: usize) -> u64 {
+    let ip_packet = &frame[ETHER_HEADER_LEN.min(frame.len())..];
+
+    let (header_len, masked): (usize, &[usize]) = match ethertype(frame) {
+        // the ttl and the header checksum
+        Some(ETHERTYPE_IPV4) if ip_packet.len() >= IPV4_HEADER_LEN => {
+            (usize::from(ip_packet[0] & 0x0f) * 4, &[8, 10, 11])
+        }
+        // the hop limit
+        Some(ETHERTYPE_IPV6) if ip_packet.len() >= IPV6_HEADER_LEN => (IPV6_HEADER_LEN, &[7]),
+        _ => return fnv1a(frame[..frame.len().min(payload_prefix)].iter().copied()),
+    };
+    let len = ip_packet.len().min(header_len + payload_prefix);
+    fnv1a(
+        ip_packet[..len]
+            .iter()
+            .enumerate()
+            .map(|(idx, byte)| if masked.contains(&idx) { 0 } else { *byte }),
+    )
+}
+
+// The 64-bit FNV-1a hash, which is fast on the short inputs.
+fn fnv1a(bytes: impl Iterator<Item = u8>) -> u64 {
+    bytes.fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use rpkt::ipv4::IpProtocol;
+
+    use super::*;
+    use crate::proto::l4_frame;
+
+    #[test]
+    fn flag_injected_duplicates() {
+        let mut conf = DedupConf::new();
+        conf.set_window(64);
+        let mut dedup = DedupWindow::new(&conf);
+        // an ipv4 udp frame with the identification `ident` and an 18-byte
+        // payload filled with `fill`
+        let udp_frame = |ident, fill| {
+            let (src, dst) = (Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2));
+            l4_frame(IpProtocol::UDP, src, dst, 1024, 2048, ident, &[fill; 18])
+        };
+
+        let mut nb_dups = 0;
+        for ident in 0..1000u16 {
+            let frame = udp_frame(ident, 0x5a);
+            assert!(!dedup.check_frame(&frame[..]));
+            // every 10th packet is seen again at another capture point, one
+            // hop further, with a different mac and ttl
+            if ident % 10 == 0 {
+                let mut mirrored = frame.clone();
+                mirrored[0..6].copy_from_slice(&[0x02, 0, 0, 0, 0, 0x03]);
+                mirrored[22] -= 1;
+                mirrored[24..26].copy_from_slice(&[0xde, 0xad]);
+                assert!(dedup.check_frame(&mirrored[..]));
+                nb_dups += 1;
+            }
+        }
+        assert_eq!(nb_dups, 100);
+        assert_eq!(dedup.len(), 64);
+
+        // the packets differing in the ip id or the payload are unique
+        assert!(!dedup.check_frame(&udp_frame(2000, 0xff)[..]));
+        assert!(!dedup.check_frame(&udp_frame(2000, 0xfe)[..]));
+        assert!(dedup.check_frame(&udp_frame(2000, 0xfe)[..]));
+        // the oldest packets are evicted
+        assert!(dedup.check_frame(&udp_frame(999, 0x5a)[..]));
+        assert!(!dedup.check_frame(&udp_frame(0, 0x5a)[..]));
+
+        dedup.clear();
+        assert!(dedup.is_empty());
+        assert!(!dedup.check_frame(&udp_frame(2000, 0xff)[..]));
+        // the non-ip frames are hashed from the start
+        assert!(!dedup.check_frame(&[0xaa; 60][..]));
+        assert!(dedup.check_frame(&[0xaa; 60][..]));
+        assert!(!dedup.check_frame(&[0xaa; 4][..]));
+    }
+}
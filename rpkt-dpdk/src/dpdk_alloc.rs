@@ -0,0 +1,110 @@
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+use rpkt_dpdk_sys as ffi;
+
+/// A pointer type that owns a value allocated by `rte_zmalloc_socket`.
+///
+/// The value is dropped and the memory is freed with `rte_free` when the
+/// `RteBox` is dropped.
+pub struct RteBox<T: ?Sized> {
+    ptr: NonNull<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for RteBox<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for RteBox<T> {}
+
+impl<T: ?Sized> Deref for RteBox<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RteBox<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T: ?Sized> Drop for RteBox<T> {
+    fn drop(&mut self) {
+        unsafe {
+            std::ptr::drop_in_place(self.ptr.as_ptr());
+            ffi::rte_free(self.ptr.as_ptr() as *mut std::os::raw::c_void);
+        }
+    }
+}
+
+// Allocate `size` zeroed bytes aligned to `align` on `socket_id`.
+fn zmalloc_socket(size: usize, align: usize, socket_id: u32) -> Option<NonNull<u8>> {
+    let socket_id = i32::try_from(socket_id).ok()?;
+    let align = u32::try_from(align).ok()?;
+    // `rte_zmalloc_socket` returns NULL for a zero size
+    let ptr = unsafe { ffi::rte_zmalloc_socket(std::ptr::null(), size.max(1), align, socket_id) };
+    NonNull::new(ptr as *mut u8)
+}
+
+/// Allocate a zeroed `T` from the hugepage memory on `socket_id`, aligned to
+/// the alignment of `T`.
+///
+/// Return `None` if the allocation fails.
+///
+/// # Safety:
+/// The all-zero bit pattern must be a valid value of `T`.
+pub unsafe fn alloc_zeroed<T>(socket_id: u32) -> Option<RteBox<T>> {
+    let ptr = zmalloc_socket(
+        std::mem::size_of::<T>(),
+        std::mem::align_of::<T>(),
+        socket_id,
+    )?;
+    Some(RteBox { ptr: ptr.cast() })
+}
+
+/// Allocate a zeroed array of `n` `T` from the hugepage memory on `socket_id`,
+/// aligned to the alignment of `T`.
+///
+/// Return `None` if the allocation fails.
+///
+/// # Safety:
+/// The all-zero bit pattern must be a valid value of `T`.
+pub unsafe fn alloc_array<T>(n: usize, socket_id: u32) -> Option<RteBox<[T]>> {
+    let size = std::mem::size_of::<T>().checked_mul(n)?;
+    let ptr = zmalloc_socket(size, std::mem::align_of::<T>(), socket_id)?;
+    let slice = std::ptr::slice_from_raw_parts_mut(ptr.as_ptr() as *mut T, n);
+    Some(RteBox {
+        ptr: NonNull::new_unchecked(slice),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn alloc_zeroed_array() {
+        DpdkOption::new().init().unwrap();
+        let socket_id = service().lcores()[0].socket_id;
+
+        let mut table = unsafe { alloc_array::<u64>(4096, socket_id) }.unwrap();
+        assert_eq!(table.len(), 4096);
+        assert!(table.iter().all(|val| *val == 0));
+        for (i, val) in table.iter_mut().enumerate() {
+            *val = i as u64;
+        }
+        assert_eq!(table[4095], 4095);
+
+        #[repr(align(64))]
+        struct Aligned([u8; 100]);
+        let aligned = unsafe { alloc_zeroed::<Aligned>(socket_id) }.unwrap();
+        assert_eq!(&*aligned as *const Aligned as usize % 64, 0);
+        assert!(aligned.0.iter().all(|val| *val == 0));
+
+        let empty = unsafe { alloc_array::<u64>(0, socket_id) }.unwrap();
+        assert!(empty.is_empty());
+    }
+}
@@ -1,6 +1,9 @@
 use std::error;
 use std::fmt;
 use std::os::raw::c_int;
+use std::ptr::NonNull;
+
+use rpkt_dpdk_sys as ffi;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -34,6 +37,26 @@ impl Error {
     }
 }
 
+/// Check the pointer returned by an FFI call that sets `rte_errno` on failure.
+///
+/// This must be called immediately after the FFI call, before any other FFI call
+/// can overwrite `rte_errno`.
+#[inline]
+pub(crate) fn check_ptr<T>(ptr: *mut T, msg: &'static str) -> Result<NonNull<T>> {
+    NonNull::new(ptr).ok_or_else(|| Error::ffi_err(unsafe { ffi::rte_errno_() }, msg))
+}
+
+/// Check the return value of an FFI call that returns a negative error number
+/// on failure, the non-negative return value is passed through.
+#[inline]
+pub(crate) fn check_ret(ret: c_int, msg: &'static str) -> Result<c_int> {
+    if ret < 0 {
+        Error::ffi_err(ret, msg).to_err()
+    } else {
+        Ok(ret)
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.kind {
@@ -57,13 +80,22 @@ pub enum ErrorKind {
     FFIError(c_int),
 }
 
+const ENOENT: i32 = 2;
+const ENOMEM: i32 = 12;
+const EEXIST: i32 = 17;
+const ENOSPC: i32 = 28;
 const ENODEV: i32 = 19;
 const ENOTSUP: i32 = 95;
 const EBUSY: i32 = 16;
 const EINVAL: i32 = 22;
 
+// The DPDK functions may return either positive or negative error numbers.
 fn errno_str(errno: i32) -> &'static str {
-    match errno {
+    match errno.abs() {
+        ENOENT => "no such file or directory",
+        ENOMEM => "out of memory",
+        EEXIST => "file exists",
+        ENOSPC => "no space left on device",
         ENODEV => "no such device",
         ENOTSUP => "operation not supported",
         EBUSY => "device or resource busy",
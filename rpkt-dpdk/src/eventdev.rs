@@ -0,0 +1,707 @@
+use std::{ffi::CString, sync::Arc};
+
+use arrayvec::ArrayVec;
+use rpkt_dpdk_sys as ffi;
+
+use crate::{
+    error::{check_ret, Error, Result},
+    Mbuf,
+};
+
+// Constants from rte_eventdev.h.
+const RTE_EVENT_OP_NEW: u8 = 0;
+const RTE_EVENT_OP_FORWARD: u8 = 1;
+const RTE_EVENT_OP_RELEASE: u8 = 2;
+const RTE_EVENT_TYPE_CPU: u32 = 0x3;
+const RTE_EVENT_DEV_PRIORITY_NORMAL: u8 = 128;
+
+/// The scheduling type of an event queue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SchedType {
+    /// Events of the same flow may be processed in parallel, but they are
+    /// restored to the original order when they are forwarded.
+    Ordered = 0,
+    /// Events of the same flow are processed by a single port at a time.
+    Atomic = 1,
+    /// No ordering guarantee.
+    Parallel = 2,
+}
+
+impl SchedType {
+    fn from_raw(val: u8) -> Self {
+        match val {
+            0 => Self::Ordered,
+            1 => Self::Atomic,
+            _ => Self::Parallel,
+        }
+    }
+}
+
+/// The operation that an enqueued event performs on the event device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventOp {
+    /// Inject a new event into the event device.
+    New,
+    /// Forward a dequeued event to the next stage.
+    Forward,
+    /// Release the flow context of a dequeued event.
+    Release,
+}
+
+#[derive(Clone)]
+pub struct EventDevConf {
+    pub nb_event_queues: u8,
+    pub nb_event_ports: u8,
+    pub nb_events_limit: i32,
+    pub nb_event_queue_flows: u32,
+    pub nb_event_port_dequeue_depth: u32,
+    pub nb_event_port_enqueue_depth: u32,
+    pub dequeue_timeout_ns: u32,
+}
+
+impl EventDevConf {
+    pub const NB_EVENTS_LIMIT: i32 = 4096;
+    pub const NB_EVENT_QUEUE_FLOWS: u32 = 1024;
+    pub const NB_EVENT_PORT_DEPTH: u32 = 32;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a configuration that uses the maximum resources of the device.
+    pub fn from_dev_info(info: &EventDevInfo, nb_event_queues: u8, nb_event_ports: u8) -> Self {
+        Self {
+            nb_event_queues,
+            nb_event_ports,
+            nb_events_limit: info.max_num_events(),
+            nb_event_queue_flows: info.max_event_queue_flows(),
+            nb_event_port_dequeue_depth: info.max_event_port_dequeue_depth(),
+            nb_event_port_enqueue_depth: info.max_event_port_enqueue_depth(),
+            dequeue_timeout_ns: info.min_dequeue_timeout_ns(),
+        }
+    }
+
+    pub fn set_nb_event_queues(&mut self, val: u8) {
+        self.nb_event_queues = val;
+    }
+
+    pub fn set_nb_event_ports(&mut self, val: u8) {
+        self.nb_event_ports = val;
+    }
+
+    pub fn set_nb_events_limit(&mut self, val: i32) {
+        self.nb_events_limit = val;
+    }
+
+    pub fn set_nb_event_queue_flows(&mut self, val: u32) {
+        self.nb_event_queue_flows = val;
+    }
+
+    pub fn set_nb_event_port_dequeue_depth(&mut self, val: u32) {
+        self.nb_event_port_dequeue_depth = val;
+    }
+
+    pub fn set_nb_event_port_enqueue_depth(&mut self, val: u32) {
+        self.nb_event_port_enqueue_depth = val;
+    }
+
+    pub fn set_dequeue_timeout_ns(&mut self, val: u32) {
+        self.dequeue_timeout_ns = val;
+    }
+}
+
+impl Default for EventDevConf {
+    fn default() -> Self {
+        Self {
+            nb_event_queues: 1,
+            nb_event_ports: 1,
+            nb_events_limit: Self::NB_EVENTS_LIMIT,
+            nb_event_queue_flows: Self::NB_EVENT_QUEUE_FLOWS,
+            nb_event_port_dequeue_depth: Self::NB_EVENT_PORT_DEPTH,
+            nb_event_port_enqueue_depth: Self::NB_EVENT_PORT_DEPTH,
+            dequeue_timeout_ns: 0,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct EventQueueConf {
+    pub nb_atomic_flows: u32,
+    pub nb_atomic_order_sequences: u32,
+    pub sched_type: SchedType,
+    pub priority: u8,
+}
+
+impl EventQueueConf {
+    pub const NB_ATOMIC_FLOWS: u32 = 1024;
+    pub const NB_ATOMIC_ORDER_SEQUENCES: u32 = 1024;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_nb_atomic_flows(&mut self, val: u32) {
+        self.nb_atomic_flows = val;
+    }
+
+    pub fn set_nb_atomic_order_sequences(&mut self, val: u32) {
+        self.nb_atomic_order_sequences = val;
+    }
+
+    pub fn set_sched_type(&mut self, val: SchedType) {
+        self.sched_type = val;
+    }
+
+    pub fn set_priority(&mut self, val: u8) {
+        self.priority = val;
+    }
+}
+
+impl Default for EventQueueConf {
+    fn default() -> Self {
+        Self {
+            nb_atomic_flows: Self::NB_ATOMIC_FLOWS,
+            nb_atomic_order_sequences: Self::NB_ATOMIC_ORDER_SEQUENCES,
+            sched_type: SchedType::Atomic,
+            priority: RTE_EVENT_DEV_PRIORITY_NORMAL,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct EventPortConf {
+    pub new_event_threshold: i32,
+    pub dequeue_depth: u16,
+    pub enqueue_depth: u16,
+}
+
+impl EventPortConf {
+    pub const NEW_EVENT_THRESHOLD: i32 = 1024;
+    pub const DEPTH: u16 = 32;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_new_event_threshold(&mut self, val: i32) {
+        self.new_event_threshold = val;
+    }
+
+    pub fn set_dequeue_depth(&mut self, val: u16) {
+        self.dequeue_depth = val;
+    }
+
+    pub fn set_enqueue_depth(&mut self, val: u16) {
+        self.enqueue_depth = val;
+    }
+}
+
+impl Default for EventPortConf {
+    fn default() -> Self {
+        Self {
+            new_event_threshold: Self::NEW_EVENT_THRESHOLD,
+            dequeue_depth: Self::DEPTH,
+            enqueue_depth: Self::DEPTH,
+        }
+    }
+}
+
+pub struct EventDevInfo {
+    raw: ffi::rte_event_dev_info,
+}
+
+impl EventDevInfo {
+    pub fn try_get(dev_id: u8) -> Result<Self> {
+        let mut raw: ffi::rte_event_dev_info = unsafe { std::mem::zeroed() };
+        let res = unsafe { ffi::rte_event_dev_info_get(dev_id, &mut raw) };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to get event dev info").to_err();
+        }
+        Ok(Self { raw })
+    }
+
+    pub fn max_event_queues(&self) -> u8 {
+        self.raw.max_event_queues
+    }
+
+    pub fn max_event_queue_flows(&self) -> u32 {
+        self.raw.max_event_queue_flows
+    }
+
+    pub fn max_event_ports(&self) -> u8 {
+        self.raw.max_event_ports
+    }
+
+    pub fn max_event_port_dequeue_depth(&self) -> u32 {
+        self.raw.max_event_port_dequeue_depth
+    }
+
+    pub fn max_event_port_enqueue_depth(&self) -> u32 {
+        self.raw.max_event_port_enqueue_depth
+    }
+
+    pub fn max_num_events(&self) -> i32 {
+        self.raw.max_num_events
+    }
+
+    pub fn min_dequeue_timeout_ns(&self) -> u32 {
+        self.raw.min_dequeue_timeout_ns
+    }
+
+    pub fn max_dequeue_timeout_ns(&self) -> u32 {
+        self.raw.max_dequeue_timeout_ns
+    }
+}
+
+/// An event that carries an mbuf through the event device.
+///
+/// The event owns the mbuf, which is freed when the event is dropped.
+#[repr(transparent)]
+pub struct Event {
+    raw: ffi::rte_event,
+}
+
+unsafe impl Send for Event {}
+
+impl Event {
+    /// Create a new event that is injected into `queue_id`.
+    ///
+    /// Events with the same `flow_id` belong to the same flow, which is used
+    /// by the atomic and ordered scheduling.
+    pub fn new(mbuf: Mbuf, queue_id: u8, sched_type: SchedType, flow_id: u32) -> Self {
+        let mut raw: ffi::rte_event = unsafe { std::mem::zeroed() };
+        let attrs = unsafe { &mut raw.__bindgen_anon_1.__bindgen_anon_1 };
+        // the flow id is a 20-bit field
+        attrs.set_flow_id(flow_id & 0xfffff);
+        attrs.set_event_type(RTE_EVENT_TYPE_CPU);
+        attrs.set_op(RTE_EVENT_OP_NEW);
+        attrs.set_sched_type(sched_type as u8);
+        attrs.queue_id = queue_id;
+        attrs.priority = RTE_EVENT_DEV_PRIORITY_NORMAL;
+        raw.__bindgen_anon_2.mbuf = mbuf.into_raw();
+
+        Self { raw }
+    }
+
+    #[inline]
+    pub fn queue_id(&self) -> u8 {
+        unsafe { self.raw.__bindgen_anon_1.__bindgen_anon_1.queue_id }
+    }
+
+    #[inline]
+    pub fn flow_id(&self) -> u32 {
+        unsafe { self.raw.__bindgen_anon_1.__bindgen_anon_1.flow_id() }
+    }
+
+    #[inline]
+    pub fn sched_type(&self) -> SchedType {
+        SchedType::from_raw(unsafe { self.raw.__bindgen_anon_1.__bindgen_anon_1.sched_type() })
+    }
+
+    #[inline]
+    pub fn priority(&self) -> u8 {
+        unsafe { self.raw.__bindgen_anon_1.__bindgen_anon_1.priority }
+    }
+
+    #[inline]
+    pub fn set_priority(&mut self, val: u8) {
+        unsafe { self.raw.__bindgen_anon_1.__bindgen_anon_1.priority = val };
+    }
+
+    /// Set the operation of the event.
+    ///
+    /// Note that the device does not return released events, so the mbuf of an
+    /// enqueued `EventOp::Release` event must be freed by the user.
+    #[inline]
+    pub fn set_op(&mut self, op: EventOp) {
+        let op = match op {
+            EventOp::New => RTE_EVENT_OP_NEW,
+            EventOp::Forward => RTE_EVENT_OP_FORWARD,
+            EventOp::Release => RTE_EVENT_OP_RELEASE,
+        };
+        unsafe { self.raw.__bindgen_anon_1.__bindgen_anon_1.set_op(op) };
+    }
+
+    /// Prepare a dequeued event to be forwarded to the next stage at `queue_id`.
+    #[inline]
+    pub fn forward(&mut self, queue_id: u8, sched_type: SchedType) {
+        let attrs = unsafe { &mut self.raw.__bindgen_anon_1.__bindgen_anon_1 };
+        attrs.set_op(RTE_EVENT_OP_FORWARD);
+        attrs.set_sched_type(sched_type as u8);
+        attrs.queue_id = queue_id;
+    }
+
+    #[inline]
+    pub fn mbuf(&self) -> &Mbuf {
+        unsafe { &*(&self.raw.__bindgen_anon_2.mbuf as *const *mut ffi::rte_mbuf as *const Mbuf) }
+    }
+
+    #[inline]
+    pub fn mbuf_mut(&mut self) -> &mut Mbuf {
+        unsafe {
+            &mut *(&mut self.raw.__bindgen_anon_2.mbuf as *mut *mut ffi::rte_mbuf as *mut Mbuf)
+        }
+    }
+
+    #[inline]
+    pub fn into_mbuf(self) -> Mbuf {
+        let mbuf = unsafe { Mbuf::from_raw(self.raw.__bindgen_anon_2.mbuf) };
+        std::mem::forget(self);
+        mbuf
+    }
+}
+
+impl Drop for Event {
+    fn drop(&mut self) {
+        unsafe { Mbuf::from_raw(self.raw.__bindgen_anon_2.mbuf) };
+    }
+}
+
+/// An event device, such as `event_sw0`.
+pub struct EventDev {
+    dev_id: u8,
+    nb_ports: u8,
+    ports: Vec<EventPort>,
+    started: bool,
+}
+
+impl EventDev {
+    /// Get the event device with the given name.
+    pub fn try_get(name: &str) -> Result<Self> {
+        let cname = CString::new(name).map_err(|_| Error::service_err("invalid event dev name"))?;
+        let res = check_ret(
+            unsafe { ffi::rte_event_dev_get_dev_id(cname.as_ptr()) },
+            "fail to find event dev",
+        )?;
+
+        Ok(Self {
+            dev_id: res as u8,
+            nb_ports: 0,
+            ports: Vec::new(),
+            started: false,
+        })
+    }
+
+    #[inline]
+    pub fn dev_id(&self) -> u8 {
+        self.dev_id
+    }
+
+    pub fn info(&self) -> Result<EventDevInfo> {
+        EventDevInfo::try_get(self.dev_id)
+    }
+
+    pub fn configure(&mut self, conf: &EventDevConf) -> Result<()> {
+        if self.started {
+            return Error::service_err("event dev is started").to_err();
+        }
+
+        let mut raw: ffi::rte_event_dev_config = unsafe { std::mem::zeroed() };
+        raw.dequeue_timeout_ns = conf.dequeue_timeout_ns;
+        raw.nb_events_limit = conf.nb_events_limit;
+        raw.nb_event_queues = conf.nb_event_queues;
+        raw.nb_event_ports = conf.nb_event_ports;
+        raw.nb_event_queue_flows = conf.nb_event_queue_flows;
+        raw.nb_event_port_dequeue_depth = conf.nb_event_port_dequeue_depth;
+        raw.nb_event_port_enqueue_depth = conf.nb_event_port_enqueue_depth;
+
+        let res = unsafe { ffi::rte_event_dev_configure(self.dev_id, &raw) };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to configure event dev").to_err();
+        }
+        self.nb_ports = conf.nb_event_ports;
+        self.ports.clear();
+
+        Ok(())
+    }
+
+    pub fn queue_setup(&mut self, queue_id: u8, conf: &EventQueueConf) -> Result<()> {
+        if self.started {
+            return Error::service_err("event dev is started").to_err();
+        }
+
+        let mut raw: ffi::rte_event_queue_conf = unsafe { std::mem::zeroed() };
+        raw.nb_atomic_flows = conf.nb_atomic_flows;
+        raw.nb_atomic_order_sequences = conf.nb_atomic_order_sequences;
+        raw.schedule_type = conf.sched_type as u8;
+        raw.priority = conf.priority;
+
+        let res = unsafe { ffi::rte_event_queue_setup(self.dev_id, queue_id, &raw) };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to setup event queue").to_err();
+        }
+
+        Ok(())
+    }
+
+    /// Setup the event ports in order, starting from port 0.
+    pub fn port_setup(&mut self, port_id: u8, conf: &EventPortConf) -> Result<()> {
+        if self.started {
+            return Error::service_err("event dev is started").to_err();
+        }
+        if usize::from(port_id) != self.ports.len() || port_id >= self.nb_ports {
+            return Error::service_err("invalid event port id").to_err();
+        }
+
+        let mut raw: ffi::rte_event_port_conf = unsafe { std::mem::zeroed() };
+        raw.new_event_threshold = conf.new_event_threshold;
+        raw.dequeue_depth = conf.dequeue_depth;
+        raw.enqueue_depth = conf.enqueue_depth;
+
+        let res = unsafe { ffi::rte_event_port_setup(self.dev_id, port_id, &raw) };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to setup event port").to_err();
+        }
+        self.ports.push(EventPort {
+            dev_id: self.dev_id,
+            port_id,
+            counter: Arc::new(()),
+        });
+
+        Ok(())
+    }
+
+    /// Link the event port to the event queues with the normal priority.
+    pub fn link(&mut self, port_id: u8, queues: &[u8]) -> Result<()> {
+        if usize::from(port_id) >= self.ports.len() {
+            return Error::service_err("invalid event port id").to_err();
+        }
+
+        let res = unsafe {
+            ffi::rte_event_port_link(
+                self.dev_id,
+                port_id,
+                queues.as_ptr(),
+                std::ptr::null(),
+                queues.len() as u16,
+            )
+        };
+        if res as usize != queues.len() {
+            return Error::ffi_err(unsafe { ffi::rte_errno_() }, "fail to link event port")
+                .to_err();
+        }
+
+        Ok(())
+    }
+
+    pub fn start(&mut self) -> Result<()> {
+        if self.started {
+            return Error::service_err("event dev is started").to_err();
+        }
+        if self.ports.len() != usize::from(self.nb_ports) {
+            return Error::service_err("event ports are not setup").to_err();
+        }
+
+        let res = unsafe { ffi::rte_event_dev_start(self.dev_id) };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to start event dev").to_err();
+        }
+        self.started = true;
+
+        Ok(())
+    }
+
+    /// Stop the event device, all the event ports must not be in use.
+    pub fn stop(&mut self) -> Result<()> {
+        if !self.started {
+            return Error::service_err("event dev is not started").to_err();
+        }
+        if self.ports.iter().any(|port| port.in_use()) {
+            return Error::service_err("event port is in use").to_err();
+        }
+
+        unsafe { ffi::rte_event_dev_stop(self.dev_id) };
+        self.started = false;
+
+        Ok(())
+    }
+
+    /// Stop and close the event device.
+    pub fn close(mut self) -> Result<()> {
+        if self.started {
+            self.stop()?;
+        }
+
+        let res = unsafe { ffi::rte_event_dev_close(self.dev_id) };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to close event dev").to_err();
+        }
+
+        Ok(())
+    }
+
+    /// Get the handle of an event port.
+    ///
+    /// Each event port can only be used by a single thread.
+    pub fn port(&self, port_id: u8) -> Result<EventPort> {
+        let port = self
+            .ports
+            .get(usize::from(port_id))
+            .ok_or(Error::service_err("invalid event port id"))?;
+        port.clone_once()
+    }
+
+    /// Run a single iteration of the scheduler on the calling lcore.
+    ///
+    /// This is required by software event devices like `event_sw`, when the
+    /// scheduler is not mapped to a service core.
+    pub fn run_scheduler_once(&self) -> Result<()> {
+        let mut service_id = 0;
+        let res = unsafe { ffi::rte_event_dev_service_id_get(self.dev_id, &mut service_id) };
+        if res != 0 {
+            return Error::ffi_err(res, "event dev has no scheduler service").to_err();
+        }
+
+        unsafe {
+            ffi::rte_service_runstate_set(service_id, 1);
+            ffi::rte_service_set_runstate_mapped_check(service_id, 0);
+        }
+        let res = unsafe { ffi::rte_service_run_iter_on_app_lcore(service_id, 1) };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to run scheduler service").to_err();
+        }
+
+        Ok(())
+    }
+}
+
+pub struct EventPort {
+    dev_id: u8,
+    port_id: u8,
+    counter: Arc<()>,
+}
+
+impl EventPort {
+    #[inline]
+    pub fn port_id(&self) -> u8 {
+        self.port_id
+    }
+
+    /// Enqueue the events to the event device, the events that are not
+    /// enqueued are left in the `batch`.
+    #[inline]
+    pub fn enqueue_burst<const N: usize>(&mut self, batch: &mut ArrayVec<Event, N>) -> usize {
+        assert!(N <= usize::from(u16::MAX));
+        unsafe {
+            let events = batch.as_mut_ptr() as *mut ffi::rte_event;
+            let nb_enq = usize::from(ffi::rte_event_enqueue_burst_(
+                self.dev_id,
+                self.port_id,
+                events,
+                batch.len() as u16,
+            ));
+            let remaining = batch.len() - nb_enq;
+            std::ptr::copy(events.add(nb_enq), events, remaining);
+            batch.set_len(remaining);
+
+            nb_enq
+        }
+    }
+
+    /// Dequeue the events from the event device.
+    ///
+    /// `timeout_ticks` is ignored unless the event device is configured with
+    /// per-dequeue timeout.
+    #[inline]
+    pub fn dequeue_burst<const N: usize>(
+        &mut self,
+        batch: &mut ArrayVec<Event, N>,
+        timeout_ticks: u64,
+    ) -> usize {
+        assert!(N <= usize::from(u16::MAX));
+        unsafe {
+            let events = (batch.as_mut_ptr() as *mut ffi::rte_event).add(batch.len());
+            let nb_deq = usize::from(ffi::rte_event_dequeue_burst_(
+                self.dev_id,
+                self.port_id,
+                events,
+                (N - batch.len()) as u16,
+                timeout_ticks,
+            ));
+            batch.set_len(batch.len() + nb_deq);
+            nb_deq
+        }
+    }
+
+    fn clone_once(&self) -> Result<EventPort> {
+        if self.in_use() {
+            return Error::service_err("event port is in use").to_err();
+        }
+
+        Ok(EventPort {
+            dev_id: self.dev_id,
+            port_id: self.port_id,
+            counter: self.counter.clone(),
+        })
+    }
+
+    fn in_use(&self) -> bool {
+        Arc::strong_count(&self.counter) != 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eventdev::*;
+    use crate::*;
+
+    // This test requires the event_sw pmd.
+    #[test]
+    fn atomic_event_pipeline() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut conf = MempoolConf::default();
+            conf.nb_mbufs = 1024;
+            let mp = service().mempool_create("wtf", &conf).unwrap();
+
+            let vdev = std::ffi::CString::new("event_sw0").unwrap();
+            let res = unsafe { rpkt_dpdk_sys::rte_vdev_init(vdev.as_ptr(), std::ptr::null()) };
+            assert_eq!(res, 0);
+
+            let mut dev = EventDev::try_get("event_sw0").unwrap();
+            let info = dev.info().unwrap();
+            dev.configure(&EventDevConf::from_dev_info(&info, 1, 1))
+                .unwrap();
+            dev.queue_setup(0, &EventQueueConf::default()).unwrap();
+            dev.port_setup(0, &EventPortConf::default()).unwrap();
+            dev.link(0, &[0]).unwrap();
+            dev.start().unwrap();
+
+            let mut port = dev.port(0).unwrap();
+            assert!(dev.port(0).is_err());
+
+            let mut batch = ArrayVec::<Event, 32>::new();
+            for i in 0..32 {
+                let mut mbuf = mp.try_alloc().unwrap();
+                mbuf.extend_from_slice(&[i; 64]);
+                batch.push(Event::new(mbuf, 0, SchedType::Atomic, u32::from(i % 4)));
+            }
+            assert_eq!(port.enqueue_burst(&mut batch), 32);
+            assert_eq!(batch.len(), 0);
+
+            let mut total = 0;
+            while total < 32 {
+                dev.run_scheduler_once().unwrap();
+                total += port.dequeue_burst(&mut batch, 0);
+            }
+            for event in batch.iter() {
+                assert_eq!(event.sched_type(), SchedType::Atomic);
+                assert_eq!(event.queue_id(), 0);
+                assert_eq!(event.flow_id(), u32::from(event.mbuf().data()[0] % 4));
+            }
+
+            // free the mbufs, the atomic flows are released implicitly by the
+            // next dequeue
+            batch.clear();
+            dev.run_scheduler_once().unwrap();
+            assert_eq!(port.dequeue_burst(&mut batch, 0), 0);
+
+            drop(port);
+            dev.close().unwrap();
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+}
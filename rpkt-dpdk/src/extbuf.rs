@@ -0,0 +1,84 @@
+use std::ffi::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr::NonNull;
+
+use rpkt_dpdk_sys as ffi;
+
+// The shared info is the first field, so that DPDK and the trampoline refer
+// to the same allocation.
+#[repr(C)]
+struct Shinfo {
+    raw: ffi::rte_mbuf_ext_shared_info,
+    free_cb: Option<Box<dyn FnOnce() + Send>>,
+}
+
+/// The shared info of an external buffer, which is attached to the mbufs by
+/// `Mbuf::attach_extbuf`.
+///
+/// The shared info is reference-counted: each clone of `ExtBufShinfo` and
+/// each mbuf attached to the external buffer holds a reference. The free
+/// callback runs once when the last reference is released, i.e. after the
+/// last mbuf is detached or freed and all the `ExtBufShinfo`s are dropped.
+/// This is usually the place to hand the buffer back to its owner, e.g. an
+/// accelerator driver.
+///
+/// The callback may run on whichever lcore frees the last mbuf, e.g. the one
+/// that cleans up the tx queue. A panic in the callback aborts the process,
+/// as it can not unwind through DPDK.
+pub struct ExtBufShinfo {
+    ptr: NonNull<Shinfo>,
+}
+
+unsafe impl Send for ExtBufShinfo {}
+unsafe impl Sync for ExtBufShinfo {}
+
+impl ExtBufShinfo {
+    /// Create the shared info of an external buffer, `free_cb` is called when
+    /// the buffer is no longer used.
+    pub fn new(free_cb: impl FnOnce() + Send + 'static) -> Self {
+        let shinfo = Box::new(Shinfo {
+            raw: unsafe { std::mem::zeroed() },
+            free_cb: Some(Box::new(free_cb)),
+        });
+        let mut ptr = NonNull::from(Box::leak(shinfo));
+        unsafe {
+            let raw = &mut ptr.as_mut().raw;
+            raw.free_cb = Some(free_trampoline);
+            raw.fcb_opaque = ptr.as_ptr() as *mut c_void;
+            ffi::rte_mbuf_ext_refcnt_set_(raw, 1);
+        }
+        Self { ptr }
+    }
+
+    #[inline]
+    pub(crate) fn as_ptr(&self) -> *mut ffi::rte_mbuf_ext_shared_info {
+        self.ptr.as_ptr() as *mut ffi::rte_mbuf_ext_shared_info
+    }
+}
+
+impl Clone for ExtBufShinfo {
+    fn clone(&self) -> Self {
+        unsafe { ffi::rte_mbuf_ext_refcnt_update_(self.as_ptr(), 1) };
+        Self { ptr: self.ptr }
+    }
+}
+
+impl Drop for ExtBufShinfo {
+    fn drop(&mut self) {
+        let refcnt = unsafe { ffi::rte_mbuf_ext_refcnt_update_(self.as_ptr(), -1) };
+        if refcnt == 0 {
+            unsafe { free_trampoline(std::ptr::null_mut(), self.ptr.as_ptr() as *mut c_void) };
+        }
+    }
+}
+
+// Called by DPDK or by the last `ExtBufShinfo` when the refcount drops to 0,
+// the shared info is freed after the callback returns.
+unsafe extern "C" fn free_trampoline(_addr: *mut c_void, opaque: *mut c_void) {
+    let mut shinfo = Box::from_raw(opaque as *mut Shinfo);
+    if let Some(free_cb) = shinfo.free_cb.take() {
+        if panic::catch_unwind(AssertUnwindSafe(free_cb)).is_err() {
+            std::process::abort();
+        }
+    }
+}
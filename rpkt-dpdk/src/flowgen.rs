@@ -0,0 +1,269 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use rpkt::ipv4::IpProtocol;
+
+use crate::proto::{
+    write_ether_header, write_ipv4_header, ETHERTYPE_IPV4, ETHER_HEADER_LEN, IPV4_HEADER_LEN,
+    TCP_HEADER_LEN, UDP_HEADER_LEN,
+};
+use crate::rss::FiveTuple;
+use crate::{Mbuf, Mempool};
+
+#[derive(Clone, Debug)]
+pub struct FlowGenConf {
+    /// The number of the flows, each packet belongs to a flow picked at random.
+    pub nb_flows: u32,
+    /// The percentage of the tcp flows, the other flows are udp.
+    pub tcp_percent: u8,
+    /// The frame lengths without the crc, each packet picks one at random, so
+    /// a length listed twice is twice as frequent. The lengths are raised to
+    /// the length of the headers.
+    pub pkt_lens: Vec<u16>,
+}
+
+impl FlowGenConf {
+    pub const NB_FLOWS: u32 = 1024;
+    pub const TCP_PERCENT: u8 = 50;
+    /// The 64-byte frames.
+    pub const PKT_LEN: u16 = 60;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_nb_flows(&mut self, val: u32) {
+        self.nb_flows = val;
+    }
+
+    pub fn set_tcp_percent(&mut self, val: u8) {
+        self.tcp_percent = val;
+    }
+
+    pub fn set_pkt_lens(&mut self, val: Vec<u16>) {
+        self.pkt_lens = val;
+    }
+}
+
+impl Default for FlowGenConf {
+    fn default() -> Self {
+        Self {
+            nb_flows: Self::NB_FLOWS,
+            tcp_percent: Self::TCP_PERCENT,
+            pkt_lens: vec![Self::PKT_LEN],
+        }
+    }
+}
+
+/// A generator of ethernet frames carrying ipv4 tcp or udp packets, from a set
+/// of random flows.
+///
+/// The flows and the stream of packets are determined by the seed, so a test
+/// that generates the same stream in every run can assert on the distribution
+/// of the flows, e.g. across the queues of `rss::SoftSteering`. The packets
+/// are allocated from the mempool and have valid checksums, the iterator ends
+/// when the mempool runs out of mbufs.
+pub struct FlowGenerator {
+    mp: Mempool,
+    flows: Vec<FiveTuple>,
+    pkt_lens: Vec<u16>,
+    state: u64,
+    frame: Vec<u8>,
+    ident: u16,
+}
+
+impl FlowGenerator {
+    /// Create a generator of the flows of `conf` seeded by `seed`.
+    ///
+    /// The source addresses are in 10.0.0.0/8 and the destination addresses
+    /// are in 172.16.0.0/12, the source ports are ephemeral.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `conf` has no flow or no packet length, or if
+    /// the tcp percentage exceeds 100.
+    pub fn new(seed: u64, conf: &FlowGenConf, mp: &Mempool) -> Self {
+        assert!(
+            conf.nb_flows > 0 && !conf.pkt_lens.is_empty() && conf.tcp_percent <= 100,
+            "invalid flow generator conf"
+        );
+
+        let mut state = seed;
+        let flows = (0..conf.nb_flows)
+            .map(|_| {
+                let r = splitmix64(&mut state);
+                let src = 0x0a00_0000 | (r as u32 & 0x00ff_ffff);
+                let dst = 0xac10_0000 | ((r >> 24) as u32 & 0x000f_ffff);
+                let r = splitmix64(&mut state);
+                let protocol = if (r % 100) < u64::from(conf.tcp_percent) {
+                    IpProtocol::TCP
+                } else {
+                    IpProtocol::UDP
+                };
+                FiveTuple {
+                    src_ip: IpAddr::V4(Ipv4Addr::from(src)),
+                    dst_ip: IpAddr::V4(Ipv4Addr::from(dst)),
+                    src_port: 32768 + (r >> 8) as u16 % 28232,
+                    dst_port: (r >> 24) as u16,
+                    protocol,
+                }
+            })
+            .collect();
+
+        Self {
+            mp: mp.clone(),
+            flows,
+            pkt_lens: conf.pkt_lens.clone(),
+            state,
+            frame: Vec::new(),
+            ident: 0,
+        }
+    }
+
+    /// Return the flows, indexed by the flow id.
+    pub fn flows(&self) -> &[FiveTuple] {
+        &self.flows
+    }
+
+    /// Generate the next frame, and return it with the id of its flow.
+    ///
+    /// The frame is valid until the next call.
+    pub fn next_frame(&mut self) -> (&[u8], usize) {
+        let r = splitmix64(&mut self.state);
+        let flow_id = (r % self.flows.len() as u64) as usize;
+        let pkt_len = self.pkt_lens[(r >> 32) as usize % self.pkt_lens.len()];
+        let flow = self.flows[flow_id];
+        self.ident = self.ident.wrapping_add(1);
+
+        let l4_header_len = if flow.protocol == IpProtocol::TCP {
+            TCP_HEADER_LEN
+        } else {
+            UDP_HEADER_LEN
+        };
+        let len = usize::from(pkt_len).max(ETHER_HEADER_LEN + IPV4_HEADER_LEN + l4_header_len);
+        self.frame.clear();
+        self.frame.resize(len, 0);
+        write_headers(&mut self.frame[..], &flow, self.ident);
+
+        (&self.frame[..], flow_id)
+    }
+}
+
+impl Iterator for FlowGenerator {
+    type Item = Mbuf;
+
+    fn next(&mut self) -> Option<Mbuf> {
+        let mut mbuf = self.mp.try_alloc()?;
+        let (frame, _) = self.next_frame();
+        mbuf.extend_from_slice(frame);
+        mbuf.finalize_checksums();
+        Some(mbuf)
+    }
+}
+
+// Write the headers of `flow` to the front of the zeroed `frame`, leaving the
+// checksums to `Mbuf::finalize_checksums`.
+fn write_headers(frame: &mut [u8], flow: &FiveTuple, ident: u16) {
+    let (IpAddr::V4(src_ip), IpAddr::V4(dst_ip)) = (flow.src_ip, flow.dst_ip) else {
+        unreachable!("the flows are ipv4");
+    };
+
+    // locally administered mac addresses
+    let dst_mac = [0x02, 0, 0, 0, 0, 0x02];
+    let src_mac = [0x02, 0, 0, 0, 0, 0x01];
+    write_ether_header(frame, dst_mac, src_mac, ETHERTYPE_IPV4);
+
+    let l3 = ETHER_HEADER_LEN;
+    write_ipv4_header(&mut frame[l3..], ident, flow.protocol, src_ip, dst_ip);
+
+    let l4 = l3 + IPV4_HEADER_LEN;
+    frame[l4..l4 + 2].copy_from_slice(&flow.src_port.to_be_bytes());
+    frame[l4 + 2..l4 + 4].copy_from_slice(&flow.dst_port.to_be_bytes());
+    if flow.protocol == IpProtocol::TCP {
+        // the data offset of 5 words, the ACK and PSH flags and the window
+        frame[l4 + 12] = 0x50;
+        frame[l4 + 13] = 0x18;
+        frame[l4 + 14..l4 + 16].copy_from_slice(&0xffff_u16.to_be_bytes());
+    } else {
+        let udp_len = frame.len() - l4;
+        frame[l4 + 4..l4 + 6].copy_from_slice(&(udp_len as u16).to_be_bytes());
+    }
+}
+
+// The SplitMix64 generator, which is fast and accepts any seed.
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::{verify, ChecksumResult};
+    use crate::offload::DEFAULT_RSS_KEY_40B;
+    use crate::rss::SoftSteering;
+    use crate::*;
+
+    use std::collections::HashSet;
+
+    #[test]
+    fn reproducible_stream() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut conf = FlowGenConf::new();
+            conf.set_nb_flows(100);
+            conf.set_tcp_percent(30);
+            conf.set_pkt_lens(vec![60, 60, 60, 570, 1514]);
+            let mut fst = FlowGenerator::new(42, &conf, &mp);
+            let mut snd = FlowGenerator::new(42, &conf, &mp);
+            let mut other = FlowGenerator::new(43, &conf, &mp);
+            assert_eq!(fst.flows(), snd.flows());
+            assert!(fst.flows() != other.flows());
+            let nb_tcp = fst
+                .flows()
+                .iter()
+                .filter(|flow| flow.protocol == IpProtocol::TCP)
+                .count();
+            assert!(nb_tcp > 10 && nb_tcp < 50);
+
+            let mut tuples = HashSet::new();
+            let mut lens = HashSet::new();
+            for _ in 0..10000 {
+                let (mbuf, same) = (fst.next().unwrap(), snd.next().unwrap());
+                assert!(mbuf.data_eq(&same));
+                assert_eq!(verify(&mbuf), ChecksumResult::Ok);
+
+                let data = mbuf.data();
+                tuples.insert((data[23], data[26..38].to_vec()));
+                lens.insert(data.len());
+            }
+            // all the flows and the lengths show up
+            assert_eq!(tuples.len(), 100);
+            assert_eq!(lens, HashSet::from([60, 570, 1514]));
+
+            let (frame, flow_id) = fst.next_frame();
+            let frame = frame.to_vec();
+            assert_eq!(frame[23], u8::from(fst.flows()[flow_id].protocol));
+            assert!(other.next_frame().0 != &frame[..]);
+
+            // the flows spread across the queues of the default key
+            conf.set_nb_flows(1000);
+            let many = FlowGenerator::new(7, &conf, &mp);
+            let steering = SoftSteering::with_queues(&DEFAULT_RSS_KEY_40B, 8, 128);
+            let mut counts = [0; 8];
+            for flow in many.flows() {
+                counts[usize::from(steering.queue_for_tuple(flow))] += 1;
+            }
+            assert!(counts.iter().all(|&count| count > 80 && count < 170));
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+}
@@ -0,0 +1,200 @@
+use crate::error::*;
+use crate::proto::{
+    checksum, ethertype, fold, ipv4_is_fragment, packet_data, pseudo_header_checksum,
+    ETHERTYPE_IPV4, ETHER_HEADER_LEN, IPPROTO_TCP, IPV4_HEADER_LEN, TCP_HEADER_LEN,
+};
+use crate::{Mbuf, Mempool};
+
+const TCP_FLAG_FIN: u8 = 0x01;
+const TCP_FLAG_PSH: u8 = 0x08;
+const TCP_FLAG_CWR: u8 = 0x80;
+
+// The offsets and lengths of the headers of an ipv4 tcp frame.
+struct TcpFrame {
+    l3_offset: usize,
+    l4_offset: usize,
+    payload_offset: usize,
+    // the end of the ipv4 packet, excluding the ethernet padding
+    payload_end: usize,
+}
+
+impl TcpFrame {
+    fn parse(data: &[u8]) -> Option<Self> {
+        let l3_offset = ETHER_HEADER_LEN;
+        if data.len() < l3_offset + IPV4_HEADER_LEN
+            || ethertype(data) != Some(ETHERTYPE_IPV4)
+            || data[l3_offset] >> 4 != 4
+            || data[l3_offset + 9] != IPPROTO_TCP
+        {
+            return None;
+        }
+
+        let l3 = &data[l3_offset..];
+        let ihl = usize::from(l3[0] & 0x0f) * 4;
+        let total_len = usize::from(u16::from_be_bytes([l3[2], l3[3]]));
+        let l4_offset = l3_offset + ihl;
+        // the fragments can not be segmented
+        if ihl < IPV4_HEADER_LEN || ipv4_is_fragment(l3) || data.len() < l4_offset + TCP_HEADER_LEN
+        {
+            return None;
+        }
+
+        let doff = usize::from(data[l4_offset + 12] >> 4) * 4;
+        let payload_offset = l4_offset + doff;
+        let payload_end = l3_offset + total_len;
+        if doff < TCP_HEADER_LEN || payload_offset > payload_end || payload_end > data.len() {
+            return None;
+        }
+
+        Some(Self {
+            l3_offset,
+            l4_offset,
+            payload_offset,
+            payload_end,
+        })
+    }
+
+    // Fix up the headers of the `index`-th segment in `seg`, which carries the
+    // payload starting at `offset` of the original packet.
+    fn fix_segment(&self, seg: &mut [u8], index: usize, offset: usize, last: bool) {
+        let l3 = self.l3_offset;
+        let l4 = self.l4_offset;
+
+        // ipv4 header
+        let total_len = (seg.len() - l3) as u16;
+        seg[l3 + 2..l3 + 4].copy_from_slice(&total_len.to_be_bytes());
+        let ident = u16::from_be_bytes([seg[l3 + 4], seg[l3 + 5]]).wrapping_add(index as u16);
+        seg[l3 + 4..l3 + 6].copy_from_slice(&ident.to_be_bytes());
+        seg[l3 + 10..l3 + 12].fill(0);
+        let cksum = !fold(checksum(&seg[l3..l4], 0));
+        seg[l3 + 10..l3 + 12].copy_from_slice(&cksum.to_be_bytes());
+
+        // tcp header
+        let seq = u32::from_be_bytes([seg[l4 + 4], seg[l4 + 5], seg[l4 + 6], seg[l4 + 7]])
+            .wrapping_add(offset as u32);
+        seg[l4 + 4..l4 + 8].copy_from_slice(&seq.to_be_bytes());
+        // FIN and PSH are only kept on the last segment, CWR on the first one
+        if !last {
+            seg[l4 + 13] &= !(TCP_FLAG_FIN | TCP_FLAG_PSH);
+        }
+        if index > 0 {
+            seg[l4 + 13] &= !TCP_FLAG_CWR;
+        }
+        seg[l4 + 16..l4 + 18].fill(0);
+
+        let accum = pseudo_header_checksum(&seg[l3..l4], IPPROTO_TCP, seg.len() - l4);
+        let cksum = !fold(checksum(&seg[l4..], accum));
+        seg[l4 + 16..l4 + 18].copy_from_slice(&cksum.to_be_bytes());
+    }
+}
+
+/// Split an ipv4 tcp packet into segments with at most `mss` bytes of payload.
+///
+/// This is the software fallback for ports without the tso offload. Each
+/// segment is allocated from `pool` and carries a copy of the headers, with the
+/// sequence number, the ipv4 identification, the length fields and the
+/// checksums fixed up. The FIN and PSH flags are only set on the last segment.
+///
+/// A packet whose payload already fits in `mss` is copied into a single
+/// segment. The original `mbuf` is left untouched.
+pub fn segment_tcp(mbuf: &Mbuf, mss: u16, pool: &Mempool) -> Result<Vec<Mbuf>> {
+    if mss == 0 {
+        return Error::service_err("invalid gso mss").to_err();
+    }
+
+    let data = packet_data(mbuf);
+    let frame = TcpFrame::parse(&data[..])
+        .ok_or(Error::service_err("gso packet is not an ipv4 tcp packet"))?;
+    let headers = &data[..frame.payload_offset];
+    let payload = &data[frame.payload_offset..frame.payload_end];
+
+    let nb_segs = std::cmp::max(1, (payload.len() + usize::from(mss) - 1) / usize::from(mss));
+    let mut segs = Vec::with_capacity(nb_segs);
+    for index in 0..nb_segs {
+        let offset = index * usize::from(mss);
+        let chunk = &payload[offset..std::cmp::min(offset + usize::from(mss), payload.len())];
+
+        let mut seg = pool
+            .try_alloc()
+            .ok_or(Error::service_err("fail to allocate gso segment"))?;
+        if seg.capacity() < headers.len() + chunk.len() {
+            return Error::service_err("gso segment exceeds the mbuf capacity").to_err();
+        }
+        seg.extend_from_slice(headers);
+        seg.extend_from_slice(chunk);
+        frame.fix_segment(seg.data_mut(), index, offset, index + 1 == nb_segs);
+
+        segs.push(seg);
+    }
+
+    Ok(segs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+    use rpkt::ether::{EtherPacket, ETHER_HEADER_TEMPLATE};
+    use rpkt::ipv4::{IpProtocol, Ipv4Packet, IPV4_HEADER_LEN, IPV4_HEADER_TEMPLATE};
+    use rpkt::tcp::{TcpPacket, TCP_HEADER_LEN, TCP_HEADER_TEMPLATE};
+    use rpkt::{Buf, Cursor, CursorMut};
+
+    #[test]
+    fn segment_4k_payload() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            config.dataroom = 8192;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let payload: Vec<u8> = (0..4096).map(|i| i as u8).collect();
+            let headers_len = ETHER_HEADER_LEN + IPV4_HEADER_LEN + TCP_HEADER_LEN;
+            let mut bytes = vec![0; headers_len + payload.len()];
+            bytes[headers_len..].copy_from_slice(&payload[..]);
+
+            let mut buf = CursorMut::new(&mut bytes[..]);
+            buf.advance(headers_len);
+            let mut tcppkt = TcpPacket::prepend_header(buf, &TCP_HEADER_TEMPLATE);
+            tcppkt.set_seq_number(0xfffff000);
+            tcppkt.set_psh(true);
+            tcppkt.set_fin(true);
+            let mut ipheader = IPV4_HEADER_TEMPLATE;
+            ipheader.set_protocol(IpProtocol::TCP);
+            ipheader.set_ident(0xfffe);
+            let mut ippkt = Ipv4Packet::prepend_header(tcppkt.release(), &ipheader);
+            ippkt.adjust_checksum();
+            EtherPacket::prepend_header(ippkt.release(), &ETHER_HEADER_TEMPLATE);
+
+            let mut mbuf = mp.try_alloc().unwrap();
+            mbuf.extend_from_slice(&bytes[..]);
+
+            let segs = segment_tcp(&mbuf, 1460, &mp).unwrap();
+            assert_eq!(segs.len(), 3);
+
+            let mut reassembled = Vec::new();
+            for (i, seg) in segs.iter().enumerate() {
+                let ethpkt = EtherPacket::parse(Cursor::new(seg.data())).unwrap();
+                let ippkt = Ipv4Packet::parse(ethpkt.payload()).unwrap();
+                assert!(ippkt.verify_checksum());
+                assert_eq!(ippkt.ident(), 0xfffe_u16.wrapping_add(i as u16));
+
+                let (src_ip, dst_ip) = (ippkt.source_ip(), ippkt.dest_ip());
+                let mut tcppkt = TcpPacket::parse(ippkt.payload()).unwrap();
+                assert!(tcppkt.verify_ipv4_checksum(src_ip, dst_ip));
+                let seq = 0xfffff000_u32.wrapping_add(reassembled.len() as u32);
+                assert_eq!(tcppkt.seq_number(), seq);
+                assert_eq!(tcppkt.fin(), i == 2);
+                assert_eq!(tcppkt.psh(), i == 2);
+
+                let chunk = tcppkt.payload();
+                assert_eq!(chunk.chunk().len(), if i < 2 { 1460 } else { 1176 });
+                reassembled.extend_from_slice(chunk.chunk());
+            }
+            assert_eq!(reassembled, payload);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+}
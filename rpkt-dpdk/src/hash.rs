@@ -0,0 +1,264 @@
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::os::raw::{c_char, c_void};
+use std::ptr::NonNull;
+
+use rpkt_dpdk_sys as ffi;
+
+use crate::error::*;
+use crate::names;
+
+#[derive(Clone, Copy, Debug)]
+pub struct HashConf {
+    /// The maximum number of the keys.
+    pub entries: u32,
+    pub socket_id: u32,
+}
+
+impl HashConf {
+    pub const ENTRIES: u32 = 1024;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_entries(&mut self, val: u32) {
+        self.entries = val;
+    }
+
+    pub fn set_socket_id(&mut self, val: u32) {
+        self.socket_id = val;
+    }
+}
+
+impl Default for HashConf {
+    fn default() -> Self {
+        Self {
+            entries: Self::ENTRIES,
+            socket_id: 0,
+        }
+    }
+}
+
+/// A fixed-size key of `Hash`, which is hashed and compared bytewise.
+///
+/// # Safety:
+/// Every byte of the key must be determined by its value, i.e. the type has no
+/// padding bytes and two equal keys have the same bytes. A `#[repr(C)]` struct
+/// of byte arrays with an explicit, zeroed padding field qualifies.
+pub unsafe trait HashKey: Copy {}
+
+macro_rules! impl_hash_key {
+    ($($ty:ty),*) => {
+        $(unsafe impl HashKey for $ty {})*
+    };
+}
+
+impl_hash_key!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+unsafe impl<T: HashKey, const N: usize> HashKey for [T; N] {}
+
+/// A hash table of fixed-size `K` keys, backed by `rte_hash`.
+///
+/// The keys are stored in the hugepage memory and compared bytewise, see
+/// `HashKey`. `rte_hash` assigns a position to each
+/// key, and the values are stored in a table indexed by the positions.
+///
+/// The table is not safe for concurrent writers, so the updates take
+/// `&mut self`.
+pub struct Hash<K, V> {
+    ptr: NonNull<ffi::rte_hash>,
+    values: Vec<Option<V>>,
+    _marker: PhantomData<K>,
+}
+
+unsafe impl<K: Send, V: Send> Send for Hash<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for Hash<K, V> {}
+
+impl<K: HashKey, V> Hash<K, V> {
+    const KEY_LEN: u32 = std::mem::size_of::<K>() as u32;
+
+    /// Create a new hash table named `name`.
+    pub fn try_create(name: String, conf: &HashConf) -> Result<Self> {
+        let err = Error::service_err("invalid hash config");
+        if Self::KEY_LEN == 0 {
+            return err.to_err();
+        }
+        let socket_id = i32::try_from(conf.socket_id).map_err(|_| err)?;
+
+        names::check_len(&name, names::HASH_NAMESIZE, "hash name is too long")?;
+        let cname = CString::new(name).map_err(|_| Error::service_err("invalid hash name"))?;
+
+        let raw = unsafe {
+            let mut params: ffi::rte_hash_parameters = std::mem::zeroed();
+            params.name = cname.as_bytes_with_nul().as_ptr() as *const c_char;
+            params.entries = conf.entries;
+            params.key_len = Self::KEY_LEN;
+            // the default hash function is used when `hash_func` is not set
+            params.socket_id = socket_id;
+            ffi::rte_hash_create(&params)
+        };
+
+        let ptr = check_ptr(raw, "fail to create hash")?;
+        Ok(Self {
+            ptr,
+            values: Vec::new(),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Insert `val` for `key`, return the replaced value of an existing key.
+    ///
+    /// This function fails if the table is full.
+    pub fn insert(&mut self, key: &K, val: V) -> Result<Option<V>> {
+        let res = unsafe { ffi::rte_hash_add_key(self.ptr.as_ptr(), Self::key_ptr(key)) };
+        let pos = check_ret(res, "fail to add hash key")? as usize;
+        if pos >= self.values.len() {
+            self.values.resize_with(pos + 1, || None);
+        }
+        Ok(self.values[pos].replace(val))
+    }
+
+    #[inline]
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let pos = self.lookup(key)?;
+        self.values[pos].as_ref()
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let pos = self.lookup(key)?;
+        self.values[pos].as_mut()
+    }
+
+    /// Remove `key` from the table, return its value.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let res = unsafe { ffi::rte_hash_del_key(self.ptr.as_ptr(), Self::key_ptr(key)) };
+        if res < 0 {
+            return None;
+        }
+        self.values[res as usize].take()
+    }
+
+    /// Keep the entries for which `f` returns true, return the number of the
+    /// removed entries.
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) -> usize {
+        // The keys are removed after the iteration, which is not stable while
+        // the table is modified.
+        let removed: Vec<K> = self
+            .positions()
+            .filter_map(|(key, pos)| {
+                let val = self.values[pos].as_mut().unwrap();
+                (!f(&key, val)).then_some(key)
+            })
+            .collect();
+
+        for key in removed.iter() {
+            self.remove(key);
+        }
+        removed.len()
+    }
+
+    /// Iterate over the entries of the table in an arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (K, &V)> + '_ {
+        self.positions()
+            .map(|(key, pos)| (key, self.values[pos].as_ref().unwrap()))
+    }
+
+    /// Return the number of the keys in the table.
+    pub fn len(&self) -> usize {
+        let res = unsafe { ffi::rte_hash_count(self.ptr.as_ptr()) };
+        usize::try_from(res).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn as_ptr(&self) -> *const ffi::rte_hash {
+        self.ptr.as_ptr()
+    }
+
+    #[inline]
+    fn lookup(&self, key: &K) -> Option<usize> {
+        let res = unsafe { ffi::rte_hash_lookup(self.ptr.as_ptr(), Self::key_ptr(key)) };
+        usize::try_from(res).ok()
+    }
+
+    // Iterate over the keys and their positions with `rte_hash_iterate`. The
+    // returned iterator does not borrow the table.
+    fn positions(&self) -> impl Iterator<Item = (K, usize)> {
+        let ptr = self.ptr;
+        let mut next = 0;
+        std::iter::from_fn(move || {
+            let mut key: *const c_void = std::ptr::null();
+            let mut data: *mut c_void = std::ptr::null_mut();
+            let res =
+                unsafe { ffi::rte_hash_iterate(ptr.as_ptr(), &mut key, &mut data, &mut next) };
+            let pos = usize::try_from(res).ok()?;
+            Some((unsafe { (key as *const K).read_unaligned() }, pos))
+        })
+    }
+
+    #[inline]
+    fn key_ptr(key: &K) -> *const c_void {
+        key as *const K as *const c_void
+    }
+}
+
+impl<K, V> Drop for Hash<K, V> {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::rte_hash_free(self.ptr.as_ptr());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn insert_lookup_and_remove() {
+        DpdkOption::new().init().unwrap();
+
+        let mut conf = HashConf::new();
+        conf.set_entries(64);
+        let mut hash = Hash::<[u8; 13], u64>::try_create("flows".to_string(), &conf).unwrap();
+        assert!(hash.is_empty());
+
+        for i in 0..32_u8 {
+            assert_eq!(hash.insert(&[i; 13], u64::from(i)).unwrap(), None);
+        }
+        assert_eq!(hash.len(), 32);
+        assert_eq!(hash.insert(&[3; 13], 300).unwrap(), Some(3));
+        assert_eq!(hash.get(&[3; 13]), Some(&300));
+        *hash.get_mut(&[4; 13]).unwrap() += 1;
+        assert_eq!(hash.get(&[4; 13]), Some(&5));
+        assert_eq!(hash.get(&[100; 13]), None);
+
+        assert_eq!(hash.remove(&[5; 13]), Some(5));
+        assert_eq!(hash.remove(&[5; 13]), None);
+        assert_eq!(hash.len(), 31);
+        assert_eq!(hash.iter().count(), 31);
+        assert!(hash.iter().all(|(key, val)| *val == u64::from(key[0])
+            || (key[0] == 3 && *val == 300)
+            || (key[0] == 4 && *val == 5)));
+
+        // keep the even keys
+        assert_eq!(hash.retain(|key, _| key[0] % 2 == 0), 15);
+        assert_eq!(hash.len(), 16);
+        assert!(hash.iter().all(|(key, _)| key[0] % 2 == 0));
+
+        // the name is taken by the existing table
+        assert!(Hash::<u64, u64>::try_create("flows".to_string(), &conf).is_err());
+
+        // the names that do not fit are rejected instead of being truncated
+        let name = "h".repeat(names::HASH_NAMESIZE);
+        let err = Hash::<u64, u64>::try_create(name, &conf).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::ServiceError);
+        let name = "h".repeat(names::HASH_NAMESIZE - 1);
+        assert!(Hash::<u64, u64>::try_create(name, &conf).is_ok());
+    }
+}
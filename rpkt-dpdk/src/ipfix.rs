@@ -0,0 +1,534 @@
+use std::collections::VecDeque;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rpkt::flow::to_ipv6_octets;
+use rpkt::ipv4::IpProtocol;
+
+use crate::proto::{
+    write_ether_header, write_ipv4_header, ETHERTYPE_IPV4, ETHER_HEADER_LEN, IPV4_HEADER_LEN,
+    UDP_HEADER_LEN,
+};
+use crate::rss::FiveTuple;
+use crate::{Mbuf, Mempool};
+
+const IPFIX_VERSION: u16 = 10;
+const MESSAGE_HEADER_LEN: usize = 16;
+const SET_HEADER_LEN: usize = 4;
+const TEMPLATE_SET_ID: u16 = 2;
+// the frame offset of the ipfix message
+const MESSAGE_OFFSET: usize = ETHER_HEADER_LEN + IPV4_HEADER_LEN + UDP_HEADER_LEN;
+
+// The information elements of the data records as (id, length), see
+// https://www.iana.org/assignments/ipfix/ipfix.xhtml. Only the addresses
+// differ between the two templates.
+const IPV4_FIELDS: [(u16, u16); 9] = [
+    // sourceIPv4Address, destinationIPv4Address
+    (8, 4),
+    (12, 4),
+    // protocolIdentifier, sourceTransportPort, destinationTransportPort
+    (4, 1),
+    (7, 2),
+    (11, 2),
+    // packetDeltaCount, octetDeltaCount
+    (2, 8),
+    (1, 8),
+    // flowStartMilliseconds, flowEndMilliseconds
+    (152, 8),
+    (153, 8),
+];
+const IPV6_FIELDS: [(u16, u16); 9] = [
+    // sourceIPv6Address, destinationIPv6Address
+    (27, 16),
+    (28, 16),
+    (4, 1),
+    (7, 2),
+    (11, 2),
+    (2, 8),
+    (1, 8),
+    (152, 8),
+    (153, 8),
+];
+const IPV4_RECORD_LEN: usize = 45;
+const IPV6_RECORD_LEN: usize = 69;
+// the set header and the two template records
+const TEMPLATE_SET_LEN: usize = SET_HEADER_LEN + 2 * (4 + 4 * IPV4_FIELDS.len());
+
+/// A flow exported by `IpfixExporter`, e.g. an entry of a `Hash` of the flows
+/// that is expired.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FlowRecord {
+    pub tuple: FiveTuple,
+    /// The number of the packets since the flow was last exported.
+    pub nb_pkts: u64,
+    /// The number of the ip bytes since the flow was last exported.
+    pub nb_bytes: u64,
+    /// The time of the first packet, in milliseconds since the unix epoch.
+    pub start_ms: u64,
+    /// The time of the last packet, in milliseconds since the unix epoch.
+    pub end_ms: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct IpfixConf {
+    /// The observation domain id of the messages.
+    pub domain_id: u32,
+    /// The id of the ipv4 template, the ipv6 template takes the next id.
+    pub template_id: u16,
+    /// The number of the messages between two messages that carry the
+    /// templates.
+    pub template_refresh: u32,
+    /// The maximum length of the ip packet of a message.
+    pub mtu: u16,
+    pub src_mac: [u8; 6],
+    pub dst_mac: [u8; 6],
+    pub src_ip: Ipv4Addr,
+    /// The address of the collector.
+    pub dst_ip: Ipv4Addr,
+    pub src_port: u16,
+    pub dst_port: u16,
+}
+
+impl IpfixConf {
+    pub const DOMAIN_ID: u32 = 0;
+    /// The first id of the templates, the lower ids are reserved for the sets.
+    pub const TEMPLATE_ID: u16 = 256;
+    pub const TEMPLATE_REFRESH: u32 = 32;
+    pub const MTU: u16 = 1500;
+    /// The udp port assigned to ipfix.
+    pub const PORT: u16 = 4739;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_domain_id(&mut self, val: u32) {
+        self.domain_id = val;
+    }
+
+    pub fn set_template_id(&mut self, val: u16) {
+        self.template_id = val;
+    }
+
+    pub fn set_template_refresh(&mut self, val: u32) {
+        self.template_refresh = val;
+    }
+
+    pub fn set_mtu(&mut self, val: u16) {
+        self.mtu = val;
+    }
+
+    pub fn set_src_mac(&mut self, val: [u8; 6]) {
+        self.src_mac = val;
+    }
+
+    pub fn set_dst_mac(&mut self, val: [u8; 6]) {
+        self.dst_mac = val;
+    }
+
+    pub fn set_src_ip(&mut self, val: Ipv4Addr) {
+        self.src_ip = val;
+    }
+
+    pub fn set_dst_ip(&mut self, val: Ipv4Addr) {
+        self.dst_ip = val;
+    }
+
+    pub fn set_src_port(&mut self, val: u16) {
+        self.src_port = val;
+    }
+
+    pub fn set_dst_port(&mut self, val: u16) {
+        self.dst_port = val;
+    }
+}
+
+impl Default for IpfixConf {
+    fn default() -> Self {
+        Self {
+            domain_id: Self::DOMAIN_ID,
+            template_id: Self::TEMPLATE_ID,
+            template_refresh: Self::TEMPLATE_REFRESH,
+            mtu: Self::MTU,
+            src_mac: [0; 6],
+            dst_mac: [0; 6],
+            src_ip: Ipv4Addr::UNSPECIFIED,
+            dst_ip: Ipv4Addr::UNSPECIFIED,
+            src_port: Self::PORT,
+            dst_port: Self::PORT,
+        }
+    }
+}
+
+/// An exporter of the flow records to an ipfix collector over udp, see rfc
+/// 7011.
+///
+/// The records are queued by `add_flow`, and `flush` packs them into ipfix
+/// messages with one data set per ip version, each message in an ipv4 udp
+/// packet that is ready for the transmit. The ipv4 flows use the template
+/// `template_id` and the other flows use the next one, with the ipv4
+/// addresses of a mixed flow mapped to ipv6.
+///
+/// The collector can only decode a data set after it has received its
+/// template, and an udp collector may miss a template or restart, so the
+/// first message carries the two templates, and so does every
+/// `template_refresh`-th message after it.
+pub struct IpfixExporter {
+    conf: IpfixConf,
+    mp: Mempool,
+    ipv4_flows: VecDeque<FlowRecord>,
+    ipv6_flows: VecDeque<FlowRecord>,
+    // the number of the exported data records, which is the sequence number
+    // of the next message
+    nb_exported: u32,
+    // the number of the messages since the templates are sent, `None` before
+    // the first message
+    since_template: Option<u32>,
+    ident: u16,
+    frame: Vec<u8>,
+}
+
+impl IpfixExporter {
+    /// Create an exporter that allocates its packets from `mp`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `conf.template_id` is below 256 or is the last
+    /// id, if `conf.template_refresh` is 0, or if `conf.mtu` is below the
+    /// minimum ipv4 mtu of 576.
+    pub fn new(conf: &IpfixConf, mp: &Mempool) -> Self {
+        assert!(
+            conf.template_id >= IpfixConf::TEMPLATE_ID
+                && conf.template_id < u16::MAX
+                && conf.template_refresh > 0
+                && conf.mtu >= 576,
+            "invalid ipfix conf"
+        );
+        Self {
+            conf: conf.clone(),
+            mp: mp.clone(),
+            ipv4_flows: VecDeque::new(),
+            ipv6_flows: VecDeque::new(),
+            nb_exported: 0,
+            since_template: None,
+            ident: 0,
+            frame: Vec::new(),
+        }
+    }
+
+    /// Queue `record` for the next `flush`.
+    pub fn add_flow(&mut self, record: FlowRecord) {
+        match (record.tuple.src_ip, record.tuple.dst_ip) {
+            (IpAddr::V4(_), IpAddr::V4(_)) => self.ipv4_flows.push_back(record),
+            _ => self.ipv6_flows.push_back(record),
+        }
+    }
+
+    /// Return the number of the queued records.
+    pub fn nb_pending(&self) -> usize {
+        self.ipv4_flows.len() + self.ipv6_flows.len()
+    }
+
+    /// Pack the queued records into ipfix messages, and return their packets
+    /// with valid checksums.
+    ///
+    /// The records are exported in the order they are queued for each ip
+    /// version. If the mempool runs out of mbufs, the records that are not
+    /// packed are left queued for the next flush.
+    pub fn flush(&mut self) -> Vec<Mbuf> {
+        let export_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as u32;
+
+        let mut mbufs = Vec::new();
+        while self.nb_pending() > 0 {
+            let Some(mut mbuf) = self.mp.try_alloc() else {
+                break;
+            };
+            let room = mbuf
+                .capacity()
+                .min(ETHER_HEADER_LEN + usize::from(self.conf.mtu));
+            self.build_message(export_time, room);
+            mbuf.extend_from_slice(&self.frame[..]);
+            mbuf.finalize_checksums();
+            mbufs.push(mbuf);
+        }
+        mbufs
+    }
+
+    // Pack the queued records into a message of at most `room` bytes, in the
+    // frame buffer.
+    fn build_message(&mut self, export_time: u32, room: usize) {
+        let frame = &mut self.frame;
+        frame.clear();
+        frame.resize(MESSAGE_OFFSET + MESSAGE_HEADER_LEN, 0);
+
+        let with_template = !self
+            .since_template
+            .is_some_and(|n| n < self.conf.template_refresh);
+        if with_template {
+            put_u16(frame, TEMPLATE_SET_ID);
+            put_u16(frame, TEMPLATE_SET_LEN as u16);
+            for (template_id, fields) in [
+                (self.conf.template_id, &IPV4_FIELDS),
+                (self.conf.template_id + 1, &IPV6_FIELDS),
+            ] {
+                put_u16(frame, template_id);
+                put_u16(frame, fields.len() as u16);
+                for (id, len) in fields.iter() {
+                    put_u16(frame, *id);
+                    put_u16(frame, *len);
+                }
+            }
+            self.since_template = Some(0);
+        }
+        self.since_template = self.since_template.map(|n| n + 1);
+
+        let seq = self.nb_exported;
+        for (flows, template_id, record_len) in [
+            (&mut self.ipv4_flows, self.conf.template_id, IPV4_RECORD_LEN),
+            (
+                &mut self.ipv6_flows,
+                self.conf.template_id + 1,
+                IPV6_RECORD_LEN,
+            ),
+        ] {
+            let avail = room.saturating_sub(frame.len() + SET_HEADER_LEN);
+            let nb_records = flows.len().min(avail / record_len);
+            if nb_records == 0 {
+                continue;
+            }
+            put_u16(frame, template_id);
+            put_u16(frame, (SET_HEADER_LEN + nb_records * record_len) as u16);
+            for record in flows.drain(..nb_records) {
+                put_record(frame, &record);
+            }
+            self.nb_exported = self.nb_exported.wrapping_add(nb_records as u32);
+        }
+
+        // the message header
+        let message = &mut frame[MESSAGE_OFFSET..];
+        let message_len = message.len() as u16;
+        message[0..2].copy_from_slice(&IPFIX_VERSION.to_be_bytes());
+        message[2..4].copy_from_slice(&message_len.to_be_bytes());
+        message[4..8].copy_from_slice(&export_time.to_be_bytes());
+        message[8..12].copy_from_slice(&seq.to_be_bytes());
+        message[12..16].copy_from_slice(&self.conf.domain_id.to_be_bytes());
+
+        // the udp and ipv4 headers, leaving the checksums to
+        // `Mbuf::finalize_checksums`
+        let conf = &self.conf;
+        write_ether_header(frame, conf.dst_mac, conf.src_mac, ETHERTYPE_IPV4);
+
+        let l3 = ETHER_HEADER_LEN;
+        self.ident = self.ident.wrapping_add(1);
+        write_ipv4_header(
+            &mut frame[l3..],
+            self.ident,
+            IpProtocol::UDP,
+            conf.src_ip,
+            conf.dst_ip,
+        );
+
+        let l4 = l3 + IPV4_HEADER_LEN;
+        let udp_len = (frame.len() - l4) as u16;
+        frame[l4..l4 + 2].copy_from_slice(&conf.src_port.to_be_bytes());
+        frame[l4 + 2..l4 + 4].copy_from_slice(&conf.dst_port.to_be_bytes());
+        frame[l4 + 4..l4 + 6].copy_from_slice(&udp_len.to_be_bytes());
+    }
+}
+
+#[inline]
+fn put_u16(frame: &mut Vec<u8>, val: u16) {
+    frame.extend_from_slice(&val.to_be_bytes());
+}
+
+// Append the data record of `record` in the field order of its template.
+fn put_record(frame: &mut Vec<u8>, record: &FlowRecord) {
+    let tuple = &record.tuple;
+    match (tuple.src_ip, tuple.dst_ip) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            frame.extend_from_slice(&src.octets());
+            frame.extend_from_slice(&dst.octets());
+        }
+        (src, dst) => {
+            frame.extend_from_slice(&to_ipv6_octets(src));
+            frame.extend_from_slice(&to_ipv6_octets(dst));
+        }
+    }
+    frame.push(tuple.protocol.into());
+    put_u16(frame, tuple.src_port);
+    put_u16(frame, tuple.dst_port);
+    for val in [
+        record.nb_pkts,
+        record.nb_bytes,
+        record.start_ms,
+        record.end_ms,
+    ] {
+        frame.extend_from_slice(&val.to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::{verify, ChecksumResult};
+    use crate::*;
+
+    fn u16_at(data: &[u8], offset: usize) -> u16 {
+        u16::from_be_bytes([data[offset], data[offset + 1]])
+    }
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn record(src_ip: IpAddr, idx: u16) -> FlowRecord {
+        FlowRecord {
+            tuple: FiveTuple {
+                src_ip,
+                dst_ip: if src_ip.is_ipv4() {
+                    IpAddr::V4(Ipv4Addr::new(172, 16, 0, 1))
+                } else {
+                    "2001:db8::1".parse().unwrap()
+                },
+                src_port: 32768 + idx,
+                dst_port: 443,
+                protocol: IpProtocol::TCP,
+            },
+            nb_pkts: u64::from(idx) + 1,
+            nb_bytes: 1500 * (u64::from(idx) + 1),
+            start_ms: 1_700_000_000_000,
+            end_ms: 1_700_000_000_000 + u64::from(idx),
+        }
+    }
+
+    // Check the headers of the exported `mbuf`, and return its ipfix message.
+    fn message(mbuf: &Mbuf, conf: &IpfixConf) -> Vec<u8> {
+        let data = mbuf.data();
+        assert_eq!(&data[0..6], &conf.dst_mac[..]);
+        assert_eq!(u16_at(data, 12), ETHERTYPE_IPV4);
+        assert_eq!(data[23], u8::from(IpProtocol::UDP));
+        assert_eq!(&data[30..34], &conf.dst_ip.octets()[..]);
+        assert_eq!(u16_at(data, 36), IpfixConf::PORT);
+        assert_eq!(usize::from(u16_at(data, 16)), data.len() - ETHER_HEADER_LEN);
+        assert_eq!(usize::from(u16_at(data, 38)), data.len() - 34);
+        assert_eq!(verify(mbuf), ChecksumResult::Ok);
+        assert!(data.len() <= ETHER_HEADER_LEN + usize::from(conf.mtu));
+
+        let message = &data[MESSAGE_OFFSET..];
+        assert_eq!(u16_at(message, 0), IPFIX_VERSION);
+        assert_eq!(usize::from(u16_at(message, 2)), message.len());
+        assert!(u32_at(message, 4) > 1_600_000_000);
+        assert_eq!(u32_at(message, 12), 7);
+        message.to_vec()
+    }
+
+    // Return the (set id, set) of the sets of `message`.
+    fn sets(message: &[u8]) -> Vec<(u16, Vec<u8>)> {
+        let mut sets = Vec::new();
+        let mut offset = MESSAGE_HEADER_LEN;
+        while offset < message.len() {
+            let set_len = usize::from(u16_at(message, offset + 2));
+            sets.push((
+                u16_at(message, offset),
+                message[offset + SET_HEADER_LEN..offset + set_len].to_vec(),
+            ));
+            offset += set_len;
+        }
+        assert_eq!(offset, message.len());
+        sets
+    }
+
+    #[test]
+    fn export_flows() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut conf = IpfixConf::new();
+            conf.set_domain_id(7);
+            conf.set_template_refresh(2);
+            conf.set_src_mac([0x02, 0, 0, 0, 0, 0x01]);
+            conf.set_dst_mac([0x02, 0, 0, 0, 0, 0x02]);
+            conf.set_src_ip(Ipv4Addr::new(10, 0, 0, 1));
+            conf.set_dst_ip(Ipv4Addr::new(10, 0, 0, 2));
+            let mut exporter = IpfixExporter::new(&conf, &mp);
+
+            for idx in 0..40 {
+                let src_ip = IpAddr::V4(Ipv4Addr::new(10, 1, 0, idx as u8));
+                exporter.add_flow(record(src_ip, idx));
+            }
+            for idx in 0..3 {
+                exporter.add_flow(record("2001:db8::2".parse().unwrap(), idx));
+            }
+            assert_eq!(exporter.nb_pending(), 43);
+
+            let mbufs = exporter.flush();
+            assert_eq!(mbufs.len(), 2);
+            assert_eq!(exporter.nb_pending(), 0);
+
+            // the first message carries the templates and 30 ipv4 records
+            let message0 = message(&mbufs[0], &conf);
+            assert_eq!(u32_at(&message0, 8), 0);
+            let sets0 = sets(&message0);
+            assert_eq!(sets0.len(), 2);
+            let (set_id, templates) = &sets0[0];
+            assert_eq!(*set_id, TEMPLATE_SET_ID);
+            assert_eq!(templates.len(), TEMPLATE_SET_LEN - SET_HEADER_LEN);
+            for (idx, fields) in [&IPV4_FIELDS, &IPV6_FIELDS].iter().enumerate() {
+                let template = &templates[idx * 40..(idx + 1) * 40];
+                assert_eq!(u16_at(template, 0), 256 + idx as u16);
+                assert_eq!(u16_at(template, 2), 9);
+                for (field, (id, len)) in fields.iter().enumerate() {
+                    assert_eq!(u16_at(template, 4 + field * 4), *id);
+                    assert_eq!(u16_at(template, 6 + field * 4), *len);
+                }
+                let record_len: u16 = fields.iter().map(|(_, len)| len).sum();
+                assert_eq!(usize::from(record_len), [45, 69][idx]);
+            }
+            let (set_id, records) = &sets0[1];
+            assert_eq!(*set_id, 256);
+            assert_eq!(records.len(), 30 * IPV4_RECORD_LEN);
+            let first = &records[..IPV4_RECORD_LEN];
+            assert_eq!(&first[0..8], &[10, 1, 0, 0, 172, 16, 0, 1][..]);
+            assert_eq!(first[8], 6);
+            assert_eq!(u16_at(first, 9), 32768);
+            assert_eq!(u16_at(first, 11), 443);
+            assert_eq!(&first[13..21], &1_u64.to_be_bytes()[..]);
+            assert_eq!(&first[21..29], &1500_u64.to_be_bytes()[..]);
+            assert_eq!(&first[29..37], &1_700_000_000_000_u64.to_be_bytes()[..]);
+            let last = &records[29 * IPV4_RECORD_LEN..];
+            assert_eq!(&last[0..4], &[10, 1, 0, 29][..]);
+
+            // the second message carries the rest, without the templates
+            let message1 = message(&mbufs[1], &conf);
+            assert_eq!(u32_at(&message1, 8), 30);
+            let sets1 = sets(&message1);
+            assert_eq!(sets1.len(), 2);
+            assert_eq!(sets1[0].0, 256);
+            assert_eq!(sets1[0].1.len(), 10 * IPV4_RECORD_LEN);
+            assert_eq!(sets1[1].0, 257);
+            assert_eq!(sets1[1].1.len(), 3 * IPV6_RECORD_LEN);
+            let first = &sets1[1].1[..IPV6_RECORD_LEN];
+            let src: std::net::Ipv6Addr = "2001:db8::2".parse().unwrap();
+            assert_eq!(&first[..16], &src.octets()[..]);
+
+            // the templates are resent in every second message
+            exporter.add_flow(record(IpAddr::V4(Ipv4Addr::new(10, 2, 0, 1)), 0));
+            let mbufs = exporter.flush();
+            let message2 = message(&mbufs[0], &conf);
+            assert_eq!(u32_at(&message2, 8), 43);
+            let sets2 = sets(&message2);
+            assert_eq!(sets2[0].0, TEMPLATE_SET_ID);
+            assert_eq!(sets2[1].1.len(), IPV4_RECORD_LEN);
+            assert!(exporter.flush().is_empty());
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+}
@@ -0,0 +1,218 @@
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+use rpkt_dpdk_sys as ffi;
+
+use crate::error::*;
+use crate::Mbuf;
+
+// The values below `SUB_BUCKETS` are recorded exactly, the larger values are
+// recorded with a relative error below 1 / (SUB_BUCKETS / 2).
+const SUB_BUCKET_BITS: u32 = 7;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+const HALF_SUB_BUCKETS: usize = SUB_BUCKETS / 2;
+const NB_BUCKETS: usize = SUB_BUCKETS + (64 - SUB_BUCKET_BITS as usize) * HALF_SUB_BUCKETS;
+
+// A log-linear histogram in the style of HdrHistogram.
+struct Histogram {
+    counts: Vec<u64>,
+    total: u64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            counts: vec![0; NB_BUCKETS],
+            total: 0,
+        }
+    }
+
+    fn index(value: u64) -> usize {
+        if value < SUB_BUCKETS as u64 {
+            return value as usize;
+        }
+        // shift the value into [HALF_SUB_BUCKETS, SUB_BUCKETS)
+        let shift = 64 - value.leading_zeros() - SUB_BUCKET_BITS;
+        SUB_BUCKETS
+            + (shift as usize - 1) * HALF_SUB_BUCKETS
+            + ((value >> shift) as usize - HALF_SUB_BUCKETS)
+    }
+
+    // Return the largest value that is recorded in the bucket of `index`.
+    fn highest_value(index: usize) -> u64 {
+        if index < SUB_BUCKETS {
+            return index as u64;
+        }
+        let shift = (index - SUB_BUCKETS) / HALF_SUB_BUCKETS + 1;
+        let sub_bucket = ((index - SUB_BUCKETS) % HALF_SUB_BUCKETS + HALF_SUB_BUCKETS) as u64;
+        ((sub_bucket + 1) << shift).wrapping_sub(1)
+    }
+
+    fn record(&mut self, value: u64) {
+        self.counts[Self::index(value)] += 1;
+        self.total += 1;
+    }
+
+    fn percentile(&self, p: f64) -> u64 {
+        if self.total == 0 {
+            return 0;
+        }
+        let p = p.clamp(0.0, 100.0);
+        let target = std::cmp::max(1, (p / 100.0 * self.total as f64).ceil() as u64);
+
+        let mut accum = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            accum += count;
+            if accum >= target {
+                return Self::highest_value(index);
+            }
+        }
+        unreachable!()
+    }
+
+    fn clear(&mut self) {
+        self.counts.fill(0);
+        self.total = 0;
+    }
+}
+
+/// A tracker of the per-packet latency through a pipeline.
+///
+/// The tracker stores a TSC timestamp in a dynamic field of the mbuf at the
+/// ingress, and records the elapsed time in a histogram at the egress. The
+/// latencies are recorded in nanoseconds with a relative error below 2%.
+pub struct LatencyTracker {
+    offset: usize,
+    tsc_hz: u64,
+    histogram: Histogram,
+}
+
+impl LatencyTracker {
+    /// The name of the registered mbuf dynamic field.
+    pub const DYNFIELD_NAME: &'static str = "rpkt_dynfield_latency_tsc";
+
+    /// Create a new tracker.
+    ///
+    /// The dynamic field is registered on the first call, the trackers created
+    /// afterwards share the same field.
+    pub fn try_create() -> Result<Self> {
+        let mut params: ffi::rte_mbuf_dynfield = unsafe { std::mem::zeroed() };
+        let name = CString::new(Self::DYNFIELD_NAME).unwrap();
+        for (dst, src) in params.name.iter_mut().zip(name.as_bytes_with_nul().iter()) {
+            *dst = *src as c_char;
+        }
+        params.size = std::mem::size_of::<u64>();
+        params.align = std::mem::align_of::<u64>();
+
+        let offset = unsafe { ffi::rte_mbuf_dynfield_register(&params) };
+        let offset = check_ret(offset, "fail to register latency dynfield")?;
+
+        Ok(Self {
+            offset: offset as usize,
+            tsc_hz: unsafe { ffi::rte_get_tsc_hz() },
+            histogram: Histogram::new(),
+        })
+    }
+
+    /// Store the current TSC value into `mbuf`.
+    #[inline]
+    pub fn stamp(&self, mbuf: &mut Mbuf) {
+        unsafe {
+            let field = (mbuf.as_ptr() as *mut u8).add(self.offset) as *mut u64;
+            field.write(ffi::rte_rdtsc_());
+        }
+    }
+
+    /// Record the time elapsed since `mbuf` was stamped.
+    ///
+    /// The result is meaningless if `mbuf` was never stamped.
+    #[inline]
+    pub fn record(&mut self, mbuf: &Mbuf) {
+        let stamp = unsafe {
+            let field = (mbuf.as_ptr() as *const u8).add(self.offset) as *const u64;
+            field.read()
+        };
+        let cycles = unsafe { ffi::rte_rdtsc_() }.saturating_sub(stamp);
+        let nanos = u128::from(cycles) * 1_000_000_000 / u128::from(self.tsc_hz);
+        self.histogram
+            .record(u64::try_from(nanos).unwrap_or(u64::MAX));
+    }
+
+    /// Return the latency in nanoseconds at the percentile `p`, which is in
+    /// the range of [0.0, 100.0].
+    ///
+    /// Return 0 if no latency is recorded.
+    pub fn percentile(&self, p: f64) -> u64 {
+        self.histogram.percentile(p)
+    }
+
+    /// Return the number of recorded latencies.
+    pub fn count(&self) -> u64 {
+        self.histogram.total
+    }
+
+    /// Clear the recorded latencies.
+    pub fn clear(&mut self) {
+        self.histogram.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+    use std::time::Duration;
+
+    #[test]
+    fn histogram_buckets() {
+        let mut histogram = Histogram::new();
+        assert_eq!(histogram.percentile(50.0), 0);
+
+        for value in [0, 1, 127, 128, 129, 1000, 123456789, u64::MAX] {
+            let index = Histogram::index(value);
+            assert!(index < NB_BUCKETS);
+            let highest = Histogram::highest_value(index);
+            assert!(highest >= value);
+            assert!(highest - value <= value / HALF_SUB_BUCKETS as u64);
+        }
+
+        for value in 1..=100 {
+            histogram.record(value * 1000);
+        }
+        assert_eq!(histogram.total, 100);
+        let p50 = histogram.percentile(50.0);
+        assert!((50000..=50000 + 50000 / 64).contains(&p50));
+        let p99 = histogram.percentile(99.0);
+        assert!((99000..=99000 + 99000 / 64).contains(&p99));
+        assert_eq!(histogram.percentile(0.0), histogram.percentile(1.0));
+    }
+
+    #[test]
+    fn stamp_and_record_latency() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut tracker = LatencyTracker::try_create().unwrap();
+            for _ in 0..10 {
+                let mut mbuf = mp.try_alloc().unwrap();
+                tracker.stamp(&mut mbuf);
+                std::thread::sleep(Duration::from_millis(2));
+                tracker.record(&mbuf);
+            }
+            assert_eq!(tracker.count(), 10);
+
+            let p50 = tracker.percentile(50.0);
+            assert!(p50 >= 2_000_000);
+            assert!(p50 < 100_000_000);
+
+            tracker.clear();
+            assert_eq!(tracker.count(), 0);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+}
@@ -24,6 +24,22 @@ impl Lcore {
     pub fn current() -> Option<Lcore> {
         LCORE.with(|tl| tl.borrow().as_ref().map(|lcore| *lcore))
     }
+
+    /// Return the id of the lcore that the current thread is bound to.
+    pub fn current_id() -> Option<u32> {
+        Self::current().map(|lcore| lcore.lcore_id)
+    }
+
+    /// Bind the current thread to the lcore indicated by `lcore_id`.
+    ///
+    /// The thread is pinned to the cpu of the lcore and registered as a non-EAL
+    /// thread, so that a thread spawned with `std::thread` can use the DPDK
+    /// APIs that depend on the lcore id, e.g. the per-lcore mempool caches.
+    ///
+    /// This is the same as `service().lcore_bind(lcore_id)`.
+    pub fn bind_current(lcore_id: u32) -> Result<()> {
+        crate::service().lcore_bind(lcore_id)
+    }
 }
 
 pub(crate) struct LcoreContext(HashMap<u32, bool>);
@@ -181,4 +197,27 @@ mod tests {
         let num = shared.load(atomic::Ordering::SeqCst);
         assert_eq!(num, 1);
     }
+
+    #[test]
+    fn bind_current_thread_to_lcore() {
+        DpdkOption::new().init().unwrap();
+
+        // lcore 1 may be taken by `bind_2_cores_to_the_same_lcore`, and an lcore
+        // can only be bound once in a process.
+        assert_eq!(service().lcores().len() >= 3, true);
+        let lcore_id = service().lcores().last().unwrap().lcore_id;
+
+        let jh = thread::spawn(move || {
+            assert_eq!(Lcore::current_id(), None);
+            Lcore::bind_current(lcore_id).unwrap();
+            assert_eq!(Lcore::current_id(), Some(lcore_id));
+
+            // the thread can not be bound twice
+            assert_eq!(Lcore::bind_current(lcore_id).is_err(), true);
+        });
+        jh.join().unwrap();
+
+        // the lcore is still occupied
+        assert_eq!(Lcore::bind_current(lcore_id).is_err(), true);
+    }
 }
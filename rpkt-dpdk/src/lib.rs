@@ -8,11 +8,26 @@ pub mod error;
 mod lcore;
 pub use lcore::Lcore;
 
+mod per_lcore;
+pub use per_lcore::PerLcore;
+
 mod service;
-pub use service::{service, try_service, DpdkOption, DpdkService};
+pub use service::{service, try_service, DpdkOption, DpdkService, EalConfig, ProcType};
+
+mod version;
+pub use version::{version, version_num, DpdkVersion};
+
+mod args;
+pub use args::AppArgs;
+
+mod vdev;
+pub use vdev::Vdev;
 
 mod mempool;
-pub use mempool::{Mempool, MempoolConf};
+pub use mempool::{Mempool, MempoolCache, MempoolConf, MempoolOps, PerSocketPools, PoolSizer};
+
+mod objpool;
+pub use objpool::{ObjPool, ObjPoolConf, PoolRef};
 
 #[cfg(not(feature = "multiseg"))]
 mod mbuf;
@@ -28,14 +43,113 @@ mod pbuf;
 #[cfg(feature = "multiseg")]
 pub use pbuf::Pbuf;
 
+mod extbuf;
+pub use extbuf::ExtBufShinfo;
+#[cfg(feature = "multiseg")]
+pub mod view;
+#[cfg(feature = "multiseg")]
+mod reassembly;
+#[cfg(feature = "multiseg")]
+pub use reassembly::{Ipv4Reassembler, ReassemblyConf};
+
+mod builder;
+pub use builder::PacketBuilder;
+
 mod port;
 pub use port::{
-    PortConf, PortInfo, PortStats, RxQueue, RxQueueConf, StatsQueryContext, TxQueue, TxQueueConf,
+    BitrateStats, DescLim, EthConf, FlowCtrlConf, FlowCtrlMode, OwnedPort, PortConf, PortInfo,
+    PortStats, RxCallback, RxQueue, RxQueueConf, StatsQueryContext, TxBuffer, TxQueue, TxQueueConf,
 };
 
 pub mod offload;
 
+pub mod eventdev;
+
+pub mod dpdk_alloc;
+
+pub mod pipeline;
+
+pub mod burst;
+
+pub mod checksum;
+
+pub mod gso;
+
+mod proto;
+
+pub mod rss;
+
 pub mod utils;
 
+pub mod names;
+
+mod memzone;
+pub use memzone::Memzone;
+
 mod ring;
-pub use ring::{RingConf, RingFlags};
+pub use ring::{
+    ContendedRing, ElemRing, EnqueuePolicy, MonitoredRing, PriorityQueue, Ring, RingConf,
+    RingFlags, RingSharder, SampleRing, SyncType,
+};
+
+mod byte_ring;
+pub use byte_ring::{ByteRing, ByteRingConf};
+
+mod hash;
+pub use hash::{Hash, HashConf, HashKey};
+
+mod mac_table;
+pub use mac_table::MacTable;
+
+mod rate_limit;
+pub use rate_limit::{FlowRateConf, FlowRateLimiter};
+
+mod lpm;
+pub use lpm::{Lpm, Lpm6, LpmConf};
+
+mod route_table;
+pub use route_table::{IpNet, RouteTable};
+
+mod latency;
+pub use latency::LatencyTracker;
+
+mod sequence;
+pub use sequence::{SeqChecker, SeqStamper, SeqStatus, SEQ_DYNFIELD_NAME};
+
+mod reorder;
+pub use reorder::{ReorderConf, Reorderer};
+
+mod retry;
+pub use retry::{Backoff, RetryPolicy};
+
+mod worker;
+pub use worker::Worker;
+
+mod flowgen;
+pub use flowgen::{FlowGenConf, FlowGenerator};
+
+mod dedup;
+pub use dedup::{DedupConf, DedupWindow};
+
+mod ipfix;
+pub use ipfix::{FlowRecord, IpfixConf, IpfixExporter};
+
+mod timestamp;
+
+#[cfg(feature = "tokio")]
+mod async_ring;
+#[cfg(feature = "tokio")]
+pub use async_ring::AsyncRing;
+
+#[cfg(feature = "replay")]
+mod replay;
+#[cfg(feature = "replay")]
+pub use replay::{ReplayConf, ReplayEngine, ReplayStats};
+
+#[cfg(feature = "trace")]
+pub mod trace;
+
+#[cfg(feature = "tap")]
+mod tap;
+#[cfg(feature = "tap")]
+pub use tap::RingTap;
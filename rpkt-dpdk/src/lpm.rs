@@ -0,0 +1,249 @@
+use std::ffi::CString;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::os::raw::c_char;
+use std::ptr::NonNull;
+
+use rpkt_dpdk_sys as ffi;
+
+use crate::error::*;
+
+#[derive(Clone, Copy, Debug)]
+pub struct LpmConf {
+    /// The maximum number of the routes.
+    pub max_rules: u32,
+    /// The number of the second-level tables. Each route that is longer than
+    /// 24 bits needs a second-level table for every 8 extra bits, unless it
+    /// shares them with another route.
+    pub number_tbl8s: u32,
+    pub socket_id: u32,
+}
+
+impl LpmConf {
+    pub const MAX_RULES: u32 = 1024;
+    pub const NUMBER_TBL8S: u32 = 256;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_max_rules(&mut self, val: u32) {
+        self.max_rules = val;
+    }
+
+    pub fn set_number_tbl8s(&mut self, val: u32) {
+        self.number_tbl8s = val;
+    }
+
+    pub fn set_socket_id(&mut self, val: u32) {
+        self.socket_id = val;
+    }
+}
+
+impl Default for LpmConf {
+    fn default() -> Self {
+        Self {
+            max_rules: Self::MAX_RULES,
+            number_tbl8s: Self::NUMBER_TBL8S,
+            socket_id: 0,
+        }
+    }
+}
+
+/// A longest prefix match table of ipv4 routes, backed by `rte_lpm`.
+///
+/// The lookups only read the table, and the updates take `&mut self`.
+pub struct Lpm {
+    ptr: NonNull<ffi::rte_lpm>,
+}
+
+unsafe impl Send for Lpm {}
+unsafe impl Sync for Lpm {}
+
+impl Lpm {
+    /// The maximum prefix length of a route, the prefix length 0 is not
+    /// supported by `rte_lpm`.
+    pub const MAX_DEPTH: u8 = 32;
+    /// The next hops are stored in 24 bits.
+    pub const MAX_NEXT_HOP: u32 = (1 << 24) - 1;
+
+    /// Create a new lpm table named `name`.
+    pub fn try_create(name: String, conf: &LpmConf) -> Result<Self> {
+        let err = Error::service_err("invalid lpm config");
+        let socket_id = i32::try_from(conf.socket_id).map_err(|_| err)?;
+
+        let cname = CString::new(name).map_err(|_| Error::service_err("invalid lpm name"))?;
+
+        let raw = unsafe {
+            let mut config: ffi::rte_lpm_config = std::mem::zeroed();
+            config.max_rules = conf.max_rules;
+            config.number_tbl8s = conf.number_tbl8s;
+            ffi::rte_lpm_create(
+                cname.as_bytes_with_nul().as_ptr() as *const c_char,
+                socket_id,
+                &config,
+            )
+        };
+
+        let ptr = check_ptr(raw, "fail to create lpm")?;
+        Ok(Self { ptr })
+    }
+
+    /// Add the route to `ip/depth`, or replace the next hop of an existing
+    /// route.
+    ///
+    /// This function fails if `depth` is not in `1..=MAX_DEPTH`, `next_hop`
+    /// exceeds `MAX_NEXT_HOP`, or the table is full.
+    pub fn add(&mut self, ip: Ipv4Addr, depth: u8, next_hop: u32) -> Result<()> {
+        if next_hop > Self::MAX_NEXT_HOP {
+            return Error::service_err("lpm next hop exceeds 24 bits").to_err();
+        }
+        let res = unsafe { ffi::rte_lpm_add(self.ptr.as_ptr(), u32::from(ip), depth, next_hop) };
+        check_ret(res, "fail to add lpm route").map(|_| ())
+    }
+
+    /// Delete the route to `ip/depth`, this function fails if the route does
+    /// not exist.
+    pub fn delete(&mut self, ip: Ipv4Addr, depth: u8) -> Result<()> {
+        let res = unsafe { ffi::rte_lpm_delete(self.ptr.as_ptr(), u32::from(ip), depth) };
+        check_ret(res, "fail to delete lpm route").map(|_| ())
+    }
+
+    /// Return the next hop of the longest route that matches `ip`.
+    #[inline]
+    pub fn lookup(&self, ip: Ipv4Addr) -> Option<u32> {
+        let mut next_hop = 0;
+        let res = unsafe { ffi::rte_lpm_lookup_(self.ptr.as_ptr(), u32::from(ip), &mut next_hop) };
+        (res == 0).then_some(next_hop)
+    }
+
+    pub fn as_ptr(&self) -> *const ffi::rte_lpm {
+        self.ptr.as_ptr()
+    }
+}
+
+impl Drop for Lpm {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::rte_lpm_free(self.ptr.as_ptr());
+        }
+    }
+}
+
+/// A longest prefix match table of ipv6 routes, backed by `rte_lpm6`.
+///
+/// The lookups only read the table, and the updates take `&mut self`.
+pub struct Lpm6 {
+    ptr: NonNull<ffi::rte_lpm6>,
+}
+
+unsafe impl Send for Lpm6 {}
+unsafe impl Sync for Lpm6 {}
+
+impl Lpm6 {
+    /// The maximum prefix length of a route, the prefix length 0 is not
+    /// supported by `rte_lpm6`.
+    pub const MAX_DEPTH: u8 = 128;
+    /// The next hops are stored in 21 bits.
+    pub const MAX_NEXT_HOP: u32 = (1 << 21) - 1;
+
+    /// Create a new lpm6 table named `name`.
+    pub fn try_create(name: String, conf: &LpmConf) -> Result<Self> {
+        let err = Error::service_err("invalid lpm6 config");
+        let socket_id = i32::try_from(conf.socket_id).map_err(|_| err)?;
+
+        let cname = CString::new(name).map_err(|_| Error::service_err("invalid lpm6 name"))?;
+
+        let raw = unsafe {
+            let mut config: ffi::rte_lpm6_config = std::mem::zeroed();
+            config.max_rules = conf.max_rules;
+            config.number_tbl8s = conf.number_tbl8s;
+            ffi::rte_lpm6_create(
+                cname.as_bytes_with_nul().as_ptr() as *const c_char,
+                socket_id,
+                &config,
+            )
+        };
+
+        let ptr = check_ptr(raw, "fail to create lpm6")?;
+        Ok(Self { ptr })
+    }
+
+    /// Add the route to `ip/depth`, or replace the next hop of an existing
+    /// route.
+    ///
+    /// This function fails if `depth` is not in `1..=MAX_DEPTH`, `next_hop`
+    /// exceeds `MAX_NEXT_HOP`, or the table is full.
+    pub fn add(&mut self, ip: Ipv6Addr, depth: u8, next_hop: u32) -> Result<()> {
+        if next_hop > Self::MAX_NEXT_HOP {
+            return Error::service_err("lpm6 next hop exceeds 21 bits").to_err();
+        }
+        let ip = ip.octets();
+        let res = unsafe { ffi::rte_lpm6_add(self.ptr.as_ptr(), ip.as_ptr(), depth, next_hop) };
+        check_ret(res, "fail to add lpm6 route").map(|_| ())
+    }
+
+    /// Delete the route to `ip/depth`, this function fails if the route does
+    /// not exist.
+    pub fn delete(&mut self, ip: Ipv6Addr, depth: u8) -> Result<()> {
+        let ip = ip.octets();
+        let res = unsafe { ffi::rte_lpm6_delete(self.ptr.as_ptr(), ip.as_ptr(), depth) };
+        check_ret(res, "fail to delete lpm6 route").map(|_| ())
+    }
+
+    /// Return the next hop of the longest route that matches `ip`.
+    #[inline]
+    pub fn lookup(&self, ip: Ipv6Addr) -> Option<u32> {
+        let ip = ip.octets();
+        let mut next_hop = 0;
+        let res = unsafe { ffi::rte_lpm6_lookup(self.ptr.as_ptr(), ip.as_ptr(), &mut next_hop) };
+        (res == 0).then_some(next_hop)
+    }
+
+    pub fn as_ptr(&self) -> *const ffi::rte_lpm6 {
+        self.ptr.as_ptr()
+    }
+}
+
+impl Drop for Lpm6 {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::rte_lpm6_free(self.ptr.as_ptr());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn lpm_longest_prefix() {
+        DpdkOption::new().init().unwrap();
+
+        let mut lpm = Lpm::try_create("lpm_test".to_string(), &LpmConf::new()).unwrap();
+        lpm.add(Ipv4Addr::new(10, 0, 0, 0), 8, 1).unwrap();
+        lpm.add(Ipv4Addr::new(10, 1, 2, 0), 24, 2).unwrap();
+        lpm.add(Ipv4Addr::new(10, 1, 2, 128), 25, 3).unwrap();
+        assert_eq!(lpm.lookup(Ipv4Addr::new(10, 200, 0, 1)), Some(1));
+        assert_eq!(lpm.lookup(Ipv4Addr::new(10, 1, 2, 1)), Some(2));
+        assert_eq!(lpm.lookup(Ipv4Addr::new(10, 1, 2, 200)), Some(3));
+        assert_eq!(lpm.lookup(Ipv4Addr::new(11, 0, 0, 1)), None);
+
+        lpm.delete(Ipv4Addr::new(10, 1, 2, 128), 25).unwrap();
+        assert_eq!(lpm.lookup(Ipv4Addr::new(10, 1, 2, 200)), Some(2));
+        assert!(lpm.delete(Ipv4Addr::new(10, 1, 2, 128), 25).is_err());
+
+        assert!(lpm.add(Ipv4Addr::new(0, 0, 0, 0), 0, 1).is_err());
+        assert!(lpm.add(Ipv4Addr::new(10, 0, 0, 0), 8, 1 << 24).is_err());
+
+        let mut lpm6 = Lpm6::try_create("lpm_test".to_string(), &LpmConf::new()).unwrap();
+        let net: Ipv6Addr = "2001:db8::".parse().unwrap();
+        lpm6.add(net, 32, 1).unwrap();
+        lpm6.add("2001:db8:0:1::".parse().unwrap(), 64, 2).unwrap();
+        assert_eq!(lpm6.lookup("2001:db8::1".parse().unwrap()), Some(1));
+        assert_eq!(lpm6.lookup("2001:db8:0:1::1".parse().unwrap()), Some(2));
+        assert_eq!(lpm6.lookup("2001:db9::1".parse().unwrap()), None);
+        assert!(lpm6.add(net, 32, 1 << 21).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use std::time::{Duration, Instant};
+
+use crate::error::*;
+use crate::{Hash, HashConf, HashKey};
+
+// The vlan id of the untagged frames, which is not a valid 12-bit vlan id.
+const NO_VLAN: u16 = u16::MAX;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MacKey {
+    mac: [u8; 6],
+    vlan: [u8; 2],
+}
+
+// The fields are byte arrays, so there is no padding.
+unsafe impl HashKey for MacKey {}
+
+impl MacKey {
+    fn new(mac: [u8; 6], vlan: Option<u16>) -> Self {
+        Self {
+            mac,
+            vlan: vlan.unwrap_or(NO_VLAN).to_be_bytes(),
+        }
+    }
+}
+
+struct MacEntry {
+    port: u16,
+    last_seen: Instant,
+}
+
+/// The forwarding database of a learning bridge.
+///
+/// The source mac address of each received frame is learned with the port it
+/// arrives on, so that the frames to this address are forwarded to that port
+/// only. The entries that are not refreshed are evicted by `age`.
+pub struct MacTable {
+    hash: Hash<MacKey, MacEntry>,
+}
+
+impl MacTable {
+    /// Create a new table named `name`, which holds up to `conf.entries` mac
+    /// addresses.
+    pub fn try_create(name: String, conf: &HashConf) -> Result<Self> {
+        Ok(Self {
+            hash: Hash::try_create(name, conf)?,
+        })
+    }
+
+    /// Learn that `mac` in `vlan` is reachable through `port`, and refresh the
+    /// timestamp of the entry.
+    ///
+    /// This function fails if the table is full.
+    #[inline]
+    pub fn learn(&mut self, mac: [u8; 6], vlan: Option<u16>, port: u16) -> Result<()> {
+        self.learn_at(mac, vlan, port, Instant::now())
+    }
+
+    /// Return the port of `mac` in `vlan`, or `None` if the address is unknown
+    /// and the frame should be flooded.
+    #[inline]
+    pub fn lookup(&self, mac: [u8; 6], vlan: Option<u16>) -> Option<u16> {
+        self.hash
+            .get(&MacKey::new(mac, vlan))
+            .map(|entry| entry.port)
+    }
+
+    /// Evict the entries that are not learned in the last `max_age`, return the
+    /// number of the evicted entries.
+    pub fn age(&mut self, max_age: Duration) -> usize {
+        self.age_at(max_age, Instant::now())
+    }
+
+    /// Forget all the entries learned on `port`, e.g. when the link of the port
+    /// goes down.
+    pub fn flush_port(&mut self, port: u16) -> usize {
+        self.hash.retain(|_, entry| entry.port != port)
+    }
+
+    pub fn len(&self) -> usize {
+        self.hash.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hash.is_empty()
+    }
+
+    // `now` is replaced by a mock clock in the tests.
+    fn learn_at(&mut self, mac: [u8; 6], vlan: Option<u16>, port: u16, now: Instant) -> Result<()> {
+        let key = MacKey::new(mac, vlan);
+        match self.hash.get_mut(&key) {
+            Some(entry) => {
+                entry.port = port;
+                entry.last_seen = now;
+            }
+            None => {
+                self.hash.insert(
+                    &key,
+                    MacEntry {
+                        port,
+                        last_seen: now,
+                    },
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn age_at(&mut self, max_age: Duration, now: Instant) -> usize {
+        self.hash
+            .retain(|_, entry| now.saturating_duration_since(entry.last_seen) <= max_age)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    fn mac(last: u8) -> [u8; 6] {
+        [0x02, 0, 0, 0, 0, last]
+    }
+
+    #[test]
+    fn learn_lookup_and_age() {
+        DpdkOption::new().init().unwrap();
+
+        let mut conf = HashConf::new();
+        conf.set_entries(16);
+        let mut table = MacTable::try_create("fdb".to_string(), &conf).unwrap();
+
+        let start = Instant::now();
+        for i in 0..4 {
+            table.learn_at(mac(i), None, u16::from(i), start).unwrap();
+        }
+        table.learn_at(mac(0), Some(100), 7, start).unwrap();
+        assert_eq!(table.len(), 5);
+        assert_eq!(table.lookup(mac(2), None), Some(2));
+        // the same address in another vlan is a different entry
+        assert_eq!(table.lookup(mac(0), None), Some(0));
+        assert_eq!(table.lookup(mac(0), Some(100)), Some(7));
+        assert_eq!(table.lookup(mac(1), Some(100)), None);
+        assert_eq!(table.lookup(mac(9), None), None);
+
+        // the station moves to another port
+        let later = start + Duration::from_secs(200);
+        table.learn_at(mac(1), None, 5, later).unwrap();
+        assert_eq!(table.lookup(mac(1), None), Some(5));
+        assert_eq!(table.len(), 5);
+
+        // only the refreshed entry survives
+        assert_eq!(table.age_at(Duration::from_secs(300), later), 0);
+        assert_eq!(
+            table.age_at(Duration::from_secs(300), start + Duration::from_secs(301)),
+            4
+        );
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.lookup(mac(1), None), Some(5));
+        assert_eq!(table.lookup(mac(0), None), None);
+
+        table.learn_at(mac(3), None, 5, later).unwrap();
+        assert_eq!(table.flush_port(5), 2);
+        assert!(table.is_empty());
+    }
+}
@@ -1,8 +1,14 @@
+use std::ffi::{c_char, c_void, CStr};
+use std::io::IoSlice;
 use std::ptr::NonNull;
 
+use arrayvec::ArrayVec;
 use rpkt_dpdk_sys as ffi;
 
-use crate::offload::{MbufRxOffload, MbufTxOffload};
+use crate::error::*;
+use crate::offload::{LayerOffsets, MbufRxOffload, MbufTxOffload, OlFlags};
+use crate::timestamp::DynTimestamp;
+use crate::{ExtBufShinfo, Mempool};
 
 #[derive(Debug)]
 pub struct Mbuf {
@@ -18,6 +24,136 @@ impl Mbuf {
         unsafe { self.ptr.as_ref().data_len.into() }
     }
 
+    /// Return the sum of the data lengths of all the segments.
+    ///
+    /// This is the same as `pkt_len` for a well-formed mbuf, but the segments
+    /// are walked instead of trusting `pkt_len`, so that the byte accounting
+    /// stays correct if the two disagree.
+    #[inline]
+    pub fn total_len(&self) -> usize {
+        let mut total = 0;
+        let mut seg: *const ffi::rte_mbuf = self.ptr.as_ptr();
+        while let Some(cur) = unsafe { seg.as_ref() } {
+            total += usize::from(cur.data_len);
+            seg = cur.next;
+        }
+        total
+    }
+
+    /// Copy the data of all the segments into a contiguous `Vec`, e.g. for the
+    /// libraries that work on byte slices.
+    pub fn copy_to_vec(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.total_len());
+        for seg in self.segs() {
+            buf.extend_from_slice(seg);
+        }
+        buf
+    }
+
+    /// Return the data of the segments as `IoSlice`s, e.g. for a vectored
+    /// `write_vectored` or `sendmsg` to a kernel socket, without copying the
+    /// segments into a contiguous buffer.
+    ///
+    /// The slices borrow the data of the mbuf, so the mbuf can be neither
+    /// changed nor freed, e.g. by a `tx_burst`, until the slices are dropped.
+    ///
+    /// # Panic:
+    /// This function panics if the mbuf has more than `N` segments.
+    pub fn to_io_slices<const N: usize>(&self) -> ArrayVec<IoSlice<'_>, N> {
+        let mut slices = ArrayVec::new();
+        for seg in self.segs() {
+            slices.push(IoSlice::new(seg));
+        }
+        slices
+    }
+
+    /// Copy `len` bytes of the data at the offset `off` into the front of
+    /// `dst`, the range may span several segments.
+    ///
+    /// Return an error if the range exceeds the data of the mbuf, or if `dst`
+    /// is shorter than `len`.
+    pub fn copy_range_to(&self, off: usize, len: usize, dst: &mut [u8]) -> Result<()> {
+        let total_len = self.total_len();
+        if off > total_len || len > total_len - off {
+            return Error::service_err("copy range exceeds the mbuf data").to_err();
+        }
+        if dst.len() < len {
+            return Error::service_err("copy destination is too short").to_err();
+        }
+
+        let mut off = off;
+        let mut dst = &mut dst[..len];
+        for seg in self.segs() {
+            if dst.is_empty() {
+                break;
+            }
+            if off >= seg.len() {
+                off -= seg.len();
+                continue;
+            }
+            let cnt = (seg.len() - off).min(dst.len());
+            let (head, tail) = std::mem::take(&mut dst).split_at_mut(cnt);
+            head.copy_from_slice(&seg[off..off + cnt]);
+            dst = tail;
+            off = 0;
+        }
+        Ok(())
+    }
+
+    /// Whether the data of all the segments equals the data of `other`,
+    /// regardless of how the two mbufs are split into segments.
+    pub fn data_eq(&self, other: &Mbuf) -> bool {
+        if self.total_len() != other.total_len() {
+            return false;
+        }
+
+        let mut others = other.segs();
+        let mut cur: &[u8] = &[];
+        for mut seg in self.segs() {
+            while !seg.is_empty() {
+                while cur.is_empty() {
+                    // the total lengths are equal
+                    cur = others.next().unwrap();
+                }
+                let cnt = seg.len().min(cur.len());
+                if seg[..cnt] != cur[..cnt] {
+                    return false;
+                }
+                seg = &seg[cnt..];
+                cur = &cur[cnt..];
+            }
+        }
+        true
+    }
+
+    /// Whether the data of all the segments equals `bytes`.
+    pub fn data_eq_slice(&self, bytes: &[u8]) -> bool {
+        if self.total_len() != bytes.len() {
+            return false;
+        }
+
+        let mut rest = bytes;
+        for seg in self.segs() {
+            let (head, tail) = rest.split_at(seg.len());
+            if head != seg {
+                return false;
+            }
+            rest = tail;
+        }
+        true
+    }
+
+    // Iterate over the data of the segments, an mbuf received with the
+    // scattered rx is chained.
+    fn segs(&self) -> impl Iterator<Item = &[u8]> {
+        let mut seg: *const ffi::rte_mbuf = self.ptr.as_ptr();
+        std::iter::from_fn(move || {
+            let cur = unsafe { seg.as_ref() }?;
+            seg = cur.next;
+            Some(unsafe { std::slice::from_raw_parts(data_addr(cur), usize::from(cur.data_len)) })
+        })
+    }
+
     #[inline]
     pub fn capacity(&self) -> usize {
         unsafe {
@@ -68,6 +204,7 @@ impl Mbuf {
         let old_len = self.len();
         unsafe { self.extend(slice.len()) };
         self.data_mut()[old_len..].copy_from_slice(slice);
+        self.debug_validate();
     }
 
     #[inline]
@@ -82,6 +219,7 @@ impl Mbuf {
     pub fn extend_front_from_slice(&mut self, slice: &[u8]) {
         unsafe { self.extend_front(slice.len()) };
         self.data_mut()[..slice.len()].copy_from_slice(slice);
+        self.debug_validate();
     }
 
     #[inline]
@@ -91,6 +229,7 @@ impl Mbuf {
             self.ptr.as_mut().data_len = cnt as u16;
             self.ptr.as_mut().pkt_len = cnt as u32;
         }
+        self.debug_validate();
     }
 
     #[inline]
@@ -101,6 +240,74 @@ impl Mbuf {
             self.ptr.as_mut().pkt_len -= cnt as u32;
             self.ptr.as_mut().data_off += cnt as u16;
         }
+        self.debug_validate();
+    }
+
+    /// Whether the mbuf owns its data buffer, i.e. it is neither attached to
+    /// another mbuf nor to an external buffer.
+    #[inline]
+    pub fn is_direct(&self) -> bool {
+        !self
+            .ol_flags()
+            .intersects(OlFlags::INDIRECT | OlFlags::EXTERNAL)
+    }
+
+    /// Whether the mbuf is attached to the buffer of another mbuf by `attach`.
+    ///
+    /// An mbuf attached to another mbuf with an external buffer shares the
+    /// external buffer instead, so it is reported by `has_extbuf`.
+    #[inline]
+    pub fn is_indirect(&self) -> bool {
+        self.ol_flags().contains(OlFlags::INDIRECT)
+    }
+
+    /// Whether the mbuf is attached to an external buffer by `attach_extbuf`,
+    /// or by `attach` to an mbuf with an external buffer.
+    #[inline]
+    pub fn has_extbuf(&self) -> bool {
+        self.ol_flags().contains(OlFlags::EXTERNAL)
+    }
+
+    /// Return the raw offload flags of the mbuf, including the flags that are
+    /// not covered by `rx_offload` and `tx_offload`.
+    #[inline]
+    pub fn ol_flags(&self) -> OlFlags {
+        OlFlags::from_bits_retain(unsafe { self.ptr.as_ref().ol_flags })
+    }
+
+    /// Set the offload `flags`, leaving the other flags unchanged.
+    ///
+    /// # Panics
+    ///
+    /// The `INDIRECT` and the `EXTERNAL` flags are managed by the attach
+    /// functions, as they decide how the mbuf is freed. This function panics
+    /// if `flags` contains any of them.
+    #[inline]
+    pub fn set_ol_flags(&mut self, flags: OlFlags) {
+        assert!(
+            !flags.intersects(OlFlags::INDIRECT | OlFlags::EXTERNAL),
+            "setting the attach flags of an mbuf"
+        );
+        unsafe {
+            self.ptr.as_mut().ol_flags |= flags.bits();
+        }
+    }
+
+    /// Clear the offload `flags`, leaving the other flags unchanged.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `flags` contains the `INDIRECT` or the
+    /// `EXTERNAL` flag, see `set_ol_flags`.
+    #[inline]
+    pub fn clear_ol_flags(&mut self, flags: OlFlags) {
+        assert!(
+            !flags.intersects(OlFlags::INDIRECT | OlFlags::EXTERNAL),
+            "clearing the attach flags of an mbuf"
+        );
+        unsafe {
+            self.ptr.as_mut().ol_flags &= !flags.bits();
+        }
     }
 
     // rx offload
@@ -114,6 +321,44 @@ impl Mbuf {
         unsafe { self.ptr.as_ref().__bindgen_anon_2.hash.rss }
     }
 
+    /// Return the layer offsets of the packet parsed by the PMD, which are
+    /// derived from the packet type and the `l2_len`, `l3_len` and `l4_len`
+    /// fields.
+    ///
+    /// Return `None` if the PMD does not parse the l2 and the l3 headers, the
+    /// packet must then be parsed in software.
+    #[inline]
+    pub fn parsed_offsets(&self) -> Option<LayerOffsets> {
+        LayerOffsets::from_raw(unsafe { self.ptr.as_ref() })
+    }
+
+    /// Return the vlan tci of the packet, it is only valid if the vlan tag is stripped
+    /// by the NIC.
+    #[inline]
+    pub fn vlan_tci(&self) -> u16 {
+        unsafe { self.ptr.as_ref().vlan_tci }
+    }
+
+    /// Check whether the vlan tag is stripped by the NIC and saved in `vlan_tci`.
+    #[inline]
+    pub fn vlan_stripped(&self) -> bool {
+        self.rx_offload().vlan_stripped()
+    }
+
+    /// Return the port id of the mbuf, which is the input port for the
+    /// received packets.
+    #[inline]
+    pub fn port(&self) -> u16 {
+        unsafe { self.ptr.as_ref().port }
+    }
+
+    /// Set the port id of the mbuf, e.g. to record the origin of a packet
+    /// that is passed through a ring.
+    #[inline]
+    pub fn set_port(&mut self, port: u16) {
+        unsafe { self.ptr.as_mut().port = port };
+    }
+
     #[inline]
     pub fn set_tx_offload(&mut self, tx_offload: MbufTxOffload) {
         unsafe {
@@ -121,6 +366,37 @@ impl Mbuf {
         }
     }
 
+    /// Return the hardware timestamp of a received mbuf, if there is one.
+    ///
+    /// The timestamp is only stamped by the ports configured with the
+    /// `DevRxOffload::timestamp` offload, and its unit is device-dependent.
+    #[inline]
+    pub fn rx_timestamp(&self) -> Option<u64> {
+        let dyn_ts = DynTimestamp::rx().ok()?;
+        unsafe { dyn_ts.read(self.ptr.as_ptr()) }
+    }
+
+    /// Return the scheduled transmit time set by `set_tx_timestamp`, if there is
+    /// one.
+    #[inline]
+    pub fn tx_timestamp(&self) -> Option<u64> {
+        let dyn_ts = DynTimestamp::tx().ok()?;
+        unsafe { dyn_ts.read(self.ptr.as_ptr()) }
+    }
+
+    /// Schedule the mbuf to be sent at `timestamp`, in the unit of the device
+    /// clock.
+    ///
+    /// The timestamp is only honored by the ports configured with the
+    /// `DevTxOffload::send_on_timestamp` offload. As `set_tx_offload` overwrites
+    /// the offload flags, it must be called before this function.
+    #[inline]
+    pub fn set_tx_timestamp(&mut self, timestamp: u64) -> Result<()> {
+        let dyn_ts = DynTimestamp::tx()?;
+        unsafe { dyn_ts.write(self.ptr.as_ptr(), timestamp) };
+        Ok(())
+    }
+
     #[inline]
     pub fn set_l2_len(&mut self, val: u64) {
         unsafe {
@@ -143,13 +419,260 @@ impl Mbuf {
         }
     }
 
-    // modified to pub for netbricks_port
+    /// Copy the mbuf into a newly allocated mbuf from `mempool`.
+    ///
+    /// The data and the packet metadata, including the offload flags and the
+    /// vlan tags, are copied, so that the new mbuf can be modified independently.
+    /// Return `None` if the allocation fails or if the data does not fit in a
+    /// single mbuf from `mempool`.
+    #[inline]
+    pub fn deep_copy(&self, mempool: &Mempool) -> Option<Self> {
+        let raw = unsafe {
+            ffi::rte_pktmbuf_copy(
+                self.ptr.as_ptr(),
+                mempool.as_ptr() as *mut ffi::rte_mempool,
+                0,
+                u32::MAX,
+            )
+        };
+        let copy = Self {
+            ptr: NonNull::new(raw)?,
+        };
+        if unsafe { copy.ptr.as_ref().nb_segs } != 1 {
+            return None;
+        }
+        Some(copy)
+    }
+
+    /// Return the number of bytes in front of the packet data, which can be
+    /// used to prepend headers.
+    #[inline]
+    pub fn headroom(&self) -> u16 {
+        unsafe { ffi::rte_pktmbuf_headroom_(self.ptr.as_ptr()) }
+    }
+
+    /// Return the number of bytes after the packet data, which can be used to
+    /// append data.
+    #[inline]
+    pub fn tailroom(&self) -> u16 {
+        unsafe { ffi::rte_pktmbuf_tailroom_(self.ptr.as_ptr()) }
+    }
+
+    /// Remove `len` bytes from the front of the packet data, and return the
+    /// remaining data.
+    ///
+    /// Return `None` and leave the mbuf unchanged if `len` exceeds the data
+    /// length.
+    #[inline]
+    pub fn adj(&mut self, len: u16) -> Option<&mut [u8]> {
+        let res = unsafe { ffi::rte_pktmbuf_adj_(self.ptr.as_ptr(), len) };
+        if res.is_null() {
+            None
+        } else {
+            Some(self.data_mut())
+        }
+    }
+
+    /// Remove `len` bytes from the end of the packet data.
+    ///
+    /// Return `false` and leave the mbuf unchanged if `len` exceeds the data
+    /// length.
+    #[inline]
+    pub fn trim(&mut self, len: u16) -> bool {
+        let res = unsafe { ffi::rte_pktmbuf_trim_(self.ptr.as_ptr(), len) == 0 };
+        self.debug_validate();
+        res
+    }
+
+    /// Return an `xxd`-style hex and ascii dump of the first `max_bytes` bytes of
+    /// the packet data.
+    pub fn hexdump(&self, max_bytes: usize) -> String {
+        let data = self.data();
+        crate::utils::hexdump(&data[..data.len().min(max_bytes)])
+    }
+
+    /// Dump the mbuf metadata and the packet data to stderr with `rte_pktmbuf_dump`.
+    pub fn dump_to_stderr(&self) {
+        unsafe { ffi::rte_pktmbuf_dump_stderr_(self.ptr.as_ptr(), self.len() as u32) };
+    }
+
+    /// Check the invariants of the mbuf with `rte_mbuf_check`, e.g. the refcount,
+    /// the data bounds of each segment and the packet length, which must equal
+    /// the sum of the data lengths of the segments.
+    ///
+    /// Return an error that describes the first violated invariant.
+    pub fn validate(&self) -> Result<()> {
+        let mut reason: *const c_char = std::ptr::null();
+        let res = unsafe { ffi::rte_mbuf_check(self.ptr.as_ptr(), 1, &mut reason) };
+        if res == 0 {
+            return Ok(());
+        }
+        // The reasons are string literals in dpdk.
+        let msg = if reason.is_null() {
+            "corrupted mbuf"
+        } else {
+            unsafe { CStr::from_ptr(reason) }
+                .to_str()
+                .unwrap_or("corrupted mbuf")
+        };
+        Error::service_err(msg).to_err()
+    }
+
+    // Panic if the mbuf is corrupted by a length update, in the debug builds.
+    #[inline]
+    fn debug_validate(&self) {
+        if cfg!(debug_assertions) {
+            if let Err(err) = self.validate() {
+                panic!("{}", err);
+            }
+        }
+    }
+
+    /// Attach the mbuf to the data of `external` without copying, so that it
+    /// becomes an indirect mbuf holding the `len` bytes at offset `off` of the
+    /// data of `external`.
+    ///
+    /// The previous data of the mbuf is discarded. The attachment increments
+    /// the refcount of the buffer of `external`, which is not returned to its
+    /// mempool until `external` and all the mbufs attached to it are dropped.
+    /// The data is shared, so writes through either mbuf are visible in both.
+    ///
+    /// # Safety:
+    /// The mbufs sharing a buffer are distinct owned values, so the borrow
+    /// checker can not see that their data aliases. Until the mbuf is dropped,
+    /// the caller must not write the shared data through `external`, the mbuf
+    /// or any other mbuf attached to the same buffer, e.g. by `data_mut`, while
+    /// a slice of the data from another of them is alive, or while another of
+    /// them is accessed from a different thread.
+    ///
+    /// # Panics:
+    /// This function panics if the mbuf is already indirect, is a multi-segment
+    /// mbuf, is attached by other mbufs, or if the range exceeds the first
+    /// segment of `external`.
+    #[inline]
+    pub unsafe fn attach(&mut self, external: &Mbuf, off: u16, len: u16) {
+        let ext_len = external.ptr.as_ref().data_len;
+        assert!(u32::from(off) + u32::from(len) <= u32::from(ext_len));
+
+        let res = ffi::rte_pktmbuf_attach_(self.ptr.as_ptr(), external.ptr.as_ptr());
+        assert!(res == 0, "the mbuf can not be attached");
+
+        let mbuf = self.ptr.as_mut();
+        mbuf.data_off += off;
+        mbuf.data_len = len;
+        mbuf.pkt_len = u32::from(len);
+    }
+
+    /// Attach the mbuf to the external buffer of `len` bytes at `buf`, so that
+    /// the data of the mbuf covers the whole external buffer without copying.
+    ///
+    /// The previous data of the mbuf is discarded. The mbuf holds a reference
+    /// of `shinfo` until it is detached or freed, and the free callback of
+    /// `shinfo` runs after the last reference is released. The mbufs attached
+    /// to this mbuf by `attach` share the external buffer as well.
+    ///
+    /// # Safety:
+    /// `buf` must be valid for reads and writes of `len` bytes, and `iova` must
+    /// be its io address if the mbuf is passed to a device, until the free
+    /// callback of `shinfo` runs.
+    ///
+    /// # Panics:
+    /// This function panics if the mbuf is indirect, is a multi-segment mbuf,
+    /// or is attached by other mbufs.
+    #[inline]
+    pub unsafe fn attach_extbuf(
+        &mut self,
+        buf: *mut u8,
+        iova: u64,
+        len: u16,
+        shinfo: &ExtBufShinfo,
+    ) {
+        let res = ffi::rte_pktmbuf_attach_extbuf_(
+            self.ptr.as_ptr(),
+            buf as *mut c_void,
+            iova,
+            len,
+            shinfo.as_ptr(),
+        );
+        assert!(res == 0, "the mbuf can not be attached");
+
+        let mbuf = self.ptr.as_mut();
+        mbuf.data_len = len;
+        mbuf.pkt_len = u32::from(len);
+    }
+
+    /// Detach the mbuf from its external buffer and release its reference of
+    /// the shared info, the mbuf is restored to its own empty buffer.
+    ///
+    /// # Panics:
+    /// This function panics if the mbuf has no external buffer, or is attached
+    /// by other mbufs.
+    #[inline]
+    pub fn detach_extbuf(&mut self) {
+        let res = unsafe { ffi::rte_pktmbuf_detach_extbuf_(self.ptr.as_ptr()) };
+        assert!(res == 0, "the mbuf can not be detached");
+    }
+
+    /// Return a typed reference to the private area of the mbuf.
+    ///
+    /// # Safety:
+    /// The private area is not initialized by the mempool, the caller must make sure
+    /// that it holds a valid `T`.
+    ///
+    /// # Panic:
+    /// In debug build, this function panics if `T` does not fit in the private area.
+    #[inline]
+    pub unsafe fn priv_data<T>(&self) -> &T {
+        debug_assert!(std::mem::size_of::<T>() <= usize::from(self.ptr.as_ref().priv_size));
+        debug_assert!(std::mem::align_of::<T>() <= 8);
+        &*(priv_addr(self.ptr.as_ref()) as *const T)
+    }
+
+    /// Return a typed mutable reference to the private area of the mbuf.
+    ///
+    /// # Safety:
+    /// The private area is not initialized by the mempool, the caller must make sure
+    /// that it holds a valid `T`.
+    ///
+    /// # Panic:
+    /// In debug build, this function panics if `T` does not fit in the private area.
+    #[inline]
+    pub unsafe fn priv_data_mut<T>(&mut self) -> &mut T {
+        debug_assert!(std::mem::size_of::<T>() <= usize::from(self.ptr.as_ref().priv_size));
+        debug_assert!(std::mem::align_of::<T>() <= 8);
+        &mut *(priv_addr(self.ptr.as_ref()) as *mut T)
+    }
+
+    /// Take the ownership of a raw mbuf, e.g. one returned by `into_raw`.
+    ///
+    /// This mirrors `Box::from_raw`, the returned `Mbuf` frees `ptr` when it is
+    /// dropped.
+    ///
+    /// # Safety
+    /// `ptr` must point to a valid mbuf that is not owned by another `Mbuf`.
     #[inline]
     pub unsafe fn from_raw(ptr: *mut ffi::rte_mbuf) -> Self {
         Self {
             ptr: NonNull::new_unchecked(ptr),
         }
     }
+
+    /// Consume the `Mbuf` and return the raw mbuf without freeing it.
+    ///
+    /// This mirrors `Box::into_raw`, the caller is responsible for the raw mbuf,
+    /// e.g. by passing it to a DPDK function that takes the ownership, or by
+    /// reclaiming it with `from_raw`.
+    #[inline]
+    pub fn into_raw(self) -> *mut ffi::rte_mbuf {
+        let ptr = self.ptr;
+        std::mem::forget(self);
+        ptr.as_ptr()
+    }
+
+    #[inline]
+    pub(crate) fn as_ptr(&self) -> *const ffi::rte_mbuf {
+        self.ptr.as_ptr()
+    }
 }
 
 impl Drop for Mbuf {
@@ -165,9 +688,19 @@ unsafe fn data_addr(mbuf: &ffi::rte_mbuf) -> *mut u8 {
     (mbuf.buf_addr as *mut u8).add(data_off)
 }
 
+// The private area immediately follows the `rte_mbuf` struct, this is
+// the same as `rte_mbuf_to_priv`.
+#[inline]
+unsafe fn priv_addr(mbuf: &ffi::rte_mbuf) -> *mut u8 {
+    (mbuf as *const ffi::rte_mbuf as *mut u8).add(std::mem::size_of::<ffi::rte_mbuf>())
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::offload::OlFlags;
     use crate::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
 
     #[test]
     fn mbuf_data_append_remove() {
@@ -241,4 +774,572 @@ mod tests {
 
         service().mempool_free("wtf").unwrap();
     }
+
+    #[test]
+    fn mbuf_deep_copy() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut mbuf = mp.try_alloc().unwrap();
+            mbuf.extend_from_slice(&[0xff; 512][..]);
+
+            let mut copy = mbuf.deep_copy(&mp).unwrap();
+            assert_eq!(copy.data(), mbuf.data());
+
+            copy.data_mut()[0] = 0;
+            assert_eq!(copy.data()[0], 0);
+            assert_eq!(mbuf.data(), &[0xff; 512][..]);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn mbuf_attach_indirect() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let payload: Vec<u8> = (0..200).collect();
+            let mut direct = mp.try_alloc().unwrap();
+            direct.extend_from_slice(&payload[..]);
+
+            let mut indirect = mp.alloc_indirect().unwrap();
+            unsafe { indirect.attach(&direct, 50, 100) };
+            assert_eq!(indirect.len(), 100);
+            assert_eq!(indirect.data(), &payload[50..150]);
+
+            // the data is shared with the direct mbuf
+            direct.data_mut()[50] = 0xff;
+            assert_eq!(indirect.data()[0], 0xff);
+
+            // the direct mbuf is only freed when the indirect mbuf is dropped
+            assert_eq!(mp.nb_mbufs(), 126);
+            drop(direct);
+            assert_eq!(mp.nb_mbufs(), 126);
+            assert_eq!(indirect.data()[1], 51);
+            drop(indirect);
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn mbuf_attach_extbuf() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut buf: Vec<u8> = (0..200).collect();
+            let buf_ptr = buf.as_mut_ptr();
+            let freed = Arc::new(AtomicBool::new(false));
+            let shinfo = {
+                let freed = freed.clone();
+                // the buffer is returned to its owner by the free callback
+                ExtBufShinfo::new(move || {
+                    assert_eq!(buf[199], 0xff);
+                    drop(buf);
+                    freed.store(true, Ordering::SeqCst);
+                })
+            };
+
+            let mut mbuf = mp.try_alloc().unwrap();
+            mbuf.extend_from_slice(&[1; 50]);
+            unsafe { mbuf.attach_extbuf(buf_ptr, u64::MAX, 200, &shinfo) };
+            assert_eq!(mbuf.len(), 200);
+            assert_eq!(mbuf.data()[10], 10);
+            mbuf.data_mut()[199] = 0xff;
+
+            // the external buffer is shared by an indirect mbuf
+            let mut indirect = mp.alloc_indirect().unwrap();
+            unsafe { indirect.attach(&mbuf, 100, 50) };
+            assert_eq!(indirect.data()[0], 100);
+
+            drop(shinfo);
+            drop(mbuf);
+            assert!(!freed.load(Ordering::SeqCst));
+            drop(indirect);
+            assert!(freed.load(Ordering::SeqCst));
+            assert_eq!(mp.nb_mbufs(), 128);
+
+            // the detached mbuf gets back its own buffer
+            let freed = Arc::new(AtomicBool::new(false));
+            let shinfo = {
+                let freed = freed.clone();
+                ExtBufShinfo::new(move || freed.store(true, Ordering::SeqCst))
+            };
+            let mut ext = [0_u8; 64];
+            let mut mbuf = mp.try_alloc().unwrap();
+            unsafe { mbuf.attach_extbuf(ext.as_mut_ptr(), u64::MAX, 64, &shinfo) };
+            drop(shinfo);
+            mbuf.detach_extbuf();
+            assert!(freed.load(Ordering::SeqCst));
+            assert_eq!(mbuf.len(), 0);
+            mbuf.extend_from_slice(&[2; 100]);
+            assert_eq!(ext, [0; 64]);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn mbuf_kind_predicates() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut mbuf = mp.try_alloc().unwrap();
+            mbuf.extend_from_slice(&[1; 50]);
+            assert!(mbuf.is_direct());
+            assert!(!mbuf.is_indirect());
+            assert!(!mbuf.has_extbuf());
+
+            let mut indirect = mp.alloc_indirect().unwrap();
+            assert!(indirect.is_direct());
+            unsafe { indirect.attach(&mbuf, 10, 20) };
+            assert!(!indirect.is_direct());
+            assert!(indirect.is_indirect());
+            assert!(!indirect.has_extbuf());
+            // the attached mbuf is still direct
+            assert!(mbuf.is_direct());
+            drop(indirect);
+
+            let mut ext = [0_u8; 64];
+            let shinfo = ExtBufShinfo::new(|| {});
+            let mut extmbuf = mp.try_alloc().unwrap();
+            unsafe { extmbuf.attach_extbuf(ext.as_mut_ptr(), u64::MAX, 64, &shinfo) };
+            assert!(!extmbuf.is_direct());
+            assert!(!extmbuf.is_indirect());
+            assert!(extmbuf.has_extbuf());
+
+            // an mbuf attached to an external buffer shares the buffer itself
+            let mut indirect = mp.alloc_indirect().unwrap();
+            unsafe { indirect.attach(&extmbuf, 0, 10) };
+            assert!(!indirect.is_indirect());
+            assert!(indirect.has_extbuf());
+            drop(indirect);
+
+            extmbuf.detach_extbuf();
+            assert!(extmbuf.is_direct());
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn set_clear_ol_flags() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut mbuf = mp.try_alloc().unwrap();
+            assert!(mbuf.ol_flags().is_empty());
+
+            mbuf.set_ol_flags(OlFlags::TX_IPV4 | OlFlags::TX_IP_CKSUM);
+            mbuf.set_ol_flags(OlFlags::TX_UDP_CKSUM);
+            assert_eq!(mbuf.ol_flags().bits(), (1 << 55) | (1 << 54) | (3 << 52));
+            assert_eq!(mbuf.ol_flags() & OlFlags::TX_L4_MASK, OlFlags::TX_UDP_CKSUM);
+
+            mbuf.clear_ol_flags(OlFlags::TX_IP_CKSUM);
+            assert_eq!(mbuf.ol_flags(), OlFlags::TX_IPV4 | OlFlags::TX_UDP_CKSUM);
+            mbuf.clear_ol_flags(OlFlags::TX_L4_MASK);
+            assert_eq!(mbuf.ol_flags().bits(), 1 << 55);
+
+            // the named rx offload reads the same flags
+            mbuf.set_ol_flags(OlFlags::RX_VLAN | OlFlags::RX_VLAN_STRIPPED);
+            assert!(mbuf.rx_offload().vlan_stripped());
+            assert!(mbuf.vlan_stripped());
+            mbuf.clear_ol_flags(OlFlags::all() - OlFlags::INDIRECT - OlFlags::EXTERNAL);
+            assert!(mbuf.ol_flags().is_empty());
+            assert!(mbuf.is_direct());
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn copy_mbuf_out() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let buf: Vec<u8> = (0..200).collect();
+            let mut mbuf = mp.try_alloc().unwrap();
+            assert!(mbuf.copy_to_vec().is_empty());
+            mbuf.extend_from_slice(&buf[..]);
+            assert_eq!(mbuf.copy_to_vec(), buf);
+
+            let slices = mbuf.to_io_slices::<4>();
+            assert_eq!(slices.len(), 1);
+            assert_eq!(&slices[0][..], &buf[..]);
+            drop(slices);
+
+            let mut dst = [0; 50];
+            mbuf.copy_range_to(150, 50, &mut dst[..]).unwrap();
+            assert_eq!(&dst[..], &buf[150..]);
+            mbuf.copy_range_to(10, 5, &mut dst[..]).unwrap();
+            assert_eq!(&dst[..5], &buf[10..15]);
+            assert_eq!(&dst[5..], &buf[155..]);
+
+            assert!(mbuf.copy_range_to(151, 50, &mut dst[..]).is_err());
+            assert!(mbuf.copy_range_to(0, 51, &mut dst[..]).is_err());
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn compare_mbuf_data() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let buf: Vec<u8> = (0..200).collect();
+            let mut fst = mp.try_alloc().unwrap();
+            fst.extend_from_slice(&buf[..]);
+            // the same packet with the header prepended to the payload
+            let mut snd = mp.try_alloc().unwrap();
+            snd.extend_from_slice(&buf[42..]);
+            snd.extend_front_from_slice(&buf[..42]);
+            assert!(fst.data_eq(&snd));
+            assert!(snd.data_eq(&fst));
+            assert!(fst.data_eq_slice(&buf[..]));
+
+            snd.data_mut()[100] ^= 0xff;
+            assert!(!fst.data_eq(&snd));
+            assert!(!snd.data_eq_slice(&buf[..]));
+
+            snd.data_mut()[100] ^= 0xff;
+            snd.truncate(199);
+            assert!(!fst.data_eq(&snd));
+            assert!(!snd.data_eq_slice(&buf[..]));
+            assert!(snd.data_eq_slice(&buf[..199]));
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn mbuf_into_from_raw() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut mbuf = mp.try_alloc().unwrap();
+            mbuf.extend_from_slice(&[0xab; 64][..]);
+            assert_eq!(mp.nb_mbufs(), 127);
+
+            // the raw mbuf is not freed
+            let raw = mbuf.into_raw();
+            assert_eq!(mp.nb_mbufs(), 127);
+
+            let mbuf = unsafe { Mbuf::from_raw(raw) };
+            assert_eq!(mbuf.data(), &[0xab; 64][..]);
+            drop(mbuf);
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn mbuf_rx_tx_timestamp() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut mbuf = mp.try_alloc().unwrap();
+            assert_eq!(mbuf.rx_timestamp(), None);
+            assert_eq!(mbuf.tx_timestamp(), None);
+
+            // stamp the mbuf like a pmd with the timestamp offload
+            let dyn_ts = crate::timestamp::DynTimestamp::rx().unwrap();
+            unsafe { dyn_ts.write(mbuf.ptr.as_ptr(), 0x1234_5678_9abc) };
+            assert_eq!(mbuf.rx_timestamp(), Some(0x1234_5678_9abc));
+            // the rx and tx timestamps use different flags
+            assert_eq!(mbuf.tx_timestamp(), None);
+
+            mbuf.set_tx_timestamp(1_000_000).unwrap();
+            assert_eq!(mbuf.tx_timestamp(), Some(1_000_000));
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn mbuf_headroom_tailroom_adj_trim() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut mbuf = mp.try_alloc().unwrap();
+            assert_eq!(mbuf.headroom(), Mempool::MBUF_HEADROOM);
+            assert_eq!(mbuf.tailroom(), MempoolConf::DATAROOM);
+
+            let data: Vec<u8> = (0..100).collect();
+            mbuf.extend_from_slice(&data[..]);
+            assert_eq!(mbuf.tailroom(), MempoolConf::DATAROOM - 100);
+
+            let remaining = mbuf.adj(14).unwrap();
+            assert_eq!(remaining, &data[14..]);
+            assert_eq!(mbuf.len(), 86);
+            assert_eq!(mbuf.headroom(), Mempool::MBUF_HEADROOM + 14);
+            assert!(mbuf.adj(87).is_none());
+            assert_eq!(mbuf.len(), 86);
+
+            assert!(mbuf.trim(6));
+            assert_eq!(mbuf.data(), &data[14..94]);
+            assert_eq!(mbuf.tailroom(), MempoolConf::DATAROOM - 94);
+            assert!(!mbuf.trim(81));
+            assert_eq!(mbuf.len(), 80);
+            unsafe {
+                assert_eq!(mbuf.ptr.as_ref().pkt_len, 80);
+                assert_eq!(mbuf.ptr.as_ref().data_len, 80);
+            }
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn mbuf_hexdump() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            // the head of an arp request
+            let frame = [
+                0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x16, 0x3e, 0x0a, 0x0b, 0x0c, 0x08, 0x06,
+                0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01,
+            ];
+            let mut mbuf = mp.try_alloc().unwrap();
+            mbuf.extend_from_slice(&frame[..]);
+
+            let dump = mbuf.hexdump(usize::MAX);
+            assert_eq!(
+                dump,
+                "00000000: ffff ffff ffff 0016 3e0a 0b0c 0806 0001  ........>.......\n\
+                 00000010: 0800 0604 0001                           ......\n"
+            );
+            assert_eq!(
+                mbuf.hexdump(4),
+                "00000000: ffff ffff                                ....\n"
+            );
+            assert_eq!(mbuf.hexdump(0), "");
+
+            mbuf.dump_to_stderr();
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn mbuf_priv_data() {
+        DpdkOption::new().init().unwrap();
+
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        struct Meta {
+            flow_id: u64,
+            ts: u32,
+            port: u16,
+        }
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            config.set_priv_size(std::mem::size_of::<Meta>() as u16);
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut mbuf = mp.try_alloc().unwrap();
+            mbuf.extend_from_slice(&[0xff; 64][..]);
+            let meta = Meta {
+                flow_id: 0xdeadbeef,
+                ts: 1024,
+                port: 80,
+            };
+            unsafe { *mbuf.priv_data_mut::<Meta>() = meta };
+
+            assert_eq!(unsafe { mbuf.priv_data::<Meta>() }, &meta);
+            // the private area does not overlap with the packet data
+            assert_eq!(mbuf.data(), &[0xff; 64][..]);
+            assert_eq!(mbuf.front_capacity(), Mempool::MBUF_HEADROOM as usize);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn mbuf_port_through_ring() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let ring = Ring::try_create(
+                "wtf".to_string(),
+                &RingConf {
+                    count: 64,
+                    socket_id: 0,
+                    flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ,
+                },
+            )
+            .unwrap();
+
+            let mut batch = arrayvec::ArrayVec::<_, 32>::new();
+            mp.fill_batch(&mut batch);
+            for (i, mbuf) in batch.iter_mut().enumerate() {
+                mbuf.set_port(i as u16 % 4);
+            }
+            assert_eq!(ring.enqueue_burst(&mut batch), 32);
+
+            assert_eq!(ring.dequeue_burst(&mut batch), 32);
+            for (i, mbuf) in batch.iter().enumerate() {
+                assert_eq!(mbuf.port(), i as u16 % 4);
+            }
+            Mempool::free_batch(&mut batch);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn validate_corrupted_mbuf() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut mbuf = mp.try_alloc().unwrap();
+            mbuf.extend_from_slice(&[0xab; 100][..]);
+            mbuf.trim_front(10);
+            assert!(mbuf.trim(10));
+            assert!(mbuf.validate().is_ok());
+
+            // the data length is out of sync with the packet length
+            unsafe { (*(mbuf.as_ptr() as *mut rpkt_dpdk_sys::rte_mbuf)).data_len += 1 };
+            let err = mbuf.validate().err().unwrap();
+            assert_eq!(err.kind(), crate::error::ErrorKind::ServiceError);
+            assert!(err.to_string().contains("pkt_len"));
+
+            // the data exceeds the buffer
+            unsafe {
+                let raw = &mut *(mbuf.as_ptr() as *mut rpkt_dpdk_sys::rte_mbuf);
+                raw.data_len = raw.buf_len;
+                raw.pkt_len = u32::from(raw.buf_len);
+            }
+            assert!(mbuf.validate().is_err());
+
+            mbuf.truncate(0);
+            assert!(mbuf.validate().is_ok());
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn offsets_parsed_by_pmd() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut mbuf = mp.try_alloc().unwrap();
+            mbuf.extend_from_slice(&[0; 100][..]);
+            // a fresh mbuf is not parsed
+            assert_eq!(mbuf.parsed_offsets(), None);
+
+            // L2_ETHER_VLAN | L3_IPV4 | L4_TCP
+            let set_ptype = |mbuf: &mut Mbuf, ptype: u32, l4_len: u64| unsafe {
+                let raw = &mut *(mbuf.as_ptr() as *mut rpkt_dpdk_sys::rte_mbuf);
+                raw.__bindgen_anon_1.packet_type = ptype;
+                raw.__bindgen_anon_3.__bindgen_anon_1.set_l4_len(l4_len);
+            };
+            set_ptype(&mut mbuf, 0x0116, 20);
+            mbuf.set_l2_len(18);
+            assert_eq!(mbuf.parsed_offsets(), None);
+            mbuf.set_l3_len(20);
+            assert_eq!(
+                mbuf.parsed_offsets(),
+                Some(offload::LayerOffsets {
+                    l3: 18,
+                    l4: 38,
+                    payload: Some(58),
+                })
+            );
+
+            // L2_ETHER | L3_IPV6 | L4_FRAG
+            set_ptype(&mut mbuf, 0x0341, 0);
+            mbuf.set_l2_len(14);
+            mbuf.set_l3_len(48);
+            assert_eq!(
+                mbuf.parsed_offsets(),
+                Some(offload::LayerOffsets {
+                    l3: 14,
+                    l4: 62,
+                    payload: None,
+                })
+            );
+
+            // the l3 type is unknown
+            set_ptype(&mut mbuf, 0x0001, 0);
+            assert_eq!(mbuf.parsed_offsets(), None);
+
+            // the offsets exceed the packet
+            set_ptype(&mut mbuf, 0x0211, 8);
+            mbuf.set_l3_len(90);
+            assert_eq!(mbuf.parsed_offsets(), None);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn mempool_with_unaligned_priv_size() {
+        DpdkOption::new().init().unwrap();
+
+        let mut config = MempoolConf::default();
+        config.set_priv_size(12);
+        assert_eq!(service().mempool_create("wtf", &config).is_err(), true);
+    }
 }
@@ -1,6 +1,7 @@
+use std::cell::UnsafeCell;
 use std::convert::TryFrom;
 use std::ffi::CString;
-use std::os::raw::c_char;
+use std::os::raw::{c_char, c_void};
 use std::ptr::NonNull;
 use std::sync::Arc;
 
@@ -8,20 +9,61 @@ use arrayvec::ArrayVec;
 use rpkt_dpdk_sys as ffi;
 
 use crate::error::*;
-use crate::Mbuf;
+use crate::{names, Mbuf, PacketBuilder, ProcType, RxQueueConf, TxQueueConf};
+
+/// The backend that stores the free objects of a mempool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MempoolOps {
+    /// The default ring-based backend, safe for multiple producers and consumers.
+    Ring,
+    /// A lock-based stack, which returns the most recently freed objects first.
+    /// It can outperform the ring when the pool is shared by cores with poor
+    /// cache locality.
+    Stack,
+    /// A bucket-based backend that keeps the objects of a bucket contiguous.
+    BucketMp,
+}
+
+impl MempoolOps {
+    /// Return the name that the backend is registered with in dpdk.
+    pub fn name(&self) -> &'static str {
+        match self {
+            MempoolOps::Ring => "ring_mp_mc",
+            MempoolOps::Stack => "stack",
+            MempoolOps::BucketMp => "bucket",
+        }
+    }
+
+    /// Check whether the backend driver is linked and registered.
+    pub fn is_registered(&self) -> bool {
+        let cname = CString::new(self.name()).unwrap();
+        unsafe {
+            ffi::rte_mempool_ops_registered_(cname.as_bytes_with_nul().as_ptr() as *const c_char)
+                != 0
+        }
+    }
+}
 
 #[derive(Clone, Copy, Debug)]
 pub struct MempoolConf {
     pub nb_mbufs: u32,
     pub per_core_caches: u32,
     pub dataroom: u16,
+    pub priv_size: u16,
+    /// The room in front of the packet data of a newly allocated mbuf, it is
+    /// reserved on top of `dataroom`.
+    pub headroom: u16,
     pub socket_id: u32,
+    /// The backend of the mempool, the platform default is used if it is `None`.
+    pub ops: Option<MempoolOps>,
 }
 
 impl MempoolConf {
     pub const DATAROOM: u16 = ffi::RTE_MBUF_DEFAULT_DATAROOM as u16;
     pub const NB_MBUFS: u32 = 2048;
     pub const PER_CORE_CACHES: u32 = 0;
+    pub const PRIV_SIZE: u16 = 0;
+    pub const HEADROOM: u16 = Mempool::MBUF_HEADROOM;
 
     pub fn new() -> Self {
         Self::default()
@@ -39,9 +81,32 @@ impl MempoolConf {
         self.dataroom = val;
     }
 
+    /// Set the size of the private area that sits between the mbuf header
+    /// and the data buffer, it must be a multiple of 8.
+    pub fn set_priv_size(&mut self, val: u16) {
+        self.priv_size = val;
+    }
+
+    /// Set the headroom of the mbufs allocated from the mempool.
+    ///
+    /// A larger headroom lets the encapsulation headers be prepended with
+    /// `Mbuf::extend_front_from_slice` instead of moving the packet data. The
+    /// data buffer of each mbuf grows by the headroom, so the usable data room
+    /// stays `dataroom`, and a smaller headroom than the default shrinks the
+    /// buffers. The headroom is applied by the allocation methods of
+    /// `Mempool`, the drivers still receive the packets at the compile-time
+    /// `Mempool::MBUF_HEADROOM`, and the extra room ends up behind the data.
+    pub fn set_headroom(&mut self, val: u16) {
+        self.headroom = val;
+    }
+
     pub fn set_socket_id(&mut self, val: u32) {
         self.socket_id = val;
     }
+
+    pub fn set_ops(&mut self, val: MempoolOps) {
+        self.ops = Some(val);
+    }
 }
 
 impl Default for MempoolConf {
@@ -50,15 +115,167 @@ impl Default for MempoolConf {
             nb_mbufs: Self::NB_MBUFS,
             per_core_caches: Self::PER_CORE_CACHES,
             dataroom: Self::DATAROOM,
+            priv_size: Self::PRIV_SIZE,
+            headroom: Self::HEADROOM,
             socket_id: 0,
+            ops: None,
         }
     }
 }
 
+/// A calculator of the number of mbufs of a mempool, from the places where the
+/// mbufs of a pipeline are held at the same time.
+///
+/// An mbuf is in flight while it sits in a rx or tx descriptor of a queue, in
+/// a ring between two stages, in the per-lcore cache of the mempool, or in the
+/// burst that an lcore is processing. A mempool that is smaller than the sum
+/// runs dry under load, and the NIC starts to drop the received packets, e.g.
+/// when the tx descriptors are full and the rx descriptors can not be
+/// refilled.
+#[derive(Clone, Debug)]
+pub struct PoolSizer {
+    pub nb_ports: u16,
+    /// The number of the rx queues of each port.
+    pub nb_rx_queues: u16,
+    /// The number of the tx queues of each port.
+    pub nb_tx_queues: u16,
+    pub nb_rx_desc: u16,
+    pub nb_tx_desc: u16,
+    /// The capacities of the rings that hold the mbufs between the stages.
+    pub ring_depths: Vec<u32>,
+    /// The number of the lcores that allocate or free the mbufs.
+    pub nb_lcores: u32,
+    /// The `MempoolConf::per_core_caches` of the mempool.
+    pub cache_size: u32,
+    /// The largest batch of mbufs that an lcore holds.
+    pub burst_size: u32,
+    /// The extra mbufs on top of the in-flight ones, as a percentage.
+    pub headroom_percent: u32,
+}
+
+impl PoolSizer {
+    pub const NB_PORTS: u16 = 1;
+    pub const NB_RX_QUEUES: u16 = 1;
+    pub const NB_TX_QUEUES: u16 = 1;
+    pub const NB_LCORES: u32 = 1;
+    pub const CACHE_SIZE: u32 = MempoolConf::PER_CORE_CACHES;
+    pub const BURST_SIZE: u32 = 32;
+    pub const HEADROOM_PERCENT: u32 = 20;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_nb_ports(&mut self, val: u16) {
+        self.nb_ports = val;
+    }
+
+    pub fn set_nb_rx_queues(&mut self, val: u16) {
+        self.nb_rx_queues = val;
+    }
+
+    pub fn set_nb_tx_queues(&mut self, val: u16) {
+        self.nb_tx_queues = val;
+    }
+
+    pub fn set_nb_rx_desc(&mut self, val: u16) {
+        self.nb_rx_desc = val;
+    }
+
+    pub fn set_nb_tx_desc(&mut self, val: u16) {
+        self.nb_tx_desc = val;
+    }
+
+    pub fn set_ring_depths(&mut self, val: Vec<u32>) {
+        self.ring_depths = val;
+    }
+
+    pub fn set_nb_lcores(&mut self, val: u32) {
+        self.nb_lcores = val;
+    }
+
+    pub fn set_cache_size(&mut self, val: u32) {
+        self.cache_size = val;
+    }
+
+    pub fn set_burst_size(&mut self, val: u32) {
+        self.burst_size = val;
+    }
+
+    pub fn set_headroom_percent(&mut self, val: u32) {
+        self.headroom_percent = val;
+    }
+
+    /// Return the number of the mbufs that may be in flight at the same time.
+    ///
+    /// A per-lcore cache grows up to 1.5 times its size before it is flushed
+    /// to the mempool, so each lcore may hold that many mbufs in its cache.
+    pub fn nb_in_flight(&self) -> u64 {
+        let nb_queues = u64::from(self.nb_ports)
+            * (u64::from(self.nb_rx_queues) * u64::from(self.nb_rx_desc)
+                + u64::from(self.nb_tx_queues) * u64::from(self.nb_tx_desc));
+        let nb_rings: u64 = self.ring_depths.iter().map(|depth| u64::from(*depth)).sum();
+        let nb_per_lcore = u64::from(self.cache_size) * 3 / 2 + u64::from(self.burst_size);
+        nb_queues + nb_rings + u64::from(self.nb_lcores) * nb_per_lcore
+    }
+
+    /// Return the recommended `MempoolConf::nb_mbufs`.
+    ///
+    /// The in-flight mbufs are increased by `headroom_percent`, and then
+    /// rounded up to a power of two minus one, which is the most memory
+    /// efficient size of a ring-based mempool. The recommendation saturates at
+    /// `u32::MAX`.
+    pub fn recommend(&self) -> u32 {
+        let nb_mbufs = self.nb_in_flight() * (100 + u64::from(self.headroom_percent));
+        let nb_mbufs = (nb_mbufs + 99) / 100;
+        let nb_mbufs = (nb_mbufs + 1).next_power_of_two() - 1;
+        u32::try_from(nb_mbufs).unwrap_or(u32::MAX)
+    }
+}
+
+impl Default for PoolSizer {
+    fn default() -> Self {
+        Self {
+            nb_ports: Self::NB_PORTS,
+            nb_rx_queues: Self::NB_RX_QUEUES,
+            nb_tx_queues: Self::NB_TX_QUEUES,
+            nb_rx_desc: RxQueueConf::NB_RX_DESC,
+            nb_tx_desc: TxQueueConf::NB_TX_DESC,
+            ring_depths: Vec::new(),
+            nb_lcores: Self::NB_LCORES,
+            cache_size: Self::CACHE_SIZE,
+            burst_size: Self::BURST_SIZE,
+            headroom_percent: Self::HEADROOM_PERCENT,
+        }
+    }
+}
+
+/// The per-lcore cache of a mempool, see `Mempool::default_cache`.
+#[repr(transparent)]
+pub struct MempoolCache(UnsafeCell<ffi::rte_mempool_cache>);
+
+impl MempoolCache {
+    /// Return the number of the cached mbufs.
+    pub fn len(&self) -> u32 {
+        unsafe { (*self.0.get()).len }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return the size of the cache, see `MempoolConf::per_core_caches`.
+    pub fn size(&self) -> u32 {
+        unsafe { (*self.0.get()).size }
+    }
+}
+
 #[derive(Clone)]
 pub struct Mempool {
     ptr: NonNull<ffi::rte_mempool>,
     counter: Arc<()>,
+    owned: bool,
+    headroom: u16,
 }
 
 unsafe impl Send for Mempool {}
@@ -71,12 +288,73 @@ impl Mempool {
     pub fn try_alloc(&self) -> Option<Mbuf> {
         let raw = unsafe { ffi::rte_pktmbuf_alloc_(self.ptr.as_ptr()) };
         if !raw.is_null() {
+            unsafe { self.reserve_headroom(raw) };
             Some(unsafe { Mbuf::from_raw(raw) })
         } else {
             None
         }
     }
 
+    /// Return the headroom of the mbufs allocated from the mempool, see
+    /// `MempoolConf::set_headroom`.
+    ///
+    /// A looked-up mempool always reports the default headroom.
+    #[inline]
+    pub fn headroom(&self) -> u16 {
+        self.headroom
+    }
+
+    // The dpdk allocation resets the data offset to the compile-time headroom.
+    #[inline]
+    unsafe fn reserve_headroom(&self, raw: *mut ffi::rte_mbuf) {
+        if self.headroom != Self::MBUF_HEADROOM {
+            (*raw).data_off = self.headroom;
+        }
+    }
+
+    /// Allocate an mbuf to be attached to the data of another mbuf with
+    /// `Mbuf::attach`.
+    ///
+    /// The data room of an attached mbuf is never used, so the indirect mbufs
+    /// can be allocated from a dedicated mempool with a zero `dataroom`.
+    #[inline]
+    pub fn alloc_indirect(&self) -> Option<Mbuf> {
+        self.try_alloc()
+    }
+
+    /// Allocate an mbuf and build a packet in it with `f`.
+    ///
+    /// The `PacketBuilder` passed to `f` appends directly to the data room of
+    /// the mbuf, so the returned mbuf is ready to be sent. Return `None` if the
+    /// mempool is exhausted.
+    #[inline]
+    pub fn build_packet(&self, f: impl FnOnce(&mut PacketBuilder)) -> Option<Mbuf> {
+        let mut mbuf = self.try_alloc()?;
+        f(&mut PacketBuilder::new(&mut mbuf));
+        Some(mbuf)
+    }
+
+    /// Allocate a burst of `N` mbufs at once and build a packet in the i-th
+    /// mbuf with `f(i, builder)`, e.g. to vary the flow of each packet.
+    ///
+    /// The returned burst is ready to be sent. Return `None` if the mempool
+    /// can not provide all the `N` mbufs, nothing is allocated in that case.
+    #[inline]
+    pub fn build_burst<const N: usize>(
+        &self,
+        mut f: impl FnMut(usize, &mut PacketBuilder),
+    ) -> Option<ArrayVec<Mbuf, N>> {
+        let mut batch = ArrayVec::new();
+        self.fill_batch(&mut batch);
+        if batch.len() < N {
+            return None;
+        }
+        for (idx, mbuf) in batch.iter_mut().enumerate() {
+            f(idx, &mut PacketBuilder::new(mbuf));
+        }
+        Some(batch)
+    }
+
     #[inline]
     pub fn fill_batch<const N: usize>(&self, batch: &mut ArrayVec<Mbuf, N>) {
         assert!(N <= usize::from(u16::MAX));
@@ -88,6 +366,11 @@ impl Mempool {
             let alloc_nb =
                 ffi::rte_pktmbuf_alloc_bulk_(self.ptr.as_ptr(), mbufs, (N - batch_len) as u32);
             if alloc_nb == 0 {
+                if self.headroom != Self::MBUF_HEADROOM {
+                    for i in batch_len..N {
+                        self.reserve_headroom(*mbufs.add(i - batch_len));
+                    }
+                }
                 batch.set_len(N);
             }
         }
@@ -108,48 +391,198 @@ impl Mempool {
         }
     }
 
+    /// Allocate an mbuf without the per-lcore cache.
+    ///
+    /// The per-lcore cache is only safe for the EAL lcores, the other threads
+    /// should allocate and free the mbufs with `generic_get` and `generic_put`.
+    #[inline]
+    pub fn generic_get(&self) -> Option<Mbuf> {
+        let raw = unsafe { ffi::rte_pktmbuf_alloc_nocache_(self.ptr.as_ptr()) };
+        if !raw.is_null() {
+            unsafe { self.reserve_headroom(raw) };
+            Some(unsafe { Mbuf::from_raw(raw) })
+        } else {
+            None
+        }
+    }
+
+    /// Free an mbuf allocated from this mempool without the per-lcore cache.
+    #[inline]
+    pub fn generic_put(&self, mbuf: Mbuf) {
+        let raw = mbuf.into_raw();
+        unsafe {
+            debug_assert!((*raw).pool == self.ptr.as_ptr());
+            ffi::rte_pktmbuf_free_nocache_(raw);
+        }
+    }
+
+    /// Return the cache of the current lcore.
+    ///
+    /// Return `None` if the mempool has no per-lcore caches, or the current
+    /// thread is not an EAL lcore.
+    pub fn default_cache(&self) -> Option<&MempoolCache> {
+        unsafe {
+            let raw = ffi::rte_mempool_default_cache_(self.ptr.as_ptr(), ffi::rte_lcore_id_());
+            (raw as *const MempoolCache).as_ref()
+        }
+    }
+
+    /// Return the mbufs in the cache of the current lcore to the mempool.
+    ///
+    /// The cached mbufs can not be allocated by the other lcores, so the cache
+    /// should be flushed before the lcore stops using the mempool.
+    pub fn cache_flush(&self) {
+        if let Some(cache) = self.default_cache() {
+            unsafe { ffi::rte_mempool_cache_flush_(cache.0.get(), self.ptr.as_ptr()) }
+        }
+    }
+
+    /// Fill the cache of the current lcore before the hot loop starts, so that
+    /// the first burst does not pay for a cold cache.
+    ///
+    /// `n` mbufs are allocated and freed at once, and their data rooms are
+    /// prefetched on the way. The cache is a stack, so the next allocations get
+    /// the same, cache-hot mbufs back. This function must be called on the
+    /// lcore that will use the mempool, only its own cache is populated.
+    pub fn warmup(&self, n: u32) {
+        let mut mbufs = Vec::with_capacity(n as usize);
+        while mbufs.len() < n as usize {
+            let Some(mbuf) = self.try_alloc() else {
+                break;
+            };
+            unsafe { ffi::rte_prefetch0_(mbuf.data().as_ptr() as *const c_void) };
+            mbufs.push(mbuf);
+        }
+    }
+
     #[inline]
     pub fn nb_mbufs(&self) -> u32 {
         unsafe { ffi::rte_mempool_avail_count(self.as_ptr()) }
     }
 
+    /// Return the number of the available mbufs in the common pool, the mbufs
+    /// in the per-lcore caches are not counted.
+    #[inline]
+    pub fn nb_common_mbufs(&self) -> u32 {
+        unsafe { ffi::rte_mempool_common_count_(self.as_ptr()) }
+    }
+
     // modified to pub for netbricks_port
     pub fn as_ptr(&self) -> *const ffi::rte_mempool {
         self.ptr.as_ptr()
     }
 
     pub(crate) fn try_create(mpool_name: String, conf: &MempoolConf) -> Result<Self> {
+        if ProcType::current() == ProcType::Secondary {
+            return Error::service_err("can not create mempool in secondary process").to_err();
+        }
+
         let err = Error::service_err("invalid mempool config");
-        let data_room_size = conf.dataroom.checked_add(Self::MBUF_HEADROOM).ok_or(err)?;
+        if conf.priv_size % 8 != 0 {
+            return err.to_err();
+        }
+        let data_room_size = conf.dataroom.checked_add(conf.headroom).ok_or(err)?;
         let socket_id = i32::try_from(conf.socket_id).map_err(|_| err)?;
 
+        names::check_len(
+            &mpool_name,
+            names::MEMPOOL_NAMESIZE,
+            "mempool name is too long",
+        )?;
+
         // create the mempool
         let cname =
             CString::new(mpool_name).map_err(|_| Error::service_err("invalid mempool name"))?;
-        let raw = unsafe {
-            ffi::rte_pktmbuf_pool_create(
-                cname.as_bytes_with_nul().as_ptr() as *const c_char,
-                conf.nb_mbufs,
-                conf.per_core_caches,
-                0,
-                data_room_size,
-                socket_id,
-            )
+        let raw = match conf.ops {
+            None => unsafe {
+                ffi::rte_pktmbuf_pool_create(
+                    cname.as_bytes_with_nul().as_ptr() as *const c_char,
+                    conf.nb_mbufs,
+                    conf.per_core_caches,
+                    conf.priv_size,
+                    data_room_size,
+                    socket_id,
+                )
+            },
+            Some(ops) => {
+                // Otherwise the creation fails with a bare EINVAL.
+                if !ops.is_registered() {
+                    return Error::service_err("mempool ops is not registered").to_err();
+                }
+                let ops_name = CString::new(ops.name()).unwrap();
+                // The ops are set with `rte_mempool_set_ops_byname` before the
+                // mempool is populated.
+                unsafe {
+                    ffi::rte_pktmbuf_pool_create_by_ops(
+                        cname.as_bytes_with_nul().as_ptr() as *const c_char,
+                        conf.nb_mbufs,
+                        conf.per_core_caches,
+                        conf.priv_size,
+                        data_room_size,
+                        socket_id,
+                        ops_name.as_bytes_with_nul().as_ptr() as *const c_char,
+                    )
+                }
+            }
         };
 
-        let ptr = NonNull::new(raw).ok_or_else(|| {
-            Error::ffi_err(unsafe { ffi::rte_errno_() }, "fail to allocate mempool")
-        })?;
+        let ptr = check_ptr(raw, "fail to allocate mempool")?;
 
         Ok(Self {
             ptr,
             counter: Arc::new(()),
+            owned: true,
+            headroom: conf.headroom,
         })
     }
 
+    // The looked-up mempool is owned by the primary process and is never freed by us.
+    pub(crate) fn try_lookup(mpool_name: String) -> Result<Self> {
+        names::check_len(
+            &mpool_name,
+            names::MEMPOOL_NAMESIZE,
+            "mempool name is too long",
+        )?;
+        let cname =
+            CString::new(mpool_name).map_err(|_| Error::service_err("invalid mempool name"))?;
+        let raw =
+            unsafe { ffi::rte_mempool_lookup(cname.as_bytes_with_nul().as_ptr() as *const c_char) };
+
+        let ptr = check_ptr(raw, "fail to lookup mempool")?;
+
+        Ok(Self {
+            ptr,
+            counter: Arc::new(()),
+            owned: false,
+            headroom: Self::MBUF_HEADROOM,
+        })
+    }
+
+    // Check whether the mempool is created with `conf`.
+    pub(crate) fn matches(&self, conf: &MempoolConf) -> bool {
+        let raw = unsafe { self.ptr.as_ref() };
+        // `rte_pktmbuf_pool_create` packs the mbuf header, the private area and
+        // the data room into one element.
+        let elt_size = std::mem::size_of::<ffi::rte_mbuf>() as u32
+            + u32::from(conf.priv_size)
+            + u32::from(conf.dataroom)
+            + u32::from(conf.headroom);
+        raw.size == conf.nb_mbufs
+            && raw.cache_size == conf.per_core_caches
+            && raw.elt_size == elt_size
+            && self.headroom == conf.headroom
+    }
+
     pub(crate) unsafe fn delete(self) {
-        assert!(self.full() == true && self.in_use() == false);
-        ffi::rte_mempool_free(self.ptr.as_ptr());
+        assert!(self.in_use() == false);
+        if self.owned {
+            assert!(self.full() == true);
+            ffi::rte_mempool_free(self.ptr.as_ptr());
+        }
+    }
+
+    pub(crate) fn owned(&self) -> bool {
+        self.owned
     }
 
     pub(crate) fn in_use(&self) -> bool {
@@ -164,6 +597,75 @@ impl Mempool {
     }
 }
 
+/// One mempool on each socket with memory, so that each lcore allocates the
+/// mbufs from the memory of its own socket.
+///
+/// The mempools are created with the service, named `<prefix>_<socket_id>`.
+pub struct PerSocketPools {
+    pools: Vec<(u32, String, Mempool)>,
+}
+
+impl PerSocketPools {
+    /// Create a mempool with `conf` on each socket, `conf.socket_id` is ignored.
+    pub fn try_create(prefix: &str, conf: &MempoolConf) -> Result<Self> {
+        let mut pools = Self { pools: Vec::new() };
+        let nb_sockets = unsafe { ffi::rte_socket_count() };
+        for idx in 0..nb_sockets {
+            let socket_id = unsafe { ffi::rte_socket_id_by_idx(idx) };
+            let socket_id = u32::try_from(socket_id)
+                .map_err(|_| Error::ffi_err(unsafe { ffi::rte_errno_() }, "invalid socket"))?;
+
+            let name = format!("{prefix}_{socket_id}");
+            let mut conf = *conf;
+            conf.set_socket_id(socket_id);
+            // The mempools created so far are freed if the creation fails.
+            let mp = match crate::service().mempool_create(&name, &conf) {
+                Ok(mp) => mp,
+                Err(err) => {
+                    pools.free()?;
+                    return Err(err);
+                }
+            };
+            pools.pools.push((socket_id, name, mp));
+        }
+        Ok(pools)
+    }
+
+    /// Return the mempool on `socket_id`.
+    pub fn pool_for_socket(&self, socket_id: u32) -> Option<&Mempool> {
+        self.pools
+            .iter()
+            .find(|(id, _, _)| *id == socket_id)
+            .map(|(_, _, mp)| mp)
+    }
+
+    /// Return the mempool on the socket of the current lcore.
+    ///
+    /// The threads that are not EAL lcores have no socket, they get the
+    /// mempool on the first socket.
+    pub fn pool_for_current(&self) -> &Mempool {
+        let socket_id = unsafe { ffi::rte_socket_id() };
+        self.pool_for_socket(socket_id)
+            .unwrap_or_else(|| &self.pools[0].2)
+    }
+
+    /// Return the socket ids of the mempools.
+    pub fn socket_ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.pools.iter().map(|(id, _, _)| *id)
+    }
+
+    /// Free all the mempools from the service.
+    ///
+    /// This function fails if the mbufs of a mempool are still in use.
+    pub fn free(self) -> Result<()> {
+        for (_, name, mp) in self.pools {
+            drop(mp);
+            crate::service().mempool_free(&name)?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::*;
@@ -191,6 +693,185 @@ mod tests {
         assert_eq!(res.is_err(), true);
     }
 
+    #[test]
+    fn pool_sizer_counts_in_flight_mbufs() {
+        // 2 ports with 4 rx and 4 tx queues, 2 rings between the rx and the
+        // worker lcores, and 6 lcores
+        let mut sizer = PoolSizer::new();
+        sizer.set_nb_ports(2);
+        sizer.set_nb_rx_queues(4);
+        sizer.set_nb_tx_queues(4);
+        sizer.set_nb_rx_desc(1024);
+        sizer.set_nb_tx_desc(512);
+        sizer.set_ring_depths(vec![4096, 4096]);
+        sizer.set_nb_lcores(6);
+        sizer.set_cache_size(256);
+        sizer.set_burst_size(32);
+
+        // 12288 in the descriptors, 8192 in the rings, 6 * (384 + 32) in the
+        // caches and the bursts
+        assert_eq!(sizer.nb_in_flight(), 12288 + 8192 + 2496);
+        let nb_mbufs = sizer.recommend();
+        assert_eq!(nb_mbufs, 32767);
+        assert!(u64::from(nb_mbufs) >= sizer.nb_in_flight() * 120 / 100);
+
+        // every source of the in-flight mbufs is counted
+        let nb_in_flight = sizer.nb_in_flight();
+        for update in [
+            (|sizer: &mut PoolSizer| sizer.nb_ports += 1) as fn(&mut PoolSizer),
+            |sizer| sizer.nb_rx_queues += 1,
+            |sizer| sizer.nb_tx_queues += 1,
+            |sizer| sizer.nb_rx_desc += 1,
+            |sizer| sizer.nb_tx_desc += 1,
+            |sizer| sizer.ring_depths.push(64),
+            |sizer| sizer.nb_lcores += 1,
+            |sizer| sizer.cache_size += 2,
+            |sizer| sizer.burst_size += 1,
+        ] {
+            let mut sizer = sizer.clone();
+            update(&mut sizer);
+            assert!(sizer.nb_in_flight() > nb_in_flight);
+        }
+
+        // a power of two minus one is kept, and the size saturates
+        sizer.set_headroom_percent(0);
+        sizer.set_ring_depths(vec![32767 - 12288 - 2496]);
+        assert_eq!(sizer.recommend(), 32767);
+        sizer.set_ring_depths(vec![u32::MAX; 2]);
+        assert_eq!(sizer.recommend(), u32::MAX);
+        assert_eq!(PoolSizer::new().recommend(), 2047);
+    }
+
+    #[test]
+    fn get_or_create_existing_mempool() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            config.dataroom = 512;
+
+            // the mempool exists in the dpdk memory, but not in the service
+            let mp = Mempool::try_create("goc".to_string(), &config).unwrap();
+            let existing = service().mempool_get_or_create("goc", &config).unwrap();
+            assert_eq!(existing.as_ptr(), mp.as_ptr());
+            assert!(service().mempool_get_or_create("goc", &config).is_err());
+            drop(existing);
+            service().mempool_free("goc").unwrap();
+
+            config.nb_mbufs = 256;
+            assert!(service().mempool_get_or_create("goc", &config).is_err());
+            config.nb_mbufs = 128;
+            config.dataroom = 1024;
+            assert!(service().mempool_get_or_create("goc", &config).is_err());
+
+            unsafe { Mempool::delete(mp) };
+
+            let mp = service().mempool_get_or_create("goc", &config).unwrap();
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("goc").unwrap();
+    }
+
+    #[test]
+    fn generic_get_from_non_eal_thread() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            config.per_core_caches = 32;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mp = std::thread::spawn(move || {
+                // a non-EAL thread has no per-lcore cache
+                assert!(mp.default_cache().is_none());
+                mp.cache_flush();
+
+                let mut mbufs: Vec<_> = (0..128).map(|_| mp.generic_get().unwrap()).collect();
+                assert!(mp.generic_get().is_none());
+                assert_eq!(mp.nb_mbufs(), 0);
+
+                mbufs[0].extend_from_slice(&[0xff; 64][..]);
+                for mbuf in mbufs.drain(..) {
+                    mp.generic_put(mbuf);
+                }
+                assert_eq!(mp.nb_mbufs(), 128);
+
+                // the mbufs are reset
+                let mbuf = mp.generic_get().unwrap();
+                assert_eq!(mbuf.len(), 0);
+                mp.generic_put(mbuf);
+                mp
+            })
+            .join()
+            .unwrap();
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn warmup_fills_the_cache() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 1024;
+            config.per_core_caches = 64;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+            let cache_size = mp.default_cache().unwrap().size();
+            assert!(mp.default_cache().unwrap().is_empty());
+            assert_eq!(mp.nb_common_mbufs(), 1024);
+
+            mp.warmup(cache_size);
+            assert!(mp.default_cache().unwrap().len() >= cache_size);
+            assert_eq!(mp.nb_mbufs(), 1024);
+
+            // the next `cache_size` allocations are served by the cache
+            let nb_common = mp.nb_common_mbufs();
+            let mbufs: Vec<_> = (0..cache_size).map(|_| mp.try_alloc().unwrap()).collect();
+            assert_eq!(mp.nb_common_mbufs(), nb_common);
+            drop(mbufs);
+
+            mp.cache_flush();
+            assert_eq!(mp.nb_common_mbufs(), 1024);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn per_socket_pools() {
+        DpdkOption::new().init().unwrap();
+
+        let mut config = MempoolConf::default();
+        config.nb_mbufs = 128;
+        let pools = PerSocketPools::try_create("wtf", &config).unwrap();
+        let socket_ids: Vec<_> = pools.socket_ids().collect();
+        assert!(!socket_ids.is_empty());
+        for socket_id in socket_ids.iter() {
+            let mp = pools.pool_for_socket(*socket_id).unwrap();
+            assert_eq!(mp.nb_mbufs(), 128);
+            assert!(service().mempool(&format!("wtf_{socket_id}")).is_ok());
+        }
+        assert!(pools.pool_for_socket(u32::MAX).is_none());
+
+        let mp = pools.pool_for_current();
+        let mut mbuf = mp.try_alloc().unwrap();
+        mbuf.extend_from_slice(&[0xff; 64][..]);
+        drop(mbuf);
+
+        // the names are taken
+        assert!(PerSocketPools::try_create("wtf", &config).is_err());
+
+        pools.free().unwrap();
+        let name = format!("wtf_{}", socket_ids[0]);
+        assert!(service().mempool(&name).is_err());
+    }
+
     #[test]
     fn mbuf_alloc_and_size_check() {
         DpdkOption::new().init().unwrap();
@@ -227,6 +908,42 @@ mod tests {
         service().mempool_free("wtf").unwrap();
     }
 
+    #[test]
+    fn mbuf_custom_headroom() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            config.dataroom = 512;
+            config.set_headroom(1024);
+            let mp = service().mempool_create("wtf", &config).unwrap();
+            assert_eq!(mp.headroom(), 1024);
+
+            let mut mbuf = mp.try_alloc().unwrap();
+            assert_eq!(mbuf.headroom(), 1024);
+            assert_eq!(mbuf.capacity(), 512);
+            mbuf.extend_from_slice(&[0xff; 64][..]);
+            // a tunnel header stack deeper than the default headroom
+            mbuf.extend_front_from_slice(&[0xee; 600][..]);
+            assert_eq!(mbuf.headroom(), 1024 - 600);
+            assert_eq!(mbuf.data()[599], 0xee);
+            assert_eq!(mbuf.data()[600], 0xff);
+            drop(mbuf);
+
+            let mbuf = mp.generic_get().unwrap();
+            assert_eq!(mbuf.headroom(), 1024);
+            mp.generic_put(mbuf);
+
+            let mut batch = ArrayVec::<_, 32>::new();
+            mp.fill_batch(&mut batch);
+            assert!(batch.iter().all(|mbuf| mbuf.headroom() == 1024));
+            Mempool::free_batch(&mut batch);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
     #[test]
     fn mbuf_data_unchanged_after_realloc() {
         DpdkOption::new().init().unwrap();
@@ -286,4 +1003,150 @@ mod tests {
 
         service().mempool_free("wtf").unwrap();
     }
+
+    #[test]
+    fn stack_backed_mempool() {
+        DpdkOption::new().init().unwrap();
+
+        assert!(MempoolOps::Ring.is_registered());
+        assert!(MempoolOps::Stack.is_registered());
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            config.set_ops(MempoolOps::Stack);
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut batch = ArrayVec::<_, 32>::new();
+            mp.fill_batch(&mut batch);
+            assert_eq!(batch.len(), 32);
+            assert_eq!(mp.nb_mbufs(), 128 - 32);
+
+            let mut mbuf = mp.try_alloc().unwrap();
+            mbuf.extend_from_slice(&[0xff; 64][..]);
+            drop(mbuf);
+            Mempool::free_batch(&mut batch);
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn build_udp_packet_in_mbuf() {
+        use rpkt::ether::*;
+        use rpkt::ipv4::*;
+        use rpkt::udp::*;
+        use rpkt::{Buf, Cursor, CursorMut};
+
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let payload = [0xab; 18];
+            let ip_len = IPV4_HEADER_LEN + UDP_HEADER_LEN + payload.len();
+            let mbuf = mp
+                .build_packet(|builder| {
+                    builder.append(ETHER_HEADER_TEMPLATE.as_bytes());
+
+                    let mut ipheader = IPV4_HEADER_TEMPLATE;
+                    ipheader.set_protocol(IpProtocol::UDP);
+                    ipheader.set_packet_len(ip_len as u16);
+                    ipheader.set_source_ip(Ipv4Addr([10, 0, 0, 1]));
+                    ipheader.set_dest_ip(Ipv4Addr([10, 0, 0, 2]));
+                    builder.append(ipheader.as_bytes());
+
+                    let mut udpheader =
+                        UdpHeader::new_unchecked(builder.append_zeroed(UDP_HEADER_LEN));
+                    udpheader.set_source_port(1024);
+                    udpheader.set_dest_port(2048);
+                    udpheader.set_packet_len((UDP_HEADER_LEN + payload.len()) as u16);
+                    builder.append(&payload[..]);
+                    assert_eq!(builder.len(), ETHER_HEADER_LEN + ip_len);
+
+                    let mut ippkt = Ipv4Packet::parse_unchecked(CursorMut::new(
+                        &mut builder.data_mut()[ETHER_HEADER_LEN..],
+                    ));
+                    ippkt.adjust_checksum();
+                })
+                .unwrap();
+            assert_eq!(mbuf.len(), ETHER_HEADER_LEN + ip_len);
+
+            let ethpkt = EtherPacket::parse(Cursor::new(mbuf.data())).unwrap();
+            assert_eq!(ethpkt.ethertype(), EtherType::IPV4);
+            let ippkt = Ipv4Packet::parse(ethpkt.payload()).unwrap();
+            assert!(ippkt.verify_checksum());
+            assert_eq!(ippkt.dest_ip(), Ipv4Addr([10, 0, 0, 2]));
+            let udppkt = UdpPacket::parse(ippkt.payload()).unwrap();
+            assert_eq!(udppkt.source_port(), 1024);
+            assert_eq!(udppkt.dest_port(), 2048);
+            assert_eq!(udppkt.payload().chunk(), &payload[..]);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn build_udp_burst() {
+        use rpkt::ether::*;
+        use rpkt::ipv4::*;
+        use rpkt::udp::*;
+        use rpkt::{Buf, Cursor};
+
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let payload = [0xab; 18];
+            let ip_len = IPV4_HEADER_LEN + UDP_HEADER_LEN + payload.len();
+            let batch = mp
+                .build_burst::<32>(|idx, builder| {
+                    builder.append(ETHER_HEADER_TEMPLATE.as_bytes());
+
+                    let mut ipheader = IPV4_HEADER_TEMPLATE;
+                    ipheader.set_protocol(IpProtocol::UDP);
+                    ipheader.set_packet_len(ip_len as u16);
+                    ipheader.set_source_ip(Ipv4Addr([10, 0, 0, 1]));
+                    ipheader.set_dest_ip(Ipv4Addr([10, 0, 0, 2]));
+                    builder.append(ipheader.as_bytes());
+
+                    let mut udpheader =
+                        UdpHeader::new_unchecked(builder.append_zeroed(UDP_HEADER_LEN));
+                    udpheader.set_source_port(1024);
+                    udpheader.set_dest_port(2048 + idx as u16);
+                    udpheader.set_packet_len((UDP_HEADER_LEN + payload.len()) as u16);
+                    builder.append(&payload[..]);
+                })
+                .unwrap();
+            assert_eq!(mp.nb_mbufs(), 128 - 32);
+
+            for (idx, mbuf) in batch.iter().enumerate() {
+                assert_eq!(mbuf.len(), ETHER_HEADER_LEN + ip_len);
+                let ethpkt = EtherPacket::parse(Cursor::new(mbuf.data())).unwrap();
+                let ippkt = Ipv4Packet::parse(ethpkt.payload()).unwrap();
+                let udppkt = UdpPacket::parse(ippkt.payload()).unwrap();
+                assert_eq!(udppkt.source_port(), 1024);
+                assert_eq!(udppkt.dest_port(), 2048 + idx as u16);
+                assert_eq!(udppkt.payload().chunk(), &payload[..]);
+            }
+
+            // the burst is all or nothing
+            let mut called = false;
+            let mut others = Vec::new();
+            while let Some(burst) = mp.build_burst::<32>(|_, _| {}) {
+                others.push(burst);
+            }
+            assert!(mp.build_burst::<64>(|_, _| called = true).is_none());
+            assert!(!called);
+            assert_eq!(mp.nb_mbufs(), 128 - 32 * (1 + others.len() as u32));
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
 }
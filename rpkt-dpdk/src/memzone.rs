@@ -0,0 +1,120 @@
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use rpkt_dpdk_sys as ffi;
+
+use crate::error::*;
+
+// Release the memzone when the last `Memzone` handle is dropped.
+//
+// A memzone that is looked up is not owned, so it is never freed.
+struct MemzoneOwner {
+    ptr: NonNull<ffi::rte_memzone>,
+    owned: bool,
+    // Whether the memory is used by a ring, see `Ring::create_in_memzone`.
+    in_use: AtomicBool,
+}
+
+unsafe impl Send for MemzoneOwner {}
+unsafe impl Sync for MemzoneOwner {}
+
+impl Drop for MemzoneOwner {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe {
+                ffi::rte_memzone_free(self.ptr.as_ptr());
+            }
+        }
+    }
+}
+
+/// A named, contiguous zone of the hugepage memory.
+///
+/// A memzone can be reserved when the application starts, and used later to
+/// hold the data structures, e.g. a ring created with `Ring::create_in_memzone`.
+#[derive(Clone)]
+pub struct Memzone {
+    owner: Arc<MemzoneOwner>,
+}
+
+impl Memzone {
+    /// Reserve a memzone of `len` bytes named `name` on `socket_id`.
+    ///
+    /// The start address of the memzone is aligned on a cache line.
+    pub fn try_reserve(name: String, len: usize, socket_id: u32) -> Result<Self> {
+        let err = Error::service_err("invalid memzone config");
+        let socket_id = i32::try_from(socket_id).map_err(|_| err)?;
+
+        let cname = CString::new(name).map_err(|_| Error::service_err("invalid memzone name"))?;
+
+        let raw = unsafe {
+            ffi::rte_memzone_reserve(
+                cname.as_bytes_with_nul().as_ptr() as *const c_char,
+                len,
+                socket_id,
+                0,
+            )
+        };
+
+        let ptr = check_ptr(raw as *mut ffi::rte_memzone, "failed to reserve memzone")?;
+        Ok(Self::new(ptr, true))
+    }
+
+    /// Look up an existing memzone named `name`.
+    ///
+    /// The returned handle does not own the memzone, dropping it never frees the
+    /// memzone.
+    pub fn try_lookup(name: String) -> Result<Self> {
+        let cname = CString::new(name).map_err(|_| Error::service_err("invalid memzone name"))?;
+
+        let raw =
+            unsafe { ffi::rte_memzone_lookup(cname.as_bytes_with_nul().as_ptr() as *const c_char) };
+
+        let ptr = check_ptr(raw as *mut ffi::rte_memzone, "failed to lookup memzone")?;
+        Ok(Self::new(ptr, false))
+    }
+
+    fn new(ptr: NonNull<ffi::rte_memzone>, owned: bool) -> Self {
+        Self {
+            owner: Arc::new(MemzoneOwner {
+                ptr,
+                owned,
+                in_use: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// Return the length of the memzone in bytes.
+    pub fn len(&self) -> usize {
+        unsafe { self.owner.ptr.as_ref().len }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return the start address of the memzone.
+    pub fn addr(&self) -> *mut c_void {
+        unsafe { self.owner.ptr.as_ref().__bindgen_anon_1.addr }
+    }
+
+    pub fn socket_id(&self) -> i32 {
+        unsafe { self.owner.ptr.as_ref().socket_id }
+    }
+
+    pub fn as_ptr(&self) -> *const ffi::rte_memzone {
+        self.owner.ptr.as_ptr()
+    }
+
+    // Mark the memzone as used, return false if it is already used.
+    pub(crate) fn acquire(&self) -> bool {
+        !self.owner.in_use.swap(true, Ordering::AcqRel)
+    }
+
+    pub(crate) fn release(&self) {
+        self.owner.in_use.store(false, Ordering::Release);
+    }
+}
@@ -1,10 +1,15 @@
+use std::ffi::{c_char, c_void, CStr};
+use std::io::IoSlice;
 use std::marker::PhantomData;
 use std::ptr::{null_mut, NonNull};
 
+use arrayvec::ArrayVec;
 use rpkt_dpdk_sys as ffi;
 
-use crate::offload::{MbufRxOffload, MbufTxOffload};
-use crate::Mempool;
+use crate::error::*;
+use crate::offload::{LayerOffsets, MbufRxOffload, MbufTxOffload, OlFlags};
+use crate::timestamp::DynTimestamp;
+use crate::{ExtBufShinfo, Mempool};
 
 #[derive(Debug)]
 pub struct Mbuf {
@@ -89,6 +94,7 @@ impl Mbuf {
         let old_len = self.len();
         unsafe { self.extend(slice.len()) };
         self.data_mut()[old_len..].copy_from_slice(slice);
+        self.debug_validate();
     }
 
     #[inline]
@@ -103,6 +109,7 @@ impl Mbuf {
     pub fn extend_front_from_slice(&mut self, slice: &[u8]) {
         unsafe { self.extend_front(slice.len()) };
         self.data_mut()[..slice.len()].copy_from_slice(slice);
+        self.debug_validate();
     }
 
     /// Truncate the current mbuf to the first `cnt` bytes.
@@ -142,6 +149,7 @@ impl Mbuf {
             // The packet length is truncated to `cnt`.
             self.ptr.as_mut().pkt_len = cnt as u32;
         }
+        self.debug_validate();
     }
 
     #[inline]
@@ -153,6 +161,74 @@ impl Mbuf {
             self.ptr.as_mut().pkt_len -= cnt as u32;
             self.ptr.as_mut().data_off += cnt as u16;
         }
+        self.debug_validate();
+    }
+
+    /// Whether the mbuf owns its data buffer, i.e. it is neither attached to
+    /// another mbuf nor to an external buffer.
+    #[inline]
+    pub fn is_direct(&self) -> bool {
+        !self
+            .ol_flags()
+            .intersects(OlFlags::INDIRECT | OlFlags::EXTERNAL)
+    }
+
+    /// Whether the mbuf is attached to the buffer of another mbuf by `attach`.
+    ///
+    /// An mbuf attached to another mbuf with an external buffer shares the
+    /// external buffer instead, so it is reported by `has_extbuf`.
+    #[inline]
+    pub fn is_indirect(&self) -> bool {
+        self.ol_flags().contains(OlFlags::INDIRECT)
+    }
+
+    /// Whether the mbuf is attached to an external buffer by `attach_extbuf`,
+    /// or by `attach` to an mbuf with an external buffer.
+    #[inline]
+    pub fn has_extbuf(&self) -> bool {
+        self.ol_flags().contains(OlFlags::EXTERNAL)
+    }
+
+    /// Return the raw offload flags of the mbuf, including the flags that are
+    /// not covered by `rx_offload` and `tx_offload`.
+    #[inline]
+    pub fn ol_flags(&self) -> OlFlags {
+        OlFlags::from_bits_retain(unsafe { self.ptr.as_ref().ol_flags })
+    }
+
+    /// Set the offload `flags`, leaving the other flags unchanged.
+    ///
+    /// # Panics
+    ///
+    /// The `INDIRECT` and the `EXTERNAL` flags are managed by the attach
+    /// functions, as they decide how the mbuf is freed. This function panics
+    /// if `flags` contains any of them.
+    #[inline]
+    pub fn set_ol_flags(&mut self, flags: OlFlags) {
+        assert!(
+            !flags.intersects(OlFlags::INDIRECT | OlFlags::EXTERNAL),
+            "setting the attach flags of an mbuf"
+        );
+        unsafe {
+            self.ptr.as_mut().ol_flags |= flags.bits();
+        }
+    }
+
+    /// Clear the offload `flags`, leaving the other flags unchanged.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `flags` contains the `INDIRECT` or the
+    /// `EXTERNAL` flag, see `set_ol_flags`.
+    #[inline]
+    pub fn clear_ol_flags(&mut self, flags: OlFlags) {
+        assert!(
+            !flags.intersects(OlFlags::INDIRECT | OlFlags::EXTERNAL),
+            "clearing the attach flags of an mbuf"
+        );
+        unsafe {
+            self.ptr.as_mut().ol_flags &= !flags.bits();
+        }
     }
 
     // rx offload
@@ -166,6 +242,44 @@ impl Mbuf {
         unsafe { self.ptr.as_ref().__bindgen_anon_2.hash.rss }
     }
 
+    /// Return the layer offsets of the packet parsed by the PMD, which are
+    /// derived from the packet type and the `l2_len`, `l3_len` and `l4_len`
+    /// fields.
+    ///
+    /// Return `None` if the PMD does not parse the l2 and the l3 headers, the
+    /// packet must then be parsed in software.
+    #[inline]
+    pub fn parsed_offsets(&self) -> Option<LayerOffsets> {
+        LayerOffsets::from_raw(unsafe { self.ptr.as_ref() })
+    }
+
+    /// Return the vlan tci of the packet, it is only valid if the vlan tag is stripped
+    /// by the NIC.
+    #[inline]
+    pub fn vlan_tci(&self) -> u16 {
+        unsafe { self.ptr.as_ref().vlan_tci }
+    }
+
+    /// Check whether the vlan tag is stripped by the NIC and saved in `vlan_tci`.
+    #[inline]
+    pub fn vlan_stripped(&self) -> bool {
+        self.rx_offload().vlan_stripped()
+    }
+
+    /// Return the port id of the mbuf, which is the input port for the
+    /// received packets.
+    #[inline]
+    pub fn port(&self) -> u16 {
+        unsafe { self.ptr.as_ref().port }
+    }
+
+    /// Set the port id of the mbuf, e.g. to record the origin of a packet
+    /// that is passed through a ring.
+    #[inline]
+    pub fn set_port(&mut self, port: u16) {
+        unsafe { self.ptr.as_mut().port = port };
+    }
+
     #[inline]
     pub fn set_tx_offload(&mut self, tx_offload: MbufTxOffload) {
         unsafe {
@@ -173,6 +287,37 @@ impl Mbuf {
         }
     }
 
+    /// Return the hardware timestamp of a received mbuf, if there is one.
+    ///
+    /// The timestamp is only stamped by the ports configured with the
+    /// `DevRxOffload::timestamp` offload, and its unit is device-dependent.
+    #[inline]
+    pub fn rx_timestamp(&self) -> Option<u64> {
+        let dyn_ts = DynTimestamp::rx().ok()?;
+        unsafe { dyn_ts.read(self.ptr.as_ptr()) }
+    }
+
+    /// Return the scheduled transmit time set by `set_tx_timestamp`, if there is
+    /// one.
+    #[inline]
+    pub fn tx_timestamp(&self) -> Option<u64> {
+        let dyn_ts = DynTimestamp::tx().ok()?;
+        unsafe { dyn_ts.read(self.ptr.as_ptr()) }
+    }
+
+    /// Schedule the mbuf to be sent at `timestamp`, in the unit of the device
+    /// clock.
+    ///
+    /// The timestamp is only honored by the ports configured with the
+    /// `DevTxOffload::send_on_timestamp` offload. As `set_tx_offload` overwrites
+    /// the offload flags, it must be called before this function.
+    #[inline]
+    pub fn set_tx_timestamp(&mut self, timestamp: u64) -> Result<()> {
+        let dyn_ts = DynTimestamp::tx()?;
+        unsafe { dyn_ts.write(self.ptr.as_ptr(), timestamp) };
+        Ok(())
+    }
+
     #[inline]
     pub fn set_l2_len(&mut self, val: u64) {
         unsafe {
@@ -195,16 +340,57 @@ impl Mbuf {
         }
     }
 
+    /// Return a typed reference to the private area of the mbuf.
+    ///
+    /// # Safety:
+    /// The private area is not initialized by the mempool, the caller must make sure
+    /// that it holds a valid `T`.
+    ///
+    /// # Panic:
+    /// In debug build, this function panics if `T` does not fit in the private area.
+    #[inline]
+    pub unsafe fn priv_data<T>(&self) -> &T {
+        debug_assert!(std::mem::size_of::<T>() <= usize::from(self.ptr.as_ref().priv_size));
+        debug_assert!(std::mem::align_of::<T>() <= 8);
+        &*(priv_addr(self.ptr.as_ref()) as *const T)
+    }
+
+    /// Return a typed mutable reference to the private area of the mbuf.
+    ///
+    /// # Safety:
+    /// The private area is not initialized by the mempool, the caller must make sure
+    /// that it holds a valid `T`.
+    ///
+    /// # Panic:
+    /// In debug build, this function panics if `T` does not fit in the private area.
     #[inline]
-    pub(crate) unsafe fn from_raw(ptr: *mut ffi::rte_mbuf) -> Self {
+    pub unsafe fn priv_data_mut<T>(&mut self) -> &mut T {
+        debug_assert!(std::mem::size_of::<T>() <= usize::from(self.ptr.as_ref().priv_size));
+        debug_assert!(std::mem::align_of::<T>() <= 8);
+        &mut *(priv_addr(self.ptr.as_ref()) as *mut T)
+    }
+
+    /// Take the ownership of a raw mbuf, e.g. one returned by `into_raw`.
+    ///
+    /// This mirrors `Box::from_raw`, the returned `Mbuf` frees `ptr` when it is
+    /// dropped.
+    ///
+    /// # Safety
+    /// `ptr` must point to a valid mbuf that is not owned by another `Mbuf`.
+    #[inline]
+    pub unsafe fn from_raw(ptr: *mut ffi::rte_mbuf) -> Self {
         Self {
             ptr: NonNull::new_unchecked(ptr),
         }
     }
 
-    // modified to pub for netbricks_port
+    /// Consume the `Mbuf` and return the raw mbuf without freeing it.
+    ///
+    /// This mirrors `Box::into_raw`, the caller is responsible for the raw mbuf,
+    /// e.g. by passing it to a DPDK function that takes the ownership, or by
+    /// reclaiming it with `from_raw`.
     #[inline]
-    pub(crate) fn into_raw(self) -> *mut ffi::rte_mbuf {
+    pub fn into_raw(self) -> *mut ffi::rte_mbuf {
         let ptr = self.ptr;
         std::mem::forget(self);
         ptr.as_ptr()
@@ -223,6 +409,119 @@ impl Mbuf {
         usize::from(unsafe { self.ptr.as_ref().nb_segs })
     }
 
+    /// Return the sum of the data lengths of all the segments.
+    ///
+    /// This is the same as `len` for a well-formed mbuf, but the segments are
+    /// walked instead of trusting `pkt_len`, so that the byte accounting stays
+    /// correct if the two disagree.
+    #[inline]
+    pub fn total_len(&self) -> usize {
+        self.seg_iter().map(|seg| seg.len()).sum()
+    }
+
+    /// Copy the data of all the segments into a contiguous `Vec`, e.g. for the
+    /// libraries that work on byte slices.
+    pub fn copy_to_vec(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.total_len());
+        for seg in self.seg_iter() {
+            buf.extend_from_slice(seg);
+        }
+        buf
+    }
+
+    /// Return the data of the segments as `IoSlice`s, e.g. for a vectored
+    /// `write_vectored` or `sendmsg` to a kernel socket, without copying the
+    /// segments into a contiguous buffer.
+    ///
+    /// The slices borrow the data of the mbuf, so the mbuf can be neither
+    /// changed nor freed, e.g. by a `tx_burst`, until the slices are dropped.
+    ///
+    /// # Panic:
+    /// This function panics if the mbuf has more than `N` segments.
+    pub fn to_io_slices<const N: usize>(&self) -> ArrayVec<IoSlice<'_>, N> {
+        let mut slices = ArrayVec::new();
+        for seg in self.seg_iter() {
+            slices.push(IoSlice::new(seg));
+        }
+        slices
+    }
+
+    /// Copy `len` bytes of the data at the offset `off` into the front of
+    /// `dst`, the range may span several segments.
+    ///
+    /// Return an error if the range exceeds the data of the mbuf, or if `dst`
+    /// is shorter than `len`.
+    pub fn copy_range_to(&self, off: usize, len: usize, dst: &mut [u8]) -> Result<()> {
+        let total_len = self.total_len();
+        if off > total_len || len > total_len - off {
+            return Error::service_err("copy range exceeds the mbuf data").to_err();
+        }
+        if dst.len() < len {
+            return Error::service_err("copy destination is too short").to_err();
+        }
+
+        let mut off = off;
+        let mut dst = &mut dst[..len];
+        for seg in self.seg_iter() {
+            if dst.is_empty() {
+                break;
+            }
+            if off >= seg.len() {
+                off -= seg.len();
+                continue;
+            }
+            let cnt = (seg.len() - off).min(dst.len());
+            let (head, tail) = std::mem::take(&mut dst).split_at_mut(cnt);
+            head.copy_from_slice(&seg[off..off + cnt]);
+            dst = tail;
+            off = 0;
+        }
+        Ok(())
+    }
+
+    /// Whether the data of all the segments equals the data of `other`,
+    /// regardless of how the two mbufs are split into segments.
+    pub fn data_eq(&self, other: &Mbuf) -> bool {
+        if self.total_len() != other.total_len() {
+            return false;
+        }
+
+        let mut others = other.seg_iter();
+        let mut cur: &[u8] = &[];
+        for mut seg in self.seg_iter() {
+            while !seg.is_empty() {
+                while cur.is_empty() {
+                    // the total lengths are equal
+                    cur = others.next().unwrap();
+                }
+                let cnt = seg.len().min(cur.len());
+                if seg[..cnt] != cur[..cnt] {
+                    return false;
+                }
+                seg = &seg[cnt..];
+                cur = &cur[cnt..];
+            }
+        }
+        true
+    }
+
+    /// Whether the data of all the segments equals `bytes`.
+    pub fn data_eq_slice(&self, bytes: &[u8]) -> bool {
+        if self.total_len() != bytes.len() {
+            return false;
+        }
+
+        let mut rest = bytes;
+        for seg in self.seg_iter() {
+            let (head, tail) = rest.split_at(seg.len());
+            if head != seg {
+                return false;
+            }
+            rest = tail;
+        }
+        true
+    }
+
     fn from_slice_slow(
         mut source: &[u8],
         mempool: &Mempool,
@@ -275,6 +574,195 @@ impl Mbuf {
         }
     }
 
+    /// Copy the mbuf into a newly allocated mbuf from `mempool`.
+    ///
+    /// Unlike sharing the underlying buffer, all the segment data are copied, so
+    /// that the new mbuf can be modified independently. The packet metadata,
+    /// including the offload flags and the vlan tags, are copied as well.
+    /// Return `None` if the mempool fails to allocate enough mbufs.
+    #[inline]
+    pub fn deep_copy(&self, mempool: &Mempool) -> Option<Self> {
+        let raw = unsafe {
+            ffi::rte_pktmbuf_copy(
+                self.ptr.as_ptr(),
+                mempool.as_ptr() as *mut ffi::rte_mempool,
+                0,
+                u32::MAX,
+            )
+        };
+        NonNull::new(raw).map(|ptr| Self { ptr })
+    }
+
+    /// Return the number of bytes in front of the packet data in the first
+    /// segment, which can be used to prepend headers.
+    #[inline]
+    pub fn headroom(&self) -> u16 {
+        unsafe { ffi::rte_pktmbuf_headroom_(self.ptr.as_ptr()) }
+    }
+
+    /// Return the number of bytes after the packet data in the last segment,
+    /// which can be used to append data.
+    #[inline]
+    pub fn tailroom(&self) -> u16 {
+        unsafe { ffi::rte_pktmbuf_tailroom_(ffi::rte_pktmbuf_lastseg_(self.ptr.as_ptr())) }
+    }
+
+    /// Remove `len` bytes from the front of the first segment, and return the
+    /// remaining data of the first segment.
+    ///
+    /// Return `None` and leave the mbuf unchanged if `len` exceeds the data
+    /// length of the first segment.
+    #[inline]
+    pub fn adj(&mut self, len: u16) -> Option<&mut [u8]> {
+        let res = unsafe { ffi::rte_pktmbuf_adj_(self.ptr.as_ptr(), len) };
+        if res.is_null() {
+            None
+        } else {
+            Some(self.data_mut())
+        }
+    }
+
+    /// Remove `len` bytes from the end of the last segment.
+    ///
+    /// Return `false` and leave the mbuf unchanged if `len` exceeds the data
+    /// length of the last segment, use `truncate` to remove data across the
+    /// segments.
+    #[inline]
+    pub fn trim(&mut self, len: u16) -> bool {
+        let res = unsafe { ffi::rte_pktmbuf_trim_(self.ptr.as_ptr(), len) == 0 };
+        self.debug_validate();
+        res
+    }
+
+    /// Return an `xxd`-style hex and ascii dump of the first `max_bytes` bytes of
+    /// the first segment.
+    pub fn hexdump(&self, max_bytes: usize) -> String {
+        let data = self.data();
+        crate::utils::hexdump(&data[..data.len().min(max_bytes)])
+    }
+
+    /// Dump the mbuf metadata and the packet data to stderr with `rte_pktmbuf_dump`.
+    pub fn dump_to_stderr(&self) {
+        unsafe { ffi::rte_pktmbuf_dump_stderr_(self.ptr.as_ptr(), self.len() as u32) };
+    }
+
+    /// Check the invariants of the mbuf with `rte_mbuf_check`, e.g. the refcount,
+    /// the data bounds of each segment and the packet length, which must equal
+    /// the sum of the data lengths of the segments.
+    ///
+    /// Return an error that describes the first violated invariant.
+    pub fn validate(&self) -> Result<()> {
+        let mut reason: *const c_char = std::ptr::null();
+        let res = unsafe { ffi::rte_mbuf_check(self.ptr.as_ptr(), 1, &mut reason) };
+        if res == 0 {
+            return Ok(());
+        }
+        // The reasons are string literals in dpdk.
+        let msg = if reason.is_null() {
+            "corrupted mbuf"
+        } else {
+            unsafe { CStr::from_ptr(reason) }
+                .to_str()
+                .unwrap_or("corrupted mbuf")
+        };
+        Error::service_err(msg).to_err()
+    }
+
+    // Panic if the mbuf is corrupted by a length update, in the debug builds.
+    #[inline]
+    fn debug_validate(&self) {
+        if cfg!(debug_assertions) {
+            if let Err(err) = self.validate() {
+                panic!("{}", err);
+            }
+        }
+    }
+
+    /// Attach the mbuf to the data of `external` without copying, so that it
+    /// becomes an indirect mbuf holding the `len` bytes at offset `off` of the
+    /// data of `external`.
+    ///
+    /// The previous data of the mbuf is discarded. The attachment increments
+    /// the refcount of the buffer of `external`, which is not returned to its
+    /// mempool until `external` and all the mbufs attached to it are dropped.
+    /// The data is shared, so writes through either mbuf are visible in both.
+    ///
+    /// # Safety:
+    /// The mbufs sharing a buffer are distinct owned values, so the borrow
+    /// checker can not see that their data aliases. Until the mbuf is dropped,
+    /// the caller must not write the shared data through `external`, the mbuf
+    /// or any other mbuf attached to the same buffer, e.g. by `data_mut`, while
+    /// a slice of the data from another of them is alive, or while another of
+    /// them is accessed from a different thread.
+    ///
+    /// # Panics:
+    /// This function panics if the mbuf is already indirect, is a multi-segment
+    /// mbuf, is attached by other mbufs, or if the range exceeds the first
+    /// segment of `external`.
+    #[inline]
+    pub unsafe fn attach(&mut self, external: &Mbuf, off: u16, len: u16) {
+        let ext_len = external.ptr.as_ref().data_len;
+        assert!(u32::from(off) + u32::from(len) <= u32::from(ext_len));
+
+        let res = ffi::rte_pktmbuf_attach_(self.ptr.as_ptr(), external.ptr.as_ptr());
+        assert!(res == 0, "the mbuf can not be attached");
+
+        let mbuf = self.ptr.as_mut();
+        mbuf.data_off += off;
+        mbuf.data_len = len;
+        mbuf.pkt_len = u32::from(len);
+    }
+
+    /// Attach the mbuf to the external buffer of `len` bytes at `buf`, so that
+    /// the data of the mbuf covers the whole external buffer without copying.
+    ///
+    /// The previous data of the mbuf is discarded. The mbuf holds a reference
+    /// of `shinfo` until it is detached or freed, and the free callback of
+    /// `shinfo` runs after the last reference is released. The mbufs attached
+    /// to this mbuf by `attach` share the external buffer as well.
+    ///
+    /// # Safety:
+    /// `buf` must be valid for reads and writes of `len` bytes, and `iova` must
+    /// be its io address if the mbuf is passed to a device, until the free
+    /// callback of `shinfo` runs.
+    ///
+    /// # Panics:
+    /// This function panics if the mbuf is indirect, is a multi-segment mbuf,
+    /// or is attached by other mbufs.
+    #[inline]
+    pub unsafe fn attach_extbuf(
+        &mut self,
+        buf: *mut u8,
+        iova: u64,
+        len: u16,
+        shinfo: &ExtBufShinfo,
+    ) {
+        let res = ffi::rte_pktmbuf_attach_extbuf_(
+            self.ptr.as_ptr(),
+            buf as *mut c_void,
+            iova,
+            len,
+            shinfo.as_ptr(),
+        );
+        assert!(res == 0, "the mbuf can not be attached");
+
+        let mbuf = self.ptr.as_mut();
+        mbuf.data_len = len;
+        mbuf.pkt_len = u32::from(len);
+    }
+
+    /// Detach the mbuf from its external buffer and release its reference of
+    /// the shared info, the mbuf is restored to its own empty buffer.
+    ///
+    /// # Panics:
+    /// This function panics if the mbuf has no external buffer, or is attached
+    /// by other mbufs.
+    #[inline]
+    pub fn detach_extbuf(&mut self) {
+        let res = unsafe { ffi::rte_pktmbuf_detach_extbuf_(self.ptr.as_ptr()) };
+        assert!(res == 0, "the mbuf can not be detached");
+    }
+
     #[inline]
     pub fn chain(&mut self, other: Mbuf) {
         assert!(
@@ -296,6 +784,74 @@ impl Mbuf {
             other_ptr.as_mut().pkt_len = other_ptr.as_ref().data_len as u32;
             other_ptr.as_mut().nb_segs = 1;
         }
+        self.debug_validate();
+    }
+
+    /// Copy the data of all the segments into the first segment, and free the
+    /// other segments.
+    ///
+    /// Return an error if the tailroom of the first segment can not hold the
+    /// data of the other segments, the mbuf is left unchanged in that case.
+    #[inline]
+    pub fn linearize(&mut self) -> Result<()> {
+        let res = unsafe { ffi::rte_pktmbuf_linearize_(self.ptr.as_ptr()) };
+        if res != 0 {
+            return Error::service_err("no enough tailroom to linearize mbuf").to_err();
+        }
+        Ok(())
+    }
+
+    /// Copy the data of the trailing segments into new segments allocated
+    /// from `mempool`, so that the mbuf has at most `max` segments, e.g. the
+    /// `PortInfo::max_tx_segments` of the port that transmits it.
+    ///
+    /// The first segment and the packet metadata are always kept, and as many
+    /// leading segments as possible. Return an error if the mempool fails to
+    /// allocate the new segments, or if the data can not fit in `max`
+    /// segments, the mbuf is left unchanged in both cases.
+    ///
+    /// # Panic:
+    /// This function panics if `max` is 0.
+    pub fn coalesce_to_max_segs(&mut self, max: u16, mempool: &Mempool) -> Result<()> {
+        assert!(max > 0, "invalid maximum number of segments");
+        let max = usize::from(max);
+        if self.num_segs() <= max {
+            return Ok(());
+        }
+
+        // keep the first `nb_kept` segments and copy the others into a chain
+        // of full segments, which holds at least one segment less
+        for nb_kept in (1..max).rev() {
+            let mut tail_bytes = Vec::new();
+            for seg in self.seg_iter().skip(nb_kept) {
+                tail_bytes.extend_from_slice(seg);
+            }
+            let tail = Mbuf::from_slice(&tail_bytes[..], mempool)
+                .ok_or(Error::service_err("fail to allocate mbuf segments"))?;
+            if nb_kept + tail.num_segs() > max {
+                continue;
+            }
+
+            unsafe {
+                let mut last_kept = self.ptr;
+                for _ in 1..nb_kept {
+                    last_kept = NonNull::new_unchecked(last_kept.as_ref().next);
+                }
+                // the freed segments are walked through their next pointers
+                ffi::rte_pktmbuf_free_(last_kept.as_ref().next);
+
+                let mut tail_ptr = tail.ptr;
+                std::mem::forget(tail);
+                last_kept.as_mut().next = tail_ptr.as_ptr();
+                self.ptr.as_mut().nb_segs = (nb_kept + tail_ptr.as_ref().nb_segs as usize) as u16;
+                // the total length is unchanged
+                tail_ptr.as_mut().pkt_len = tail_ptr.as_ref().data_len as u32;
+                tail_ptr.as_mut().nb_segs = 1;
+            }
+            self.debug_validate();
+            return Ok(());
+        }
+        Error::service_err("mbuf does not fit in the maximum segments").to_err()
     }
 
     #[inline]
@@ -342,6 +898,13 @@ pub(crate) unsafe fn data_addr(mbuf: &ffi::rte_mbuf) -> *mut u8 {
     (mbuf.buf_addr as *mut u8).add(data_off)
 }
 
+// The private area immediately follows the `rte_mbuf` struct, this is
+// the same as `rte_mbuf_to_priv`.
+#[inline]
+unsafe fn priv_addr(mbuf: &ffi::rte_mbuf) -> *mut u8 {
+    (mbuf as *const ffi::rte_mbuf as *mut u8).add(std::mem::size_of::<ffi::rte_mbuf>())
+}
+
 pub struct Appender<'a> {
     buf: &'a mut Mbuf,
     last_seg: NonNull<ffi::rte_mbuf>,
@@ -367,6 +930,7 @@ impl<'a> Appender<'a> {
             // update the last_seg
             self.last_seg = other_ptr;
         }
+        self.buf.debug_validate();
     }
 }
 
@@ -414,7 +978,9 @@ impl<'a> Iterator for SegIterMut<'a> {
 
 #[cfg(test)]
 mod tests {
+    use crate::offload::MbufTxOffload;
     use crate::*;
+    use arrayvec::ArrayVec;
 
     #[test]
     fn mbuf_data_append_remove() {
@@ -595,6 +1161,287 @@ mod tests {
         service().mempool_free("wtf").unwrap();
     }
 
+    #[test]
+    fn total_len_of_multiseg_mbuf() {
+        DpdkOption::new().init().unwrap();
+        let buf = [0xac; 9000];
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            config.dataroom = 2048;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut batch = ArrayVec::<_, 4>::new();
+            batch.push(Mbuf::from_slice(&buf[..], &mp).unwrap());
+            batch.push(Mbuf::from_slice(&buf[..100], &mp).unwrap());
+            batch.push(Mbuf::from_slice(&buf[..4096], &mp).unwrap());
+            assert!(batch[0].num_segs() > 1);
+            for mbuf in batch.iter() {
+                let seg_sum: usize = mbuf.seg_iter().map(|seg| seg.len()).sum();
+                assert_eq!(mbuf.total_len(), seg_sum);
+                assert_eq!(mbuf.total_len(), mbuf.len());
+            }
+            assert_eq!(burst::total_bytes(&batch), 9000 + 100 + 4096);
+
+            // the segments are counted even if `pkt_len` is wrong
+            let raw = batch[0].as_ptr() as *mut rpkt_dpdk_sys::rte_mbuf;
+            unsafe { (*raw).pkt_len -= 1000 };
+            assert_eq!(batch[0].len(), 8000);
+            assert_eq!(batch[0].total_len(), 9000);
+            unsafe { (*raw).pkt_len += 1000 };
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn copy_multiseg_mbuf_out() {
+        DpdkOption::new().init().unwrap();
+        let buf: Vec<u8> = (0..9000).map(|i| i as u8).collect();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            config.dataroom = 2048;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mbuf = Mbuf::from_slice(&buf[..100], &mp).unwrap();
+            assert_eq!(mbuf.copy_to_vec(), &buf[..100]);
+
+            let mbuf = Mbuf::from_slice(&buf[..], &mp).unwrap();
+            assert!(mbuf.num_segs() > 1);
+            assert_eq!(mbuf.copy_to_vec(), buf);
+
+            // the range straddles the first segment boundary
+            let fst_len = mbuf.seg_iter().next().unwrap().len();
+            let mut dst = [0; 64];
+            mbuf.copy_range_to(fst_len - 10, 20, &mut dst[..]).unwrap();
+            assert_eq!(&dst[..20], &buf[fst_len - 10..fst_len + 10]);
+            assert_eq!(&dst[20..], &[0; 44]);
+
+            // the range covers several segments and ends at the end of the data
+            let mut dst = vec![0; 5000];
+            mbuf.copy_range_to(4000, 5000, &mut dst[..]).unwrap();
+            assert_eq!(dst, &buf[4000..]);
+
+            assert!(mbuf.copy_range_to(4001, 5000, &mut dst[..]).is_err());
+            assert!(mbuf.copy_range_to(0, 5001, &mut dst[..]).is_err());
+            assert!(mbuf.copy_range_to(usize::MAX, 1, &mut dst[..]).is_err());
+            mbuf.copy_range_to(9000, 0, &mut []).unwrap();
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn compare_multiseg_mbuf_data() {
+        DpdkOption::new().init().unwrap();
+        let buf: Vec<u8> = (0..9000).map(|i| i as u8).collect();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            config.dataroom = 2048;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            // the same data split at different segment boundaries
+            let fst = Mbuf::from_slice(&buf[..], &mp).unwrap();
+            let mut snd = Mbuf::from_slice(&buf[..1000], &mp).unwrap();
+            snd.chain(Mbuf::from_slice(&buf[1000..], &mp).unwrap());
+            assert!(fst.seg_iter().next().unwrap().len() > 1000);
+            assert_eq!(snd.seg_iter().next().unwrap().len(), 1000);
+            assert!(fst.data_eq(&snd));
+            assert!(snd.data_eq(&fst));
+            assert!(fst.data_eq_slice(&buf[..]));
+            assert!(snd.data_eq_slice(&buf[..]));
+
+            // a byte differs in the last segment
+            let mut other = buf.clone();
+            other[8999] ^= 0xff;
+            let thd = Mbuf::from_slice(&other[..], &mp).unwrap();
+            assert!(!fst.data_eq(&thd));
+            assert!(!snd.data_eq_slice(&other[..]));
+
+            let short = Mbuf::from_slice(&buf[..8999], &mp).unwrap();
+            assert!(!fst.data_eq(&short));
+            assert!(!short.data_eq_slice(&buf[..]));
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn coalesce_segments() {
+        DpdkOption::new().init().unwrap();
+        let buf: Vec<u8> = (0..10000).map(|i| i as u8).collect();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            config.dataroom = 2048;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+            // a chain of 5 segments of `seg_len` bytes
+            let chain = |seg_len: usize| {
+                let mut mbuf = Mbuf::from_slice(&buf[..seg_len], &mp).unwrap();
+                let mut appender = mbuf.appender();
+                for i in 1..5 {
+                    let seg = &buf[i * seg_len..(i + 1) * seg_len];
+                    appender.append_seg(Mbuf::from_slice(seg, &mp).unwrap());
+                }
+                mbuf
+            };
+
+            // the last 3 segments are copied into one
+            let mut mbuf = chain(100);
+            mbuf.set_port(3);
+            assert_eq!(mbuf.num_segs(), 5);
+            mbuf.coalesce_to_max_segs(3, &mp).unwrap();
+            assert_eq!(mbuf.num_segs(), 3);
+            let seg_lens: Vec<_> = mbuf.seg_iter().map(|seg| seg.len()).collect();
+            assert_eq!(seg_lens, [100, 100, 300]);
+            assert!(mbuf.data_eq_slice(&buf[..500]));
+            assert_eq!(mbuf.port(), 3);
+            mbuf.validate().unwrap();
+            // a chain within the limit is left unchanged
+            mbuf.coalesce_to_max_segs(3, &mp).unwrap();
+            assert_eq!(mbuf.num_segs(), 3);
+
+            // 4 segments of 1000 bytes fit in 2 full segments
+            let mut mbuf = chain(1000);
+            mbuf.coalesce_to_max_segs(3, &mp).unwrap();
+            let seg_lens: Vec<_> = mbuf.seg_iter().map(|seg| seg.len()).collect();
+            assert_eq!(seg_lens, [1000, 2048, 1952]);
+            assert!(mbuf.data_eq_slice(&buf[..5000]));
+            mbuf.validate().unwrap();
+            drop(mbuf);
+
+            // 10000 bytes do not fit in 3 segments
+            let mut mbuf = chain(2000);
+            assert!(mbuf.coalesce_to_max_segs(3, &mp).is_err());
+            assert_eq!(mbuf.num_segs(), 5);
+            assert!(mbuf.data_eq_slice(&buf[..]));
+            mbuf.coalesce_to_max_segs(5, &mp).unwrap();
+            drop(mbuf);
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn multiseg_mbuf_io_slices() {
+        use std::io::Write;
+
+        DpdkOption::new().init().unwrap();
+        let buf: Vec<u8> = (0..9000).map(|i| i as u8).collect();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            config.dataroom = 2048;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut mbuf = Mbuf::from_slice(&buf[..100], &mp).unwrap();
+            mbuf.chain(Mbuf::from_slice(&buf[100..], &mp).unwrap());
+            let nb_segs = mbuf.num_segs();
+            assert!(nb_segs > 2);
+
+            let slices = mbuf.to_io_slices::<8>();
+            assert_eq!(slices.len(), nb_segs);
+            assert_eq!(slices[0].len(), 100);
+            for (slice, seg) in slices.iter().zip(mbuf.seg_iter()) {
+                assert_eq!(slice.as_ptr(), seg.as_ptr());
+            }
+
+            // a vectored write reassembles the packet
+            let mut out = Vec::new();
+            assert_eq!(out.write_vectored(&slices[..]).unwrap(), 9000);
+            assert_eq!(out, buf);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn deep_copy_multiseg_mbuf() {
+        DpdkOption::new().init().unwrap();
+        let mut buf: [u8; 9000] = [0xac; 9000];
+        for i in 0..9000 {
+            buf[i] = (i % u8::MAX as usize) as u8;
+        }
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            config.dataroom = 2048;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+            let mut mbuf = Mbuf::from_slice(&buf[..], &mp).unwrap();
+            let mut tx_offload = MbufTxOffload::ALL_DISABLED;
+            tx_offload.enable_ip_cksum();
+            mbuf.set_tx_offload(tx_offload);
+
+            let mut copy = mbuf.deep_copy(&mp).unwrap();
+            assert_eq!(copy.len(), mbuf.len());
+            assert_eq!(copy.num_segs(), mbuf.num_segs());
+            assert_eq!(copy.rx_offload(), mbuf.rx_offload());
+
+            for seg in copy.seg_iter_mut() {
+                for byte in seg.iter_mut() {
+                    *byte = 0;
+                }
+            }
+
+            let mut buf_copy = &buf[..];
+            for seg in mbuf.seg_iter() {
+                assert_eq!(seg, &buf_copy[..seg.len()]);
+                buf_copy = &buf_copy[seg.len()..];
+            }
+            for seg in copy.seg_iter() {
+                assert_eq!(seg.iter().all(|byte| *byte == 0), true);
+            }
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn multiseg_mbuf_adj_trim() {
+        DpdkOption::new().init().unwrap();
+        let buf: Vec<u8> = (0..5000).map(|i| (i % 256) as u8).collect();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            config.dataroom = 2048;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+            let mut mbuf = Mbuf::from_slice(&buf[..], &mp).unwrap();
+            assert_eq!(mbuf.num_segs(), 3);
+            assert_eq!(mbuf.headroom(), Mempool::MBUF_HEADROOM);
+            // the last segment holds 904 bytes
+            assert_eq!(mbuf.tailroom(), 2048 - 904);
+
+            assert_eq!(mbuf.adj(100).unwrap(), &buf[100..2048]);
+            assert_eq!(mbuf.len(), 4900);
+            assert_eq!(mbuf.headroom(), Mempool::MBUF_HEADROOM + 100);
+
+            assert!(mbuf.trim(4));
+            assert_eq!(mbuf.len(), 4896);
+            assert_eq!(mbuf.tailroom(), 2048 - 900);
+            // trim can not remove more than the last segment
+            assert!(!mbuf.trim(901));
+            assert_eq!(mbuf.len(), 4896);
+
+            let mut buf_copy = &buf[100..4996];
+            for seg in mbuf.seg_iter() {
+                assert_eq!(seg, &buf_copy[..seg.len()]);
+                buf_copy = &buf_copy[seg.len()..];
+            }
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
     #[test]
     fn truncate_multiseg_mbuf() {
         DpdkOption::new().init().unwrap();
@@ -633,4 +1480,56 @@ mod tests {
 
         service().mempool_free("wtf").unwrap();
     }
+
+    // This test requires a usable port 0, e.g. a `net_null` virtual device.
+    #[test]
+    fn tx_header_with_attached_payload() {
+        DpdkOption::new().init().unwrap();
+
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        utils::init_port(0, 1, 1, 512, "wtf", 512, 0).unwrap();
+
+        {
+            let mp = service().mempool("wtf").unwrap();
+            let mut txq = service().tx_queue(0, 0).unwrap();
+            let mut stats_query = service().stats_query(0).unwrap();
+            let opackets = stats_query.query().opackets();
+
+            let header = [0xff; 42];
+            let payload: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+            let mut payload_mbuf = mp.try_alloc().unwrap();
+            payload_mbuf.extend_from_slice(&payload[..]);
+
+            let avail = mp.nb_mbufs();
+            let mut batch = ArrayVec::<_, 4>::new();
+            for i in 0..4 {
+                let mut pkt = mp.try_alloc().unwrap();
+                pkt.extend_from_slice(&header[..]);
+                let mut indirect = mp.alloc_indirect().unwrap();
+                unsafe { indirect.attach(&payload_mbuf, i * 100, 500) };
+                pkt.chain(indirect);
+
+                assert_eq!(pkt.num_segs(), 2);
+                assert_eq!(pkt.len(), 42 + 500);
+                let segs: Vec<&[u8]> = pkt.seg_iter().collect();
+                assert_eq!(segs[0], &header[..]);
+                let off = usize::from(i) * 100;
+                assert_eq!(segs[1], &payload[off..off + 500]);
+                batch.push(pkt);
+            }
+
+            while !batch.is_empty() {
+                txq.tx(&mut batch);
+            }
+            assert_eq!(stats_query.query().opackets(), opackets + 4);
+
+            // the payload is kept alive until the last attached segment is freed
+            assert_eq!(payload_mbuf.data(), &payload[..]);
+            drop(payload_mbuf);
+            assert_eq!(mp.nb_mbufs(), avail + 1);
+        }
+
+        service().port_close(0).unwrap();
+        service().mempool_free("wtf").unwrap();
+    }
 }
@@ -0,0 +1,122 @@
+//! The names of the dpdk objects.
+//!
+//! The rings, the mempools and the hash tables share a flat namespace, and
+//! each name is stored in a fixed-size buffer. Dpdk compares the names only up
+//! to the buffer size, so two long names with the same leading bytes would
+//! refer to the same object. The names that do not fit are rejected instead.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::error::*;
+
+/// The size of the name of a ring, including the nul terminator.
+///
+/// This is `RTE_RING_NAMESIZE`, the memzone name of the ring is prefixed with
+/// "RG_".
+pub const RING_NAMESIZE: usize = 29;
+
+/// The size of the name of a mempool, including the nul terminator.
+///
+/// This is `RTE_MEMPOOL_NAMESIZE`, the ring name of the mempool is prefixed
+/// with "MP_".
+pub const MEMPOOL_NAMESIZE: usize = 26;
+
+/// The size of the name of a hash table, including the nul terminator.
+///
+/// The ring of the free slots of the table is named "HT_" and the name of the
+/// table, it must fit in `RING_NAMESIZE`.
+pub const HASH_NAMESIZE: usize = RING_NAMESIZE - 3;
+
+/// The maximum length of the names returned by `unique`, which fit a ring, a
+/// mempool and a hash table.
+pub const UNIQUE_NAME_LEN: usize = MEMPOOL_NAMESIZE - 1;
+
+/// Return a name that starts with `prefix` and is unique among the processes
+/// sharing the dpdk namespace.
+///
+/// The suffix holds the process id and a per-process counter. The prefix is
+/// truncated so that the name never exceeds `UNIQUE_NAME_LEN`, i.e. a
+/// shortened prefix still makes a unique name.
+pub fn unique(prefix: &str) -> String {
+    static NEXT: AtomicU32 = AtomicU32::new(0);
+
+    let suffix = format!(
+        "_{:x}_{:x}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    );
+    let mut end = prefix.len().min(UNIQUE_NAME_LEN - suffix.len());
+    while !prefix.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", &prefix[..end], suffix)
+}
+
+// Fail with `msg` if `name` does not fit in a name of `namesize` bytes.
+pub(crate) fn check_len(name: &str, namesize: usize, msg: &'static str) -> Result<()> {
+    if name.len() >= namesize {
+        return Error::service_err(msg).to_err();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn unique_names_fit_the_limit() {
+        let short = unique("rx");
+        assert!(short.starts_with("rx_"));
+        assert_ne!(unique("rx"), short);
+
+        let long = "a_very_long_prefix_for_the_rx_rings";
+        let names: Vec<_> = (0..100).map(|_| unique(long)).collect();
+        for name in names.iter() {
+            assert!(name.len() <= UNIQUE_NAME_LEN);
+            assert!(name.starts_with("a_very"));
+        }
+        let mut dedup = names.clone();
+        dedup.sort();
+        dedup.dedup();
+        assert_eq!(dedup.len(), names.len());
+
+        // the prefix is not split inside a character
+        let name = unique(&"é".repeat(20));
+        assert!(name.len() <= UNIQUE_NAME_LEN);
+    }
+
+    #[test]
+    fn over_length_names() {
+        DpdkOption::new().init().unwrap();
+
+        let conf = RingConf {
+            count: 64,
+            socket_id: 0,
+            flag: RingFlags::empty(),
+        };
+        let name = "r".repeat(RING_NAMESIZE);
+        let err = Ring::try_create(name.clone(), &conf).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::ServiceError);
+        assert!(Ring::try_lookup(name).is_err());
+        let name = "r".repeat(RING_NAMESIZE - 1);
+        let ring = Ring::try_create(name.clone(), &conf).unwrap();
+        assert_eq!(Ring::try_lookup(name).unwrap().as_ptr(), ring.as_ptr());
+
+        let name = "m".repeat(MEMPOOL_NAMESIZE);
+        let err = service()
+            .mempool_create(&name, &MempoolConf::default())
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::ServiceError);
+
+        // a unique name is valid for both a ring and a mempool
+        let name = unique("names_over_length_test");
+        let _ring = Ring::try_create(name.clone(), &conf).unwrap();
+        let mut mp_conf = MempoolConf::default();
+        mp_conf.set_nb_mbufs(64);
+        drop(service().mempool_create(&name, &mp_conf).unwrap());
+        service().mempool_free(&name).unwrap();
+    }
+}
@@ -0,0 +1,309 @@
+use std::ffi::CString;
+use std::ops::{Deref, DerefMut};
+use std::os::raw::{c_char, c_void};
+use std::ptr::NonNull;
+use std::sync::Arc;
+
+use rpkt_dpdk_sys as ffi;
+
+use crate::error::*;
+use crate::names;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ObjPoolConf {
+    pub nb_objs: u32,
+    pub per_core_caches: u32,
+    pub socket_id: u32,
+}
+
+impl ObjPoolConf {
+    pub const NB_OBJS: u32 = 1024;
+    pub const PER_CORE_CACHES: u32 = 0;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_nb_objs(&mut self, val: u32) {
+        self.nb_objs = val;
+    }
+
+    pub fn set_per_core_caches(&mut self, val: u32) {
+        self.per_core_caches = val;
+    }
+
+    pub fn set_socket_id(&mut self, val: u32) {
+        self.socket_id = val;
+    }
+}
+
+impl Default for ObjPoolConf {
+    fn default() -> Self {
+        Self {
+            nb_objs: Self::NB_OBJS,
+            per_core_caches: Self::PER_CORE_CACHES,
+            socket_id: 0,
+        }
+    }
+}
+
+// Release the mempool when the pool and all the `PoolRef` are dropped, so no
+// object is outstanding when the mempool is freed.
+struct ObjPoolOwner<T> {
+    ptr: NonNull<ffi::rte_mempool>,
+    init: Box<dyn Fn() -> T + Send + Sync>,
+}
+
+impl<T> Drop for ObjPoolOwner<T> {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::rte_mempool_free(self.ptr.as_ptr());
+        }
+    }
+}
+
+/// A pool of fixed-size `T` objects in the hugepage memory.
+///
+/// Unlike `Mempool`, which holds mbufs, `ObjPool` holds the application
+/// structs, e.g. the per-flow states. An object is initialized by the
+/// constructor of the pool when it is taken with `get`, and dropped when it is
+/// returned to the pool.
+pub struct ObjPool<T> {
+    owner: Arc<ObjPoolOwner<T>>,
+}
+
+unsafe impl<T: Send> Send for ObjPool<T> {}
+unsafe impl<T: Send> Sync for ObjPool<T> {}
+
+impl<T> Clone for ObjPool<T> {
+    fn clone(&self) -> Self {
+        Self {
+            owner: self.owner.clone(),
+        }
+    }
+}
+
+impl<T> ObjPool<T> {
+    /// Create a new pool named `name`, whose objects are initialized with `init`.
+    ///
+    /// The objects are aligned on a cache line, so the alignment of `T` must
+    /// not exceed 64 bytes.
+    pub fn try_create_with<F>(name: String, conf: &ObjPoolConf, init: F) -> Result<Self>
+    where
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        let err = Error::service_err("invalid object pool config");
+        if std::mem::align_of::<T>() > 64 {
+            return err.to_err();
+        }
+        let elt_size = u32::try_from(std::mem::size_of::<T>().max(1)).map_err(|_| err)?;
+        let socket_id = i32::try_from(conf.socket_id).map_err(|_| err)?;
+
+        names::check_len(
+            &name,
+            names::MEMPOOL_NAMESIZE,
+            "object pool name is too long",
+        )?;
+        let cname =
+            CString::new(name).map_err(|_| Error::service_err("invalid object pool name"))?;
+        let raw = unsafe {
+            ffi::rte_mempool_create(
+                cname.as_bytes_with_nul().as_ptr() as *const c_char,
+                conf.nb_objs,
+                elt_size,
+                conf.per_core_caches,
+                0,
+                None,
+                std::ptr::null_mut(),
+                None,
+                std::ptr::null_mut(),
+                socket_id,
+                0,
+            )
+        };
+
+        let ptr = check_ptr(raw, "fail to allocate object pool")?;
+        Ok(Self {
+            owner: Arc::new(ObjPoolOwner {
+                ptr,
+                init: Box::new(init),
+            }),
+        })
+    }
+
+    /// Take an object from the pool, return `None` if the pool is exhausted.
+    #[inline]
+    pub fn get(&self) -> Option<PoolRef<T>> {
+        let mut obj: *mut c_void = std::ptr::null_mut();
+        let res = unsafe { ffi::rte_mempool_get_(self.owner.ptr.as_ptr(), &mut obj) };
+        if res != 0 {
+            return None;
+        }
+
+        let ptr = NonNull::new(obj as *mut T).unwrap();
+        // the object is returned to the pool if the constructor panics
+        let guard = PutGuard {
+            mp: self.owner.ptr,
+            obj,
+        };
+        let value = (self.owner.init)();
+        std::mem::forget(guard);
+        unsafe { ptr.as_ptr().write(value) };
+        Some(PoolRef {
+            ptr,
+            pool: self.owner.clone(),
+        })
+    }
+
+    /// Return the number of the objects that can be taken from the pool.
+    #[inline]
+    pub fn avail_count(&self) -> u32 {
+        unsafe { ffi::rte_mempool_avail_count(self.as_ptr()) }
+    }
+
+    pub fn as_ptr(&self) -> *const ffi::rte_mempool {
+        self.owner.ptr.as_ptr()
+    }
+}
+
+impl<T: Default> ObjPool<T> {
+    /// Create a new pool named `name`, whose objects are initialized with
+    /// `T::default`.
+    pub fn try_create(name: String, conf: &ObjPoolConf) -> Result<Self> {
+        Self::try_create_with(name, conf, T::default)
+    }
+}
+
+// Put an uninitialized object back to the mempool on drop.
+struct PutGuard {
+    mp: NonNull<ffi::rte_mempool>,
+    obj: *mut c_void,
+}
+
+impl Drop for PutGuard {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { ffi::rte_mempool_put_(self.mp.as_ptr(), self.obj) };
+    }
+}
+
+/// An object taken from an `ObjPool`, which is returned to the pool on drop.
+pub struct PoolRef<T> {
+    ptr: NonNull<T>,
+    pool: Arc<ObjPoolOwner<T>>,
+}
+
+unsafe impl<T: Send> Send for PoolRef<T> {}
+unsafe impl<T: Sync> Sync for PoolRef<T> {}
+
+impl<T> Deref for PoolRef<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> DerefMut for PoolRef<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T> Drop for PoolRef<T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            std::ptr::drop_in_place(self.ptr.as_ptr());
+            ffi::rte_mempool_put_(self.pool.ptr.as_ptr(), self.ptr.as_ptr() as *mut c_void);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[repr(C, align(64))]
+    #[derive(Default)]
+    struct FlowState {
+        packets: u64,
+        bytes: u64,
+        last_seen: u64,
+    }
+
+    #[test]
+    fn get_and_return_objects() {
+        DpdkOption::new().init().unwrap();
+
+        assert_eq!(std::mem::size_of::<FlowState>(), 64);
+        let mut conf = ObjPoolConf::new();
+        conf.set_nb_objs(32);
+        let pool = ObjPool::<FlowState>::try_create("flows".to_string(), &conf).unwrap();
+        assert_eq!(pool.avail_count(), 32);
+
+        let mut flows: Vec<_> = (0..10).map(|_| pool.get().unwrap()).collect();
+        assert_eq!(pool.avail_count(), 22);
+        for (i, flow) in flows.iter_mut().enumerate() {
+            assert_eq!(flow.packets, 0);
+            assert_eq!(&**flow as *const FlowState as usize % 64, 0);
+            flow.packets = i as u64;
+            flow.bytes = 64 * i as u64;
+        }
+        assert_eq!(flows[3].bytes, 192);
+        drop(flows);
+        assert_eq!(pool.avail_count(), 32);
+
+        // the returned objects are initialized again
+        let flows: Vec<_> = (0..32).map(|_| pool.get().unwrap()).collect();
+        assert!(flows
+            .iter()
+            .all(|flow| flow.packets == 0 && flow.last_seen == 0));
+        assert_eq!(pool.avail_count(), 0);
+        assert!(pool.get().is_none());
+
+        // the objects keep the pool alive
+        drop(pool);
+        drop(flows);
+    }
+
+    #[test]
+    fn create_with_constructor() {
+        DpdkOption::new().init().unwrap();
+
+        let mut conf = ObjPoolConf::new();
+        conf.set_nb_objs(8);
+        let pool =
+            ObjPool::try_create_with("ctor_objs".to_string(), &conf, || vec![1_u8; 4]).unwrap();
+
+        let mut obj = pool.get().unwrap();
+        assert_eq!(&obj[..], &[1; 4][..]);
+        obj.push(2);
+        assert_eq!(pool.avail_count(), 7);
+        drop(obj);
+        assert_eq!(pool.avail_count(), 8);
+
+        assert!(ObjPool::<u64>::try_create("ctor_objs".to_string(), &conf).is_err());
+    }
+
+    #[test]
+    fn get_with_panicking_constructor() {
+        DpdkOption::new().init().unwrap();
+
+        let mut conf = ObjPoolConf::new();
+        conf.set_nb_objs(4);
+        let pool = ObjPool::<u64>::try_create_with("panic_objs".to_string(), &conf, || {
+            panic!("constructor panics")
+        })
+        .unwrap();
+
+        for _ in 0..8 {
+            let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| pool.get()));
+            assert!(res.is_err());
+        }
+        assert_eq!(pool.avail_count(), 4);
+    }
+}
@@ -1,3 +1,6 @@
+use bitflags::bitflags;
+use rpkt_dpdk_sys as ffi;
+
 // A macro used for generating dpdk bit-level configuration.
 macro_rules! dpdk_offload_conf {
     (
@@ -36,6 +39,23 @@ macro_rules! dpdk_offload_conf {
             );
 
             pub const ALL_DISABLED: Self = Self(0);
+
+            /// Return an error message naming the first field that is enabled in
+            /// `self` but not in `capa`.
+            #[allow(dead_code)]
+            pub(crate) fn unsupported_by(&self, capa: Self) -> Option<&'static str> {
+                $(
+                    if self.$field_name() && !capa.$field_name() {
+                        return Some(concat!(
+                            "unsupported ",
+                            stringify!($conf_ident),
+                            ": ",
+                            stringify!($field_name)
+                        ));
+                    }
+                )+
+                None
+            }
         }
     };
 }
@@ -103,6 +123,117 @@ dpdk_offload_conf!(
 
         /// #define RTE_MBUF_F_RX_L4_CKSUM_GOOD    (1ULL << 8)
         l4_cksum_good, _do_not_use_5, 1 << 8,
+
+        /// #define RTE_MBUF_F_RX_VLAN             (1ULL << 0)
+        vlan,          _do_not_use_6, 1 << 0,
+
+        /// #define RTE_MBUF_F_RX_VLAN_STRIPPED    (1ULL << 6)
+        vlan_stripped, _do_not_use_7, 1 << 6,
+    }
+);
+
+bitflags! {
+    /// The raw offload flags of an mbuf, i.e. the `ol_flags` field.
+    ///
+    /// The flags are named after the `RTE_MBUF_F_*` macros, which replace the
+    /// `PKT_RX_*` and the `PKT_TX_*` macros of the older dpdk releases. The
+    /// l4 checksum request of the tx path is a 2-bit field, so `TX_UDP_CKSUM`
+    /// contains `TX_TCP_CKSUM`, compare against `TX_L4_MASK` to tell them
+    /// apart.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct OlFlags: u64 {
+        /// #define RTE_MBUF_F_RX_VLAN             (1ULL << 0)
+        const RX_VLAN               = 1 << 0;
+        /// #define RTE_MBUF_F_RX_RSS_HASH         (1ULL << 1)
+        const RX_RSS_HASH           = 1 << 1;
+        /// #define RTE_MBUF_F_RX_FDIR             (1ULL << 2)
+        const RX_FDIR               = 1 << 2;
+        /// #define RTE_MBUF_F_RX_L4_CKSUM_BAD     (1ULL << 3)
+        const RX_L4_CKSUM_BAD       = 1 << 3;
+        /// #define RTE_MBUF_F_RX_IP_CKSUM_BAD     (1ULL << 4)
+        const RX_IP_CKSUM_BAD       = 1 << 4;
+        /// #define RTE_MBUF_F_RX_OUTER_IP_CKSUM_BAD (1ULL << 5)
+        const RX_OUTER_IP_CKSUM_BAD = 1 << 5;
+        /// #define RTE_MBUF_F_RX_VLAN_STRIPPED    (1ULL << 6)
+        const RX_VLAN_STRIPPED      = 1 << 6;
+        /// #define RTE_MBUF_F_RX_IP_CKSUM_GOOD    (1ULL << 7)
+        const RX_IP_CKSUM_GOOD      = 1 << 7;
+        /// #define RTE_MBUF_F_RX_L4_CKSUM_GOOD    (1ULL << 8)
+        const RX_L4_CKSUM_GOOD      = 1 << 8;
+        /// #define RTE_MBUF_F_RX_IEEE1588_PTP     (1ULL << 9)
+        const RX_IEEE1588_PTP       = 1 << 9;
+        /// #define RTE_MBUF_F_RX_IEEE1588_TMST    (1ULL << 10)
+        const RX_IEEE1588_TMST      = 1 << 10;
+        /// #define RTE_MBUF_F_RX_FDIR_ID          (1ULL << 13)
+        const RX_FDIR_ID            = 1 << 13;
+        /// #define RTE_MBUF_F_RX_FDIR_FLX         (1ULL << 14)
+        const RX_FDIR_FLX           = 1 << 14;
+        /// #define RTE_MBUF_F_RX_QINQ_STRIPPED    (1ULL << 15)
+        const RX_QINQ_STRIPPED      = 1 << 15;
+        /// #define RTE_MBUF_F_RX_LRO              (1ULL << 16)
+        const RX_LRO                = 1 << 16;
+        /// #define RTE_MBUF_F_RX_SEC_OFFLOAD      (1ULL << 18)
+        const RX_SEC_OFFLOAD        = 1 << 18;
+        /// #define RTE_MBUF_F_RX_SEC_OFFLOAD_FAILED (1ULL << 19)
+        const RX_SEC_OFFLOAD_FAILED = 1 << 19;
+        /// #define RTE_MBUF_F_RX_QINQ             (1ULL << 20)
+        const RX_QINQ               = 1 << 20;
+
+        /// #define RTE_MBUF_F_TX_OUTER_UDP_CKSUM  (1ULL << 41)
+        const TX_OUTER_UDP_CKSUM    = 1 << 41;
+        /// #define RTE_MBUF_F_TX_UDP_SEG          (1ULL << 42)
+        const TX_UDP_SEG            = 1 << 42;
+        /// #define RTE_MBUF_F_TX_SEC_OFFLOAD      (1ULL << 43)
+        const TX_SEC_OFFLOAD        = 1 << 43;
+        /// #define RTE_MBUF_F_TX_MACSEC           (1ULL << 44)
+        const TX_MACSEC             = 1 << 44;
+        /// #define RTE_MBUF_F_TX_QINQ             (1ULL << 49)
+        const TX_QINQ               = 1 << 49;
+        /// #define RTE_MBUF_F_TX_TCP_SEG          (1ULL << 50)
+        const TX_TCP_SEG            = 1 << 50;
+        /// #define RTE_MBUF_F_TX_IEEE1588_TMST    (1ULL << 51)
+        const TX_IEEE1588_TMST      = 1 << 51;
+        /// #define RTE_MBUF_F_TX_TCP_CKSUM        (1ULL << 52)
+        const TX_TCP_CKSUM          = 1 << 52;
+        /// #define RTE_MBUF_F_TX_SCTP_CKSUM       (2ULL << 52)
+        const TX_SCTP_CKSUM         = 2 << 52;
+        /// #define RTE_MBUF_F_TX_UDP_CKSUM        (3ULL << 52)
+        const TX_UDP_CKSUM          = 3 << 52;
+        /// #define RTE_MBUF_F_TX_L4_MASK          (3ULL << 52)
+        const TX_L4_MASK            = 3 << 52;
+        /// #define RTE_MBUF_F_TX_IP_CKSUM         (1ULL << 54)
+        const TX_IP_CKSUM           = 1 << 54;
+        /// #define RTE_MBUF_F_TX_IPV4             (1ULL << 55)
+        const TX_IPV4               = 1 << 55;
+        /// #define RTE_MBUF_F_TX_IPV6             (1ULL << 56)
+        const TX_IPV6               = 1 << 56;
+        /// #define RTE_MBUF_F_TX_VLAN             (1ULL << 57)
+        const TX_VLAN               = 1 << 57;
+        /// #define RTE_MBUF_F_TX_OUTER_IP_CKSUM   (1ULL << 58)
+        const TX_OUTER_IP_CKSUM     = 1 << 58;
+        /// #define RTE_MBUF_F_TX_OUTER_IPV4       (1ULL << 59)
+        const TX_OUTER_IPV4         = 1 << 59;
+        /// #define RTE_MBUF_F_TX_OUTER_IPV6       (1ULL << 60)
+        const TX_OUTER_IPV6         = 1 << 60;
+
+        /// #define RTE_MBUF_F_EXTERNAL            (1ULL << 61)
+        const EXTERNAL              = 1 << 61;
+        /// #define RTE_MBUF_F_INDIRECT            (1ULL << 62)
+        const INDIRECT              = 1 << 62;
+    }
+}
+
+// The vlan offload bit fields are extracted from dpdk/lib/ethdev/rte_ethdev.h
+dpdk_offload_conf!(
+    pub struct VlanOffload(u32) {
+        /// #define RTE_ETH_VLAN_STRIP_OFFLOAD  0x0001
+        strip,  enable_strip,  1 << 0,
+
+        /// #define RTE_ETH_VLAN_FILTER_OFFLOAD 0x0002
+        filter, enable_filter, 1 << 1,
+
+        /// #define RTE_ETH_VLAN_EXTEND_OFFLOAD 0x0004
+        extend, enable_extend, 1 << 2,
     }
 );
 
@@ -119,6 +250,9 @@ dpdk_offload_conf!(
 
         /// #define RTE_ETH_TX_OFFLOAD_TCP_CKSUM        RTE_BIT64(3)
         tcp_cksum,  enable_tcp_cksum,  1 << 3,
+
+        /// #define RTE_ETH_TX_OFFLOAD_SEND_ON_TIMESTAMP RTE_BIT64(21)
+        send_on_timestamp, enable_send_on_timestamp, 1 << 21,
     }
 );
 
@@ -136,6 +270,9 @@ dpdk_offload_conf!(
 
         /// #define RTE_ETH_RX_OFFLOAD_RSS_HASH         RTE_BIT64(19)
         rss_hash,   enable_rss_hash,   1 << 19,
+
+        /// #define RTE_ETH_RX_OFFLOAD_TIMESTAMP        RTE_BIT64(14)
+        timestamp,  enable_timestamp,  1 << 14,
     }
 );
 
@@ -156,6 +293,9 @@ dpdk_offload_conf!(
 
         /// #define RTE_ETH_TX_OFFLOAD_MULTI_SEGS       RTE_BIT64(15)
         multi_segs, enable_multi_segs, 1 << 15,
+
+        /// #define RTE_ETH_TX_OFFLOAD_SEND_ON_TIMESTAMP RTE_BIT64(21)
+        send_on_timestamp, enable_send_on_timestamp, 1 << 21,
     }
 );
 
@@ -179,6 +319,90 @@ dpdk_offload_conf!(
 
         /// #define RTE_ETH_RX_OFFLOAD_SCATTER          RTE_BIT64(13)
         scatter,    enable_scatter,    1 << 13,
+
+        /// #define RTE_ETH_RX_OFFLOAD_TIMESTAMP        RTE_BIT64(14)
+        timestamp,  enable_timestamp,  1 << 14,
         
     }
 );
+
+// The packet type masks are extracted from dpdk/lib/mbuf/rte_mbuf_ptype.h
+const RTE_PTYPE_L2_MASK: u32 = 0x0000_000f;
+const RTE_PTYPE_L3_MASK: u32 = 0x0000_00f0;
+const RTE_PTYPE_L4_MASK: u32 = 0x0000_0f00;
+const RTE_PTYPE_L4_TCP: u32 = 0x0000_0100;
+const RTE_PTYPE_L4_UDP: u32 = 0x0000_0200;
+const RTE_PTYPE_L4_SCTP: u32 = 0x0000_0400;
+
+/// The offsets of the layers of a packet, which are parsed by the PMD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayerOffsets {
+    /// The offset of the l3 header, which is the length of the l2 headers.
+    pub l3: usize,
+    /// The offset of the l3 payload, which is the l4 header unless the packet
+    /// is a non-first fragment.
+    pub l4: usize,
+    /// The offset of the l4 payload, which is only known for the tcp, udp and
+    /// sctp packets with a parsed l4 header length.
+    pub payload: Option<usize>,
+}
+
+impl LayerOffsets {
+    // Compute the offsets from the `packet_type` and the `l2_len`, `l3_len` and
+    // `l4_len` fields of an mbuf, return `None` if the l2 or the l3 header is not
+    // parsed, or the offsets exceed `pkt_len`.
+    pub(crate) fn from_raw(raw: &ffi::rte_mbuf) -> Option<Self> {
+        let ptype = unsafe { raw.__bindgen_anon_1.packet_type };
+        let (l2_len, l3_len, l4_len) = unsafe {
+            let lens = &raw.__bindgen_anon_3.__bindgen_anon_1;
+            (lens.l2_len(), lens.l3_len(), lens.l4_len())
+        };
+        if ptype & RTE_PTYPE_L2_MASK == 0 || ptype & RTE_PTYPE_L3_MASK == 0 {
+            return None;
+        }
+        if l2_len == 0 || l3_len == 0 {
+            return None;
+        }
+
+        let l3 = l2_len as usize;
+        let l4 = l3 + l3_len as usize;
+        let payload = match ptype & RTE_PTYPE_L4_MASK {
+            RTE_PTYPE_L4_TCP | RTE_PTYPE_L4_UDP | RTE_PTYPE_L4_SCTP if l4_len != 0 => {
+                Some(l4 + l4_len as usize)
+            }
+            _ => None,
+        };
+        if payload.unwrap_or(l4) > raw.pkt_len as usize {
+            return None;
+        }
+        Some(Self { l3, l4, payload })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsupported_offload_is_named() {
+        let mut capa = DevRxOffload::ALL_DISABLED;
+        capa.enable_ipv4_cksum();
+
+        let mut offloads = DevRxOffload::ALL_DISABLED;
+        offloads.enable_ipv4_cksum();
+        assert_eq!(offloads.unsupported_by(capa), None);
+
+        offloads.enable_udp_cksum();
+        assert_eq!(
+            offloads.unsupported_by(capa),
+            Some("unsupported DevRxOffload: udp_cksum")
+        );
+
+        let mut offloads = DevTxOffload::ALL_DISABLED;
+        offloads.enable_tcp_cksum();
+        assert_eq!(
+            offloads.unsupported_by(DevTxOffload::ALL_DISABLED),
+            Some("unsupported DevTxOffload: tcp_cksum")
+        );
+    }
+}
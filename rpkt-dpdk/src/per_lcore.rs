@@ -0,0 +1,176 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use rpkt_dpdk_sys as ffi;
+
+// Each slot takes its own cache line, so that the lcores updating their own
+// slots do not false-share.
+#[repr(align(64))]
+struct Slot<T> {
+    value: T,
+    // the token of the thread that owns the slot, or 0 if no thread has
+    // accessed it through `get`
+    owner: AtomicU64,
+}
+
+// A token of the current thread that is unique in the process, unlike the
+// lcore id that is reused once a non-EAL thread unregisters.
+fn thread_token() -> u64 {
+    static NEXT_TOKEN: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static TOKEN: u64 = NEXT_TOKEN.fetch_add(1, Ordering::Relaxed);
+    }
+    TOKEN.with(|token| *token)
+}
+
+/// A value of `T` for each lcore, indexed by `rte_lcore_id`.
+///
+/// This is the counterpart of `RTE_PER_LCORE`: each lcore accesses its own
+/// slot through `get` without locking, e.g. for the per-lcore counters and
+/// scratch buffers of a pipeline. The slots of all the lcores are visited with
+/// `iter` or `iter_mut`, e.g. to aggregate the counters.
+///
+/// The first thread that accesses a slot through `get` owns it for the
+/// lifetime of the `PerLcore`. A non-EAL thread may unregister while it still
+/// holds a reference to its slot, and the next thread registered with the
+/// same lcore id gets no slot, rather than sharing it.
+pub struct PerLcore<T> {
+    slots: Box<[Slot<T>]>,
+}
+
+unsafe impl<T: Send> Send for PerLcore<T> {}
+// Safety: `get` hands out a slot only to its owner thread, so the slots are
+// only shared across threads through `iter`, which requires `T: Sync`.
+unsafe impl<T: Send> Sync for PerLcore<T> {}
+
+impl<T> PerLcore<T> {
+    /// Create the slots for all the `RTE_MAX_LCORE` lcores, the slot of each
+    /// lcore is initialized by `f` with the lcore id.
+    pub fn new(f: impl FnMut(u32) -> T) -> Self {
+        Self {
+            slots: (0..ffi::RTE_MAX_LCORE)
+                .map(f)
+                .map(|value| Slot {
+                    value,
+                    owner: AtomicU64::new(0),
+                })
+                .collect(),
+        }
+    }
+
+    /// Return the slot of the current lcore.
+    ///
+    /// # Panics
+    /// This function panics if the current thread has no lcore id, i.e. it is
+    /// neither an EAL lcore nor a registered non-EAL thread, or if the slot is
+    /// owned by another thread, see `PerLcore`.
+    #[inline]
+    pub fn get(&self) -> &T {
+        self.try_get()
+            .expect("the current thread is not the owner of an lcore slot")
+    }
+
+    /// Return the slot of the current lcore, or `None` if the current thread
+    /// has no lcore id or the slot is owned by another thread.
+    #[inline]
+    pub fn try_get(&self) -> Option<&T> {
+        let lcore_id = unsafe { ffi::rte_lcore_id_() };
+        let slot = self.slots.get(lcore_id as usize)?;
+        let token = thread_token();
+        if slot.owner.load(Ordering::Relaxed) != token {
+            // the slot is claimed by the first thread that accesses it
+            slot.owner
+                .compare_exchange(0, token, Ordering::Relaxed, Ordering::Relaxed)
+                .ok()?;
+        }
+        Some(&slot.value)
+    }
+
+    /// Iterate over the slots of all the lcores with exclusive access, e.g.
+    /// to aggregate the non-atomic counters after the lcores stop.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        self.slots.iter_mut().map(|slot| &mut slot.value)
+    }
+}
+
+impl<T: Sync> PerLcore<T> {
+    /// Iterate over the slots of all the lcores, while the lcores may still
+    /// update their own slots.
+    ///
+    /// This requires `T: Sync`, e.g. the atomic counters, as the slots are read
+    /// from another thread.
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.slots.iter().map(|slot| &slot.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[test]
+    fn count_on_two_lcores() {
+        DpdkOption::new().init().unwrap();
+
+        let mut counters = PerLcore::new(|_| Cell::new(0_u64));
+        let totals = PerLcore::new(|_| AtomicU64::new(0));
+        std::thread::scope(|s| {
+            for n in [1000, 234] {
+                let (counters, totals) = (&counters, &totals);
+                s.spawn(move || {
+                    // the test threads have no lcore id until they are
+                    // registered
+                    assert!(counters.try_get().is_none());
+                    assert_eq!(unsafe { ffi::rte_thread_register() }, 0);
+                    for _ in 0..n {
+                        let counter = counters.get();
+                        counter.set(counter.get() + 1);
+                        totals.get().fetch_add(1, Ordering::Relaxed);
+                    }
+                    assert_eq!(counters.get().get(), n);
+                });
+            }
+        });
+
+        let sum: u64 = counters.iter_mut().map(|counter| counter.get()).sum();
+        assert_eq!(sum, 1234);
+        // exactly two slots are used
+        assert_eq!(counters.iter_mut().filter(|c| c.get() > 0).count(), 2);
+        let sum: u64 = totals
+            .iter()
+            .map(|total| total.load(Ordering::Relaxed))
+            .sum();
+        assert_eq!(sum, 1234);
+    }
+
+    #[test]
+    fn reused_lcore_id() {
+        DpdkOption::new().init().unwrap();
+
+        let counters = PerLcore::new(|_| Cell::new(0_u64));
+        let lcore_id = std::thread::scope(|s| {
+            s.spawn(|| {
+                assert_eq!(unsafe { ffi::rte_thread_register() }, 0);
+                counters.get().set(1);
+                let lcore_id = unsafe { ffi::rte_lcore_id_() };
+                unsafe { ffi::rte_thread_unregister() };
+                lcore_id
+            })
+            .join()
+            .unwrap()
+        });
+
+        // the released lcore id is given to the next registered thread, which
+        // does not get the slot of the previous thread
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                assert_eq!(unsafe { ffi::rte_thread_register() }, 0);
+                assert_eq!(unsafe { ffi::rte_lcore_id_() }, lcore_id);
+                assert!(counters.try_get().is_none());
+                unsafe { ffi::rte_thread_unregister() };
+            });
+        });
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use arrayvec::ArrayVec;
+
+use crate::Mbuf;
+
+/// A stage of a packet processing pipeline that works on bursts of mbufs.
+///
+/// A stage consumes all the mbufs in `input`, and pushes the mbufs that are
+/// passed to the next stage into `output`. The mbufs that do not fit in
+/// `output` are dropped. The burst size `N` is a parameter of the trait, so that
+/// stages can be chained as trait objects.
+pub trait Stage<const N: usize> {
+    fn process(&mut self, input: &mut ArrayVec<Mbuf, N>, output: &mut ArrayVec<Mbuf, N>);
+}
+
+/// A chain of stages, where the output of each stage is the input of the next.
+///
+/// `Pipeline` is a `Stage` itself, so pipelines can be nested.
+pub struct Pipeline<const N: usize> {
+    stages: Vec<Box<dyn Stage<N> + Send>>,
+    scratch: ArrayVec<Mbuf, N>,
+}
+
+impl<const N: usize> Pipeline<N> {
+    pub fn new() -> Self {
+        Self {
+            stages: Vec::new(),
+            scratch: ArrayVec::new(),
+        }
+    }
+
+    /// Append a stage to the end of the pipeline.
+    pub fn add_stage<S: Stage<N> + Send + 'static>(&mut self, stage: S) {
+        self.stages.push(Box::new(stage));
+    }
+
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+}
+
+impl<const N: usize> Default for Pipeline<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Stage<N> for Pipeline<N> {
+    fn process(&mut self, input: &mut ArrayVec<Mbuf, N>, output: &mut ArrayVec<Mbuf, N>) {
+        let nb_stages = self.stages.len();
+        if nb_stages == 0 {
+            pass_all(input, output);
+            return;
+        }
+
+        for (i, stage) in self.stages.iter_mut().enumerate() {
+            #[cfg(feature = "trace")]
+            let _span = crate::trace::stage(i, input.len()).entered();
+            if i + 1 == nb_stages {
+                stage.process(input, output);
+            } else {
+                stage.process(input, &mut self.scratch);
+                // `input` is now empty, the output of this stage becomes the
+                // input of the next stage
+                std::mem::swap(input, &mut self.scratch);
+            }
+        }
+    }
+}
+
+// Move all the mbufs from `input` to `output`, the extra mbufs are dropped.
+fn pass_all<const N: usize>(input: &mut ArrayVec<Mbuf, N>, output: &mut ArrayVec<Mbuf, N>) {
+    for mbuf in input.drain(..) {
+        let _ = output.try_push(mbuf);
+    }
+}
+
+/// A stage that drops all the mbufs.
+pub struct DropStage;
+
+impl<const N: usize> Stage<N> for DropStage {
+    fn process(&mut self, input: &mut ArrayVec<Mbuf, N>, _output: &mut ArrayVec<Mbuf, N>) {
+        input.clear();
+    }
+}
+
+/// A stage that counts the mbufs passing through it.
+///
+/// The counter is shared, so that it can be read after the stage is moved
+/// into a `Pipeline`.
+#[derive(Default)]
+pub struct CountStage {
+    counter: Arc<AtomicU64>,
+}
+
+impl CountStage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return a handle to the counter.
+    pub fn counter(&self) -> Arc<AtomicU64> {
+        self.counter.clone()
+    }
+}
+
+impl<const N: usize> Stage<N> for CountStage {
+    fn process(&mut self, input: &mut ArrayVec<Mbuf, N>, output: &mut ArrayVec<Mbuf, N>) {
+        self.counter
+            .fetch_add(input.len() as u64, Ordering::Relaxed);
+        pass_all(input, output);
+    }
+}
+
+/// A stage that keeps the mbufs matching the predicate and drops the others.
+pub struct FilterStage<F> {
+    predicate: F,
+}
+
+impl<F: FnMut(&Mbuf) -> bool> FilterStage<F> {
+    pub fn new(predicate: F) -> Self {
+        Self { predicate }
+    }
+}
+
+impl<const N: usize, F: FnMut(&Mbuf) -> bool> Stage<N> for FilterStage<F> {
+    fn process(&mut self, input: &mut ArrayVec<Mbuf, N>, output: &mut ArrayVec<Mbuf, N>) {
+        for mbuf in input.drain(..) {
+            if (self.predicate)(&mbuf) {
+                let _ = output.try_push(mbuf);
+            }
+        }
+    }
+}
+
+/// The reason why a pipeline drops a packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DropReason {
+    /// The packet can not be parsed.
+    ParseError,
+    /// There is no route for the packet.
+    NoRoute,
+    /// The packet is rejected by a filter.
+    Filtered,
+    /// The ring or the queue that the packet is sent to is full.
+    RingFull,
+    /// The packet is dropped for any other reason.
+    Other,
+}
+
+impl DropReason {
+    /// All the drop reasons.
+    pub const ALL: [DropReason; 5] = [
+        DropReason::ParseError,
+        DropReason::NoRoute,
+        DropReason::Filtered,
+        DropReason::RingFull,
+        DropReason::Other,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// The per-reason counters of the dropped packets.
+///
+/// The counters are atomic, so that they can be shared by the pipelines
+/// running on different lcores, e.g. through an `Arc`.
+#[derive(Default)]
+pub struct DropCounters {
+    counters: [AtomicU64; DropReason::ALL.len()],
+}
+
+impl DropCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a packet dropped for `reason`.
+    #[inline]
+    pub fn inc(&self, reason: DropReason) {
+        self.counters[reason.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Return the number of the packets dropped for `reason`.
+    pub fn get(&self, reason: DropReason) -> u64 {
+        self.counters[reason.index()].load(Ordering::Relaxed)
+    }
+
+    /// Return the number of the dropped packets of each reason.
+    pub fn snapshot(&self) -> HashMap<DropReason, u64> {
+        DropReason::ALL
+            .iter()
+            .map(|reason| (*reason, self.get(*reason)))
+            .collect()
+    }
+}
+
+/// Free `mbuf` and count it as dropped for `reason`.
+#[inline]
+pub fn drop_mbuf(mbuf: Mbuf, reason: DropReason, counters: &DropCounters) {
+    counters.inc(reason);
+    drop(mbuf);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn filter_and_count_pipeline() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let before = CountStage::new();
+            let total = before.counter();
+            let after = CountStage::new();
+            let passed = after.counter();
+
+            let mut pipeline = Pipeline::<32>::new();
+            pipeline.add_stage(before);
+            // keep the mbufs with an even length
+            pipeline.add_stage(FilterStage::new(|mbuf: &Mbuf| mbuf.len() % 2 == 0));
+            pipeline.add_stage(after);
+            assert_eq!(pipeline.len(), 3);
+
+            let mut input = ArrayVec::<_, 32>::new();
+            mp.fill_batch(&mut input);
+            for (i, mbuf) in input.iter_mut().enumerate() {
+                mbuf.extend_from_slice(&vec![0; i + 1][..]);
+            }
+            let mut output = ArrayVec::new();
+            pipeline.process(&mut input, &mut output);
+
+            assert_eq!(input.len(), 0);
+            assert_eq!(output.len(), 16);
+            assert!(output.iter().all(|mbuf| mbuf.len() % 2 == 0));
+            assert_eq!(total.load(Ordering::Relaxed), 32);
+            assert_eq!(passed.load(Ordering::Relaxed), 16);
+            // the filtered mbufs are returned to the mempool
+            assert_eq!(mp.nb_mbufs(), 128 - 16);
+
+            let mut input = output;
+            let mut output = ArrayVec::new();
+            DropStage.process(&mut input, &mut output);
+            assert_eq!(input.len() + output.len(), 0);
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn drop_with_reasons() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let counters = DropCounters::new();
+            let reasons = [
+                (DropReason::ParseError, 3),
+                (DropReason::Filtered, 5),
+                (DropReason::RingFull, 1),
+            ];
+            for (reason, nb_drops) in reasons {
+                for _ in 0..nb_drops {
+                    drop_mbuf(mp.try_alloc().unwrap(), reason, &counters);
+                }
+            }
+            assert_eq!(mp.nb_mbufs(), 128);
+
+            let snapshot = counters.snapshot();
+            assert_eq!(snapshot.len(), DropReason::ALL.len());
+            assert_eq!(snapshot[&DropReason::ParseError], 3);
+            assert_eq!(snapshot[&DropReason::Filtered], 5);
+            assert_eq!(snapshot[&DropReason::RingFull], 1);
+            assert_eq!(snapshot[&DropReason::NoRoute], 0);
+            assert_eq!(counters.get(DropReason::Other), 0);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+}
@@ -1,11 +1,21 @@
+#[cfg(feature = "trace")]
+use std::cell::Cell;
+use std::cell::UnsafeCell;
 use std::ffi::CStr;
+use std::os::raw::c_void;
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 
 use arrayvec::ArrayVec;
 use rpkt_dpdk_sys as ffi;
 
 use crate::error::*;
 use crate::offload::*;
+use crate::service;
+#[cfg(feature = "trace")]
+use crate::trace;
 use crate::Mbuf;
 use crate::Mempool;
 
@@ -23,6 +33,18 @@ impl DescLim {
     pub fn nb_align(&self) -> u16 {
         self.0.nb_align
     }
+
+    /// Return the maximum number of the segments of a packet, which is the
+    /// maximum number of the descriptors that a packet takes.
+    pub fn nb_seg_max(&self) -> u16 {
+        self.0.nb_seg_max
+    }
+
+    /// Return the maximum number of the segments of a packet that does not
+    /// exceed the mtu, i.e. a non-tso packet.
+    pub fn nb_mtu_seg_max(&self) -> u16 {
+        self.0.nb_mtu_seg_max
+    }
 }
 
 pub struct PortInfo {
@@ -31,6 +53,8 @@ pub struct PortInfo {
     pub started: bool,
     pub eth_addr: [u8; 6],
     pub driver_name: String,
+    bus_name: String,
+    bus_info: Option<String>,
     raw: ffi::rte_eth_dev_info,
 }
 
@@ -53,6 +77,14 @@ impl PortInfo {
             return Error::ffi_err(res, "fail to get eth mac addrress").to_err();
         }
 
+        // The bus info is only reported by some buses, e.g. the vendor and the
+        // device ids of a pci device.
+        let to_string = |s: *const std::os::raw::c_char| {
+            (!s.is_null()).then(|| CStr::from_ptr(s).to_str().unwrap_or("").to_owned())
+        };
+        let bus_name = to_string(ffi::rte_bus_name(ffi::rte_dev_bus(dev_info.device)));
+        let bus_info = to_string(ffi::rte_dev_bus_info(dev_info.device));
+
         Ok(PortInfo {
             port_id,
             socket_id: socket_id as u32,
@@ -62,12 +94,27 @@ impl PortInfo {
                 .to_str()
                 .unwrap_or("")
                 .to_owned(),
+            bus_name: bus_name.unwrap_or_default(),
+            bus_info,
             raw: dev_info,
         })
     }
 }
 
 impl PortInfo {
+    // device info
+    /// Return the name of the bus of the device, e.g. `pci` or `vdev`.
+    pub fn bus_name(&self) -> &str {
+        &self.bus_name
+    }
+
+    /// Return the bus-specific info of the device, e.g.
+    /// `vendor_id=8086, device_id=1572` for a pci device, or `None` if the bus
+    /// reports no info.
+    pub fn bus_info(&self) -> Option<&str> {
+        self.bus_info.as_deref()
+    }
+
     // mtu info
     pub fn min_mtu(&self) -> u16 {
         self.raw.min_mtu
@@ -105,6 +152,16 @@ impl PortInfo {
         DevTxOffload(self.raw.tx_offload_capa & DevTxOffload::ALL_ENABLED.0)
     }
 
+    /// Check whether all the rx offloads enabled in `offloads` are supported.
+    pub fn supports_rx(&self, offloads: DevRxOffload) -> bool {
+        offloads.unsupported_by(self.rx_offload_capa()).is_none()
+    }
+
+    /// Check whether all the tx offloads enabled in `offloads` are supported.
+    pub fn supports_tx(&self, offloads: DevTxOffload) -> bool {
+        offloads.unsupported_by(self.tx_offload_capa()).is_none()
+    }
+
     // rss info
     pub fn reta_size(&self) -> u16 {
         self.raw.reta_size
@@ -122,11 +179,130 @@ impl PortInfo {
         DescLim(self.raw.tx_desc_lim)
     }
 
+    /// Return the maximum number of the segments of a non-tso packet that the
+    /// port can transmit, a longer chain is dropped by the driver. See
+    /// `Mbuf::coalesce_to_max_segs`.
+    pub fn max_tx_segments(&self) -> u16 {
+        self.raw.tx_desc_lim.nb_mtu_seg_max
+    }
+
     pub fn rx_desc_lim(&self) -> DescLim {
         DescLim(self.raw.rx_desc_lim)
     }
 }
 
+/// The flow control (pause frame) mode of a port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlowCtrlMode {
+    /// Disable flow control.
+    None,
+    /// Rx pause frames are honored.
+    RxPause,
+    /// Tx pause frames are sent.
+    TxPause,
+    /// Both rx and tx pause are enabled.
+    Full,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FlowCtrlConf {
+    pub mode: FlowCtrlMode,
+    /// The high threshold value to trigger XOFF.
+    pub high_water: u32,
+    /// The low threshold value to trigger XON.
+    pub low_water: u32,
+    /// The pause quota in the pause frame.
+    pub pause_time: u16,
+    /// Whether to send XON frames.
+    pub send_xon: bool,
+    /// Whether to forward MAC control frames.
+    pub mac_ctrl_frame_fwd: bool,
+    /// Whether to use pause autoneg.
+    pub autoneg: bool,
+}
+
+impl FlowCtrlConf {
+    pub fn set_mode(&mut self, val: FlowCtrlMode) {
+        self.mode = val;
+    }
+
+    pub fn set_high_water(&mut self, val: u32) {
+        self.high_water = val;
+    }
+
+    pub fn set_low_water(&mut self, val: u32) {
+        self.low_water = val;
+    }
+
+    pub fn set_pause_time(&mut self, val: u16) {
+        self.pause_time = val;
+    }
+
+    pub fn set_send_xon(&mut self, val: bool) {
+        self.send_xon = val;
+    }
+
+    pub fn set_mac_ctrl_frame_fwd(&mut self, val: bool) {
+        self.mac_ctrl_frame_fwd = val;
+    }
+
+    pub fn set_autoneg(&mut self, val: bool) {
+        self.autoneg = val;
+    }
+
+    pub(crate) fn try_get(port_id: u16) -> Result<Self> {
+        let mut fc_conf: ffi::rte_eth_fc_conf = unsafe { std::mem::zeroed() };
+        let res = unsafe {
+            ffi::rte_eth_dev_flow_ctrl_get(port_id, &mut fc_conf as *mut ffi::rte_eth_fc_conf)
+        };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to get flow control conf").to_err();
+        }
+
+        let mode = match fc_conf.mode {
+            ffi::rte_eth_fc_mode_RTE_ETH_FC_RX_PAUSE => FlowCtrlMode::RxPause,
+            ffi::rte_eth_fc_mode_RTE_ETH_FC_TX_PAUSE => FlowCtrlMode::TxPause,
+            ffi::rte_eth_fc_mode_RTE_ETH_FC_FULL => FlowCtrlMode::Full,
+            _ => FlowCtrlMode::None,
+        };
+
+        Ok(Self {
+            mode,
+            high_water: fc_conf.high_water,
+            low_water: fc_conf.low_water,
+            pause_time: fc_conf.pause_time,
+            send_xon: fc_conf.send_xon != 0,
+            mac_ctrl_frame_fwd: fc_conf.mac_ctrl_frame_fwd != 0,
+            autoneg: fc_conf.autoneg != 0,
+        })
+    }
+
+    pub(crate) fn apply(&self, port_id: u16) -> Result<()> {
+        let mut fc_conf: ffi::rte_eth_fc_conf = unsafe { std::mem::zeroed() };
+        fc_conf.mode = match self.mode {
+            FlowCtrlMode::None => ffi::rte_eth_fc_mode_RTE_ETH_FC_NONE,
+            FlowCtrlMode::RxPause => ffi::rte_eth_fc_mode_RTE_ETH_FC_RX_PAUSE,
+            FlowCtrlMode::TxPause => ffi::rte_eth_fc_mode_RTE_ETH_FC_TX_PAUSE,
+            FlowCtrlMode::Full => ffi::rte_eth_fc_mode_RTE_ETH_FC_FULL,
+        };
+        fc_conf.high_water = self.high_water;
+        fc_conf.low_water = self.low_water;
+        fc_conf.pause_time = self.pause_time;
+        fc_conf.send_xon = u16::from(self.send_xon);
+        fc_conf.mac_ctrl_frame_fwd = u8::from(self.mac_ctrl_frame_fwd);
+        fc_conf.autoneg = u8::from(self.autoneg);
+
+        // The PMDs without flow control support return -ENOTSUP.
+        let res = unsafe {
+            ffi::rte_eth_dev_flow_ctrl_set(port_id, &mut fc_conf as *mut ffi::rte_eth_fc_conf)
+        };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to set flow control conf").to_err();
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone)]
 pub struct PortConf {
     pub mtu: u32, // packet length except ethernet overhead
@@ -135,6 +311,8 @@ pub struct PortConf {
     pub rss_hf: RssHashFunc,
     pub rss_hash_key: Vec<u8>,
     pub enable_promiscuous: bool,
+    /// Enable the rx queue interrupts, see `RxQueue::intr_fd`.
+    pub enable_rx_intr: bool,
 }
 
 impl PortConf {
@@ -214,6 +392,7 @@ impl PortConf {
             rss_hf: port_info.flow_type_rss_offloads(),
             rss_hash_key: DEFAULT_RSS_KEY_40B.to_vec(),
             enable_promiscuous: true,
+            enable_rx_intr: false,
         })
     }
 
@@ -243,36 +422,27 @@ impl PortConf {
         self.enable_promiscuous = val;
     }
 
+    pub fn set_enable_rx_intr(&mut self, val: bool) {
+        self.enable_rx_intr = val;
+    }
+
     // Safety: The returned `rte_eth_conf` must not live past `PortConf`.
     unsafe fn rte_eth_conf(&self, nb_rxq: u16, _nb_txq: u16) -> ffi::rte_eth_conf {
-        let mut rx_mode: ffi::rte_eth_rxmode = std::mem::zeroed();
+        let mut eth_conf = EthConf::new();
         if nb_rxq > 0 {
-            rx_mode.mq_mode = ffi::rte_eth_rx_mq_mode_RTE_ETH_MQ_RX_RSS;
-        } else {
-            rx_mode.mq_mode = ffi::rte_eth_rx_mq_mode_RTE_ETH_MQ_RX_NONE;
+            eth_conf.rx_mq_rss(self.rss_hf);
         }
         // for mlx5 nic, we must set kernel mtu to 9000 first in order to send jumbo frames
         // we can set with this command: ifconfig 'IFACE' mtu 9000
         // Yupeng provides this link: https://docs.nvidia.com/networking/display/MFTv4110/Using+mlxconfig
-        rx_mode.mtu = self.mtu;
-        // rx_mode.max_lro_pkt_size
-        rx_mode.offloads = self.rx_offloads.0;
-
-        let mut tx_mode: ffi::rte_eth_txmode = std::mem::zeroed();
-        tx_mode.mq_mode = ffi::rte_eth_tx_mq_mode_RTE_ETH_MQ_TX_NONE;
-        tx_mode.offloads = self.tx_offloads.0;
-
-        let mut rss_conf: ffi::rte_eth_rss_conf = std::mem::zeroed();
-        rss_conf.rss_key = self.rss_hash_key.as_ptr() as *mut u8;
-        rss_conf.rss_key_len = self.rss_hash_key.len() as u8;
-        rss_conf.rss_hf = self.rss_hf.0;
-
-        let mut eth_conf: ffi::rte_eth_conf = std::mem::zeroed();
-        eth_conf.rxmode = rx_mode;
-        eth_conf.txmode = tx_mode;
-        eth_conf.rx_adv_conf.rss_conf = rss_conf;
-
         eth_conf
+            .mtu(self.mtu)
+            .rx_offloads(self.rx_offloads)
+            .tx_offloads(self.tx_offloads)
+            .rss_hash_key(&self.rss_hash_key[..])
+            .rx_intr(self.enable_rx_intr);
+
+        eth_conf.build()
     }
 }
 
@@ -285,6 +455,121 @@ impl Default for PortConf {
             rss_hf: RssHashFunc::ALL_DISABLED,
             rss_hash_key: DEFAULT_RSS_KEY_40B.to_vec(),
             enable_promiscuous: true,
+            enable_rx_intr: false,
+        }
+    }
+}
+
+/// A builder of the raw `rte_eth_conf` passed to `rte_eth_dev_configure`.
+///
+/// It takes care of the nesting of the `rxmode`, `txmode` and `rx_adv_conf`
+/// fields. The fields that are not set are left zeroed.
+#[derive(Clone, Copy)]
+pub struct EthConf<'a> {
+    rx_mq_rss: bool,
+    rss_hf: RssHashFunc,
+    rss_hash_key: Option<&'a [u8]>,
+    mtu: u32,
+    rx_offloads: DevRxOffload,
+    tx_offloads: DevTxOffload,
+    rx_intr: bool,
+}
+
+impl<'a> EthConf<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Distribute the received packets to the rx queues with RSS, using hash
+    /// functions in `hash_types`.
+    pub fn rx_mq_rss(&mut self, hash_types: RssHashFunc) -> &mut Self {
+        self.rx_mq_rss = true;
+        self.rss_hf = hash_types;
+        self
+    }
+
+    /// Set the RSS hash key, the default key of the driver is used if it is not
+    /// set.
+    pub fn rss_hash_key(&mut self, key: &'a [u8]) -> &mut Self {
+        assert!(key.len() <= usize::from(u8::MAX));
+        self.rss_hash_key = Some(key);
+        self
+    }
+
+    pub fn rx_offloads(&mut self, val: DevRxOffload) -> &mut Self {
+        self.rx_offloads = val;
+        self
+    }
+
+    pub fn tx_offloads(&mut self, val: DevTxOffload) -> &mut Self {
+        self.tx_offloads = val;
+        self
+    }
+
+    /// Set the mtu, which is the packet length except the ethernet overhead.
+    pub fn mtu(&mut self, val: u32) -> &mut Self {
+        self.mtu = val;
+        self
+    }
+
+    /// Enable the rx queue interrupts, which are needed by `RxQueue::intr_fd`.
+    pub fn rx_intr(&mut self, val: bool) -> &mut Self {
+        self.rx_intr = val;
+        self
+    }
+
+    /// Set the maximum length of the received frames, including the ethernet
+    /// header and the crc.
+    ///
+    /// Recent dpdk versions configure the mtu instead of the frame length, so
+    /// this is a shorthand for setting the mtu to `len - RTE_ETHER_OVERHEAD`.
+    pub fn max_rx_pkt_len(&mut self, len: u32) -> &mut Self {
+        self.mtu(len.saturating_sub(u32::from(PortConf::RTE_ETHER_OVERHEAD)))
+    }
+
+    /// Produce the `rte_eth_conf`.
+    ///
+    /// The `rss_key` field of the result points to the key passed to
+    /// `rss_hash_key`, so the result must not be used after the key is dropped.
+    pub fn build(&self) -> ffi::rte_eth_conf {
+        let mut eth_conf: ffi::rte_eth_conf = unsafe { std::mem::zeroed() };
+
+        eth_conf.rxmode.mq_mode = if self.rx_mq_rss {
+            ffi::rte_eth_rx_mq_mode_RTE_ETH_MQ_RX_RSS
+        } else {
+            ffi::rte_eth_rx_mq_mode_RTE_ETH_MQ_RX_NONE
+        };
+        eth_conf.rxmode.mtu = self.mtu;
+        eth_conf.rxmode.offloads = self.rx_offloads.0;
+
+        eth_conf.txmode.mq_mode = ffi::rte_eth_tx_mq_mode_RTE_ETH_MQ_TX_NONE;
+        eth_conf.txmode.offloads = self.tx_offloads.0;
+
+        eth_conf.intr_conf.set_rxq(u32::from(self.rx_intr));
+
+        if self.rx_mq_rss {
+            let rss_conf = &mut eth_conf.rx_adv_conf.rss_conf;
+            rss_conf.rss_hf = self.rss_hf.0;
+            if let Some(key) = self.rss_hash_key {
+                rss_conf.rss_key = key.as_ptr() as *mut u8;
+                rss_conf.rss_key_len = key.len() as u8;
+            }
+        }
+
+        eth_conf
+    }
+}
+
+impl<'a> Default for EthConf<'a> {
+    fn default() -> Self {
+        Self {
+            rx_mq_rss: false,
+            rss_hf: RssHashFunc::ALL_DISABLED,
+            rss_hash_key: None,
+            mtu: u32::from(PortConf::RTE_ETHER_MTU),
+            rx_offloads: DevRxOffload::ALL_DISABLED,
+            tx_offloads: DevTxOffload::ALL_DISABLED,
+            rx_intr: false,
         }
     }
 }
@@ -312,6 +597,22 @@ impl Port {
             return Error::service_err("invalid rx/tx queues").to_err();
         }
 
+        // Make sure that the device supports all the requested offloads, otherwise
+        // `rte_eth_dev_configure` fails with a bare errno.
+        let port_info = unsafe { PortInfo::try_get(port_id)? };
+        if let Some(msg) = port_conf
+            .rx_offloads
+            .unsupported_by(port_info.rx_offload_capa())
+        {
+            return Error::service_err(msg).to_err();
+        }
+        if let Some(msg) = port_conf
+            .tx_offloads
+            .unsupported_by(port_info.tx_offload_capa())
+        {
+            return Error::service_err(msg).to_err();
+        }
+
         // Safety: The `rte_eth_dev_configure` only copies the payload.
         let eth_conf =
             unsafe { port_conf.rte_eth_conf(rxq_confs.len() as u16, txq_confs.len() as u16) };
@@ -327,15 +628,18 @@ impl Port {
             return Error::ffi_err(res, "fail to configure eth dev").to_err();
         }
 
+        // The descriptor numbers are adjusted to the limits of the device, so that
+        // the queue setup does not fail with a bare EINVAL.
         let rxq_cts = rxq_confs
             .iter()
             .enumerate()
             .map(move |(rx_queue_id, (nb_rx_desc, socket_id, mp))| unsafe {
+                let (nb_rx_desc, _) = Self::adjust_nb_desc(port_id, *nb_rx_desc, 0)?;
                 // Safety: rxq lives as long as mp
                 RxQueue::try_create(
                     port_id,
                     rx_queue_id as u16,
-                    *nb_rx_desc,
+                    nb_rx_desc,
                     *socket_id,
                     mp.as_ptr() as *mut ffi::rte_mempool,
                 )
@@ -347,7 +651,8 @@ impl Port {
             .into_iter()
             .enumerate()
             .map(move |(tx_queue_id, (nb_tx_desc, socket_id))| {
-                TxQueue::try_create(port_id, tx_queue_id as u16, *nb_tx_desc, *socket_id)
+                let (_, nb_tx_desc) = Self::adjust_nb_desc(port_id, 0, *nb_tx_desc)?;
+                TxQueue::try_create(port_id, tx_queue_id as u16, nb_tx_desc, *socket_id)
             })
             .collect::<Result<Vec<TxQueue>>>()?;
 
@@ -376,6 +681,22 @@ impl Port {
         })
     }
 
+    // Clamp the descriptor numbers to the limits of the device and round them up
+    // to the alignment with `rte_eth_dev_adjust_nb_rx_tx_desc`.
+    pub(crate) fn adjust_nb_desc(
+        port_id: u16,
+        mut nb_rx_desc: u16,
+        mut nb_tx_desc: u16,
+    ) -> Result<(u16, u16)> {
+        let res = unsafe {
+            ffi::rte_eth_dev_adjust_nb_rx_tx_desc(port_id, &mut nb_rx_desc, &mut nb_tx_desc)
+        };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to adjust the number of descriptors").to_err();
+        }
+        Ok((nb_rx_desc, nb_tx_desc))
+    }
+
     pub(crate) fn rx_queue(&self, qid: u16) -> Result<RxQueue> {
         let rxq_ct = self
             .rxq_cts
@@ -415,6 +736,94 @@ impl Port {
         true
     }
 
+    pub(crate) fn set_vlan_offload(&self, vlan_offload: VlanOffload) -> Result<()> {
+        let res = unsafe { ffi::rte_eth_dev_set_vlan_offload(self.port_id, vlan_offload.0 as i32) };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to set vlan offload").to_err();
+        }
+        Ok(())
+    }
+
+    pub(crate) fn vlan_offload(&self) -> Result<VlanOffload> {
+        let res = unsafe { ffi::rte_eth_dev_get_vlan_offload(self.port_id) };
+        if res < 0 {
+            return Error::ffi_err(res, "fail to get vlan offload").to_err();
+        }
+        Ok(VlanOffload(res as u32 & VlanOffload::ALL_ENABLED.0))
+    }
+
+    pub(crate) fn rss_reta_query(&self) -> Result<Vec<u16>> {
+        let reta_size = unsafe { PortInfo::try_get(self.port_id) }?.reta_size();
+        if reta_size == 0 {
+            return Error::service_err("rss reta is not supported").to_err();
+        }
+
+        // The reta is queried in groups of 64 entries, the mask of each group
+        // selects the entries to read.
+        let group_size = ffi::RTE_ETH_RETA_GROUP_SIZE as usize;
+        let nb_groups = (usize::from(reta_size) + group_size - 1) / group_size;
+        let mut groups: Vec<ffi::rte_eth_rss_reta_entry64> =
+            vec![unsafe { std::mem::zeroed() }; nb_groups];
+        for (i, group) in groups.iter_mut().enumerate() {
+            let nb_entries = std::cmp::min(group_size, usize::from(reta_size) - i * group_size);
+            group.mask = u64::MAX >> (group_size - nb_entries);
+        }
+
+        let res = unsafe {
+            ffi::rte_eth_dev_rss_reta_query(self.port_id, groups.as_mut_ptr(), reta_size)
+        };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to query rss reta").to_err();
+        }
+
+        Ok((0..usize::from(reta_size))
+            .map(|i| groups[i / group_size].reta[i % group_size])
+            .collect())
+    }
+
+    pub(crate) fn vlan_filter(&self, vlan_id: u16, on: bool) -> Result<()> {
+        let res = unsafe { ffi::rte_eth_dev_vlan_filter(self.port_id, vlan_id, i32::from(on)) };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to set vlan filter").to_err();
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_queue_stats_mapping(
+        &self,
+        qid: u16,
+        stat_idx: u8,
+        is_rx: bool,
+    ) -> Result<()> {
+        // The PMDs without per-queue stats mapping support return -ENOTSUP.
+        let res = unsafe {
+            if is_rx {
+                ffi::rte_eth_dev_set_rx_queue_stats_mapping(self.port_id, qid, stat_idx)
+            } else {
+                ffi::rte_eth_dev_set_tx_queue_stats_mapping(self.port_id, qid, stat_idx)
+            }
+        };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to set queue stats mapping").to_err();
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_queue_rate_limit(&self, qid: u16, tx_rate: u32) -> Result<()> {
+        // The PMDs without per-queue rate limit support return -ENOTSUP.
+        let res = unsafe { ffi::rte_eth_set_queue_rate_limit(self.port_id, qid, tx_rate) };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to set queue rate limit").to_err();
+        }
+        Ok(())
+    }
+
+    pub(crate) fn link_up(&self) -> Result<bool> {
+        let res = unsafe { ffi::rte_eth_link_status_(self.port_id) };
+        let status = check_ret(res, "fail to get link status")?;
+        Ok(status != 0)
+    }
+
     // Safety: the associated mempools for rxqs should be alive.
     pub(crate) fn stop_port(&self) -> Result<()> {
         if unsafe { ffi::rte_eth_dev_stop(self.port_id) } != 0 {
@@ -429,6 +838,65 @@ impl Port {
     }
 }
 
+/// A configured port that is closed when it is dropped.
+///
+/// A port must be closed with `DpdkService::port_close` before it can be
+/// configured again, or before its virtual device is removed, which is easily
+/// missed on the error paths. An `OwnedPort` takes over a port configured by
+/// `DpdkService::port_configure`, and closes it with `close` or on drop. If the
+/// port belongs to a virtual device set with `set_vdev`, the device is removed
+/// after the port is closed.
+pub struct OwnedPort {
+    port_id: u16,
+    vdev: Option<String>,
+    closed: bool,
+}
+
+impl OwnedPort {
+    /// Take over the configured port `port_id`.
+    pub fn new(port_id: u16) -> Self {
+        Self {
+            port_id,
+            vdev: None,
+            closed: false,
+        }
+    }
+
+    pub fn port_id(&self) -> u16 {
+        self.port_id
+    }
+
+    /// Remove the virtual device named `name` after the port is closed, see
+    /// `DpdkService::port_vdev_remove`.
+    pub fn set_vdev(&mut self, name: &str) {
+        self.vdev = Some(name.to_string());
+    }
+
+    /// Stop and close the port, then remove its virtual device if it is set.
+    ///
+    /// This fails if the rx/tx queues or the stats of the port are still in
+    /// use, the port is then left open.
+    pub fn close(mut self) -> Result<()> {
+        self.closed = true;
+        service().port_close(self.port_id)?;
+        if let Some(name) = self.vdev.as_ref() {
+            service().port_vdev_remove(name)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for OwnedPort {
+    fn drop(&mut self) {
+        if !self.closed {
+            let _ = service().port_close(self.port_id);
+            if let Some(name) = self.vdev.as_ref() {
+                let _ = service().port_vdev_remove(name);
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct RxQueueConf {
     pub nb_rx_desc: u16,
@@ -470,13 +938,30 @@ pub struct RxQueue {
     port_id: u16,
     qid: u16,
     counter: Arc<()>,
+    callbacks: Vec<Arc<RxCallbackState>>,
 }
 
 impl RxQueue {
     #[inline]
     pub fn rx<const N: usize>(&mut self, batch: &mut ArrayVec<Mbuf, N>) -> usize {
+        self.rx_at_most(batch, N)
+    }
+
+    /// Receive at most `max` mbufs to the end of `batch`, e.g. with a burst size
+    /// suggested by `burst::AdaptiveBurst`.
+    #[inline]
+    pub fn rx_at_most<const N: usize>(
+        &mut self,
+        batch: &mut ArrayVec<Mbuf, N>,
+        max: usize,
+    ) -> usize {
         assert!(N <= usize::from(u16::MAX));
-        unsafe {
+        if !self.callbacks.is_empty() {
+            self.release_callbacks();
+        }
+        #[cfg(feature = "trace")]
+        let span = trace::rx_burst(self.port_id, self.qid).entered();
+        let nb_rx = unsafe {
             let mbufs = std::mem::transmute::<*mut Mbuf, *mut *mut ffi::rte_mbuf>(
                 batch.as_mut_ptr().add(batch.len()),
             );
@@ -484,11 +969,105 @@ impl RxQueue {
                 self.port_id,
                 self.qid,
                 mbufs,
-                (N - batch.len()) as u16,
+                (N - batch.len()).min(max) as u16,
             ));
             batch.set_len(batch.len() + nb_rx);
             nb_rx
+        };
+        #[cfg(feature = "trace")]
+        {
+            let dropped = self
+                .callbacks
+                .iter()
+                .map(|state| state.dropped.take())
+                .sum();
+            let bytes = trace::burst_bytes(&span, &batch[batch.len() - nb_rx..]);
+            trace::record_burst(&span, nb_rx, bytes, dropped);
         }
+        nb_rx
+    }
+
+    /// Install `f` as a callback that is invoked on each received burst, before
+    /// the burst is returned by `rx`.
+    ///
+    /// `f` may reorder the mbufs of the burst, and returns the number of the
+    /// mbufs to keep: the mbufs after this number are dropped. The callback is
+    /// removed when the returned `RxCallback` or the queue is dropped.
+    pub fn add_callback<F>(&mut self, f: F) -> Result<RxCallback>
+    where
+        F: FnMut(&mut [Mbuf]) -> usize + Send + 'static,
+    {
+        let state = Arc::new(RxCallbackState {
+            f: UnsafeCell::new(Box::new(f)),
+            cb: AtomicPtr::new(std::ptr::null_mut()),
+            removed: AtomicBool::new(false),
+            #[cfg(feature = "trace")]
+            dropped: Cell::new(0),
+        });
+        let raw = unsafe {
+            ffi::rte_eth_add_rx_callback(
+                self.port_id,
+                self.qid,
+                Some(rx_callback_trampoline),
+                Arc::as_ptr(&state) as *mut c_void,
+            )
+        };
+        let cb = check_ptr(
+            raw as *mut ffi::rte_eth_rxtx_callback,
+            "fail to add rx callback",
+        )?;
+        state.cb.store(cb.as_ptr(), Ordering::Release);
+
+        self.callbacks.push(state.clone());
+        Ok(RxCallback {
+            port_id: self.port_id,
+            qid: self.qid,
+            state,
+        })
+    }
+
+    /// Enable the interrupt of the queue, so that the fd returned by `intr_fd`
+    /// becomes readable when a packet is received.
+    ///
+    /// The port must be configured with `PortConf::enable_rx_intr`.
+    pub fn enable_intr(&self) -> Result<()> {
+        let res = unsafe { ffi::rte_eth_dev_rx_intr_enable(self.port_id, self.qid) };
+        check_ret(res, "fail to enable rx interrupt").map(|_| ())
+    }
+
+    /// Disable the interrupt of the queue, e.g. while the queue is polled.
+    pub fn disable_intr(&self) -> Result<()> {
+        let res = unsafe { ffi::rte_eth_dev_rx_intr_disable(self.port_id, self.qid) };
+        check_ret(res, "fail to disable rx interrupt").map(|_| ())
+    }
+
+    /// Return the event fd of the rx interrupt of the queue, which can be
+    /// registered to an external event loop, e.g. epoll or mio.
+    ///
+    /// The setup sequence is:
+    /// 1. configure the port with `PortConf::enable_rx_intr`.
+    /// 2. get the fd with `intr_fd` and register it for readability.
+    /// 3. call `enable_intr` before waiting on the event loop.
+    /// 4. on a wakeup, read the 8-byte counter from the fd to clear it, call
+    ///    `disable_intr` and poll with `rx` until the queue is empty, then
+    ///    go back to 3.
+    ///
+    /// The fd is owned by dpdk and must not be closed.
+    pub fn intr_fd(&self) -> Result<RawFd> {
+        let fd = unsafe { ffi::rte_eth_dev_rx_intr_ctl_q_get_fd(self.port_id, self.qid) };
+        if fd < 0 {
+            return Error::ffi_err(unsafe { ffi::rte_errno_() }, "fail to get rx interrupt fd")
+                .to_err();
+        }
+        Ok(fd)
+    }
+
+    // Release the closures of the removed callbacks. No burst of this queue is
+    // running, so the removed callbacks can not be invoked any more.
+    #[cold]
+    fn release_callbacks(&mut self) {
+        self.callbacks
+            .retain(|state| !state.removed.load(Ordering::Acquire));
     }
 
     // Safety: the mp must be a valid pointer throughout the lifetime of the RxQueue
@@ -515,6 +1094,7 @@ impl RxQueue {
                 port_id,
                 qid: rx_queue_id,
                 counter: Arc::new(()),
+                callbacks: Vec::new(),
             })
         }
     }
@@ -528,6 +1108,7 @@ impl RxQueue {
             port_id: self.port_id,
             qid: self.qid,
             counter: self.counter.clone(),
+            callbacks: Vec::new(),
         })
     }
 
@@ -536,6 +1117,79 @@ impl RxQueue {
     }
 }
 
+impl Drop for RxQueue {
+    fn drop(&mut self) {
+        // Removing a callback twice is harmless, dpdk no longer finds it.
+        for state in self.callbacks.iter() {
+            state.remove(self.port_id, self.qid);
+        }
+    }
+}
+
+type RxCallbackFn = dyn FnMut(&mut [Mbuf]) -> usize + Send;
+
+// The closure of an rx callback, which is shared by the `RxCallback` handle and
+// the `RxQueue`. The handle may be dropped while a burst is running the
+// callback, so the queue keeps the closure alive until its next burst.
+struct RxCallbackState {
+    f: UnsafeCell<Box<RxCallbackFn>>,
+    cb: AtomicPtr<ffi::rte_eth_rxtx_callback>,
+    removed: AtomicBool,
+    // The number of the mbufs dropped by the closure since the last burst.
+    #[cfg(feature = "trace")]
+    dropped: Cell<usize>,
+}
+
+// The closure is only invoked by the bursts of the queue.
+unsafe impl Sync for RxCallbackState {}
+
+impl RxCallbackState {
+    fn remove(&self, port_id: u16, qid: u16) {
+        unsafe {
+            ffi::rte_eth_remove_rx_callback(port_id, qid, self.cb.load(Ordering::Acquire));
+        }
+    }
+}
+
+unsafe extern "C" fn rx_callback_trampoline(
+    _port_id: u16,
+    _queue: u16,
+    pkts: *mut *mut ffi::rte_mbuf,
+    nb_pkts: u16,
+    _max_pkts: u16,
+    user_param: *mut c_void,
+) -> u16 {
+    let state = &*(user_param as *const RxCallbackState);
+    let mbufs = std::slice::from_raw_parts_mut(pkts as *mut Mbuf, usize::from(nb_pkts));
+
+    let nb_kept = (*state.f.get())(&mut *mbufs).min(mbufs.len());
+    #[cfg(feature = "trace")]
+    state
+        .dropped
+        .set(state.dropped.get() + mbufs.len() - nb_kept);
+    for mbuf in mbufs[nb_kept..].iter_mut() {
+        std::ptr::drop_in_place(mbuf);
+    }
+    nb_kept as u16
+}
+
+/// An rx callback installed with `RxQueue::add_callback`, which is removed
+/// when dropped.
+pub struct RxCallback {
+    port_id: u16,
+    qid: u16,
+    state: Arc<RxCallbackState>,
+}
+
+impl Drop for RxCallback {
+    fn drop(&mut self) {
+        // The queue may release the closure as soon as the flag is set, so the
+        // callback is removed first.
+        self.state.remove(self.port_id, self.qid);
+        self.state.removed.store(true, Ordering::Release);
+    }
+}
+
 #[derive(Clone)]
 pub struct TxQueueConf {
     pub nb_tx_desc: u16,
@@ -577,7 +1231,11 @@ impl TxQueue {
     #[inline]
     pub fn tx<const N: usize>(&mut self, batch: &mut ArrayVec<Mbuf, N>) -> usize {
         assert!(N <= usize::from(u16::MAX));
-        unsafe {
+        #[cfg(feature = "trace")]
+        let span = trace::tx_burst(self.port_id, self.qid).entered();
+        #[cfg(feature = "trace")]
+        let bytes = trace::burst_bytes(&span, batch);
+        let nb_tx = unsafe {
             let mbufs =
                 std::mem::transmute::<*mut Mbuf, *mut *mut ffi::rte_mbuf>(batch.as_mut_ptr());
             let nb_tx = usize::from(ffi::rte_eth_tx_burst_(
@@ -591,7 +1249,70 @@ impl TxQueue {
             batch.set_len(remaining);
 
             nb_tx
+        };
+        #[cfg(feature = "trace")]
+        {
+            // the unsent mbufs are still in the batch
+            let bytes = bytes - trace::burst_bytes(&span, batch);
+            trace::record_burst(&span, nb_tx, bytes, batch.len());
+        }
+        nb_tx
+    }
+
+    /// Ask the driver to free the mbufs of the completed tx descriptors, at
+    /// most `free_cnt` of them, or all the completed ones if `free_cnt` is 0.
+    /// Return the number of the freed mbufs.
+    ///
+    /// Drivers normally free the sent mbufs lazily, when their descriptors are
+    /// reused by later bursts. A generator that reuses a fixed set of mbufs by
+    /// reference counting can call this before refilling an mbuf, so that the
+    /// data is not modified while the NIC may still be reading it.
+    ///
+    /// Not all the PMDs support this, the unsupported ones fail with
+    /// `-ENOTSUP`.
+    pub fn done_cleanup(&mut self, free_cnt: u32) -> Result<u32> {
+        let res = unsafe { ffi::rte_eth_tx_done_cleanup(self.port_id, self.qid, free_cnt) };
+        check_ret(res, "fail to clean up tx descriptors").map(|n| n as u32)
+    }
+
+    /// Send `mbuf` to each of `txqs`, e.g. to flood a frame out of all the
+    /// ports of a bridge.
+    ///
+    /// The mbuf is not copied: its reference count is raised so that each tx
+    /// queue holds one reference, and it returns to the mempool when all the
+    /// queues complete the transmission. So the queues must not modify the mbuf,
+    /// and their ports must not enable the mbuf fast free tx offload.
+    /// Return the number of queues that accept the mbuf.
+    ///
+    /// The segments of a chained mbuf are freed one by one, so the reference
+    /// count of each segment is raised, not only the one of the first segment.
+    pub fn flood(txqs: &mut [&mut TxQueue], mbuf: Mbuf) -> usize {
+        if txqs.is_empty() {
+            return 0;
+        }
+        let extra_refs = i16::try_from(txqs.len() - 1).expect("too many tx queues");
+
+        let raw = mbuf.into_raw();
+        let mut seg = raw;
+        while !seg.is_null() {
+            unsafe {
+                ffi::rte_mbuf_refcnt_update_(seg, extra_refs);
+                seg = (*seg).next;
+            }
+        }
+        let mut nb_tx = 0;
+        for txq in txqs.iter_mut() {
+            let mut mbufs = [raw];
+            let sent =
+                unsafe { ffi::rte_eth_tx_burst_(txq.port_id, txq.qid, mbufs.as_mut_ptr(), 1) };
+            if sent == 1 {
+                nb_tx += 1;
+            } else {
+                // release the reference of the rejecting queue
+                unsafe { ffi::rte_pktmbuf_free_(raw) };
+            }
         }
+        nb_tx
     }
 
     fn try_create(port_id: u16, tx_queue_id: u16, nb_tx_desc: u16, socket_id: u32) -> Result<Self> {
@@ -633,6 +1354,94 @@ impl TxQueue {
     }
 }
 
+/// A buffer that accumulates mbufs and sends them to a `TxQueue` in bulk.
+///
+/// This amortizes the cost of `rte_eth_tx_burst` when the mbufs are produced
+/// one at a time. The buffered mbufs are not sent until the buffer is full
+/// or `flush` is called. The buffer is flushed when it is dropped, so no mbuf
+/// is leaked, and the mbufs that fail to be sent are freed after the err
+/// callback.
+pub struct TxBuffer<const N: usize> {
+    // it is only taken by `into_inner`
+    txq: Option<TxQueue>,
+    batch: ArrayVec<Mbuf, N>,
+    err_callback: Option<Box<dyn FnMut(&mut ArrayVec<Mbuf, N>) + Send>>,
+}
+
+impl<const N: usize> TxBuffer<N> {
+    pub fn new(txq: TxQueue) -> Self {
+        Self {
+            txq: Some(txq),
+            batch: ArrayVec::new(),
+            err_callback: None,
+        }
+    }
+
+    /// Set a callback that is invoked with the mbufs that the tx queue fails to send.
+    ///
+    /// The mbufs left in the batch after the callback returns are dropped.
+    /// Without a callback, the unsent mbufs are dropped directly.
+    pub fn set_err_callback<F: FnMut(&mut ArrayVec<Mbuf, N>) + Send + 'static>(&mut self, f: F) {
+        self.err_callback = Some(Box::new(f));
+    }
+
+    /// Buffer `mbuf`, flush the buffer if it becomes full.
+    ///
+    /// Return the number of sent mbufs.
+    #[inline]
+    pub fn add(&mut self, mbuf: Mbuf) -> usize {
+        self.batch.push(mbuf);
+        if self.batch.is_full() {
+            self.flush()
+        } else {
+            0
+        }
+    }
+
+    /// Send all the buffered mbufs with a single tx burst.
+    ///
+    /// Return the number of sent mbufs.
+    #[inline]
+    pub fn flush(&mut self) -> usize {
+        if self.batch.is_empty() {
+            return 0;
+        }
+
+        let nb_tx = self.txq.as_mut().unwrap().tx(&mut self.batch);
+        if !self.batch.is_empty() {
+            if let Some(err_callback) = self.err_callback.as_mut() {
+                err_callback(&mut self.batch);
+            }
+            self.batch.clear();
+        }
+        nb_tx
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.batch.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.batch.is_empty()
+    }
+
+    /// Flush the buffer and return the inner `TxQueue`.
+    pub fn into_inner(mut self) -> TxQueue {
+        self.flush();
+        self.txq.take().unwrap()
+    }
+}
+
+impl<const N: usize> Drop for TxBuffer<N> {
+    fn drop(&mut self) {
+        if self.txq.is_some() {
+            self.flush();
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct PortStats(ffi::rte_eth_stats);
 
@@ -695,6 +1504,100 @@ impl Default for PortStats {
     }
 }
 
+/// A bit rate calculator over the periodic `PortStats` snapshots of a port.
+///
+/// This follows `rte_stats_bitrate`: the mean rate is the unsmoothed rate over
+/// the last interval, the ewma rate is smoothed with a weight of
+/// `alpha_percent` on the last interval, and the peak rate is the highest mean
+/// rate seen so far. All the rates are returned as a `(rx, tx)` pair in bits per
+/// second.
+pub struct BitrateStats {
+    alpha_percent: u32,
+    last: Option<(u64, u64, Instant)>,
+    mean_bps: (u64, u64),
+    ewma_bps: (u64, u64),
+    peak_bps: (u64, u64),
+}
+
+impl BitrateStats {
+    /// The default weight of the last interval, the same as `rte_stats_bitrate`.
+    pub const ALPHA_PERCENT: u32 = 20;
+
+    pub fn new() -> Self {
+        Self {
+            alpha_percent: Self::ALPHA_PERCENT,
+            last: None,
+            mean_bps: (0, 0),
+            ewma_bps: (0, 0),
+            peak_bps: (0, 0),
+        }
+    }
+
+    /// # Panics:
+    /// This function panics if `val` is larger than 100.
+    pub fn set_alpha_percent(&mut self, val: u32) {
+        assert!(val <= 100);
+        self.alpha_percent = val;
+    }
+
+    /// Feed a stats snapshot taken at `now`.
+    ///
+    /// The first snapshot only sets the baseline. A snapshot taken no later than
+    /// the previous one is ignored, and a counter that goes backwards, e.g. after
+    /// a stats reset, counts as zero bytes.
+    pub fn update(&mut self, stats: &PortStats, now: Instant) {
+        let (ibytes, obytes) = (stats.ibytes(), stats.obytes());
+        if let Some((last_ibytes, last_obytes, last_time)) = self.last {
+            let nanos = now.saturating_duration_since(last_time).as_nanos();
+            if nanos == 0 {
+                return;
+            }
+
+            let bps = |bytes: u64, last_bytes: u64| {
+                let bits = u128::from(bytes.saturating_sub(last_bytes)) * 8;
+                u64::try_from(bits * 1_000_000_000 / nanos).unwrap_or(u64::MAX)
+            };
+            self.mean_bps = (bps(ibytes, last_ibytes), bps(obytes, last_obytes));
+            self.ewma_bps = (
+                self.ewma(self.ewma_bps.0, self.mean_bps.0),
+                self.ewma(self.ewma_bps.1, self.mean_bps.1),
+            );
+            self.peak_bps = (
+                std::cmp::max(self.peak_bps.0, self.mean_bps.0),
+                std::cmp::max(self.peak_bps.1, self.mean_bps.1),
+            );
+        }
+        self.last = Some((ibytes, obytes, now));
+    }
+
+    /// Return the rates over the last interval.
+    pub fn mean_bps(&self) -> (u64, u64) {
+        self.mean_bps
+    }
+
+    /// Return the smoothed rates.
+    pub fn ewma_bps(&self) -> (u64, u64) {
+        self.ewma_bps
+    }
+
+    /// Return the highest rates over all the intervals.
+    pub fn peak_bps(&self) -> (u64, u64) {
+        self.peak_bps
+    }
+
+    fn ewma(&self, ewma: u64, sample: u64) -> u64 {
+        let alpha = u128::from(self.alpha_percent);
+        let res = (u128::from(sample) * alpha + u128::from(ewma) * (100 - alpha) + 50) / 100;
+        res as u64
+    }
+}
+
+impl Default for BitrateStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// A context to query the stats counters from the port.
 /// This context is reference counted. 
 pub struct StatsQueryContext {
@@ -737,3 +1640,621 @@ impl StatsQueryContext {
         Arc::strong_count(&self.counter) != 1
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::*;
+    use arrayvec::ArrayVec;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn eth_conf_with_rss_and_cksum() {
+        let mut rss_hf = offload::RssHashFunc::ALL_DISABLED;
+        rss_hf.enable_ipv4();
+        rss_hf.enable_nonfrag_ipv4_tcp();
+        let mut rx_offloads = offload::DevRxOffload::ALL_DISABLED;
+        rx_offloads.enable_ipv4_cksum();
+        rx_offloads.enable_tcp_cksum();
+        let mut tx_offloads = offload::DevTxOffload::ALL_DISABLED;
+        tx_offloads.enable_ipv4_cksum();
+        tx_offloads.enable_udp_cksum();
+        let key = offload::DEFAULT_RSS_KEY_40B;
+
+        let eth_conf = EthConf::new()
+            .rx_mq_rss(rss_hf)
+            .rss_hash_key(&key[..])
+            .rx_offloads(rx_offloads)
+            .tx_offloads(tx_offloads)
+            .max_rx_pkt_len(9018)
+            .build();
+
+        assert_eq!(
+            eth_conf.rxmode.mq_mode,
+            rpkt_dpdk_sys::rte_eth_rx_mq_mode_RTE_ETH_MQ_RX_RSS
+        );
+        assert_eq!(eth_conf.rxmode.mtu, 9000);
+        assert_eq!(eth_conf.rxmode.offloads, (1 << 1) | (1 << 3));
+        assert_eq!(
+            eth_conf.txmode.mq_mode,
+            rpkt_dpdk_sys::rte_eth_tx_mq_mode_RTE_ETH_MQ_TX_NONE
+        );
+        assert_eq!(eth_conf.txmode.offloads, (1 << 1) | (1 << 2));
+        assert_eq!(eth_conf.rx_adv_conf.rss_conf.rss_hf, (1 << 2) | (1 << 4));
+        assert_eq!(
+            eth_conf.rx_adv_conf.rss_conf.rss_key,
+            key.as_ptr() as *mut u8
+        );
+        assert_eq!(eth_conf.rx_adv_conf.rss_conf.rss_key_len, 40);
+
+        // without rss, the rss conf is left zeroed
+        let eth_conf = EthConf::new().build();
+        assert_eq!(
+            eth_conf.rxmode.mq_mode,
+            rpkt_dpdk_sys::rte_eth_rx_mq_mode_RTE_ETH_MQ_RX_NONE
+        );
+        assert_eq!(eth_conf.rxmode.mtu, 1500);
+        assert_eq!(eth_conf.rx_adv_conf.rss_conf.rss_hf, 0);
+        assert!(eth_conf.rx_adv_conf.rss_conf.rss_key.is_null());
+    }
+
+    // This test requires at least one usable port.
+    #[test]
+    fn discover_ports() {
+        DpdkOption::new().init().unwrap();
+
+        let port_num = service().port_num().unwrap();
+        assert_eq!(port_num >= 1, true);
+
+        let port_ids = service().port_ids().unwrap();
+        assert_eq!(port_ids.len(), port_num as usize);
+
+        for port_id in port_ids {
+            assert_eq!(service().port_name(port_id).unwrap().is_empty(), false);
+            let port_info = service().port_info(port_id).unwrap();
+            assert_eq!(port_info.port_id, port_id);
+            assert_eq!(port_info.driver_name.is_empty(), false);
+            assert_eq!(port_info.max_rx_queues() >= 1, true);
+            assert_eq!(port_info.max_tx_queues() >= 1, true);
+        }
+    }
+
+    #[test]
+    fn vdev_fw_and_bus_info() {
+        DpdkOption::new().init().unwrap();
+
+        let port_id = service().port_vdev_add("net_null_fw", "").unwrap();
+        let port_info = unsafe { PortInfo::try_get(port_id) }.unwrap();
+        assert_eq!(port_info.driver_name, "net_null");
+        assert_eq!(port_info.bus_name(), "vdev");
+        assert_eq!(port_info.bus_info(), None);
+        // the null pmd reports no firmware
+        let err = service().port_fw_version(port_id).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::FFIError(-libc::ENOTSUP));
+        service().port_vdev_remove("net_null_fw").unwrap();
+    }
+
+    // This test requires a usable port 0, e.g. a pci device that reports its
+    // firmware.
+    #[test]
+    fn port_fw_version() {
+        DpdkOption::new().init().unwrap();
+
+        let fw_version = service().port_fw_version(0).unwrap();
+        assert!(!fw_version.is_empty());
+        let port_info = service().port_info(0).unwrap();
+        assert!(!port_info.driver_name.is_empty());
+        assert!(!port_info.bus_name().is_empty());
+    }
+
+    // This test requires a usable port 0.
+    #[test]
+    fn get_flow_ctrl_conf() {
+        DpdkOption::new().init().unwrap();
+
+        match service().port_flow_ctrl(0) {
+            Ok(conf) => {
+                // write back the same configuration
+                let res = service().port_set_flow_ctrl(0, &conf);
+                if let Err(err) = res {
+                    assert_eq!(err.kind(), error::ErrorKind::FFIError(-95));
+                }
+            }
+            // flow control is not supported by the PMD
+            Err(err) => assert_eq!(err.kind(), error::ErrorKind::FFIError(-95)),
+        }
+    }
+
+    // This test requires port 0 to support the vlan strip offload.
+    #[test]
+    fn enable_vlan_strip() {
+        DpdkOption::new().init().unwrap();
+
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        utils::init_port(0, 1, 1, 512, "wtf", 512, 0).unwrap();
+
+        let mut vlan_offload = service().port_vlan_offload(0).unwrap();
+        vlan_offload.enable_strip();
+        service().port_set_vlan_offload(0, vlan_offload).unwrap();
+        assert_eq!(service().port_vlan_offload(0).unwrap().strip(), true);
+
+        service()
+            .port_set_vlan_offload(0, offload::VlanOffload::ALL_DISABLED)
+            .unwrap();
+        assert_eq!(service().port_vlan_offload(0).unwrap().strip(), false);
+
+        service().port_close(0).unwrap();
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn bitrate_from_stats_snapshots() {
+        let start = Instant::now();
+        let mut stats = PortStats::default();
+        let mut bitrate = BitrateStats::new();
+
+        stats.0.ibytes = 1000;
+        bitrate.update(&stats, start);
+        assert_eq!(bitrate.mean_bps(), (0, 0));
+
+        // 1 Gbps rx and 100 Mbps tx
+        stats.0.ibytes += 125_000_000;
+        stats.0.obytes += 12_500_000;
+        bitrate.update(&stats, start + Duration::from_secs(1));
+        assert_eq!(bitrate.mean_bps(), (1_000_000_000, 100_000_000));
+        assert_eq!(bitrate.peak_bps(), (1_000_000_000, 100_000_000));
+        assert_eq!(bitrate.ewma_bps(), (200_000_000, 20_000_000));
+
+        // the rate is halved over a 2-second interval
+        stats.0.ibytes += 125_000_000;
+        bitrate.update(&stats, start + Duration::from_secs(3));
+        assert_eq!(bitrate.mean_bps(), (500_000_000, 0));
+        assert_eq!(bitrate.peak_bps(), (1_000_000_000, 100_000_000));
+        assert_eq!(bitrate.ewma_bps(), (260_000_000, 16_000_000));
+
+        // a snapshot at the same time is ignored
+        bitrate.update(&stats, start + Duration::from_secs(3));
+        assert_eq!(bitrate.mean_bps(), (500_000_000, 0));
+    }
+
+    // This test requires a usable port 0, e.g. a `net_null` virtual device.
+    #[test]
+    fn adjust_out_of_range_nb_desc() {
+        DpdkOption::new().init().unwrap();
+
+        let port_info = service().port_info(0).unwrap();
+        let within_lim = |nb_desc: u16, lim: DescLim| {
+            nb_desc >= lim.nb_min()
+                && nb_desc <= lim.nb_max()
+                && nb_desc % std::cmp::max(lim.nb_align(), 1) == 0
+        };
+
+        for (rx, tx) in [(u16::MAX, u16::MAX), (1, 1), (0, 0)] {
+            let (nb_rx_desc, nb_tx_desc) = service().port_adjust_nb_desc(0, rx, tx).unwrap();
+            assert!(within_lim(nb_rx_desc, port_info.rx_desc_lim()));
+            assert!(within_lim(nb_tx_desc, port_info.tx_desc_lim()));
+        }
+        assert!(service().port_adjust_nb_desc(u16::MAX, 512, 512).is_err());
+
+        // the out-of-range numbers are adjusted by the queue setup
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        utils::init_port(0, 1, 1, 1, "wtf", 1, 0).unwrap();
+
+        service().port_close(0).unwrap();
+        service().mempool_free("wtf").unwrap();
+    }
+
+    // This test requires a usable port 0, e.g. a `net_null` virtual device.
+    #[test]
+    fn tx_buffer_flush() {
+        DpdkOption::new().init().unwrap();
+
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        utils::init_port(0, 1, 1, 512, "wtf", 512, 0).unwrap();
+
+        {
+            let mp = service().mempool("wtf").unwrap();
+            let txq = service().tx_queue(0, 0).unwrap();
+            let mut stats_query = service().stats_query(0).unwrap();
+            let opackets = stats_query.query().opackets();
+
+            let mut txbuf = TxBuffer::<32>::new(txq);
+            for _ in 0..16 {
+                let mut mbuf = mp.try_alloc().unwrap();
+                mbuf.extend_from_slice(&[0xff; 64][..]);
+                assert_eq!(txbuf.add(mbuf), 0);
+            }
+            assert_eq!(txbuf.len(), 16);
+            assert_eq!(stats_query.query().opackets(), opackets);
+
+            assert_eq!(txbuf.flush(), 16);
+            assert_eq!(txbuf.is_empty(), true);
+            assert_eq!(stats_query.query().opackets(), opackets + 16);
+
+            // the buffer is flushed as soon as it becomes full
+            for i in 0..32 {
+                let mut mbuf = mp.try_alloc().unwrap();
+                mbuf.extend_from_slice(&[0xff; 64][..]);
+                let nb_tx = txbuf.add(mbuf);
+                if i == 31 {
+                    assert_eq!(nb_tx, 32);
+                } else {
+                    assert_eq!(nb_tx, 0);
+                }
+            }
+            assert_eq!(stats_query.query().opackets(), opackets + 48);
+
+            // the buffered mbufs are sent when the buffer is dropped
+            for _ in 0..8 {
+                let mut mbuf = mp.try_alloc().unwrap();
+                mbuf.extend_from_slice(&[0xff; 64][..]);
+                txbuf.add(mbuf);
+            }
+            drop(txbuf);
+            assert_eq!(stats_query.query().opackets(), opackets + 56);
+        }
+
+        service().port_close(0).unwrap();
+        service().mempool_free("wtf").unwrap();
+    }
+
+    // This test requires a usable port 0, e.g. a `net_null` virtual device.
+    #[test]
+    fn rx_callback_counts_and_filters() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::sync::Arc;
+
+        DpdkOption::new().init().unwrap();
+
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        utils::init_port(0, 1, 1, 512, "wtf", 512, 0).unwrap();
+
+        {
+            let mut rxq = service().rx_queue(0, 0).unwrap();
+            let counted = Arc::new(AtomicU64::new(0));
+            let cb = rxq
+                .add_callback({
+                    let counted = counted.clone();
+                    move |mbufs| {
+                        counted.fetch_add(mbufs.len() as u64, Ordering::Relaxed);
+                        // keep the first half of the burst
+                        mbufs.len() / 2
+                    }
+                })
+                .unwrap();
+
+            let mut nb_rx = 0;
+            for _ in 0..10 {
+                let mut batch = ArrayVec::<Mbuf, 32>::new();
+                nb_rx += rxq.rx(&mut batch);
+                assert!(batch.len() <= 16);
+            }
+            let nb_counted = counted.load(Ordering::Relaxed);
+            assert!(nb_counted > 0);
+            assert_eq!(nb_rx as u64, nb_counted / 2);
+
+            // the callback is no longer invoked, and its closure is released
+            drop(cb);
+            let mut batch = ArrayVec::<Mbuf, 32>::new();
+            rxq.rx(&mut batch);
+            assert_eq!(counted.load(Ordering::Relaxed), nb_counted);
+            assert!(rxq.callbacks.is_empty());
+            assert_eq!(Arc::strong_count(&counted), 1);
+        }
+
+        service().port_close(0).unwrap();
+        service().mempool_free("wtf").unwrap();
+    }
+
+    // This test requires a usable port 0, e.g. a `net_null` virtual device.
+    #[test]
+    fn flood_shares_one_mbuf() {
+        DpdkOption::new().init().unwrap();
+
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        utils::init_port(0, 1, 3, 512, "wtf", 512, 0).unwrap();
+
+        {
+            let mp = service().mempool("wtf").unwrap();
+            let mut txqs: Vec<_> = (0..3)
+                .map(|qid| service().tx_queue(0, qid).unwrap())
+                .collect();
+            let mut stats_query = service().stats_query(0).unwrap();
+            let stats = stats_query.query();
+            let nb_mbufs = mp.nb_mbufs();
+
+            let mut mbuf = mp.try_alloc().unwrap();
+            mbuf.extend_from_slice(&[0xff; 64][..]);
+            assert_eq!(mp.nb_mbufs(), nb_mbufs - 1);
+
+            let mut txq_refs: Vec<_> = txqs.iter_mut().collect();
+            assert_eq!(TxQueue::flood(&mut txq_refs[..], mbuf), 3);
+            let new_stats = stats_query.query();
+            for qid in 0..3 {
+                assert_eq!(new_stats.q_opackets(qid), stats.q_opackets(qid) + 1);
+            }
+            // the mbuf is freed once by each queue
+            assert_eq!(mp.nb_mbufs(), nb_mbufs);
+
+            let mbuf = mp.try_alloc().unwrap();
+            assert_eq!(TxQueue::flood(&mut [], mbuf), 0);
+            assert_eq!(mp.nb_mbufs(), nb_mbufs);
+        }
+
+        service().port_close(0).unwrap();
+        service().mempool_free("wtf").unwrap();
+    }
+
+    // This test requires a usable port 0, e.g. a `net_null` virtual device.
+    #[cfg(feature = "multiseg")]
+    #[test]
+    fn flood_shares_chained_mbuf() {
+        DpdkOption::new().init().unwrap();
+
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        utils::init_port(0, 1, 3, 512, "wtf", 512, 0).unwrap();
+
+        {
+            let mp = service().mempool("wtf").unwrap();
+            let mut txqs: Vec<_> = (0..3)
+                .map(|qid| service().tx_queue(0, qid).unwrap())
+                .collect();
+            let nb_mbufs = mp.nb_mbufs();
+
+            let mut mbuf = mp.try_alloc().unwrap();
+            mbuf.extend_from_slice(&[0xff; 64][..]);
+            for _ in 0..2 {
+                let mut seg = mp.try_alloc().unwrap();
+                seg.extend_from_slice(&[0xee; 64][..]);
+                mbuf.appender().append_seg(seg);
+            }
+            assert_eq!(mbuf.num_segs(), 3);
+            assert_eq!(mp.nb_mbufs(), nb_mbufs - 3);
+
+            let mut txq_refs: Vec<_> = txqs.iter_mut().collect();
+            assert_eq!(TxQueue::flood(&mut txq_refs[..], mbuf), 3);
+            // each segment is freed once by each queue, and is only returned
+            // to the mempool after the last one
+            assert_eq!(mp.nb_mbufs(), nb_mbufs);
+        }
+
+        service().port_close(0).unwrap();
+        service().mempool_free("wtf").unwrap();
+    }
+
+    // This test requires a usable port 0, e.g. a `net_null` virtual device.
+    #[test]
+    fn queue_stats_mapping() {
+        DpdkOption::new().init().unwrap();
+
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        utils::init_port(0, 1, 1, 512, "wtf", 512, 0).unwrap();
+
+        {
+            // the virtual devices map the queues to the counters of the same index
+            for res in [
+                service().port_set_rx_queue_stats_mapping(0, 0, 0),
+                service().port_set_tx_queue_stats_mapping(0, 0, 0),
+            ] {
+                if let Err(err) = res {
+                    assert_eq!(err.kind(), ErrorKind::FFIError(-libc::ENOTSUP));
+                }
+            }
+            let stat_idx = u8::try_from(PortStats::QUEUE_STAT_CNTRS).unwrap();
+            assert!(service()
+                .port_set_tx_queue_stats_mapping(0, 0, stat_idx)
+                .is_err());
+            assert!(service().port_set_tx_queue_stats_mapping(1, 0, 0).is_err());
+
+            let mp = service().mempool("wtf").unwrap();
+            let mut txq = service().tx_queue(0, 0).unwrap();
+            let mut stats_query = service().stats_query(0).unwrap();
+            let q_opackets = stats_query.query().q_opackets(0);
+
+            let mut batch = ArrayVec::<_, 32>::new();
+            mp.fill_batch(&mut batch);
+            for mbuf in batch.iter_mut() {
+                mbuf.extend_from_slice(&[0xff; 64][..]);
+            }
+            let nb_tx = batch.len() as u64;
+            while !batch.is_empty() {
+                txq.tx(&mut batch);
+            }
+            assert_eq!(stats_query.query().q_opackets(0), q_opackets + nb_tx);
+        }
+
+        service().port_close(0).unwrap();
+        service().mempool_free("wtf").unwrap();
+    }
+
+    // This test requires a port 0 that supports the per-queue rate limits, e.g.
+    // an ixgbe port, with the link up.
+    #[test]
+    fn queue_rate_limit() {
+        DpdkOption::new().init().unwrap();
+
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        utils::init_port(0, 1, 1, 512, "wtf", 512, 0).unwrap();
+
+        service().port_set_queue_rate_limit(0, 0, 100).unwrap();
+        // the rate is capped by the link speed
+        assert_eq!(
+            service()
+                .port_set_queue_rate_limit(0, 0, u32::MAX)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::FFIError(-libc::EINVAL)
+        );
+        service().port_set_queue_rate_limit(0, 0, 0).unwrap();
+        assert!(service().port_set_queue_rate_limit(1, 0, 100).is_err());
+
+        service().port_close(0).unwrap();
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn queue_rate_limit_not_supported() {
+        DpdkOption::new().init().unwrap();
+
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        let port_id = service().port_vdev_add("net_null_rate", "").unwrap();
+        utils::init_port(port_id, 1, 1, 512, "wtf", 512, 0).unwrap();
+
+        let err = service()
+            .port_set_queue_rate_limit(port_id, 0, 100)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::FFIError(-libc::ENOTSUP));
+
+        service().port_close(port_id).unwrap();
+        service().port_vdev_remove("net_null_rate").unwrap();
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn owned_port_close() {
+        DpdkOption::new().init().unwrap();
+
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        for close in [true, false] {
+            let port_id = service().port_vdev_add("net_null_owned", "").unwrap();
+            utils::init_port(port_id, 1, 1, 512, "wtf", 512, 0).unwrap();
+            let mut port = OwnedPort::new(port_id);
+            port.set_vdev("net_null_owned");
+            assert_eq!(port.port_id(), port_id);
+            if close {
+                port.close().unwrap();
+            } else {
+                drop(port);
+            }
+            // the vdev is removed, so it can be added again
+            assert!(service().port_vdev_add("net_null_owned", "").is_ok());
+            service().port_vdev_remove("net_null_owned").unwrap();
+        }
+
+        // the port is left open if its queues are in use
+        let port_id = service().port_vdev_add("net_null_owned", "").unwrap();
+        utils::init_port(port_id, 1, 1, 512, "wtf", 512, 0).unwrap();
+        let mut port = OwnedPort::new(port_id);
+        port.set_vdev("net_null_owned");
+        let rxq = service().rx_queue(port_id, 0).unwrap();
+        assert!(port.close().is_err());
+        drop(rxq);
+        service().port_close(port_id).unwrap();
+        service().port_vdev_remove("net_null_owned").unwrap();
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    // This test requires the net_tap pmd and the permission to create tap
+    // interfaces.
+    #[test]
+    fn tap_vdev_add_remove() {
+        DpdkOption::new().init().unwrap();
+
+        let port_id = service()
+            .port_vdev_add("net_tap_wtf", "iface=dtap_wtf")
+            .unwrap();
+        assert!(service().port_ids().unwrap().contains(&port_id));
+        assert_eq!(service().port_name(port_id).unwrap(), "net_tap_wtf");
+        assert!(service().port_vdev_add("net_tap_wtf", "").is_err());
+
+        service().port_vdev_remove("net_tap_wtf").unwrap();
+        assert!(!service().port_ids().unwrap().contains(&port_id));
+        assert!(service().port_vdev_remove("net_tap_wtf").is_err());
+    }
+
+    // This test requires port 0 to support rx interrupts, e.g. a nic bound to
+    // vfio-pci.
+    #[test]
+    fn rx_intr_fd() {
+        DpdkOption::new().init().unwrap();
+
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        let mut port_conf = PortConf::from_port_info(&service().port_info(0).unwrap()).unwrap();
+        port_conf.set_enable_rx_intr(true);
+        let mut rxq_conf = RxQueueConf::new();
+        rxq_conf.set_mp_name("wtf");
+        service()
+            .port_configure(0, &port_conf, &vec![rxq_conf], &vec![TxQueueConf::new()])
+            .unwrap();
+
+        {
+            let rxq = service().rx_queue(0, 0).unwrap();
+            let fd = rxq.intr_fd().unwrap();
+            assert!(fd >= 0);
+            assert_eq!(rxq.intr_fd().unwrap(), fd);
+            rxq.enable_intr().unwrap();
+            rxq.disable_intr().unwrap();
+        }
+
+        service().port_close(0).unwrap();
+        service().mempool_free("wtf").unwrap();
+    }
+
+    // This test requires port 0 to support `rte_eth_tx_done_cleanup`.
+    #[test]
+    fn tx_done_cleanup() {
+        DpdkOption::new().init().unwrap();
+
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        utils::init_port(0, 1, 1, 512, "wtf", 512, 0).unwrap();
+
+        {
+            let mp = service().mempool("wtf").unwrap();
+            let mut txq = service().tx_queue(0, 0).unwrap();
+
+            let mut batch = ArrayVec::<_, 32>::new();
+            mp.fill_batch(&mut batch);
+            for mbuf in batch.iter_mut() {
+                mbuf.extend_from_slice(&[0xff; 64][..]);
+            }
+            let nb_tx = txq.tx(&mut batch);
+            assert!(nb_tx > 0);
+            drop(batch);
+
+            // give the nic some time to complete the transmission
+            std::thread::sleep(Duration::from_millis(10));
+            let freed = txq.done_cleanup(0).unwrap();
+            assert!(freed > 0);
+            assert!(freed as usize <= nb_tx);
+        }
+
+        service().port_close(0).unwrap();
+        service().mempool_free("wtf").unwrap();
+    }
+
+    // This test requires port 0 to support rss with 4 rx queues.
+    #[test]
+    fn query_rss_reta() {
+        DpdkOption::new().init().unwrap();
+
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        utils::init_port(0, 4, 4, 512, "wtf", 512, 0).unwrap();
+
+        let reta_size = service().port_info(0).unwrap().reta_size();
+        let reta = service().port_rss_reta_query(0).unwrap();
+        assert_eq!(reta.len(), usize::from(reta_size));
+        assert!(reta.iter().all(|qid| *qid < 4));
+        assert!(service().port_rss_reta_query(1).is_err());
+
+        service().port_close(0).unwrap();
+        service().mempool_free("wtf").unwrap();
+    }
+
+    // This test requires a usable port 0, e.g. a `net_null` virtual device.
+    #[test]
+    fn wait_link_up() {
+        DpdkOption::new().init().unwrap();
+
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        utils::init_port(0, 1, 1, 512, "wtf", 512, 0).unwrap();
+
+        let policy = RetryPolicy::new(90, Backoff::Sleep(Duration::from_millis(100)));
+        assert!(service().port_wait_link_up(0, &policy).unwrap());
+        assert!(service().port_link_up(0).unwrap());
+        assert!(service().port_wait_link_up(1, &policy).is_err());
+
+        service().port_close(0).unwrap();
+        service().mempool_free("wtf").unwrap();
+    }
+}
@@ -0,0 +1,221 @@
+//! The protocol constants and the checksum helpers of the modules that read or
+//! write the packet headers in place, without going through `rpkt`.
+
+use std::borrow::Cow;
+use std::net::Ipv4Addr;
+
+use rpkt::ipv4::IpProtocol;
+
+use crate::Mbuf;
+
+pub(crate) const ETHER_HEADER_LEN: usize = 14;
+pub(crate) const ETHERTYPE_IPV4: u16 = 0x0800;
+pub(crate) const ETHERTYPE_IPV6: u16 = 0x86dd;
+
+// the ipv4 header without the options
+pub(crate) const IPV4_HEADER_LEN: usize = 20;
+pub(crate) const IPV6_HEADER_LEN: usize = 40;
+// the tcp header without the options
+pub(crate) const TCP_HEADER_LEN: usize = 20;
+pub(crate) const UDP_HEADER_LEN: usize = 8;
+
+// the more fragments flag and the fragment offset of the flags and fragment
+// offset field, a packet with either one set is a fragment
+pub(crate) const IPV4_MF: u16 = 0x2000;
+pub(crate) const IPV4_FRAG_OFFSET_MASK: u16 = 0x1fff;
+
+pub(crate) const IPPROTO_ICMP: u8 = 1;
+pub(crate) const IPPROTO_TCP: u8 = 6;
+pub(crate) const IPPROTO_UDP: u8 = 17;
+pub(crate) const IPPROTO_SCTP: u8 = 132;
+
+// The ethertype of an ethernet frame, or `None` if the frame is truncated.
+pub(crate) fn ethertype(frame: &[u8]) -> Option<u16> {
+    frame
+        .get(12..ETHER_HEADER_LEN)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+// Whether the ipv4 header `ip_header` is the header of a fragment.
+pub(crate) fn ipv4_is_fragment(ip_header: &[u8]) -> bool {
+    u16::from_be_bytes([ip_header[6], ip_header[7]]) & (IPV4_MF | IPV4_FRAG_OFFSET_MASK) != 0
+}
+
+// Accumulate the RFC 1071 checksum of `data`.
+pub(crate) fn checksum(data: &[u8], mut accum: u32) -> u32 {
+    let mut chunks = data.chunks_exact(2);
+    for word in &mut chunks {
+        accum += u32::from(u16::from_be_bytes([word[0], word[1]]));
+    }
+    if let [byte] = chunks.remainder() {
+        accum += u32::from(*byte) << 8;
+    }
+    accum
+}
+
+pub(crate) fn fold(mut accum: u32) -> u16 {
+    while accum > 0xffff {
+        accum = (accum >> 16) + (accum & 0xffff);
+    }
+    accum as u16
+}
+
+// Accumulate the checksum of the pseudo header of an l4 packet of `l4_len`
+// bytes, whose ipv4 header is `ip_header`.
+pub(crate) fn pseudo_header_checksum(ip_header: &[u8], protocol: u8, l4_len: usize) -> u32 {
+    let mut pseudo_header = [0; 12];
+    pseudo_header[0..8].copy_from_slice(&ip_header[12..20]);
+    pseudo_header[9] = protocol;
+    pseudo_header[10..12].copy_from_slice(&(l4_len as u16).to_be_bytes());
+    checksum(&pseudo_header[..], 0)
+}
+
+// Write the ethernet header to the front of `frame`.
+pub(crate) fn write_ether_header(frame: &mut [u8], dst: [u8; 6], src: [u8; 6], ethertype: u16) {
+    frame[0..6].copy_from_slice(&dst);
+    frame[6..12].copy_from_slice(&src);
+    frame[12..ETHER_HEADER_LEN].copy_from_slice(&ethertype.to_be_bytes());
+}
+
+// Write an ipv4 header without options to the front of the zeroed
+// `ip_packet`, which spans the whole ipv4 packet. The don't fragment flag is
+// set and the header checksum is left to `Mbuf::finalize_checksums`.
+pub(crate) fn write_ipv4_header(
+    ip_packet: &mut [u8],
+    ident: u16,
+    protocol: IpProtocol,
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+) {
+    let total_len = ip_packet.len() as u16;
+    ip_packet[0] = 0x45;
+    ip_packet[2..4].copy_from_slice(&total_len.to_be_bytes());
+    ip_packet[4..6].copy_from_slice(&ident.to_be_bytes());
+    ip_packet[6] = 0x40;
+    ip_packet[8] = 64;
+    ip_packet[9] = protocol.into();
+    ip_packet[12..16].copy_from_slice(&src.octets());
+    ip_packet[16..20].copy_from_slice(&dst.octets());
+}
+
+// The whole packet data of `mbuf`, which is copied if the packet spans more
+// than one segment.
+#[cfg(not(feature = "multiseg"))]
+pub(crate) fn packet_data(mbuf: &Mbuf) -> Cow<'_, [u8]> {
+    Cow::Borrowed(mbuf.data())
+}
+
+#[cfg(feature = "multiseg")]
+pub(crate) fn packet_data(mbuf: &Mbuf) -> Cow<'_, [u8]> {
+    if mbuf.num_segs() == 1 {
+        Cow::Borrowed(mbuf.data())
+    } else {
+        Cow::Owned(mbuf.seg_iter().flatten().copied().collect())
+    }
+}
+
+// Build an ethernet frame carrying an ipv4 tcp or udp packet, with valid
+// checksums and without the don't fragment flag, so that it can be split by
+// `fragment`. The tcp header has no options.
+#[cfg(test)]
+pub(crate) fn l4_frame(
+    protocol: IpProtocol,
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+    src_port: u16,
+    dst_port: u16,
+    ident: u16,
+    payload: &[u8],
+) -> Vec<u8> {
+    let (l4_header_len, cksum_offset) = if protocol == IpProtocol::TCP {
+        (TCP_HEADER_LEN, 16)
+    } else {
+        (UDP_HEADER_LEN, 6)
+    };
+    let l4_len = l4_header_len + payload.len();
+    let mut frame = vec![0; ETHER_HEADER_LEN + IPV4_HEADER_LEN + l4_len];
+    write_ether_header(
+        &mut frame[..],
+        [0x02, 0, 0, 0, 0, 0x02],
+        [0x02, 0, 0, 0, 0, 0x01],
+        ETHERTYPE_IPV4,
+    );
+
+    let ip_packet = &mut frame[ETHER_HEADER_LEN..];
+    write_ipv4_header(ip_packet, ident, protocol, src, dst);
+    ip_packet[6] = 0;
+    let cksum = !fold(checksum(&ip_packet[..IPV4_HEADER_LEN], 0));
+    ip_packet[10..12].copy_from_slice(&cksum.to_be_bytes());
+
+    let (ip_header, l4) = ip_packet.split_at_mut(IPV4_HEADER_LEN);
+    l4[0..2].copy_from_slice(&src_port.to_be_bytes());
+    l4[2..4].copy_from_slice(&dst_port.to_be_bytes());
+    if protocol == IpProtocol::TCP {
+        l4[12] = (TCP_HEADER_LEN as u8 / 4) << 4;
+    } else {
+        l4[4..6].copy_from_slice(&(l4_len as u16).to_be_bytes());
+    }
+    l4[l4_header_len..].copy_from_slice(payload);
+    let accum = pseudo_header_checksum(ip_header, protocol.into(), l4_len);
+    let cksum = match !fold(checksum(l4, accum)) {
+        // a zero udp checksum means no checksum
+        0 if protocol == IpProtocol::UDP => 0xffff,
+        cksum => cksum,
+    };
+    l4[cksum_offset..cksum_offset + 2].copy_from_slice(&cksum.to_be_bytes());
+    frame
+}
+
+// Split the ip payload of the unfragmented `frame` at `splits` into fragments.
+#[cfg(test)]
+pub(crate) fn fragment(frame: &[u8], splits: &[usize]) -> Vec<Vec<u8>> {
+    let l4 = ETHER_HEADER_LEN + IPV4_HEADER_LEN;
+    let payload = &frame[l4..];
+    let mut bounds = vec![0];
+    bounds.extend_from_slice(splits);
+    bounds.push(payload.len());
+    bounds
+        .windows(2)
+        .map(|bound| {
+            let mut fragment = frame[..l4].to_vec();
+            fragment.extend_from_slice(&payload[bound[0]..bound[1]]);
+            let ip = &mut fragment[ETHER_HEADER_LEN..];
+            let ip_len = (IPV4_HEADER_LEN + bound[1] - bound[0]) as u16;
+            ip[2..4].copy_from_slice(&ip_len.to_be_bytes());
+            let mut flags = (bound[0] / 8) as u16;
+            if bound[1] != payload.len() {
+                flags |= IPV4_MF;
+            }
+            ip[6..8].copy_from_slice(&flags.to_be_bytes());
+            fragment
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc1071_checksum() {
+        // the example of section 3 of RFC 1071
+        let data = [0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7];
+        assert_eq!(fold(checksum(&data[..], 0)), 0xddf2);
+
+        // an odd trailing byte is padded with a zero byte, and the carries are
+        // added back
+        assert_eq!(fold(checksum(&data[..7], 0)), 0xdcfb);
+        assert_eq!(fold(checksum(&[0xf2], 0xffff)), 0xf200);
+
+        // a valid header sums to 0xffff with the checksum in place
+        let mut header = [
+            0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+        ];
+        let cksum = !fold(checksum(&header[..], 0));
+        assert_eq!(cksum, 0xb861);
+        header[10..12].copy_from_slice(&cksum.to_be_bytes());
+        assert_eq!(fold(checksum(&header[..], 0)), 0xffff);
+        assert!(!ipv4_is_fragment(&header[..]));
+    }
+}
@@ -0,0 +1,245 @@
+use rpkt::flow::FIVE_TUPLE_KEY_LEN;
+use rpkt_dpdk_sys as ffi;
+
+use crate::error::*;
+use crate::rss::{parse_tuple, FiveTuple};
+use crate::{Hash, HashConf};
+
+#[derive(Clone, Copy, Debug)]
+pub struct FlowRateConf {
+    /// The default rate of a flow in bytes per second.
+    pub rate: u64,
+    /// The default bucket size of a flow in bytes, i.e. the largest burst
+    /// that passes at once.
+    pub burst: u64,
+}
+
+impl FlowRateConf {
+    /// 1 Gbps.
+    pub const RATE: u64 = 125_000_000;
+    /// 64 full-sized ethernet frames.
+    pub const BURST: u64 = 64 * 1518;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_rate(&mut self, val: u64) {
+        self.rate = val;
+    }
+
+    pub fn set_burst(&mut self, val: u64) {
+        self.burst = val;
+    }
+}
+
+impl Default for FlowRateConf {
+    fn default() -> Self {
+        Self {
+            rate: Self::RATE,
+            burst: Self::BURST,
+        }
+    }
+}
+
+// The token bucket of a flow, the tokens are bytes.
+struct Bucket {
+    tokens_per_cycle: f64,
+    tokens: f64,
+    burst: f64,
+    last: u64,
+}
+
+impl Bucket {
+    fn new(rate: u64, burst: u64, tsc_hz: u64, now: u64) -> Self {
+        Self {
+            tokens_per_cycle: rate as f64 / tsc_hz as f64,
+            tokens: burst as f64,
+            burst: burst as f64,
+            last: now,
+        }
+    }
+
+    #[inline]
+    fn allow(&mut self, pkt_len: usize, now: u64) -> bool {
+        if now > self.last {
+            self.tokens =
+                (self.tokens + (now - self.last) as f64 * self.tokens_per_cycle).min(self.burst);
+            self.last = now;
+        }
+
+        let len = pkt_len as f64;
+        if self.tokens < len {
+            return false;
+        }
+        self.tokens -= len;
+        true
+    }
+}
+
+/// A policer that limits the rate of each flow with a token bucket.
+///
+/// The flows are identified by the 5-tuple of `rss::FiveTuple`, and their
+/// buckets are stored in a `Hash`. A flow is added with the default rate of
+/// `FlowRateConf` on its first packet, unless its rate is set in advance with
+/// `set_flow_rate`. The buckets are refilled with the elapsed tsc cycles, so
+/// the data path makes no clock syscall.
+///
+/// The non-ip frames are not policed. The packets of a new flow are not
+/// policed either if the table is full, which is counted by `nb_untracked`.
+pub struct FlowRateLimiter {
+    hash: Hash<[u8; FIVE_TUPLE_KEY_LEN], Bucket>,
+    conf: FlowRateConf,
+    tsc_hz: u64,
+    nb_untracked: u64,
+}
+
+impl FlowRateLimiter {
+    /// Create a new limiter named `name`, which tracks up to
+    /// `hash_conf.entries` flows.
+    pub fn try_create(name: String, hash_conf: &HashConf, conf: &FlowRateConf) -> Result<Self> {
+        Ok(Self {
+            hash: Hash::try_create(name, hash_conf)?,
+            conf: *conf,
+            tsc_hz: unsafe { ffi::rte_get_tsc_hz() },
+            nb_untracked: 0,
+        })
+    }
+
+    /// Check whether the packet of `pkt_len` bytes, whose ethernet frame
+    /// starts with `frame`, conforms to the rate of its flow.
+    ///
+    /// A conforming packet consumes `pkt_len` tokens of the flow, while an
+    /// excess packet should be dropped.
+    #[inline]
+    pub fn allow(&mut self, frame: &[u8], pkt_len: usize) -> bool {
+        self.allow_at(frame, pkt_len, unsafe { ffi::rte_rdtsc_() })
+    }
+
+    /// Set the rate of the flow `tuple` to `rate` bytes per second with a
+    /// bucket of `burst` bytes, which starts full.
+    ///
+    /// This function fails if the table is full.
+    pub fn set_flow_rate(&mut self, tuple: &FiveTuple, rate: u64, burst: u64) -> Result<()> {
+        let bucket = Bucket::new(rate, burst, self.tsc_hz, unsafe { ffi::rte_rdtsc_() });
+        self.hash.insert(&tuple.as_key_bytes(), bucket)?;
+        Ok(())
+    }
+
+    /// Forget the flow `tuple`, return whether it is tracked.
+    pub fn remove_flow(&mut self, tuple: &FiveTuple) -> bool {
+        self.hash.remove(&tuple.as_key_bytes()).is_some()
+    }
+
+    /// Return the number of the packets that are not policed, as their flows
+    /// do not fit in the table.
+    pub fn nb_untracked(&self) -> u64 {
+        self.nb_untracked
+    }
+
+    /// Return the number of the tracked flows.
+    pub fn len(&self) -> usize {
+        self.hash.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hash.is_empty()
+    }
+
+    // `now` is replaced by a mock tsc counter in the tests.
+    fn allow_at(&mut self, frame: &[u8], pkt_len: usize, now: u64) -> bool {
+        let tuple = match parse_tuple(frame) {
+            Some(tuple) => tuple,
+            None => return true,
+        };
+        let key = tuple.as_key_bytes();
+        if let Some(bucket) = self.hash.get_mut(&key) {
+            return bucket.allow(pkt_len, now);
+        }
+
+        let mut bucket = Bucket::new(self.conf.rate, self.conf.burst, self.tsc_hz, now);
+        let allowed = bucket.allow(pkt_len, now);
+        if self.hash.insert(&key, bucket).is_err() {
+            self.nb_untracked += 1;
+            return true;
+        }
+        allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use rpkt::ipv4::IpProtocol;
+
+    use super::*;
+    use crate::proto::l4_frame;
+    use crate::*;
+
+    #[test]
+    fn police_flows_over_their_rate() {
+        DpdkOption::new().init().unwrap();
+
+        let mut hash_conf = HashConf::new();
+        hash_conf.set_entries(16);
+        // 1000 bytes per second with a 10-packet burst
+        let mut conf = FlowRateConf::new();
+        conf.set_rate(1000);
+        conf.set_burst(1000);
+        let mut limiter =
+            FlowRateLimiter::try_create("flow_rate".to_string(), &hash_conf, &conf).unwrap();
+        let tsc_hz = limiter.tsc_hz;
+
+        // an ipv4 udp frame from 10.0.0.`host` with a 46-byte ip packet
+        let udp_frame = |host| {
+            let (src, dst) = (Ipv4Addr::new(10, 0, 0, host), Ipv4Addr::new(10, 0, 0, 100));
+            l4_frame(IpProtocol::UDP, src, dst, 1024, 2048, 0, &[0; 18])
+        };
+
+        // the heavy flow sends 100 packets of 100 bytes in 1 second, only the
+        // burst and the refill of 1 second pass
+        let heavy = udp_frame(1);
+        let mut nb_allowed = 0;
+        for i in 0..100 {
+            if limiter.allow_at(&heavy[..], 100, i * tsc_hz / 100) {
+                nb_allowed += 1;
+            }
+        }
+        assert!((19..=20).contains(&nb_allowed));
+
+        // the light flow sends 5 packets of 100 bytes per second, all pass
+        let light = udp_frame(2);
+        for i in 0..20 {
+            assert!(limiter.allow_at(&light[..], 100, i * tsc_hz / 5));
+        }
+        assert_eq!(limiter.len(), 2);
+
+        // a flow with a configured rate of 100 bytes per second, whose bucket
+        // holds a single packet
+        let tuple = FiveTuple {
+            src_ip: Ipv4Addr::new(10, 0, 0, 3).into(),
+            dst_ip: Ipv4Addr::new(10, 0, 0, 100).into(),
+            src_port: 1024,
+            dst_port: 2048,
+            protocol: IpProtocol::UDP,
+        };
+        limiter.set_flow_rate(&tuple, 100, 100).unwrap();
+        let capped = udp_frame(3);
+        let now = limiter.hash.get(&tuple.as_key_bytes()).unwrap().last;
+        assert!(limiter.allow_at(&capped[..], 100, now));
+        assert!(!limiter.allow_at(&capped[..], 100, now + tsc_hz / 2));
+        // the bucket is full again after 2 seconds
+        assert!(limiter.allow_at(&capped[..], 100, now + 2 * tsc_hz));
+        assert_eq!(limiter.len(), 3);
+
+        // the non-ip frames are not policed
+        for _ in 0..100 {
+            assert!(limiter.allow_at(&[0xff; 60][..], 1000, 0));
+        }
+        assert!(limiter.remove_flow(&tuple));
+        assert!(!limiter.remove_flow(&tuple));
+        assert_eq!(limiter.len(), 2);
+        assert_eq!(limiter.nb_untracked(), 0);
+    }
+}
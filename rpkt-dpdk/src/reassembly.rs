@@ -0,0 +1,493 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::proto::{
+    checksum, ethertype, fold, ETHERTYPE_IPV4, ETHER_HEADER_LEN, IPV4_FRAG_OFFSET_MASK,
+    IPV4_HEADER_LEN, IPV4_MF,
+};
+use crate::Mbuf;
+
+const IPV4_MAX_PACKET_LEN: usize = 65535;
+
+#[derive(Clone, Debug)]
+pub struct ReassemblyConf {
+    /// The time from the first fragment of a datagram after which the datagram
+    /// is dropped if it is still incomplete.
+    pub timeout: Duration,
+    /// The maximum number of the incomplete datagrams, the oldest one is
+    /// dropped to make room for a new one.
+    pub max_datagrams: u32,
+    /// The maximum number of the fragments of a datagram, a datagram with
+    /// more fragments is dropped.
+    pub max_fragments: u16,
+}
+
+impl ReassemblyConf {
+    pub const TIMEOUT: Duration = Duration::from_secs(2);
+    pub const MAX_DATAGRAMS: u32 = 1024;
+    pub const MAX_FRAGMENTS: u16 = 16;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_timeout(&mut self, val: Duration) {
+        self.timeout = val;
+    }
+
+    pub fn set_max_datagrams(&mut self, val: u32) {
+        self.max_datagrams = val;
+    }
+
+    pub fn set_max_fragments(&mut self, val: u16) {
+        self.max_fragments = val;
+    }
+}
+
+impl Default for ReassemblyConf {
+    fn default() -> Self {
+        Self {
+            timeout: Self::TIMEOUT,
+            max_datagrams: Self::MAX_DATAGRAMS,
+            max_fragments: Self::MAX_FRAGMENTS,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct DatagramKey {
+    src: [u8; 4],
+    dst: [u8; 4],
+    protocol: u8,
+    ident: u16,
+}
+
+struct Fragment {
+    // the offset and the length in the ip payload of the datagram
+    offset: usize,
+    len: usize,
+    ihl: usize,
+    mbuf: Mbuf,
+}
+
+enum Insert {
+    Pending,
+    Complete,
+    // an exact duplicate of a received fragment
+    Duplicate,
+    // an overlapping or an inconsistent fragment, or too many fragments
+    Invalid,
+}
+
+struct Datagram {
+    first_seen: Instant,
+    // the fragments sorted by their offset, without overlaps
+    fragments: Vec<Fragment>,
+    // the length of the ip payload, known from the last fragment
+    total_len: Option<usize>,
+    nb_bytes: usize,
+}
+
+impl Datagram {
+    fn insert(&mut self, fragment: Fragment, more: bool, max_fragments: usize) -> Insert {
+        let end = fragment.offset + fragment.len;
+        let pos = self
+            .fragments
+            .partition_point(|other| other.offset < fragment.offset);
+        if self
+            .fragments
+            .get(pos)
+            .is_some_and(|other| other.offset == fragment.offset && other.len == fragment.len)
+        {
+            return Insert::Duplicate;
+        }
+
+        if !more {
+            if self.total_len.is_some_and(|total_len| total_len != end)
+                || self
+                    .fragments
+                    .iter()
+                    .any(|other| other.offset + other.len > end)
+            {
+                return Insert::Invalid;
+            }
+            self.total_len = Some(end);
+        }
+        if self.total_len.is_some_and(|total_len| end > total_len)
+            || (pos > 0 && {
+                let prev = &self.fragments[pos - 1];
+                prev.offset + prev.len > fragment.offset
+            })
+            || self
+                .fragments
+                .get(pos)
+                .is_some_and(|next| end > next.offset)
+            || self.fragments.len() >= max_fragments
+        {
+            return Insert::Invalid;
+        }
+
+        self.nb_bytes += fragment.len;
+        self.fragments.insert(pos, fragment);
+        // the fragments do not overlap, so they cover the whole datagram
+        if self.total_len == Some(self.nb_bytes) {
+            Insert::Complete
+        } else {
+            Insert::Pending
+        }
+    }
+
+    // Chain the fragments behind the first one, which carries the ip header
+    // of the datagram. The length of the datagram is checked to fit in the ip
+    // header.
+    fn assemble(self) -> Mbuf {
+        let total_len = self.total_len.unwrap();
+        let mut fragments = self.fragments.into_iter();
+        let first = fragments.next().unwrap();
+        let mut mbuf = first.mbuf;
+
+        let header = &mut mbuf.data_mut()[ETHER_HEADER_LEN..ETHER_HEADER_LEN + first.ihl];
+        header[2..4].copy_from_slice(&((first.ihl + total_len) as u16).to_be_bytes());
+        let flags = u16::from_be_bytes([header[6], header[7]]) & !(IPV4_MF | IPV4_FRAG_OFFSET_MASK);
+        header[6..8].copy_from_slice(&flags.to_be_bytes());
+        header[10..12].fill(0);
+        let cksum = !fold(checksum(header, 0));
+        header[10..12].copy_from_slice(&cksum.to_be_bytes());
+
+        let mut appender = mbuf.appender();
+        for fragment in fragments.filter(|fragment| fragment.len > 0) {
+            let mut seg = fragment.mbuf;
+            seg.trim_front(ETHER_HEADER_LEN + fragment.ihl);
+            appender.append_seg(seg);
+        }
+        mbuf
+    }
+}
+
+enum Parsed {
+    NotFragment,
+    Fragment {
+        key: DatagramKey,
+        fragment_offset: usize,
+        packet_len: usize,
+        ihl: usize,
+        more: bool,
+    },
+    Malformed,
+}
+
+// Parse the ipv4 header of an ethernet frame, a frame that is not a well-formed
+// ipv4 packet is not a fragment.
+fn parse(data: &[u8], len: usize) -> Parsed {
+    if data.len() < ETHER_HEADER_LEN + IPV4_HEADER_LEN || ethertype(data) != Some(ETHERTYPE_IPV4) {
+        return Parsed::NotFragment;
+    }
+    let header = &data[ETHER_HEADER_LEN..];
+    let ihl = usize::from(header[0] & 0x0f) * 4;
+    if header[0] >> 4 != 4 || ihl < IPV4_HEADER_LEN || header.len() < ihl {
+        return Parsed::NotFragment;
+    }
+    let flags = u16::from_be_bytes([header[6], header[7]]);
+    let more = flags & IPV4_MF != 0;
+    let fragment_offset = usize::from(flags & IPV4_FRAG_OFFSET_MASK) * 8;
+    if !more && fragment_offset == 0 {
+        return Parsed::NotFragment;
+    }
+
+    let packet_len = usize::from(u16::from_be_bytes([header[2], header[3]]));
+    if packet_len < ihl
+        || len < ETHER_HEADER_LEN + packet_len
+        || (more && (packet_len == ihl || (packet_len - ihl) % 8 != 0))
+        || fragment_offset + packet_len > IPV4_MAX_PACKET_LEN
+    {
+        return Parsed::Malformed;
+    }
+    Parsed::Fragment {
+        key: DatagramKey {
+            src: header[12..16].try_into().unwrap(),
+            dst: header[16..20].try_into().unwrap(),
+            protocol: header[9],
+            ident: u16::from_be_bytes([header[4], header[5]]),
+        },
+        fragment_offset,
+        packet_len,
+        ihl,
+        more,
+    }
+}
+
+/// A reassembler of the ipv4 fragments of the ethernet frames.
+///
+/// The fragments of a datagram are kept until the last missing one arrives,
+/// and then chained into a single mbuf behind the first fragment, which
+/// carries the ip header and the mbuf metadata. The frames that are not
+/// fragments are passed through.
+///
+/// A fragment flood is bounded by the configuration: an incomplete datagram
+/// is dropped after `timeout`, the oldest datagram is dropped when there are
+/// `max_datagrams` of them, and a datagram with more than `max_fragments`
+/// fragments is dropped. A datagram with overlapping or inconsistent
+/// fragments is dropped as well, while an exact duplicate of a fragment is
+/// ignored. The vlan-tagged frames are not recognized.
+pub struct Ipv4Reassembler {
+    conf: ReassemblyConf,
+    datagrams: HashMap<DatagramKey, Datagram>,
+    // the datagrams in the order of their first fragments, including the ones
+    // that are already completed
+    fifo: VecDeque<(Instant, DatagramKey)>,
+    nb_dropped: u64,
+}
+
+impl Ipv4Reassembler {
+    /// # Panics
+    ///
+    /// This function panics if `conf.max_datagrams` or `conf.max_fragments` is
+    /// 0.
+    pub fn new(conf: &ReassemblyConf) -> Self {
+        assert!(
+            conf.max_datagrams > 0 && conf.max_fragments > 0,
+            "invalid reassembly conf"
+        );
+        Self {
+            conf: conf.clone(),
+            datagrams: HashMap::new(),
+            fifo: VecDeque::new(),
+            nb_dropped: 0,
+        }
+    }
+
+    /// Feed the frame in `mbuf`, and return the frame of a datagram once it is
+    /// complete.
+    ///
+    /// A frame that is not a fragment is returned at once, a fragment is kept
+    /// and `None` is returned until its datagram is complete. The reassembled
+    /// mbuf has a header segment and a segment per fragment, and its ip header
+    /// is updated with the length of the datagram.
+    #[inline]
+    pub fn push(&mut self, mbuf: Mbuf) -> Option<Mbuf> {
+        self.push_at(mbuf, Instant::now())
+    }
+
+    // `now` is given by the tests.
+    fn push_at(&mut self, mut mbuf: Mbuf, now: Instant) -> Option<Mbuf> {
+        self.expire(now);
+
+        let (key, fragment_offset, packet_len, ihl, more) = match parse(mbuf.data(), mbuf.len()) {
+            Parsed::NotFragment => return Some(mbuf),
+            Parsed::Fragment {
+                key,
+                fragment_offset,
+                packet_len,
+                ihl,
+                more,
+            } => (key, fragment_offset, packet_len, ihl, more),
+            Parsed::Malformed => {
+                self.nb_dropped += 1;
+                return None;
+            }
+        };
+        // strip the padding of a short frame
+        mbuf.truncate(ETHER_HEADER_LEN + packet_len);
+        if mbuf.linearize().is_err() {
+            self.nb_dropped += 1;
+            return None;
+        }
+
+        if !self.datagrams.contains_key(&key) {
+            while self.datagrams.len() >= self.conf.max_datagrams as usize {
+                self.drop_oldest();
+            }
+            self.datagrams.insert(
+                key,
+                Datagram {
+                    first_seen: now,
+                    fragments: Vec::new(),
+                    total_len: None,
+                    nb_bytes: 0,
+                },
+            );
+            self.fifo.push_back((now, key));
+        }
+
+        let fragment = Fragment {
+            offset: fragment_offset,
+            len: packet_len - ihl,
+            ihl,
+            mbuf,
+        };
+        let datagram = self.datagrams.get_mut(&key).unwrap();
+        match datagram.insert(fragment, more, usize::from(self.conf.max_fragments)) {
+            Insert::Pending => None,
+            Insert::Complete => {
+                let datagram = self.datagrams.remove(&key).unwrap();
+                // the length of the datagram is bounded by the ip headers of
+                // the other fragments, which may be shorter than the one of
+                // the first fragment
+                if datagram.fragments[0].ihl + datagram.total_len.unwrap() > IPV4_MAX_PACKET_LEN {
+                    self.nb_dropped += datagram.fragments.len() as u64;
+                    return None;
+                }
+                Some(datagram.assemble())
+            }
+            Insert::Duplicate => {
+                self.nb_dropped += 1;
+                None
+            }
+            Insert::Invalid => {
+                let datagram = self.datagrams.remove(&key).unwrap();
+                self.nb_dropped += datagram.fragments.len() as u64 + 1;
+                None
+            }
+        }
+    }
+
+    // Drop the incomplete datagrams whose first fragment is older than the
+    // timeout.
+    fn expire(&mut self, now: Instant) {
+        while let Some((first_seen, _)) = self.fifo.front() {
+            if now.saturating_duration_since(*first_seen) < self.conf.timeout {
+                break;
+            }
+            self.drop_oldest();
+        }
+    }
+
+    // Drop the oldest incomplete datagram, skipping the completed ones.
+    fn drop_oldest(&mut self) {
+        while let Some((first_seen, key)) = self.fifo.pop_front() {
+            // the key may be reused by a later datagram
+            if self
+                .datagrams
+                .get(&key)
+                .is_some_and(|datagram| datagram.first_seen == first_seen)
+            {
+                let datagram = self.datagrams.remove(&key).unwrap();
+                self.nb_dropped += datagram.fragments.len() as u64;
+                return;
+            }
+        }
+    }
+
+    /// Return the number of the incomplete datagrams.
+    pub fn nb_pending(&self) -> usize {
+        self.datagrams.len()
+    }
+
+    /// Return the number of the dropped fragments.
+    pub fn nb_dropped(&self) -> u64 {
+        self.nb_dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use rpkt::ipv4::IpProtocol;
+
+    use super::*;
+    use crate::proto::{fragment, l4_frame};
+    use crate::*;
+
+    #[test]
+    fn reassemble_with_bounds() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+            let alloc = |bytes: &[u8]| Mbuf::from_slice(bytes, &mp).unwrap();
+            // an ipv4 udp frame whose payload bytes are the low bytes of their
+            // offsets
+            let udp_frame = |ident, payload_len| {
+                let payload: Vec<_> = (0..payload_len).map(|idx| idx as u8).collect();
+                let (src, dst) = (Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2));
+                l4_frame(IpProtocol::UDP, src, dst, 12345, 53, ident, &payload[..])
+            };
+
+            let mut conf = ReassemblyConf::new();
+            conf.set_max_datagrams(2);
+            conf.set_max_fragments(4);
+            let mut reassembler = Ipv4Reassembler::new(&conf);
+            let start = Instant::now();
+
+            // an unfragmented frame is passed through
+            let frame = udp_frame(1, 1000);
+            let mbuf = reassembler.push_at(alloc(&frame), start).unwrap();
+            assert!(mbuf.data_eq_slice(&frame));
+
+            // the fragments arrive out of order, with a duplicate and padding
+            let fragments = fragment(&frame, &[400, 808]);
+            assert!(reassembler.push_at(alloc(&fragments[2]), start).is_none());
+            assert!(reassembler.push_at(alloc(&fragments[0]), start).is_none());
+            assert!(reassembler.push_at(alloc(&fragments[0]), start).is_none());
+            let mut padded = fragments[1].clone();
+            padded.extend_from_slice(&[0xee; 4]);
+            let mbuf = reassembler.push_at(alloc(&padded), start).unwrap();
+            assert_eq!(mbuf.num_segs(), 3);
+            assert!(mbuf.data_eq_slice(&frame));
+            assert_eq!(reassembler.nb_pending(), 0);
+            assert_eq!(reassembler.nb_dropped(), 1);
+            drop(mbuf);
+
+            // an incomplete datagram times out
+            let fragments = fragment(&udp_frame(2, 1000), &[400]);
+            assert!(reassembler.push_at(alloc(&fragments[0]), start).is_none());
+            let later = start + ReassemblyConf::TIMEOUT;
+            assert!(reassembler.push_at(alloc(&fragments[1]), later).is_none());
+            assert_eq!(reassembler.nb_pending(), 1);
+            assert_eq!(reassembler.nb_dropped(), 2);
+
+            // the oldest datagram makes room for a new one
+            for ident in 3..5 {
+                let fragments = fragment(&udp_frame(ident, 1000), &[400]);
+                assert!(reassembler.push_at(alloc(&fragments[0]), later).is_none());
+            }
+            assert_eq!(reassembler.nb_pending(), 2);
+            assert_eq!(reassembler.nb_dropped(), 3);
+
+            // the overlapping fragments and the excess fragments drop the
+            // datagram
+            let fragments = fragment(&udp_frame(3, 1000), &[200]);
+            assert!(reassembler.push_at(alloc(&fragments[0]), later).is_none());
+            assert_eq!(reassembler.nb_pending(), 1);
+            assert_eq!(reassembler.nb_dropped(), 5);
+            let fragments = fragment(&udp_frame(5, 1000), &[8, 16, 24, 32]);
+            for fragment in &fragments {
+                assert!(reassembler.push_at(alloc(fragment), later).is_none());
+            }
+            assert_eq!(reassembler.nb_dropped(), 10);
+
+            // a non-final fragment that is not a multiple of 8 bytes
+            let fragments = fragment(&udp_frame(6, 1000), &[404]);
+            assert!(reassembler.push_at(alloc(&fragments[0]), later).is_none());
+            assert_eq!(reassembler.nb_dropped(), 11);
+
+            let later = later + ReassemblyConf::TIMEOUT;
+            reassembler.expire(later);
+            assert_eq!(reassembler.nb_pending(), 0);
+            assert_eq!(mp.nb_mbufs(), 128);
+
+            // a datagram of the maximum length whose first fragment carries ip
+            // options, so that the reassembled packet exceeds 65535 bytes
+            conf.set_max_fragments(64);
+            let mut reassembler = Ipv4Reassembler::new(&conf);
+            let frame = udp_frame(7, IPV4_MAX_PACKET_LEN - IPV4_HEADER_LEN - 8);
+            let splits: Vec<_> = (1..45).map(|idx| idx * 1480).collect();
+            let mut fragments = fragment(&frame, &splits);
+            let first = &mut fragments[0];
+            first.splice(34..34, [0x01; 4]);
+            first[ETHER_HEADER_LEN] = 0x46;
+            first[16..18].copy_from_slice(&1504_u16.to_be_bytes());
+            for fragment in &fragments {
+                assert!(reassembler.push_at(alloc(fragment), start).is_none());
+            }
+            assert_eq!(reassembler.nb_pending(), 0);
+            assert_eq!(reassembler.nb_dropped(), 45);
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+}
@@ -0,0 +1,234 @@
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr::NonNull;
+
+use arrayvec::ArrayVec;
+use rpkt_dpdk_sys as ffi;
+
+use crate::error::*;
+use crate::Mbuf;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ReorderConf {
+    /// The number of the sequence numbers that can be buffered ahead of the
+    /// oldest missing one, which must be a power of 2.
+    pub size: u32,
+    pub socket_id: u32,
+}
+
+impl ReorderConf {
+    pub const SIZE: u32 = 1024;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_size(&mut self, val: u32) {
+        self.size = val;
+    }
+
+    pub fn set_socket_id(&mut self, val: u32) {
+        self.socket_id = val;
+    }
+}
+
+impl Default for ReorderConf {
+    fn default() -> Self {
+        Self {
+            size: Self::SIZE,
+            socket_id: 0,
+        }
+    }
+}
+
+/// A reorder buffer that restores the order of the mbufs after a parallel
+/// stage, backed by `rte_reorder`.
+///
+/// The mbufs are stamped with incrementing sequence numbers before they are
+/// spread across the workers, e.g. with `Reorderer::stamp`. After the workers,
+/// the mbufs are inserted in any order and drained in the order of the
+/// sequence numbers, as soon as they are contiguous.
+///
+/// The sequence numbers are stored in the dynamic field registered by
+/// `rte_reorder`, so the stamping lcore can also call `Reorderer::set_seqn`
+/// with its own counter, as long as the numbers start from 0.
+pub struct Reorderer {
+    ptr: NonNull<ffi::rte_reorder_buffer>,
+    next: u32,
+}
+
+unsafe impl Send for Reorderer {}
+
+impl Reorderer {
+    /// Create a new reorder buffer named `name`, which expects the sequence
+    /// number 0 first.
+    pub fn try_create(name: String, conf: &ReorderConf) -> Result<Self> {
+        if !conf.size.is_power_of_two() {
+            return Error::service_err("reorder size is not a power of 2").to_err();
+        }
+
+        let cname = CString::new(name).map_err(|_| Error::service_err("invalid reorder name"))?;
+
+        let raw = unsafe {
+            ffi::rte_reorder_create(
+                cname.as_bytes_with_nul().as_ptr() as *const c_char,
+                conf.socket_id,
+                conf.size,
+            )
+        };
+        let ptr = check_ptr(raw, "fail to create reorder buffer")?;
+        let reorderer = Self { ptr, next: 0 };
+
+        // Otherwise the first inserted mbuf sets the oldest expected sequence
+        // number, and the mbufs reordered behind it would be rejected.
+        let res = unsafe { ffi::rte_reorder_min_seqn_set(ptr.as_ptr(), 0) };
+        check_ret(res, "fail to set reorder sequence number")?;
+
+        Ok(reorderer)
+    }
+
+    /// Return the sequence number of `mbuf`.
+    #[inline]
+    pub fn seqn(mbuf: &Mbuf) -> u32 {
+        unsafe { *ffi::rte_reorder_seqn_(mbuf.as_ptr() as *mut ffi::rte_mbuf) }
+    }
+
+    /// Set the sequence number of `mbuf`.
+    ///
+    /// The dynamic field is registered when the first reorder buffer is
+    /// created, so this must not be called before.
+    #[inline]
+    pub fn set_seqn(mbuf: &mut Mbuf, seqn: u32) {
+        unsafe { *ffi::rte_reorder_seqn_(mbuf.as_ptr() as *mut ffi::rte_mbuf) = seqn };
+    }
+
+    /// Return the sequence number of the next stamped mbuf.
+    pub fn next_seqn(&self) -> u32 {
+        self.next
+    }
+
+    /// Stamp `mbuf` with the next sequence number.
+    #[inline]
+    pub fn stamp(&mut self, mbuf: &mut Mbuf) {
+        Self::set_seqn(mbuf, self.next);
+        self.next = self.next.wrapping_add(1);
+    }
+
+    /// Insert `mbuf` into the buffer.
+    ///
+    /// The mbuf is returned if its sequence number is older than the drained
+    /// ones, or if it is too far ahead of the oldest missing one to fit in the
+    /// buffer. The caller may send it out of order or drop it.
+    #[inline]
+    pub fn insert(&mut self, mbuf: Mbuf) -> std::result::Result<(), Mbuf> {
+        let raw = mbuf.into_raw();
+        let res = unsafe { ffi::rte_reorder_insert(self.ptr.as_ptr(), raw) };
+        if res < 0 {
+            return Err(unsafe { Mbuf::from_raw(raw) });
+        }
+        Ok(())
+    }
+
+    /// Drain the mbufs with contiguous sequence numbers from the oldest missing
+    /// one, until `batch` is full.
+    ///
+    /// Return the number of drained mbufs.
+    #[inline]
+    pub fn drain<const N: usize>(&mut self, batch: &mut ArrayVec<Mbuf, N>) -> usize {
+        assert!(N <= u32::MAX as usize);
+        unsafe {
+            let nb_drain = ffi::rte_reorder_drain(
+                self.ptr.as_ptr(),
+                batch.as_mut_ptr().add(batch.len()) as *mut *mut ffi::rte_mbuf,
+                (N - batch.len()) as u32,
+            ) as usize;
+            batch.set_len(batch.len() + nb_drain);
+
+            nb_drain
+        }
+    }
+
+    pub fn as_ptr(&self) -> *const ffi::rte_reorder_buffer {
+        self.ptr.as_ptr()
+    }
+}
+
+impl Drop for Reorderer {
+    fn drop(&mut self) {
+        // the buffered mbufs are freed as well
+        unsafe {
+            ffi::rte_reorder_free(self.ptr.as_ptr());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn drain_in_sequence() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut conf = ReorderConf::new();
+            conf.set_size(8);
+            let mut reorderer = Reorderer::try_create("reorder".to_string(), &conf).unwrap();
+            conf.set_size(12);
+            assert!(Reorderer::try_create("bad_reorder".to_string(), &conf).is_err());
+
+            let mut mbufs = Vec::new();
+            for i in 0..10 {
+                let mut mbuf = mp.try_alloc().unwrap();
+                mbuf.extend_from_slice(&[i as u8; 1][..]);
+                reorderer.stamp(&mut mbuf);
+                mbufs.push(Some(mbuf));
+            }
+            assert_eq!(reorderer.next_seqn(), 10);
+            assert_eq!(Reorderer::seqn(mbufs[3].as_ref().unwrap()), 3);
+
+            let mut batch = ArrayVec::<_, 16>::new();
+            let mut drained = Vec::new();
+            // the mbufs arrive in the order 3, 1, 2, 0
+            for i in [3, 1, 2] {
+                assert!(reorderer.insert(mbufs[i].take().unwrap()).is_ok());
+                assert_eq!(reorderer.drain(&mut batch), 0);
+            }
+            assert!(reorderer.insert(mbufs[0].take().unwrap()).is_ok());
+            assert_eq!(reorderer.drain(&mut batch), 4);
+            drained.extend(batch.drain(..).map(|mbuf| mbuf.data()[0]));
+
+            // 5 is missing, so 6 and 7 are held back
+            for i in [6, 7, 4] {
+                assert!(reorderer.insert(mbufs[i].take().unwrap()).is_ok());
+            }
+            assert_eq!(reorderer.drain(&mut batch), 1);
+            drained.extend(batch.drain(..).map(|mbuf| mbuf.data()[0]));
+            assert!(reorderer.insert(mbufs[5].take().unwrap()).is_ok());
+            assert_eq!(reorderer.drain(&mut batch), 3);
+            drained.extend(batch.drain(..).map(|mbuf| mbuf.data()[0]));
+            assert_eq!(drained, [0, 1, 2, 3, 4, 5, 6, 7]);
+
+            // a drained sequence number is rejected
+            let mut late = mp.try_alloc().unwrap();
+            Reorderer::set_seqn(&mut late, 2);
+            let late = reorderer.insert(late).unwrap_err();
+            assert_eq!(Reorderer::seqn(&late), 2);
+            drop(late);
+
+            // the buffered mbufs are freed with the buffer
+            assert!(reorderer.insert(mbufs[9].take().unwrap()).is_ok());
+            assert_eq!(reorderer.drain(&mut batch), 0);
+            drop(mbufs);
+            drop(reorderer);
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+}
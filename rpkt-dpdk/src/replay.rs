@@ -0,0 +1,367 @@
+use std::io::Read;
+use std::time::Duration;
+
+use arrayvec::ArrayVec;
+use rpkt::editor::PacketEditor;
+use rpkt::ipv4::IpProtocol;
+use rpkt::pcap::{PcapReader, LINKTYPE_ETHERNET};
+use rpkt_dpdk_sys as ffi;
+use rpkt_time::{Instant, Pacer};
+
+use crate::burst::total_bytes;
+use crate::error::*;
+use crate::proto::fold;
+use crate::{Mbuf, Mempool, TxQueue};
+
+const BURST: usize = 32;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ReplayConf {
+    /// The target rate in packets per second, 0 sends as fast as the tx queue
+    /// accepts the packets.
+    pub pps: u64,
+    /// The number of the simulated flows. The i-th replay of the capture adds
+    /// `i % nb_flows` to the ipv4 source addresses, so 1 replays the capture
+    /// unmodified.
+    pub nb_flows: u32,
+}
+
+impl ReplayConf {
+    pub const PPS: u64 = 0;
+    pub const NB_FLOWS: u32 = 1;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_pps(&mut self, val: u64) {
+        self.pps = val;
+    }
+
+    pub fn set_nb_flows(&mut self, val: u32) {
+        self.nb_flows = val;
+    }
+}
+
+impl Default for ReplayConf {
+    fn default() -> Self {
+        Self {
+            pps: Self::PPS,
+            nb_flows: Self::NB_FLOWS,
+        }
+    }
+}
+
+/// The outcome of `ReplayEngine::run`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ReplayStats {
+    /// The number of the packets accepted by the tx queue.
+    pub packets: u64,
+    /// The number of the bytes accepted by the tx queue.
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl ReplayStats {
+    /// Return the achieved rate in packets per second.
+    pub fn pps(&self) -> f64 {
+        self.packets as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Return the achieved rate in bits per second, excluding the preamble
+    /// and the inter-frame gap.
+    pub fn bps(&self) -> f64 {
+        self.bytes as f64 * 8.0 / self.elapsed.as_secs_f64()
+    }
+}
+
+// The location of the ipv4 source address and of the checksums covering it.
+#[derive(Clone, Copy, Debug)]
+struct SrcRewrite {
+    l3_offset: usize,
+    // the offset of the tcp/udp checksum, and whether it is a udp checksum
+    l4_cksum: Option<(usize, bool)>,
+    src: u32,
+}
+
+impl SrcRewrite {
+    fn new(frame: &mut [u8]) -> Option<Self> {
+        let editor = PacketEditor::new(frame)?;
+        let l3_offset = editor.l3_offset();
+        let l4_offset = editor.l4_offset();
+
+        let l3 = &frame[l3_offset..];
+        let l4_cksum = l4_offset.map(|offset| match IpProtocol::from(l3[9]) {
+            IpProtocol::UDP => (offset + 6, true),
+            _ => (offset + 16, false),
+        });
+        Some(Self {
+            l3_offset,
+            l4_cksum,
+            src: u32::from_be_bytes(l3[12..16].try_into().unwrap()),
+        })
+    }
+
+    // Set the source address of the frame, whose source address is `self.src`,
+    // to `src`, and fix up the checksums incrementally.
+    fn apply(&self, frame: &mut [u8], src: u32) {
+        let l3 = &mut frame[self.l3_offset..];
+        l3[12..16].copy_from_slice(&src.to_be_bytes());
+        let cksum = u16::from_be_bytes([l3[10], l3[11]]);
+        let cksum = update_checksum(cksum, self.src, src);
+        l3[10..12].copy_from_slice(&cksum.to_be_bytes());
+
+        if let Some((offset, udp)) = self.l4_cksum {
+            let cksum = u16::from_be_bytes([frame[offset], frame[offset + 1]]);
+            // a zero udp checksum means that there is no checksum
+            if udp && cksum == 0 {
+                return;
+            }
+            let mut cksum = update_checksum(cksum, self.src, src);
+            if udp && cksum == 0 {
+                cksum = 0xffff;
+            }
+            frame[offset..offset + 2].copy_from_slice(&cksum.to_be_bytes());
+        }
+    }
+}
+
+// Update a checksum after a 32-bit word of the covered data changes from `old`
+// to `new`, following eqn. 3 of RFC 1624: HC' = ~(~HC + ~m + m').
+fn update_checksum(cksum: u16, old: u32, new: u32) -> u16 {
+    let mut accum = u32::from(!cksum);
+    for shift in [16, 0] {
+        accum += u32::from(!((old >> shift) as u16)) + u32::from((new >> shift) as u16);
+    }
+    !fold(accum)
+}
+
+struct Template {
+    mbuf: Mbuf,
+    rewrite: Option<SrcRewrite>,
+}
+
+/// A traffic generator that replays a pcap capture out of a tx queue.
+///
+/// The packets of the capture are copied into the mbufs once when the engine
+/// is loaded, and the mbufs are sent over and over in the capture order, so
+/// the hot loop neither parses nor copies the packets. The same mbuf is sent
+/// by raising its reference count, so the port must not enable the mbuf fast
+/// free tx offload.
+///
+/// When `ReplayConf::nb_flows` exceeds 1, the packets with a rewritten source
+/// address are copied from the loaded mbufs, and the checksums are fixed up
+/// incrementally.
+pub struct ReplayEngine {
+    templates: Vec<Template>,
+    mp: Mempool,
+    conf: ReplayConf,
+    next: usize,
+    iteration: u32,
+}
+
+impl ReplayEngine {
+    /// Load the ethernet frames of `pcap` into the mbufs of `mp`.
+    ///
+    /// The copies of the rewritten packets are also allocated from `mp`, which
+    /// should have room for the tx descriptors of the queue on top of the
+    /// capture. This function fails if the capture is empty, is not an
+    /// ethernet capture, or a packet does not fit in an mbuf.
+    pub fn load<R: Read>(mut pcap: PcapReader<R>, mp: &Mempool, conf: &ReplayConf) -> Result<Self> {
+        if pcap.linktype() != LINKTYPE_ETHERNET || conf.nb_flows == 0 {
+            return Error::service_err("invalid replay config").to_err();
+        }
+
+        let mut templates = Vec::new();
+        while let Some(mut record) = pcap
+            .next_record()
+            .map_err(|_| Error::service_err("fail to read pcap"))?
+        {
+            let mut mbuf = mp
+                .try_alloc()
+                .ok_or(Error::service_err("replay mempool is exhausted"))?;
+            if record.data.len() > mbuf.capacity() {
+                return Error::service_err("pcap record exceeds the mbuf").to_err();
+            }
+            mbuf.extend_from_slice(&record.data[..]);
+            let rewrite = SrcRewrite::new(&mut record.data[..]);
+            templates.push(Template { mbuf, rewrite });
+        }
+        if templates.is_empty() {
+            return Error::service_err("empty pcap").to_err();
+        }
+
+        Ok(Self {
+            templates,
+            mp: mp.clone(),
+            conf: *conf,
+            next: 0,
+            iteration: 0,
+        })
+    }
+
+    /// Return the number of the loaded packets.
+    pub fn len(&self) -> usize {
+        self.templates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+
+    /// Send the packets to `txq` for `duration`, at the rate of
+    /// `ReplayConf::pps`.
+    ///
+    /// A later `run` resumes from the packet after the last one sent.
+    pub fn run(&mut self, txq: &mut TxQueue, duration: Duration) -> ReplayStats {
+        let mut pacer = (self.conf.pps > 0).then(|| Pacer::new(self.conf.pps));
+        let mut batch = ArrayVec::<Mbuf, BURST>::new();
+        let mut stats = ReplayStats::default();
+
+        let start = Instant::now();
+        while start.elapsed() < duration {
+            let room = (BURST - batch.len()) as u32;
+            let n = pacer.as_mut().map_or(room, |pacer| pacer.allow(room));
+            for _ in 0..n {
+                match self.next_mbuf() {
+                    Some(mbuf) => batch.push(mbuf),
+                    None => break,
+                }
+            }
+
+            let (nb_pkts, nb_bytes) = (batch.len(), total_bytes(&batch));
+            txq.tx(&mut batch);
+            stats.packets += (nb_pkts - batch.len()) as u64;
+            stats.bytes += (nb_bytes - total_bytes(&batch)) as u64;
+        }
+        stats.elapsed = start.elapsed();
+
+        // the packets that are not accepted by the tx queue are dropped, and a
+        // later run sends them again
+        for _ in batch.drain(..) {
+            if self.next == 0 {
+                self.next = self.templates.len();
+                self.iteration = self.iteration.wrapping_sub(1);
+            }
+            self.next -= 1;
+        }
+        stats
+    }
+
+    // Return the next packet to send, or `None` if the mempool has no room for
+    // a rewritten packet.
+    fn next_mbuf(&mut self) -> Option<Mbuf> {
+        let template = &self.templates[self.next];
+        let flow = self.iteration % self.conf.nb_flows;
+        let mbuf = match template.rewrite {
+            Some(rewrite) if flow != 0 => {
+                let mut mbuf = template.mbuf.deep_copy(&self.mp)?;
+                rewrite.apply(mbuf.data_mut(), rewrite.src.wrapping_add(flow));
+                mbuf
+            }
+            _ => {
+                let raw = template.mbuf.as_ptr() as *mut ffi::rte_mbuf;
+                unsafe {
+                    ffi::rte_pktmbuf_refcnt_update_(raw, 1);
+                    Mbuf::from_raw(raw)
+                }
+            }
+        };
+
+        self.next += 1;
+        if self.next == self.templates.len() {
+            self.next = 0;
+            self.iteration = self.iteration.wrapping_add(1);
+        }
+        Some(mbuf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+    use rpkt::ether::EtherPacket;
+    use rpkt::ipv4::Ipv4Packet;
+    use rpkt::malformed::TCP_FRAME;
+    use rpkt::tcp::TcpPacket;
+    use rpkt::Cursor;
+
+    // A little-endian microsecond capture of `frames`.
+    fn build_pcap(frames: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for word in [0xa1b2c3d4, 0x0004_0002, 0, 0, 65535, LINKTYPE_ETHERNET] {
+            bytes.extend_from_slice(&u32::to_le_bytes(word));
+        }
+        for (i, frame) in frames.iter().enumerate() {
+            for word in [i as u32, 0, frame.len() as u32, frame.len() as u32] {
+                bytes.extend_from_slice(&u32::to_le_bytes(word));
+            }
+            bytes.extend_from_slice(frame);
+        }
+        bytes
+    }
+
+    #[test]
+    fn rewrite_source_ip() {
+        let mut frame = TCP_FRAME;
+        let rewrite = SrcRewrite::new(&mut frame[..]).unwrap();
+        assert_eq!(rewrite.src, u32::from_be_bytes([192, 168, 1, 140]));
+
+        for flow in [1, 200, 0x10000] {
+            let mut frame = TCP_FRAME;
+            rewrite.apply(&mut frame[..], rewrite.src.wrapping_add(flow));
+
+            let ethpkt = EtherPacket::parse(Cursor::new(&frame[..])).unwrap();
+            let ippkt = Ipv4Packet::parse(ethpkt.payload()).unwrap();
+            assert!(ippkt.verify_checksum());
+            let (src_ip, dst_ip) = (ippkt.source_ip(), ippkt.dest_ip());
+            assert_eq!(u32::from_be_bytes(src_ip.0), rewrite.src.wrapping_add(flow));
+            let mut tcppkt = TcpPacket::parse(ippkt.payload()).unwrap();
+            assert!(tcppkt.verify_ipv4_checksum(src_ip, dst_ip));
+        }
+
+        // a non-ip frame is not rewritten
+        let mut frame = TCP_FRAME;
+        frame[12..14].copy_from_slice(&[0x08, 0x06]);
+        assert!(SrcRewrite::new(&mut frame[..]).is_none());
+    }
+
+    // This test requires the net_null pmd.
+    #[test]
+    fn replay_to_null_vdev() {
+        DpdkOption::new().init().unwrap();
+
+        let port_id = service().port_vdev_add("net_null_replay", "").unwrap();
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        utils::init_port(port_id, 1, 1, 512, "wtf", 512, 0).unwrap();
+
+        {
+            let mp = service().mempool("wtf").unwrap();
+            let bytes = build_pcap(&[&TCP_FRAME[..], &[0xff; 60][..]]);
+            let pcap = PcapReader::new(&bytes[..]).unwrap();
+            let mut conf = ReplayConf::new();
+            conf.set_pps(10_000);
+            conf.set_nb_flows(4);
+            let mut engine = ReplayEngine::load(pcap, &mp, &conf).unwrap();
+            assert_eq!(engine.len(), 2);
+
+            let mut txq = service().tx_queue(port_id, 0).unwrap();
+            let mut stats_query = service().stats_query(port_id).unwrap();
+            let opackets = stats_query.query().opackets();
+            let stats = engine.run(&mut txq, Duration::from_millis(200));
+            assert_eq!(stats_query.query().opackets(), opackets + stats.packets);
+            assert_eq!(
+                stats.bytes,
+                (stats.packets + 1) / 2 * 74 + stats.packets / 2 * 60
+            );
+            // the pacer allows a burst on top of the rate
+            assert!(stats.packets <= 2000 + Pacer::BURST as u64);
+            assert!(stats.pps() > 5000.0);
+        }
+
+        service().port_close(port_id).unwrap();
+        service().mempool_free("wtf").unwrap();
+        service().port_vdev_remove("net_null_replay").unwrap();
+    }
+}
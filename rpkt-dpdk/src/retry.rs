@@ -0,0 +1,156 @@
+use std::time::Duration;
+
+/// The backoff between two attempts of a `RetryPolicy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+    /// Retry immediately.
+    None,
+    /// Spin for the given number of pause instructions.
+    Pause(u32),
+    /// Yield the cpu to the other threads, see `std::thread::yield_now`.
+    Yield,
+    /// Put the current thread to sleep.
+    Sleep(Duration),
+}
+
+impl Backoff {
+    /// Wait for the duration of the backoff.
+    #[inline]
+    pub fn wait(&self) {
+        self.wait_with(std::thread::sleep)
+    }
+
+    #[inline]
+    fn wait_with(&self, mut sleep: impl FnMut(Duration)) {
+        match *self {
+            Backoff::None => {}
+            Backoff::Pause(n) => {
+                for _ in 0..n {
+                    std::hint::spin_loop();
+                }
+            }
+            Backoff::Yield => std::thread::yield_now(),
+            Backoff::Sleep(duration) => sleep(duration),
+        }
+    }
+}
+
+/// A retry policy of the blocking operations, e.g. `Ring::enqueue_burst_with_retry`.
+///
+/// The busy-waiting backoffs `Backoff::None` and `Backoff::Pause` have the lowest
+/// latency, the other backoffs leave the cpu to the other threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one.
+    pub max_attempts: usize,
+    pub backoff: Backoff,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: usize, backoff: Backoff) -> Self {
+        Self {
+            max_attempts,
+            backoff,
+        }
+    }
+
+    pub fn set_max_attempts(&mut self, val: usize) -> &mut Self {
+        self.max_attempts = val;
+        self
+    }
+
+    pub fn set_backoff(&mut self, val: Backoff) -> &mut Self {
+        self.backoff = val;
+        self
+    }
+
+    /// Call `f` until it returns `Some`, with a backoff between two calls.
+    ///
+    /// Return `None` if `f` still returns `None` after `max_attempts` calls.
+    pub fn retry<T>(&self, f: impl FnMut() -> Option<T>) -> Option<T> {
+        self.retry_with(f, std::thread::sleep)
+    }
+
+    // `sleep` is replaced by a mock clock in the tests.
+    fn retry_with<T>(
+        &self,
+        mut f: impl FnMut() -> Option<T>,
+        mut sleep: impl FnMut(Duration),
+    ) -> Option<T> {
+        for attempt in 0..self.max_attempts {
+            if attempt > 0 {
+                self.backoff.wait_with(&mut sleep);
+            }
+            if let Some(res) = f() {
+                return Some(res);
+            }
+        }
+        None
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(1000, Backoff::Pause(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Return a closure that succeeds on the `n`-th call, and counts the calls.
+    fn succeed_at(n: usize, calls: &mut usize) -> impl FnMut() -> Option<usize> + '_ {
+        move || {
+            *calls += 1;
+            if *calls == n {
+                Some(*calls)
+            } else {
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn retry_attempt_counts() {
+        for backoff in [Backoff::None, Backoff::Pause(10), Backoff::Yield] {
+            let policy = RetryPolicy::new(5, backoff);
+
+            let mut calls = 0;
+            assert_eq!(policy.retry(succeed_at(3, &mut calls)), Some(3));
+            assert_eq!(calls, 3);
+
+            let mut calls = 0;
+            assert_eq!(policy.retry(succeed_at(6, &mut calls)), None);
+            assert_eq!(calls, 5);
+        }
+
+        let mut calls = 0;
+        let policy = RetryPolicy::new(0, Backoff::None);
+        assert_eq!(policy.retry(succeed_at(1, &mut calls)), None);
+        assert_eq!(calls, 0);
+    }
+
+    #[test]
+    fn retry_with_sleep() {
+        let mut policy = RetryPolicy::default();
+        policy
+            .set_max_attempts(4)
+            .set_backoff(Backoff::Sleep(Duration::from_secs(1)));
+
+        // the mock clock advances on each sleep
+        let mut clock = Duration::ZERO;
+        let mut calls = 0;
+        let res = policy.retry_with(succeed_at(3, &mut calls), |d| clock += d);
+        assert_eq!(res, Some(3));
+        assert_eq!(calls, 3);
+        assert_eq!(clock, Duration::from_secs(2));
+
+        let mut clock = Duration::ZERO;
+        let mut calls = 0;
+        let res = policy.retry_with(succeed_at(10, &mut calls), |d| clock += d);
+        assert_eq!(res, None);
+        assert_eq!(calls, 4);
+        assert_eq!(clock, Duration::from_secs(3));
+    }
+}
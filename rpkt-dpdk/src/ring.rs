@@ -1,12 +1,18 @@
 use crate::{
-    error::{Error, Result},
-    Mbuf,
+    error::{check_ptr, check_ret, Error, ErrorKind, Result},
+    names,
+    offload::{OlFlags, DEFAULT_RSS_KEY_40B},
+    rss, Backoff, Mbuf, Mempool, Memzone, ProcType, RetryPolicy,
 };
 use std::{
     ffi::{c_char, CString},
+    marker::PhantomData,
     os::raw::c_void,
     ptr::NonNull,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
 };
 
 use arrayvec::ArrayVec;
@@ -36,13 +42,51 @@ bitflags! {
 /// Ring size mask
 const RTE_RING_SZ_MASK: u32 = 0x7fffffff;
 
+/// The synchronization mode of the producer or the consumer of a ring.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncType {
+    /// Multi-thread safe, the default mode.
+    MultiThread,
+    /// Single thread only.
+    SingleThread,
+    /// Multi-thread relaxed tail sync.
+    Rts,
+    /// Multi-thread head/tail sync.
+    Hts,
+}
+
+impl SyncType {
+    // Convert from `enum rte_ring_sync_type`.
+    fn from_raw(val: u32) -> Self {
+        match val {
+            1 => Self::SingleThread,
+            2 => Self::Rts,
+            3 => Self::Hts,
+            _ => Self::MultiThread,
+        }
+    }
+}
+
+/// What an enqueue to a full ring does with the mbufs that do not fit, see
+/// `Ring::enqueue_burst_with`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnqueuePolicy {
+    /// Drop the mbufs and return them to their mempool.
+    DropAndFree,
+    /// Leave the mbufs in the batch for the caller.
+    ReturnToCaller,
+    /// Spin until the mbufs fit, with at most the given number of attempts,
+    /// and leave the mbufs that still do not fit in the batch.
+    BlockSpin(usize),
+}
+
 #[derive(Clone, Debug)]
 pub struct RingConf {
     ///   The size of the ring (must be a power of 2,
     ///   unless RING_F_EXACT_SZ is set in flags).
     pub count: u32,
     /// The *socket_id* argument is the socket identifier in case of
-    ///   NUMA. The value can be *SOCKET_ID_ANY* if there is no NUMA
+    ///   NUMA. The value can be *RingConf::SOCKET_ID_ANY* if there is no NUMA
     ///   constraint for the reserved zone.
     pub socket_id: u32,
 
@@ -81,20 +125,82 @@ pub struct RingConf {
     pub flag: RingFlags,
 }
 
+impl RingConf {
+    /// The ring can be allocated on any socket, see `Ring::socket_id`.
+    pub const SOCKET_ID_ANY: u32 = u32::MAX;
+}
+
+// Return the socket id of `conf` that is passed to dpdk.
+fn dpdk_socket_id(conf: &RingConf) -> Result<i32> {
+    if conf.socket_id == RingConf::SOCKET_ID_ANY {
+        Ok(-1)
+    } else {
+        i32::try_from(conf.socket_id).map_err(|_| Error::service_err("invalid ring config"))
+    }
+}
+
+// Release the ring when the last `Ring` handle is dropped.
+//
+// A ring that is looked up from a secondary process belongs to the primary
+// process, so it is never freed. A ring that is initialized in a memzone is
+// not freed either, the memzone is released for another ring instead.
+struct RingOwner {
+    ptr: NonNull<ffi::rte_ring>,
+    owned: bool,
+    memzone: Option<Memzone>,
+}
+
+unsafe impl Send for RingOwner {}
+unsafe impl Sync for RingOwner {}
+
+impl Drop for RingOwner {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe {
+                ffi::rte_ring_free(self.ptr.as_ptr());
+            }
+        }
+        if let Some(memzone) = self.memzone.as_ref() {
+            memzone.release();
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Ring {
     ptr: NonNull<ffi::rte_ring>,
-    counter: Arc<()>,
+    owner: Arc<RingOwner>,
 }
 
 unsafe impl Send for Ring {}
 unsafe impl Sync for Ring {}
 
+// Return the number of slots allocated for a ring created with `conf`, as
+// `rte_ring_init` rounds the count up with `RingFlags::EXACT_SZ`.
+fn alloc_count(conf: &RingConf) -> Result<u32> {
+    if conf.flag.contains(RingFlags::EXACT_SZ) {
+        conf.count
+            .checked_add(1)
+            .and_then(u32::checked_next_power_of_two)
+            .ok_or(Error::service_err("invalid ring config"))
+    } else {
+        Ok(conf.count)
+    }
+}
+
 impl Ring {
-    pub(crate) fn try_create(name: String, conf: &RingConf) -> Result<Self> {
-        let err = Error::service_err("invalid ring config");
-        let socket_id = i32::try_from(conf.socket_id).map_err(|_| err)?;
+    /// Create a new ring named `name`.
+    ///
+    /// This function fails in a secondary process, as the ring memory must be
+    /// owned by the primary process.
+    pub fn try_create(name: String, conf: &RingConf) -> Result<Self> {
+        if ProcType::current() == ProcType::Secondary {
+            return Error::service_err("can not create ring in secondary process").to_err();
+        }
+
+        let socket_id = dpdk_socket_id(conf)?;
 
+        names::check_len(&name, names::RING_NAMESIZE, "ring name is too long")?;
         let cname = CString::new(name).map_err(|_| Error::service_err("invalid ring name"))?;
 
         let raw = unsafe {
@@ -106,37 +212,433 @@ impl Ring {
             )
         };
 
-        let ptr = NonNull::new(raw).ok_or_else(|| {
-            Error::ffi_err(unsafe { ffi::rte_errno_() }, "failed to allocate ring")
-        })?;
+        let ptr = check_ptr(raw, "failed to allocate ring")?;
+        Ok(Self {
+            ptr,
+            owner: Arc::new(RingOwner {
+                ptr,
+                owned: true,
+                memzone: None,
+            }),
+        })
+    }
+
+    /// Return the number of bytes of a ring created with `conf`.
+    ///
+    /// This is the minimum length of the memzone that is passed to
+    /// `Ring::create_in_memzone`.
+    ///
+    /// The memory of all the rings can be summed up before reserving the
+    /// hugepages, so that the application does not run out of memory halfway
+    /// through creating the rings.
+    pub fn memsize(conf: &RingConf) -> Result<usize> {
+        let memsize = unsafe { ffi::rte_ring_get_memsize(alloc_count(conf)?) };
+        if memsize < 0 {
+            return Error::ffi_err(memsize as i32, "invalid ring size").to_err();
+        }
+        Ok(memsize as usize)
+    }
+
+    /// Create a new ring named `name` in the memory of `memzone`.
+    ///
+    /// Unlike `Ring::try_create`, the ring memory is not allocated by dpdk, so
+    /// all the hugepage memory can be reserved when the application starts. The
+    /// memzone must hold at least `Ring::memsize(conf)` bytes, and it can only
+    /// hold one ring at a time. The ring keeps the memzone alive, and releases it
+    /// when the last handle is dropped.
+    ///
+    /// The ring is not registered by name, so it can not be looked up with
+    /// `Ring::try_lookup`, and `conf.socket_id` is ignored.
+    pub fn create_in_memzone(name: String, conf: &RingConf, memzone: &Memzone) -> Result<Self> {
+        if memzone.len() < Self::memsize(conf)? {
+            return Error::service_err("memzone is too small for the ring").to_err();
+        }
+
+        names::check_len(&name, names::RING_NAMESIZE, "ring name is too long")?;
+        let cname = CString::new(name).map_err(|_| Error::service_err("invalid ring name"))?;
+
+        if !memzone.acquire() {
+            return Error::service_err("memzone is already used by a ring").to_err();
+        }
+        let raw = memzone.addr() as *mut ffi::rte_ring;
+        let res = unsafe {
+            ffi::rte_ring_init(
+                raw,
+                cname.as_bytes_with_nul().as_ptr() as *const c_char,
+                conf.count,
+                conf.flag.bits(),
+            )
+        };
+        if let Err(err) = check_ret(res, "failed to initialize ring") {
+            memzone.release();
+            return Err(err);
+        }
+
+        let ptr = NonNull::new(raw).unwrap();
         Ok(Self {
             ptr,
-            counter: Arc::new(()),
+            owner: Arc::new(RingOwner {
+                ptr,
+                owned: false,
+                memzone: Some(memzone.clone()),
+            }),
         })
     }
 
+    /// Look up an existing ring named `name`.
+    ///
+    /// The returned handle does not own the ring, dropping it never frees the ring.
+    /// This is how a secondary process accesses a ring created by the primary process.
+    pub fn try_lookup(name: String) -> Result<Self> {
+        names::check_len(&name, names::RING_NAMESIZE, "ring name is too long")?;
+        let cname = CString::new(name).map_err(|_| Error::service_err("invalid ring name"))?;
+
+        let raw =
+            unsafe { ffi::rte_ring_lookup(cname.as_bytes_with_nul().as_ptr() as *const c_char) };
+
+        let ptr = check_ptr(raw, "failed to lookup ring")?;
+        Ok(Self {
+            ptr,
+            owner: Arc::new(RingOwner {
+                ptr,
+                owned: false,
+                memzone: None,
+            }),
+        })
+    }
+
+    /// Create a new ring named `name`, or look up the ring if it already exists.
+    ///
+    /// This is useful when the application is restarted while the ring is still
+    /// alive, e.g. it is created by another process. The existing ring is
+    /// returned as a non-owning handle like `Ring::try_lookup`, and this function
+    /// fails if its size or flags differ from `conf`. A secondary process always
+    /// looks up the ring.
+    pub fn get_or_create(name: String, conf: &RingConf) -> Result<Self> {
+        if ProcType::current() == ProcType::Primary {
+            match Self::try_create(name.clone(), conf) {
+                Err(err) if err.kind() == ErrorKind::FFIError(libc::EEXIST) => {}
+                res => return res,
+            }
+        }
+
+        let ring = Self::try_lookup(name)?;
+        if !ring.matches(conf) {
+            return Error::service_err("existing ring has a different config").to_err();
+        }
+        Ok(ring)
+    }
+
+    // Check whether the ring is created with `conf`.
+    fn matches(&self, conf: &RingConf) -> bool {
+        let raw = unsafe { self.ptr.as_ref() };
+        let capacity = if conf.flag.contains(RingFlags::EXACT_SZ) {
+            conf.count
+        } else {
+            conf.count.wrapping_sub(1)
+        };
+        raw.capacity == capacity && raw.flags as u32 == conf.flag.bits()
+    }
+
+    /// Return the socket that the ring memory is allocated on, which is the
+    /// actual socket of a ring created with `RingConf::SOCKET_ID_ANY`.
+    ///
+    /// Return -1 if the socket is unknown, i.e. for a looked-up ring that is
+    /// initialized in the memory of another process.
+    pub fn socket_id(&self) -> i32 {
+        if let Some(memzone) = self.owner.memzone.as_ref() {
+            return memzone.socket_id();
+        }
+        // `rte_ring_create` records the memzone that it reserves
+        unsafe {
+            self.ptr
+                .as_ref()
+                .memzone
+                .as_ref()
+                .map_or(-1, |memzone| memzone.socket_id)
+        }
+    }
+
+    /// Enqueue as many mbufs from `batch` as the ring can hold.
+    ///
+    /// The enqueued mbufs are removed from the front of `batch`, the remaining
+    /// mbufs are shifted to the front. Return the number of enqueued mbufs.
+    #[inline]
+    pub fn enqueue_burst<const N: usize>(&self, batch: &mut ArrayVec<Mbuf, N>) -> usize {
+        self.enqueue_burst_free_space(batch, std::ptr::null_mut())
+    }
+
+    // Enqueue like `enqueue_burst`, and write the free space of the ring after
+    // the enqueue to `free_space` if it is not null.
     #[inline]
-    pub unsafe fn enqueue_burst<const N: usize>(
+    fn enqueue_burst_free_space<const N: usize>(
         &self,
         batch: &mut ArrayVec<Mbuf, N>,
-    ) -> Result<()> {
-        let mbufs = std::mem::transmute::<*mut Mbuf, *mut *mut ffi::rte_mbuf>(batch.as_mut_ptr());
-
-        let res = ffi::rte_ring_enqueue_burst_(
-            self.ptr.as_ptr(),
-            mbufs as *const *mut c_void,
-            batch.len() as u32,
-            std::ptr::null_mut(),
-        );
-        if res != 0 {
-            return Error::ffi_err(res as i32, "fail to enqueue burst").to_err();
+        free_space: *mut u32,
+    ) -> usize {
+        assert!(N <= u32::MAX as usize);
+        unsafe {
+            let mbufs =
+                std::mem::transmute::<*mut Mbuf, *mut *mut ffi::rte_mbuf>(batch.as_mut_ptr());
+            let nb_enq = ffi::rte_ring_enqueue_burst_(
+                self.ptr.as_ptr(),
+                mbufs as *const *mut c_void,
+                batch.len() as u32,
+                free_space,
+            ) as usize;
+            let remaining = batch.len() - nb_enq;
+            std::ptr::copy(mbufs.add(nb_enq), mbufs, remaining);
+            batch.set_len(remaining);
+
+            nb_enq
+        }
+    }
+
+    /// Enqueue the mbufs from `batch` until `batch` is empty, retrying with
+    /// `policy` while the ring is full.
+    ///
+    /// The mbufs that are not enqueued after `policy.max_attempts` attempts are
+    /// left in `batch`. Return the number of enqueued mbufs.
+    pub fn enqueue_burst_with_retry<const N: usize>(
+        &self,
+        batch: &mut ArrayVec<Mbuf, N>,
+        policy: &RetryPolicy,
+    ) -> usize {
+        let mut nb_enq = 0;
+        policy.retry(|| {
+            nb_enq += self.enqueue_burst(batch);
+            batch.is_empty().then_some(())
+        });
+        nb_enq
+    }
+
+    /// Enqueue the mbufs from `batch`, applying `policy` to the mbufs that the
+    /// ring can not hold.
+    ///
+    /// `batch` is empty afterwards, except for the mbufs returned by
+    /// `EnqueuePolicy::ReturnToCaller` or left by an exhausted
+    /// `EnqueuePolicy::BlockSpin`. Return the number of enqueued mbufs.
+    #[inline]
+    pub fn enqueue_burst_with<const N: usize>(
+        &self,
+        batch: &mut ArrayVec<Mbuf, N>,
+        policy: EnqueuePolicy,
+    ) -> usize {
+        match policy {
+            EnqueuePolicy::DropAndFree => {
+                let nb_enq = self.enqueue_burst(batch);
+                Mempool::free_batch(batch);
+                nb_enq
+            }
+            EnqueuePolicy::ReturnToCaller => self.enqueue_burst(batch),
+            EnqueuePolicy::BlockSpin(max_attempts) => self.enqueue_burst_with_retry(
+                batch,
+                &RetryPolicy::new(max_attempts, Backoff::Pause(1)),
+            ),
+        }
+    }
+
+    /// Dequeue mbufs from the ring until `batch` is full or the ring is empty.
+    ///
+    /// The mbufs are appended to the caller-owned `batch`, so a poll loop can
+    /// reuse one batch for all the polls. An empty ring returns 0 without
+    /// touching `batch`, the cost of an empty poll is the load of the ring
+    /// indexes, see the `ring_poll` example. Return the number of dequeued
+    /// mbufs.
+    #[inline]
+    pub fn dequeue_burst<const N: usize>(&self, batch: &mut ArrayVec<Mbuf, N>) -> usize {
+        self.dequeue_at_most(batch, N)
+    }
+
+    // Dequeue at most `max` mbufs to the end of `batch`.
+    #[inline]
+    fn dequeue_at_most<const N: usize>(&self, batch: &mut ArrayVec<Mbuf, N>, max: usize) -> usize {
+        assert!(N <= u32::MAX as usize);
+        unsafe {
+            let mbufs = std::mem::transmute::<*mut Mbuf, *mut *mut c_void>(
+                batch.as_mut_ptr().add(batch.len()),
+            );
+            let nb_deq = ffi::rte_ring_dequeue_burst_(
+                self.ptr.as_ptr(),
+                mbufs,
+                (N - batch.len()).min(max) as u32,
+                std::ptr::null_mut(),
+            ) as usize;
+            batch.set_len(batch.len() + nb_deq);
+
+            nb_deq
+        }
+    }
+
+    /// Enqueue as many mbufs from `batch` as the ring can hold, the same as
+    /// `enqueue_burst` but for a `Vec` of any length.
+    ///
+    /// The enqueued mbufs are removed from the front of `batch`. This is meant
+    /// for the control paths where the burst size is configured at runtime,
+    /// the const-generic `enqueue_burst` keeps the batch on the stack and is
+    /// preferred on the hot path.
+    #[inline]
+    pub fn enqueue_burst_slice(&self, batch: &mut Vec<Mbuf>) -> usize {
+        assert!(batch.len() <= u32::MAX as usize);
+        self.enqueue_front(batch, batch.len())
+    }
+
+    /// Enqueue the mbufs from the front of `batch` that fit in the free space
+    /// of the ring, leaving the rest in `batch` for a later attempt.
+    ///
+    /// The free space is read by `free_count` before the enqueue. With
+    /// multiple producers, the space may be taken by another producer in
+    /// between, and fewer mbufs are enqueued. Return the number of enqueued
+    /// mbufs.
+    #[inline]
+    pub fn enqueue_available(&self, batch: &mut Vec<Mbuf>) -> usize {
+        let nb_free = self.free_count() as usize;
+        self.enqueue_front(batch, nb_free.min(batch.len()))
+    }
+
+    // Enqueue at most `max` mbufs from the front of `batch`.
+    #[inline]
+    fn enqueue_front(&self, batch: &mut Vec<Mbuf>, max: usize) -> usize {
+        unsafe {
+            let mbufs =
+                std::mem::transmute::<*mut Mbuf, *mut *mut ffi::rte_mbuf>(batch.as_mut_ptr());
+            let nb_enq = ffi::rte_ring_enqueue_burst_(
+                self.ptr.as_ptr(),
+                mbufs as *const *mut c_void,
+                max as u32,
+                std::ptr::null_mut(),
+            ) as usize;
+            let remaining = batch.len() - nb_enq;
+            std::ptr::copy(mbufs.add(nb_enq), mbufs, remaining);
+            batch.set_len(remaining);
+
+            nb_enq
+        }
+    }
+
+    /// Dequeue up to `max` mbufs from the ring and append them to `out`, the
+    /// same as `dequeue_burst` but with a runtime burst size.
+    ///
+    /// `out` grows to hold `max` more mbufs, which may allocate. The
+    /// const-generic `dequeue_burst` is preferred on the hot path. Return the
+    /// number of dequeued mbufs.
+    #[inline]
+    pub fn dequeue_burst_into(&self, out: &mut Vec<Mbuf>, max: usize) -> usize {
+        assert!(max <= u32::MAX as usize);
+        out.reserve(max);
+        unsafe {
+            let mbufs =
+                std::mem::transmute::<*mut Mbuf, *mut *mut c_void>(out.as_mut_ptr().add(out.len()));
+            let nb_deq = ffi::rte_ring_dequeue_burst_(
+                self.ptr.as_ptr(),
+                mbufs,
+                max as u32,
+                std::ptr::null_mut(),
+            ) as usize;
+            out.set_len(out.len() + nb_deq);
+
+            nb_deq
+        }
+    }
+
+    /// Move the mbufs from this ring to the `dst` ring through `batch`.
+    ///
+    /// The mbufs are dequeued to fill `batch`, and then enqueued to `dst` from
+    /// the front of `batch`. Return the number of forwarded mbufs, or an error
+    /// if `batch` is not empty and `dst` can hold none of its mbufs.
+    ///
+    /// The unforwarded mbufs stay in `batch` whether this succeeds or fails,
+    /// the caller either retries them or drops them, e.g. with
+    /// `Mempool::free_batch`.
+    #[inline]
+    pub fn forward_to<const N: usize>(
+        &self,
+        dst: &Ring,
+        batch: &mut ArrayVec<Mbuf, N>,
+    ) -> Result<usize> {
+        self.dequeue_burst(batch);
+        let nb_fwd = dst.enqueue_burst(batch);
+        if nb_fwd == 0 && !batch.is_empty() {
+            return Error::service_err("destination ring is full").to_err();
+        }
+        Ok(nb_fwd)
+    }
+
+    /// Create a new ring with `conf` and move all the mbufs of this ring to it,
+    /// e.g. to grow an under-provisioned ring, as a ring can not be resized in
+    /// place.
+    ///
+    /// The new ring gets a temporary name from `names::unique`, and the caller
+    /// swaps its handles of this ring for the returned one. The producers and
+    /// the consumers must be quiesced from the start of the migration until
+    /// they all use the new ring, otherwise the mbufs enqueued to this ring
+    /// during the migration are left behind, and the mbufs that the new ring
+    /// can no longer hold are dropped.
+    ///
+    /// The mbufs keep their order. This fails without moving any mbuf if the
+    /// new ring can not hold all of them.
+    pub fn migrate_to(&self, conf: &RingConf) -> Result<Ring> {
+        let ring = Ring::try_create(names::unique("migrated"), conf)?;
+        if ring.capacity() < self.count() {
+            return Error::service_err("new ring is too small for the mbufs").to_err();
+        }
+
+        let mut batch = ArrayVec::<Mbuf, 32>::new();
+        while self.dequeue_burst(&mut batch) > 0 {
+            ring.enqueue_burst_with(&mut batch, EnqueuePolicy::DropAndFree);
         }
-        Ok(())
+        Ok(ring)
     }
 
+    /// Return the number of the free entries of the ring.
     #[inline]
-    pub unsafe fn dequeue_burst<const N: usize>(&self) -> Result<&ArrayVec<Mbuf, N>> {
-        todo!()
+    pub fn free_count(&self) -> u32 {
+        unsafe { ffi::rte_ring_free_count_(self.ptr.as_ptr()) }
+    }
+
+    /// Return the number of the entries the ring can hold.
+    #[inline]
+    pub fn capacity(&self) -> u32 {
+        unsafe { self.ptr.as_ref().capacity }
+    }
+
+    /// Return the number of the entries in the ring.
+    ///
+    /// With concurrent producers or consumers, the count may be stale as soon
+    /// as it is returned.
+    #[inline]
+    pub fn count(&self) -> u32 {
+        self.capacity().saturating_sub(self.free_count())
+    }
+
+    /// Return the synchronization mode of the producer.
+    #[inline]
+    pub fn prod_sync_type(&self) -> SyncType {
+        // The `sync_type` field is at the same offset for all the head/tail
+        // structs in the union.
+        let raw = unsafe {
+            self.ptr
+                .as_ref()
+                .__bindgen_anon_1
+                .prod
+                .__bindgen_anon_1
+                .sync_type
+        };
+        SyncType::from_raw(raw as u32)
+    }
+
+    /// Return the synchronization mode of the consumer.
+    #[inline]
+    pub fn cons_sync_type(&self) -> SyncType {
+        let raw = unsafe {
+            self.ptr
+                .as_ref()
+                .__bindgen_anon_2
+                .cons
+                .__bindgen_anon_1
+                .sync_type
+        };
+        SyncType::from_raw(raw as u32)
     }
 
     pub fn as_ptr(&self) -> *const ffi::rte_ring {
@@ -144,10 +646,1627 @@ impl Ring {
     }
 }
 
-impl Drop for Ring {
-    fn drop(&mut self) {
+/// A ring of fixed-size `T` values, which are copied into and out of the ring.
+///
+/// Unlike `Ring`, which passes the pointers of mbufs, `ElemRing` is useful for
+/// passing small metadata records between lcores without the indirection.
+pub struct ElemRing<T> {
+    ptr: NonNull<ffi::rte_ring>,
+    owner: Arc<RingOwner>,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for ElemRing<T> {}
+unsafe impl<T: Send> Sync for ElemRing<T> {}
+
+impl<T> Clone for ElemRing<T> {
+    fn clone(&self) -> Self {
+        Self {
+            ptr: self.ptr,
+            owner: self.owner.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Copy> ElemRing<T> {
+    const ESIZE: u32 = std::mem::size_of::<T>() as u32;
+
+    /// Create a new ring named `name`, with `T` as the element type.
+    ///
+    /// The size of `T` must be a non-zero multiple of 4 bytes.
+    /// This function fails in a secondary process, as the ring memory must be
+    /// owned by the primary process.
+    pub fn try_create(name: String, conf: &RingConf) -> Result<Self> {
+        if ProcType::current() == ProcType::Secondary {
+            return Error::service_err("can not create ring in secondary process").to_err();
+        }
+        if Self::ESIZE == 0 || Self::ESIZE % 4 != 0 {
+            return Error::service_err("invalid ring element size").to_err();
+        }
+
+        let socket_id = dpdk_socket_id(conf)?;
+
+        names::check_len(&name, names::RING_NAMESIZE, "ring name is too long")?;
+        let cname = CString::new(name).map_err(|_| Error::service_err("invalid ring name"))?;
+
+        let raw = unsafe {
+            ffi::rte_ring_create_elem(
+                cname.as_bytes_with_nul().as_ptr() as *const c_char,
+                Self::ESIZE,
+                conf.count,
+                socket_id,
+                conf.flag.bits(),
+            )
+        };
+
+        let ptr = check_ptr(raw, "failed to allocate ring")?;
+        Ok(Self {
+            ptr,
+            owner: Arc::new(RingOwner {
+                ptr,
+                owned: true,
+                memzone: None,
+            }),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Return the number of bytes of a ring of `T` created with `conf`, see
+    /// `Ring::memsize`.
+    pub fn memsize(conf: &RingConf) -> Result<usize> {
+        if Self::ESIZE == 0 || Self::ESIZE % 4 != 0 {
+            return Error::service_err("invalid ring element size").to_err();
+        }
+
+        let memsize = unsafe { ffi::rte_ring_get_memsize_elem(Self::ESIZE, alloc_count(conf)?) };
+        if memsize < 0 {
+            return Error::ffi_err(memsize as i32, "invalid ring size").to_err();
+        }
+        Ok(memsize as usize)
+    }
+
+    /// Enqueue as many values from `batch` as the ring can hold.
+    ///
+    /// The enqueued values are removed from the front of `batch`, the remaining
+    /// values are shifted to the front. Return the number of enqueued values.
+    #[inline]
+    pub fn enqueue_burst<const N: usize>(&self, batch: &mut ArrayVec<T, N>) -> usize {
+        assert!(N <= u32::MAX as usize);
+        let nb_enq = unsafe {
+            ffi::rte_ring_enqueue_burst_elem_(
+                self.ptr.as_ptr(),
+                batch.as_ptr() as *const c_void,
+                Self::ESIZE,
+                batch.len() as u32,
+                std::ptr::null_mut(),
+            ) as usize
+        };
+        batch.drain(..nb_enq);
+
+        nb_enq
+    }
+
+    /// Dequeue values from the ring until `batch` is full or the ring is empty.
+    ///
+    /// Return the number of dequeued values.
+    #[inline]
+    pub fn dequeue_burst<const N: usize>(&self, batch: &mut ArrayVec<T, N>) -> usize {
+        assert!(N <= u32::MAX as usize);
         unsafe {
-            ffi::rte_ring_free(self.ptr.as_ptr());
+            let nb_deq = ffi::rte_ring_dequeue_burst_elem_(
+                self.ptr.as_ptr(),
+                batch.as_mut_ptr().add(batch.len()) as *mut c_void,
+                Self::ESIZE,
+                (N - batch.len()) as u32,
+                std::ptr::null_mut(),
+            ) as usize;
+            batch.set_len(batch.len() + nb_deq);
+
+            nb_deq
         }
     }
+
+    pub fn as_ptr(&self) -> *const ffi::rte_ring {
+        self.ptr.as_ptr()
+    }
+}
+
+/// A ring of mbufs that drops the oldest mbuf when it is full.
+///
+/// This is useful for lossy telemetry, e.g. sampling packets for analysis,
+/// where the newest packets are more valuable than the old ones.
+///
+/// Dropping the oldest mbuf and enqueuing the new one is not atomic, so a
+/// `SampleRing` must only be used by a single producer at a time. Consumers can
+/// dequeue from the ring concurrently.
+pub struct SampleRing {
+    ring: Ring,
+}
+
+impl SampleRing {
+    pub fn new(ring: Ring) -> Self {
+        Self { ring }
+    }
+
+    /// Enqueue `mbuf`, dropping the oldest mbufs in the ring to make room for it.
+    ///
+    /// Return the number of dropped mbufs.
+    pub fn enqueue(&self, mbuf: Mbuf) -> usize {
+        let mut batch = ArrayVec::<Mbuf, 1>::new();
+        batch.push(mbuf);
+
+        let mut nb_drop = 0;
+        while self.ring.enqueue_burst(&mut batch) == 0 {
+            // A concurrent consumer may empty the ring before we dequeue, in that
+            // case the enqueue is simply retried.
+            let mut oldest = ArrayVec::<Mbuf, 1>::new();
+            nb_drop += self.ring.dequeue_burst(&mut oldest);
+        }
+
+        nb_drop
+    }
+
+    /// Dequeue mbufs from the ring until `batch` is full or the ring is empty.
+    ///
+    /// Return the number of dequeued mbufs.
+    pub fn dequeue_burst<const N: usize>(&self, batch: &mut ArrayVec<Mbuf, N>) -> usize {
+        self.ring.dequeue_burst(batch)
+    }
+
+    /// Return the wrapped ring.
+    pub fn ring(&self) -> &Ring {
+        &self.ring
+    }
+}
+
+/// A ring that records the peak number of mbufs it held, for sizing the rings
+/// of a deployment under load.
+///
+/// The count after each enqueue is returned by the enqueue itself, so the
+/// overhead is a relaxed atomic max on the enqueue path. The dequeues never
+/// raise the count, they are passed through to the ring. The clones share the
+/// ring and the watermark.
+#[derive(Clone)]
+pub struct MonitoredRing {
+    ring: Ring,
+    high: Arc<AtomicU32>,
+}
+
+impl MonitoredRing {
+    pub fn new(ring: Ring) -> Self {
+        let high = Arc::new(AtomicU32::new(ring.count()));
+        Self { ring, high }
+    }
+
+    /// Enqueue as many mbufs from `batch` as the ring can hold, see
+    /// `Ring::enqueue_burst`.
+    #[inline]
+    pub fn enqueue_burst<const N: usize>(&self, batch: &mut ArrayVec<Mbuf, N>) -> usize {
+        let mut free_space = 0;
+        let nb_enq = self.ring.enqueue_burst_free_space(batch, &mut free_space);
+        if nb_enq > 0 {
+            let count = self.ring.capacity().saturating_sub(free_space);
+            self.high.fetch_max(count, Ordering::Relaxed);
+        }
+        nb_enq
+    }
+
+    /// Dequeue mbufs from the ring until `batch` is full or the ring is empty.
+    ///
+    /// Return the number of dequeued mbufs.
+    #[inline]
+    pub fn dequeue_burst<const N: usize>(&self, batch: &mut ArrayVec<Mbuf, N>) -> usize {
+        self.ring.dequeue_burst(batch)
+    }
+
+    /// Return the peak number of mbufs in the ring since it is wrapped, or
+    /// since the last `reset_watermark`.
+    pub fn high_watermark(&self) -> u32 {
+        self.high.load(Ordering::Relaxed)
+    }
+
+    /// Reset the watermark to the current number of mbufs in the ring, and
+    /// return the previous watermark.
+    pub fn reset_watermark(&self) -> u32 {
+        self.high.swap(self.ring.count(), Ordering::Relaxed)
+    }
+
+    /// Return the wrapped ring.
+    pub fn ring(&self) -> &Ring {
+        &self.ring
+    }
+}
+
+/// A ring that counts the enqueue attempts that fail to enqueue the whole
+/// batch, for spotting the rings that are a hotspot of their producers.
+///
+/// The ring retries a lost compare-and-swap internally, so an enqueue is only
+/// short when the producer finds no space as it claims its entries, which
+/// under multiple producers is mostly the space taken by the others. A high
+/// `contention_ratio` on a ring that is rarely full suggests the HTS or RTS
+/// sync modes, or sharding the producers with a `RingSharder`.
+///
+/// The counters are relaxed atomics on the enqueue path, the dequeues are
+/// passed through to the ring. The clones share the ring and the counters.
+#[derive(Clone)]
+pub struct ContendedRing {
+    ring: Ring,
+    attempts: Arc<AtomicU64>,
+    successes: Arc<AtomicU64>,
+}
+
+impl ContendedRing {
+    pub fn new(ring: Ring) -> Self {
+        Self {
+            ring,
+            attempts: Arc::new(AtomicU64::new(0)),
+            successes: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Enqueue as many mbufs from `batch` as the ring can hold, see
+    /// `Ring::enqueue_burst`. An empty batch is not counted.
+    #[inline]
+    pub fn enqueue_burst<const N: usize>(&self, batch: &mut ArrayVec<Mbuf, N>) -> usize {
+        if batch.is_empty() {
+            return 0;
+        }
+        let nb_enq = self.ring.enqueue_burst(batch);
+        self.attempts.fetch_add(1, Ordering::Relaxed);
+        if batch.is_empty() {
+            self.successes.fetch_add(1, Ordering::Relaxed);
+        }
+        nb_enq
+    }
+
+    /// Dequeue mbufs from the ring until `batch` is full or the ring is empty.
+    ///
+    /// Return the number of dequeued mbufs.
+    #[inline]
+    pub fn dequeue_burst<const N: usize>(&self, batch: &mut ArrayVec<Mbuf, N>) -> usize {
+        self.ring.dequeue_burst(batch)
+    }
+
+    /// Return the number of the enqueue attempts.
+    pub fn attempts(&self) -> u64 {
+        self.attempts.load(Ordering::Relaxed)
+    }
+
+    /// Return the number of the enqueue attempts that enqueued the whole batch.
+    pub fn successes(&self) -> u64 {
+        self.successes.load(Ordering::Relaxed)
+    }
+
+    /// Return the fraction of the enqueue attempts that left mbufs in the
+    /// batch, from 0.0 to 1.0, or 0.0 before the first attempt.
+    pub fn contention_ratio(&self) -> f64 {
+        let attempts = self.attempts();
+        if attempts == 0 {
+            return 0.0;
+        }
+        // the relaxed counters may be seen out of order
+        attempts.saturating_sub(self.successes()) as f64 / attempts as f64
+    }
+
+    /// Reset the counters to 0.
+    pub fn reset(&self) {
+        self.attempts.store(0, Ordering::Relaxed);
+        self.successes.store(0, Ordering::Relaxed);
+    }
+
+    /// Return the wrapped ring.
+    pub fn ring(&self) -> &Ring {
+        &self.ring
+    }
+}
+
+/// A bounded queue of mbufs with multiple priority levels, each backed by a
+/// ring. The priority 0 is the highest one.
+///
+/// By default, `dequeue_burst` serves the priorities strictly: a ring is only
+/// dequeued when all the rings of the higher priorities are empty, so a busy
+/// high priority can starve the lower ones. With `set_weights`, the rings are
+/// served by weighted round robin instead, each ring dequeues up to its weight
+/// of mbufs in turn, and the share of an empty ring goes to the next ones.
+///
+/// The producers enqueue concurrently if the rings allow multiple producers,
+/// while the dequeue keeps the round robin state, so there is a single
+/// consumer.
+pub struct PriorityQueue {
+    rings: Vec<Ring>,
+    weights: Option<Vec<u32>>,
+    // The ring being served by weighted round robin and its remaining credit.
+    cursor: usize,
+    credit: u32,
+}
+
+impl PriorityQueue {
+    /// Create a strict priority queue of `rings`, in the order of the
+    /// priority.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `rings` is empty.
+    pub fn new(rings: Vec<Ring>) -> Self {
+        assert!(!rings.is_empty(), "no priority ring");
+        Self {
+            rings,
+            weights: None,
+            cursor: 0,
+            credit: 0,
+        }
+    }
+
+    /// Serve the rings by weighted round robin with the weights `val`, or
+    /// strictly by priority if `val` is `None`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the number of the weights does not match the
+    /// number of the rings, or if a weight is 0.
+    pub fn set_weights(&mut self, val: Option<Vec<u32>>) {
+        if let Some(weights) = val.as_ref() {
+            assert!(
+                weights.len() == self.rings.len() && weights.iter().all(|w| *w > 0),
+                "invalid priority weights"
+            );
+            self.cursor = 0;
+            self.credit = weights[0];
+        }
+        self.weights = val;
+    }
+
+    pub fn weights(&self) -> Option<&[u32]> {
+        self.weights.as_deref()
+    }
+
+    /// Return the number of the priority levels.
+    pub fn nb_prios(&self) -> usize {
+        self.rings.len()
+    }
+
+    /// Return the ring of the priority `prio`.
+    pub fn ring(&self, prio: usize) -> &Ring {
+        &self.rings[prio]
+    }
+
+    /// Enqueue `mbuf` to the ring of the priority `prio`.
+    ///
+    /// The mbuf is returned if the ring is full.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `prio` is not less than `nb_prios`.
+    pub fn enqueue(&self, mbuf: Mbuf, prio: usize) -> std::result::Result<(), Mbuf> {
+        let mut batch = ArrayVec::<Mbuf, 1>::new();
+        batch.push(mbuf);
+        self.rings[prio].enqueue_burst(&mut batch);
+        match batch.pop() {
+            Some(mbuf) => Err(mbuf),
+            None => Ok(()),
+        }
+    }
+
+    /// Enqueue as many mbufs from `batch` to the ring of the priority `prio`
+    /// as the ring can hold, see `Ring::enqueue_burst`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `prio` is not less than `nb_prios`.
+    pub fn enqueue_burst<const N: usize>(
+        &self,
+        batch: &mut ArrayVec<Mbuf, N>,
+        prio: usize,
+    ) -> usize {
+        self.rings[prio].enqueue_burst(batch)
+    }
+
+    /// Dequeue mbufs from the rings until `batch` is full or all the rings are
+    /// empty, in the order given by the priorities or the weights.
+    ///
+    /// Return the number of dequeued mbufs.
+    pub fn dequeue_burst<const N: usize>(&mut self, batch: &mut ArrayVec<Mbuf, N>) -> usize {
+        let len = batch.len();
+        match self.weights.as_ref() {
+            None => {
+                for ring in self.rings.iter() {
+                    if batch.is_full() {
+                        break;
+                    }
+                    ring.dequeue_burst(batch);
+                }
+            }
+            Some(weights) => {
+                // The number of the rings that are found empty in a row.
+                let mut nb_empty = 0;
+                while !batch.is_full() && nb_empty < self.rings.len() {
+                    if self.credit == 0 {
+                        self.cursor = (self.cursor + 1) % self.rings.len();
+                        self.credit = weights[self.cursor];
+                    }
+                    let nb_deq =
+                        self.rings[self.cursor].dequeue_at_most(batch, self.credit as usize);
+                    if nb_deq == 0 {
+                        nb_empty += 1;
+                        self.credit = 0;
+                    } else {
+                        nb_empty = 0;
+                        self.credit -= nb_deq as u32;
+                    }
+                }
+            }
+        }
+        batch.len() - len
+    }
+}
+
+/// A distributor of mbufs to a set of rings by the flow, e.g. one ring per
+/// worker, so that the packets of a flow stay in order on a single worker.
+///
+/// The ring of an mbuf is its RSS hash modulo the number of the rings. The hash
+/// reported by the NIC is used if the mbuf carries one, or the Toeplitz hash
+/// of the packet is computed in software with the key of `set_rss_key`
+/// otherwise. The two hashes agree when the key is the RSS key of the port,
+/// which is `DEFAULT_RSS_KEY_40B` by default, so a flow is not split between
+/// the ports with and without the RSS offload.
+pub struct RingSharder {
+    rings: Vec<Ring>,
+    rss_key: Vec<u8>,
+}
+
+impl RingSharder {
+    /// Create a sharder over `rings`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `rings` is empty.
+    pub fn new(rings: Vec<Ring>) -> Self {
+        assert!(!rings.is_empty(), "no shard ring");
+        Self {
+            rings,
+            rss_key: DEFAULT_RSS_KEY_40B.to_vec(),
+        }
+    }
+
+    /// Set the RSS key of the software hash.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `val` is shorter than 40 bytes.
+    pub fn set_rss_key(&mut self, val: &[u8]) {
+        assert!(val.len() >= 40, "invalid rss key");
+        self.rss_key = val.to_vec();
+    }
+
+    pub fn rss_key(&self) -> &[u8] {
+        &self.rss_key
+    }
+
+    /// Return the number of the rings.
+    pub fn nb_shards(&self) -> usize {
+        self.rings.len()
+    }
+
+    /// Return the ring of the shard `shard`.
+    pub fn ring(&self, shard: usize) -> &Ring {
+        &self.rings[shard]
+    }
+
+    /// Return the shard of `mbuf`.
+    ///
+    /// The non-ip packets without an RSS hash are all assigned to the shard 0.
+    #[inline]
+    pub fn shard_of(&self, mbuf: &Mbuf) -> usize {
+        let hash = if mbuf.ol_flags().contains(OlFlags::RX_RSS_HASH) {
+            mbuf.rss()
+        } else {
+            rss::frame_hash(&self.rss_key, mbuf.data())
+        };
+        hash as usize % self.rings.len()
+    }
+
+    /// Enqueue `mbuf` to the ring of its shard.
+    ///
+    /// The mbuf is returned if the ring is full.
+    pub fn enqueue(&self, mbuf: Mbuf) -> std::result::Result<(), Mbuf> {
+        let shard = self.shard_of(&mbuf);
+        let mut batch = ArrayVec::<Mbuf, 1>::new();
+        batch.push(mbuf);
+        self.rings[shard].enqueue_burst(&mut batch);
+        match batch.pop() {
+            Some(mbuf) => Err(mbuf),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use rpkt::ipv4::IpProtocol;
+
+    use super::*;
+    use crate::error::ErrorKind;
+    use crate::proto::l4_frame;
+    use crate::*;
+
+    #[test]
+    fn forward_between_rings() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let src = Ring::try_create(
+                "src".to_string(),
+                &RingConf {
+                    count: 64,
+                    socket_id: 0,
+                    flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ,
+                },
+            )
+            .unwrap();
+            let dst = Ring::try_create(
+                "dst".to_string(),
+                &RingConf {
+                    count: 16,
+                    socket_id: 0,
+                    flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ | RingFlags::EXACT_SZ,
+                },
+            )
+            .unwrap();
+
+            let mut batch = ArrayVec::<_, 32>::new();
+            mp.fill_batch(&mut batch);
+            for (i, mbuf) in batch.iter_mut().enumerate() {
+                mbuf.extend_from_slice(&[i as u8; 1][..]);
+            }
+            assert_eq!(src.enqueue_burst(&mut batch), 32);
+            assert_eq!(batch.len(), 0);
+            assert_eq!(mp.nb_mbufs(), 128 - 32);
+
+            // Only 8 mbufs are moved across.
+            let mut fwd = ArrayVec::<_, 8>::new();
+            assert_eq!(src.forward_to(&dst, &mut fwd).unwrap(), 8);
+            assert!(fwd.is_empty());
+
+            let mut batch = ArrayVec::<_, 32>::new();
+            assert_eq!(dst.dequeue_burst(&mut batch), 8);
+            for (i, mbuf) in batch.iter().enumerate() {
+                assert_eq!(mbuf.data()[0], i as u8);
+            }
+            assert_eq!(dst.enqueue_burst(&mut batch), 8);
+
+            // The destination ring can only accept 8 more mbufs, the
+            // other 16 are left in the batch.
+            let mut fwd = ArrayVec::<_, 32>::new();
+            assert_eq!(src.forward_to(&dst, &mut fwd).unwrap(), 8);
+            assert_eq!(fwd.len(), 16);
+            assert_eq!(fwd[0].data()[0], 16);
+
+            // Nothing can be forwarded to the full ring, the mbufs stay in
+            // the batch.
+            assert!(src.forward_to(&dst, &mut fwd).is_err());
+            assert_eq!(fwd.len(), 16);
+            Mempool::free_batch(&mut fwd);
+            assert_eq!(mp.nb_mbufs(), 128 - 16);
+            assert_eq!(src.dequeue_burst(&mut batch), 0);
+
+            assert_eq!(dst.dequeue_burst(&mut batch), 16);
+            Mempool::free_batch(&mut batch);
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn runtime_sized_bursts() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let ring = Ring::try_create(
+                "runtime_burst".to_string(),
+                &RingConf {
+                    count: 64,
+                    socket_id: 0,
+                    flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ | RingFlags::EXACT_SZ,
+                },
+            )
+            .unwrap();
+
+            let mut batch: Vec<Mbuf> = (0..100)
+                .map(|i| {
+                    let mut mbuf = mp.try_alloc().unwrap();
+                    mbuf.extend_from_slice(&[i as u8; 1][..]);
+                    mbuf
+                })
+                .collect();
+            // the ring is full after 64 mbufs, the rest stay in the batch
+            assert_eq!(ring.enqueue_burst_slice(&mut batch), 64);
+            assert_eq!(batch.len(), 36);
+            assert_eq!(batch[0].data()[0], 64);
+            assert_eq!(ring.enqueue_burst_slice(&mut batch), 0);
+            drop(batch);
+
+            let mut out = Vec::new();
+            let mut nb_deq = 0;
+            for burst_size in [1, 7, 0, 20, 40] {
+                let n = ring.dequeue_burst_into(&mut out, burst_size);
+                assert_eq!(n, burst_size.min(64 - nb_deq));
+                nb_deq += n;
+                assert_eq!(out.len(), nb_deq);
+            }
+            assert_eq!(nb_deq, 64);
+            for (i, mbuf) in out.iter().enumerate() {
+                assert_eq!(mbuf.data()[0], i as u8);
+            }
+            assert_eq!(ring.dequeue_burst_into(&mut out, 8), 0);
+
+            drop(out);
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn enqueue_available_space() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let ring = Ring::try_create(
+                "enqueue_available".to_string(),
+                &RingConf {
+                    count: 32,
+                    socket_id: 0,
+                    flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ | RingFlags::EXACT_SZ,
+                },
+            )
+            .unwrap();
+            assert_eq!(ring.free_count(), 32);
+
+            let mut batch: Vec<Mbuf> = (0..50)
+                .map(|i| {
+                    let mut mbuf = mp.try_alloc().unwrap();
+                    mbuf.extend_from_slice(&[i as u8; 1][..]);
+                    mbuf
+                })
+                .collect();
+            let mut out = Vec::new();
+            assert_eq!(ring.enqueue_available(&mut batch), 32);
+            ring.dequeue_burst_into(&mut out, 12);
+            assert_eq!(ring.free_count(), 12);
+
+            // only the free space is filled, the rest stay in the batch
+            assert_eq!(ring.enqueue_available(&mut batch), 12);
+            assert_eq!(ring.free_count(), 0);
+            assert_eq!(batch.len(), 6);
+            assert_eq!(batch[0].data()[0], 44);
+            assert_eq!(ring.enqueue_available(&mut batch), 0);
+            assert_eq!(batch.len(), 6);
+
+            ring.dequeue_burst_into(&mut out, 64);
+            assert_eq!(ring.enqueue_available(&mut batch), 6);
+            assert!(batch.is_empty());
+            ring.dequeue_burst_into(&mut out, 64);
+
+            // no mbuf is lost or reordered
+            assert_eq!(out.len(), 50);
+            for (i, mbuf) in out.iter().enumerate() {
+                assert_eq!(mbuf.data()[0], i as u8);
+            }
+            drop(out);
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn ring_in_memzone() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let conf = RingConf {
+                count: 64,
+                socket_id: 0,
+                flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ,
+            };
+            let memsize = Ring::memsize(&conf).unwrap();
+
+            // the memzone is too small
+            let small = Memzone::try_reserve("small_mz".to_string(), memsize - 64, 0).unwrap();
+            assert!(Ring::create_in_memzone("mz_ring".to_string(), &conf, &small).is_err());
+            // the count is not a power of 2
+            let mut bad_conf = conf.clone();
+            bad_conf.count = 63;
+            assert!(Ring::memsize(&bad_conf).is_err());
+
+            let memzone = Memzone::try_reserve("ring_mz".to_string(), memsize, 0).unwrap();
+            assert!(memzone.len() >= memsize);
+            let ring = Ring::create_in_memzone("mz_ring".to_string(), &conf, &memzone).unwrap();
+            assert_eq!(ring.as_ptr() as *mut c_void, memzone.addr());
+            // the memzone only holds one ring at a time
+            assert!(Ring::create_in_memzone("mz_ring2".to_string(), &conf, &memzone).is_err());
+
+            let mut batch = ArrayVec::<_, 32>::new();
+            mp.fill_batch(&mut batch);
+            for (i, mbuf) in batch.iter_mut().enumerate() {
+                mbuf.extend_from_slice(&[i as u8; 1][..]);
+            }
+            assert_eq!(ring.enqueue_burst(&mut batch), 32);
+            assert_eq!(mp.nb_mbufs(), 128 - 32);
+            assert_eq!(ring.dequeue_burst(&mut batch), 32);
+            for (i, mbuf) in batch.iter().enumerate() {
+                assert_eq!(mbuf.data()[0], i as u8);
+            }
+            Mempool::free_batch(&mut batch);
+            assert_eq!(mp.nb_mbufs(), 128);
+
+            // the ring keeps the memzone alive
+            drop(memzone);
+            assert!(Memzone::try_lookup("ring_mz".to_string()).is_ok());
+            drop(ring);
+            assert!(Memzone::try_lookup("ring_mz".to_string()).is_err());
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn memsize_matches_allocation() {
+        DpdkOption::new().init().unwrap();
+
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        struct Desc {
+            a: u64,
+            b: u32,
+        }
+
+        let conf = RingConf {
+            count: 256,
+            socket_id: 0,
+            flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ,
+        };
+        let memsize = Ring::memsize(&conf).unwrap();
+        let ring = Ring::try_create("msz".to_string(), &conf).unwrap();
+        // `rte_ring_create` reserves a memzone with the "RG_" prefix
+        let memzone = Memzone::try_lookup("RG_msz".to_string()).unwrap();
+        assert_eq!(memzone.len(), memsize);
+        drop(ring);
+
+        // the count is rounded up to 128
+        let mut exact_conf = conf.clone();
+        exact_conf.count = 100;
+        exact_conf.flag |= RingFlags::EXACT_SZ;
+        let elem_memsize = ElemRing::<Desc>::memsize(&exact_conf).unwrap();
+        assert!(elem_memsize > ElemRing::<u64>::memsize(&exact_conf).unwrap());
+        assert!(elem_memsize < memsize);
+        let ring = ElemRing::<Desc>::try_create("msz_elem".to_string(), &exact_conf).unwrap();
+        let memzone = Memzone::try_lookup("RG_msz_elem".to_string()).unwrap();
+        assert_eq!(memzone.len(), elem_memsize);
+        drop(ring);
+
+        // the element size is not a multiple of 4
+        assert!(ElemRing::<[u8; 6]>::memsize(&conf).is_err());
+        exact_conf.flag = RingFlags::empty();
+        assert!(ElemRing::<Desc>::memsize(&exact_conf).is_err());
+    }
+
+    #[test]
+    fn allocated_socket_id() {
+        DpdkOption::new().init().unwrap();
+
+        let mut conf = RingConf {
+            count: 64,
+            socket_id: RingConf::SOCKET_ID_ANY,
+            flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ,
+        };
+        let ring = Ring::try_create("any_socket".to_string(), &conf).unwrap();
+        let socket_id = ring.socket_id();
+        assert!(socket_id >= 0);
+        let memzone = Memzone::try_lookup("RG_any_socket".to_string()).unwrap();
+        assert_eq!(memzone.socket_id(), socket_id);
+        // the looked-up handles see the same socket
+        let lookup = Ring::try_lookup("any_socket".to_string()).unwrap();
+        assert_eq!(lookup.socket_id(), socket_id);
+        drop(lookup);
+        drop(ring);
+
+        conf.socket_id = socket_id as u32;
+        let ring = Ring::try_create("local_socket".to_string(), &conf).unwrap();
+        assert_eq!(ring.socket_id(), socket_id);
+        drop(ring);
+
+        let memzone = Memzone::try_reserve(
+            "ring_socket_mz".to_string(),
+            Ring::memsize(&conf).unwrap(),
+            socket_id as u32,
+        )
+        .unwrap();
+        let ring = Ring::create_in_memzone("mz_socket".to_string(), &conf, &memzone).unwrap();
+        assert_eq!(ring.socket_id(), socket_id);
+    }
+
+    #[test]
+    fn migrate_to_larger_ring() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+            let ring = Ring::try_create(
+                "small".to_string(),
+                &RingConf {
+                    count: 16,
+                    socket_id: 0,
+                    flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ,
+                },
+            )
+            .unwrap();
+
+            // the ring is nearly full, with 14 of its 15 entries
+            let mut batch = ArrayVec::<_, 32>::new();
+            for i in 0..14u8 {
+                let mut mbuf = mp.try_alloc().unwrap();
+                mbuf.extend_from_slice(&[i; 1][..]);
+                batch.push(mbuf);
+            }
+            assert_eq!(ring.enqueue_burst(&mut batch), 14);
+
+            // a ring that is too small gets nothing
+            let mut conf = RingConf {
+                count: 8,
+                socket_id: 0,
+                flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ,
+            };
+            assert!(ring.migrate_to(&conf).is_err());
+            assert_eq!(ring.count(), 14);
+
+            conf.count = 64;
+            let migrated = ring.migrate_to(&conf).unwrap();
+            assert_eq!(ring.count(), 0);
+            assert_eq!(migrated.count(), 14);
+            assert_eq!(migrated.capacity(), 63);
+            assert_eq!(migrated.dequeue_burst(&mut batch), 14);
+            for (i, mbuf) in batch.iter().enumerate() {
+                assert_eq!(mbuf.data()[0], i as u8);
+            }
+
+            // the new ring takes the traffic beyond the old capacity
+            batch.extend((0..18).map(|_| mp.try_alloc().unwrap()));
+            assert_eq!(migrated.enqueue_burst(&mut batch), 32);
+            drop(ring);
+            while migrated.dequeue_burst(&mut batch) > 0 {
+                Mempool::free_batch(&mut batch);
+            }
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn contended_ring_ratio() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+            let ring = Ring::try_create(
+                "contended".to_string(),
+                &RingConf {
+                    count: 16,
+                    socket_id: 0,
+                    flag: RingFlags::SC_DEQ | RingFlags::EXACT_SZ,
+                },
+            )
+            .unwrap();
+            let contended = ContendedRing::new(ring);
+            assert_eq!(contended.contention_ratio(), 0.0);
+
+            // 4 producers hammer the ring with bursts of 8 mbufs
+            const NB_PER_PRODUCER: usize = 8000;
+            std::thread::scope(|s| {
+                for _ in 0..4 {
+                    let (contended, mp) = (contended.clone(), &mp);
+                    s.spawn(move || {
+                        let mut batch = ArrayVec::<_, 8>::new();
+                        let mut nb_alloc = 0;
+                        while nb_alloc < NB_PER_PRODUCER || !batch.is_empty() {
+                            while nb_alloc < NB_PER_PRODUCER && !batch.is_full() {
+                                batch.push(mp.try_alloc().unwrap());
+                                nb_alloc += 1;
+                            }
+                            contended.enqueue_burst(&mut batch);
+                        }
+                    });
+                }
+
+                let mut nb_deq = 0;
+                let mut batch = ArrayVec::<_, 4>::new();
+                while nb_deq < 4 * NB_PER_PRODUCER {
+                    nb_deq += contended.dequeue_burst(&mut batch);
+                    Mempool::free_batch(&mut batch);
+                }
+            });
+
+            assert!(contended.attempts() >= 4 * NB_PER_PRODUCER as u64 / 8);
+            assert!(contended.successes() < contended.attempts());
+            let ratio = contended.contention_ratio();
+            assert!(ratio > 0.0 && ratio <= 1.0);
+            assert_eq!(mp.nb_mbufs(), 128);
+
+            contended.reset();
+            assert_eq!(contended.attempts(), 0);
+            assert_eq!(contended.contention_ratio(), 0.0);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn enqueue_policies_on_full_ring() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+            let ring = Ring::try_create(
+                "full".to_string(),
+                &RingConf {
+                    count: 16,
+                    socket_id: 0,
+                    flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ | RingFlags::EXACT_SZ,
+                },
+            )
+            .unwrap();
+            let alloc_batch = || {
+                let mut batch = ArrayVec::<_, 32>::new();
+                mp.fill_batch(&mut batch);
+                for (i, mbuf) in batch.iter_mut().enumerate() {
+                    mbuf.extend_from_slice(&[i as u8; 1][..]);
+                }
+                batch
+            };
+
+            // the mbufs that do not fit are returned in order
+            let mut batch = alloc_batch();
+            let nb_enq = ring.enqueue_burst_with(&mut batch, EnqueuePolicy::ReturnToCaller);
+            assert_eq!(nb_enq, 16);
+            assert_eq!(batch.len(), 16);
+            for (i, mbuf) in batch.iter().enumerate() {
+                assert_eq!(mbuf.data()[0], i as u8 + 16);
+            }
+            Mempool::free_batch(&mut batch);
+            assert_eq!(mp.nb_mbufs(), 128 - 16);
+
+            // the mbufs that do not fit are freed
+            let mut batch = alloc_batch();
+            let nb_enq = ring.enqueue_burst_with(&mut batch, EnqueuePolicy::DropAndFree);
+            assert_eq!(nb_enq, 0);
+            assert!(batch.is_empty());
+            assert_eq!(mp.nb_mbufs(), 128 - 16);
+
+            // the spin gives up without a consumer
+            let mut batch = alloc_batch();
+            let nb_enq = ring.enqueue_burst_with(&mut batch, EnqueuePolicy::BlockSpin(10));
+            assert_eq!(nb_enq, 0);
+            assert_eq!(batch.len(), 32);
+
+            // the spin waits for a consumer thread to drain the ring
+            std::thread::scope(|s| {
+                s.spawn(|| {
+                    let mut nb_deq = 0;
+                    let mut drained = ArrayVec::<_, 8>::new();
+                    while nb_deq < 48 {
+                        nb_deq += ring.dequeue_burst(&mut drained);
+                        Mempool::free_batch(&mut drained);
+                    }
+                });
+                let policy = EnqueuePolicy::BlockSpin(usize::MAX);
+                assert_eq!(ring.enqueue_burst_with(&mut batch, policy), 32);
+            });
+            assert!(batch.is_empty());
+            assert_eq!(ring.count(), 0);
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn dequeue_empty_ring_reuses_batch() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+            let ring = Ring::try_create(
+                "polled".to_string(),
+                &RingConf {
+                    count: 64,
+                    socket_id: 0,
+                    flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ,
+                },
+            )
+            .unwrap();
+
+            let mut batch = ArrayVec::<_, 32>::new();
+            let buf_addr = batch.as_ptr();
+            for _ in 0..1000 {
+                assert_eq!(ring.dequeue_burst(&mut batch), 0);
+            }
+            assert!(batch.is_empty());
+
+            // the leftover mbufs of a poll are kept at the front
+            batch.push(mp.try_alloc().unwrap());
+            assert_eq!(ring.dequeue_burst(&mut batch), 0);
+            assert_eq!(batch.len(), 1);
+
+            let mut input = ArrayVec::<_, 32>::new();
+            input.extend((0..10).map(|_| mp.try_alloc().unwrap()));
+            assert_eq!(ring.enqueue_burst(&mut input), 10);
+            assert_eq!(ring.dequeue_burst(&mut batch), 10);
+            assert_eq!(batch.len(), 11);
+            assert_eq!(batch.as_ptr(), buf_addr);
+            Mempool::free_batch(&mut batch);
+            assert_eq!(ring.dequeue_burst(&mut batch), 0);
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn monitor_peak_occupancy() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+            let ring = Ring::try_create(
+                "monitored".to_string(),
+                &RingConf {
+                    count: 64,
+                    socket_id: 0,
+                    flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ,
+                },
+            )
+            .unwrap();
+            assert_eq!(ring.capacity(), 63);
+            let monitored = MonitoredRing::new(ring);
+            assert_eq!(monitored.high_watermark(), 0);
+
+            // the ring peaks at 40 mbufs
+            let mut batch = ArrayVec::<_, 32>::new();
+            for _ in 0..2 {
+                batch.extend((0..20).map(|_| mp.try_alloc().unwrap()));
+                assert_eq!(monitored.enqueue_burst(&mut batch), 20);
+            }
+            assert_eq!(monitored.ring().count(), 40);
+            assert_eq!(monitored.dequeue_burst(&mut batch), 30);
+            Mempool::free_batch(&mut batch);
+            batch.extend((0..5).map(|_| mp.try_alloc().unwrap()));
+            assert_eq!(monitored.enqueue_burst(&mut batch), 5);
+            assert_eq!(monitored.ring().count(), 15);
+            assert_eq!(monitored.high_watermark(), 40);
+
+            // the clones share the watermark
+            let clone = monitored.clone();
+            assert_eq!(clone.reset_watermark(), 40);
+            assert_eq!(monitored.high_watermark(), 15);
+            assert_eq!(monitored.dequeue_burst(&mut batch), 15);
+            Mempool::free_batch(&mut batch);
+            assert_eq!(monitored.high_watermark(), 15);
+
+            // a full ring
+            for _ in 0..2 {
+                mp.fill_batch(&mut batch);
+                monitored.enqueue_burst(&mut batch);
+            }
+            assert_eq!(clone.high_watermark(), 63);
+            Mempool::free_batch(&mut batch);
+            while monitored.dequeue_burst(&mut batch) > 0 {
+                Mempool::free_batch(&mut batch);
+            }
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn enqueue_with_retry() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let ring = Ring::try_create(
+                "wtf".to_string(),
+                &RingConf {
+                    count: 16,
+                    socket_id: 0,
+                    flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ | RingFlags::EXACT_SZ,
+                },
+            )
+            .unwrap();
+
+            let mut batch = ArrayVec::<_, 32>::new();
+            mp.fill_batch(&mut batch);
+            let policy = RetryPolicy::new(3, Backoff::Yield);
+            assert_eq!(ring.enqueue_burst_with_retry(&mut batch, &policy), 16);
+            assert_eq!(batch.len(), 16);
+
+            // a consumer thread drains the ring while the producer retries
+            std::thread::scope(|s| {
+                s.spawn(|| {
+                    let mut drained = ArrayVec::<_, 32>::new();
+                    while drained.len() < 32 {
+                        ring.dequeue_burst(&mut drained);
+                    }
+                    Mempool::free_batch(&mut drained);
+                });
+                let policy = RetryPolicy::new(usize::MAX, Backoff::Pause(16));
+                assert_eq!(ring.enqueue_burst_with_retry(&mut batch, &policy), 16);
+            });
+            assert_eq!(batch.len(), 0);
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    // The child half of `lookup_ring_from_secondary`, it does nothing unless
+    // it is spawned by the primary process.
+    #[test]
+    fn secondary_ring_lookup() {
+        if std::env::var("RPKT_DPDK_SECONDARY").is_err() {
+            return;
+        }
+
+        let mut option = DpdkOption::new();
+        option.set_proc_type(ProcType::Secondary);
+        option.init().unwrap();
+        assert_eq!(ProcType::current(), ProcType::Secondary);
+
+        let err = Ring::try_create(
+            "wtf".to_string(),
+            &RingConf {
+                count: 64,
+                socket_id: 0,
+                flag: RingFlags::empty(),
+            },
+        );
+        assert_eq!(err.is_err(), true);
+
+        let ring = Ring::try_lookup("shared".to_string()).unwrap();
+        let mut batch = ArrayVec::<_, 32>::new();
+        assert_eq!(ring.dequeue_burst(&mut batch), 16);
+        for (i, mbuf) in batch.iter().enumerate() {
+            assert_eq!(mbuf.data()[0], i as u8);
+        }
+        assert_eq!(ring.enqueue_burst(&mut batch), 16);
+        // dropping the looked-up ring must not free it
+        drop(ring);
+        let ring = Ring::try_lookup("shared".to_string()).unwrap();
+        let mut batch = ArrayVec::<_, 32>::new();
+        assert_eq!(ring.dequeue_burst(&mut batch), 16);
+        assert_eq!(ring.enqueue_burst(&mut batch), 16);
+    }
+
+    #[test]
+    fn lookup_ring_from_secondary() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let ring = Ring::try_create(
+                "shared".to_string(),
+                &RingConf {
+                    count: 64,
+                    socket_id: 0,
+                    flag: RingFlags::empty(),
+                },
+            )
+            .unwrap();
+
+            let mut batch = ArrayVec::<_, 16>::new();
+            mp.fill_batch(&mut batch);
+            for (i, mbuf) in batch.iter_mut().enumerate() {
+                mbuf.extend_from_slice(&[i as u8; 1][..]);
+            }
+            assert_eq!(ring.enqueue_burst(&mut batch), 16);
+
+            let status = std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "ring::tests::secondary_ring_lookup"])
+                .env("RPKT_DPDK_SECONDARY", "1")
+                .status()
+                .unwrap();
+            assert_eq!(status.success(), true);
+
+            // the mbufs are handed back by the secondary process
+            let mut batch = ArrayVec::<_, 32>::new();
+            assert_eq!(ring.dequeue_burst(&mut batch), 16);
+            for (i, mbuf) in batch.iter().enumerate() {
+                assert_eq!(mbuf.data()[0], i as u8);
+            }
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn ring_sync_types() {
+        DpdkOption::new().init().unwrap();
+
+        let cases = [
+            (
+                RingFlags::empty(),
+                SyncType::MultiThread,
+                SyncType::MultiThread,
+            ),
+            (
+                RingFlags::SP_ENQ | RingFlags::SC_DEQ,
+                SyncType::SingleThread,
+                SyncType::SingleThread,
+            ),
+            (
+                RingFlags::MP_RTS_ENQ | RingFlags::MC_RTS_DEQ,
+                SyncType::Rts,
+                SyncType::Rts,
+            ),
+            (
+                RingFlags::MP_HTS_ENQ | RingFlags::MC_HTS_DEQ,
+                SyncType::Hts,
+                SyncType::Hts,
+            ),
+            (
+                RingFlags::SP_ENQ | RingFlags::MC_HTS_DEQ,
+                SyncType::SingleThread,
+                SyncType::Hts,
+            ),
+        ];
+
+        for (i, (flag, prod, cons)) in cases.into_iter().enumerate() {
+            let ring = Ring::try_create(
+                format!("sync{i}"),
+                &RingConf {
+                    count: 64,
+                    socket_id: 0,
+                    flag,
+                },
+            )
+            .unwrap();
+            assert_eq!(ring.prod_sync_type(), prod);
+            assert_eq!(ring.cons_sync_type(), cons);
+        }
+    }
+
+    #[test]
+    fn elem_ring_copies_values() {
+        DpdkOption::new().init().unwrap();
+
+        #[repr(C)]
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        struct Desc {
+            a: u64,
+            b: u64,
+        }
+
+        let ring = ElemRing::<Desc>::try_create(
+            "desc".to_string(),
+            &RingConf {
+                count: 64,
+                socket_id: 0,
+                flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ,
+            },
+        )
+        .unwrap();
+
+        let mut batch = ArrayVec::<_, 128>::new();
+        for i in 0..100 {
+            batch.push(Desc { a: i, b: !i });
+        }
+        // the usable space of the ring is 63
+        assert_eq!(ring.enqueue_burst(&mut batch), 63);
+        assert_eq!(batch.len(), 37);
+        assert_eq!(batch[0], Desc { a: 63, b: !63 });
+
+        let mut out = ArrayVec::<_, 32>::new();
+        assert_eq!(ring.dequeue_burst(&mut out), 32);
+        for (i, desc) in (0..).zip(out.iter()) {
+            assert_eq!(*desc, Desc { a: i, b: !i });
+        }
+        out.clear();
+        assert_eq!(ring.dequeue_burst(&mut out), 31);
+        assert_eq!(out[30], Desc { a: 62, b: !62 });
+        assert_eq!(ring.dequeue_burst(&mut out), 0);
+
+        // the element size must be a multiple of 4 bytes
+        assert!(ElemRing::<[u8; 6]>::try_create(
+            "bad".to_string(),
+            &RingConf {
+                count: 64,
+                socket_id: 0,
+                flag: RingFlags::empty(),
+            },
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn duplicate_ring_name_sets_errno() {
+        DpdkOption::new().init().unwrap();
+
+        let conf = RingConf {
+            count: 64,
+            socket_id: 0,
+            flag: RingFlags::empty(),
+        };
+        let _ring = Ring::try_create("dup".to_string(), &conf).unwrap();
+        let err = Ring::try_create("dup".to_string(), &conf).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::FFIError(libc::EEXIST));
+        let err = ElemRing::<u64>::try_create("dup".to_string(), &conf)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::FFIError(libc::EEXIST));
+    }
+
+    #[test]
+    fn get_or_create_existing_ring() {
+        DpdkOption::new().init().unwrap();
+
+        let mut conf = RingConf {
+            count: 64,
+            socket_id: 0,
+            flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ,
+        };
+        let ring = Ring::get_or_create("goc".to_string(), &conf).unwrap();
+        let existing = Ring::get_or_create("goc".to_string(), &conf).unwrap();
+        assert_eq!(existing.as_ptr(), ring.as_ptr());
+        assert_eq!(existing.prod_sync_type(), SyncType::SingleThread);
+
+        // the existing ring is not freed by the non-owning handle
+        drop(existing);
+        assert!(Ring::try_lookup("goc".to_string()).is_ok());
+
+        conf.count = 128;
+        assert!(Ring::get_or_create("goc".to_string(), &conf).is_err());
+        conf.count = 64;
+        conf.flag = RingFlags::empty();
+        assert!(Ring::get_or_create("goc".to_string(), &conf).is_err());
+    }
+
+    #[test]
+    fn sample_ring_keeps_newest() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let ring = Ring::try_create(
+                "sample".to_string(),
+                &RingConf {
+                    count: 16,
+                    socket_id: 0,
+                    flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ | RingFlags::EXACT_SZ,
+                },
+            )
+            .unwrap();
+            let sample_ring = SampleRing::new(ring);
+
+            let mut nb_drop = 0;
+            for i in 0..40 {
+                let mut mbuf = mp.try_alloc().unwrap();
+                mbuf.extend_from_slice(&[i as u8; 1][..]);
+                nb_drop += sample_ring.enqueue(mbuf);
+            }
+            assert_eq!(nb_drop, 24);
+            // the dropped mbufs are returned to the mempool
+            assert_eq!(mp.nb_mbufs(), 128 - 16);
+
+            let mut batch = ArrayVec::<_, 32>::new();
+            assert_eq!(sample_ring.dequeue_burst(&mut batch), 16);
+            for (i, mbuf) in (24..).zip(batch.iter()) {
+                assert_eq!(mbuf.data()[0], i as u8);
+            }
+            Mempool::free_batch(&mut batch);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    fn priority_rings(name: &str, nb_prios: usize) -> Vec<Ring> {
+        (0..nb_prios)
+            .map(|prio| {
+                Ring::try_create(
+                    format!("{}{}", name, prio),
+                    &RingConf {
+                        count: 64,
+                        socket_id: 0,
+                        flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ,
+                    },
+                )
+                .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn priority_queue_strict_order() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut queue = PriorityQueue::new(priority_rings("strict", 3));
+            for (i, prio) in [2, 0, 1, 2, 1, 0, 2, 0].into_iter().enumerate() {
+                let mut mbuf = mp.try_alloc().unwrap();
+                mbuf.extend_from_slice(&[prio as u8, i as u8][..]);
+                queue.enqueue(mbuf, prio).unwrap();
+            }
+
+            let mut batch = ArrayVec::<_, 4>::new();
+            assert_eq!(queue.dequeue_burst(&mut batch), 4);
+            let order: Vec<_> = batch.iter().map(|mbuf| mbuf.data()[1]).collect();
+            assert_eq!(order, [1, 5, 7, 2]);
+            Mempool::free_batch(&mut batch);
+
+            // the empty rings fall through to the lower priorities
+            let mut batch = ArrayVec::<_, 32>::new();
+            assert_eq!(queue.dequeue_burst(&mut batch), 4);
+            let order: Vec<_> = batch.iter().map(|mbuf| mbuf.data()[1]).collect();
+            assert_eq!(order, [4, 0, 3, 6]);
+            assert_eq!(queue.dequeue_burst(&mut batch), 0);
+            Mempool::free_batch(&mut batch);
+
+            // a full ring returns the mbuf
+            let rings = priority_rings("strict_full", 1);
+            let queue = PriorityQueue::new(rings);
+            for _ in 0..63 {
+                queue.enqueue(mp.try_alloc().unwrap(), 0).unwrap();
+            }
+            assert!(queue.enqueue(mp.try_alloc().unwrap(), 0).is_err());
+            let mut batch = ArrayVec::<_, 64>::new();
+            queue.ring(0).dequeue_burst(&mut batch);
+            Mempool::free_batch(&mut batch);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn priority_queue_weighted() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut queue = PriorityQueue::new(priority_rings("wrr", 2));
+            queue.set_weights(Some(vec![4, 1]));
+            assert_eq!(queue.weights(), Some(&[4, 1][..]));
+            for prio in [0, 1] {
+                let mut batch = ArrayVec::<_, 40>::new();
+                mp.fill_batch(&mut batch);
+                for mbuf in batch.iter_mut() {
+                    mbuf.extend_from_slice(&[prio as u8][..]);
+                }
+                assert_eq!(queue.enqueue_burst(&mut batch, prio), 40);
+            }
+
+            // the low priority gets 1 of every 5 mbufs while the high priority
+            // is busy
+            for _ in 0..8 {
+                let mut batch = ArrayVec::<_, 5>::new();
+                assert_eq!(queue.dequeue_burst(&mut batch), 5);
+                let prios: Vec<_> = batch.iter().map(|mbuf| mbuf.data()[0]).collect();
+                assert_eq!(prios, [0, 0, 0, 0, 1]);
+                Mempool::free_batch(&mut batch);
+            }
+
+            // the low priority takes the rest of the burst once the high
+            // priority is empty
+            let mut batch = ArrayVec::<_, 64>::new();
+            assert_eq!(queue.dequeue_burst(&mut batch), 40);
+            let prios: Vec<_> = batch.iter().map(|mbuf| mbuf.data()[0]).collect();
+            assert_eq!(prios[..10], [0, 0, 0, 0, 1, 0, 0, 0, 0, 1]);
+            assert!(prios[10..].iter().all(|prio| *prio == 1));
+            assert_eq!(queue.dequeue_burst(&mut batch), 0);
+            Mempool::free_batch(&mut batch);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn shard_by_flow() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let sharder = RingSharder::new(priority_rings("shard", 4));
+            assert_eq!(sharder.nb_shards(), 4);
+            let alloc = |frame: &[u8]| {
+                let mut mbuf = mp.try_alloc().unwrap();
+                mbuf.extend_from_slice(frame);
+                mbuf
+            };
+
+            // the packets of a flow, in both directions with the symmetric
+            // default key, are assigned to the same shard
+            let (a, b) = (Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2));
+            let udp_frame = |src, dst, src_port, dst_port| {
+                l4_frame(IpProtocol::UDP, src, dst, src_port, dst_port, 0, &[])
+            };
+            let fst = alloc(&udp_frame(a, b, 5000, 53));
+            let snd = alloc(&udp_frame(a, b, 5000, 53));
+            let reply = alloc(&udp_frame(b, a, 53, 5000));
+            let shard = sharder.shard_of(&fst);
+            assert_eq!(sharder.shard_of(&snd), shard);
+            assert_eq!(sharder.shard_of(&reply), shard);
+
+            sharder.enqueue(fst).unwrap();
+            sharder.enqueue(snd).unwrap();
+            let mut batch = ArrayVec::<_, 8>::new();
+            assert_eq!(sharder.ring(shard).dequeue_burst(&mut batch), 2);
+            batch.clear();
+
+            // the flows spread across all the shards
+            let mut counts = [0; 4];
+            for src_port in 0..64 {
+                let mbuf = alloc(&udp_frame(a, b, 10000 + src_port, 53));
+                counts[sharder.shard_of(&mbuf)] += 1;
+            }
+            assert!(counts.iter().all(|count| *count > 0));
+
+            // the rss hash of the nic takes precedence
+            let mut mbuf = alloc(&udp_frame(a, b, 5000, 53));
+            let raw = mbuf.as_ptr() as *mut rpkt_dpdk_sys::rte_mbuf;
+            unsafe { (*raw).__bindgen_anon_2.hash.rss = 6 };
+            mbuf.set_ol_flags(OlFlags::RX_RSS_HASH);
+            assert_eq!(sharder.shard_of(&mbuf), 2);
+            sharder.enqueue(mbuf).unwrap();
+            assert_eq!(sharder.ring(2).dequeue_burst(&mut batch), 1);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
 }
@@ -0,0 +1,203 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::error::*;
+use crate::{Lpm, Lpm6, LpmConf};
+
+/// An ip prefix, i.e. an address with a prefix length, such as "10.0.0.0/8".
+///
+/// The bits of the address after the prefix length are kept, but they are
+/// ignored by the route table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNet {
+    /// Return `None` if `prefix_len` exceeds the length of `addr`.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Option<Self> {
+        let max_len = match addr {
+            IpAddr::V4(_) => Lpm::MAX_DEPTH,
+            IpAddr::V6(_) => Lpm6::MAX_DEPTH,
+        };
+        (prefix_len <= max_len).then_some(Self { addr, prefix_len })
+    }
+
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let err = Error::service_err("invalid ip prefix");
+        let (addr, prefix_len) = s.split_once('/').ok_or(err)?;
+        let addr = addr.parse().map_err(|_| err)?;
+        let prefix_len = prefix_len.parse().map_err(|_| err)?;
+        Self::new(addr, prefix_len).ok_or(err)
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// A dual-stack route table, the ipv4 routes are stored in an `Lpm` and the
+/// ipv6 routes in an `Lpm6`.
+///
+/// The default routes are kept aside, as the lpm tables do not support the
+/// prefix length 0.
+pub struct RouteTable {
+    lpm: Lpm,
+    lpm6: Lpm6,
+    default_v4: Option<u32>,
+    default_v6: Option<u32>,
+}
+
+impl RouteTable {
+    /// Create a new route table, the two lpm tables are both named `name` and
+    /// are both configured with `conf`.
+    pub fn try_create(name: String, conf: &LpmConf) -> Result<Self> {
+        Ok(Self {
+            lpm: Lpm::try_create(name.clone(), conf)?,
+            lpm6: Lpm6::try_create(name, conf)?,
+            default_v4: None,
+            default_v6: None,
+        })
+    }
+
+    /// Add the route to `prefix`, or replace the next hop of an existing
+    /// route.
+    ///
+    /// The next hop is limited by `Lpm::MAX_NEXT_HOP` or `Lpm6::MAX_NEXT_HOP`.
+    pub fn add(&mut self, prefix: IpNet, next_hop: u32) -> Result<()> {
+        match (prefix.addr, prefix.prefix_len) {
+            (IpAddr::V4(_), 0) => {
+                if next_hop > Lpm::MAX_NEXT_HOP {
+                    return Error::service_err("lpm next hop exceeds 24 bits").to_err();
+                }
+                self.default_v4 = Some(next_hop);
+                Ok(())
+            }
+            (IpAddr::V6(_), 0) => {
+                if next_hop > Lpm6::MAX_NEXT_HOP {
+                    return Error::service_err("lpm6 next hop exceeds 21 bits").to_err();
+                }
+                self.default_v6 = Some(next_hop);
+                Ok(())
+            }
+            (IpAddr::V4(ip), depth) => self.lpm.add(ip, depth, next_hop),
+            (IpAddr::V6(ip), depth) => self.lpm6.add(ip, depth, next_hop),
+        }
+    }
+
+    /// Delete the route to `prefix`, this function fails if the route does
+    /// not exist.
+    pub fn delete(&mut self, prefix: IpNet) -> Result<()> {
+        let default = match (prefix.addr, prefix.prefix_len) {
+            (IpAddr::V4(_), 0) => &mut self.default_v4,
+            (IpAddr::V6(_), 0) => &mut self.default_v6,
+            (IpAddr::V4(ip), depth) => return self.lpm.delete(ip, depth),
+            (IpAddr::V6(ip), depth) => return self.lpm6.delete(ip, depth),
+        };
+        default
+            .take()
+            .map(|_| ())
+            .ok_or(Error::service_err("no such route"))
+    }
+
+    /// Return the next hop of the longest route that matches `addr`.
+    #[inline]
+    pub fn lookup(&self, addr: IpAddr) -> Option<u32> {
+        match addr {
+            IpAddr::V4(ip) => self.lpm.lookup(ip).or(self.default_v4),
+            IpAddr::V6(ip) => self.lpm6.lookup(ip).or(self.default_v6),
+        }
+    }
+
+    pub fn lpm(&self) -> &Lpm {
+        &self.lpm
+    }
+
+    pub fn lpm6(&self) -> &Lpm6 {
+        &self.lpm6
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    fn net(s: &str) -> IpNet {
+        s.parse().unwrap()
+    }
+
+    fn addr(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_ip_prefix() {
+        let prefix = net("10.0.0.0/8");
+        assert_eq!(prefix.addr(), addr("10.0.0.0"));
+        assert_eq!(prefix.prefix_len(), 8);
+        assert_eq!(prefix.to_string(), "10.0.0.0/8");
+        assert_eq!(net("2001:db8::/32").prefix_len(), 32);
+        assert_eq!(net("::/0").prefix_len(), 0);
+
+        for s in [
+            "10.0.0.0",
+            "10.0.0.0/33",
+            "2001:db8::/129",
+            "10.0.0/8",
+            "/8",
+        ] {
+            assert!(s.parse::<IpNet>().is_err());
+        }
+    }
+
+    #[test]
+    fn dual_stack_lookup() {
+        DpdkOption::new().init().unwrap();
+
+        let mut table = RouteTable::try_create("dual_stack".to_string(), &LpmConf::new()).unwrap();
+        table.add(net("10.0.0.0/8"), 1).unwrap();
+        table.add(net("10.1.0.0/16"), 2).unwrap();
+        table.add(net("2001:db8::/32"), 3).unwrap();
+        table.add(net("2001:db8:1::/48"), 4).unwrap();
+
+        assert_eq!(table.lookup(addr("10.2.0.1")), Some(1));
+        assert_eq!(table.lookup(addr("10.1.0.1")), Some(2));
+        assert_eq!(table.lookup(addr("2001:db8:2::1")), Some(3));
+        assert_eq!(table.lookup(addr("2001:db8:1::1")), Some(4));
+        // the ipv4-mapped address is not looked up in the ipv4 table
+        assert_eq!(table.lookup(addr("::ffff:10.1.0.1")), None);
+        assert_eq!(table.lookup(addr("192.168.0.1")), None);
+
+        // the default routes are per address family
+        table.add(net("0.0.0.0/0"), 5).unwrap();
+        assert_eq!(table.lookup(addr("192.168.0.1")), Some(5));
+        assert_eq!(table.lookup(addr("2001:db9::1")), None);
+        table.add(net("::/0"), 6).unwrap();
+        assert_eq!(table.lookup(addr("2001:db9::1")), Some(6));
+        assert_eq!(table.lookup(addr("10.1.0.1")), Some(2));
+
+        table.delete(net("10.1.0.0/16")).unwrap();
+        assert_eq!(table.lookup(addr("10.1.0.1")), Some(1));
+        table.delete(net("0.0.0.0/0")).unwrap();
+        assert!(table.delete(net("0.0.0.0/0")).is_err());
+        assert_eq!(table.lookup(addr("192.168.0.1")), None);
+        assert_eq!(table.lookup(addr("2001:db9::1")), Some(6));
+    }
+}
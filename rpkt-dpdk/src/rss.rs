@@ -0,0 +1,389 @@
+use std::net::IpAddr;
+
+use rpkt::flow::to_ipv6_octets;
+use rpkt::ipv4::IpProtocol;
+
+use crate::proto::{
+    ethertype, ipv4_is_fragment, ETHERTYPE_IPV4, ETHERTYPE_IPV6, ETHER_HEADER_LEN, IPPROTO_SCTP,
+    IPPROTO_TCP, IPPROTO_UDP, IPV4_HEADER_LEN, IPV6_HEADER_LEN,
+};
+
+/// The 40-byte RSS key from the Microsoft RSS verification suite, which is also
+/// the default key of many NICs.
+pub const MS_RSS_KEY_40B: [u8; 40] = [
+    0x6d, 0x5a, 0x56, 0xda, 0x25, 0x5b, 0x0e, 0xc2, 0x41, 0x67, 0x25, 0x3d, 0x43, 0xa3, 0x8f, 0xb0,
+    0xd0, 0xca, 0x2b, 0xcb, 0xae, 0x7b, 0x30, 0xb4, 0x77, 0xcb, 0x2d, 0xa3, 0x80, 0x30, 0xf2, 0x0c,
+    0x6a, 0x42, 0xb7, 0x3b, 0xbe, 0xac, 0x01, 0xfa,
+];
+
+/// The flow identifier used as the input of the RSS hash, see `rpkt::flow`.
+pub use rpkt::flow::FiveTuple;
+
+// Serialize the tuple in the order used by the NIC: source address,
+// destination address, source port and destination port.
+//
+// The ports are only included for TCP, UDP and SCTP. If the two addresses
+// are from different families, the ipv4 address is mapped to ipv6.
+fn write_input(tuple: &FiveTuple, buf: &mut [u8; 36]) -> usize {
+    let mut len = match (tuple.src_ip, tuple.dst_ip) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            buf[0..4].copy_from_slice(&src.octets());
+            buf[4..8].copy_from_slice(&dst.octets());
+            8
+        }
+        (src, dst) => {
+            buf[0..16].copy_from_slice(&to_ipv6_octets(src));
+            buf[16..32].copy_from_slice(&to_ipv6_octets(dst));
+            32
+        }
+    };
+
+    if matches!(
+        tuple.protocol,
+        IpProtocol::TCP | IpProtocol::UDP | IpProtocol::SCTP
+    ) {
+        buf[len..len + 2].copy_from_slice(&tuple.src_port.to_be_bytes());
+        buf[len + 2..len + 4].copy_from_slice(&tuple.dst_port.to_be_bytes());
+        len += 4;
+    }
+
+    len
+}
+
+/// Compute the Toeplitz hash of `tuple` with the RSS `key`.
+///
+/// The result matches the RSS hash computed by the NIC when it is configured with
+/// the same key, so it can be used to predict the queue that a flow lands on.
+/// Using `offload::DEFAULT_RSS_KEY_40B` as the key gives a symmetric hash, where
+/// both directions of a flow have the same hash value.
+///
+/// # Panic:
+/// This function panics if the key is shorter than the serialized tuple plus 4 bytes.
+/// A 40-byte key is long enough for all the tuples.
+pub fn toeplitz_hash(key: &[u8], tuple: &FiveTuple) -> u32 {
+    let mut input = [0; 36];
+    let len = write_input(tuple, &mut input);
+    toeplitz_hash_bytes(key, &input[..len])
+}
+
+fn toeplitz_hash_bytes(key: &[u8], input: &[u8]) -> u32 {
+    assert!(key.len() >= input.len() + 4);
+
+    let mut result = 0;
+    // The left-most 32 bits of the key, shifted by one bit for each input bit.
+    let mut window = u32::from_be_bytes([key[0], key[1], key[2], key[3]]);
+    for (byte, next_key_byte) in input.iter().zip(&key[4..]) {
+        for bit in 0..8 {
+            if byte & (0x80 >> bit) != 0 {
+                result ^= window;
+            }
+            window = (window << 1) | u32::from((next_key_byte >> (7 - bit)) & 1);
+        }
+    }
+
+    result
+}
+
+/// A software emulation of the RSS steering of a NIC.
+///
+/// The queue of a packet is looked up in the redirection table (reta) with the
+/// Toeplitz hash of the packet, as the NIC does when it is configured with the
+/// same key and reta. This makes the per-queue worker assignment testable
+/// without the RSS-capable hardware.
+#[derive(Clone, Debug)]
+pub struct SoftSteering {
+    key: Vec<u8>,
+    reta: Vec<u16>,
+}
+
+impl SoftSteering {
+    /// Create a steering with the RSS `key` and the redirection table `reta`.
+    ///
+    /// # Panic:
+    /// This function panics if `reta` is empty, or if `key` is shorter than 40 bytes.
+    pub fn new(key: &[u8], reta: Vec<u16>) -> Self {
+        assert!(!reta.is_empty() && key.len() >= 40);
+        Self {
+            key: key.to_vec(),
+            reta,
+        }
+    }
+
+    /// Create a steering over `nb_queues` queues, with the default reta of the
+    /// NICs that assigns the `reta_size` entries to the queues in round-robin.
+    pub fn with_queues(key: &[u8], nb_queues: u16, reta_size: usize) -> Self {
+        assert!(nb_queues > 0);
+        let reta = (0..reta_size).map(|i| (i % usize::from(nb_queues)) as u16);
+        Self::new(key, reta.collect())
+    }
+
+    pub fn reta(&self) -> &[u16] {
+        &self.reta
+    }
+
+    /// Return the queue of the flow identified by `tuple`.
+    pub fn queue_for_tuple(&self, tuple: &FiveTuple) -> u16 {
+        let hash = toeplitz_hash(&self.key, tuple);
+        self.reta[hash as usize % self.reta.len()]
+    }
+
+    /// Return the queue of the ethernet frame in `frame`.
+    ///
+    /// The ipv4 and ipv6 packets are hashed on the addresses and, if the packet
+    /// carries the first or the only fragment of a tcp, udp or sctp packet, the
+    /// ports. The non-ip frames are steered to queue 0, like the NIC does.
+    pub fn queue_for(&self, frame: &[u8]) -> u16 {
+        match parse_tuple(frame) {
+            Some(tuple) => self.queue_for_tuple(&tuple),
+            None => 0,
+        }
+    }
+}
+
+/// Compute the Toeplitz hash of the ethernet frame in `frame` with the RSS
+/// `key`, on the same input as `SoftSteering::queue_for`.
+///
+/// The non-ip frames have no hash input, and their hash is 0.
+///
+/// # Panic:
+/// This function panics if `key` is shorter than 40 bytes.
+pub fn frame_hash(key: &[u8], frame: &[u8]) -> u32 {
+    match parse_tuple(frame) {
+        Some(tuple) => toeplitz_hash(key, &tuple),
+        None => 0,
+    }
+}
+
+// Parse the hash input of an ethernet frame, the ports are left out by
+// setting the protocol to 0 if the transport header is absent.
+pub(crate) fn parse_tuple(frame: &[u8]) -> Option<FiveTuple> {
+    let l3 = frame.get(ETHER_HEADER_LEN..)?;
+    let (src_ip, dst_ip, protocol, l4) = match ethertype(frame)? {
+        ETHERTYPE_IPV4 if l3.len() >= IPV4_HEADER_LEN && l3[0] >> 4 == 4 => {
+            let ihl = usize::from(l3[0] & 0x0f) * 4;
+            let src: [u8; 4] = l3[12..16].try_into().unwrap();
+            let dst: [u8; 4] = l3[16..20].try_into().unwrap();
+            // the non-first fragments and the fragmented packets are hashed on
+            // the addresses only
+            let protocol = if ipv4_is_fragment(l3) { 0 } else { l3[9] };
+            (
+                IpAddr::from(src),
+                IpAddr::from(dst),
+                protocol,
+                l3.get(ihl..),
+            )
+        }
+        ETHERTYPE_IPV6 if l3.len() >= IPV6_HEADER_LEN => {
+            let src: [u8; 16] = l3[8..24].try_into().unwrap();
+            let dst: [u8; 16] = l3[24..40].try_into().unwrap();
+            let l4 = l3.get(IPV6_HEADER_LEN..);
+            (IpAddr::from(src), IpAddr::from(dst), l3[6], l4)
+        }
+        _ => return None,
+    };
+
+    let mut tuple = FiveTuple {
+        src_ip,
+        dst_ip,
+        src_port: 0,
+        dst_port: 0,
+        protocol: IpProtocol::from(0),
+    };
+    if matches!(protocol, IPPROTO_TCP | IPPROTO_UDP | IPPROTO_SCTP) {
+        if let Some(ports) = l4.and_then(|l4| l4.get(..4)) {
+            tuple.src_port = u16::from_be_bytes([ports[0], ports[1]]);
+            tuple.dst_port = u16::from_be_bytes([ports[2], ports[3]]);
+            tuple.protocol = IpProtocol::from(protocol);
+        }
+    }
+    Some(tuple)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::offload::DEFAULT_RSS_KEY_40B;
+    use crate::proto::l4_frame;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    // The test vectors are taken from the dpdk/app/test/test_thash.c file,
+    // in the form of (src_ip, dst_ip, src_port, dst_port, l3 hash, l3l4 hash).
+    type TestVector<A> = (A, A, u16, u16, u32, u32);
+
+    const V4_TBL: [TestVector<[u8; 4]>; 5] = [
+        (
+            [66, 9, 149, 187],
+            [161, 142, 100, 80],
+            2794,
+            1766,
+            0x323e8fc2,
+            0x51ccc178,
+        ),
+        (
+            [199, 92, 111, 2],
+            [65, 69, 140, 83],
+            14230,
+            4739,
+            0xd718262a,
+            0xc626b0ea,
+        ),
+        (
+            [24, 19, 198, 95],
+            [12, 22, 207, 184],
+            12898,
+            38024,
+            0xd2d0a5de,
+            0x5c2b394a,
+        ),
+        (
+            [38, 27, 205, 30],
+            [209, 142, 163, 6],
+            48228,
+            2217,
+            0x82989176,
+            0xafc7327f,
+        ),
+        (
+            [153, 39, 163, 191],
+            [202, 188, 127, 2],
+            44251,
+            1303,
+            0x5d1809c5,
+            0x10e828a2,
+        ),
+    ];
+
+    const V6_TBL: [TestVector<&str>; 3] = [
+        (
+            "3ffe:2501:200:1fff::7",
+            "3ffe:2501:200:3::1",
+            2794,
+            1766,
+            0x2cc18cd5,
+            0x40207d3d,
+        ),
+        (
+            "3ffe:501:8::260:97ff:fe40:efab",
+            "ff02::1",
+            14230,
+            4739,
+            0x0f0c461c,
+            0xdde51bbf,
+        ),
+        (
+            "3ffe:1900:4545:3:200:f8ff:fe21:67cf",
+            "fe80::200:f8ff:fe21:67cf",
+            44251,
+            38024,
+            0x4b61e985,
+            0x02d1feef,
+        ),
+    ];
+
+    #[test]
+    fn toeplitz_hash_ipv4() {
+        for (src_ip, dst_ip, src_port, dst_port, l3_hash, l3l4_hash) in V4_TBL {
+            let mut tuple = FiveTuple {
+                src_ip: IpAddr::V4(Ipv4Addr::from(src_ip)),
+                dst_ip: IpAddr::V4(Ipv4Addr::from(dst_ip)),
+                src_port,
+                dst_port,
+                protocol: IpProtocol::TCP,
+            };
+            assert_eq!(toeplitz_hash(&MS_RSS_KEY_40B[..], &tuple), l3l4_hash);
+
+            // the ports are ignored for protocols other than tcp, udp and sctp
+            tuple.protocol = IpProtocol::ICMP;
+            assert_eq!(toeplitz_hash(&MS_RSS_KEY_40B[..], &tuple), l3_hash);
+        }
+    }
+
+    #[test]
+    fn toeplitz_hash_ipv6() {
+        for (src_ip, dst_ip, src_port, dst_port, l3_hash, l3l4_hash) in V6_TBL {
+            let mut tuple = FiveTuple {
+                src_ip: IpAddr::V6(src_ip.parse::<Ipv6Addr>().unwrap()),
+                dst_ip: IpAddr::V6(dst_ip.parse::<Ipv6Addr>().unwrap()),
+                src_port,
+                dst_port,
+                protocol: IpProtocol::UDP,
+            };
+            assert_eq!(toeplitz_hash(&MS_RSS_KEY_40B[..], &tuple), l3l4_hash);
+
+            tuple.protocol = IpProtocol::ICMPV6;
+            assert_eq!(toeplitz_hash(&MS_RSS_KEY_40B[..], &tuple), l3_hash);
+        }
+    }
+
+    #[test]
+    fn symmetric_toeplitz_hash() {
+        for (src_ip, dst_ip, src_port, dst_port, _, _) in V4_TBL {
+            let tuple = FiveTuple {
+                src_ip: IpAddr::V4(Ipv4Addr::from(src_ip)),
+                dst_ip: IpAddr::V4(Ipv4Addr::from(dst_ip)),
+                src_port,
+                dst_port,
+                protocol: IpProtocol::TCP,
+            };
+            let reversed = FiveTuple {
+                src_ip: tuple.dst_ip,
+                dst_ip: tuple.src_ip,
+                src_port: tuple.dst_port,
+                dst_port: tuple.src_port,
+                protocol: IpProtocol::TCP,
+            };
+            assert_eq!(
+                toeplitz_hash(&DEFAULT_RSS_KEY_40B[..], &tuple),
+                toeplitz_hash(&DEFAULT_RSS_KEY_40B[..], &reversed)
+            );
+        }
+    }
+
+    #[test]
+    fn soft_steering_spreads_flows() {
+        let build_tcp_frame = |src_ip: [u8; 4], dst_ip: [u8; 4], src_port, dst_port| {
+            let (src_ip, dst_ip) = (Ipv4Addr::from(src_ip), Ipv4Addr::from(dst_ip));
+            l4_frame(IpProtocol::TCP, src_ip, dst_ip, src_port, dst_port, 0, &[])
+        };
+
+        // 4 queues, the queue of a flow is the low 2 bits of its hash
+        let steering = SoftSteering::with_queues(&MS_RSS_KEY_40B[..], 4, 128);
+        assert_eq!(&steering.reta()[..6], &[0, 1, 2, 3, 0, 1]);
+
+        let mut queues = Vec::new();
+        for (src_ip, dst_ip, src_port, dst_port, l3_hash, l3l4_hash) in V4_TBL {
+            let mut frame = build_tcp_frame(src_ip, dst_ip, src_port, dst_port);
+            let queue = steering.queue_for(&frame[..]);
+            assert_eq!(queue, (l3l4_hash & 3) as u16);
+            queues.push(queue);
+
+            // a fragment is steered on the addresses only
+            frame[14 + 6] = 0x20;
+            assert_eq!(steering.queue_for(&frame[..]), (l3_hash & 3) as u16);
+        }
+        assert_eq!(queues, [0, 2, 2, 3, 2]);
+
+        for (src_ip, dst_ip, src_port, dst_port, _, l3l4_hash) in V6_TBL {
+            let mut frame = [0; 14 + 40 + 8];
+            frame[12..14].copy_from_slice(&[0x86, 0xdd]);
+            frame[14] = 0x60;
+            frame[14 + 6] = IPPROTO_UDP;
+            let src_ip: Ipv6Addr = src_ip.parse().unwrap();
+            let dst_ip: Ipv6Addr = dst_ip.parse().unwrap();
+            frame[14 + 8..14 + 24].copy_from_slice(&src_ip.octets());
+            frame[14 + 24..14 + 40].copy_from_slice(&dst_ip.octets());
+            frame[54..56].copy_from_slice(&src_port.to_be_bytes());
+            frame[56..58].copy_from_slice(&dst_port.to_be_bytes());
+            assert_eq!(steering.queue_for(&frame[..]), (l3l4_hash & 3) as u16);
+        }
+
+        // a custom reta sends all the flows to queue 7
+        let steering = SoftSteering::new(&MS_RSS_KEY_40B[..], vec![7; 64]);
+        let (src_ip, dst_ip, src_port, dst_port, _, _) = V4_TBL[0];
+        let frame = build_tcp_frame(src_ip, dst_ip, src_port, dst_port);
+        assert_eq!(steering.queue_for(&frame[..]), 7);
+        // the non-ip and truncated frames are steered to queue 0
+        assert_eq!(steering.queue_for(&frame[..20]), 0);
+        assert_eq!(steering.queue_for(&[0; 60][..]), 0);
+    }
+}
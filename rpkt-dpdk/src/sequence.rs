@@ -0,0 +1,222 @@
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+use arrayvec::ArrayVec;
+use rpkt_dpdk_sys as ffi;
+
+use crate::error::*;
+use crate::{Mbuf, Ring};
+
+/// The name of the registered mbuf dynamic field of the sequence numbers.
+pub const SEQ_DYNFIELD_NAME: &str = "rpkt_dynfield_seq";
+
+// Register the dynamic field, or look up the field registered by an earlier
+// call, and return its offset.
+fn register_dynfield() -> Result<usize> {
+    let mut params: ffi::rte_mbuf_dynfield = unsafe { std::mem::zeroed() };
+    let name = CString::new(SEQ_DYNFIELD_NAME).unwrap();
+    for (dst, src) in params.name.iter_mut().zip(name.as_bytes_with_nul().iter()) {
+        *dst = *src as c_char;
+    }
+    params.size = std::mem::size_of::<u64>();
+    params.align = std::mem::align_of::<u64>();
+
+    let offset = unsafe { ffi::rte_mbuf_dynfield_register(&params) };
+    let offset = check_ret(offset, "fail to register sequence dynfield")?;
+    Ok(offset as usize)
+}
+
+/// A producer of the sequence numbers for detecting the loss or the reordering
+/// of the mbufs through a pipeline, e.g. across a ring under stress.
+///
+/// The stamper writes an incrementing sequence number into a dynamic field of
+/// each mbuf, starting from 0, which is checked by a `SeqChecker` at the
+/// consumer.
+pub struct SeqStamper {
+    offset: usize,
+    next: u64,
+}
+
+impl SeqStamper {
+    /// Create a new stamper.
+    ///
+    /// The dynamic field is registered on the first call, the stampers and the
+    /// checkers created afterwards share the same field.
+    pub fn try_create() -> Result<Self> {
+        Ok(Self {
+            offset: register_dynfield()?,
+            next: 0,
+        })
+    }
+
+    /// Return the sequence number of the next stamped mbuf.
+    pub fn next_seq(&self) -> u64 {
+        self.next
+    }
+
+    /// Stamp `mbuf` with the next sequence number.
+    #[inline]
+    pub fn stamp(&mut self, mbuf: &mut Mbuf) {
+        unsafe {
+            let field = (mbuf.as_ptr() as *mut u8).add(self.offset) as *mut u64;
+            field.write(self.next);
+        }
+        self.next += 1;
+    }
+
+    /// Stamp the mbufs of `batch` and enqueue them to `ring`, see
+    /// `Ring::enqueue_burst`.
+    ///
+    /// The mbufs that are not enqueued are left in `batch` and do not consume
+    /// sequence numbers, they are stamped again when they are enqueued later.
+    /// Return the number of enqueued mbufs.
+    pub fn enqueue_burst<const N: usize>(
+        &mut self,
+        ring: &Ring,
+        batch: &mut ArrayVec<Mbuf, N>,
+    ) -> usize {
+        let start = self.next;
+        for mbuf in batch.iter_mut() {
+            self.stamp(mbuf);
+        }
+        let nb_enq = ring.enqueue_burst(batch);
+        self.next = start + nb_enq as u64;
+        nb_enq
+    }
+}
+
+/// The result of checking the sequence number of an mbuf.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeqStatus {
+    /// The mbuf has the expected sequence number.
+    InOrder,
+    /// The given number of mbufs before this one are missing, i.e. lost, or
+    /// reordered behind this one.
+    Gap(u64),
+    /// The sequence number has been seen before, the mbuf is either a
+    /// duplicate or reordered behind a later one.
+    Duplicate,
+}
+
+/// A consumer of the sequence numbers stamped by a `SeqStamper`.
+pub struct SeqChecker {
+    offset: usize,
+    next: u64,
+}
+
+impl SeqChecker {
+    /// Create a new checker that expects the sequence number 0 first.
+    pub fn try_create() -> Result<Self> {
+        Ok(Self {
+            offset: register_dynfield()?,
+            next: 0,
+        })
+    }
+
+    /// Return the sequence number that is expected next.
+    pub fn next_seq(&self) -> u64 {
+        self.next
+    }
+
+    /// Return the sequence number of `mbuf`.
+    ///
+    /// The result is meaningless if `mbuf` was never stamped.
+    #[inline]
+    pub fn seq(&self, mbuf: &Mbuf) -> u64 {
+        unsafe {
+            let field = (mbuf.as_ptr() as *const u8).add(self.offset) as *const u64;
+            field.read()
+        }
+    }
+
+    /// Check the sequence number of `mbuf` against the last seen one.
+    ///
+    /// After a gap, the checker expects the numbers following `mbuf`.
+    #[inline]
+    pub fn check(&mut self, mbuf: &Mbuf) -> SeqStatus {
+        let seq = self.seq(mbuf);
+        if seq < self.next {
+            return SeqStatus::Duplicate;
+        }
+        let missing = seq - self.next;
+        self.next = seq + 1;
+        if missing == 0 {
+            SeqStatus::InOrder
+        } else {
+            SeqStatus::Gap(missing)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn detect_gap_and_duplicate() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+            let ring = Ring::try_create(
+                "seq".to_string(),
+                &RingConf {
+                    count: 32,
+                    socket_id: 0,
+                    flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ,
+                },
+            )
+            .unwrap();
+
+            let mut stamper = SeqStamper::try_create().unwrap();
+            let mut checker = SeqChecker::try_create().unwrap();
+
+            // the ring holds 31 mbufs, the last one is stamped again later
+            let mut batch = ArrayVec::<_, 32>::new();
+            mp.fill_batch(&mut batch);
+            assert_eq!(stamper.enqueue_burst(&ring, &mut batch), 31);
+            assert_eq!(stamper.next_seq(), 31);
+
+            let mut out = ArrayVec::<_, 32>::new();
+            assert_eq!(ring.dequeue_burst(&mut out), 31);
+            assert!(out
+                .iter()
+                .all(|mbuf| checker.check(mbuf) == SeqStatus::InOrder));
+            Mempool::free_batch(&mut out);
+
+            assert_eq!(stamper.enqueue_burst(&ring, &mut batch), 1);
+            for _ in 0..4 {
+                let mut mbuf = mp.try_alloc().unwrap();
+                stamper.stamp(&mut mbuf);
+                batch.push(mbuf);
+            }
+            // drop the mbuf of 33
+            batch.remove(1);
+            assert_eq!(ring.enqueue_burst(&mut batch), 3);
+
+            ring.dequeue_burst(&mut out);
+            let status: Vec<_> = out.iter().map(|mbuf| checker.check(mbuf)).collect();
+            assert_eq!(
+                status,
+                [
+                    SeqStatus::InOrder,
+                    SeqStatus::InOrder,
+                    SeqStatus::Gap(1),
+                    SeqStatus::InOrder
+                ]
+            );
+            assert_eq!(checker.seq(&out[0]), 31);
+            assert_eq!(checker.next_seq(), 36);
+
+            // a reordered or duplicated mbuf
+            assert_eq!(checker.check(&out[1]), SeqStatus::Duplicate);
+            assert_eq!(checker.next_seq(), 36);
+            Mempool::free_batch(&mut out);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_int};
+use std::path::PathBuf;
 use std::sync::{Mutex, MutexGuard};
 
 use once_cell::sync::OnceCell;
@@ -9,28 +10,214 @@ use rpkt_dpdk_sys as ffi;
 use super::error::*;
 use super::lcore::{self, *};
 use super::mempool::*;
+use super::offload::VlanOffload;
 use super::port::*;
+use super::retry::RetryPolicy;
 
 pub(crate) static SERVICE: OnceCell<DpdkService> = OnceCell::new();
 
-pub struct DpdkOption {}
+/// The role of the current process in a DPDK multi-process setup.
+///
+/// The primary process owns all the shared memory objects, while a secondary
+/// process can only look up the objects created by the primary.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProcType {
+    Primary,
+    Secondary,
+}
+
+impl ProcType {
+    /// Return the process type of the current process, as detected by the DPDK EAL.
+    pub fn current() -> ProcType {
+        if unsafe { ffi::rte_eal_process_type() } == ffi::rte_proc_type_t_RTE_PROC_SECONDARY {
+            ProcType::Secondary
+        } else {
+            ProcType::Primary
+        }
+    }
+
+    fn as_arg(&self) -> &'static str {
+        match self {
+            ProcType::Primary => "primary",
+            ProcType::Secondary => "secondary",
+        }
+    }
+}
+
+/// The typed EAL parameters used by `DpdkOption` to initialize DPDK.
+///
+/// The default configuration runs on lcore 0 with 4 memory channels as the
+/// primary process, i.e. "--lcores=0 --main-lcore=0 -n 4 --proc-type=primary".
+#[derive(Clone, Debug)]
+pub struct EalConfig {
+    /// The lcores used by the EAL, which must not be empty.
+    pub lcores: Vec<u32>,
+    /// The main lcore, which must be one of `lcores`.
+    pub main_lcore: u32,
+    /// The number of memory channels, 0 leaves it to the EAL.
+    pub memory_channels: u32,
+    /// The megabytes of hugepage memory preallocated on each socket, an empty
+    /// list leaves it to the EAL.
+    pub socket_mem: Vec<u32>,
+    pub huge_dir: Option<PathBuf>,
+    pub no_huge: bool,
+    pub proc_type: ProcType,
+    /// The prefix of the hugepage files, an empty prefix leaves it to the EAL.
+    pub file_prefix: String,
+    /// The raw EAL arguments rendered after the typed parameters, e.g. the EAL
+    /// part returned by `AppArgs::split`.
+    pub extra_args: Vec<String>,
+}
+
+impl EalConfig {
+    pub fn new() -> Self {
+        Self {
+            lcores: vec![0],
+            main_lcore: 0,
+            memory_channels: 4,
+            socket_mem: Vec::new(),
+            huge_dir: None,
+            no_huge: false,
+            proc_type: ProcType::Primary,
+            file_prefix: String::new(),
+            extra_args: Vec::new(),
+        }
+    }
+
+    pub fn set_lcores(&mut self, val: Vec<u32>) {
+        self.lcores = val;
+    }
+
+    pub fn set_main_lcore(&mut self, val: u32) {
+        self.main_lcore = val;
+    }
+
+    pub fn set_memory_channels(&mut self, val: u32) {
+        self.memory_channels = val;
+    }
+
+    pub fn set_socket_mem(&mut self, val: Vec<u32>) {
+        self.socket_mem = val;
+    }
+
+    pub fn set_huge_dir(&mut self, val: Option<PathBuf>) {
+        self.huge_dir = val;
+    }
+
+    pub fn set_no_huge(&mut self, val: bool) {
+        self.no_huge = val;
+    }
+
+    pub fn set_proc_type(&mut self, val: ProcType) {
+        self.proc_type = val;
+    }
+
+    pub fn set_file_prefix(&mut self, val: &str) {
+        self.file_prefix = val.to_owned();
+    }
+
+    pub fn set_extra_args(&mut self, val: Vec<String>) {
+        self.extra_args = val;
+    }
+
+    /// Render the EAL arguments, including the program name.
+    ///
+    /// The lcores are rendered as ranges, e.g. "--lcores=0-3,6". This returns an
+    /// error if the lcore list is empty or does not contain the main lcore, or if
+    /// a string parameter contains a nul byte.
+    pub fn into_args(self) -> Result<Vec<CString>> {
+        if self.lcores.is_empty() {
+            return Error::service_err("empty eal lcore list").to_err();
+        }
+        if !self.lcores.contains(&self.main_lcore) {
+            return Error::service_err("main lcore is not in the eal lcore list").to_err();
+        }
+
+        let mut args = vec!["./prefix".to_owned()];
+        args.push(format!("--lcores={}", lcore_ranges(&self.lcores)));
+        args.push(format!("--main-lcore={}", self.main_lcore));
+        if self.memory_channels > 0 {
+            args.push("-n".to_owned());
+            args.push(self.memory_channels.to_string());
+        }
+        if !self.socket_mem.is_empty() {
+            let socket_mem: Vec<String> = self.socket_mem.iter().map(|m| m.to_string()).collect();
+            args.push(format!("--socket-mem={}", socket_mem.join(",")));
+        }
+        if let Some(huge_dir) = self.huge_dir.as_ref() {
+            let huge_dir = huge_dir
+                .to_str()
+                .ok_or(Error::service_err("invalid eal huge dir"))?;
+            args.push(format!("--huge-dir={huge_dir}"));
+        }
+        if self.no_huge {
+            args.push("--no-huge".to_owned());
+        }
+        args.push(format!("--proc-type={}", self.proc_type.as_arg()));
+        if !self.file_prefix.is_empty() {
+            args.push(format!("--file-prefix={}", self.file_prefix));
+        }
+        args.extend(self.extra_args);
+
+        args.into_iter()
+            .map(|arg| CString::new(arg).map_err(|_| Error::service_err("invalid eal argument")))
+            .collect()
+    }
+}
+
+impl Default for EalConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Render the lcores as a list of ranges, e.g. "0-3,6".
+fn lcore_ranges(lcores: &[u32]) -> String {
+    let mut lcores = lcores.to_vec();
+    lcores.sort_unstable();
+    lcores.dedup();
+
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for i in 1..=lcores.len() {
+        if i == lcores.len() || lcores[i] != lcores[i - 1] + 1 {
+            if start == i - 1 {
+                ranges.push(lcores[start].to_string());
+            } else {
+                ranges.push(format!("{}-{}", lcores[start], lcores[i - 1]));
+            }
+            start = i;
+        }
+    }
+    ranges.join(",")
+}
+
+pub struct DpdkOption {
+    eal_config: EalConfig,
+}
 
 impl DpdkOption {
     /// Create a new EalOption.
     pub fn new() -> Self {
-        DpdkOption {}
+        DpdkOption {
+            eal_config: EalConfig::new(),
+        }
+    }
+
+    /// Set the process type used to initialize the EAL, the default is `ProcType::Primary`.
+    pub fn set_proc_type(&mut self, val: ProcType) {
+        self.eal_config.proc_type = val;
+    }
+
+    /// Set the EAL parameters, the default is `EalConfig::default()`.
+    pub fn set_eal_config(&mut self, val: EalConfig) {
+        self.eal_config = val;
     }
 
     pub fn init(self) -> Result<()> {
         SERVICE.get_or_try_init(|| {
-            // prepare the eal paramters, "-c 1 -n 4 --proc-type primary"
-            let mut args: Vec<CString> = vec![CString::new("./prefix").unwrap()];
-            args.push(CString::new("-c").unwrap());
-            args.push(CString::new("1").unwrap());
-            args.push(CString::new("-n").unwrap());
-            args.push(CString::new("4").unwrap());
-            args.push(CString::new("--proc-type").unwrap());
-            args.push(CString::new("primary").unwrap());
+            // prepare the eal paramters
+            let args = self.eal_config.into_args()?;
 
             // let potential errors panic early
             let lcores = lcore::detect_lcores();
@@ -129,7 +316,7 @@ impl DpdkService {
             .get_mut(name)
             .ok_or(Error::service_err("no such mempool"))?;
 
-        if !mp.in_use() && mp.full() {
+        if !mp.in_use() && (!mp.owned() || mp.full()) {
             // We are the sole owner of the counter, this also means that
             // we are the sole owner of the PtrWrapper, and we are safe to deallocate it.
             // The mempool to be removed is also full, this means that there are no out-going
@@ -142,6 +329,63 @@ impl DpdkService {
         }
     }
 
+    /// Look up a mempool that is created by the primary process.
+    ///
+    /// The returned `Mempool` does not own the underlying DPDK mempool, calling
+    /// `mempool_free` on it only removes it from the service without releasing
+    /// the shared memory.
+    pub fn mempool_lookup<S: AsRef<str>>(&self, name: S) -> Result<Mempool> {
+        let mut inner = self.try_lock()?;
+
+        if inner.mpools.contains_key(name.as_ref()) {
+            return Error::service_err("mempool already exists").to_err();
+        }
+
+        let mp = Mempool::try_lookup(name.as_ref().to_string())?;
+        inner.mpools.insert(name.as_ref().to_string(), mp.clone());
+
+        Ok(mp)
+    }
+
+    /// Create a mempool named `name`, or look up the mempool if it already
+    /// exists in the dpdk memory.
+    ///
+    /// The existing mempool is registered like `mempool_lookup`, so it is not
+    /// owned by the service. This function fails if the existing mempool is
+    /// not created with `conf`. A secondary process always looks up the mempool.
+    pub fn mempool_get_or_create<S: AsRef<str>>(
+        &self,
+        name: S,
+        conf: &MempoolConf,
+    ) -> Result<Mempool> {
+        let mut inner = self.try_lock()?;
+
+        if inner.mpools.contains_key(name.as_ref()) {
+            return Error::service_err("mempool already exists").to_err();
+        }
+
+        let created = match ProcType::current() {
+            ProcType::Primary => match Mempool::try_create(name.as_ref().to_string(), conf) {
+                Err(err) if err.kind() == ErrorKind::FFIError(libc::EEXIST) => None,
+                res => Some(res?),
+            },
+            ProcType::Secondary => None,
+        };
+        let mp = match created {
+            Some(mp) => mp,
+            None => {
+                let mp = Mempool::try_lookup(name.as_ref().to_string())?;
+                if !mp.matches(conf) {
+                    return Error::service_err("existing mempool has a different config").to_err();
+                }
+                mp
+            }
+        };
+        inner.mpools.insert(name.as_ref().to_string(), mp.clone());
+
+        Ok(mp)
+    }
+
     pub fn mempool(&self, name: &str) -> Result<Mempool> {
         let inner = self.try_lock()?;
 
@@ -157,6 +401,131 @@ impl DpdkService {
         unsafe { Ok(ffi::rte_eth_dev_count_avail()) }
     }
 
+    /// Return the ids of all the available ports.
+    ///
+    /// The port ids are not necessarily contiguous, e.g. when some of the ports
+    /// are owned by other DPDK entities.
+    pub fn port_ids(&self) -> Result<Vec<u16>> {
+        let _inner = self.try_lock()?;
+
+        // This is the same as the RTE_ETH_FOREACH_DEV macro.
+        let mut port_ids = Vec::new();
+        let mut next_id: u64 = 0;
+        loop {
+            let port_id = unsafe {
+                ffi::rte_eth_find_next_owned_by(next_id as u16, ffi::RTE_ETH_DEV_NO_OWNER as u64)
+            };
+            if port_id >= ffi::RTE_MAX_ETHPORTS as u64 {
+                break;
+            }
+            port_ids.push(port_id as u16);
+            next_id = port_id + 1;
+        }
+
+        Ok(port_ids)
+    }
+
+    /// Return the device name of the port, e.g. the PCI address or the vdev name.
+    pub fn port_name(&self, port_id: u16) -> Result<String> {
+        let _inner = self.try_lock()?;
+
+        let mut name: [c_char; ffi::RTE_ETH_NAME_MAX_LEN as usize] =
+            [0; ffi::RTE_ETH_NAME_MAX_LEN as usize];
+        let res = unsafe { ffi::rte_eth_dev_get_name_by_port(port_id, name.as_mut_ptr()) };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to get eth dev name").to_err();
+        }
+
+        let name = unsafe { CStr::from_ptr(name.as_ptr()) };
+        Ok(name.to_str().unwrap_or("").to_owned())
+    }
+
+    /// Return the firmware version of the port, e.g. for the inventory of the
+    /// NICs.
+    ///
+    /// This returns an `ErrorKind::FFIError` with `-ENOTSUP` if the PMD does not
+    /// report the firmware, which is the case for most virtual devices.
+    pub fn port_fw_version(&self, port_id: u16) -> Result<String> {
+        let _inner = self.try_lock()?;
+
+        // The first call returns the buffer size required by the version,
+        // including the terminating nul.
+        let res = unsafe { ffi::rte_eth_dev_fw_version_get(port_id, std::ptr::null_mut(), 0) };
+        if res < 0 {
+            return Error::ffi_err(res, "fail to get eth dev fw version").to_err();
+        }
+        if res == 0 {
+            return Ok(String::new());
+        }
+
+        let mut version: Vec<c_char> = vec![0; res as usize];
+        let res = unsafe {
+            ffi::rte_eth_dev_fw_version_get(port_id, version.as_mut_ptr(), version.len())
+        };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to get eth dev fw version").to_err();
+        }
+
+        let version = unsafe { CStr::from_ptr(version.as_ptr()) };
+        Ok(version.to_str().unwrap_or("").to_owned())
+    }
+
+    /// Create a virtual ethernet device with the vdev `name` and the device
+    /// arguments `args`, and return the id of the new port.
+    ///
+    /// This is mostly used to hand packets off to the kernel stack, e.g. with a
+    /// `net_tap0` device and `iface=dtap0` arguments, or a `virtio_user0` device
+    /// and `path=/dev/vhost-net,iface=dtap0` arguments. The new port is then
+    /// configured with `port_configure` like any other port: the packets sent to
+    /// its tx queues are delivered to the kernel, and the packets sent by the
+    /// kernel are received from its rx queues.
+    pub fn port_vdev_add(&self, name: &str, args: &str) -> Result<u16> {
+        let _inner = self.try_lock()?;
+
+        let bus_name = CString::new("vdev").unwrap();
+        let dev_name = CString::new(name).map_err(|_| Error::service_err("invalid vdev name"))?;
+        let dev_args = CString::new(args).map_err(|_| Error::service_err("invalid vdev args"))?;
+        let res = unsafe {
+            ffi::rte_eal_hotplug_add(bus_name.as_ptr(), dev_name.as_ptr(), dev_args.as_ptr())
+        };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to add vdev").to_err();
+        }
+
+        let mut port_id: u16 = 0;
+        let res = unsafe { ffi::rte_eth_dev_get_port_by_name(dev_name.as_ptr(), &mut port_id) };
+        if res != 0 {
+            // the vdev is not an ethernet device
+            unsafe { ffi::rte_eal_hotplug_remove(bus_name.as_ptr(), dev_name.as_ptr()) };
+            return Error::ffi_err(res, "vdev is not an eth dev").to_err();
+        }
+        Ok(port_id)
+    }
+
+    /// Remove the virtual ethernet device created by `port_vdev_add`.
+    ///
+    /// The port must be closed with `port_close` first if it is configured.
+    pub fn port_vdev_remove(&self, name: &str) -> Result<()> {
+        let inner = self.try_lock()?;
+
+        let bus_name = CString::new("vdev").unwrap();
+        let dev_name = CString::new(name).map_err(|_| Error::service_err("invalid vdev name"))?;
+        let mut port_id: u16 = 0;
+        let res = unsafe { ffi::rte_eth_dev_get_port_by_name(dev_name.as_ptr(), &mut port_id) };
+        if res != 0 {
+            return Error::ffi_err(res, "no such vdev").to_err();
+        }
+        if inner.ports.get(&port_id).is_some() {
+            return Error::service_err("port is in use").to_err();
+        }
+
+        let res = unsafe { ffi::rte_eal_hotplug_remove(bus_name.as_ptr(), dev_name.as_ptr()) };
+        if res != 0 {
+            return Error::ffi_err(res, "fail to remove vdev").to_err();
+        }
+        Ok(())
+    }
+
     pub fn port_info(&self, port_id: u16) -> Result<PortInfo> {
         let _inner = self.try_lock()?;
 
@@ -166,6 +535,40 @@ impl DpdkService {
         unsafe { PortInfo::try_get(port_id) }
     }
 
+    /// Get the flow control (pause frame) configuration of the port.
+    pub fn port_flow_ctrl(&self, port_id: u16) -> Result<FlowCtrlConf> {
+        let _inner = self.try_lock()?;
+        FlowCtrlConf::try_get(port_id)
+    }
+
+    /// Set the flow control (pause frame) configuration of the port.
+    ///
+    /// This returns an `ErrorKind::FFIError` with `-ENOTSUP` if the PMD does not
+    /// support flow control.
+    pub fn port_set_flow_ctrl(&self, port_id: u16, conf: &FlowCtrlConf) -> Result<()> {
+        let _inner = self.try_lock()?;
+        conf.apply(port_id)
+    }
+
+    /// Return the numbers of rx and tx descriptors adjusted to the limits of the
+    /// port, i.e. clamped to the supported range and rounded up to the alignment.
+    ///
+    /// The descriptor numbers passed to `port_configure` are adjusted in the
+    /// same way.
+    pub fn port_adjust_nb_desc(
+        &self,
+        port_id: u16,
+        nb_rx_desc: u16,
+        nb_tx_desc: u16,
+    ) -> Result<(u16, u16)> {
+        let _inner = self.try_lock()?;
+
+        if port_id >= unsafe { ffi::rte_eth_dev_count_avail() } {
+            return Err(Error::service_err("invalid port id"));
+        }
+        Port::adjust_nb_desc(port_id, nb_rx_desc, nb_tx_desc)
+    }
+
     // rte_eth_dev_configure
     pub fn port_configure(
         &self,
@@ -220,6 +623,134 @@ impl DpdkService {
         Ok(())
     }
 
+    /// Enable or disable the vlan strip/filter/extend offloads of a configured port.
+    pub fn port_set_vlan_offload(&self, port_id: u16, vlan_offload: VlanOffload) -> Result<()> {
+        let inner = self.try_lock()?;
+        let port = inner
+            .ports
+            .get(&port_id)
+            .ok_or(Error::service_err("invalid port id"))?;
+        port.set_vlan_offload(vlan_offload)
+    }
+
+    pub fn port_vlan_offload(&self, port_id: u16) -> Result<VlanOffload> {
+        let inner = self.try_lock()?;
+        let port = inner
+            .ports
+            .get(&port_id)
+            .ok_or(Error::service_err("invalid port id"))?;
+        port.vlan_offload()
+    }
+
+    /// Add (`on` is true) or remove (`on` is false) `vlan_id` from the vlan filter of
+    /// a configured port.
+    pub fn port_vlan_filter(&self, port_id: u16, vlan_id: u16, on: bool) -> Result<()> {
+        let inner = self.try_lock()?;
+        let port = inner
+            .ports
+            .get(&port_id)
+            .ok_or(Error::service_err("invalid port id"))?;
+        port.vlan_filter(vlan_id, on)
+    }
+
+    /// Return the rss redirection table of a configured port, i.e. the rx queue
+    /// of each hash bucket.
+    ///
+    /// A packet is received from the queue in the entry of index
+    /// `hash % reta.len()`, where `hash` is the rss hash of the packet, e.g. the
+    /// toeplitz hash computed by `rss::toeplitz_hash`.
+    pub fn port_rss_reta_query(&self, port_id: u16) -> Result<Vec<u16>> {
+        let inner = self.try_lock()?;
+        let port = inner
+            .ports
+            .get(&port_id)
+            .ok_or(Error::service_err("invalid port id"))?;
+        port.rss_reta_query()
+    }
+
+    /// Return whether the link of a configured port is up, without waiting for
+    /// the link to complete.
+    pub fn port_link_up(&self, port_id: u16) -> Result<bool> {
+        let inner = self.try_lock()?;
+        let port = inner
+            .ports
+            .get(&port_id)
+            .ok_or(Error::service_err("invalid port id"))?;
+        port.link_up()
+    }
+
+    /// Wait for the link of a configured port to come up, polling the link
+    /// status with `policy`.
+    ///
+    /// Return `false` if the link is still down after `policy.max_attempts`
+    /// polls. The service is not locked between two polls.
+    pub fn port_wait_link_up(&self, port_id: u16, policy: &RetryPolicy) -> Result<bool> {
+        let res = policy.retry(|| match self.port_link_up(port_id) {
+            Ok(false) => None,
+            res => Some(res),
+        });
+        res.unwrap_or(Ok(false))
+    }
+
+    /// Map the rx queue `qid` of a configured port to the per-queue stats counter
+    /// `stat_idx`, which must be less than `PortStats::QUEUE_STAT_CNTRS`.
+    ///
+    /// Many NICs leave the per-queue counters unmapped by default. This returns an
+    /// `ErrorKind::FFIError` with `-ENOTSUP` if the PMD does not support the mapping,
+    /// e.g. the virtual devices that always map a queue to the counter of the same
+    /// index.
+    pub fn port_set_rx_queue_stats_mapping(
+        &self,
+        port_id: u16,
+        qid: u16,
+        stat_idx: u8,
+    ) -> Result<()> {
+        let inner = self.try_lock()?;
+        let port = inner
+            .ports
+            .get(&port_id)
+            .ok_or(Error::service_err("invalid port id"))?;
+        port.set_queue_stats_mapping(qid, stat_idx, true)
+    }
+
+    /// Map the tx queue `qid` of a configured port to the per-queue stats counter
+    /// `stat_idx`, see `port_set_rx_queue_stats_mapping`.
+    pub fn port_set_tx_queue_stats_mapping(
+        &self,
+        port_id: u16,
+        qid: u16,
+        stat_idx: u8,
+    ) -> Result<()> {
+        let inner = self.try_lock()?;
+        let port = inner
+            .ports
+            .get(&port_id)
+            .ok_or(Error::service_err("invalid port id"))?;
+        port.set_queue_stats_mapping(qid, stat_idx, false)
+    }
+
+    /// Limit the transmit rate of the tx queue `qid` of a started port to
+    /// `tx_rate_mbps` Mbps in the NIC, e.g. to cap the queue of a tenant.
+    ///
+    /// The rate must not exceed the speed of the link, which must be up, and a
+    /// rate of 0 removes the limit on most PMDs. This returns an
+    /// `ErrorKind::FFIError` with `-ENOTSUP` if the PMD does not support the
+    /// per-queue rate limits, e.g. the virtual devices, and with `-EINVAL` if
+    /// the rate exceeds the link speed.
+    pub fn port_set_queue_rate_limit(
+        &self,
+        port_id: u16,
+        qid: u16,
+        tx_rate_mbps: u32,
+    ) -> Result<()> {
+        let inner = self.try_lock()?;
+        let port = inner
+            .ports
+            .get(&port_id)
+            .ok_or(Error::service_err("invalid port id"))?;
+        port.set_queue_rate_limit(qid, tx_rate_mbps)
+    }
+
     pub fn rx_queue(&self, port_id: u16, qid: u16) -> Result<RxQueue> {
         let inner = self.service.lock().unwrap();
         let port = inner
@@ -288,3 +819,72 @@ pub fn service() -> &'static DpdkService {
         None => panic!("dpdk service is not initialized"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(config: &EalConfig) -> Vec<String> {
+        config
+            .clone()
+            .into_args()
+            .unwrap()
+            .into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn render_eal_args() {
+        assert_eq!(
+            render(&EalConfig::default()),
+            [
+                "./prefix",
+                "--lcores=0",
+                "--main-lcore=0",
+                "-n",
+                "4",
+                "--proc-type=primary"
+            ]
+        );
+
+        let mut config = EalConfig::default();
+        config.set_lcores(vec![3, 1, 0, 2, 6, 8, 9]);
+        config.set_main_lcore(1);
+        config.set_memory_channels(0);
+        config.set_socket_mem(vec![1024, 0]);
+        config.set_huge_dir(Some(PathBuf::from("/mnt/huge")));
+        config.set_proc_type(ProcType::Secondary);
+        config.set_file_prefix("wtf");
+        assert_eq!(
+            render(&config),
+            [
+                "./prefix",
+                "--lcores=0-3,6,8-9",
+                "--main-lcore=1",
+                "--socket-mem=1024,0",
+                "--huge-dir=/mnt/huge",
+                "--proc-type=secondary",
+                "--file-prefix=wtf"
+            ]
+        );
+
+        let mut config = EalConfig::default();
+        config.set_no_huge(true);
+        assert!(render(&config).contains(&"--no-huge".to_owned()));
+    }
+
+    #[test]
+    fn reject_invalid_eal_config() {
+        let mut config = EalConfig::default();
+        config.set_main_lcore(1);
+        assert!(config.clone().into_args().is_err());
+
+        config.set_lcores(Vec::new());
+        assert!(config.into_args().is_err());
+
+        let mut config = EalConfig::default();
+        config.set_file_prefix("w\0tf");
+        assert!(config.into_args().is_err());
+    }
+}
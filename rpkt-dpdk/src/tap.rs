@@ -0,0 +1,191 @@
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use arrayvec::ArrayVec;
+use rpkt::pcap::PcapWriter;
+
+use crate::error::*;
+use crate::proto::packet_data;
+use crate::{Mbuf, Ring};
+
+/// A debug tap that writes copies of the mbufs passing through a ring to a
+/// pcap capture, like a tcpdump on the ring.
+///
+/// One in every `every` mbufs is sampled, its data is copied to the capture
+/// and the mbuf itself flows on unchanged. The mbufs are tapped as they are
+/// dequeued with `dequeue_burst`, or at any other point of the pipeline with
+/// `tap_burst`. The enqueues are passed through to the ring.
+///
+/// The capture is written on the dequeuing lcore, so `W` should be buffered,
+/// and the tap is meant for the debugging of a stuck or misbehaving pipeline
+/// rather than for a permanent capture.
+pub struct RingTap<W: Write> {
+    ring: Ring,
+    writer: PcapWriter<W>,
+    every: u32,
+    // the number of the mbufs to skip before the next sample
+    skip: u32,
+    nb_tapped: u64,
+}
+
+impl<W: Write> RingTap<W> {
+    /// Tap `ring`, writing one in every `every` mbufs to `writer`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `every` is 0.
+    pub fn new(ring: Ring, writer: PcapWriter<W>, every: u32) -> Self {
+        assert!(every > 0, "invalid sampling rate");
+        Self {
+            ring,
+            writer,
+            every,
+            skip: 0,
+            nb_tapped: 0,
+        }
+    }
+
+    /// Change the sampling rate to one in every `every` mbufs, the next mbuf is
+    /// sampled.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `every` is 0.
+    pub fn set_sampling(&mut self, every: u32) {
+        assert!(every > 0, "invalid sampling rate");
+        self.every = every;
+        self.skip = 0;
+    }
+
+    /// Return the sampling rate, one in every returned number of mbufs.
+    pub fn sampling(&self) -> u32 {
+        self.every
+    }
+
+    /// Enqueue as many mbufs from `batch` as the ring can hold, see
+    /// `Ring::enqueue_burst`.
+    #[inline]
+    pub fn enqueue_burst<const N: usize>(&self, batch: &mut ArrayVec<Mbuf, N>) -> usize {
+        self.ring.enqueue_burst(batch)
+    }
+
+    /// Dequeue mbufs from the ring until `batch` is full or the ring is empty,
+    /// and tap the dequeued mbufs.
+    ///
+    /// The dequeued mbufs are left in `batch` even if the capture fails to be
+    /// written. Return the number of dequeued mbufs.
+    #[inline]
+    pub fn dequeue_burst<const N: usize>(
+        &mut self,
+        batch: &mut ArrayVec<Mbuf, N>,
+    ) -> Result<usize> {
+        let old_len = batch.len();
+        let nb_deq = self.ring.dequeue_burst(batch);
+        self.tap_burst(&batch[old_len..])?;
+        Ok(nb_deq)
+    }
+
+    /// Write the sampled mbufs of `mbufs` to the capture.
+    pub fn tap_burst(&mut self, mbufs: &[Mbuf]) -> Result<()> {
+        if mbufs.is_empty() {
+            return Ok(());
+        }
+
+        // the mbufs of a burst share the timestamp
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut mbufs = mbufs;
+        while let Some(mbuf) = mbufs.get(self.skip as usize) {
+            self.writer
+                .write_packet(now.as_secs() as u32, now.subsec_nanos(), &packet_data(mbuf))
+                .map_err(|_| Error::service_err("fail to write pcap"))?;
+            self.nb_tapped += 1;
+            mbufs = &mbufs[self.skip as usize + 1..];
+            self.skip = self.every - 1;
+        }
+        // the mbufs after the last sample count towards the next one
+        self.skip -= mbufs.len() as u32;
+        Ok(())
+    }
+
+    /// Return the number of the mbufs written to the capture.
+    pub fn nb_tapped(&self) -> u64 {
+        self.nb_tapped
+    }
+
+    /// Return the tapped ring.
+    pub fn ring(&self) -> &Ring {
+        &self.ring
+    }
+
+    /// Flush the capture and return its writer.
+    pub fn into_writer(mut self) -> Result<PcapWriter<W>> {
+        self.writer
+            .flush()
+            .map_err(|_| Error::service_err("fail to write pcap"))?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    use rpkt::pcap::PcapReader;
+
+    #[test]
+    fn tap_one_in_two() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+            let ring = Ring::try_create(
+                "tapped".to_string(),
+                &RingConf {
+                    count: 128,
+                    socket_id: 0,
+                    flag: RingFlags::SP_ENQ | RingFlags::SC_DEQ,
+                },
+            )
+            .unwrap();
+            let writer = PcapWriter::new(Vec::new(), 65535).unwrap();
+            let mut tap = RingTap::new(ring, writer, 2);
+
+            // forward 100 mbufs through the ring in odd-sized bursts
+            let mut batch = ArrayVec::<_, 32>::new();
+            let mut forwarded = Vec::new();
+            for i in 0..100u8 {
+                let mut mbuf = mp.try_alloc().unwrap();
+                mbuf.extend_from_slice(&[i; 64][..]);
+                batch.push(mbuf);
+                if batch.len() == 7 || i == 99 {
+                    let nb_enq = batch.len();
+                    assert_eq!(tap.enqueue_burst(&mut batch), nb_enq);
+                    while tap.dequeue_burst(&mut batch).unwrap() > 0 {
+                        forwarded.extend(batch.drain(..).map(|mbuf| mbuf.data()[0]));
+                    }
+                }
+            }
+            // the originals flow on untouched
+            assert_eq!(forwarded, (0..100).collect::<Vec<_>>());
+            assert_eq!(tap.nb_tapped(), 50);
+
+            let bytes = tap.into_writer().unwrap().into_inner();
+            let records: Vec<_> = PcapReader::new(&bytes[..])
+                .unwrap()
+                .map(|record| record.unwrap())
+                .collect();
+            assert_eq!(records.len(), 50);
+            for (idx, record) in records.iter().enumerate() {
+                assert_eq!(&record.data[..], &[idx as u8 * 2; 64][..]);
+            }
+            assert_eq!(mp.nb_mbufs(), 128);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+}
@@ -0,0 +1,64 @@
+use std::os::raw::c_int;
+
+use once_cell::sync::OnceCell;
+use rpkt_dpdk_sys as ffi;
+
+use crate::error::*;
+
+static RX_TIMESTAMP: OnceCell<DynTimestamp> = OnceCell::new();
+static TX_TIMESTAMP: OnceCell<DynTimestamp> = OnceCell::new();
+
+// The offset of the timestamp dynamic field and the dynamic flag that marks a
+// valid timestamp.
+//
+// The rx and tx timestamps share the same field, but use different flags. The
+// registration is idempotent and is also performed by the PMDs that support the
+// timestamp offloads, so the registered field is cached after the first success.
+pub(crate) struct DynTimestamp {
+    offset: usize,
+    flag: u64,
+}
+
+impl DynTimestamp {
+    pub(crate) fn rx() -> Result<&'static Self> {
+        RX_TIMESTAMP.get_or_try_init(|| {
+            let (mut offset, mut flag): (c_int, u64) = (0, 0);
+            let res = unsafe { ffi::rte_mbuf_dyn_rx_timestamp_register(&mut offset, &mut flag) };
+            Self::try_create(res, offset, flag, "fail to register rx timestamp dynfield")
+        })
+    }
+
+    pub(crate) fn tx() -> Result<&'static Self> {
+        TX_TIMESTAMP.get_or_try_init(|| {
+            let (mut offset, mut flag): (c_int, u64) = (0, 0);
+            let res = unsafe { ffi::rte_mbuf_dyn_tx_timestamp_register(&mut offset, &mut flag) };
+            Self::try_create(res, offset, flag, "fail to register tx timestamp dynfield")
+        })
+    }
+
+    fn try_create(res: c_int, offset: c_int, flag: u64, msg: &'static str) -> Result<Self> {
+        if res != 0 {
+            return Error::ffi_err(unsafe { ffi::rte_errno_() }, msg).to_err();
+        }
+        Ok(Self {
+            offset: offset as usize,
+            flag,
+        })
+    }
+
+    // Safety: `mbuf` must point to a valid mbuf.
+    #[inline]
+    pub(crate) unsafe fn read(&self, mbuf: *const ffi::rte_mbuf) -> Option<u64> {
+        if (*mbuf).ol_flags & self.flag == 0 {
+            return None;
+        }
+        Some(((mbuf as *const u8).add(self.offset) as *const u64).read())
+    }
+
+    // Safety: `mbuf` must point to a valid mbuf.
+    #[inline]
+    pub(crate) unsafe fn write(&self, mbuf: *mut ffi::rte_mbuf, value: u64) {
+        ((mbuf as *mut u8).add(self.offset) as *mut u64).write(value);
+        (*mbuf).ol_flags |= self.flag;
+    }
+}
@@ -0,0 +1,231 @@
+//! The `tracing` instrumentation of the bursts and the pipeline stages.
+//!
+//! This module is enabled by the `trace` feature. Without the feature, the
+//! instrumentation is not compiled at all. All the spans are at the `TRACE`
+//! level:
+//!
+//! - `rx_burst`, for each `RxQueue::rx`, with the fields `port`, `queue`,
+//!   `packets` and `bytes` of the received mbufs, and `dropped`, the number of
+//!   the mbufs dropped by the rx callbacks.
+//! - `tx_burst`, for each `TxQueue::tx`, with the fields `port`, `queue`,
+//!   `packets` and `bytes` of the sent mbufs, and `dropped`, the number of the
+//!   mbufs that are not accepted by the device and are left in the batch.
+//! - `stage`, for each stage run by `Pipeline`, with the fields `index` of the
+//!   stage and `packets` of its input.
+//!
+//! The bytes are only counted if a subscriber is interested in the span.
+
+use tracing::field::Empty;
+use tracing::Span;
+
+use crate::Mbuf;
+
+#[inline]
+pub(crate) fn rx_burst(port: u16, queue: u16) -> Span {
+    tracing::trace_span!(
+        "rx_burst",
+        port,
+        queue,
+        packets = Empty,
+        bytes = Empty,
+        dropped = Empty
+    )
+}
+
+#[inline]
+pub(crate) fn tx_burst(port: u16, queue: u16) -> Span {
+    tracing::trace_span!(
+        "tx_burst",
+        port,
+        queue,
+        packets = Empty,
+        bytes = Empty,
+        dropped = Empty
+    )
+}
+
+#[inline]
+pub(crate) fn stage(index: usize, packets: usize) -> Span {
+    tracing::trace_span!("stage", index, packets)
+}
+
+// Return the total length of `mbufs`, or 0 if `span` is disabled.
+#[inline]
+pub(crate) fn burst_bytes(span: &Span, mbufs: &[Mbuf]) -> u64 {
+    if span.is_disabled() {
+        return 0;
+    }
+    mbufs.iter().map(|mbuf| mbuf.total_len() as u64).sum()
+}
+
+#[inline]
+pub(crate) fn record_burst(span: &Span, packets: usize, bytes: u64, dropped: usize) {
+    span.record("packets", packets);
+    span.record("bytes", bytes);
+    span.record("dropped", dropped);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+
+    use arrayvec::ArrayVec;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    use crate::pipeline::*;
+    use crate::*;
+
+    #[derive(Debug, Default)]
+    struct SpanRecord {
+        name: &'static str,
+        fields: HashMap<&'static str, u64>,
+    }
+
+    impl Visit for SpanRecord {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            self.fields.insert(field.name(), value);
+        }
+
+        fn record_debug(&mut self, _field: &Field, _value: &dyn Debug) {}
+    }
+
+    // A subscriber that keeps the numeric fields of all the spans.
+    #[derive(Clone, Default)]
+    struct Collector {
+        spans: Arc<Mutex<Vec<SpanRecord>>>,
+    }
+
+    impl Collector {
+        fn spans(&self, name: &str) -> Vec<HashMap<&'static str, u64>> {
+            let spans = self.spans.lock().unwrap();
+            spans
+                .iter()
+                .filter(|span| span.name == name)
+                .map(|span| span.fields.clone())
+                .collect()
+        }
+    }
+
+    impl Subscriber for Collector {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+            let mut span = SpanRecord {
+                name: attrs.metadata().name(),
+                fields: HashMap::new(),
+            };
+            attrs.record(&mut span);
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(span);
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, id: &Id, values: &Record<'_>) {
+            let mut spans = self.spans.lock().unwrap();
+            values.record(&mut spans[id.into_u64() as usize - 1]);
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, _event: &Event<'_>) {}
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn pipeline_stage_spans() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let mut pipeline = Pipeline::<32>::new();
+            pipeline.add_stage(FilterStage::new(|mbuf: &Mbuf| mbuf.len() % 2 == 0));
+            pipeline.add_stage(CountStage::new());
+
+            let collector = Collector::default();
+            tracing::subscriber::with_default(collector.clone(), || {
+                let mut input = ArrayVec::<_, 32>::new();
+                mp.fill_batch(&mut input);
+                for (i, mbuf) in input.iter_mut().enumerate() {
+                    mbuf.extend_from_slice(&vec![0; i + 1][..]);
+                }
+                let mut output = ArrayVec::new();
+                pipeline.process(&mut input, &mut output);
+            });
+
+            let stages = collector.spans("stage");
+            assert_eq!(stages.len(), 2);
+            assert_eq!(stages[0]["index"], 0);
+            assert_eq!(stages[0]["packets"], 32);
+            assert_eq!(stages[1]["index"], 1);
+            assert_eq!(stages[1]["packets"], 16);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn burst_spans() {
+        DpdkOption::new().init().unwrap();
+
+        let port_id = service().port_vdev_add("net_null_trace", "").unwrap();
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        utils::init_port(port_id, 1, 1, 512, "wtf", 512, 0).unwrap();
+
+        {
+            let mp = service().mempool("wtf").unwrap();
+            let mut rxq = service().rx_queue(port_id, 0).unwrap();
+            let mut txq = service().tx_queue(port_id, 0).unwrap();
+            // keep the first half of the burst
+            let _cb = rxq.add_callback(|mbufs| mbufs.len() / 2).unwrap();
+
+            let collector = Collector::default();
+            tracing::subscriber::with_default(collector.clone(), || {
+                let mut batch = ArrayVec::<Mbuf, 32>::new();
+                rxq.rx(&mut batch);
+                batch.clear();
+
+                mp.fill_batch(&mut batch);
+                for mbuf in batch.iter_mut() {
+                    mbuf.extend_from_slice(&[0; 100][..]);
+                }
+                while !batch.is_empty() {
+                    txq.tx(&mut batch);
+                }
+            });
+
+            let rx = collector.spans("rx_burst");
+            assert_eq!(rx.len(), 1);
+            assert_eq!(rx[0]["port"], u64::from(port_id));
+            assert_eq!(rx[0]["queue"], 0);
+            assert!(rx[0]["packets"] > 0);
+            let nb_rx = rx[0]["packets"] + rx[0]["dropped"];
+            assert_eq!(rx[0]["packets"], nb_rx / 2);
+            // the null device receives 64-byte packets
+            assert_eq!(rx[0]["bytes"], rx[0]["packets"] * 64);
+
+            let tx = collector.spans("tx_burst");
+            assert!(!tx.is_empty());
+            let sent: u64 = tx.iter().map(|span| span["packets"]).sum();
+            let bytes: u64 = tx.iter().map(|span| span["bytes"]).sum();
+            assert_eq!(sent, 32);
+            assert_eq!(bytes, 32 * 100);
+            assert_eq!(tx[0]["dropped"], 32 - tx[0]["packets"]);
+        }
+
+        service().port_close(port_id).unwrap();
+        service().mempool_free("wtf").unwrap();
+        service().port_vdev_remove("net_null_trace").unwrap();
+    }
+}
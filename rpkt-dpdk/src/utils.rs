@@ -66,3 +66,34 @@ pub fn fill_mempool(mp_name: &str, packet_template: &[u8]) -> Result<()> {
 
     Ok(())
 }
+
+// Format `data` in the same way as `xxd`, with 16 bytes per line.
+pub(crate) fn hexdump(data: &[u8]) -> String {
+    use std::fmt::Write;
+
+    let mut s = String::new();
+    for (i, line) in data.chunks(16).enumerate() {
+        let _ = write!(s, "{:08x}:", i * 16);
+        for j in 0..16 {
+            if j % 2 == 0 {
+                s.push(' ');
+            }
+            match line.get(j) {
+                Some(byte) => {
+                    let _ = write!(s, "{byte:02x}");
+                }
+                None => s.push_str("  "),
+            }
+        }
+        s.push_str("  ");
+        for byte in line {
+            if byte.is_ascii_graphic() || *byte == b' ' {
+                s.push(char::from(*byte));
+            } else {
+                s.push('.');
+            }
+        }
+        s.push('\n');
+    }
+    s
+}
@@ -0,0 +1,246 @@
+use std::path::Path;
+
+use crate::error::*;
+use crate::service;
+
+/// A virtual device, i.e. the name of a software pmd device with its device
+/// arguments.
+///
+/// The arguments are rendered as the `key=value,...` string expected by the
+/// vdev bus, and the keys and the values are checked so that they can not
+/// break the string. The device is created with `add`, or it can be passed to
+/// the EAL with `--vdev` rendered by `devargs`.
+#[derive(Clone, Debug)]
+pub struct Vdev {
+    name: String,
+    args: Vec<(String, String)>,
+}
+
+impl Vdev {
+    /// A device of the `driver` pmd, which is named `driver` followed by `id`,
+    /// e.g. `net_null0` for the driver `net_null` and the id `0`.
+    pub fn new(driver: &str, id: &str) -> Self {
+        Self {
+            name: format!("{}{}", driver, id),
+            args: Vec::new(),
+        }
+    }
+
+    /// A `net_null` device, which drops the sent packets and receives
+    /// packets of a fixed size.
+    pub fn null(id: &str) -> Self {
+        Self::new("net_null", id)
+    }
+
+    /// A `net_pcap` device, which reads the received packets from and writes
+    /// the sent packets to pcap files or interfaces.
+    pub fn pcap(id: &str) -> Self {
+        Self::new("net_pcap", id)
+    }
+
+    /// A `net_ring` device, which is backed by a pair of rings.
+    pub fn ring(id: &str) -> Self {
+        Self::new("net_ring", id)
+    }
+
+    /// A `net_af_xdp` device, which receives and sends on the queues of a
+    /// kernel interface through AF_XDP sockets, so the interface needs not be
+    /// bound to a dpdk driver.
+    ///
+    /// The pmd is only built if libxdp or libbpf is found when dpdk is built.
+    /// The interface is set by `set_iface`, and the device requires the
+    /// `CAP_NET_RAW` and `CAP_BPF` capabilities, a kernel of 5.4 or later, and
+    /// a locked memory limit that fits the umem. The queues of the interface
+    /// should be reduced to the ones used by the device, e.g. with
+    /// `ethtool -L <iface> combined 1`, as the packets steered to the other
+    /// queues are not received.
+    pub fn af_xdp(id: &str) -> Self {
+        Self::new("net_af_xdp", id)
+    }
+
+    /// A `net_af_packet` device, which receives and sends on a kernel
+    /// interface through AF_PACKET sockets.
+    ///
+    /// It is slower than `af_xdp`, but works on any interface, including the
+    /// loopback, with only the `CAP_NET_RAW` capability. The interface is set
+    /// by `set_iface`.
+    pub fn af_packet(id: &str) -> Self {
+        Self::new("net_af_packet", id)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Set the device argument `key` to `val`, replacing the previous value.
+    pub fn set_arg<S: AsRef<str>>(&mut self, key: &str, val: S) {
+        let val = val.as_ref().to_string();
+        match self.args.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = val,
+            None => self.args.push((key.to_string(), val)),
+        }
+    }
+
+    /// Set the size of the packets received by a `net_null` device.
+    pub fn set_size(&mut self, val: u32) {
+        self.set_arg("size", val.to_string());
+    }
+
+    /// Set whether a `net_null` device copies the packet data.
+    pub fn set_copy(&mut self, val: bool) {
+        self.set_arg("copy", if val { "1" } else { "0" });
+    }
+
+    /// Set the pcap file that a `net_pcap` device receives packets from.
+    pub fn set_rx_pcap<P: AsRef<Path>>(&mut self, val: P) {
+        self.set_arg("rx_pcap", val.as_ref().to_string_lossy());
+    }
+
+    /// Set the pcap file that a `net_pcap` device writes the sent packets to.
+    pub fn set_tx_pcap<P: AsRef<Path>>(&mut self, val: P) {
+        self.set_arg("tx_pcap", val.as_ref().to_string_lossy());
+    }
+
+    /// Set the interface that a `net_pcap`, `net_af_xdp` or `net_af_packet`
+    /// device receives and sends on.
+    pub fn set_iface(&mut self, val: &str) {
+        self.set_arg("iface", val);
+    }
+
+    /// Set the first queue of the interface used by a `net_af_xdp` device.
+    pub fn set_start_queue(&mut self, val: u16) {
+        self.set_arg("start_queue", val.to_string());
+    }
+
+    /// Set the number of the queues of the interface used by a `net_af_xdp`
+    /// device, starting from the start queue.
+    pub fn set_queue_count(&mut self, val: u16) {
+        self.set_arg("queue_count", val.to_string());
+    }
+
+    /// Set the number of the queue pairs of a `net_af_packet` device.
+    pub fn set_qpairs(&mut self, val: u16) {
+        self.set_arg("qpairs", val.to_string());
+    }
+
+    /// Render the device arguments, e.g. `size=64,copy=0`.
+    ///
+    /// Return an error if a key or a value is empty, or contains any of the
+    /// separators `,` `=` or a nul.
+    pub fn args(&self) -> Result<String> {
+        let invalid = |s: &str| s.is_empty() || s.contains([',', '=', '\0']);
+        let mut args = Vec::with_capacity(self.args.len());
+        for (key, val) in self.args.iter() {
+            if invalid(key) || invalid(val) {
+                return Error::service_err("invalid vdev args").to_err();
+            }
+            args.push(format!("{}={}", key, val));
+        }
+        Ok(args.join(","))
+    }
+
+    /// Render the name and the arguments for the `--vdev` EAL argument, e.g.
+    /// `net_null0,size=64`.
+    pub fn devargs(&self) -> Result<String> {
+        let args = self.args()?;
+        if args.is_empty() {
+            Ok(self.name.clone())
+        } else {
+            Ok(format!("{},{}", self.name, args))
+        }
+    }
+
+    /// Create the device and return the id of its port, see
+    /// `DpdkService::port_vdev_add`.
+    pub fn add(&self) -> Result<u16> {
+        service().port_vdev_add(&self.name, &self.args()?)
+    }
+
+    /// Remove the device, see `DpdkService::port_vdev_remove`.
+    pub fn remove(&self) -> Result<()> {
+        service().port_vdev_remove(&self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn render_vdev_args() {
+        let mut vdev = Vdev::pcap("0");
+        assert_eq!(vdev.name(), "net_pcap0");
+        assert_eq!(vdev.devargs().unwrap(), "net_pcap0");
+        vdev.set_rx_pcap("/tmp/in.pcap");
+        vdev.set_tx_pcap("/tmp/out.pcap");
+        assert_eq!(
+            vdev.args().unwrap(),
+            "rx_pcap=/tmp/in.pcap,tx_pcap=/tmp/out.pcap"
+        );
+        vdev.set_rx_pcap("/tmp/other.pcap");
+        assert_eq!(
+            vdev.devargs().unwrap(),
+            "net_pcap0,rx_pcap=/tmp/other.pcap,tx_pcap=/tmp/out.pcap"
+        );
+
+        let mut vdev = Vdev::null("0");
+        vdev.set_size(64);
+        vdev.set_copy(false);
+        assert_eq!(vdev.devargs().unwrap(), "net_null0,size=64,copy=0");
+
+        let mut vdev = Vdev::af_xdp("0");
+        vdev.set_iface("eth0");
+        vdev.set_start_queue(2);
+        vdev.set_queue_count(1);
+        assert_eq!(
+            vdev.devargs().unwrap(),
+            "net_af_xdp0,iface=eth0,start_queue=2,queue_count=1"
+        );
+
+        for (key, val) in [("iface", "a,b"), ("iface", "a=b"), ("iface", ""), ("", "a")] {
+            let mut vdev = Vdev::pcap("0");
+            vdev.set_arg(key, val);
+            assert!(vdev.args().is_err());
+        }
+    }
+
+    #[test]
+    fn add_null_vdev() {
+        DpdkOption::new().init().unwrap();
+
+        let mut vdev = Vdev::null("_vdev_test");
+        vdev.set_size(128);
+        vdev.set_copy(false);
+        let port_id = vdev.add().unwrap();
+        assert!(service().port_ids().unwrap().contains(&port_id));
+        assert_eq!(service().port_name(port_id).unwrap(), "net_null_vdev_test");
+        assert!(vdev.add().is_err());
+
+        vdev.remove().unwrap();
+        assert!(!service().port_ids().unwrap().contains(&port_id));
+
+        let mut vdev = Vdev::null("_vdev_invalid");
+        vdev.set_iface("a,b");
+        assert_eq!(vdev.add().err().unwrap().kind(), ErrorKind::ServiceError);
+    }
+
+    // This test requires the af_packet pmd and the `CAP_NET_RAW` capability.
+    #[test]
+    fn add_af_packet_vdev() {
+        DpdkOption::new().init().unwrap();
+
+        let mut vdev = Vdev::af_packet("_vdev_test");
+        vdev.set_iface("lo");
+        vdev.set_qpairs(1);
+        let port_id = vdev.add().unwrap();
+        assert!(service().port_ids().unwrap().contains(&port_id));
+        assert_eq!(
+            service().port_name(port_id).unwrap(),
+            "net_af_packet_vdev_test"
+        );
+
+        vdev.remove().unwrap();
+        assert!(!service().port_ids().unwrap().contains(&port_id));
+    }
+}
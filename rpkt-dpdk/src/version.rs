@@ -0,0 +1,91 @@
+use std::ffi::CStr;
+use std::fmt;
+
+use rpkt_dpdk_sys as ffi;
+
+/// The version of the linked dpdk library, e.g. 22.11.1.
+///
+/// The versions are ordered by the major, the minor and the patch numbers, so
+/// they can be compared directly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DpdkVersion {
+    /// The year of the release.
+    pub major: u8,
+    /// The month of the release.
+    pub minor: u8,
+    pub patch: u8,
+}
+
+impl DpdkVersion {
+    /// Return the version number in the format of the `RTE_VERSION_NUM` macro
+    /// with a release of 0, e.g. `0x160b0100` for 22.11.1.
+    pub fn num(&self) -> u32 {
+        (u32::from(self.major) << 24) | (u32::from(self.minor) << 16) | (u32::from(self.patch) << 8)
+    }
+
+    // Parse the string returned by `rte_version`, e.g. "DPDK 22.11.1" or
+    // "DPDK 23.07.0-rc2".
+    fn parse(s: &str) -> Option<Self> {
+        let mut nums = s.strip_prefix("DPDK ")?.splitn(3, '.').map(|num| {
+            let end = num.find(|c: char| !c.is_ascii_digit()).unwrap_or(num.len());
+            num[..end].parse::<u8>().ok()
+        });
+        Some(Self {
+            major: nums.next()??,
+            minor: nums.next()??,
+            patch: nums.next()??,
+        })
+    }
+}
+
+impl fmt::Display for DpdkVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:02}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Return the version of the linked dpdk library, which is parsed from
+/// `rte_version`. It is available before the EAL is initialized.
+pub fn version() -> DpdkVersion {
+    let s = unsafe { CStr::from_ptr(ffi::rte_version_()) };
+    s.to_str()
+        .ok()
+        .and_then(DpdkVersion::parse)
+        .expect("invalid dpdk version string")
+}
+
+/// Return the version number of the linked dpdk library, see
+/// `DpdkVersion::num`.
+pub fn version_num() -> u32 {
+    version().num()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_version_string() {
+        let version = DpdkVersion::parse("DPDK 22.11.1").unwrap();
+        assert_eq!((version.major, version.minor, version.patch), (22, 11, 1));
+        assert_eq!(version.num(), 0x160b0100);
+        assert_eq!(version.to_string(), "22.11.1");
+
+        let rc = DpdkVersion::parse("DPDK 23.07.0-rc2").unwrap();
+        assert_eq!(rc.to_string(), "23.07.0");
+        assert!(rc > version);
+        assert!(rc.num() > version.num());
+
+        assert_eq!(DpdkVersion::parse("22.11.1"), None);
+        assert_eq!(DpdkVersion::parse("DPDK 22.11"), None);
+        assert_eq!(DpdkVersion::parse("DPDK a.b.c"), None);
+    }
+
+    #[test]
+    fn linked_version() {
+        let version = version();
+        assert!(version.major >= 20);
+        assert!((1..=12).contains(&version.minor));
+        assert_eq!(version_num(), version.num());
+    }
+}
@@ -0,0 +1,260 @@
+use std::ops::Deref;
+
+use rpkt::{parse_untrusted, ParseResult};
+
+use crate::error::*;
+use crate::{Ipv4Reassembler, Mbuf, ReassemblyConf};
+
+/// Linearize `mbuf` and parse all the layers of the packet with
+/// `rpkt::parse_untrusted`.
+///
+/// Return an error if `mbuf` can not be linearized, see `Mbuf::linearize`.
+pub fn parse_linearized(mbuf: &mut Mbuf) -> Result<ParseResult> {
+    mbuf.linearize()?;
+    Ok(parse_untrusted(mbuf.data()))
+}
+
+/// The bytes of a header read by `SegmentedPacketView::header`.
+///
+/// The bytes are borrowed from the mbuf if the header is in a single segment,
+/// and copied into a stack buffer if the header straddles a segment boundary.
+#[derive(Debug, Clone, Copy)]
+pub enum HeaderBytes<'a, const N: usize> {
+    Borrowed(&'a [u8]),
+    Copied([u8; N]),
+}
+
+impl<'a, const N: usize> Deref for HeaderBytes<'a, N> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Borrowed(data) => data,
+            Self::Copied(data) => &data[..],
+        }
+    }
+}
+
+/// A read-only view of the packet data in the segments of an mbuf.
+///
+/// Unlike `Mbuf::data`, which only covers the first segment, the view reads the
+/// headers at any offset of the packet without changing the mbuf.
+pub struct SegmentedPacketView<'a> {
+    mbuf: &'a Mbuf,
+}
+
+impl<'a> SegmentedPacketView<'a> {
+    pub fn new(mbuf: &'a Mbuf) -> Self {
+        Self { mbuf }
+    }
+
+    /// Return the total length of the packet.
+    pub fn len(&self) -> usize {
+        self.mbuf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mbuf.len() == 0
+    }
+
+    /// Return the `N` bytes at `offset` of the packet.
+    ///
+    /// Return `None` if the packet is shorter than `offset + N`.
+    pub fn header<const N: usize>(&self, offset: usize) -> Option<HeaderBytes<'a, N>> {
+        if offset.checked_add(N)? > self.len() {
+            return None;
+        }
+        if N == 0 {
+            return Some(HeaderBytes::Borrowed(&[]));
+        }
+
+        let mut segs = self.mbuf.seg_iter();
+        // the offset of `offset` in the segment that contains it
+        let mut seg_offset = offset;
+        let seg = loop {
+            let seg = segs.next()?;
+            if seg_offset < seg.len() {
+                break seg;
+            }
+            seg_offset -= seg.len();
+        };
+        if seg_offset + N <= seg.len() {
+            return Some(HeaderBytes::Borrowed(&seg[seg_offset..seg_offset + N]));
+        }
+
+        // the header straddles the segment boundary
+        let mut buf = [0; N];
+        let mut copied = seg.len() - seg_offset;
+        buf[..copied].copy_from_slice(&seg[seg_offset..]);
+        while copied < N {
+            let seg = segs.next()?;
+            let len = std::cmp::min(seg.len(), N - copied);
+            buf[copied..copied + len].copy_from_slice(&seg[..len]);
+            copied += len;
+        }
+        Some(HeaderBytes::Copied(buf))
+    }
+}
+
+/// A view of the packets that reassembles the ipv4 fragments first, so that
+/// the view always covers the whole datagram.
+///
+/// The packet of the last view stays in the `ReassemblingView` until it's
+/// taken or the next packet is pushed.
+pub struct ReassemblingView {
+    reassembler: Ipv4Reassembler,
+    packet: Option<Mbuf>,
+}
+
+impl ReassemblingView {
+    pub fn new(conf: &ReassemblyConf) -> Self {
+        Self {
+            reassembler: Ipv4Reassembler::new(conf),
+            packet: None,
+        }
+    }
+
+    /// Feed the frame in `mbuf`, and return a view of the packet once it is
+    /// whole.
+    ///
+    /// A frame that is not a fragment is viewed at once, while `None` is
+    /// returned for a fragment until its datagram is complete, see
+    /// `Ipv4Reassembler::push`.
+    pub fn push(&mut self, mbuf: Mbuf) -> Option<SegmentedPacketView<'_>> {
+        self.packet = self.reassembler.push(mbuf);
+        self.packet.as_ref().map(SegmentedPacketView::new)
+    }
+
+    /// Take the packet of the last view.
+    pub fn take(&mut self) -> Option<Mbuf> {
+        self.packet.take()
+    }
+
+    pub fn reassembler(&self) -> &Ipv4Reassembler {
+        &self.reassembler
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::proto::{checksum, fold, fragment, l4_frame};
+    use crate::*;
+    use rpkt::ether::{EtherPacket, ETHER_HEADER_LEN, ETHER_HEADER_TEMPLATE};
+    use rpkt::ipv4::{IpProtocol, Ipv4Packet, IPV4_HEADER_LEN, IPV4_HEADER_TEMPLATE};
+    use rpkt::tcp::{TcpHeader, TcpPacket, TCP_HEADER_LEN, TCP_HEADER_TEMPLATE};
+    use rpkt::udp::{UdpHeader, UDP_HEADER_LEN};
+    use rpkt::untrusted::Transport;
+    use rpkt::{Buf, CursorMut};
+
+    #[test]
+    fn tcp_header_across_segments() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let headers_len = ETHER_HEADER_LEN + IPV4_HEADER_LEN + TCP_HEADER_LEN;
+            let mut bytes = vec![0xab; headers_len + 100];
+            let mut buf = CursorMut::new(&mut bytes[..]);
+            buf.advance(headers_len);
+            let mut tcppkt = TcpPacket::prepend_header(buf, &TCP_HEADER_TEMPLATE);
+            tcppkt.set_src_port(50000);
+            tcppkt.set_dst_port(443);
+            let mut ipheader = IPV4_HEADER_TEMPLATE;
+            ipheader.set_protocol(IpProtocol::TCP);
+            let mut ippkt = Ipv4Packet::prepend_header(tcppkt.release(), &ipheader);
+            ippkt.adjust_checksum();
+            EtherPacket::prepend_header(ippkt.release(), &ETHER_HEADER_TEMPLATE);
+
+            // the first segment ends in the middle of the tcp header
+            let l4_offset = ETHER_HEADER_LEN + IPV4_HEADER_LEN;
+            let split = l4_offset + 3;
+            let mut mbuf = mp.try_alloc().unwrap();
+            mbuf.extend_from_slice(&bytes[..split]);
+            let mut seg = mp.try_alloc().unwrap();
+            seg.extend_from_slice(&bytes[split..]);
+            mbuf.chain(seg);
+            assert_eq!(mbuf.num_segs(), 2);
+
+            let view = SegmentedPacketView::new(&mbuf);
+            assert_eq!(view.len(), bytes.len());
+            let header = view.header::<TCP_HEADER_LEN>(l4_offset).unwrap();
+            assert!(matches!(header, HeaderBytes::Copied(_)));
+            assert_eq!(&header[..], &bytes[l4_offset..headers_len]);
+            let tcpheader = TcpHeader::new(&header[..]).unwrap();
+            assert_eq!(tcpheader.src_port(), 50000);
+            assert_eq!(tcpheader.dst_port(), 443);
+
+            // the headers within a single segment are borrowed
+            let header = view.header::<IPV4_HEADER_LEN>(ETHER_HEADER_LEN).unwrap();
+            assert!(matches!(header, HeaderBytes::Borrowed(_)));
+            let header = view.header::<4>(headers_len).unwrap();
+            assert!(matches!(header, HeaderBytes::Borrowed(_)));
+            assert_eq!(&header[..], &[0xab; 4][..]);
+            assert!(view.header::<4>(bytes.len() - 3).is_none());
+            assert!(view.header::<0>(bytes.len()).is_some());
+
+            // the first segment only carries a partial tcp header
+            assert!(parse_untrusted(mbuf.data()).is_err());
+            let parsed = parse_linearized(&mut mbuf).unwrap().unwrap();
+            assert_eq!(mbuf.num_segs(), 1);
+            assert_eq!(
+                parsed.transport,
+                Some(Transport::Tcp {
+                    src_port: 50000,
+                    dst_port: 443
+                })
+            );
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+
+    #[test]
+    fn view_reassembled_udp() {
+        DpdkOption::new().init().unwrap();
+
+        {
+            let mut config = MempoolConf::default();
+            config.nb_mbufs = 128;
+            let mp = service().mempool_create("wtf", &config).unwrap();
+
+            let l4_offset = ETHER_HEADER_LEN + IPV4_HEADER_LEN;
+            let mut view = ReassemblingView::new(&ReassemblyConf::new());
+            let (src, dst) = (Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2));
+            let payload: Vec<_> = (0..1200).map(|idx| idx as u8).collect();
+            // the fragments arrive in order, and then in reverse order
+            for (ident, first) in [(1, 0), (2, 1)] {
+                let frame = l4_frame(IpProtocol::UDP, src, dst, 12345, 53, ident, &payload[..]);
+                let fragments = fragment(&frame, &[600]);
+                let mbuf = Mbuf::from_slice(&fragments[first], &mp).unwrap();
+                assert!(view.push(mbuf).is_none());
+                assert_eq!(view.reassembler().nb_pending(), 1);
+
+                let mbuf = Mbuf::from_slice(&fragments[1 - first], &mp).unwrap();
+                let packet = view.push(mbuf).unwrap();
+                assert_eq!(packet.len(), frame.len());
+                let header = packet.header::<UDP_HEADER_LEN>(l4_offset).unwrap();
+                let udpheader = UdpHeader::new(&header[..]).unwrap();
+                assert_eq!(udpheader.dest_port(), 53);
+                assert_eq!(usize::from(udpheader.packet_len()), 1208);
+                let tail = packet.header::<8>(frame.len() - 8).unwrap();
+                assert_eq!(&tail[..], &frame[frame.len() - 8..]);
+
+                let mbuf = view.take().unwrap();
+                assert_eq!(mbuf.num_segs(), 2);
+                assert_eq!(&mbuf.copy_to_vec()[..], &frame[..]);
+                let header = &mbuf.data()[ETHER_HEADER_LEN..l4_offset];
+                assert_eq!(fold(checksum(header, 0)), 0xffff);
+            }
+            assert_eq!(view.reassembler().nb_pending(), 0);
+        }
+
+        service().mempool_free("wtf").unwrap();
+    }
+}
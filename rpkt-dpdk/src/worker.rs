@@ -0,0 +1,226 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use arrayvec::ArrayVec;
+
+use crate::error::*;
+use crate::{service, Backoff, Mbuf, Mempool, RxQueue, TxQueue};
+
+/// A run-to-completion poll loop over an rx queue and a tx queue of a port.
+///
+/// Each iteration receives a burst of up to `N` mbufs, passes the burst to the
+/// processing closure, and sends the mbufs that are left in the burst. The
+/// closure drops or keeps the mbufs by removing them from the burst.
+pub struct Worker<const N: usize> {
+    rxq: RxQueue,
+    txq: TxQueue,
+    idle_backoff: Backoff,
+    low_watermark: Option<LowWatermark>,
+}
+
+// The low watermark of the available mbufs of a mempool.
+struct LowWatermark {
+    mp: Mempool,
+    watermark: u32,
+    on_low: Box<dyn FnMut(u32) + Send>,
+    // Whether the mempool is below the watermark, the callback only fires again
+    // after the mempool recovers.
+    low: bool,
+}
+
+impl LowWatermark {
+    fn check(&mut self) {
+        let avail = self.mp.nb_mbufs();
+        if avail >= self.watermark {
+            self.low = false;
+        } else if !self.low {
+            self.low = true;
+            (self.on_low)(avail);
+        }
+    }
+}
+
+impl<const N: usize> Worker<N> {
+    /// The default backoff after an empty burst.
+    pub const IDLE_BACKOFF: Backoff = Backoff::Pause(1);
+
+    pub fn new(rxq: RxQueue, txq: TxQueue) -> Self {
+        Self {
+            rxq,
+            txq,
+            idle_backoff: Self::IDLE_BACKOFF,
+            low_watermark: None,
+        }
+    }
+
+    /// Create a worker over the rx queue and the tx queue `qid` of the port.
+    ///
+    /// The mbufs are allocated from the mempool of the rx queue, see
+    /// `RxQueueConf::set_mp_name`.
+    pub fn from_port(port_id: u16, qid: u16) -> Result<Self> {
+        let rxq = service().rx_queue(port_id, qid)?;
+        let txq = service().tx_queue(port_id, qid)?;
+        Ok(Self::new(rxq, txq))
+    }
+
+    /// Set the backoff after an empty burst, `Backoff::None` keeps the core
+    /// spinning on the rx queue.
+    pub fn set_idle_backoff(&mut self, val: Backoff) -> &mut Self {
+        self.idle_backoff = val;
+        self
+    }
+
+    /// Watch the available mbufs of `mp`, and call `on_low` with the number of
+    /// the available mbufs once they drop below `watermark`.
+    ///
+    /// When the mempool of the rx queue runs dry, the rx bursts get shorter and
+    /// the dropped packets are only counted by `rx_nombuf`. The watermark is
+    /// checked before each rx burst, so that the application can shed load
+    /// before that. The callback fires again only after the available mbufs
+    /// recover to the watermark, the current state is returned by
+    /// `mempool_low`. Reading the available mbufs walks the per-core caches of
+    /// the mempool, so the watermark adds a small cost to each burst.
+    pub fn set_low_watermark<C: FnMut(u32) + Send + 'static>(
+        &mut self,
+        mp: &Mempool,
+        watermark: u32,
+        on_low: C,
+    ) -> &mut Self {
+        self.low_watermark = Some(LowWatermark {
+            mp: mp.clone(),
+            watermark,
+            on_low: Box::new(on_low),
+            low: false,
+        });
+        self
+    }
+
+    /// Whether the available mbufs were below the low watermark at the last
+    /// check, see `set_low_watermark`.
+    pub fn mempool_low(&self) -> bool {
+        self.low_watermark.as_ref().is_some_and(|wm| wm.low)
+    }
+
+    /// Run the poll loop with `f` as the processing closure, until `stop` is set.
+    ///
+    /// The mbufs that can not be sent are dropped. Return the number of
+    /// received mbufs.
+    pub fn run<F: FnMut(&mut ArrayVec<Mbuf, N>)>(&mut self, stop: &AtomicBool, mut f: F) -> u64 {
+        let mut nb_rx = 0;
+        let mut batch = ArrayVec::<Mbuf, N>::new();
+        while !stop.load(Ordering::Relaxed) {
+            if let Some(low_watermark) = self.low_watermark.as_mut() {
+                low_watermark.check();
+            }
+            if self.rxq.rx(&mut batch) == 0 {
+                self.idle_backoff.wait();
+                continue;
+            }
+            nb_rx += batch.len() as u64;
+
+            f(&mut batch);
+            while !batch.is_empty() {
+                if self.txq.tx(&mut batch) == 0 {
+                    break;
+                }
+            }
+            Mempool::free_batch(&mut batch);
+        }
+        nb_rx
+    }
+
+    /// Consume the worker and return the rx queue and the tx queue.
+    pub fn into_queues(self) -> (RxQueue, TxQueue) {
+        (self.rxq, self.txq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    use std::sync::atomic::AtomicU32;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    // This test requires a usable port 0, e.g. a `net_null` virtual device.
+    #[test]
+    fn worker_stops_on_flag() {
+        DpdkOption::new().init().unwrap();
+
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        utils::init_port(0, 1, 1, 512, "wtf", 512, 0).unwrap();
+
+        let mut worker = Worker::<32>::from_port(0, 0).unwrap();
+        worker.set_idle_backoff(Backoff::Yield);
+        // the queues are owned by the worker
+        assert!(service().rx_queue(0, 0).is_err());
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = std::thread::spawn({
+            let stop = stop.clone();
+            move || {
+                let mut counted = 0;
+                let nb_rx = worker.run(&stop, |batch| {
+                    counted += batch.len() as u64;
+                    // drop every other mbuf, and send the rest
+                    let mut i = 0;
+                    batch.retain(|_| {
+                        i += 1;
+                        i % 2 == 0
+                    });
+                });
+                (worker, nb_rx, counted)
+            }
+        });
+
+        std::thread::sleep(Duration::from_millis(200));
+        stop.store(true, Ordering::Relaxed);
+        let (worker, nb_rx, counted) = handle.join().unwrap();
+        assert!(nb_rx > 0);
+        assert_eq!(nb_rx, counted);
+
+        drop(worker);
+        service().port_close(0).unwrap();
+        service().mempool_free("wtf").unwrap();
+    }
+
+    // This test requires a usable port 0, e.g. a `net_null` virtual device.
+    #[test]
+    fn low_watermark_fires_on_rx() {
+        DpdkOption::new().init().unwrap();
+
+        utils::init_mempool("wtf", 2048, 0, 0).unwrap();
+        utils::init_port(0, 1, 1, 512, "wtf", 512, 0).unwrap();
+
+        {
+            let mp = service().mempool("wtf").unwrap();
+            let mut held = Vec::new();
+            while mp.nb_mbufs() >= 64 {
+                held.push(mp.try_alloc().unwrap());
+            }
+
+            let stop = Arc::new(AtomicBool::new(false));
+            let fired = Arc::new(AtomicU32::new(u32::MAX));
+            let mut worker = Worker::<32>::from_port(0, 0).unwrap();
+            worker.set_low_watermark(&mp, 64, {
+                let stop = stop.clone();
+                let fired = fired.clone();
+                move |avail| {
+                    fired.store(avail, Ordering::Relaxed);
+                    stop.store(true, Ordering::Relaxed);
+                }
+            });
+            assert!(!worker.mempool_low());
+
+            // the callback stops the loop after the first burst
+            worker.run(&stop, |_| {});
+            assert!(fired.load(Ordering::Relaxed) < 64);
+            assert!(worker.mempool_low());
+            drop(held);
+        }
+
+        service().port_close(0).unwrap();
+        service().mempool_free("wtf").unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{tsc, Instant};
+
+/// A token bucket that paces the packet transmission to a target rate.
+///
+/// The bucket is refilled according to the tsc counter, and each packet consumes
+/// a token. The bucket holds at most `burst` tokens, so that the pacer does not
+/// send a huge burst after being idle for a long time.
+///
+/// # Examples
+/// ```no_run
+/// use rpkt_time::Pacer;
+///
+/// let mut pacer = Pacer::new(1_000_000);
+/// loop {
+///     let n = pacer.allow(32);
+///     // send n packets with tx_burst
+/// #   break;
+/// }
+/// ```
+pub struct Pacer {
+    tokens_per_cycle: f64,
+    tokens: f64,
+    burst: f64,
+    last: u64,
+}
+
+impl Pacer {
+    /// The default maximum number of packets that can be sent at once.
+    pub const BURST: u32 = 32;
+
+    /// Create a pacer that sends `pps` packets per second.
+    pub fn new(pps: u64) -> Self {
+        Self::new_at(pps as f64, tsc::cycles_per_sec(), Instant::now().raw())
+    }
+
+    /// Create a pacer that sends `bps` bits per second, where the average
+    /// packet length is `avg_pkt_len` bytes.
+    ///
+    /// The packet length should include the 20-byte preamble and inter-frame
+    /// gap if `bps` is the line rate.
+    ///
+    /// # Panic:
+    /// This function panics if `avg_pkt_len` is 0.
+    pub fn new_bps(bps: u64, avg_pkt_len: u32) -> Self {
+        assert!(avg_pkt_len > 0);
+        Self::new_at(
+            bps as f64 / (f64::from(avg_pkt_len) * 8.0),
+            tsc::cycles_per_sec(),
+            Instant::now().raw(),
+        )
+    }
+
+    fn new_at(pps: f64, cycles_per_sec: u64, now: u64) -> Self {
+        let burst = f64::from(Self::BURST);
+        Self {
+            tokens_per_cycle: pps / cycles_per_sec as f64,
+            tokens: burst,
+            burst,
+            last: now,
+        }
+    }
+
+    /// Set the maximum number of packets that can be sent at once.
+    pub fn set_burst(&mut self, val: u32) {
+        self.burst = f64::from(val);
+        self.tokens = self.tokens.min(self.burst);
+    }
+
+    /// Return how many of the `n` packets can be sent now.
+    #[inline]
+    pub fn allow(&mut self, n: u32) -> u32 {
+        self.allow_at(Instant::now().raw(), n)
+    }
+
+    /// Return how many of the `n` packets can be sent at the tsc counter value
+    /// `now`, which is the raw value of an `Instant`.
+    #[inline]
+    pub fn allow_at(&mut self, now: u64, n: u32) -> u32 {
+        if now > self.last {
+            self.tokens =
+                (self.tokens + (now - self.last) as f64 * self.tokens_per_cycle).min(self.burst);
+            self.last = now;
+        }
+
+        let allowed = (self.tokens as u32).min(n);
+        self.tokens -= f64::from(allowed);
+        allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CYCLES_PER_SEC: u64 = 2_000_000_000;
+
+    fn run(pacer: &mut Pacer, secs: u64, step: u64) -> u64 {
+        let mut sent = 0;
+        let mut now = 0;
+        while now < secs * CYCLES_PER_SEC {
+            now += step;
+            sent += u64::from(pacer.allow_at(now, 32));
+        }
+        sent
+    }
+
+    #[test]
+    fn pacer_tracks_target_rate() {
+        for pps in [1000, 1_000_000, 14_880_000] {
+            let mut pacer = Pacer::new_at(pps as f64, CYCLES_PER_SEC, 0);
+            let sent = run(&mut pacer, 2, 97);
+            let target = 2 * pps;
+            // the initial burst is the only allowed excess
+            assert!(sent <= target + u64::from(Pacer::BURST));
+            assert!(sent + 1 >= target);
+        }
+    }
+
+    #[test]
+    fn pacer_limits_burst_after_idle() {
+        // 8 Gbps with 1000-byte packets is 1 Mpps
+        let mut pacer = Pacer::new_at(8e9 / 8000.0, CYCLES_PER_SEC, 0);
+        pacer.set_burst(16);
+        assert_eq!(pacer.allow_at(0, 32), 16);
+        assert_eq!(pacer.allow_at(0, 32), 0);
+
+        // idle for one second, only a single burst is allowed
+        assert_eq!(pacer.allow_at(CYCLES_PER_SEC, 32), 16);
+        assert_eq!(pacer.allow_at(CYCLES_PER_SEC, 32), 0);
+
+        // 10us later, 10 more packets
+        assert_eq!(pacer.allow_at(CYCLES_PER_SEC + 20_000, 32), 10);
+    }
+}
@@ -0,0 +1,404 @@
+//! Prefix-preserving anonymization of the addresses in captured packets.
+//!
+//! The addresses are scrambled in the style of Crypto-PAn: the i-th bit of an
+//! address is flipped by a pseudorandom function of the key and the first i bits
+//! of the address. Two addresses sharing a k-bit prefix are therefore mapped to
+//! two addresses sharing a k-bit prefix, so the subnet structure of a capture is
+//! retained. SipHash-2-4 is used as the pseudorandom function.
+
+use byteorder::{ByteOrder, LittleEndian, NetworkEndian};
+
+use crate::checksum_utils;
+use crate::ether::{EtherType, ETHER_HEADER_LEN};
+use crate::ipv4::IpProtocol;
+
+const IPV4_SRC_OFFSET: usize = 12;
+const IPV4_CKSUM_OFFSET: usize = 10;
+const IPV6_HEADER_LEN: usize = 40;
+const IPV6_SRC_OFFSET: usize = 8;
+
+/// Scramble the bits of the address in `addr` with `key`.
+///
+/// The mapping is deterministic and prefix-preserving.
+pub fn scramble_prefix(addr: &mut [u8], key: &[u8; 16]) {
+    assert!(addr.len() <= 16);
+    let orig = addr.to_vec();
+
+    // the input of the prf is the bit index followed by the prefix
+    let mut input = [0; 17];
+    for i in 0..orig.len() * 8 {
+        let (byte, bit) = (i / 8, 7 - i % 8);
+        input[0] = i as u8;
+        input[1..1 + byte].copy_from_slice(&orig[..byte]);
+        input[1 + byte] = orig[byte] & !(0xff >> (i % 8));
+
+        let flip = (siphash24(key, &input[..1 + orig.len()]) & 1) as u8;
+        addr[byte] ^= flip << bit;
+    }
+}
+
+/// Scramble the source and destination ip addresses of the ethernet frame in
+/// `packet` with `key`.
+///
+/// Both ipv4 and ipv6 are supported. The ipv4 header checksum and the
+/// tcp, udp and icmpv6 checksums covering the addresses are fixed up
+/// incrementally. A frame that is neither ipv4 nor ipv6, or is truncated, is left
+/// untouched.
+pub fn scramble_addresses(packet: &mut [u8], key: &[u8; 16]) {
+    if packet.len() < ETHER_HEADER_LEN {
+        return;
+    }
+    let ethertype = EtherType::from(NetworkEndian::read_u16(&packet[12..14]));
+    let l3 = &mut packet[ETHER_HEADER_LEN..];
+    if ethertype == EtherType::IPV4 {
+        scramble_ipv4(l3, key);
+    } else if ethertype == EtherType::IPV6 {
+        scramble_ipv6(l3, key);
+    }
+}
+
+/// Scramble the source and destination mac addresses of the ethernet frame in
+/// `packet` with `key`.
+pub fn scramble_macs(packet: &mut [u8], key: &[u8; 16]) {
+    if packet.len() < ETHER_HEADER_LEN {
+        return;
+    }
+    scramble_prefix(&mut packet[0..6], key);
+    scramble_prefix(&mut packet[6..12], key);
+}
+
+fn scramble_ipv4(l3: &mut [u8], key: &[u8; 16]) {
+    if l3.len() < 20 || l3[0] >> 4 != 4 {
+        return;
+    }
+    let ihl = usize::from(l3[0] & 0x0f) * 4;
+    if ihl < 20 || l3.len() < ihl {
+        return;
+    }
+
+    let mut addrs = [0; 8];
+    addrs.copy_from_slice(&l3[IPV4_SRC_OFFSET..IPV4_SRC_OFFSET + 8]);
+    scramble_prefix(&mut addrs[0..4], key);
+    scramble_prefix(&mut addrs[4..8], key);
+
+    let cksum = NetworkEndian::read_u16(&l3[IPV4_CKSUM_OFFSET..]);
+    let cksum = update_checksum(cksum, &l3[IPV4_SRC_OFFSET..IPV4_SRC_OFFSET + 8], &addrs);
+    NetworkEndian::write_u16(&mut l3[IPV4_CKSUM_OFFSET..], cksum);
+
+    // the non-first fragments do not carry the l4 header
+    let frag_offset = NetworkEndian::read_u16(&l3[6..8]) & 0x1fff;
+    if frag_offset == 0 {
+        let protocol = IpProtocol::from(l3[9]);
+        let (header, l4) = l3.split_at_mut(ihl);
+        update_l4_checksum(
+            protocol,
+            l4,
+            &header[IPV4_SRC_OFFSET..IPV4_SRC_OFFSET + 8],
+            &addrs,
+        );
+    }
+    l3[IPV4_SRC_OFFSET..IPV4_SRC_OFFSET + 8].copy_from_slice(&addrs);
+}
+
+fn scramble_ipv6(l3: &mut [u8], key: &[u8; 16]) {
+    if l3.len() < IPV6_HEADER_LEN || l3[0] >> 4 != 6 {
+        return;
+    }
+
+    let mut addrs = [0; 32];
+    addrs.copy_from_slice(&l3[IPV6_SRC_OFFSET..IPV6_SRC_OFFSET + 32]);
+    scramble_prefix(&mut addrs[0..16], key);
+    scramble_prefix(&mut addrs[16..32], key);
+
+    // walk the extension headers to find the l4 header
+    let mut next_header = IpProtocol::from(l3[6]);
+    let mut offset = IPV6_HEADER_LEN;
+    let l4_offset = loop {
+        match next_header {
+            IpProtocol::HOPOPT | IpProtocol::IPV6_ROUTE | IpProtocol::IPV6_OPTS => {
+                if l3.len() < offset + 8 {
+                    break None;
+                }
+                next_header = IpProtocol::from(l3[offset]);
+                offset += (usize::from(l3[offset + 1]) + 1) * 8;
+            }
+            IpProtocol::IPV6_FRAG => {
+                if l3.len() < offset + 8
+                    || NetworkEndian::read_u16(&l3[offset + 2..offset + 4]) >> 3 != 0
+                {
+                    break None;
+                }
+                next_header = IpProtocol::from(l3[offset]);
+                offset += 8;
+            }
+            _ => break Some(offset),
+        }
+    };
+
+    if let Some(l4_offset) = l4_offset.filter(|l4_offset| *l4_offset <= l3.len()) {
+        let (header, l4) = l3.split_at_mut(l4_offset);
+        let old_addrs = &header[IPV6_SRC_OFFSET..IPV6_SRC_OFFSET + 32];
+        update_l4_checksum(next_header, l4, old_addrs, &addrs);
+    }
+    l3[IPV6_SRC_OFFSET..IPV6_SRC_OFFSET + 32].copy_from_slice(&addrs);
+}
+
+// Fix up the checksum of the l4 header in `l4`, whose pseudo header carries
+// the addresses that change from `old` to `new`.
+fn update_l4_checksum(protocol: IpProtocol, l4: &mut [u8], old: &[u8], new: &[u8]) {
+    let cksum_offset = match protocol {
+        IpProtocol::TCP => 16,
+        IpProtocol::UDP => 6,
+        IpProtocol::ICMPV6 => 2,
+        _ => return,
+    };
+    if l4.len() < cksum_offset + 2 {
+        return;
+    }
+
+    let cksum = NetworkEndian::read_u16(&l4[cksum_offset..]);
+    // a zero udp checksum over ipv4 means that there is no checksum
+    if protocol == IpProtocol::UDP && cksum == 0 {
+        return;
+    }
+    let mut cksum = update_checksum(cksum, old, new);
+    if protocol == IpProtocol::UDP && cksum == 0 {
+        cksum = 0xffff;
+    }
+    NetworkEndian::write_u16(&mut l4[cksum_offset..], cksum);
+}
+
+fn update_checksum(mut cksum: u16, old: &[u8], new: &[u8]) -> u16 {
+    for (old_word, new_word) in old.chunks_exact(2).zip(new.chunks_exact(2)) {
+        cksum = checksum_utils::incremental_update(
+            cksum,
+            NetworkEndian::read_u16(old_word),
+            NetworkEndian::read_u16(new_word),
+        );
+    }
+    cksum
+}
+
+// SipHash-2-4 of `data` with `key`.
+fn siphash24(key: &[u8; 16], data: &[u8]) -> u64 {
+    let k0 = LittleEndian::read_u64(&key[0..8]);
+    let k1 = LittleEndian::read_u64(&key[8..16]);
+    let mut v = [
+        k0 ^ 0x736f6d6570736575,
+        k1 ^ 0x646f72616e646f6d,
+        k0 ^ 0x6c7967656e657261,
+        k1 ^ 0x7465646279746573,
+    ];
+
+    fn round(v: &mut [u64; 4]) {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    }
+
+    let mut compress = |m: u64| {
+        v[3] ^= m;
+        round(&mut v);
+        round(&mut v);
+        v[0] ^= m;
+    };
+
+    let mut blocks = data.chunks_exact(8);
+    for block in &mut blocks {
+        compress(LittleEndian::read_u64(block));
+    }
+    let mut last = [0; 8];
+    last[..blocks.remainder().len()].copy_from_slice(blocks.remainder());
+    last[7] = data.len() as u8;
+    compress(LittleEndian::read_u64(&last));
+
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        round(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ether::*;
+    use crate::ipv4::*;
+    use crate::tcp::*;
+    use crate::udp::*;
+    use crate::{Cursor, CursorMut};
+    use bytes::Buf;
+
+    const KEY: [u8; 16] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f,
+    ];
+
+    fn scramble_ipv4_addr(addr: [u8; 4]) -> [u8; 4] {
+        let mut addr = addr;
+        scramble_prefix(&mut addr[..], &KEY);
+        addr
+    }
+
+    // The number of leading bits shared by `a` and `b`.
+    fn common_prefix_len(a: &[u8], b: &[u8]) -> u32 {
+        let mut len = 0;
+        for (a, b) in a.iter().zip(b.iter()) {
+            let diff = a ^ b;
+            len += diff.leading_zeros();
+            if diff != 0 {
+                break;
+            }
+        }
+        len
+    }
+
+    #[test]
+    fn siphash_test_vectors() {
+        // the test vectors from the reference implementation
+        assert_eq!(siphash24(&KEY, &[]), 0x726fdb47dd0e0e31);
+        let data: Vec<u8> = (0..15).collect();
+        assert_eq!(siphash24(&KEY, &data[..]), 0xa129ca6149be45e5);
+    }
+
+    #[test]
+    fn scramble_is_prefix_preserving() {
+        let addrs = [
+            [10, 0, 0, 1],
+            [10, 0, 0, 2],
+            [10, 0, 1, 1],
+            [10, 128, 0, 1],
+            [192, 168, 1, 1],
+        ];
+        for a in addrs.iter() {
+            assert_eq!(scramble_ipv4_addr(*a), scramble_ipv4_addr(*a));
+            for b in addrs.iter() {
+                let (sa, sb) = (scramble_ipv4_addr(*a), scramble_ipv4_addr(*b));
+                assert_eq!(common_prefix_len(a, b), common_prefix_len(&sa, &sb));
+            }
+        }
+        assert_ne!(scramble_ipv4_addr([10, 0, 0, 1]), [10, 0, 0, 1]);
+
+        let mut other_key = KEY;
+        other_key[0] = 0xff;
+        let mut addr = [10, 0, 0, 1];
+        scramble_prefix(&mut addr[..], &other_key);
+        assert_ne!(addr, scramble_ipv4_addr([10, 0, 0, 1]));
+    }
+
+    fn build_frame(protocol: IpProtocol, src: Ipv4Addr, dst: Ipv4Addr) -> Vec<u8> {
+        let headers_len = ETHER_HEADER_LEN + IPV4_HEADER_LEN + TCP_HEADER_LEN;
+        let mut bytes = vec![0x5a; headers_len + 25];
+
+        let mut buf = CursorMut::new(&mut bytes[..]);
+        buf.advance(headers_len);
+        let buf = if protocol == IpProtocol::TCP {
+            let mut tcppkt = TcpPacket::prepend_header(buf, &TCP_HEADER_TEMPLATE);
+            tcppkt.adjust_ipv4_checksum(src, dst);
+            tcppkt.release()
+        } else {
+            let mut udppkt = UdpPacket::prepend_header(buf, &UDP_HEADER_TEMPLATE);
+            udppkt.adjust_ipv4_checksum(src, dst);
+            udppkt.release()
+        };
+        let mut ipheader = IPV4_HEADER_TEMPLATE;
+        ipheader.set_protocol(protocol);
+        ipheader.set_source_ip(src);
+        ipheader.set_dest_ip(dst);
+        let mut ippkt = Ipv4Packet::prepend_header(buf, &ipheader);
+        ippkt.adjust_checksum();
+        let ethpkt = EtherPacket::prepend_header(ippkt.release(), &ETHER_HEADER_TEMPLATE);
+
+        let offset = ethpkt.buf().cursor();
+        bytes[offset..].to_vec()
+    }
+
+    #[test]
+    fn scramble_ipv4_frames() {
+        let src = Ipv4Addr([10, 0, 0, 1]);
+        let dst = Ipv4Addr([10, 0, 3, 2]);
+        for protocol in [IpProtocol::TCP, IpProtocol::UDP] {
+            let mut frame = build_frame(protocol, src, dst);
+            scramble_addresses(&mut frame[..], &KEY);
+            scramble_macs(&mut frame[..], &KEY);
+
+            let ethpkt = EtherPacket::parse(Cursor::new(&frame[..])).unwrap();
+            assert_eq!(ethpkt.dest_mac(), ethpkt.source_mac());
+            assert_ne!(ethpkt.source_mac(), MacAddr([0; 6]));
+            let ippkt = Ipv4Packet::parse(ethpkt.payload()).unwrap();
+            assert!(ippkt.verify_checksum());
+            assert_eq!(ippkt.source_ip(), Ipv4Addr(scramble_ipv4_addr(src.0)));
+            assert_eq!(ippkt.dest_ip(), Ipv4Addr(scramble_ipv4_addr(dst.0)));
+
+            let (src_ip, dst_ip) = (ippkt.source_ip(), ippkt.dest_ip());
+            if protocol == IpProtocol::TCP {
+                let mut tcppkt = TcpPacket::parse(ippkt.payload()).unwrap();
+                assert!(tcppkt.verify_ipv4_checksum(src_ip, dst_ip));
+            } else {
+                let mut udppkt = UdpPacket::parse(ippkt.payload()).unwrap();
+                assert_ne!(udppkt.checksum(), 0);
+                assert!(udppkt.verify_ipv4_checksum(src_ip, dst_ip));
+            }
+        }
+
+        // the frames that are not ip are untouched
+        let mut frame = build_frame(IpProtocol::TCP, src, dst);
+        frame[12..14].copy_from_slice(&[0x08, 0x06]);
+        let orig = frame.clone();
+        scramble_addresses(&mut frame[..], &KEY);
+        assert_eq!(frame, orig);
+        scramble_addresses(&mut frame[..20], &KEY);
+    }
+
+    #[test]
+    fn scramble_ipv6_frame() {
+        // an ipv6 udp frame with a destination options header
+        let mut frame = vec![0; ETHER_HEADER_LEN + 40 + 8 + 8 + 4];
+        frame[12..14].copy_from_slice(&[0x86, 0xdd]);
+        let l3 = &mut frame[ETHER_HEADER_LEN..];
+        l3[0] = 0x60;
+        NetworkEndian::write_u16(&mut l3[4..6], 20);
+        l3[6] = 60;
+        l3[8..24].copy_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+        l3[24..40].copy_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 2]);
+        l3[40] = 17;
+        l3[48..50].copy_from_slice(&1234_u16.to_be_bytes());
+        l3[50..52].copy_from_slice(&53_u16.to_be_bytes());
+        l3[52..54].copy_from_slice(&12_u16.to_be_bytes());
+        l3[56..60].copy_from_slice(&[1, 2, 3, 4]);
+
+        fn udp_checksum(l3: &[u8]) -> u16 {
+            let mut pseudo_header = l3[8..40].to_vec();
+            pseudo_header.extend_from_slice(&[0, 0, 0, 12, 0, 0, 0, 17]);
+            pseudo_header.extend_from_slice(&l3[48..60]);
+            let mut accum = 0_u32;
+            for word in pseudo_header.chunks_exact(2) {
+                accum += u32::from(NetworkEndian::read_u16(word));
+            }
+            while accum > 0xffff {
+                accum = (accum >> 16) + (accum & 0xffff);
+            }
+            !(accum as u16)
+        }
+        let cksum = udp_checksum(&frame[ETHER_HEADER_LEN..]);
+        NetworkEndian::write_u16(&mut frame[ETHER_HEADER_LEN + 54..], cksum);
+        assert_eq!(udp_checksum(&frame[ETHER_HEADER_LEN..]), 0);
+
+        let orig = frame.clone();
+        scramble_addresses(&mut frame[..], &KEY);
+        let (l3, orig_l3) = (&frame[ETHER_HEADER_LEN..], &orig[ETHER_HEADER_LEN..]);
+        assert_ne!(l3[8..24], orig_l3[8..24]);
+        assert_eq!(
+            common_prefix_len(&l3[8..24], &l3[24..40]),
+            common_prefix_len(&orig_l3[8..24], &orig_l3[24..40])
+        );
+        assert_eq!(udp_checksum(l3), 0);
+    }
+}
@@ -1,7 +1,7 @@
 use bytes::Buf;
 
 use crate::ether::EtherType;
-use crate::PktMut;
+use crate::{Cursor, Header, ParseError, PktMut};
 
 use super::{sha, spa, tha, tpa, ArpHeader, Hardware, Operation, ARP_HEADER_LEN};
 
@@ -61,6 +61,24 @@ impl<T: Buf> ArpPacket<T> {
     }
 }
 
+impl<'a> Header<'a> for ArpPacket<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        ArpPacket::parse(Cursor::new(buf)).map_err(|_| ParseError::Arp)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        ARP_HEADER_LEN
+    }
+
+    /// Return the bytes after the arp packet, e.g. the ethernet padding.
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        &self.buf.chunk_shared_lifetime()[ARP_HEADER_LEN..]
+    }
+}
+
 impl<T: PktMut> ArpPacket<T> {
     #[inline]
     pub fn prepend_header<HT: AsRef<[u8]>>(mut buf: T, header: &ArpHeader<HT>) -> ArpPacket<T> {
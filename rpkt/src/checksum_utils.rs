@@ -54,6 +54,14 @@ pub(crate) fn combine(checksums: &[u16]) -> u16 {
     propagate_carries(accum)
 }
 
+// Incrementally update a checksum field after a 16-bit word of the covered
+// data changes from `old_word` to `new_word`, following eqn. 3 of RFC 1624:
+// HC' = ~(~HC + ~m + m').
+pub(crate) fn incremental_update(cksum: u16, old_word: u16, new_word: u16) -> u16 {
+    let accum = (!cksum) as u32 + (!old_word) as u32 + new_word as u32;
+    !propagate_carries(accum)
+}
+
 fn from_slice_with_tail_byte(
     mut data: &[u8],
     accum: &mut u32,
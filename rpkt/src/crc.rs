@@ -0,0 +1,87 @@
+//! CRC-32 computation for the Ethernet frame check sequence (FCS).
+
+/// The length of the Ethernet FCS.
+pub const FCS_LEN: usize = 4;
+
+// The reflected form of the IEEE 802.3 polynomial 0x04c11db7.
+const POLY: u32 = 0xedb88320;
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Compute the Ethernet FCS of `frame`, which starts from the destination mac
+/// address and does not contain the FCS.
+///
+/// The FCS is the IEEE 802.3 CRC-32 of the frame. It is transmitted in the
+/// little-endian byte order, so `ethernet_fcs(frame).to_le_bytes()` gives the
+/// 4 bytes that follow the frame on the wire.
+pub fn ethernet_fcs(frame: &[u8]) -> u32 {
+    let mut crc = 0xffffffff_u32;
+    for &byte in frame {
+        crc = CRC_TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+/// Check whether the trailing 4 bytes of `frame_with_fcs` is the correct FCS
+/// of the rest of the frame.
+///
+/// Return `false` if `frame_with_fcs` is shorter than the FCS.
+pub fn verify_fcs(frame_with_fcs: &[u8]) -> bool {
+    if frame_with_fcs.len() < FCS_LEN {
+        return false;
+    }
+    let (frame, fcs) = frame_with_fcs.split_at(frame_with_fcs.len() - FCS_LEN);
+    ethernet_fcs(frame).to_le_bytes() == fcs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A minimum-sized arp request frame, padded to 60 bytes.
+    static ARP_FRAME: [u8; 60] = [
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x16, 0x3e, 0x0a, 0x0b, 0x0c, 0x08, 0x06, 0x00,
+        0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01, 0x00, 0x16, 0x3e, 0x0a, 0x0b, 0x0c, 0xc0, 0xa8,
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc0, 0xa8, 0x00, 0x02, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn crc32_check_value() {
+        // the standard check value of CRC-32/ISO-HDLC
+        assert_eq!(ethernet_fcs(b"123456789"), 0xcbf43926);
+        assert_eq!(ethernet_fcs(&[]), 0);
+    }
+
+    #[test]
+    fn compute_and_verify_fcs() {
+        assert_eq!(ethernet_fcs(&ARP_FRAME[..]), 0x860d195a);
+
+        let mut frame = ARP_FRAME.to_vec();
+        frame.extend_from_slice(&[0x5a, 0x19, 0x0d, 0x86]);
+        assert!(verify_fcs(&frame[..]));
+
+        frame[20] ^= 0x01;
+        assert!(!verify_fcs(&frame[..]));
+
+        assert!(!verify_fcs(&frame[..3]));
+    }
+}
@@ -0,0 +1,22 @@
+enum_sim! {
+    /// See https://www.iana.org/assignments/aaa-parameters/aaa-parameters.xhtml#aaa-parameters-3
+    pub struct DiameterCommand (u32) {
+        CAPABILITIES_EXCHANGE = 257,
+        RE_AUTH = 258,
+        ACCOUNTING = 271,
+        CREDIT_CONTROL = 272,
+        ABORT_SESSION = 274,
+        SESSION_TERMINATION = 275,
+        DEVICE_WATCHDOG = 280,
+        DISCONNECT_PEER = 282,
+    }
+}
+
+/// The length of the diameter header, which precedes the avps.
+pub const DIAMETER_HEADER_LEN: usize = 20;
+
+/// The tcp and sctp port assigned to diameter.
+pub const DIAMETER_PORT: u16 = 3868;
+
+mod packet;
+pub use packet::{DiameterAvp, DiameterAvpIter, DiameterPacket};
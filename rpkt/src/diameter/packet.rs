@@ -0,0 +1,354 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use bytes::Buf;
+
+use crate::{Cursor, Header, ParseError};
+
+use super::{DiameterCommand, DIAMETER_HEADER_LEN};
+
+/// A diameter message, carried by a tcp or sctp connection on the diameter
+/// port.
+///
+/// The fixed header is followed by a list of avps up to the length of the
+/// message. Each avp is padded to a multiple of 4 bytes, and the padding is
+/// included in the message length.
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct DiameterPacket<T> {
+    buf: T,
+}
+
+impl<T: Buf> DiameterPacket<T> {
+    #[inline]
+    pub fn parse_unchecked(buf: T) -> Self {
+        Self { buf }
+    }
+
+    #[inline]
+    pub fn buf(&self) -> &T {
+        &self.buf
+    }
+
+    #[inline]
+    pub fn release(self) -> T {
+        self.buf
+    }
+
+    /// Parse a diameter message of version 1 from `buf`, the length of the
+    /// message must fit in `buf`.
+    ///
+    /// The avps are checked by `check_avps`.
+    #[inline]
+    pub fn parse(buf: T) -> Result<DiameterPacket<T>, T> {
+        let chunk_len = buf.chunk().len();
+        if chunk_len < DIAMETER_HEADER_LEN {
+            return Err(buf);
+        }
+
+        let packet = DiameterPacket { buf };
+        let packet_len = packet.packet_len() as usize;
+        if packet.version() != 1
+            || packet_len < DIAMETER_HEADER_LEN
+            || packet_len % 4 != 0
+            || packet_len > chunk_len
+        {
+            return Err(packet.release());
+        }
+
+        Ok(packet)
+    }
+
+    #[inline]
+    pub fn version(&self) -> u8 {
+        self.buf.chunk()[0]
+    }
+
+    /// Return the 24-bit length of the message, including the header.
+    #[inline]
+    pub fn packet_len(&self) -> u32 {
+        NetworkEndian::read_u24(&self.buf.chunk()[1..4])
+    }
+
+    #[inline]
+    pub fn command_flags(&self) -> u8 {
+        self.buf.chunk()[4]
+    }
+
+    /// Whether the message is a request, otherwise it is an answer.
+    #[inline]
+    pub fn request(&self) -> bool {
+        self.command_flags() & 0x80 != 0
+    }
+
+    #[inline]
+    pub fn proxiable(&self) -> bool {
+        self.command_flags() & 0x40 != 0
+    }
+
+    /// Whether the message is an answer that carries a protocol error.
+    #[inline]
+    pub fn error(&self) -> bool {
+        self.command_flags() & 0x20 != 0
+    }
+
+    #[inline]
+    pub fn retransmitted(&self) -> bool {
+        self.command_flags() & 0x10 != 0
+    }
+
+    #[inline]
+    pub fn command_code(&self) -> DiameterCommand {
+        NetworkEndian::read_u24(&self.buf.chunk()[5..8]).into()
+    }
+
+    #[inline]
+    pub fn application_id(&self) -> u32 {
+        NetworkEndian::read_u32(&self.buf.chunk()[8..12])
+    }
+
+    #[inline]
+    pub fn hop_by_hop_id(&self) -> u32 {
+        NetworkEndian::read_u32(&self.buf.chunk()[12..16])
+    }
+
+    #[inline]
+    pub fn end_to_end_id(&self) -> u32 {
+        NetworkEndian::read_u32(&self.buf.chunk()[16..20])
+    }
+
+    #[inline]
+    pub fn avp_bytes(&self) -> &[u8] {
+        &self.buf.chunk()[DIAMETER_HEADER_LEN..self.packet_len() as usize]
+    }
+
+    #[inline]
+    pub fn avps(&self) -> DiameterAvpIter<'_> {
+        DiameterAvpIter::from_avp_bytes(self.avp_bytes())
+    }
+
+    /// Check that the padded avps fill the message exactly.
+    #[inline]
+    pub fn check_avps(&self) -> bool {
+        DiameterAvpIter::check_avp_bytes(self.avp_bytes())
+    }
+}
+
+impl<'a> DiameterPacket<Cursor<'a>> {
+    #[inline]
+    pub fn cursor_avp_bytes(&self) -> &'a [u8] {
+        &self.buf.chunk_shared_lifetime()[DIAMETER_HEADER_LEN..self.packet_len() as usize]
+    }
+}
+
+impl<'a> Header<'a> for DiameterPacket<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        DiameterPacket::parse(Cursor::new(buf)).map_err(|_| ParseError::Diameter)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        DIAMETER_HEADER_LEN
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        self.cursor_avp_bytes()
+    }
+}
+
+/// An avp of a diameter message, without the padding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiameterAvp<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> DiameterAvp<'a> {
+    #[inline]
+    pub fn code(&self) -> u32 {
+        NetworkEndian::read_u32(&self.buf[0..4])
+    }
+
+    #[inline]
+    pub fn flags(&self) -> u8 {
+        self.buf[4]
+    }
+
+    /// Whether the avp carries a vendor id.
+    #[inline]
+    pub fn vendor_specific(&self) -> bool {
+        self.flags() & 0x80 != 0
+    }
+
+    #[inline]
+    pub fn mandatory(&self) -> bool {
+        self.flags() & 0x40 != 0
+    }
+
+    /// The length of the avp, including the avp header but not the padding.
+    #[inline]
+    pub fn avp_len(&self) -> usize {
+        self.buf.len()
+    }
+
+    #[inline]
+    pub fn vendor_id(&self) -> Option<u32> {
+        self.vendor_specific()
+            .then(|| NetworkEndian::read_u32(&self.buf[8..12]))
+    }
+
+    #[inline]
+    pub fn data(&self) -> &'a [u8] {
+        let header_len = if self.vendor_specific() { 12 } else { 8 };
+        &self.buf[header_len..]
+    }
+}
+
+pub struct DiameterAvpIter<'a> {
+    buf: &'a [u8],
+    valid: bool,
+}
+
+impl<'a> DiameterAvpIter<'a> {
+    #[inline]
+    pub fn from_avp_bytes(buf: &'a [u8]) -> DiameterAvpIter<'a> {
+        Self { buf, valid: true }
+    }
+
+    #[inline]
+    pub fn check_avp_bytes(buf: &'a [u8]) -> bool {
+        let mut reader = Self::from_avp_bytes(buf);
+        for _ in &mut reader {}
+        reader.valid
+    }
+}
+
+impl<'a> Iterator for DiameterAvpIter<'a> {
+    type Item = DiameterAvp<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.valid || self.buf.is_empty() {
+            return None;
+        }
+
+        // the length covers the 8 or 12-byte avp header, so the iterator
+        // always advances
+        if self.buf.len() < 8 {
+            self.valid = false;
+            return None;
+        }
+        let header_len = if self.buf[4] & 0x80 != 0 { 12 } else { 8 };
+        let avp_len = NetworkEndian::read_u24(&self.buf[5..8]) as usize;
+        let padded_len = (avp_len + 3) & !3;
+        if avp_len < header_len || self.buf.len() < padded_len {
+            self.valid = false;
+            return None;
+        }
+
+        let avp = DiameterAvp {
+            buf: &self.buf[..avp_len],
+        };
+        self.buf = &self.buf[padded_len..];
+        Some(avp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn avp(code: u32, flags: u8, vendor_id: Option<u32>, data: &[u8]) -> Vec<u8> {
+        let header_len = if vendor_id.is_some() { 12 } else { 8 };
+        let mut bytes = code.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&((header_len + data.len()) as u32).to_be_bytes());
+        bytes[4] = flags;
+        if let Some(vendor_id) = vendor_id {
+            bytes.extend_from_slice(&vendor_id.to_be_bytes());
+        }
+        bytes.extend_from_slice(data);
+        while bytes.len() % 4 != 0 {
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    // A capabilities-exchange request from the peer host.example.com.
+    fn build_cer() -> Vec<u8> {
+        let mut avps = avp(264, 0x40, None, b"host.example.com");
+        avps.extend(avp(296, 0x40, None, b"example.com"));
+        avps.extend(avp(257, 0x40, None, &[0, 1, 10, 0, 0, 1]));
+        avps.extend(avp(266, 0x40, None, &0_u32.to_be_bytes()));
+        avps.extend(avp(269, 0x00, None, b"rpkt"));
+        avps.extend(avp(628, 0x80, Some(10415), &1_u32.to_be_bytes()));
+
+        let mut bytes = vec![0x01];
+        bytes.extend_from_slice(&((20 + avps.len()) as u32).to_be_bytes()[1..]);
+        bytes.extend_from_slice(&[0x80, 0x00, 0x01, 0x01]);
+        bytes.extend_from_slice(&0_u32.to_be_bytes());
+        bytes.extend_from_slice(&0x1234_5678_u32.to_be_bytes());
+        bytes.extend_from_slice(&0x9abc_def0_u32.to_be_bytes());
+        bytes.extend_from_slice(&avps[..]);
+        bytes
+    }
+
+    #[test]
+    fn parse_capabilities_exchange() {
+        let bytes = build_cer();
+        let pkt = DiameterPacket::parse(Cursor::new(&bytes[..])).unwrap();
+        assert_eq!(pkt.version(), 1);
+        assert_eq!(pkt.packet_len(), 120);
+        assert!(pkt.request());
+        assert!(!pkt.proxiable() && !pkt.error() && !pkt.retransmitted());
+        assert_eq!(pkt.command_code(), DiameterCommand::CAPABILITIES_EXCHANGE);
+        assert_eq!(pkt.application_id(), 0);
+        assert_eq!(pkt.hop_by_hop_id(), 0x1234_5678);
+        assert_eq!(pkt.end_to_end_id(), 0x9abc_def0);
+        assert!(pkt.check_avps());
+
+        let avps: Vec<_> = pkt.avps().collect();
+        assert_eq!(avps.len(), 6);
+        assert_eq!(avps[0].code(), 264);
+        assert!(avps[0].mandatory());
+        assert_eq!(avps[0].data(), b"host.example.com");
+        // the padding is not a part of the data
+        assert_eq!(avps[1].avp_len(), 19);
+        assert_eq!(avps[1].data(), b"example.com");
+        assert_eq!(avps[2].data(), &[0, 1, 10, 0, 0, 1][..]);
+        assert_eq!(avps[4].vendor_id(), None);
+        assert_eq!(avps[4].data(), b"rpkt");
+        assert!(avps[5].vendor_specific());
+        assert_eq!(avps[5].vendor_id(), Some(10415));
+        assert_eq!(avps[5].data(), &1_u32.to_be_bytes()[..]);
+
+        // the length exceeds the message
+        assert!(DiameterPacket::parse(Cursor::new(&bytes[..116])).is_err());
+        // the version is not 1
+        let mut bytes = build_cer();
+        bytes[0] = 2;
+        assert!(DiameterPacket::parse(Cursor::new(&bytes[..])).is_err());
+    }
+
+    #[test]
+    fn malformed_avps() {
+        // an avp length below the avp header would not advance the iterator
+        let mut bytes = build_cer();
+        bytes[27] = 0;
+        let pkt = DiameterPacket::parse(Cursor::new(&bytes[..])).unwrap();
+        assert!(!pkt.check_avps());
+        assert_eq!(pkt.avps().count(), 0);
+
+        // a vendor-specific avp must carry the vendor id
+        let mut bytes = build_cer();
+        bytes[24] = 0x80;
+        bytes[27] = 10;
+        let pkt = DiameterPacket::parse(Cursor::new(&bytes[..])).unwrap();
+        assert!(!pkt.check_avps());
+
+        // the padding of the last avp exceeds the message
+        let mut bytes = build_cer();
+        let last = bytes.len() - 16;
+        bytes[last + 7] = 17;
+        let pkt = DiameterPacket::parse(Cursor::new(&bytes[..])).unwrap();
+        assert!(!pkt.check_avps());
+        assert_eq!(pkt.avps().count(), 5);
+    }
+}
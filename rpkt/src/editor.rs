@@ -0,0 +1,220 @@
+use crate::ether::{EtherHeader, EtherPacket, EtherType, ETHER_HEADER_LEN};
+use crate::ipv4::{IpProtocol, Ipv4Addr, Ipv4Header, Ipv4Packet, IPV4_HEADER_LEN};
+use crate::tcp::{TcpHeader, TcpPacket, TCP_HEADER_LEN};
+use crate::udp::{UdpHeader, UdpPacket, UDP_HEADER_LEN};
+use crate::{Cursor, CursorMut};
+
+/// An in-place editor of an ethernet frame carrying an ipv4 tcp/udp packet,
+/// e.g. the data of an mbuf.
+///
+/// The offsets of the layers are recorded when the editor is created, and the
+/// headers are exposed as typed mutable headers. The editor tracks the layers
+/// that are borrowed mutably, so that `finalize` only recomputes the affected
+/// checksums.
+///
+/// The length fields of the headers must not be modified through the editor.
+pub struct PacketEditor<'a> {
+    buf: &'a mut [u8],
+    l3_offset: usize,
+    l4: Option<(IpProtocol, usize)>,
+    // the end of the ipv4 packet, excluding the ethernet padding
+    l3_end: usize,
+    l3_dirty: bool,
+    l4_dirty: bool,
+    // the addresses before `ipv4_mut` is called, as they are covered by the
+    // pseudo header of the tcp/udp checksum
+    orig_addrs: Option<(Ipv4Addr, Ipv4Addr)>,
+}
+
+impl<'a> PacketEditor<'a> {
+    /// Create an editor over the ethernet frame in `buf`.
+    ///
+    /// Return `None` if the frame is not an ipv4 packet. The transport layer is
+    /// only recorded for tcp/udp packets that are not non-first fragments.
+    pub fn new(buf: &'a mut [u8]) -> Option<Self> {
+        let ethpkt = EtherPacket::parse(Cursor::new(buf)).ok()?;
+        if ethpkt.ethertype() != EtherType::IPV4 {
+            return None;
+        }
+
+        let ippkt = Ipv4Packet::parse(ethpkt.payload()).ok()?;
+        let l3_offset = ETHER_HEADER_LEN;
+        let l3_end = l3_offset + usize::from(ippkt.packet_len());
+        let l4_offset = l3_offset + usize::from(ippkt.header_len());
+
+        let l4 = match ippkt.protocol() {
+            _ if ippkt.frag_offset() != 0 => None,
+            IpProtocol::TCP => {
+                TcpPacket::parse(ippkt.payload()).ok()?;
+                Some((IpProtocol::TCP, l4_offset))
+            }
+            IpProtocol::UDP => {
+                UdpPacket::parse(ippkt.payload()).ok()?;
+                Some((IpProtocol::UDP, l4_offset))
+            }
+            _ => None,
+        };
+
+        Some(Self {
+            buf,
+            l3_offset,
+            l4,
+            l3_end,
+            l3_dirty: false,
+            l4_dirty: false,
+            orig_addrs: None,
+        })
+    }
+
+    /// Return the offset of the ipv4 header.
+    pub fn l3_offset(&self) -> usize {
+        self.l3_offset
+    }
+
+    /// Return the offset of the tcp/udp header, if there is one.
+    pub fn l4_offset(&self) -> Option<usize> {
+        self.l4.map(|(_, offset)| offset)
+    }
+
+    /// Return the mutable ethernet header, which is not covered by any checksum.
+    pub fn ether_mut(&mut self) -> EtherHeader<&mut [u8]> {
+        EtherHeader::new_unchecked(&mut self.buf[..ETHER_HEADER_LEN])
+    }
+
+    /// Return the mutable ipv4 header, the ipv4 checksum is recomputed by
+    /// `finalize`.
+    pub fn ipv4_mut(&mut self) -> Ipv4Header<&mut [u8]> {
+        self.l3_dirty = true;
+        let header = Ipv4Header::new_unchecked(
+            &mut self.buf[self.l3_offset..self.l3_offset + IPV4_HEADER_LEN],
+        );
+        if self.orig_addrs.is_none() {
+            self.orig_addrs = Some((header.source_ip(), header.dest_ip()));
+        }
+        header
+    }
+
+    /// Return the mutable tcp header, if the packet is a tcp packet.
+    pub fn tcp_mut(&mut self) -> Option<TcpHeader<&mut [u8]>> {
+        match self.l4 {
+            Some((IpProtocol::TCP, offset)) => {
+                self.l4_dirty = true;
+                Some(TcpHeader::new_unchecked(
+                    &mut self.buf[offset..offset + TCP_HEADER_LEN],
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the mutable udp header, if the packet is a udp packet.
+    pub fn udp_mut(&mut self) -> Option<UdpHeader<&mut [u8]>> {
+        match self.l4 {
+            Some((IpProtocol::UDP, offset)) => {
+                self.l4_dirty = true;
+                Some(UdpHeader::new_unchecked(
+                    &mut self.buf[offset..offset + UDP_HEADER_LEN],
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Recompute the checksums of the modified layers.
+    ///
+    /// The tcp/udp checksum is recomputed if the transport header was modified,
+    /// or if the ipv4 addresses in the pseudo header have changed. It is
+    /// computed before the ipv4 checksum. A udp checksum of zero, which means
+    /// that the checksum is disabled, is left untouched.
+    pub fn finalize(&mut self) {
+        let l3_header =
+            Ipv4Header::new_unchecked(&self.buf[self.l3_offset..self.l3_offset + IPV4_HEADER_LEN]);
+        let (src_ip, dst_ip) = (l3_header.source_ip(), l3_header.dest_ip());
+        let addrs_changed = self
+            .orig_addrs
+            .is_some_and(|addrs| addrs != (src_ip, dst_ip));
+
+        if let Some((protocol, offset)) = self.l4 {
+            if self.l4_dirty || addrs_changed {
+                let buf = CursorMut::new(&mut self.buf[offset..self.l3_end]);
+                if protocol == IpProtocol::TCP {
+                    TcpPacket::parse_unchecked(buf).adjust_ipv4_checksum(src_ip, dst_ip);
+                } else {
+                    let mut udppkt = UdpPacket::parse_unchecked(buf);
+                    if udppkt.checksum() != 0 {
+                        udppkt.adjust_ipv4_checksum(src_ip, dst_ip);
+                    }
+                }
+            }
+        }
+
+        if self.l3_dirty {
+            let buf = CursorMut::new(&mut self.buf[self.l3_offset..self.l3_end]);
+            Ipv4Packet::parse_unchecked(buf).adjust_checksum();
+        }
+
+        self.l3_dirty = false;
+        self.l4_dirty = false;
+        self.orig_addrs = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::malformed::TCP_FRAME;
+
+    fn verify_checksums(frame: &[u8]) -> (Ipv4Addr, u16) {
+        let ethpkt = EtherPacket::parse(Cursor::new(frame)).unwrap();
+        let ippkt = Ipv4Packet::parse(ethpkt.payload()).unwrap();
+        assert!(ippkt.verify_checksum());
+
+        let (src_ip, dst_ip) = (ippkt.source_ip(), ippkt.dest_ip());
+        let mut tcppkt = TcpPacket::parse(ippkt.payload()).unwrap();
+        assert!(tcppkt.verify_ipv4_checksum(src_ip, dst_ip));
+        (dst_ip, tcppkt.dst_port())
+    }
+
+    #[test]
+    fn rewrite_tcp_dest() {
+        let mut bytes = TCP_FRAME;
+        verify_checksums(&bytes[..]);
+
+        let mut editor = PacketEditor::new(&mut bytes[..]).unwrap();
+        assert_eq!(editor.l3_offset(), ETHER_HEADER_LEN);
+        assert_eq!(editor.l4_offset(), Some(ETHER_HEADER_LEN + IPV4_HEADER_LEN));
+        assert!(editor.udp_mut().is_none());
+
+        editor.ipv4_mut().set_dest_ip(Ipv4Addr([10, 0, 0, 1]));
+        editor.tcp_mut().unwrap().set_dst_port(8080);
+        editor.finalize();
+
+        assert_eq!(
+            verify_checksums(&bytes[..]),
+            (Ipv4Addr([10, 0, 0, 1]), 8080)
+        );
+    }
+
+    #[test]
+    fn rewrite_addrs_only() {
+        let mut bytes = TCP_FRAME;
+
+        // the tcp checksum is updated as the pseudo header has changed
+        let mut editor = PacketEditor::new(&mut bytes[..]).unwrap();
+        editor.ipv4_mut().set_source_ip(Ipv4Addr([192, 168, 1, 1]));
+        editor.finalize();
+        verify_checksums(&bytes[..]);
+
+        // the tcp checksum is untouched if only the ttl changes
+        let tcp_cksum = [bytes[50], bytes[51]];
+        let mut editor = PacketEditor::new(&mut bytes[..]).unwrap();
+        editor.ipv4_mut().set_time_to_live(10);
+        editor.finalize();
+        assert_eq!(&bytes[50..52], &tcp_cksum[..]);
+        verify_checksums(&bytes[..]);
+
+        // the editor rejects non-ipv4 frames
+        bytes[12..14].copy_from_slice(&[0x08, 0x06]);
+        assert!(PacketEditor::new(&mut bytes[..]).is_none());
+    }
+}
@@ -0,0 +1,99 @@
+use std::fmt;
+
+/// The header that fails to parse, in `parse_untrusted` or in `Header::parse`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    Ether,
+    Arp,
+    Ipv4,
+    Ipv4Options,
+    Ipv6,
+    Ipv6ExtHeader,
+    Tcp,
+    TcpOptions,
+    Udp,
+    Geneve,
+    Pppoe,
+    Icmpv4,
+    Icmpv6,
+    Igmp,
+    Esp,
+    Ah,
+    Radius,
+    Diameter,
+    L2tp,
+    Ptp,
+    Quic,
+    Tls,
+    SomeIp,
+    Http,
+    /// A protocol of `ProtocolRegistry` whose handler rejects the packet.
+    Custom,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let layer = match self {
+            ParseError::Ether => "ethernet header",
+            ParseError::Arp => "arp packet",
+            ParseError::Ipv4 => "ipv4 header",
+            ParseError::Ipv4Options => "ipv4 options",
+            ParseError::Ipv6 => "ipv6 header",
+            ParseError::Ipv6ExtHeader => "ipv6 extension header",
+            ParseError::Tcp => "tcp header",
+            ParseError::TcpOptions => "tcp options",
+            ParseError::Udp => "udp header",
+            ParseError::Geneve => "geneve header",
+            ParseError::Pppoe => "pppoe session header",
+            ParseError::Icmpv4 => "icmpv4 message",
+            ParseError::Icmpv6 => "icmpv6 message",
+            ParseError::Igmp => "igmp message",
+            ParseError::Esp => "esp header",
+            ParseError::Ah => "authentication header",
+            ParseError::Radius => "radius packet",
+            ParseError::Diameter => "diameter message",
+            ParseError::L2tp => "l2tp header",
+            ParseError::Ptp => "ptp message",
+            ParseError::Quic => "quic packet",
+            ParseError::Tls => "tls record",
+            ParseError::SomeIp => "some/ip message",
+            ParseError::Http => "http request head",
+            ParseError::Custom => "custom protocol header",
+        };
+        write!(f, "malformed {layer}")
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The error of checking a message of a protocol over tcp, whose messages may
+/// be split across tcp segments, e.g. a tls record or an http request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamError {
+    /// The input is truncated, the caller can retry after appending the next
+    /// tcp segment. It holds the total length of the input that is required
+    /// to continue, counted from the start of the message, if the length is
+    /// known before the message is complete.
+    NeedMoreData(Option<usize>),
+    /// A limit of the parser is exceeded, e.g. the length of an http header
+    /// line.
+    TooLong,
+    /// The input is not of the protocol, or a length field exceeds its
+    /// enclosing structure.
+    Malformed,
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StreamError::NeedMoreData(Some(len)) => {
+                write!(f, "input is truncated, need {len} bytes")
+            }
+            StreamError::NeedMoreData(None) => write!(f, "input is truncated"),
+            StreamError::TooLong => write!(f, "input exceeds the limits of the parser"),
+            StreamError::Malformed => write!(f, "malformed input"),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {}
@@ -66,16 +66,48 @@ enum_sim! {
         ARP =  0x0806,
         IPV4 = 0x0800,
         IPV6 = 0x86DD,
+        TEB = 0x6558,
+        PPPOE_DISCOVERY = 0x8863,
+        PPPOE_SESSION = 0x8864,
+        MAC_CONTROL = 0x8808,
+        /// The 802.1Q vlan tag.
+        VLAN = 0x8100,
+        /// The 802.1ad service vlan tag.
+        QINQ = 0x88A8,
+        MPLS = 0x8847,
+        MPLS_MULTICAST = 0x8848,
+        /// The precision time protocol, ieee 1588.
+        PTP = 0x88F7,
+    }
+}
+
+impl EtherType {
+    /// Return the name of a known ethertype, e.g. for logging.
+    pub fn name(&self) -> Option<&'static str> {
+        let name = match *self {
+            EtherType::ARP => "ARP",
+            EtherType::IPV4 => "IPv4",
+            EtherType::IPV6 => "IPv6",
+            EtherType::TEB => "TEB",
+            EtherType::PPPOE_DISCOVERY => "PPPoE-Discovery",
+            EtherType::PPPOE_SESSION => "PPPoE-Session",
+            EtherType::MAC_CONTROL => "MAC-Control",
+            EtherType::VLAN => "VLAN",
+            EtherType::QINQ => "QinQ",
+            EtherType::MPLS => "MPLS",
+            EtherType::MPLS_MULTICAST => "MPLS-Multicast",
+            EtherType::PTP => "PTP",
+            _ => return None,
+        };
+        Some(name)
     }
 }
 
 impl fmt::Display for EtherType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
-            EtherType::ARP => write!(f, "ARP"),
-            EtherType::IPV4 => write!(f, "IPv4"),
-            EtherType::IPV6 => write!(f, "IPv6"),
-            _ => write!(f, "0x{:04x}", u16::from(*self)),
+        match self.name() {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "0x{:04x}", u16::from(*self)),
         }
     }
 }
@@ -87,3 +119,6 @@ mod packet;
 pub use self::packet::{
     EtherPacket, ETHER_MAX_JUMBO_PKT_LEN, ETHER_MAX_LEN, ETHER_MIN_LEN, ETHER_MTU, ETHER_OVERHEAD,
 };
+
+mod pfc;
+pub use pfc::{build_pfc_pause, MAC_CONTROL_DEST_MAC, PFC_FRAME_LEN, PFC_OPCODE};
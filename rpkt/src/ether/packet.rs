@@ -2,6 +2,7 @@ use bytes::Buf;
 
 use crate::PktMut;
 use crate::{Cursor, CursorMut};
+use crate::{Header, ParseError};
 
 use super::header::{EtherHeader, ETHER_HEADER_LEN};
 use super::{EtherType, MacAddr};
@@ -97,6 +98,23 @@ impl<'a> EtherPacket<Cursor<'a>> {
     }
 }
 
+impl<'a> Header<'a> for EtherPacket<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        EtherPacket::parse(Cursor::new(buf)).map_err(|_| ParseError::Ether)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        ETHER_HEADER_LEN
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        self.cursor_payload().chunk_shared_lifetime()
+    }
+}
+
 impl<'a> EtherPacket<CursorMut<'a>> {
     #[inline]
     pub fn split(self) -> (EtherHeader<&'a mut [u8]>, CursorMut<'a>) {
@@ -0,0 +1,79 @@
+use super::header::ETHER_HEADER_LEN;
+use super::{EtherType, MacAddr, ETHER_MIN_LEN};
+
+/// The reserved destination address of the mac control frames.
+pub const MAC_CONTROL_DEST_MAC: MacAddr = MacAddr([0x01, 0x80, 0xc2, 0x00, 0x00, 0x01]);
+
+/// The mac control opcode of the 802.1Qbb priority-based flow control frames.
+pub const PFC_OPCODE: u16 = 0x0101;
+
+/// The length of a pfc frame without the fcs, including the padding.
+pub const PFC_FRAME_LEN: usize = ETHER_MIN_LEN - 4;
+
+/// Write an 802.1Qbb pfc frame into `buf`, and return the length of the frame.
+///
+/// The pause time of priority `i` is set to `quanta[i]` if bit `i` of
+/// `priority_enable_mask` is set, and it is ignored by the receiver otherwise.
+/// The source address is left zero, it can be set with
+/// `EtherPacket::set_source_mac`.
+///
+/// # Panics
+/// This function panics if `buf` is shorter than `PFC_FRAME_LEN`.
+pub fn build_pfc_pause(priority_enable_mask: u8, quanta: [u16; 8], buf: &mut [u8]) -> usize {
+    assert!(buf.len() >= PFC_FRAME_LEN);
+    let frame = &mut buf[..PFC_FRAME_LEN];
+    frame.fill(0);
+
+    frame[0..6].copy_from_slice(MAC_CONTROL_DEST_MAC.as_bytes());
+    frame[12..14].copy_from_slice(&u16::from(EtherType::MAC_CONTROL).to_be_bytes());
+
+    let control = &mut frame[ETHER_HEADER_LEN..];
+    control[0..2].copy_from_slice(&PFC_OPCODE.to_be_bytes());
+    // the class-enable vector, the high byte is reserved
+    control[3] = priority_enable_mask;
+    for (i, quantum) in quanta.iter().enumerate() {
+        control[4 + 2 * i..6 + 2 * i].copy_from_slice(&quantum.to_be_bytes());
+    }
+
+    PFC_FRAME_LEN
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ether::EtherPacket;
+    use crate::{Buf, Cursor};
+
+    #[test]
+    fn pfc_pause_for_priority_3() {
+        let mut quanta = [0; 8];
+        quanta[3] = 0xffff;
+        let mut buf = [0xaa; 128];
+        let len = build_pfc_pause(1 << 3, quanta, &mut buf[..]);
+        assert_eq!(len, 60);
+
+        let ethpkt = EtherPacket::parse(Cursor::new(&buf[..len])).unwrap();
+        assert_eq!(ethpkt.dest_mac(), MAC_CONTROL_DEST_MAC);
+        assert!(ethpkt.dest_mac().is_multicast());
+        assert_eq!(ethpkt.source_mac(), MacAddr([0; 6]));
+        assert_eq!(ethpkt.ethertype(), EtherType::MAC_CONTROL);
+
+        let control = ethpkt.payload();
+        let control = control.chunk();
+        assert_eq!(&control[0..2], &[0x01, 0x01]);
+        assert_eq!(&control[2..4], &[0x00, 0x08]);
+        for i in 0..8 {
+            let quantum = u16::from_be_bytes([control[4 + 2 * i], control[5 + 2 * i]]);
+            assert_eq!(quantum, quanta[i]);
+        }
+        // the padding is zeroed, the bytes after the frame are untouched
+        assert!(control[20..].iter().all(|b| *b == 0));
+        assert_eq!(buf[len], 0xaa);
+    }
+
+    #[test]
+    #[should_panic]
+    fn pfc_pause_short_buf() {
+        build_pfc_pause(0xff, [1; 8], &mut [0; 59][..]);
+    }
+}
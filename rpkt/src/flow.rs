@@ -0,0 +1,231 @@
+use std::net::IpAddr;
+
+use byteorder::{ByteOrder, NetworkEndian};
+
+use crate::ether::{EtherPacket, EtherType};
+use crate::ipv4::{IpProtocol, Ipv4Packet};
+use crate::ipv6::Ipv6Packet;
+use crate::tcp::TcpPacket;
+use crate::udp::UdpPacket;
+use crate::Cursor;
+
+/// The length of the byte key returned by `FiveTuple::as_key_bytes`.
+pub const FIVE_TUPLE_KEY_LEN: usize = 37;
+
+/// The 5-tuple that identifies a tcp/udp flow.
+///
+/// The two addresses are usually from the same family, a tuple of mixed
+/// families is handled as if the ipv4 address were mapped to ipv6.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub struct FiveTuple {
+    pub src_ip: IpAddr,
+    pub dst_ip: IpAddr,
+    pub protocol: IpProtocol,
+    pub src_port: u16,
+    pub dst_port: u16,
+}
+
+impl FiveTuple {
+    /// Extract the 5-tuple from an ethernet frame.
+    ///
+    /// Return `None` if the frame is not an ipv4 or ipv6 tcp/udp packet, if it
+    /// is a non-first ipv4 fragment that does not carry the transport header,
+    /// or if the transport header of an ipv6 packet follows extension headers.
+    pub fn from_packet(frame: Cursor<'_>) -> Option<Self> {
+        let ethpkt = EtherPacket::parse(frame).ok()?;
+        let (src_ip, dst_ip, protocol, l4) = match ethpkt.ethertype() {
+            EtherType::IPV4 => {
+                let ippkt = Ipv4Packet::parse(ethpkt.payload()).ok()?;
+                if ippkt.frag_offset() != 0 {
+                    return None;
+                }
+                let src_ip = IpAddr::from(ippkt.source_ip().0);
+                let dst_ip = IpAddr::from(ippkt.dest_ip().0);
+                (src_ip, dst_ip, ippkt.protocol(), ippkt.payload())
+            }
+            EtherType::IPV6 => {
+                let ippkt = Ipv6Packet::parse(ethpkt.payload()).ok()?;
+                let src_ip = IpAddr::from(ippkt.source_ip().0);
+                let dst_ip = IpAddr::from(ippkt.dest_ip().0);
+                (src_ip, dst_ip, ippkt.next_header(), ippkt.payload())
+            }
+            _ => return None,
+        };
+
+        let (src_port, dst_port) = match protocol {
+            IpProtocol::TCP => {
+                let tcppkt = TcpPacket::parse(l4).ok()?;
+                (tcppkt.src_port(), tcppkt.dst_port())
+            }
+            IpProtocol::UDP => {
+                let udppkt = UdpPacket::parse(l4).ok()?;
+                (udppkt.source_port(), udppkt.dest_port())
+            }
+            _ => return None,
+        };
+
+        Some(Self {
+            src_ip,
+            dst_ip,
+            protocol,
+            src_port,
+            dst_port,
+        })
+    }
+
+    /// Serialize the 5-tuple into a fixed-size byte key, e.g. for a `rte_hash` table.
+    ///
+    /// The layout is source ip, destination ip, protocol, source port and destination
+    /// port, with all the fields in network byte order. The ipv4 addresses are
+    /// mapped to ipv6, so that the tuples of both families share the key layout.
+    pub fn as_key_bytes(&self) -> [u8; FIVE_TUPLE_KEY_LEN] {
+        let mut key = [0; FIVE_TUPLE_KEY_LEN];
+        key[0..16].copy_from_slice(&to_ipv6_octets(self.src_ip));
+        key[16..32].copy_from_slice(&to_ipv6_octets(self.dst_ip));
+        key[32] = self.protocol.into();
+        NetworkEndian::write_u16(&mut key[33..35], self.src_port);
+        NetworkEndian::write_u16(&mut key[35..37], self.dst_port);
+        key
+    }
+
+    /// Return the 5-tuple of the reverse direction of the flow.
+    pub fn reversed(&self) -> Self {
+        Self {
+            src_ip: self.dst_ip,
+            dst_ip: self.src_ip,
+            protocol: self.protocol,
+            src_port: self.dst_port,
+            dst_port: self.src_port,
+        }
+    }
+}
+
+/// Return the octets of `addr`, an ipv4 address is mapped to ipv6.
+pub fn to_ipv6_octets(addr: IpAddr) -> [u8; 16] {
+    match addr {
+        IpAddr::V4(addr) => addr.to_ipv6_mapped().octets(),
+        IpAddr::V6(addr) => addr.octets(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ether::*;
+    use crate::ipv4::*;
+    use crate::ipv6::*;
+    use crate::malformed::TCP_FRAME;
+    use crate::udp::*;
+    use crate::{Buf, CursorMut};
+
+    #[test]
+    fn five_tuple_from_tcp_packet() {
+        let tuple = FiveTuple::from_packet(Cursor::new(&TCP_FRAME[..])).unwrap();
+        assert_eq!(tuple.src_ip, IpAddr::from([192, 168, 1, 140]));
+        assert_eq!(tuple.dst_ip, IpAddr::from([174, 143, 213, 184]));
+        assert_eq!(tuple.protocol, IpProtocol::TCP);
+        assert_eq!(tuple.src_port, 57678);
+        assert_eq!(tuple.dst_port, 80);
+
+        assert_eq!(
+            tuple.as_key_bytes(),
+            [
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 192, 168, 1, 140, 0, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 0xff, 0xff, 174, 143, 213, 184, 6, 0xe1, 0x4e, 0x00, 0x50
+            ]
+        );
+
+        let reversed = tuple.reversed();
+        assert_eq!(reversed.src_ip, tuple.dst_ip);
+        assert_eq!(reversed.dst_ip, tuple.src_ip);
+        assert_eq!(reversed.protocol, IpProtocol::TCP);
+        assert_eq!(reversed.src_port, tuple.dst_port);
+        assert_eq!(reversed.dst_port, tuple.src_port);
+        assert_eq!(reversed.reversed(), tuple);
+    }
+
+    #[test]
+    fn five_tuple_from_udp_packet() {
+        let mut bytes = [0xff; 64];
+        let mut buf = CursorMut::new(&mut bytes[..]);
+        buf.advance(ETHER_HEADER_LEN + IPV4_HEADER_LEN + UDP_HEADER_LEN);
+
+        let mut udppkt = UdpPacket::prepend_header(buf, &UDP_HEADER_TEMPLATE);
+        udppkt.set_source_port(53);
+        udppkt.set_dest_port(1024);
+
+        let mut ipheader = IPV4_HEADER_TEMPLATE;
+        ipheader.set_protocol(IpProtocol::UDP);
+        ipheader.set_source_ip(Ipv4Addr([10, 0, 0, 1]));
+        ipheader.set_dest_ip(Ipv4Addr([10, 0, 0, 2]));
+        let mut ippkt = Ipv4Packet::prepend_header(udppkt.release(), &ipheader);
+        ippkt.adjust_checksum();
+
+        let ethpkt = EtherPacket::prepend_header(ippkt.release(), &ETHER_HEADER_TEMPLATE);
+        let frame = ethpkt.release();
+
+        let tuple = FiveTuple::from_packet(Cursor::new(frame.chunk())).unwrap();
+        assert_eq!(tuple.src_ip, IpAddr::from([10, 0, 0, 1]));
+        assert_eq!(tuple.dst_ip, IpAddr::from([10, 0, 0, 2]));
+        assert_eq!(tuple.protocol, IpProtocol::UDP);
+        assert_eq!(tuple.src_port, 53);
+        assert_eq!(tuple.dst_port, 1024);
+
+        let reversed = tuple.reversed();
+        assert_eq!(reversed.src_ip, IpAddr::from([10, 0, 0, 2]));
+        assert_eq!(reversed.dst_ip, IpAddr::from([10, 0, 0, 1]));
+        assert_eq!(reversed.src_port, 1024);
+        assert_eq!(reversed.dst_port, 53);
+    }
+
+    #[test]
+    fn five_tuple_from_ipv6_packet() {
+        let src_ip = [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        let dst_ip = [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+
+        let mut bytes = [0xff; 80];
+        let mut buf = CursorMut::new(&mut bytes[..]);
+        buf.advance(ETHER_HEADER_LEN + IPV6_HEADER_LEN + UDP_HEADER_LEN);
+
+        let mut udppkt = UdpPacket::prepend_header(buf, &UDP_HEADER_TEMPLATE);
+        udppkt.set_source_port(546);
+        udppkt.set_dest_port(547);
+
+        let mut ipheader = Ipv6Header::new_unchecked([0; IPV6_HEADER_LEN]);
+        ipheader.adjust_version();
+        ipheader.set_next_header(IpProtocol::UDP);
+        ipheader.set_hop_limit(64);
+        ipheader.set_source_ip(&Ipv6Addr(src_ip));
+        ipheader.set_dest_ip(&Ipv6Addr(dst_ip));
+        let ippkt = Ipv6Packet::prepend_header(udppkt.release(), &ipheader);
+
+        let mut ethpkt = EtherPacket::prepend_header(ippkt.release(), &ETHER_HEADER_TEMPLATE);
+        ethpkt.set_ethertype(EtherType::IPV6);
+        let frame = ethpkt.release();
+
+        let tuple = FiveTuple::from_packet(Cursor::new(frame.chunk())).unwrap();
+        assert_eq!(tuple.src_ip, IpAddr::from(src_ip));
+        assert_eq!(tuple.dst_ip, IpAddr::from(dst_ip));
+        assert_eq!(tuple.protocol, IpProtocol::UDP);
+        assert_eq!(tuple.src_port, 546);
+        assert_eq!(tuple.dst_port, 547);
+
+        let key = tuple.as_key_bytes();
+        assert_eq!(key[0..16], src_ip);
+        assert_eq!(key[16..32], dst_ip);
+        assert_eq!(key[32..], [17, 0x02, 0x22, 0x02, 0x23]);
+    }
+
+    #[test]
+    fn five_tuple_from_non_tcp_udp_packet() {
+        let mut bytes = TCP_FRAME;
+        // change the ip protocol to icmp
+        bytes[23] = 1;
+        assert_eq!(FiveTuple::from_packet(Cursor::new(&bytes[..])), None);
+
+        // change the ethertype to arp
+        let mut bytes = TCP_FRAME;
+        bytes[12..14].copy_from_slice(&[0x08, 0x06]);
+        assert_eq!(FiveTuple::from_packet(Cursor::new(&bytes[..])), None);
+    }
+}
@@ -0,0 +1,140 @@
+use byteorder::{ByteOrder, NetworkEndian};
+
+use crate::ether::EtherType;
+
+header_field_val_accessors! {
+    (ver_opt_len, ver_opt_len_mut, 0),
+    (flags, flags_mut, 1),
+}
+
+header_field_range_accessors! {
+    (protocol_type, protocol_type_mut, 2..4),
+    (vni, vni_mut, 4..7),
+}
+
+pub const GENEVE_HEADER_LEN: usize = 8;
+
+/// A geneve header with no options, carrying an ethernet frame.
+pub const GENEVE_HEADER_TEMPLATE: GeneveHeader<[u8; 8]> = GeneveHeader {
+    buf: [0x00, 0x00, 0x65, 0x58, 0x00, 0x00, 0x00, 0x00],
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct GeneveHeader<T> {
+    buf: T,
+}
+
+impl<T: AsRef<[u8]>> GeneveHeader<T> {
+    #[inline]
+    pub fn new(buf: T) -> Result<Self, T> {
+        if buf.as_ref().len() >= GENEVE_HEADER_LEN {
+            Ok(Self { buf })
+        } else {
+            Err(buf)
+        }
+    }
+
+    #[inline]
+    pub fn new_unchecked(buf: T) -> Self {
+        Self { buf }
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf.as_ref()[0..GENEVE_HEADER_LEN]
+    }
+
+    #[inline]
+    pub fn to_owned(&self) -> GeneveHeader<[u8; GENEVE_HEADER_LEN]> {
+        let mut buf = [0; GENEVE_HEADER_LEN];
+        buf.copy_from_slice(self.as_bytes());
+        GeneveHeader { buf }
+    }
+
+    #[inline]
+    pub fn version(&self) -> u8 {
+        let data = ver_opt_len(self.buf.as_ref());
+        *data >> 6
+    }
+
+    /// The length of the options in bytes.
+    ///
+    /// The header stores the length in 4-byte units.
+    #[inline]
+    pub fn option_len(&self) -> usize {
+        let data = ver_opt_len(self.buf.as_ref());
+        usize::from(*data & 0x3f) * 4
+    }
+
+    /// The length of the header, including the options.
+    #[inline]
+    pub fn header_len(&self) -> usize {
+        GENEVE_HEADER_LEN + self.option_len()
+    }
+
+    #[inline]
+    pub fn oam(&self) -> bool {
+        let data = flags(self.buf.as_ref());
+        *data & 0x80 != 0
+    }
+
+    #[inline]
+    pub fn critical(&self) -> bool {
+        let data = flags(self.buf.as_ref());
+        *data & 0x40 != 0
+    }
+
+    #[inline]
+    pub fn protocol_type(&self) -> EtherType {
+        let data = protocol_type(self.buf.as_ref());
+        NetworkEndian::read_u16(data).into()
+    }
+
+    #[inline]
+    pub fn vni(&self) -> u32 {
+        let data = vni(self.buf.as_ref());
+        NetworkEndian::read_u24(data)
+    }
+}
+
+impl<T: AsMut<[u8]>> GeneveHeader<T> {
+    #[inline]
+    pub fn set_version(&mut self, value: u8) {
+        assert!(value <= 0x03);
+        let data = ver_opt_len_mut(self.buf.as_mut());
+        *data = (*data & 0x3f) | (value << 6);
+    }
+
+    /// Set the length of the options in bytes, which must be a multiple of 4.
+    #[inline]
+    pub fn set_option_len(&mut self, value: usize) {
+        assert!(value <= 0x3f * 4 && value % 4 == 0);
+        let data = ver_opt_len_mut(self.buf.as_mut());
+        *data = (*data & 0xc0) | (value / 4) as u8;
+    }
+
+    #[inline]
+    pub fn set_oam(&mut self, value: bool) {
+        let data = flags_mut(self.buf.as_mut());
+        *data = (*data & !0x80) | (u8::from(value) << 7);
+    }
+
+    #[inline]
+    pub fn set_critical(&mut self, value: bool) {
+        let data = flags_mut(self.buf.as_mut());
+        *data = (*data & !0x40) | (u8::from(value) << 6);
+    }
+
+    #[inline]
+    pub fn set_protocol_type(&mut self, value: EtherType) {
+        let data = protocol_type_mut(self.buf.as_mut());
+        NetworkEndian::write_u16(data, value.into());
+    }
+
+    #[inline]
+    pub fn set_vni(&mut self, value: u32) {
+        assert!(value <= 0xffffff);
+        let data = vni_mut(self.buf.as_mut());
+        NetworkEndian::write_u24(data, value);
+    }
+}
@@ -0,0 +1,11 @@
+mod header;
+pub use header::{GeneveHeader, GENEVE_HEADER_LEN, GENEVE_HEADER_TEMPLATE};
+
+mod option;
+pub use option::{GeneveOption, GeneveOptionIter};
+
+mod packet;
+pub use self::packet::GenevePacket;
+
+/// The udp destination port assigned to geneve.
+pub const GENEVE_UDP_PORT: u16 = 6081;
@@ -0,0 +1,85 @@
+use byteorder::{ByteOrder, NetworkEndian};
+
+/// A variable-length option of a geneve header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GeneveOption<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> GeneveOption<'a> {
+    #[inline]
+    pub fn class(&self) -> u16 {
+        NetworkEndian::read_u16(&self.buf[0..2])
+    }
+
+    /// The option type, whose most significant bit is the critical bit.
+    #[inline]
+    pub fn opt_type(&self) -> u8 {
+        self.buf[2]
+    }
+
+    #[inline]
+    pub fn critical(&self) -> bool {
+        self.buf[2] & 0x80 != 0
+    }
+
+    /// The length of the option data in bytes.
+    ///
+    /// The option stores the length in 4-byte units, excluding the 4-byte
+    /// option header.
+    #[inline]
+    pub fn data_len(&self) -> usize {
+        usize::from(self.buf[3] & 0x1f) * 4
+    }
+
+    #[inline]
+    pub fn data(&self) -> &'a [u8] {
+        &self.buf[4..]
+    }
+}
+
+pub struct GeneveOptionIter<'a> {
+    buf: &'a [u8],
+    valid: bool,
+}
+
+impl<'a> GeneveOptionIter<'a> {
+    #[inline]
+    pub fn from_option_bytes(buf: &'a [u8]) -> GeneveOptionIter<'a> {
+        Self { buf, valid: true }
+    }
+
+    #[inline]
+    pub fn check_option_bytes(buf: &'a [u8]) -> bool {
+        let mut reader = Self::from_option_bytes(buf);
+        for _ in &mut reader {}
+        reader.valid
+    }
+}
+
+impl<'a> Iterator for GeneveOptionIter<'a> {
+    type Item = GeneveOption<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.valid || self.buf.is_empty() {
+            return None;
+        }
+
+        // each option carries a 4-byte header, so the iterator always advances
+        if self.buf.len() < 4 {
+            self.valid = false;
+            return None;
+        }
+        let opt_len = 4 + usize::from(self.buf[3] & 0x1f) * 4;
+        if self.buf.len() < opt_len {
+            self.valid = false;
+            return None;
+        }
+
+        let opt = GeneveOption {
+            buf: &self.buf[..opt_len],
+        };
+        self.buf = &self.buf[opt_len..];
+        Some(opt)
+    }
+}
@@ -0,0 +1,254 @@
+use bytes::Buf;
+
+use crate::ether::EtherType;
+use crate::PktMut;
+use crate::{Cursor, CursorMut, Header, ParseError};
+
+use super::header::{GeneveHeader, GENEVE_HEADER_LEN};
+use super::option::GeneveOptionIter;
+
+packet_base! {
+    pub struct GenevePacket: GeneveHeader {
+        header_len: GENEVE_HEADER_LEN,
+        get_methods: [
+            (version, u8),
+            (option_len, usize),
+            (header_len, usize),
+            (oam, bool),
+            (critical, bool),
+            (protocol_type, EtherType),
+            (vni, u32),
+        ],
+        set_methods: [
+            (set_version, value: u8),
+            (set_oam, value: bool),
+            (set_critical, value: bool),
+            (set_protocol_type, value: EtherType),
+            (set_vni, value: u32),
+        ],
+        unchecked_set_methods:[
+            (set_option_len_unchecked, set_option_len, value: usize)
+        ]
+    }
+}
+
+impl<T: Buf> GenevePacket<T> {
+    #[inline]
+    pub fn parse(buf: T) -> Result<GenevePacket<T>, T> {
+        if buf.chunk().len() < GENEVE_HEADER_LEN {
+            return Err(buf);
+        }
+
+        let packet = GenevePacket::parse_unchecked(buf);
+
+        if packet.header_len() <= packet.buf.chunk().len() {
+            Ok(packet)
+        } else {
+            Err(packet.release())
+        }
+    }
+
+    #[inline]
+    pub fn option_bytes(&self) -> &[u8] {
+        &self.buf.chunk()[GENEVE_HEADER_LEN..self.header_len()]
+    }
+
+    #[inline]
+    pub fn option_iter(&self) -> GeneveOptionIter<'_> {
+        GeneveOptionIter::from_option_bytes(self.option_bytes())
+    }
+
+    /// Return the inner frame, which starts past the options.
+    #[inline]
+    pub fn payload(self) -> T {
+        let header_len = self.header_len();
+
+        let mut buf = self.release();
+        buf.advance(header_len);
+
+        buf
+    }
+}
+
+impl<T: PktMut> GenevePacket<T> {
+    /// Prepend `header` and the `option_len` bytes of options to `buf`.
+    ///
+    /// The options are left uninitialized, they can be filled in with
+    /// `option_bytes_mut`.
+    #[inline]
+    pub fn prepend_header<TH: AsRef<[u8]>>(
+        mut buf: T,
+        header: &GeneveHeader<TH>,
+        option_len: usize,
+    ) -> GenevePacket<T> {
+        let header_len = GENEVE_HEADER_LEN + option_len;
+        assert!(buf.chunk_headroom() >= header_len);
+        buf.move_back(header_len);
+
+        let data = &mut buf.chunk_mut()[0..GENEVE_HEADER_LEN];
+        data.copy_from_slice(header.as_bytes());
+
+        let mut genevepkt = GenevePacket::parse_unchecked(buf);
+        genevepkt.set_option_len_unchecked(option_len);
+        genevepkt
+    }
+
+    #[inline]
+    pub fn option_bytes_mut(&mut self) -> &mut [u8] {
+        let header_len = self.header_len();
+        &mut self.buf.chunk_mut()[GENEVE_HEADER_LEN..header_len]
+    }
+}
+
+impl<'a> GenevePacket<Cursor<'a>> {
+    #[inline]
+    pub fn cursor_header(&self) -> GeneveHeader<&'a [u8]> {
+        let data = &self.buf.chunk_shared_lifetime()[..GENEVE_HEADER_LEN];
+        GeneveHeader::new_unchecked(data)
+    }
+
+    #[inline]
+    pub fn cursor_option_iter(&self) -> GeneveOptionIter<'a> {
+        let data = &self.buf.chunk_shared_lifetime()[GENEVE_HEADER_LEN..self.header_len()];
+        GeneveOptionIter::from_option_bytes(data)
+    }
+
+    #[inline]
+    pub fn cursor_payload(&self) -> Cursor<'a> {
+        Cursor::new(&self.buf.chunk_shared_lifetime()[self.header_len()..])
+    }
+}
+
+impl<'a> Header<'a> for GenevePacket<Cursor<'a>> {
+    /// Parse a geneve header, the options are checked as well.
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        let genevepkt = GenevePacket::parse(Cursor::new(buf)).map_err(|_| ParseError::Geneve)?;
+        if !GeneveOptionIter::check_option_bytes(genevepkt.option_bytes()) {
+            return Err(ParseError::Geneve);
+        }
+        Ok(genevepkt)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        self.header_len()
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        self.cursor_payload().chunk_shared_lifetime()
+    }
+}
+
+impl<'a> GenevePacket<CursorMut<'a>> {
+    #[inline]
+    pub fn split(self) -> (GeneveHeader<&'a mut [u8]>, &'a mut [u8], CursorMut<'a>) {
+        let header_len = self.header_len();
+
+        let (hdr, payload) = self
+            .buf
+            .chunk_mut_shared_lifetime()
+            .split_at_mut(header_len);
+        let (hdr, option_bytes) = hdr.split_at_mut(GENEVE_HEADER_LEN);
+
+        (
+            GeneveHeader::new_unchecked(hdr),
+            option_bytes,
+            CursorMut::new(payload),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ether::*;
+    use crate::geneve::GENEVE_HEADER_TEMPLATE;
+
+    // A geneve header with two options, followed by 4 bytes of the inner frame.
+    static GENEVE_BYTES: [u8; 32] = [
+        0x05, 0xc0, 0x65, 0x58, 0x12, 0x34, 0x56, 0x00, // header, 5 option units
+        0x01, 0x02, 0x80, 0x01, 0xaa, 0xbb, 0xcc, 0xdd, // class 0x0102, critical, 4 bytes
+        0xff, 0xff, 0x03, 0x02, 0x00, 0x01, 0x02, 0x03, // class 0xffff, 8 bytes
+        0x04, 0x05, 0x06, 0x07, 0xde, 0xad, 0xbe, 0xef, // inner frame
+    ];
+
+    #[test]
+    fn packet_parse_with_options() {
+        let genevepkt = GenevePacket::parse(Cursor::new(&GENEVE_BYTES[..])).unwrap();
+        assert_eq!(genevepkt.version(), 0);
+        assert_eq!(genevepkt.option_len(), 20);
+        assert_eq!(genevepkt.header_len(), 28);
+        assert!(genevepkt.oam());
+        assert!(genevepkt.critical());
+        assert_eq!(genevepkt.protocol_type(), EtherType::TEB);
+        assert_eq!(genevepkt.vni(), 0x123456);
+
+        assert!(GeneveOptionIter::check_option_bytes(
+            genevepkt.option_bytes()
+        ));
+        let mut opt_iter = genevepkt.cursor_option_iter();
+        let opt = opt_iter.next().unwrap();
+        assert_eq!(opt.class(), 0x0102);
+        assert_eq!(opt.opt_type(), 0x80);
+        assert!(opt.critical());
+        assert_eq!(opt.data_len(), 4);
+        assert_eq!(opt.data(), &[0xaa, 0xbb, 0xcc, 0xdd][..]);
+        let opt = opt_iter.next().unwrap();
+        assert_eq!(opt.class(), 0xffff);
+        assert!(!opt.critical());
+        assert_eq!(opt.data_len(), 8);
+        assert_eq!(opt.data(), &GENEVE_BYTES[20..28]);
+        assert!(opt_iter.next().is_none());
+
+        assert_eq!(genevepkt.cursor_payload().chunk(), &GENEVE_BYTES[28..]);
+        assert_eq!(genevepkt.payload().chunk(), &[0xde, 0xad, 0xbe, 0xef][..]);
+    }
+
+    #[test]
+    fn packet_parse_invalid() {
+        // the options exceed the packet
+        let mut bytes = GENEVE_BYTES;
+        bytes[0] = 0x07;
+        assert!(GenevePacket::parse(Cursor::new(&bytes[..])).is_err());
+        assert!(GenevePacket::parse(Cursor::new(&bytes[..7])).is_err());
+
+        // an option longer than the option bytes
+        bytes[0] = 0x05;
+        bytes[19] = 0x03;
+        let genevepkt = GenevePacket::parse(Cursor::new(&bytes[..])).unwrap();
+        assert!(!GeneveOptionIter::check_option_bytes(
+            genevepkt.option_bytes()
+        ));
+        assert_eq!(genevepkt.option_iter().count(), 1);
+    }
+
+    #[test]
+    fn packet_build() {
+        let mut bytes = [0xff; 64];
+
+        let mut buf = CursorMut::new(&mut bytes[..]);
+        buf.advance(GENEVE_HEADER_LEN + 8 + ETHER_HEADER_LEN);
+        let ethpkt = EtherPacket::prepend_header(buf, &ETHER_HEADER_TEMPLATE);
+
+        let mut genevepkt =
+            GenevePacket::prepend_header(ethpkt.release(), &GENEVE_HEADER_TEMPLATE, 8);
+        genevepkt.set_vni(0xabcdef);
+        genevepkt.set_critical(true);
+        genevepkt
+            .option_bytes_mut()
+            .copy_from_slice(&[0x01, 0x02, 0x03, 0x01, 0x0a, 0x0b, 0x0c, 0x0d]);
+        assert_eq!(genevepkt.buf().cursor(), 0);
+
+        let genevepkt = GenevePacket::parse(Cursor::new(genevepkt.buf().chunk())).unwrap();
+        assert_eq!(genevepkt.header_len(), 16);
+        assert_eq!(genevepkt.vni(), 0xabcdef);
+        assert!(genevepkt.critical());
+        assert!(!genevepkt.oam());
+        assert_eq!(genevepkt.option_iter().count(), 1);
+
+        let ethpkt = EtherPacket::parse(genevepkt.payload()).unwrap();
+        assert_eq!(ethpkt.ethertype(), EtherType::IPV4);
+    }
+}
@@ -0,0 +1,13 @@
+/// The maximum length of the request line or of a header line, excluding the
+/// line terminator. A longer line is rejected with `StreamError::TooLong`.
+pub const HTTP_MAX_LINE_LEN: usize = 8192;
+
+/// The maximum number of the header fields of a request. A request with more
+/// fields is rejected with `StreamError::TooLong`.
+pub const HTTP_MAX_HEADERS: usize = 100;
+
+/// The tcp port assigned to http.
+pub const HTTP_PORT: u16 = 80;
+
+mod packet;
+pub use packet::{HttpHeaderIter, HttpRequest};
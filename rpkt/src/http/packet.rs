@@ -0,0 +1,438 @@
+use bytes::Buf;
+
+use super::{HTTP_MAX_HEADERS, HTTP_MAX_LINE_LEN};
+use crate::{Cursor, Header, ParseError, PktBuf, StreamError};
+
+/// The request line and the headers of an http/1.x request at the front of a
+/// tcp payload.
+///
+/// The body, if any, starts at `head_len` of the payload and is not parsed.
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct HttpRequest<T> {
+    buf: T,
+}
+
+impl<T: Buf> HttpRequest<T> {
+    #[inline]
+    pub fn parse_unchecked(buf: T) -> Self {
+        Self { buf }
+    }
+
+    #[inline]
+    pub fn buf(&self) -> &T {
+        &self.buf
+    }
+
+    #[inline]
+    pub fn release(self) -> T {
+        self.buf
+    }
+
+    /// Parse the request at the front of `buf`, the request line and the
+    /// headers must be complete, see `check_head`.
+    #[inline]
+    pub fn parse(buf: T) -> Result<HttpRequest<T>, T> {
+        if Self::check_head(buf.chunk()).is_err() {
+            return Err(buf);
+        }
+        Ok(HttpRequest { buf })
+    }
+
+    /// Check the request line and the headers at the front of `buf` and
+    /// return their length.
+    ///
+    /// Return `NeedMoreData` until the empty line that ends the headers is
+    /// seen, so that the caller can retry after appending the next tcp
+    /// segment. The total length is not known by then. Each line is checked
+    /// as soon as it is complete, and the method is checked from the first
+    /// bytes, so that a malformed or non-http payload is rejected early.
+    ///
+    /// The lines must end with a crlf and the obsolete line folding is
+    /// rejected. The header values may carry utf-8 but no control characters
+    /// other than the tab. A line longer than `HTTP_MAX_LINE_LEN` or more than
+    /// `HTTP_MAX_HEADERS` header fields return `TooLong`.
+    pub fn check_head(buf: &[u8]) -> Result<usize, StreamError> {
+        let mut rest = buf;
+        let request_line = next_line(&mut rest).map_err(|err| {
+            let method_len = buf.iter().position(|b| *b == b' ').unwrap_or(buf.len());
+            if err == StreamError::NeedMoreData(None)
+                && !buf[..method_len].iter().all(|b| is_tchar(*b))
+            {
+                StreamError::Malformed
+            } else {
+                err
+            }
+        })?;
+
+        let mut parts = request_line.splitn(3, |b| *b == b' ');
+        let (Some(method), Some(target), Some(version)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(StreamError::Malformed);
+        };
+        if method.is_empty()
+            || !method.iter().all(|b| is_tchar(*b))
+            || target.is_empty()
+            || !target.iter().all(|b| b.is_ascii_graphic())
+            || version.len() != 8
+            || !version.starts_with(b"HTTP/1.")
+            || !version[7].is_ascii_digit()
+        {
+            return Err(StreamError::Malformed);
+        }
+
+        let headers_start = buf.len() - rest.len();
+        let mut nb_headers = 0;
+        loop {
+            let line = next_line(&mut rest)?;
+            if line.is_empty() {
+                break;
+            }
+            nb_headers += 1;
+            if nb_headers > HTTP_MAX_HEADERS {
+                return Err(StreamError::TooLong);
+            }
+            check_header_line(line)?;
+        }
+        let head_len = buf.len() - rest.len();
+        // the headers end with the crlf of the last header line
+        if std::str::from_utf8(&buf[headers_start..head_len - 2]).is_err() {
+            return Err(StreamError::Malformed);
+        }
+        Ok(head_len)
+    }
+
+    #[inline]
+    pub fn method(&self) -> &str {
+        request_line(self.buf.chunk()).0
+    }
+
+    /// Return the request target, which is the path and the query for the
+    /// requests to an origin server.
+    #[inline]
+    pub fn target(&self) -> &str {
+        request_line(self.buf.chunk()).1
+    }
+
+    /// Return the http version, e.g. "HTTP/1.1".
+    #[inline]
+    pub fn version(&self) -> &str {
+        request_line(self.buf.chunk()).2
+    }
+
+    /// Return the length of the request line and the headers, including the
+    /// empty line that ends them.
+    #[inline]
+    pub fn head_len(&self) -> usize {
+        head_len(self.buf.chunk())
+    }
+
+    #[inline]
+    pub fn headers(&self) -> HttpHeaderIter<'_> {
+        HttpHeaderIter {
+            headers: header_lines(self.buf.chunk()),
+        }
+    }
+
+    /// Return the value of the first header field named `name`, which is
+    /// matched case-insensitively.
+    #[inline]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(self.headers(), name)
+    }
+
+    /// Return the body, or a prefix of it, past the headers.
+    #[inline]
+    pub fn payload(self) -> T
+    where
+        T: PktBuf,
+    {
+        let head_len = self.head_len();
+
+        let mut buf = self.release();
+        buf.advance(head_len);
+
+        buf
+    }
+}
+
+impl<'a> HttpRequest<Cursor<'a>> {
+    #[inline]
+    pub fn cursor_headers(&self) -> HttpHeaderIter<'a> {
+        HttpHeaderIter {
+            headers: header_lines(self.buf.chunk_shared_lifetime()),
+        }
+    }
+
+    #[inline]
+    pub fn cursor_header(&self, name: &str) -> Option<&'a str> {
+        find_header(self.cursor_headers(), name)
+    }
+
+    #[inline]
+    pub fn cursor_body(&self) -> &'a [u8] {
+        &self.buf.chunk_shared_lifetime()[self.head_len()..]
+    }
+}
+
+impl<'a> Header<'a> for HttpRequest<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        HttpRequest::parse(Cursor::new(buf)).map_err(|_| ParseError::Http)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        self.head_len()
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        self.cursor_body()
+    }
+}
+
+// The lines of a checked head cannot contain a crlf, so the first empty line
+// ends the head.
+#[inline]
+fn head_len(buf: &[u8]) -> usize {
+    buf.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4
+}
+
+// Return the method, the target and the version of a checked head, which are
+// checked to be ascii.
+#[inline]
+fn request_line(buf: &[u8]) -> (&str, &str, &str) {
+    let line_len = buf.iter().position(|b| *b == b'\r').unwrap();
+    let line = std::str::from_utf8(&buf[..line_len]).unwrap();
+    let mut parts = line.splitn(3, ' ');
+    (
+        parts.next().unwrap(),
+        parts.next().unwrap(),
+        parts.next().unwrap(),
+    )
+}
+
+// Return the header lines of a checked head, each ends with a crlf.
+#[inline]
+fn header_lines(buf: &[u8]) -> &str {
+    let headers_start = buf.iter().position(|b| *b == b'\n').unwrap() + 1;
+    std::str::from_utf8(&buf[headers_start..head_len(buf) - 2]).unwrap()
+}
+
+#[inline]
+fn find_header<'a>(mut headers: HttpHeaderIter<'a>, name: &str) -> Option<&'a str> {
+    headers
+        .find(|(field_name, _)| field_name.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+/// An iterator over the names and the values of the header fields of a
+/// request, the values are trimmed of the surrounding whitespace.
+pub struct HttpHeaderIter<'a> {
+    headers: &'a str,
+}
+
+impl<'a> Iterator for HttpHeaderIter<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // each line is checked to end with a crlf and to contain a colon
+        let (line, rest) = self.headers.split_once("\r\n")?;
+        self.headers = rest;
+        let (name, value) = line.split_once(':')?;
+        Some((name, value.trim_matches(|c| c == ' ' || c == '\t')))
+    }
+}
+
+// Split the next line from `buf`, without the crlf.
+#[inline]
+fn next_line<'a>(buf: &mut &'a [u8]) -> Result<&'a [u8], StreamError> {
+    let search_len = buf.len().min(HTTP_MAX_LINE_LEN + 2);
+    match buf[..search_len].iter().position(|b| *b == b'\n') {
+        Some(pos) => {
+            if pos == 0 || buf[pos - 1] != b'\r' {
+                return Err(StreamError::Malformed);
+            }
+            let line = &buf[..pos - 1];
+            *buf = &buf[pos + 1..];
+            Ok(line)
+        }
+        None if buf.len() >= HTTP_MAX_LINE_LEN + 2 => Err(StreamError::TooLong),
+        None => Err(StreamError::NeedMoreData(None)),
+    }
+}
+
+// Check a header line of a field name, a colon and a field value.
+fn check_header_line(line: &[u8]) -> Result<(), StreamError> {
+    let colon = line
+        .iter()
+        .position(|b| *b == b':')
+        .ok_or(StreamError::Malformed)?;
+    // a whitespace before the colon or at the start of a folded line is not a
+    // valid token character
+    let (name, value) = (&line[..colon], &line[colon + 1..]);
+    if name.is_empty()
+        || !name.iter().all(|b| is_tchar(*b))
+        || !value
+            .iter()
+            .all(|b| *b == b'\t' || (b' '..=b'~').contains(b) || *b >= 0x80)
+    {
+        return Err(StreamError::Malformed);
+    }
+    Ok(())
+}
+
+// The characters of a token, which are the method and the field names.
+#[inline]
+fn is_tchar(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A request of curl 8.5 with a second request pipelined after it.
+    static GET_REQUEST_BYTES: &[u8] = b"GET /api/v1/items?page=2 HTTP/1.1\r\n\
+Host: www.example.com\r\n\
+User-Agent: curl/8.5.0\r\n\
+Accept: */*\r\n\
+X-Forwarded-For:\t10.0.0.1, 10.0.0.2 \r\n\
+\r\n\
+GET / HTTP/1.1\r\n";
+
+    const HEAD_LEN: usize = 135;
+
+    #[test]
+    fn parse_get_request() {
+        let req = HttpRequest::parse(Cursor::new(GET_REQUEST_BYTES)).unwrap();
+        assert_eq!(req.method(), "GET");
+        assert_eq!(req.target(), "/api/v1/items?page=2");
+        assert_eq!(req.version(), "HTTP/1.1");
+        assert_eq!(req.head_len(), HEAD_LEN);
+        assert_eq!(req.cursor_body(), b"GET / HTTP/1.1\r\n");
+
+        let headers: Vec<_> = req.headers().collect();
+        assert_eq!(
+            headers,
+            [
+                ("Host", "www.example.com"),
+                ("User-Agent", "curl/8.5.0"),
+                ("Accept", "*/*"),
+                ("X-Forwarded-For", "10.0.0.1, 10.0.0.2"),
+            ]
+        );
+        assert_eq!(req.header("host"), Some("www.example.com"));
+        assert_eq!(req.header("USER-AGENT"), Some("curl/8.5.0"));
+        assert_eq!(req.header("Content-Length"), None);
+
+        // a request without headers
+        let req = HttpRequest::parse(Cursor::new(b"OPTIONS * HTTP/1.0\r\n\r\n")).unwrap();
+        assert_eq!(req.target(), "*");
+        assert_eq!(req.headers().count(), 0);
+        assert_eq!(req.head_len(), 22);
+    }
+
+    #[test]
+    fn parse_split_request() {
+        for len in 0..HEAD_LEN {
+            let res = HttpRequest::<Cursor>::check_head(&GET_REQUEST_BYTES[..len]);
+            assert_eq!(res.err(), Some(StreamError::NeedMoreData(None)));
+        }
+
+        // the request is split across two tcp segments in the middle of the
+        // host header
+        let (first, second) = GET_REQUEST_BYTES[..HEAD_LEN].split_at(45);
+        assert_eq!(
+            HttpRequest::<Cursor>::check_head(first).err(),
+            Some(StreamError::NeedMoreData(None))
+        );
+        let mut reassembled = first.to_vec();
+        reassembled.extend_from_slice(second);
+        let req = HttpRequest::parse(Cursor::new(&reassembled[..])).unwrap();
+        assert_eq!(req.header("Host"), Some("www.example.com"));
+        assert_eq!(req.head_len(), HEAD_LEN);
+    }
+
+    #[test]
+    fn malformed_requests() {
+        let malformed = |bytes: &[u8]| HttpRequest::<Cursor>::check_head(bytes).err();
+
+        // not http, rejected from the first bytes
+        assert_eq!(malformed(&[0x16, 0x03, 0x01]), Some(StreamError::Malformed));
+        assert_eq!(malformed(b"GE(T"), Some(StreamError::Malformed));
+        // a malformed request line
+        assert_eq!(malformed(b"GET /\r\n\r\n"), Some(StreamError::Malformed));
+        assert_eq!(
+            malformed(b"GET  / HTTP/1.1\r\n\r\n"),
+            Some(StreamError::Malformed)
+        );
+        assert_eq!(
+            malformed(b"GET / HTTP/2.0\r\n\r\n"),
+            Some(StreamError::Malformed)
+        );
+        assert_eq!(
+            malformed(b"GET / HTTP/1.1 \r\n\r\n"),
+            Some(StreamError::Malformed)
+        );
+        // a bare lf
+        assert_eq!(
+            malformed(b"GET / HTTP/1.1\n\n"),
+            Some(StreamError::Malformed)
+        );
+        assert_eq!(
+            malformed(b"GET / HTTP/1.1\r\nHost: a\n\r\n"),
+            Some(StreamError::Malformed)
+        );
+        // a whitespace before the colon, a folded line and a missing colon
+        for line in [&b"Host : a"[..], b" folded", b"Host"] {
+            let mut bytes = b"GET / HTTP/1.1\r\nAccept: */*\r\n".to_vec();
+            bytes.extend_from_slice(line);
+            bytes.extend_from_slice(b"\r\n\r\n");
+            assert_eq!(malformed(&bytes[..]), Some(StreamError::Malformed));
+            // a malformed line is rejected before the end of the headers
+            assert_eq!(
+                malformed(&bytes[..bytes.len() - 2]),
+                Some(StreamError::Malformed)
+            );
+        }
+        // a control character or an invalid utf-8 in a value
+        let res = malformed(b"GET / HTTP/1.1\r\nHost: a\x00b\r\n\r\n");
+        assert_eq!(res, Some(StreamError::Malformed));
+        let res = malformed(b"GET / HTTP/1.1\r\nHost: a\xffb\r\n\r\n");
+        assert_eq!(res, Some(StreamError::Malformed));
+        let req = HttpRequest::parse(Cursor::new(
+            "GET / HTTP/1.1\r\nX-Name: ü\r\n\r\n".as_bytes(),
+        ))
+        .unwrap();
+        assert_eq!(req.header("x-name"), Some("ü"));
+
+        // a header line with the maximum length
+        let mut bytes = b"GET / HTTP/1.1\r\nCookie: ".to_vec();
+        bytes.resize(16 + HTTP_MAX_LINE_LEN, b'a');
+        assert_eq!(malformed(&bytes[..]), Some(StreamError::NeedMoreData(None)));
+        bytes.push(b'\r');
+        assert_eq!(malformed(&bytes[..]), Some(StreamError::NeedMoreData(None)));
+        let mut complete = bytes.clone();
+        complete.extend_from_slice(b"\n\r\n");
+        assert!(HttpRequest::parse(Cursor::new(&complete[..])).is_ok());
+        // one byte longer, even before the line is complete
+        bytes.insert(30, b'a');
+        assert_eq!(malformed(&bytes[..]), Some(StreamError::TooLong));
+        bytes.extend_from_slice(b"\n\r\n");
+        assert_eq!(malformed(&bytes[..]), Some(StreamError::TooLong));
+
+        // too many headers
+        let mut bytes = b"GET / HTTP/1.1\r\n".to_vec();
+        for _ in 0..HTTP_MAX_HEADERS {
+            bytes.extend_from_slice(b"X-A: b\r\n");
+        }
+        let mut complete = bytes.clone();
+        complete.extend_from_slice(b"\r\n");
+        assert!(HttpRequest::parse(Cursor::new(&complete[..])).is_ok());
+        bytes.extend_from_slice(b"X-A: b\r\n\r\n");
+        assert_eq!(malformed(&bytes[..]), Some(StreamError::TooLong));
+    }
+}
@@ -17,4 +17,6 @@ mod header;
 pub use header::{Icmpv4Header, ICMPV4_HEADER_LEN, ICMPV4_HEADER_TEMPLATE};
 
 mod packet;
-pub use self::packet::Icmpv4Packet;
\ No newline at end of file
+pub use self::packet::Icmpv4Packet;
+mod pmtu;
+pub use pmtu::{build_frag_needed, ICMP_CODE_FRAG_NEEDED};
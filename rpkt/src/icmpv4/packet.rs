@@ -2,7 +2,7 @@ use bytes::Buf;
 
 use crate::checksum_utils;
 use crate::ipv4::Ipv4Addr;
-use crate::{PktBuf, PktMut};
+use crate::{Cursor, Header, ParseError, PktBuf, PktMut};
 
 use super::header::{Icmpv4Header, ICMPV4_HEADER_LEN};
 use super::IcmpType;
@@ -53,6 +53,23 @@ impl<T: Buf> Icmpv4Packet<T> {
     }
 }
 
+impl<'a> Header<'a> for Icmpv4Packet<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        Icmpv4Packet::parse(Cursor::new(buf)).map_err(|_| ParseError::Icmpv4)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        ICMPV4_HEADER_LEN
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        &self.buf.chunk_shared_lifetime()[ICMPV4_HEADER_LEN..]
+    }
+}
+
 impl<T: PktBuf> Icmpv4Packet<T> {
     #[inline]
     pub fn calc_checksum(&mut self) -> u16 {
@@ -0,0 +1,88 @@
+use crate::checksum_utils;
+
+use super::header::{Icmpv4Header, ICMPV4_HEADER_LEN};
+use super::IcmpType;
+
+/// The code of a destination unreachable message for a packet that needs to be
+/// fragmented but has the DF flag set.
+pub const ICMP_CODE_FRAG_NEEDED: u8 = 4;
+
+// The number of the original payload bytes embedded after the ipv4 header.
+const EMBEDDED_PAYLOAD_LEN: usize = 8;
+
+/// Build an icmpv4 "Destination Unreachable / Fragmentation Needed" message in
+/// `buf` for the dropped ipv4 packet `original_packet`, advertising the
+/// next-hop mtu `mtu` as described in RFC 1191.
+///
+/// The message embeds the ipv4 header and the first 8 bytes of the payload of
+/// the original packet, i.e. the first 28 bytes if the header has no options.
+/// The icmp checksum is computed, while the enclosing ipv4 header is left to
+/// the caller.
+///
+/// Return the length of the message written to `buf`.
+///
+/// # Panics
+/// This function panics if `buf` can not hold the message.
+pub fn build_frag_needed(original_packet: &[u8], mtu: u16, buf: &mut [u8]) -> usize {
+    let header_len = original_packet
+        .first()
+        .map_or(0, |byte| usize::from(byte & 0x0f) * 4);
+    let embedded_len = std::cmp::min(header_len + EMBEDDED_PAYLOAD_LEN, original_packet.len());
+    let msg_len = ICMPV4_HEADER_LEN + embedded_len;
+    assert!(buf.len() >= msg_len);
+
+    let mut header = Icmpv4Header::new_unchecked(&mut buf[..ICMPV4_HEADER_LEN]);
+    header.set_icmp_type(IcmpType::DST_UNREACHABLE);
+    header.set_code(ICMP_CODE_FRAG_NEEDED);
+    header.set_checksum(0);
+    // the first half of the rest of the header is unused
+    header.set_ident(0);
+    header.set_next_hop_mtu(mtu);
+    buf[ICMPV4_HEADER_LEN..msg_len].copy_from_slice(&original_packet[..embedded_len]);
+
+    let cksum = !checksum_utils::from_slice(&buf[..msg_len]);
+    Icmpv4Header::new_unchecked(&mut buf[..ICMPV4_HEADER_LEN]).set_checksum(cksum);
+
+    msg_len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::icmpv4::Icmpv4Packet;
+    use crate::ipv4::*;
+    use crate::{Buf, Cursor, CursorMut};
+
+    #[test]
+    fn build_frag_needed_for_oversized_packet() {
+        let mut bytes = [0xab; 1500];
+        let mut buf = CursorMut::new(&mut bytes[..]);
+        buf.advance(IPV4_HEADER_LEN);
+        let mut ipheader = IPV4_HEADER_TEMPLATE;
+        ipheader.set_protocol(IpProtocol::UDP);
+        ipheader.set_dont_frag(true);
+        ipheader.set_packet_len(1500);
+        ipheader.set_source_ip(Ipv4Addr([10, 0, 0, 1]));
+        ipheader.set_dest_ip(Ipv4Addr([10, 0, 1, 1]));
+        let mut ippkt = Ipv4Packet::prepend_header(buf, &ipheader);
+        ippkt.adjust_checksum();
+
+        let mut msg = [0; 64];
+        let msg_len = build_frag_needed(&bytes[..], 1400, &mut msg[..]);
+        assert_eq!(msg_len, ICMPV4_HEADER_LEN + 28);
+
+        let mut icmppkt = Icmpv4Packet::parse(Cursor::new(&msg[..msg_len])).unwrap();
+        assert_eq!(icmppkt.icmp_type(), IcmpType::DST_UNREACHABLE);
+        assert_eq!(icmppkt.code(), ICMP_CODE_FRAG_NEEDED);
+        assert_eq!(icmppkt.ident(), 0);
+        assert_eq!(icmppkt.next_hop_mtu(), 1400);
+        assert!(icmppkt.verify_checksum());
+
+        let embedded = icmppkt.data();
+        assert_eq!(embedded.chunk(), &bytes[..28]);
+        let embedded_header = Ipv4Header::new(embedded.chunk()).unwrap();
+        assert_eq!(embedded_header.packet_len(), 1500);
+        assert_eq!(embedded_header.dest_ip(), Ipv4Addr([10, 0, 1, 1]));
+        assert!(embedded_header.dont_frag());
+    }
+}
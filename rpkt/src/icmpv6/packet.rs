@@ -1,4 +1,7 @@
-use crate::PktMut;
+use crate::checksum_utils;
+use crate::ipv4::IpProtocol;
+use crate::ipv6::Ipv6Addr;
+use crate::{Cursor, Header, ParseError, PktMut};
 use byteorder::{ByteOrder, NetworkEndian};
 use bytes::Buf;
 
@@ -90,6 +93,29 @@ impl<T: Buf> Icmpv6Packet<T> {
         }
     }
 
+    /// Compute the checksum over the ipv6 pseudo header and the message.
+    #[inline]
+    pub fn calc_checksum(&self, src_addr: Ipv6Addr, dst_addr: Ipv6Addr) -> u16 {
+        let data = self.buf.chunk();
+
+        // upper-layer length and next header of the pseudo header
+        let mut len_nxt = [0; 8];
+        NetworkEndian::write_u32(&mut len_nxt[0..4], data.len() as u32);
+        len_nxt[7] = IpProtocol::ICMPV6.into();
+
+        checksum_utils::combine(&[
+            checksum_utils::from_slice(src_addr.as_bytes()),
+            checksum_utils::from_slice(dst_addr.as_bytes()),
+            checksum_utils::from_slice(&len_nxt[..]),
+            checksum_utils::from_slice(data),
+        ])
+    }
+
+    #[inline]
+    pub fn verify_checksum(&self, src_addr: Ipv6Addr, dst_addr: Ipv6Addr) -> bool {
+        self.calc_checksum(src_addr, dst_addr) == !0
+    }
+
     #[inline]
     pub fn msg(&self) -> Icmpv6Msg {
         match self.msg_type() {
@@ -166,6 +192,25 @@ impl<T: Buf> Icmpv6Packet<T> {
     }
 }
 
+impl<'a> Header<'a> for Icmpv6Packet<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        Icmpv6Packet::parse(Cursor::new(buf)).map_err(|_| ParseError::Icmpv6)
+    }
+
+    /// Return the length of the type, the code, the checksum and the 4-byte
+    /// message header that all the messages carry.
+    #[inline]
+    fn header_len(&self) -> usize {
+        8
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        &self.buf.chunk_shared_lifetime()[8..]
+    }
+}
+
 impl<T: PktMut> Icmpv6Packet<T> {
     #[inline]
     pub fn set_msg_type(&mut self, value: u8) {
@@ -188,6 +233,13 @@ impl<T: PktMut> Icmpv6Packet<T> {
         &mut self.buf.chunk_mut()[4..]
     }
 
+    #[inline]
+    pub fn adjust_checksum(&mut self, src_addr: Ipv6Addr, dst_addr: Ipv6Addr) {
+        self.set_checksum(0);
+        let cksum = !self.calc_checksum(src_addr, dst_addr);
+        self.set_checksum(cksum);
+    }
+
     #[inline]
     pub fn msg_mut(&mut self) -> Icmpv6MsgMut {
         match self.msg_type() {
@@ -384,3 +436,116 @@ impl<T: PktMut> Icmpv6Packet<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ether::*;
+    use crate::icmpv6::ndp::{NdpOption, NdpOptionIter};
+    use crate::ipv6::*;
+    use crate::{Cursor, CursorMut};
+
+    // A neighbor solicitation from fe80::1 for fe80::2, carrying the source
+    // link-layer address option.
+    static NS_FRAME_BYTES: [u8; 86] = [
+        0x33, 0x33, 0xff, 0x00, 0x00, 0x02, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x86, 0xdd, 0x60,
+        0x00, 0x00, 0x00, 0x00, 0x20, 0x3a, 0xff, 0xfe, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0xff, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x01, 0xff, 0x00, 0x00, 0x02, 0x87, 0x00, 0x15, 0xff, 0x00, 0x00,
+        0x00, 0x00, 0xfe, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x02, 0x01, 0x01, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55,
+    ];
+
+    // The solicited neighbor advertisement replied by fe80::2, carrying the
+    // target link-layer address option.
+    static NA_FRAME_BYTES: [u8; 86] = [
+        0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x86, 0xdd, 0x60,
+        0x00, 0x00, 0x00, 0x00, 0x20, 0x3a, 0xff, 0xfe, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0xfe, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x88, 0x00, 0x80, 0x4f, 0x60, 0x00,
+        0x00, 0x00, 0xfe, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x02, 0x02, 0x01, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb,
+    ];
+
+    const TARGET_ADDR: [u8; 16] = [0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02];
+
+    #[test]
+    fn parse_neighbor_solicit() {
+        let ethpkt = EtherPacket::parse(Cursor::new(&NS_FRAME_BYTES[..])).unwrap();
+        assert_eq!(ethpkt.ethertype(), EtherType::IPV6);
+        let ippkt = Ipv6Packet::parse(ethpkt.payload()).unwrap();
+        assert_eq!(ippkt.next_header(), IpProtocol::ICMPV6);
+        let (src_ip, dst_ip) = (ippkt.source_ip(), ippkt.dest_ip());
+
+        let icmppkt = Icmpv6Packet::parse(ippkt.payload()).unwrap();
+        assert_eq!(icmppkt.msg_type(), Icmpv6MsgType::NDP_NEIGHBOR_SOLICIT);
+        assert_eq!(icmppkt.checksum(), 0x15ff);
+        assert!(icmppkt.verify_checksum(src_ip, dst_ip));
+        // the pseudo header covers the addresses
+        assert!(!icmppkt.verify_checksum(src_ip, Ipv6Addr::from_bytes(&TARGET_ADDR[..])));
+
+        let msg = match icmppkt.msg() {
+            Icmpv6Msg::NdpNeighborSolicit(msg) => msg,
+            _ => panic!(),
+        };
+        assert_eq!(msg.target_addr(), &TARGET_ADDR[..]);
+
+        let mut options = NdpOptionIter::from_option_bytes(msg.option_bytes());
+        match options.next() {
+            Some(NdpOption::SrcLinkAddr(opt)) => {
+                assert_eq!(opt.link_addr(), &[0x00, 0x11, 0x22, 0x33, 0x44, 0x55][..]);
+            }
+            _ => panic!(),
+        }
+        assert!(options.next().is_none());
+        assert!(NdpOptionIter::check_option_bytes(msg.option_bytes()));
+    }
+
+    #[test]
+    fn parse_neighbor_adv() {
+        let ethpkt = EtherPacket::parse(Cursor::new(&NA_FRAME_BYTES[..])).unwrap();
+        let ippkt = Ipv6Packet::parse(ethpkt.payload()).unwrap();
+        let (src_ip, dst_ip) = (ippkt.source_ip(), ippkt.dest_ip());
+
+        let icmppkt = Icmpv6Packet::parse(ippkt.payload()).unwrap();
+        assert_eq!(icmppkt.msg_type(), Icmpv6MsgType::NDP_NEIGHBOR_ADV);
+        assert!(icmppkt.verify_checksum(src_ip, dst_ip));
+
+        let msg = match icmppkt.msg() {
+            Icmpv6Msg::NdpNeighborAdv(msg) => msg,
+            _ => panic!(),
+        };
+        assert!(!msg.r_flag());
+        assert!(msg.s_flag());
+        assert!(msg.o_flag());
+        assert_eq!(msg.target_addr(), &TARGET_ADDR[..]);
+
+        let mut options = NdpOptionIter::from_option_bytes(msg.option_bytes());
+        match options.next() {
+            Some(NdpOption::DstLinkAddr(opt)) => {
+                assert_eq!(opt.link_addr(), &[0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb][..]);
+            }
+            _ => panic!(),
+        }
+        assert!(options.next().is_none());
+    }
+
+    #[test]
+    fn adjust_neighbor_adv_checksum() {
+        let mut bytes = NA_FRAME_BYTES;
+        let src_ip = Ipv6Addr::from_bytes(&bytes[22..38]);
+        let dst_ip = Ipv6Addr::from_bytes(&bytes[38..54]);
+
+        let mut buf = CursorMut::new(&mut bytes[..]);
+        buf.advance(ETHER_HEADER_LEN + IPV6_HEADER_LEN);
+        let mut icmppkt = Icmpv6Packet::parse(buf).unwrap();
+        match icmppkt.msg_mut() {
+            Icmpv6MsgMut::NdpNeighborAdv(mut msg) => msg.set_o_flag(false),
+            _ => panic!(),
+        }
+        assert!(!icmppkt.verify_checksum(src_ip, dst_ip));
+
+        icmppkt.adjust_checksum(src_ip, dst_ip);
+        assert!(icmppkt.verify_checksum(src_ip, dst_ip));
+    }
+}
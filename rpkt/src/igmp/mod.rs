@@ -0,0 +1,19 @@
+enum_sim! {
+    /// See https://www.iana.org/assignments/igmp-type-numbers/igmp-type-numbers.xhtml
+    pub struct IgmpMsgType (u8) {
+        MEMBERSHIP_QUERY = 0x11,
+        V1_MEMBERSHIP_REPORT = 0x12,
+        V2_MEMBERSHIP_REPORT = 0x16,
+        V2_LEAVE_GROUP = 0x17,
+        V3_MEMBERSHIP_REPORT = 0x22,
+    }
+}
+
+/// The length of the igmpv1/v2 messages.
+pub const IGMP_MSG_LEN: usize = 8;
+
+/// The minimum length of the igmpv3 membership query.
+pub const IGMPV3_QUERY_LEN: usize = 12;
+
+mod packet;
+pub use packet::{IgmpGroupRecord, IgmpGroupRecordIter, IgmpPacket};
@@ -0,0 +1,419 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use bytes::Buf;
+
+use crate::checksum_utils;
+use crate::ipv4::Ipv4Addr;
+use crate::{Cursor, Header, ParseError, PktMut};
+
+use super::{IgmpMsgType, IGMPV3_QUERY_LEN, IGMP_MSG_LEN};
+
+/// An igmp message, the payload of an ipv4 packet with protocol `IGMP`.
+///
+/// The igmpv1/v2 messages and the igmpv3 membership queries share the first 8
+/// bytes, while the igmpv3 membership report carries a list of group records
+/// after the record count.
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct IgmpPacket<T> {
+    buf: T,
+}
+
+impl<T: Buf> IgmpPacket<T> {
+    #[inline]
+    pub fn parse_unchecked(buf: T) -> Self {
+        Self { buf }
+    }
+
+    #[inline]
+    pub fn buf(&self) -> &T {
+        &self.buf
+    }
+
+    #[inline]
+    pub fn release(self) -> T {
+        self.buf
+    }
+
+    /// Parse an igmp message from `buf`.
+    ///
+    /// For an igmpv3 membership query, the source list must fit in `buf`. The
+    /// group records of an igmpv3 report are checked by `check_group_records`.
+    #[inline]
+    pub fn parse(buf: T) -> Result<IgmpPacket<T>, T> {
+        let chunk_len = buf.chunk().len();
+        if chunk_len < IGMP_MSG_LEN || chunk_len != buf.remaining() {
+            return Err(buf);
+        }
+
+        let packet = IgmpPacket { buf };
+        // a query longer than 8 bytes is an igmpv3 query
+        if packet.msg_type() == IgmpMsgType::MEMBERSHIP_QUERY
+            && chunk_len > IGMP_MSG_LEN
+            && (chunk_len < IGMPV3_QUERY_LEN
+                || chunk_len < IGMPV3_QUERY_LEN + usize::from(packet.num_sources()) * 4)
+        {
+            return Err(packet.release());
+        }
+
+        Ok(packet)
+    }
+
+    #[inline]
+    pub fn msg_type(&self) -> IgmpMsgType {
+        self.buf.chunk()[0].into()
+    }
+
+    /// Return the max response time of a query in 1/10 seconds. For an
+    /// igmpv3 query, this is the raw max response code.
+    #[inline]
+    pub fn max_resp_time(&self) -> u8 {
+        self.buf.chunk()[1]
+    }
+
+    #[inline]
+    pub fn checksum(&self) -> u16 {
+        let data = &self.buf.chunk()[2..4];
+        NetworkEndian::read_u16(data)
+    }
+
+    /// Return the group address, which is not present in an igmpv3 report.
+    #[inline]
+    pub fn group_addr(&self) -> Ipv4Addr {
+        Ipv4Addr::from_bytes(&self.buf.chunk()[4..8])
+    }
+
+    #[inline]
+    pub fn calc_checksum(&self) -> u16 {
+        checksum_utils::from_slice(self.buf.chunk())
+    }
+
+    #[inline]
+    pub fn verify_checksum(&self) -> bool {
+        self.calc_checksum() == !0
+    }
+
+    /// Whether the message is an igmpv3 membership query.
+    ///
+    /// The igmpv3 queries are distinguished from the v1/v2 queries by length,
+    /// the query fields below are only valid if this returns true.
+    #[inline]
+    pub fn is_v3_query(&self) -> bool {
+        self.msg_type() == IgmpMsgType::MEMBERSHIP_QUERY
+            && self.buf.chunk().len() >= IGMPV3_QUERY_LEN
+    }
+
+    #[inline]
+    pub fn s_flag(&self) -> bool {
+        (self.buf.chunk()[8] >> 3) & 1 == 1
+    }
+
+    #[inline]
+    pub fn qrv(&self) -> u8 {
+        self.buf.chunk()[8] & 0x07
+    }
+
+    #[inline]
+    pub fn qqic(&self) -> u8 {
+        self.buf.chunk()[9]
+    }
+
+    #[inline]
+    pub fn num_sources(&self) -> u16 {
+        let data = &self.buf.chunk()[10..12];
+        NetworkEndian::read_u16(data)
+    }
+
+    /// Return the `idx`-th source address of an igmpv3 query.
+    ///
+    /// # Panics
+    /// This function panics if `idx` is not smaller than `num_sources`.
+    #[inline]
+    pub fn source_addr(&self, idx: usize) -> Ipv4Addr {
+        assert!(idx < usize::from(self.num_sources()));
+        let offset = IGMPV3_QUERY_LEN + idx * 4;
+        Ipv4Addr::from_bytes(&self.buf.chunk()[offset..offset + 4])
+    }
+
+    /// Whether the message is an igmpv3 membership report, which carries the
+    /// group records.
+    #[inline]
+    pub fn is_v3_report(&self) -> bool {
+        self.msg_type() == IgmpMsgType::V3_MEMBERSHIP_REPORT
+    }
+
+    /// Return the number of group records of an igmpv3 report, which is 0 for
+    /// the other messages.
+    #[inline]
+    pub fn num_group_records(&self) -> u16 {
+        if !self.is_v3_report() {
+            return 0;
+        }
+        let data = &self.buf.chunk()[6..8];
+        NetworkEndian::read_u16(data)
+    }
+
+    /// Return an iterator over the group records of an igmpv3 report, which is
+    /// empty for the other messages.
+    #[inline]
+    pub fn group_records(&self) -> IgmpGroupRecordIter<'_> {
+        IgmpGroupRecordIter {
+            buf: &self.buf.chunk()[IGMP_MSG_LEN..],
+            remaining: self.num_group_records(),
+            valid: true,
+        }
+    }
+
+    /// Check that all the group records of an igmpv3 report fit in the
+    /// message. The other messages carry no group records and always pass.
+    #[inline]
+    pub fn check_group_records(&self) -> bool {
+        let mut reader = self.group_records();
+        while reader.next().is_some() {}
+        reader.valid
+    }
+}
+
+impl<'a> Header<'a> for IgmpPacket<Cursor<'a>> {
+    /// Parse an igmp message, the group records of an igmpv3 report are
+    /// checked as well.
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        let igmppkt = IgmpPacket::parse(Cursor::new(buf)).map_err(|_| ParseError::Igmp)?;
+        if igmppkt.is_v3_report() && !igmppkt.check_group_records() {
+            return Err(ParseError::Igmp);
+        }
+        Ok(igmppkt)
+    }
+
+    /// Return the length of the message up to the source list of an igmpv3
+    /// query, or up to the group records of an igmpv3 report.
+    #[inline]
+    fn header_len(&self) -> usize {
+        if self.is_v3_query() {
+            IGMPV3_QUERY_LEN + usize::from(self.num_sources()) * 4
+        } else {
+            IGMP_MSG_LEN
+        }
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        &self.buf.chunk_shared_lifetime()[Header::header_len(self)..]
+    }
+}
+
+impl<T: PktMut> IgmpPacket<T> {
+    #[inline]
+    pub fn set_msg_type(&mut self, value: IgmpMsgType) {
+        self.buf.chunk_mut()[0] = value.into();
+    }
+
+    #[inline]
+    pub fn set_max_resp_time(&mut self, value: u8) {
+        self.buf.chunk_mut()[1] = value;
+    }
+
+    #[inline]
+    pub fn set_checksum(&mut self, value: u16) {
+        let data = &mut self.buf.chunk_mut()[2..4];
+        NetworkEndian::write_u16(data, value);
+    }
+
+    #[inline]
+    pub fn set_group_addr(&mut self, value: Ipv4Addr) {
+        self.buf.chunk_mut()[4..8].copy_from_slice(value.as_bytes());
+    }
+
+    #[inline]
+    pub fn adjust_checksum(&mut self) {
+        self.set_checksum(0);
+        let cksum = !self.calc_checksum();
+        self.set_checksum(cksum);
+    }
+}
+
+/// A group record of an igmpv3 membership report.
+pub struct IgmpGroupRecord<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> IgmpGroupRecord<'a> {
+    #[inline]
+    pub fn record_type(&self) -> u8 {
+        self.buf[0]
+    }
+
+    /// Return the length of the auxiliary data in 32-bit words.
+    #[inline]
+    pub fn aux_data_len(&self) -> u8 {
+        self.buf[1]
+    }
+
+    #[inline]
+    pub fn num_sources(&self) -> u16 {
+        NetworkEndian::read_u16(&self.buf[2..4])
+    }
+
+    #[inline]
+    pub fn mcast_addr(&self) -> Ipv4Addr {
+        Ipv4Addr::from_bytes(&self.buf[4..8])
+    }
+
+    /// Return the `idx`-th source address.
+    ///
+    /// # Panics
+    /// This function panics if `idx` is not smaller than `num_sources`.
+    #[inline]
+    pub fn source_addr(&self, idx: usize) -> Ipv4Addr {
+        assert!(idx < usize::from(self.num_sources()));
+        let offset = 8 + idx * 4;
+        Ipv4Addr::from_bytes(&self.buf[offset..offset + 4])
+    }
+
+    #[inline]
+    pub fn aux_data(&self) -> &'a [u8] {
+        let offset = 8 + usize::from(self.num_sources()) * 4;
+        &self.buf[offset..]
+    }
+}
+
+pub struct IgmpGroupRecordIter<'a> {
+    buf: &'a [u8],
+    remaining: u16,
+    valid: bool,
+}
+
+impl<'a> Iterator for IgmpGroupRecordIter<'a> {
+    type Item = IgmpGroupRecord<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.valid || self.remaining == 0 {
+            return None;
+        }
+
+        if self.buf.len() < 8 {
+            self.valid = false;
+            return None;
+        }
+
+        let num_sources = usize::from(NetworkEndian::read_u16(&self.buf[2..4]));
+        let record_len = 8 + num_sources * 4 + usize::from(self.buf[1]) * 4;
+        if self.buf.len() < record_len {
+            self.valid = false;
+            return None;
+        }
+
+        let record = IgmpGroupRecord {
+            buf: &self.buf[..record_len],
+        };
+        self.buf = &self.buf[record_len..];
+        self.remaining -= 1;
+        Some(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cursor, CursorMut};
+
+    // An igmpv2 membership report for 224.0.0.251.
+    static V2_REPORT_BYTES: [u8; 8] = [0x16, 0x00, 0x09, 0x04, 0xe0, 0x00, 0x00, 0xfb];
+
+    // A general igmpv3 query with a max response code of 100.
+    static V3_QUERY_BYTES: [u8; 12] = [
+        0x11, 0x64, 0xec, 0x1e, 0x00, 0x00, 0x00, 0x00, 0x02, 0x7d, 0x00, 0x00,
+    ];
+
+    // An igmpv3 report with two group records, the second one carrying two
+    // sources and one word of auxiliary data.
+    static V3_REPORT_BYTES: [u8; 36] = [
+        0x22, 0x00, 0x46, 0x52, 0x00, 0x00, 0x00, 0x02, 0x04, 0x00, 0x00, 0x00, 0xef, 0x01, 0x01,
+        0x01, 0x01, 0x01, 0x00, 0x02, 0xef, 0x02, 0x02, 0x02, 0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00,
+        0x00, 0x02, 0xde, 0xad, 0xbe, 0xef,
+    ];
+
+    #[test]
+    fn parse_v2_report() {
+        let pkt = IgmpPacket::parse(Cursor::new(&V2_REPORT_BYTES[..])).unwrap();
+        assert_eq!(pkt.msg_type(), IgmpMsgType::V2_MEMBERSHIP_REPORT);
+        assert_eq!(pkt.max_resp_time(), 0);
+        assert_eq!(pkt.checksum(), 0x0904);
+        assert_eq!(pkt.group_addr(), Ipv4Addr([224, 0, 0, 251]));
+        assert!(pkt.verify_checksum());
+        assert!(!pkt.is_v3_query());
+        // the group address is not read as a record count
+        assert!(!pkt.is_v3_report());
+        assert_eq!(pkt.num_group_records(), 0);
+        assert_eq!(pkt.group_records().count(), 0);
+        assert!(pkt.check_group_records());
+
+        assert!(IgmpPacket::parse(Cursor::new(&V2_REPORT_BYTES[..7])).is_err());
+    }
+
+    #[test]
+    fn parse_v3_query() {
+        let pkt = IgmpPacket::parse(Cursor::new(&V3_QUERY_BYTES[..])).unwrap();
+        assert_eq!(pkt.msg_type(), IgmpMsgType::MEMBERSHIP_QUERY);
+        assert!(pkt.verify_checksum());
+        assert!(pkt.is_v3_query());
+        assert_eq!(pkt.max_resp_time(), 100);
+        assert_eq!(pkt.group_addr(), Ipv4Addr([0, 0, 0, 0]));
+        assert!(!pkt.s_flag());
+        assert_eq!(pkt.qrv(), 2);
+        assert_eq!(pkt.qqic(), 125);
+        assert_eq!(pkt.num_sources(), 0);
+        assert!(!pkt.is_v3_report());
+        assert_eq!(pkt.num_group_records(), 0);
+
+        // the source list must fit in the message
+        let mut bytes = V3_QUERY_BYTES;
+        bytes[11] = 1;
+        assert!(IgmpPacket::parse(Cursor::new(&bytes[..])).is_err());
+        assert!(IgmpPacket::parse(Cursor::new(&bytes[..10])).is_err());
+    }
+
+    #[test]
+    fn parse_v3_report() {
+        let pkt = IgmpPacket::parse(Cursor::new(&V3_REPORT_BYTES[..])).unwrap();
+        assert_eq!(pkt.msg_type(), IgmpMsgType::V3_MEMBERSHIP_REPORT);
+        assert!(pkt.verify_checksum());
+        assert_eq!(pkt.num_group_records(), 2);
+        assert!(pkt.check_group_records());
+
+        let records: Vec<_> = pkt.group_records().collect();
+        assert_eq!(records.len(), 2);
+
+        assert_eq!(records[0].record_type(), 4);
+        assert_eq!(records[0].num_sources(), 0);
+        assert_eq!(records[0].mcast_addr(), Ipv4Addr([239, 1, 1, 1]));
+        assert!(records[0].aux_data().is_empty());
+
+        assert_eq!(records[1].record_type(), 1);
+        assert_eq!(records[1].aux_data_len(), 1);
+        assert_eq!(records[1].num_sources(), 2);
+        assert_eq!(records[1].mcast_addr(), Ipv4Addr([239, 2, 2, 2]));
+        assert_eq!(records[1].source_addr(0), Ipv4Addr([10, 0, 0, 1]));
+        assert_eq!(records[1].source_addr(1), Ipv4Addr([10, 0, 0, 2]));
+        assert_eq!(records[1].aux_data(), &[0xde, 0xad, 0xbe, 0xef][..]);
+
+        // the second record is truncated
+        let pkt = IgmpPacket::parse(Cursor::new(&V3_REPORT_BYTES[..32])).unwrap();
+        assert_eq!(pkt.group_records().count(), 1);
+        assert!(!pkt.check_group_records());
+    }
+
+    #[test]
+    fn build_v2_leave() {
+        let mut bytes = V2_REPORT_BYTES;
+        let mut pkt = IgmpPacket::parse(CursorMut::new(&mut bytes[..])).unwrap();
+        pkt.set_msg_type(IgmpMsgType::V2_LEAVE_GROUP);
+        pkt.set_group_addr(Ipv4Addr([239, 1, 1, 1]));
+        assert!(!pkt.verify_checksum());
+
+        pkt.adjust_checksum();
+        assert!(pkt.verify_checksum());
+        assert_eq!(pkt.msg_type(), IgmpMsgType::V2_LEAVE_GROUP);
+        assert_eq!(pkt.group_addr(), Ipv4Addr([239, 1, 1, 1]));
+    }
+}
@@ -4,6 +4,7 @@ use bytes::Buf;
 use crate::ipv4::IpProtocol;
 use crate::PktMut;
 use crate::{Cursor, CursorMut};
+use crate::{Header, ParseError};
 
 header_field_val_accessors! {
     (next_header, next_header_mut, 0),
@@ -201,6 +202,23 @@ impl<'a> IpsecAuthHdrPacket<Cursor<'a>> {
     }
 }
 
+impl<'a> Header<'a> for IpsecAuthHdrPacket<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        IpsecAuthHdrPacket::parse(Cursor::new(buf)).map_err(|_| ParseError::Ah)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        self.header_len()
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        self.cursor_payload().chunk_shared_lifetime()
+    }
+}
+
 impl<'a> IpsecAuthHdrPacket<CursorMut<'a>> {
     #[inline]
     pub fn split(self) -> (IpsecAuthHeader<&'a mut [u8]>, &'a [u8], CursorMut<'a>) {
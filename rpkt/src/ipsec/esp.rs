@@ -3,6 +3,7 @@ use bytes::Buf;
 
 use crate::PktMut;
 use crate::{Cursor, CursorMut};
+use crate::{Header, ParseError};
 
 header_field_range_accessors! {
     (spi, spi_mut, 0..4),
@@ -132,6 +133,23 @@ impl<'a> IpsecEspPacket<Cursor<'a>> {
     }
 }
 
+impl<'a> Header<'a> for IpsecEspPacket<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        IpsecEspPacket::parse(Cursor::new(buf)).map_err(|_| ParseError::Esp)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        IPSEC_ESP_HEADER_LEN
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        self.cursor_payload().chunk_shared_lifetime()
+    }
+}
+
 impl<'a> IpsecEspPacket<CursorMut<'a>> {
     #[inline]
     pub fn split(self) -> (Ipv6EspHeader<&'a mut [u8]>, CursorMut<'a>) {
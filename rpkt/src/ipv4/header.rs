@@ -1,6 +1,9 @@
+use std::fmt;
+
 use byteorder::{ByteOrder, NetworkEndian};
 
 use super::{IpProtocol, Ipv4Addr};
+use crate::checksum_utils;
 
 header_field_val_accessors! {
     (ver_ihl, ver_ihl_mut, 0),
@@ -252,3 +255,41 @@ impl<T: AsMut<[u8]>> Ipv4Header<T> {
         data.copy_from_slice(value.as_bytes())
     }
 }
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Ipv4Header<T> {
+    /// Decrement the ttl of a forwarded packet and return the new ttl.
+    ///
+    /// The checksum is updated incrementally following RFC 1624, so a valid
+    /// checksum stays valid. Return `TtlExpired` and leave the header untouched
+    /// if the ttl is 0 or 1.
+    #[inline]
+    pub fn decrement_ttl(&mut self) -> Result<u8, TtlExpired> {
+        let ttl = self.time_to_live();
+        if ttl <= 1 {
+            return Err(TtlExpired);
+        }
+
+        // The ttl is the high byte of the 16-bit checksum word shared with
+        // the protocol.
+        let old_word = NetworkEndian::read_u16(&self.buf.as_ref()[8..10]);
+        self.set_time_to_live(ttl - 1);
+        let cksum =
+            checksum_utils::incremental_update(self.checksum(), old_word, old_word - 0x0100);
+        self.set_checksum(cksum);
+
+        Ok(ttl - 1)
+    }
+}
+
+/// The error of `Ipv4Header::decrement_ttl`, the packet must be dropped, and an
+/// icmp time exceeded message may be sent back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TtlExpired;
+
+impl fmt::Display for TtlExpired {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ipv4 ttl expired in transit")
+    }
+}
+
+impl std::error::Error for TtlExpired {}
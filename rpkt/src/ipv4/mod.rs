@@ -87,26 +87,61 @@ enum_sim! {
     /// See https://www.iana.org/assignments/protocol-numbers/protocol-numbers.xhtml
     pub struct IpProtocol (u8) {
         ICMP = 1,
+        IGMP = 2,
         TCP = 6,
         UDP =  17,
         /// The IPv6 Hop-by-hop extention number
         HOPOPT = 0,
+        /// An ipv4 packet encapsulated in ip.
+        IPIP = 4,
+        /// An ipv6 packet encapsulated in ip.
+        IPV6 = 41,
         IPV6_ROUTE = 43,
         IPV6_FRAG = 44,
+        GRE = 47,
         ESP = 50,
         AH = 51,
+        ICMPV6 = 58,
         IPV6_NO_NXT = 59,
         IPV6_OPTS = 60,
+        /// An l2tpv3 message directly over ip.
+        L2TP = 115,
+        SCTP = 132,
+    }
+}
+
+impl IpProtocol {
+    /// Return the name of a known protocol number, e.g. for logging.
+    pub fn name(&self) -> Option<&'static str> {
+        let name = match *self {
+            IpProtocol::ICMP => "ICMP",
+            IpProtocol::IGMP => "IGMP",
+            IpProtocol::TCP => "TCP",
+            IpProtocol::UDP => "UDP",
+            IpProtocol::HOPOPT => "HOPOPT",
+            IpProtocol::IPIP => "IPIP",
+            IpProtocol::IPV6 => "IPv6",
+            IpProtocol::IPV6_ROUTE => "IPv6-Route",
+            IpProtocol::IPV6_FRAG => "IPv6-Frag",
+            IpProtocol::GRE => "GRE",
+            IpProtocol::ESP => "ESP",
+            IpProtocol::AH => "AH",
+            IpProtocol::ICMPV6 => "ICMPv6",
+            IpProtocol::IPV6_NO_NXT => "IPv6-NoNxt",
+            IpProtocol::IPV6_OPTS => "IPv6-Opts",
+            IpProtocol::L2TP => "L2TP",
+            IpProtocol::SCTP => "SCTP",
+            _ => return None,
+        };
+        Some(name)
     }
 }
 
 impl fmt::Display for IpProtocol {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
-            IpProtocol::ICMP => write!(f, "ICMP"),
-            IpProtocol::TCP => write!(f, "TCP"),
-            IpProtocol::UDP => write!(f, "UDP"),
-            _ => write!(f, "0x{:02x}", u8::from(*self)),
+        match self.name() {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "0x{:02x}", u8::from(*self)),
         }
     }
 }
@@ -161,7 +196,9 @@ impl Ipv4PseudoHeader {
 }
 
 mod header;
-pub use header::{Ipv4Header, IPV4_HEADER_LEN, IPV4_HEADER_LEN_MAX, IPV4_HEADER_TEMPLATE};
+pub use header::{
+    Ipv4Header, TtlExpired, IPV4_HEADER_LEN, IPV4_HEADER_LEN_MAX, IPV4_HEADER_TEMPLATE,
+};
 
 mod packet;
 pub use self::packet::Ipv4Packet;
@@ -309,7 +309,9 @@ impl<'a> Iterator for Ipv4OptionIter<'a> {
                     }
                     _ => {
                         let opt_len = usize::from(self.buf[1]);
-                        if self.buf.len() < opt_len {
+                        // the length covers the type and length bytes, a shorter
+                        // option would never advance the iterator
+                        if opt_len < 2 || self.buf.len() < opt_len {
                             self.valid = false;
                             None
                         } else {
@@ -439,7 +441,9 @@ impl<'a> Iterator for Ipv4OptionIterMut<'a> {
                     }
                     _ => {
                         let opt_len = usize::from(self.buf[1]);
-                        if self.buf.len() < opt_len {
+                        // the length covers the type and length bytes, a shorter
+                        // option would never advance the iterator
+                        if opt_len < 2 || self.buf.len() < opt_len {
                             self.valid = false;
                             None
                         } else {
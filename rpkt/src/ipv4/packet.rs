@@ -2,9 +2,10 @@ use bytes::Buf;
 
 use crate::checksum_utils;
 use crate::{Cursor, CursorMut};
+use crate::{Header, ParseError};
 use crate::{PktBuf, PktMut};
 
-use super::header::{Ipv4Header, IPV4_HEADER_LEN};
+use super::header::{Ipv4Header, TtlExpired, IPV4_HEADER_LEN};
 use super::{IpProtocol, Ipv4Addr};
 
 packet_base! {
@@ -115,6 +116,12 @@ impl<T: PktMut> Ipv4Packet<T> {
         self.set_checksum(checksum)
     }
 
+    /// Decrement the ttl and update the checksum, see `Ipv4Header::decrement_ttl`.
+    #[inline]
+    pub fn decrement_ttl(&mut self) -> Result<u8, TtlExpired> {
+        Ipv4Header::new_unchecked(&mut self.buf.chunk_mut()[..IPV4_HEADER_LEN]).decrement_ttl()
+    }
+
     #[inline]
     pub fn prepend_header<HT: AsRef<[u8]>>(mut buf: T, header: &Ipv4Header<HT>) -> Ipv4Packet<T> {
         let header_len: usize = header.header_len().into();
@@ -152,6 +159,23 @@ impl<'a> Ipv4Packet<Cursor<'a>> {
     }
 }
 
+impl<'a> Header<'a> for Ipv4Packet<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        Ipv4Packet::parse(Cursor::new(buf)).map_err(|_| ParseError::Ipv4)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        usize::from(self.header_len())
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        self.cursor_payload().chunk_shared_lifetime()
+    }
+}
+
 impl<'a> Ipv4Packet<CursorMut<'a>> {
     #[inline]
     pub fn split(self) -> (Ipv4Header<&'a mut [u8]>, &'a [u8], CursorMut<'a>) {
@@ -223,6 +247,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn decrement_ttl() {
+        let mut bytes = FRAME_BYTES;
+        let mut ippkt = Ipv4Packet::parse(CursorMut::new(&mut bytes[ETHER_HEADER_LEN..])).unwrap();
+        // the checksum of the captured packet was left to the nic
+        ippkt.adjust_checksum();
+        assert!(ippkt.verify_checksum());
+
+        for ttl in (1..128).rev() {
+            assert_eq!(ippkt.decrement_ttl(), Ok(ttl));
+            assert_eq!(ippkt.time_to_live(), ttl);
+            assert!(ippkt.verify_checksum());
+        }
+
+        // the header is untouched once the ttl expires
+        let checksum = ippkt.checksum();
+        assert_eq!(ippkt.decrement_ttl(), Err(TtlExpired));
+        assert_eq!(ippkt.time_to_live(), 1);
+        assert_eq!(ippkt.checksum(), checksum);
+        ippkt.set_time_to_live(0);
+        assert_eq!(ippkt.decrement_ttl(), Err(TtlExpired));
+        assert_eq!(ippkt.time_to_live(), 0);
+
+        // the incremental update matches a full recomputation
+        let mut header = IPV4_HEADER_TEMPLATE;
+        header.set_time_to_live(64);
+        header.set_source_ip(Ipv4Addr([192, 168, 29, 58]));
+        let mut buf = [0; IPV4_HEADER_LEN];
+        buf.copy_from_slice(header.as_bytes());
+        Ipv4Packet::parse(CursorMut::new(&mut buf[..]))
+            .unwrap()
+            .adjust_checksum();
+        let mut header = Ipv4Header::new(&mut buf[..]).unwrap();
+        assert_eq!(header.decrement_ttl(), Ok(63));
+        let checksum = header.checksum();
+        let mut ippkt = Ipv4Packet::parse(CursorMut::new(&mut buf[..])).unwrap();
+        ippkt.adjust_checksum();
+        assert_eq!(ippkt.checksum(), checksum);
+    }
+
     #[test]
     fn packet_build() {
         let mut bytes = [0xff; 110];
@@ -0,0 +1,287 @@
+use crate::ipv4::IpProtocol;
+use crate::PktMut;
+
+use super::frag::{FragHeader, FRAG_HEADER_LEN};
+use super::option::Ipv6OptionWriter;
+use super::routing::RoutingMsgType;
+
+// The maximum length of an extension header with an 8-bit `hdr_ext_len` field.
+const MAX_EXT_HEADER_LEN: usize = 2048;
+
+enum ExtHeader<'a> {
+    Options(IpProtocol, &'a [(u8, &'a [u8])]),
+    Routing(RoutingMsgType, u8, &'a [u8]),
+    Frag(u16, bool, u32),
+}
+
+impl<'a> ExtHeader<'a> {
+    fn protocol(&self) -> IpProtocol {
+        match self {
+            ExtHeader::Options(protocol, _) => *protocol,
+            ExtHeader::Routing(..) => IpProtocol::IPV6_ROUTE,
+            ExtHeader::Frag(..) => IpProtocol::IPV6_FRAG,
+        }
+    }
+
+    fn header_len(&self) -> usize {
+        match self {
+            ExtHeader::Options(_, options) => {
+                let len: usize = options.iter().map(|(_, data)| data.len() + 2).sum();
+                round_up(len + 2)
+            }
+            ExtHeader::Routing(_, _, data) => round_up(data.len() + 4),
+            ExtHeader::Frag(..) => FRAG_HEADER_LEN,
+        }
+    }
+
+    // Write the header to `buf`, which has exactly `self.header_len()` bytes.
+    fn write(&self, buf: &mut [u8], next_header: IpProtocol) {
+        match self {
+            ExtHeader::Options(_, options) => {
+                buf[0] = next_header.into();
+                buf[1] = ((buf.len() - 8) / 8) as u8;
+
+                let mut writer = Ipv6OptionWriter::from_option_bytes_mut(&mut buf[2..]);
+                for (opt_type, data) in options.iter() {
+                    writer
+                        .generic(*opt_type, data.len() + 2)
+                        .option_data_mut()
+                        .copy_from_slice(data);
+                }
+                match writer.remaining_bytes() {
+                    0 => {}
+                    1 => writer.pad0(),
+                    n => writer.padn(n),
+                }
+            }
+            ExtHeader::Routing(msg_type, segments_left, data) => {
+                buf[0] = next_header.into();
+                buf[1] = ((buf.len() - 8) / 8) as u8;
+                buf[2] = (*msg_type).into();
+                buf[3] = *segments_left;
+                buf[4..4 + data.len()].copy_from_slice(data);
+                buf[4 + data.len()..].fill(0);
+            }
+            ExtHeader::Frag(frag_off, m_flag, ident) => {
+                let mut header = FragHeader::new_unchecked(buf);
+                header.set_next_header(next_header);
+                header.adjust_reserved();
+                header.set_frag_off(*frag_off);
+                header.set_m_flag(*m_flag);
+                header.set_ident(*ident);
+            }
+        }
+    }
+}
+
+fn round_up(len: usize) -> usize {
+    let len = (len + 7) / 8 * 8;
+    assert!(len <= MAX_EXT_HEADER_LEN);
+    len
+}
+
+/// A builder that composes a chain of IPv6 extension headers.
+///
+/// The extension headers are prepended in the order that they are added, the
+/// `next_header` fields are chained and the `hdr_ext_len` fields are computed
+/// automatically. The Hop-by-Hop and Destination Options headers are padded to
+/// a multiple of 8 octets with Pad1 or PadN options.
+#[derive(Default)]
+pub struct Ipv6ExtHeaderBuilder<'a> {
+    headers: Vec<ExtHeader<'a>>,
+}
+
+impl<'a> Ipv6ExtHeaderBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a Hop-by-Hop Options header with options in the form of
+    /// (option type, option data).
+    ///
+    /// # Panic:
+    /// This function panics if the Hop-by-Hop header is not the first one, or if
+    /// the option data is longer than 255 bytes.
+    pub fn hop_by_hop(&mut self, options: &'a [(u8, &'a [u8])]) -> &mut Self {
+        assert!(self.headers.is_empty());
+        self.add_options(IpProtocol::HOPOPT, options)
+    }
+
+    /// Add a Destination Options header with options in the form of
+    /// (option type, option data).
+    pub fn dest_opts(&mut self, options: &'a [(u8, &'a [u8])]) -> &mut Self {
+        self.add_options(IpProtocol::IPV6_OPTS, options)
+    }
+
+    /// Add a Routing header with the type-specific `data`, which is padded with
+    /// zeros to a multiple of 8 octets.
+    pub fn routing(
+        &mut self,
+        msg_type: RoutingMsgType,
+        segments_left: u8,
+        data: &'a [u8],
+    ) -> &mut Self {
+        self.push(ExtHeader::Routing(msg_type, segments_left, data))
+    }
+
+    /// Add a Fragment header.
+    pub fn fragment(&mut self, frag_off: u16, m_flag: bool, ident: u32) -> &mut Self {
+        self.push(ExtHeader::Frag(frag_off, m_flag, ident))
+    }
+
+    /// Return the total length of the extension headers.
+    pub fn header_len(&self) -> usize {
+        self.headers.iter().map(|header| header.header_len()).sum()
+    }
+
+    /// Prepend the extension headers before the payload in `buf`, where
+    /// `next_header` is the protocol of the payload.
+    ///
+    /// Return the buffer and the protocol of the first extension header, which
+    /// should be set to the `next_header` field of the IPv6 header.
+    ///
+    /// # Panic:
+    /// This function panics if `buf` does not have enough headroom.
+    pub fn prepend_headers<T: PktMut>(
+        &self,
+        mut buf: T,
+        next_header: IpProtocol,
+    ) -> (T, IpProtocol) {
+        assert!(buf.chunk_headroom() >= self.header_len());
+
+        let mut next_header = next_header;
+        for header in self.headers.iter().rev() {
+            let header_len = header.header_len();
+            buf.move_back(header_len);
+            header.write(&mut buf.chunk_mut()[..header_len], next_header);
+            next_header = header.protocol();
+        }
+
+        (buf, next_header)
+    }
+
+    fn add_options(&mut self, protocol: IpProtocol, options: &'a [(u8, &'a [u8])]) -> &mut Self {
+        assert!(options.iter().all(|(_, data)| data.len() <= 255));
+        self.push(ExtHeader::Options(protocol, options))
+    }
+
+    fn push(&mut self, header: ExtHeader<'a>) -> &mut Self {
+        // validate the header length
+        header.header_len();
+        self.headers.push(header);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipv6::extentions::*;
+    use crate::ipv6::*;
+    use crate::{Buf, Cursor, CursorMut};
+
+    const ROUTER_ALERT: u8 = 0x05;
+
+    fn build(builder: &Ipv6ExtHeaderBuilder, payload: &[u8], bytes: &mut [u8]) -> usize {
+        let total_len = IPV6_HEADER_LEN + builder.header_len() + payload.len();
+        bytes[total_len - payload.len()..total_len].copy_from_slice(payload);
+
+        let mut pktbuf = CursorMut::new(&mut bytes[..total_len]);
+        pktbuf.advance(total_len - payload.len());
+
+        let (pktbuf, next_header) = builder.prepend_headers(pktbuf, IpProtocol::UDP);
+        let mut header = Ipv6Header::new_unchecked([0; IPV6_HEADER_LEN]);
+        header.adjust_version();
+        header.set_next_header(next_header);
+        header.set_hop_limit(64);
+        Ipv6Packet::prepend_header(pktbuf, &header);
+
+        total_len
+    }
+
+    #[test]
+    fn hbh_and_frag_round_trip() {
+        let options: [(u8, &[u8]); 2] = [(ROUTER_ALERT, &[0, 0]), (0x3e, &[1, 2, 3, 4, 5])];
+        let mut builder = Ipv6ExtHeaderBuilder::new();
+        builder
+            .hop_by_hop(&options[..])
+            .fragment(0x123, true, 0xdeadbeef);
+        // 2 + 4 + 7 bytes of options are padded to 16 bytes
+        assert_eq!(builder.header_len(), 16 + FRAG_HEADER_LEN);
+
+        let mut bytes = [0xff; 128];
+        let payload = [0xaa; 20];
+        let total_len = build(&builder, &payload[..], &mut bytes[..]);
+
+        let ippkt = Ipv6Packet::parse(Cursor::new(&bytes[..total_len])).unwrap();
+        assert_eq!(ippkt.next_header(), IpProtocol::HOPOPT);
+        assert_eq!(usize::from(ippkt.payload_len()), 16 + FRAG_HEADER_LEN + 20);
+
+        let hbhpkt = HbhOptPacket::parse(ippkt.payload()).unwrap();
+        assert_eq!(hbhpkt.next_header(), IpProtocol::IPV6_FRAG);
+        assert_eq!(hbhpkt.header_len(), 16);
+        assert!(Ipv6OptionIter::check_option_bytes(hbhpkt.option_bytes()));
+
+        let mut iter = Ipv6OptionIter::from_option_bytes(hbhpkt.option_bytes());
+        match iter.next() {
+            Some(Ipv6Option::Generic(opt)) => {
+                assert_eq!(opt.option_type(), ROUTER_ALERT);
+                assert_eq!(opt.option_data(), &[0, 0]);
+            }
+            _ => panic!(),
+        }
+        match iter.next() {
+            Some(Ipv6Option::Generic(opt)) => {
+                assert_eq!(opt.option_type(), 0x3e);
+                assert_eq!(opt.option_data(), &[1, 2, 3, 4, 5]);
+            }
+            _ => panic!(),
+        }
+        assert!(matches!(iter.next(), Some(Ipv6Option::PadN)));
+        assert!(iter.next().is_none());
+
+        let fragpkt = FragPacket::parse(hbhpkt.payload()).unwrap();
+        assert_eq!(fragpkt.next_header(), IpProtocol::UDP);
+        assert_eq!(fragpkt.frag_off(), 0x123);
+        assert!(fragpkt.m_flag());
+        assert_eq!(fragpkt.ident(), 0xdeadbeef);
+        assert_eq!(fragpkt.payload().chunk(), &payload[..]);
+    }
+
+    #[test]
+    fn routing_and_dest_opts_round_trip() {
+        let addr = Ipv6Addr::LOOPBACK;
+        let mut routing_data = [0; 20];
+        routing_data[4..].copy_from_slice(addr.as_bytes());
+        let options: [(u8, &[u8]); 1] = [(0x1e, &[7])];
+
+        let mut builder = Ipv6ExtHeaderBuilder::new();
+        builder
+            .routing(RoutingMsgType::TYPE0, 1, &routing_data[..])
+            .dest_opts(&options[..]);
+        assert_eq!(builder.header_len(), 24 + 8);
+
+        let mut bytes = [0xff; 128];
+        let total_len = build(&builder, &[0xbb; 8], &mut bytes[..]);
+
+        let ippkt = Ipv6Packet::parse(Cursor::new(&bytes[..total_len])).unwrap();
+        assert_eq!(ippkt.next_header(), IpProtocol::IPV6_ROUTE);
+
+        let routingpkt = RoutingPacket::parse(ippkt.payload()).unwrap();
+        assert_eq!(routingpkt.next_header(), IpProtocol::IPV6_OPTS);
+        assert_eq!(routingpkt.header_len(), 24);
+        assert_eq!(routingpkt.msg_type(), RoutingMsgType::TYPE0);
+        assert_eq!(routingpkt.segments_left(), 1);
+        match routingpkt.msg() {
+            Some(RoutingMsg::Type0(msg)) => assert_eq!(msg.addr(0), addr.as_bytes()),
+            _ => panic!(),
+        }
+
+        let dstpkt = DstOptPacket::parse(routingpkt.payload()).unwrap();
+        assert_eq!(dstpkt.next_header(), IpProtocol::UDP);
+        assert_eq!(dstpkt.header_len(), 8);
+        // 3 bytes of option is followed by a 3-byte PadN
+        assert_eq!(dstpkt.option_bytes(), &[0x1e, 1, 7, 1, 1, 0]);
+        assert_eq!(dstpkt.payload().chunk(), &[0xbb; 8]);
+    }
+}
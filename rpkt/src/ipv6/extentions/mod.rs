@@ -18,3 +18,6 @@ pub use routing::{
     RoutingMsg, RoutingMsgCompressed, RoutingMsgGeneric, RoutingMsgMut, RoutingMsgType,
     RoutingPacket,
 };
+
+mod builder;
+pub use builder::Ipv6ExtHeaderBuilder;
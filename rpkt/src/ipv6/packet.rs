@@ -2,6 +2,7 @@ use bytes::Buf;
 
 use crate::ipv4::IpProtocol;
 use crate::{Cursor, CursorMut};
+use crate::{Header, ParseError};
 use crate::{PktBuf, PktMut};
 
 use super::header::{Ipv6Header, IPV6_HEADER_LEN};
@@ -99,6 +100,23 @@ impl<'a> Ipv6Packet<Cursor<'a>> {
     }
 }
 
+impl<'a> Header<'a> for Ipv6Packet<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        Ipv6Packet::parse(Cursor::new(buf)).map_err(|_| ParseError::Ipv6)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        IPV6_HEADER_LEN
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        self.cursor_payload().chunk_shared_lifetime()
+    }
+}
+
 impl<'a> Ipv6Packet<CursorMut<'a>> {
     #[inline]
     pub fn split(self) -> (Ipv6Header<&'a mut [u8]>, CursorMut<'a>) {
@@ -0,0 +1,9 @@
+/// The udp port of l2tp, which is shared by l2tpv2 and l2tpv3 over udp.
+pub const L2TP_UDP_PORT: u16 = 1701;
+
+/// The length of the control message header of l2tpv3, from the flags to the
+/// nr field.
+pub const L2TPV3_CONTROL_HEADER_LEN: usize = 12;
+
+mod packet;
+pub use packet::L2tpPacket;
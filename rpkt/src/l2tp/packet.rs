@@ -0,0 +1,559 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use bytes::Buf;
+
+use crate::{Cursor, Header, ParseError, PktBuf};
+
+use super::L2TPV3_CONTROL_HEADER_LEN;
+
+// The flags in the first byte of the header, the other bits are reserved.
+const FLAG_TYPE: u8 = 0x80;
+const FLAG_LENGTH: u8 = 0x40;
+const FLAG_SEQUENCE: u8 = 0x08;
+const FLAG_OFFSET: u8 = 0x02;
+const FLAG_PRIORITY: u8 = 0x01;
+
+// The address and control fields that may lead a ppp frame.
+const PPP_ADDRESS_CONTROL: [u8; 2] = [0xff, 0x03];
+
+/// An l2tp message, either l2tpv2 (RFC 2661) over udp, or l2tpv3 (RFC 3931)
+/// over udp or directly over ip.
+///
+/// The optional fields of l2tpv2 are present according to the flags: the
+/// length with the l flag, ns and nr with the s flag, and the offset size and
+/// the offset padding with the o flag. The data messages of l2tpv2 carry a ppp
+/// frame.
+///
+/// The data messages of l2tpv3 start with a 32-bit session id and a cookie,
+/// which is followed by the frame of the pseudowire, e.g. an ethernet frame.
+/// Neither the encapsulation nor the cookie length, which is configured per
+/// session, is signaled in the header, so both are kept next to the buffer.
+/// The control messages of both versions carry a list of avps.
+#[derive(Debug)]
+pub struct L2tpPacket<T> {
+    buf: T,
+    over_ip: bool,
+    cookie_len: usize,
+}
+
+impl<T: Buf> L2tpPacket<T> {
+    /// Wrap the l2tp message over udp without a cookie in `buf`.
+    #[inline]
+    pub fn parse_unchecked(buf: T) -> Self {
+        Self {
+            buf,
+            over_ip: false,
+            cookie_len: 0,
+        }
+    }
+
+    #[inline]
+    pub fn buf(&self) -> &T {
+        &self.buf
+    }
+
+    #[inline]
+    pub fn release(self) -> T {
+        self.buf
+    }
+
+    /// Parse the l2tp message over udp at the front of `buf`, the l2tpv3 data
+    /// messages carry no cookie.
+    #[inline]
+    pub fn parse(buf: T) -> Result<L2tpPacket<T>, T> {
+        Self::parse_udp(buf, 0)
+    }
+
+    /// Parse the l2tp message at the front of the udp payload `buf`, whose
+    /// version is read from the version field.
+    ///
+    /// `cookie_len` is the cookie length of the l2tpv3 data messages, which is
+    /// 0, 4 or 8 bytes. The parse fails if a field is truncated, if the message
+    /// is shorter than its length field, or if the flags are invalid, e.g. an
+    /// l2tpv2 control message without the length or the sequence numbers.
+    #[inline]
+    pub fn parse_udp(buf: T, cookie_len: usize) -> Result<L2tpPacket<T>, T> {
+        Self::parse_encap(buf, false, cookie_len)
+    }
+
+    /// Parse the l2tpv3 message at the front of `buf`, the payload of an ip
+    /// packet of `IpProtocol::L2TP`.
+    ///
+    /// A message that starts with a zero session id is a control message,
+    /// whose control header follows the session id. See `parse_udp` for
+    /// `cookie_len`.
+    #[inline]
+    pub fn parse_ip(buf: T, cookie_len: usize) -> Result<L2tpPacket<T>, T> {
+        Self::parse_encap(buf, true, cookie_len)
+    }
+
+    #[inline]
+    fn parse_encap(buf: T, over_ip: bool, cookie_len: usize) -> Result<L2tpPacket<T>, T> {
+        if check_message(buf.chunk(), over_ip, cookie_len).is_none() {
+            return Err(buf);
+        }
+        Ok(L2tpPacket {
+            buf,
+            over_ip,
+            cookie_len,
+        })
+    }
+
+    /// Return the version, which is 2 or 3.
+    #[inline]
+    pub fn version(&self) -> u8 {
+        match self.kind() {
+            Kind::V2 => 2,
+            _ => 3,
+        }
+    }
+
+    /// Return whether the message is a control message, whose payload is a
+    /// list of avps.
+    #[inline]
+    pub fn is_control(&self) -> bool {
+        match self.kind() {
+            Kind::V2 => self.flags() & FLAG_TYPE != 0,
+            Kind::V3Control(_) => true,
+            Kind::V3Data(_) => false,
+        }
+    }
+
+    /// Return the length field, which counts the whole message of l2tpv2, and
+    /// the control message of l2tpv3 from its control header.
+    #[inline]
+    pub fn length(&self) -> Option<u16> {
+        match self.kind() {
+            Kind::V2 if self.flags() & FLAG_LENGTH != 0 => Some(self.read_u16(2)),
+            Kind::V3Control(start) => Some(self.read_u16(start + 2)),
+            _ => None,
+        }
+    }
+
+    /// Return the tunnel id of l2tpv2, or the control connection id of an
+    /// l2tpv3 control message. The l2tpv3 data messages are only identified
+    /// by the session id.
+    #[inline]
+    pub fn tunnel_id(&self) -> Option<u32> {
+        match self.kind() {
+            Kind::V2 => Some(u32::from(self.read_u16(2 + self.v2_length_len()))),
+            Kind::V3Control(start) => Some(NetworkEndian::read_u32(
+                &self.buf.chunk()[start + 4..start + 8],
+            )),
+            Kind::V3Data(_) => None,
+        }
+    }
+
+    /// Return the session id, which is 16 bits long in l2tpv2, and 0 for an
+    /// l2tpv3 control message.
+    #[inline]
+    pub fn session_id(&self) -> u32 {
+        match self.kind() {
+            Kind::V2 => u32::from(self.read_u16(4 + self.v2_length_len())),
+            Kind::V3Control(_) => 0,
+            Kind::V3Data(start) => NetworkEndian::read_u32(&self.buf.chunk()[start..start + 4]),
+        }
+    }
+
+    #[inline]
+    pub fn ns(&self) -> Option<u16> {
+        self.seq_offset().map(|offset| self.read_u16(offset))
+    }
+
+    #[inline]
+    pub fn nr(&self) -> Option<u16> {
+        self.seq_offset().map(|offset| self.read_u16(offset + 2))
+    }
+
+    /// Return the offset size of l2tpv2, i.e. the length of the padding that
+    /// precedes the payload.
+    #[inline]
+    pub fn offset_size(&self) -> Option<u16> {
+        match self.kind() {
+            Kind::V2 if self.flags() & FLAG_OFFSET != 0 => {
+                Some(self.read_u16(6 + self.v2_length_len() + self.v2_seq_len()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the cookie of an l2tpv3 data message.
+    #[inline]
+    pub fn cookie(&self) -> &[u8] {
+        match self.kind() {
+            Kind::V3Data(start) => &self.buf.chunk()[start + 4..start + 4 + self.cookie_len],
+            _ => &[],
+        }
+    }
+
+    /// Return the length of the header, including the optional fields, the
+    /// offset padding and the cookie.
+    #[inline]
+    pub fn header_len(&self) -> usize {
+        match self.kind() {
+            Kind::V2 => {
+                6 + self.v2_length_len()
+                    + self.v2_seq_len()
+                    + self.offset_size().map_or(0, |size| 2 + usize::from(size))
+            }
+            Kind::V3Control(start) => start + L2TPV3_CONTROL_HEADER_LEN,
+            Kind::V3Data(start) => start + 4 + self.cookie_len,
+        }
+    }
+
+    /// Return the length of the message, the bytes past the length field are
+    /// not part of it.
+    #[inline]
+    pub fn message_len(&self) -> usize {
+        match self.kind() {
+            Kind::V2 => self.length().map_or(self.buf.chunk().len(), usize::from),
+            Kind::V3Control(start) => start + usize::from(self.length().unwrap()),
+            Kind::V3Data(_) => self.buf.chunk().len(),
+        }
+    }
+
+    /// Return the ppp frame of an l2tpv2 data message without the address and
+    /// control fields, so that it starts with the protocol field of
+    /// `PppPacket`.
+    #[inline]
+    pub fn ppp_frame(&self) -> Option<&[u8]> {
+        if self.version() != 2 || self.is_control() {
+            return None;
+        }
+        let payload = &self.buf.chunk()[self.header_len()..self.message_len()];
+        Some(
+            payload
+                .strip_prefix(&PPP_ADDRESS_CONTROL[..])
+                .unwrap_or(payload),
+        )
+    }
+
+    /// Return the payload, excluding the bytes past the length field.
+    #[inline]
+    pub fn payload(self) -> T
+    where
+        T: PktBuf,
+    {
+        let trim_size = self.buf.remaining() - self.message_len();
+        let header_len = self.header_len();
+
+        let mut buf = self.release();
+        if trim_size > 0 {
+            buf.trim_off(trim_size);
+        }
+        buf.advance(header_len);
+
+        buf
+    }
+
+    // The message is checked by `parse`.
+    #[inline]
+    fn kind(&self) -> Kind {
+        message_kind(self.buf.chunk(), self.over_ip).unwrap()
+    }
+
+    #[inline]
+    fn flags(&self) -> u8 {
+        self.buf.chunk()[0]
+    }
+
+    #[inline]
+    fn read_u16(&self, offset: usize) -> u16 {
+        NetworkEndian::read_u16(&self.buf.chunk()[offset..offset + 2])
+    }
+
+    #[inline]
+    fn v2_length_len(&self) -> usize {
+        if self.flags() & FLAG_LENGTH != 0 {
+            2
+        } else {
+            0
+        }
+    }
+
+    #[inline]
+    fn v2_seq_len(&self) -> usize {
+        if self.flags() & FLAG_SEQUENCE != 0 {
+            4
+        } else {
+            0
+        }
+    }
+
+    #[inline]
+    fn seq_offset(&self) -> Option<usize> {
+        match self.kind() {
+            Kind::V2 if self.flags() & FLAG_SEQUENCE != 0 => Some(6 + self.v2_length_len()),
+            Kind::V3Control(start) => Some(start + 8),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> L2tpPacket<Cursor<'a>> {
+    #[inline]
+    pub fn cursor_payload(&self) -> &'a [u8] {
+        &self.buf.chunk_shared_lifetime()[self.header_len()..self.message_len()]
+    }
+}
+
+impl<'a> Header<'a> for L2tpPacket<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        L2tpPacket::parse(Cursor::new(buf)).map_err(|_| ParseError::L2tp)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        self.header_len()
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        self.cursor_payload()
+    }
+}
+
+// The layout of a message, with the offset of the l2tpv3 control header or
+// session id.
+#[derive(Clone, Copy)]
+enum Kind {
+    V2,
+    V3Control(usize),
+    V3Data(usize),
+}
+
+#[inline]
+fn message_kind(buf: &[u8], over_ip: bool) -> Option<Kind> {
+    if over_ip {
+        if NetworkEndian::read_u32(buf.get(0..4)?) == 0 {
+            Some(Kind::V3Control(4))
+        } else {
+            Some(Kind::V3Data(0))
+        }
+    } else {
+        let flags = *buf.first()?;
+        match *buf.get(1)? {
+            2 => Some(Kind::V2),
+            3 if flags & FLAG_TYPE != 0 => Some(Kind::V3Control(0)),
+            // the flags and the reserved field lead the session id
+            3 => Some(Kind::V3Data(4)),
+            _ => None,
+        }
+    }
+}
+
+// Check that the fields of the message at the front of `buf` are valid and fit
+// in `buf`.
+fn check_message(buf: &[u8], over_ip: bool, cookie_len: usize) -> Option<()> {
+    match message_kind(buf, over_ip)? {
+        Kind::V2 => check_v2(buf),
+        Kind::V3Control(start) => check_v3_control(buf, start),
+        Kind::V3Data(start) => {
+            // a zero session id is reserved
+            let session_id = NetworkEndian::read_u32(buf.get(start..start + 4)?);
+            if session_id == 0 || buf.len() < start + 4 + cookie_len {
+                return None;
+            }
+            Some(())
+        }
+    }
+}
+
+fn check_v2(buf: &[u8]) -> Option<()> {
+    let flags = buf[0];
+    let control = flags & FLAG_TYPE != 0;
+    let reserved = !(FLAG_TYPE | FLAG_LENGTH | FLAG_SEQUENCE | FLAG_OFFSET | FLAG_PRIORITY);
+    // the control messages always carry the length and the sequence
+    // numbers, and never the offset
+    if flags & reserved != 0
+        || (control
+            && flags & (FLAG_LENGTH | FLAG_SEQUENCE | FLAG_OFFSET) != FLAG_LENGTH | FLAG_SEQUENCE)
+    {
+        return None;
+    }
+
+    let mut cursor = &buf[2..];
+    let length = match flags & FLAG_LENGTH {
+        0 => None,
+        _ => Some(take_u16(&mut cursor)?),
+    };
+    // the tunnel id and the session id
+    take(&mut cursor, 4)?;
+    if flags & FLAG_SEQUENCE != 0 {
+        take(&mut cursor, 4)?;
+    }
+    // the offset padding must fit in the message
+    if flags & FLAG_OFFSET != 0 {
+        let offset_size = take_u16(&mut cursor)?;
+        take(&mut cursor, usize::from(offset_size))?;
+    }
+
+    let header_len = buf.len() - cursor.len();
+    check_length(buf, 0, header_len, length)
+}
+
+// The control header starts at `start` of `buf`, the length field counts the
+// bytes from the control header to the end of the message.
+fn check_v3_control(buf: &[u8], start: usize) -> Option<()> {
+    let header = buf.get(start..start + L2TPV3_CONTROL_HEADER_LEN)?;
+    if header[0] & (FLAG_TYPE | FLAG_LENGTH | FLAG_SEQUENCE)
+        != FLAG_TYPE | FLAG_LENGTH | FLAG_SEQUENCE
+        || header[1] & 0x0f != 3
+    {
+        return None;
+    }
+
+    let length = NetworkEndian::read_u16(&header[2..4]);
+    check_length(buf, start, start + L2TPV3_CONTROL_HEADER_LEN, Some(length))
+}
+
+// Check that the length field of a message that starts at `start` of `buf`
+// covers the header and fits in `buf`.
+fn check_length(buf: &[u8], start: usize, header_len: usize, length: Option<u16>) -> Option<()> {
+    if let Some(length) = length {
+        let end = start + usize::from(length);
+        if end < header_len || end > buf.len() {
+            return None;
+        }
+    }
+    Some(())
+}
+
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if buf.len() < len {
+        return None;
+    }
+    let (head, tail) = buf.split_at(len);
+    *buf = tail;
+    Some(head)
+}
+
+fn take_u16(buf: &mut &[u8]) -> Option<u16> {
+    take(buf, 2).map(NetworkEndian::read_u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pppoe::{PppPacket, PppProtocol};
+    use crate::{Buf, Cursor};
+
+    // An ipv4 pkt of a ppp session, followed by 4 bytes of payload.
+    static IPV4_PACKET: [u8; 24] = [
+        0x45, 0x00, 0x00, 0x18, 0x00, 0x01, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x64, 0x40, 0x00,
+        0x02, 0x08, 0x08, 0x08, 0x08, 0xde, 0xad, 0xbe, 0xef,
+    ];
+
+    #[test]
+    fn parse_l2tpv2_messages() {
+        // a data message with the length and a 2-byte offset padding
+        let mut bytes = vec![0x42, 0x02, 0x00, 0x00, 0x12, 0x34, 0x56, 0x78, 0x00, 0x02];
+        bytes.extend_from_slice(&[0xee; 2]);
+        bytes.extend_from_slice(&[0xff, 0x03, 0x00, 0x21]);
+        bytes.extend_from_slice(&IPV4_PACKET[..]);
+        let len = bytes.len() as u16;
+        bytes[2..4].copy_from_slice(&len.to_be_bytes());
+        // the trailing bytes past the length are not part of the message
+        bytes.extend_from_slice(&[0; 4]);
+
+        let pkt = L2tpPacket::parse_udp(Cursor::new(&bytes[..]), 0).unwrap();
+        assert_eq!(pkt.version(), 2);
+        assert!(!pkt.is_control());
+        assert_eq!(pkt.length(), Some(len));
+        assert_eq!(pkt.tunnel_id(), Some(0x1234));
+        assert_eq!(pkt.session_id(), 0x5678);
+        assert_eq!(pkt.ns(), None);
+        assert_eq!(pkt.offset_size(), Some(2));
+        assert_eq!(pkt.header_len(), 12);
+        assert_eq!(pkt.cursor_payload().len(), 4 + IPV4_PACKET.len());
+        let ppppkt = PppPacket::parse(Cursor::new(pkt.ppp_frame().unwrap())).unwrap();
+        assert_eq!(ppppkt.protocol(), PppProtocol::IPV4);
+        assert_eq!(ppppkt.payload().chunk(), &IPV4_PACKET[..]);
+        // the trailing bytes are trimmed off the payload
+        assert_eq!(pkt.payload().chunk(), &bytes[12..bytes.len() - 4]);
+
+        // a data message with the sequence numbers and without the address
+        // and control fields
+        let mut bytes = vec![0x08, 0x02, 0x00, 0x07, 0x00, 0x09, 0x00, 0x05, 0x00, 0x03];
+        bytes.extend_from_slice(&[0x00, 0x21]);
+        bytes.extend_from_slice(&IPV4_PACKET[..]);
+        let pkt = L2tpPacket::parse_udp(Cursor::new(&bytes[..]), 0).unwrap();
+        assert_eq!((pkt.tunnel_id(), pkt.session_id()), (Some(7), 9));
+        assert_eq!((pkt.ns(), pkt.nr()), (Some(5), Some(3)));
+        assert_eq!(pkt.header_len(), 10);
+        assert_eq!(pkt.ppp_frame().unwrap(), &bytes[10..]);
+
+        // a hello control message with a message type avp
+        let bytes = [
+            0xc8, 0x02, 0x00, 0x14, 0x00, 0x07, 0x00, 0x00, 0x00, 0x01, 0x00, 0x02, 0x80, 0x08,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x06,
+        ];
+        let pkt = L2tpPacket::parse_udp(Cursor::new(&bytes[..]), 0).unwrap();
+        assert!(pkt.is_control());
+        assert_eq!(pkt.session_id(), 0);
+        assert_eq!(pkt.cursor_payload(), &bytes[12..]);
+        assert_eq!(pkt.ppp_frame(), None);
+
+        // every truncation of the control message is rejected
+        for len in 0..bytes.len() {
+            assert!(L2tpPacket::parse_udp(Cursor::new(&bytes[..len]), 0).is_err());
+        }
+        // a control message must carry the length and the sequence numbers
+        let mut invalid = bytes;
+        invalid[0] = 0xc0;
+        assert!(L2tpPacket::parse_udp(Cursor::new(&invalid[..]), 0).is_err());
+        // the offset padding must fit in the message
+        let invalid = [0x02, 0x02, 0x00, 0x07, 0x00, 0x09, 0x00, 0x08, 0x00];
+        assert!(L2tpPacket::parse_udp(Cursor::new(&invalid[..]), 0).is_err());
+        // the reserved bits and the unknown versions are rejected
+        assert!(L2tpPacket::parse_udp(Cursor::new(&[0x20, 0x02, 0, 7, 0, 9]), 0).is_err());
+        assert!(L2tpPacket::parse_udp(Cursor::new(&[0x00, 0x01, 0, 7, 0, 9]), 0).is_err());
+    }
+
+    #[test]
+    fn parse_l2tpv3_messages() {
+        // an ethernet pseudowire data message over ip with a 4-byte cookie
+        let mut bytes = vec![0x00, 0x01, 0xe2, 0x40, 0xca, 0xfe, 0xba, 0xbe];
+        bytes.extend_from_slice(&[0x02, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        bytes.extend_from_slice(&[0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x08, 0x00]);
+        bytes.extend_from_slice(&IPV4_PACKET[..]);
+        let pkt = L2tpPacket::parse_ip(Cursor::new(&bytes[..]), 4).unwrap();
+        assert_eq!(pkt.version(), 3);
+        assert!(!pkt.is_control());
+        assert_eq!(pkt.tunnel_id(), None);
+        assert_eq!(pkt.session_id(), 123456);
+        assert_eq!(pkt.cookie(), &[0xca, 0xfe, 0xba, 0xbe]);
+        assert_eq!(pkt.header_len(), 8);
+        assert_eq!(pkt.cursor_payload(), &bytes[8..]);
+        assert_eq!(pkt.ppp_frame(), None);
+        assert!(L2tpPacket::parse_ip(Cursor::new(&bytes[..7]), 4).is_err());
+
+        // the same message over udp
+        let mut udp_bytes = vec![0x00, 0x03, 0x00, 0x00];
+        udp_bytes.extend_from_slice(&bytes[..]);
+        let pkt = L2tpPacket::parse_udp(Cursor::new(&udp_bytes[..]), 4).unwrap();
+        assert_eq!(pkt.session_id(), 123456);
+        assert_eq!(pkt.header_len(), 12);
+        assert_eq!(pkt.cursor_payload(), &bytes[8..]);
+        // a zero session id is reserved
+        udp_bytes[4..8].fill(0);
+        assert!(L2tpPacket::parse_udp(Cursor::new(&udp_bytes[..]), 4).is_err());
+
+        // a hello control message over ip, behind the zero session id
+        let bytes = [
+            0x00, 0x00, 0x00, 0x00, 0xc8, 0x03, 0x00, 0x14, 0x00, 0x00, 0x30, 0x39, 0x00, 0x01,
+            0x00, 0x02, 0x80, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x06,
+        ];
+        let pkt = L2tpPacket::parse_ip(Cursor::new(&bytes[..]), 4).unwrap();
+        assert!(pkt.is_control());
+        assert_eq!(pkt.tunnel_id(), Some(12345));
+        assert_eq!(pkt.session_id(), 0);
+        assert_eq!((pkt.ns(), pkt.nr()), (Some(1), Some(2)));
+        assert_eq!(pkt.header_len(), 4 + L2TPV3_CONTROL_HEADER_LEN);
+        assert_eq!(pkt.cursor_payload(), &bytes[16..]);
+        let pkt = L2tpPacket::parse_udp(Cursor::new(&bytes[4..]), 0).unwrap();
+        assert_eq!(pkt.cursor_payload(), &bytes[16..]);
+        // the length field exceeds the message
+        assert!(L2tpPacket::parse_ip(Cursor::new(&bytes[..23]), 4).is_err());
+    }
+}
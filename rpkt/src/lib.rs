@@ -1,8 +1,11 @@
 #[macro_use]
 mod macros;
 
+mod error;
+pub use error::{ParseError, StreamError};
+
 mod traits;
-pub use traits::{Buf, PktBuf, PktMut};
+pub use traits::{Buf, Header, PktBuf, PktMut};
 
 pub(crate) mod checksum_utils;
 
@@ -11,12 +14,38 @@ pub use cursors::{Cursor, CursorMut};
 
 pub mod cursors_old;
 
+pub mod anonymize;
 pub mod arp;
+pub mod crc;
+pub mod diameter;
+pub mod editor;
 pub mod ether;
+pub mod flow;
+pub mod geneve;
+pub mod http;
 pub mod icmpv4;
 pub mod icmpv6;
+pub mod igmp;
 pub mod ipsec;
 pub mod ipv4;
 pub mod ipv6;
+pub mod l2tp;
+#[cfg(any(test, feature = "testing"))]
+pub mod malformed;
+pub mod matcher;
+pub mod payload;
+pub mod pcap;
+pub mod pppoe;
+pub mod ptp;
+pub mod quic;
+pub mod radius;
+pub mod someip;
 pub mod tcp;
+pub mod tls;
+pub mod typed;
 pub mod udp;
+
+// `fuzzing` is set by cargo-fuzz, `unexpected_cfgs` is unknown before rust 1.80
+#[allow(unknown_lints, unexpected_cfgs)]
+pub mod untrusted;
+pub use untrusted::{parse_untrusted, parse_untrusted_with, ParseResult};
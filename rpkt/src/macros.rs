@@ -49,7 +49,7 @@ macro_rules! enum_sim {
             ),+ $(,)?
         }
     ) => {
-        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
         $(#[$enum_attr])*
         pub struct $tname($size_t);
 
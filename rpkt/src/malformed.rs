@@ -0,0 +1,304 @@
+//! A builder of malformed packets for the negative tests of the parsers and
+//! for the seeds of the fuzz targets, enabled by the `testing` feature.
+//!
+//! The builder starts from a valid ethernet frame. Each mutation breaks a single
+//! field and leaves the rest of the frame untouched, so a test can pin the
+//! rejection on that field.
+
+use byteorder::{ByteOrder, NetworkEndian};
+
+use crate::checksum_utils;
+use crate::ether::{EtherType, ETHER_HEADER_LEN};
+use crate::ipv4::{IpProtocol, IPV4_HEADER_LEN};
+use crate::udp::UDP_HEADER_LEN;
+
+/// An ethernet frame carrying the TCP SYN of an HTTP connection.
+pub const TCP_FRAME: [u8; 74] = [
+    0x00, 0x26, 0x62, 0x2f, 0x47, 0x87, 0x00, 0x1d, 0x60, 0xb3, 0x01, 0x84, 0x08, 0x00, 0x45, 0x00,
+    0x00, 0x3c, 0xcb, 0x5c, 0x40, 0x00, 0x40, 0x06, 0x28, 0xe3, 0xc0, 0xa8, 0x01, 0x8c, 0xae, 0x8f,
+    0xd5, 0xb8, 0xe1, 0x4e, 0x00, 0x50, 0x8e, 0x50, 0x19, 0x01, 0x00, 0x00, 0x00, 0x00, 0xa0, 0x02,
+    0xfa, 0xf0, 0xab, 0x21, 0x00, 0x00, 0x02, 0x04, 0x05, 0xb4, 0x04, 0x02, 0x08, 0x0a, 0x00, 0x21,
+    0xd2, 0x5f, 0x00, 0x00, 0x00, 0x00, 0x01, 0x03, 0x03, 0x07,
+];
+
+const DNS_PORT: u16 = 53;
+const DNS_HEADER_LEN: usize = 12;
+const VLAN_TAG_LEN: usize = 4;
+
+/// A builder of a malformed ethernet frame, see the module documentation.
+///
+/// The ipv4 header and the l4 header are located in the original frame, and
+/// their offsets follow the inserted vlan tags. A mutation of a header that
+/// the original frame does not carry panics.
+pub struct MalformedPacketBuilder {
+    bytes: Vec<u8>,
+    l3_offset: Option<usize>,
+    l4: Option<(IpProtocol, usize)>,
+}
+
+impl MalformedPacketBuilder {
+    /// Start from the ethernet frame in `frame`.
+    pub fn new(frame: &[u8]) -> Self {
+        let mut l3_offset = None;
+        let mut l4 = None;
+        if frame.len() >= ETHER_HEADER_LEN + IPV4_HEADER_LEN
+            && NetworkEndian::read_u16(&frame[12..ETHER_HEADER_LEN]) == u16::from(EtherType::IPV4)
+        {
+            l3_offset = Some(ETHER_HEADER_LEN);
+            let header_len = usize::from(frame[ETHER_HEADER_LEN] & 0x0f) * 4;
+            if header_len >= IPV4_HEADER_LEN && frame.len() > ETHER_HEADER_LEN + header_len {
+                let protocol = IpProtocol::from(frame[ETHER_HEADER_LEN + 9]);
+                l4 = Some((protocol, ETHER_HEADER_LEN + header_len));
+            }
+        }
+        Self {
+            bytes: frame.to_vec(),
+            l3_offset,
+            l4,
+        }
+    }
+
+    /// Start from `TCP_FRAME`.
+    pub fn tcp() -> Self {
+        Self::new(&TCP_FRAME[..])
+    }
+
+    /// Start from a frame carrying a dns query of the a record of
+    /// `example.com` over udp.
+    pub fn dns_query() -> Self {
+        let mut frame = TCP_FRAME[..ETHER_HEADER_LEN + IPV4_HEADER_LEN].to_vec();
+        frame[ETHER_HEADER_LEN + 9] = IpProtocol::UDP.into();
+        frame.extend_from_slice(&50000_u16.to_be_bytes());
+        frame.extend_from_slice(&DNS_PORT.to_be_bytes());
+        frame.extend_from_slice(&[0; 4]);
+        // a recursive query with a single question
+        frame.extend_from_slice(&[0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        frame.extend_from_slice(b"\x07example\x03com\x00");
+        frame.extend_from_slice(&[0, 1, 0, 1]);
+
+        let mut builder = Self::new(&frame[..]);
+        builder.adjust_udp_lengths();
+        builder
+    }
+
+    /// Truncate the frame to `len` bytes, e.g. in the middle of a header.
+    pub fn truncate(&mut self, len: usize) {
+        self.bytes.truncate(len);
+    }
+
+    /// Set an ipv4 header checksum that does not match the header.
+    pub fn set_bad_ipv4_checksum(&mut self) {
+        let l3_offset = self.l3_offset();
+        let header_len = std::cmp::max(
+            usize::from(self.bytes[l3_offset] & 0x0f) * 4,
+            IPV4_HEADER_LEN,
+        );
+        let header_end = std::cmp::min(l3_offset + header_len, self.bytes.len());
+        let header = &mut self.bytes[l3_offset..header_end];
+        header[10..12].fill(0);
+        let cksum = !checksum_utils::from_slice(header);
+        // 0 and 0xffff are the same checksum, which the xor never maps onto
+        NetworkEndian::write_u16(&mut header[10..12], cksum ^ 0x5555);
+    }
+
+    /// Set the ipv4 header length to `ihl` 4-byte words, e.g. less than 5 for
+    /// a header shorter than the minimum.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `ihl` does not fit in 4 bits.
+    pub fn set_ihl(&mut self, ihl: u8) {
+        assert!(ihl < 16);
+        let l3_offset = self.l3_offset();
+        self.bytes[l3_offset] = (self.bytes[l3_offset] & 0xf0) | ihl;
+    }
+
+    /// Set the tcp header length to `data_offset` 4-byte words, e.g. less than
+    /// 5 for a header shorter than the minimum.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `data_offset` does not fit in 4 bits, or if the
+    /// original frame is not a tcp packet.
+    pub fn set_tcp_data_offset(&mut self, data_offset: u8) {
+        assert!(data_offset < 16);
+        let l4_offset = self.l4_offset(IpProtocol::TCP);
+        let byte = &mut self.bytes[l4_offset + 12];
+        *byte = (data_offset << 4) | (*byte & 0x0f);
+    }
+
+    /// Insert `depth` 802.1q tags in front of the ethertype, e.g. more tags than
+    /// a parser is willing to skip.
+    pub fn overflow_vlan_stack(&mut self, depth: usize) {
+        let tags = (0..depth).flat_map(|idx| {
+            let vlan_id = (idx % 4095 + 1) as u16;
+            let [tag_hi, tag_lo] = u16::from(EtherType::VLAN).to_be_bytes();
+            let [id_hi, id_lo] = vlan_id.to_be_bytes();
+            [tag_hi, tag_lo, id_hi, id_lo]
+        });
+        self.bytes.splice(12..12, tags);
+
+        let shift = depth * VLAN_TAG_LEN;
+        self.l3_offset = self.l3_offset.map(|offset| offset + shift);
+        self.l4 = self.l4.map(|(protocol, offset)| (protocol, offset + shift));
+    }
+
+    /// Replace the question of a dns message with a compressed name that points
+    /// to itself, so that a decompressor that does not bound the pointer chain
+    /// loops forever.
+    ///
+    /// The udp and ipv4 lengths and the ipv4 checksum are updated.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the original frame is not a udp packet carrying
+    /// a dns header.
+    pub fn loop_dns_compression(&mut self) {
+        let dns_offset = self.l4_offset(IpProtocol::UDP) + UDP_HEADER_LEN;
+        assert!(self.bytes.len() >= dns_offset + DNS_HEADER_LEN);
+
+        // a single question whose name is the pointer to its own offset
+        self.bytes.truncate(dns_offset + DNS_HEADER_LEN);
+        self.bytes[dns_offset + 4..dns_offset + DNS_HEADER_LEN]
+            .copy_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+        let pointer = 0xc000 | DNS_HEADER_LEN as u16;
+        self.bytes.extend_from_slice(&pointer.to_be_bytes());
+        self.bytes.extend_from_slice(&[0, 1, 0, 1]);
+        self.adjust_udp_lengths();
+    }
+
+    /// Return the bytes of the frame.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..]
+    }
+
+    /// Return a copy of the frame.
+    pub fn build(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
+
+    fn l3_offset(&self) -> usize {
+        self.l3_offset.expect("the frame is not an ipv4 packet")
+    }
+
+    fn l4_offset(&self, protocol: IpProtocol) -> usize {
+        match self.l4 {
+            Some((l4_protocol, offset)) if l4_protocol == protocol => offset,
+            _ => panic!("the frame does not carry the l4 protocol"),
+        }
+    }
+
+    // Fit the udp and ipv4 lengths to the end of the frame, the udp checksum is
+    // disabled.
+    fn adjust_udp_lengths(&mut self) {
+        let l3_offset = self.l3_offset();
+        let l4_offset = self.l4_offset(IpProtocol::UDP);
+        let udp_len = (self.bytes.len() - l4_offset) as u16;
+        let udp = &mut self.bytes[l4_offset..l4_offset + UDP_HEADER_LEN];
+        NetworkEndian::write_u16(&mut udp[4..6], udp_len);
+        udp[6..8].fill(0);
+
+        let ip_len = (self.bytes.len() - l3_offset) as u16;
+        let header = &mut self.bytes[l3_offset..l4_offset];
+        NetworkEndian::write_u16(&mut header[2..4], ip_len);
+        header[10..12].fill(0);
+        let cksum = !checksum_utils::from_slice(header);
+        NetworkEndian::write_u16(&mut header[10..12], cksum);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipv4::{Ipv4Addr, Ipv4Packet};
+    use crate::untrusted::{Network, Transport};
+    use crate::{parse_untrusted, Cursor, ParseError};
+
+    #[test]
+    fn parsers_reject_malformed_packets() {
+        let dns_transport = Some(Transport::Udp {
+            src_port: 50000,
+            dst_port: DNS_PORT,
+        });
+        let parsed = parse_untrusted(MalformedPacketBuilder::tcp().as_bytes()).unwrap();
+        assert_eq!(parsed.payload_offset, TCP_FRAME.len());
+        let builder = MalformedPacketBuilder::dns_query();
+        let parsed = parse_untrusted(builder.as_bytes()).unwrap();
+        assert_eq!(parsed.transport, dns_transport);
+        let ippkt = Ipv4Packet::parse(Cursor::new(&builder.as_bytes()[ETHER_HEADER_LEN..]));
+        assert!(ippkt.unwrap().verify_checksum());
+
+        let mut frames = Vec::new();
+        let mut builder = MalformedPacketBuilder::tcp();
+        builder.truncate(ETHER_HEADER_LEN + 10);
+        assert_eq!(parse_untrusted(builder.as_bytes()), Err(ParseError::Ipv4));
+        frames.push(builder.build());
+
+        let mut builder = MalformedPacketBuilder::tcp();
+        builder.set_ihl(4);
+        assert_eq!(parse_untrusted(builder.as_bytes()), Err(ParseError::Ipv4));
+        frames.push(builder.build());
+        builder.set_ihl(15);
+        assert!(parse_untrusted(builder.as_bytes()).is_err());
+        frames.push(builder.build());
+
+        let mut builder = MalformedPacketBuilder::tcp();
+        builder.set_tcp_data_offset(4);
+        assert_eq!(parse_untrusted(builder.as_bytes()), Err(ParseError::Tcp));
+        frames.push(builder.build());
+        builder.set_tcp_data_offset(15);
+        assert_eq!(parse_untrusted(builder.as_bytes()), Err(ParseError::Tcp));
+        frames.push(builder.build());
+
+        // the checksums are left to the caller of `parse_untrusted`
+        let mut builder = MalformedPacketBuilder::tcp();
+        builder.set_bad_ipv4_checksum();
+        assert!(parse_untrusted(builder.as_bytes()).is_ok());
+        let ippkt = Ipv4Packet::parse(Cursor::new(&builder.as_bytes()[ETHER_HEADER_LEN..]));
+        assert!(!ippkt.unwrap().verify_checksum());
+        frames.push(builder.build());
+
+        // the vlan tags are not followed
+        let mut builder = MalformedPacketBuilder::tcp();
+        builder.overflow_vlan_stack(64);
+        assert_eq!(
+            builder.as_bytes().len(),
+            TCP_FRAME.len() + 64 * VLAN_TAG_LEN
+        );
+        let parsed = parse_untrusted(builder.as_bytes()).unwrap();
+        assert_eq!(parsed.network, Network::Other(EtherType::VLAN));
+        // the mutations still find the headers behind the tags
+        builder.set_tcp_data_offset(4);
+        assert_eq!(
+            builder.as_bytes()[ETHER_HEADER_LEN + 64 * VLAN_TAG_LEN + 20 + 12],
+            0x40
+        );
+        frames.push(builder.build());
+
+        // the dns payload is not decompressed by `parse_untrusted`
+        let mut builder = MalformedPacketBuilder::dns_query();
+        builder.loop_dns_compression();
+        let parsed = parse_untrusted(builder.as_bytes()).unwrap();
+        assert_eq!(
+            parsed.network,
+            Network::Ipv4 {
+                src: Ipv4Addr([192, 168, 1, 140]),
+                dst: Ipv4Addr([174, 143, 213, 184]),
+                protocol: IpProtocol::UDP,
+            }
+        );
+        assert_eq!(parsed.transport, dns_transport);
+        let dns = &builder.as_bytes()[parsed.payload_offset..];
+        assert_eq!(&dns[DNS_HEADER_LEN..DNS_HEADER_LEN + 2], &[0xc0, 0x0c]);
+        let ippkt = Ipv4Packet::parse(Cursor::new(&builder.as_bytes()[ETHER_HEADER_LEN..]));
+        assert!(ippkt.unwrap().verify_checksum());
+        frames.push(builder.build());
+
+        // every truncation of the malformed frames is handled
+        for frame in frames.iter() {
+            for len in 0..frame.len() {
+                let _ = parse_untrusted(&frame[..len]);
+            }
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use crate::ether::{EtherPacket, EtherType};
+use crate::ipv4::{IpProtocol, Ipv4Addr, Ipv4Packet};
+use crate::tcp::TcpPacket;
+use crate::udp::UdpPacket;
+use crate::Cursor;
+
+/// A packet filter expression over ethernet frames carrying ipv4 packets.
+///
+/// A matcher is built from the field matchers, e.g. `Matcher::ip_src`, and the
+/// `and`, `or` and `not` combinators. It is evaluated with `matches`, or
+/// compiled into a closure that can be passed to a packet filter, e.g.
+/// `FilterStage::new(move |mbuf| f(mbuf.data()))` of `rpkt-dpdk`.
+///
+/// The headers of a frame are parsed once per evaluation. A field matcher does
+/// not match if the frame does not carry the field, e.g. `tcp_dport` never
+/// matches a udp packet or a non-first ipv4 fragment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Matcher {
+    IpSrc(Ipv4Addr),
+    IpDst(Ipv4Addr),
+    IpProto(IpProtocol),
+    TcpSport(u16),
+    TcpDport(u16),
+    UdpSport(u16),
+    UdpDport(u16),
+    And(Box<Matcher>, Box<Matcher>),
+    Or(Box<Matcher>, Box<Matcher>),
+    Not(Box<Matcher>),
+}
+
+impl Matcher {
+    pub fn ip_src(addr: Ipv4Addr) -> Self {
+        Self::IpSrc(addr)
+    }
+
+    pub fn ip_dst(addr: Ipv4Addr) -> Self {
+        Self::IpDst(addr)
+    }
+
+    pub fn ip_proto(protocol: IpProtocol) -> Self {
+        Self::IpProto(protocol)
+    }
+
+    pub fn tcp_sport(port: u16) -> Self {
+        Self::TcpSport(port)
+    }
+
+    pub fn tcp_dport(port: u16) -> Self {
+        Self::TcpDport(port)
+    }
+
+    pub fn udp_sport(port: u16) -> Self {
+        Self::UdpSport(port)
+    }
+
+    pub fn udp_dport(port: u16) -> Self {
+        Self::UdpDport(port)
+    }
+
+    pub fn and(self, other: Matcher) -> Self {
+        Self::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: Matcher) -> Self {
+        Self::Or(Box::new(self), Box::new(other))
+    }
+
+    /// Return whether the ethernet frame in `frame` matches the expression.
+    pub fn matches(&self, frame: &[u8]) -> bool {
+        self.eval(&Fields::parse(frame))
+    }
+
+    /// Compile the expression into a closure over ethernet frames.
+    pub fn compile(self) -> impl Fn(&[u8]) -> bool + Send + Sync + 'static {
+        move |frame| self.matches(frame)
+    }
+
+    fn eval(&self, fields: &Fields) -> bool {
+        match self {
+            Self::IpSrc(addr) => fields.ipv4.is_some_and(|(src, _, _)| src == *addr),
+            Self::IpDst(addr) => fields.ipv4.is_some_and(|(_, dst, _)| dst == *addr),
+            Self::IpProto(protocol) => fields.ipv4.is_some_and(|(_, _, proto)| proto == *protocol),
+            Self::TcpSport(port) => fields.ports(IpProtocol::TCP).is_some_and(|p| p.0 == *port),
+            Self::TcpDport(port) => fields.ports(IpProtocol::TCP).is_some_and(|p| p.1 == *port),
+            Self::UdpSport(port) => fields.ports(IpProtocol::UDP).is_some_and(|p| p.0 == *port),
+            Self::UdpDport(port) => fields.ports(IpProtocol::UDP).is_some_and(|p| p.1 == *port),
+            Self::And(lhs, rhs) => lhs.eval(fields) && rhs.eval(fields),
+            Self::Or(lhs, rhs) => lhs.eval(fields) || rhs.eval(fields),
+            Self::Not(inner) => !inner.eval(fields),
+        }
+    }
+}
+
+impl std::ops::Not for Matcher {
+    type Output = Matcher;
+
+    fn not(self) -> Matcher {
+        Matcher::Not(Box::new(self))
+    }
+}
+
+// The header fields that are referenced by the matchers.
+struct Fields {
+    // source ip, destination ip and protocol
+    ipv4: Option<(Ipv4Addr, Ipv4Addr, IpProtocol)>,
+    // source port and destination port of a tcp/udp packet
+    ports: Option<(u16, u16)>,
+}
+
+impl Fields {
+    fn parse(frame: &[u8]) -> Self {
+        let mut fields = Self {
+            ipv4: None,
+            ports: None,
+        };
+
+        let ethpkt = match EtherPacket::parse(Cursor::new(frame)) {
+            Ok(ethpkt) if ethpkt.ethertype() == EtherType::IPV4 => ethpkt,
+            _ => return fields,
+        };
+        let ippkt = match Ipv4Packet::parse(ethpkt.payload()) {
+            Ok(ippkt) => ippkt,
+            Err(_) => return fields,
+        };
+        let protocol = ippkt.protocol();
+        fields.ipv4 = Some((ippkt.source_ip(), ippkt.dest_ip(), protocol));
+
+        // the non-first fragments do not carry the transport header
+        if ippkt.frag_offset() != 0 {
+            return fields;
+        }
+        fields.ports = match protocol {
+            IpProtocol::TCP => TcpPacket::parse(ippkt.payload())
+                .ok()
+                .map(|tcppkt| (tcppkt.src_port(), tcppkt.dst_port())),
+            IpProtocol::UDP => UdpPacket::parse(ippkt.payload())
+                .ok()
+                .map(|udppkt| (udppkt.source_port(), udppkt.dest_port())),
+            _ => None,
+        };
+        fields
+    }
+
+    fn ports(&self, protocol: IpProtocol) -> Option<(u16, u16)> {
+        match self.ipv4 {
+            Some((_, _, proto)) if proto == protocol => self.ports,
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ether::*;
+    use crate::ipv4::*;
+    use crate::tcp::*;
+    use crate::udp::*;
+    use crate::{Buf, CursorMut};
+    use std::ops::Not;
+
+    // Build an ethernet frame carrying a tcp/udp packet from 10.0.0.`src`.
+    fn build_frame(protocol: IpProtocol, src: u8, dst_port: u16) -> Vec<u8> {
+        let mut bytes = [0; 64];
+        let mut buf = CursorMut::new(&mut bytes[..]);
+        buf.advance(ETHER_HEADER_LEN + IPV4_HEADER_LEN + TCP_HEADER_LEN);
+
+        let buf = if protocol == IpProtocol::TCP {
+            let mut tcppkt = TcpPacket::prepend_header(buf, &TCP_HEADER_TEMPLATE);
+            tcppkt.set_src_port(50000);
+            tcppkt.set_dst_port(dst_port);
+            tcppkt.release()
+        } else {
+            let mut udppkt = UdpPacket::prepend_header(buf, &UDP_HEADER_TEMPLATE);
+            udppkt.set_source_port(50000);
+            udppkt.set_dest_port(dst_port);
+            udppkt.release()
+        };
+
+        let mut ipheader = IPV4_HEADER_TEMPLATE;
+        ipheader.set_protocol(protocol);
+        ipheader.set_source_ip(Ipv4Addr([10, 0, 0, src]));
+        ipheader.set_dest_ip(Ipv4Addr([10, 0, 0, 2]));
+        let mut ippkt = Ipv4Packet::prepend_header(buf, &ipheader);
+        ippkt.adjust_checksum();
+
+        let ethpkt = EtherPacket::prepend_header(ippkt.release(), &ETHER_HEADER_TEMPLATE);
+        ethpkt.release().chunk().to_vec()
+    }
+
+    #[test]
+    fn match_ip_src_and_tcp_dport() {
+        let f = Matcher::ip_src(Ipv4Addr([10, 0, 0, 1]))
+            .and(Matcher::tcp_dport(443))
+            .compile();
+
+        assert!(f(&build_frame(IpProtocol::TCP, 1, 443)));
+        assert!(!f(&build_frame(IpProtocol::TCP, 3, 443)));
+        assert!(!f(&build_frame(IpProtocol::TCP, 1, 80)));
+        // the udp port does not match a tcp port matcher
+        assert!(!f(&build_frame(IpProtocol::UDP, 1, 443)));
+        assert!(!f(&[0; 10][..]));
+    }
+
+    #[test]
+    fn match_or_not() {
+        let matcher = Matcher::udp_dport(53)
+            .or(Matcher::tcp_dport(53))
+            .and(!Matcher::ip_src(Ipv4Addr([10, 0, 0, 1])));
+
+        let frame = build_frame(IpProtocol::UDP, 3, 53);
+        assert!(matcher.matches(&frame));
+        assert!(Matcher::ip_proto(IpProtocol::UDP).matches(&frame));
+        assert!(Matcher::ip_dst(Ipv4Addr([10, 0, 0, 2])).matches(&frame));
+        assert!(Matcher::udp_sport(50000).matches(&frame));
+        assert!(matcher.matches(&build_frame(IpProtocol::TCP, 3, 53)));
+        assert!(!matcher.matches(&build_frame(IpProtocol::UDP, 1, 53)));
+        assert!(!matcher.matches(&build_frame(IpProtocol::UDP, 3, 54)));
+
+        // the ports of a non-first fragment are not matched
+        let mut frame = build_frame(IpProtocol::TCP, 3, 53);
+        frame[ETHER_HEADER_LEN + 7] = 1;
+        assert!(!matcher.matches(&frame));
+        assert!(Matcher::tcp_sport(50000).not().matches(&frame));
+    }
+}
@@ -0,0 +1,132 @@
+//! Payload patterns for generating test traffic.
+//!
+//! There is no packet builder in this crate: a packet is built by writing the
+//! payload into the buffer first, then prepending the protocol headers with
+//! `prepend_header`. `PayloadPattern` generates the payload bytes, so that
+//! test traffic of a given size can be built without supplying the bytes.
+
+/// The pattern used to fill the payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadPattern<'a> {
+    /// All bytes are zero.
+    Zeros,
+    /// The i-th byte is `i % 256`.
+    Incrementing,
+    /// Pseudo-random bytes, the same seed always generates the same bytes.
+    Random(u64),
+    /// The slice is repeated until the payload is filled.
+    ///
+    /// An empty slice fills the payload with zeros.
+    Repeating(&'a [u8]),
+}
+
+impl<'a> PayloadPattern<'a> {
+    /// Fill the whole `buf` with the pattern.
+    pub fn fill(&self, buf: &mut [u8]) {
+        match self {
+            PayloadPattern::Zeros => buf.fill(0),
+            PayloadPattern::Incrementing => {
+                for (i, byte) in buf.iter_mut().enumerate() {
+                    *byte = i as u8;
+                }
+            }
+            PayloadPattern::Random(seed) => {
+                let mut state = *seed;
+                for chunk in buf.chunks_mut(8) {
+                    let bytes = splitmix64(&mut state).to_le_bytes();
+                    chunk.copy_from_slice(&bytes[..chunk.len()]);
+                }
+            }
+            PayloadPattern::Repeating(pattern) => {
+                if pattern.is_empty() {
+                    buf.fill(0);
+                    return;
+                }
+                for chunk in buf.chunks_mut(pattern.len()) {
+                    chunk.copy_from_slice(&pattern[..chunk.len()]);
+                }
+            }
+        }
+    }
+
+    /// Generate a payload of `len` bytes with the pattern.
+    pub fn to_vec(&self, len: usize) -> Vec<u8> {
+        let mut buf = vec![0; len];
+        self.fill(&mut buf[..]);
+        buf
+    }
+}
+
+// The SplitMix64 generator, which is fast and accepts any seed.
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ether::*;
+    use crate::ipv4::*;
+    use crate::udp::*;
+    use crate::{Buf, Cursor, CursorMut};
+
+    #[test]
+    fn fixed_patterns() {
+        assert_eq!(PayloadPattern::Zeros.to_vec(5), vec![0; 5]);
+
+        let payload = PayloadPattern::Incrementing.to_vec(300);
+        assert_eq!(&payload[..3], &[0, 1, 2]);
+        assert_eq!(&payload[254..258], &[254, 255, 0, 1]);
+
+        let payload = PayloadPattern::Repeating(&[0xde, 0xad, 0xbe]).to_vec(7);
+        assert_eq!(payload, vec![0xde, 0xad, 0xbe, 0xde, 0xad, 0xbe, 0xde]);
+        assert_eq!(PayloadPattern::Repeating(&[]).to_vec(3), vec![0; 3]);
+
+        assert_eq!(PayloadPattern::Incrementing.to_vec(0), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn random_pattern_is_reproducible() {
+        let payload = PayloadPattern::Random(7).to_vec(21);
+        assert_eq!(payload.len(), 21);
+        assert_eq!(payload, PayloadPattern::Random(7).to_vec(21));
+        assert_ne!(payload, PayloadPattern::Random(8).to_vec(21));
+
+        // a shorter payload is a prefix of a longer one with the same seed
+        assert_eq!(&PayloadPattern::Random(7).to_vec(64)[..21], &payload[..]);
+        assert!(PayloadPattern::Random(0).to_vec(16).iter().any(|b| *b != 0));
+    }
+
+    #[test]
+    fn build_udp_packet_with_pattern() {
+        let mut bytes = [0xff; 128];
+        let payload_len = 50;
+        let header_len = ETHER_HEADER_LEN + IPV4_HEADER_LEN + UDP_HEADER_LEN;
+        PayloadPattern::Incrementing.fill(&mut bytes[header_len..header_len + payload_len]);
+
+        let mut pktbuf = CursorMut::new(&mut bytes[..header_len + payload_len]);
+        pktbuf.advance(header_len);
+
+        let mut udppkt = UdpPacket::prepend_header(pktbuf, &UDP_HEADER_TEMPLATE);
+        udppkt.set_source_port(60376);
+        udppkt.set_dest_port(161);
+        let mut ippkt = Ipv4Packet::prepend_header(udppkt.release(), &IPV4_HEADER_TEMPLATE);
+        ippkt.set_protocol(IpProtocol::UDP);
+        let mut ethpkt = EtherPacket::prepend_header(ippkt.release(), &ETHER_HEADER_TEMPLATE);
+        ethpkt.set_ethertype(EtherType::IPV4);
+        let buf = ethpkt.release();
+        assert_eq!(buf.chunk().len(), header_len + payload_len);
+
+        let ethpkt = EtherPacket::parse(Cursor::new(buf.chunk())).unwrap();
+        let ippkt = Ipv4Packet::parse(ethpkt.payload()).unwrap();
+        let udppkt = UdpPacket::parse(ippkt.payload()).unwrap();
+        assert_eq!(
+            udppkt.payload().chunk(),
+            &PayloadPattern::Incrementing.to_vec(50)[..]
+        );
+    }
+}
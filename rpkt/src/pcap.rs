@@ -0,0 +1,341 @@
+//! A reader and a writer of the classic pcap capture files.
+//!
+//! Both the microsecond and the nanosecond formats are read, in either byte
+//! order. The writer always writes the little-endian nanosecond format. The
+//! pcapng format is not supported.
+
+use std::io::{self, Read, Write};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+const PCAP_HEADER_LEN: usize = 24;
+const RECORD_HEADER_LEN: usize = 16;
+
+const MAGIC_MICROS: u32 = 0xa1b2c3d4;
+const MAGIC_NANOS: u32 = 0xa1b23c4d;
+
+/// The link type of the ethernet captures.
+pub const LINKTYPE_ETHERNET: u32 = 1;
+
+/// The maximum length of a captured packet, the same as the `MAXIMUM_SNAPLEN`
+/// of libpcap. A longer record is treated as a corrupted file.
+pub const MAX_SNAPLEN: u32 = 262144;
+
+/// A packet read from a capture file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PcapRecord {
+    /// The seconds of the capture timestamp.
+    pub ts_sec: u32,
+    /// The nanoseconds of the capture timestamp, which is scaled from the
+    /// microseconds of a microsecond capture.
+    pub ts_nsec: u32,
+    /// The length of the packet on the wire, which exceeds the length of
+    /// `data` if the packet is truncated by the snapshot length.
+    pub orig_len: u32,
+    pub data: Vec<u8>,
+}
+
+/// A reader of the records of a pcap capture from `R`.
+///
+/// The records are read with `next_record` or by iterating over the reader.
+pub struct PcapReader<R> {
+    reader: R,
+    big_endian: bool,
+    nanos: bool,
+    snaplen: u32,
+    linktype: u32,
+}
+
+impl<R: Read> PcapReader<R> {
+    /// Read the file header from `reader`.
+    ///
+    /// This function fails if `reader` does not start with a pcap file header.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; PCAP_HEADER_LEN];
+        reader.read_exact(&mut header[..])?;
+
+        let (big_endian, nanos) = match (
+            LittleEndian::read_u32(&header[0..4]),
+            BigEndian::read_u32(&header[0..4]),
+        ) {
+            (MAGIC_MICROS, _) => (false, false),
+            (MAGIC_NANOS, _) => (false, true),
+            (_, MAGIC_MICROS) => (true, false),
+            (_, MAGIC_NANOS) => (true, true),
+            _ => return Err(invalid_data("not a pcap file")),
+        };
+
+        let mut pcap = Self {
+            reader,
+            big_endian,
+            nanos,
+            snaplen: 0,
+            linktype: 0,
+        };
+        pcap.snaplen = pcap.read_u32(&header[16..20]);
+        pcap.linktype = pcap.read_u32(&header[20..24]) & 0x0fff_ffff;
+        Ok(pcap)
+    }
+
+    /// Return the link type of the packets, e.g. `LINKTYPE_ETHERNET`.
+    pub fn linktype(&self) -> u32 {
+        self.linktype
+    }
+
+    /// Return the snapshot length of the capture, the packets are truncated to
+    /// this length.
+    pub fn snaplen(&self) -> u32 {
+        self.snaplen
+    }
+
+    /// Read the next record, return `None` at the end of the capture.
+    ///
+    /// A record that is cut by the end of the capture is an error.
+    pub fn next_record(&mut self) -> io::Result<Option<PcapRecord>> {
+        let mut header = [0; RECORD_HEADER_LEN];
+        let mut filled = 0;
+        while filled < RECORD_HEADER_LEN {
+            match self.reader.read(&mut header[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => filled += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        let ts_sec = self.read_u32(&header[0..4]);
+        let ts_frac = self.read_u32(&header[4..8]);
+        let incl_len = self.read_u32(&header[8..12]);
+        let orig_len = self.read_u32(&header[12..16]);
+        if incl_len > MAX_SNAPLEN.max(self.snaplen) {
+            return Err(invalid_data("pcap record exceeds the snapshot length"));
+        }
+
+        let mut data = vec![0; incl_len as usize];
+        self.reader.read_exact(&mut data[..])?;
+        Ok(Some(PcapRecord {
+            ts_sec,
+            ts_nsec: if self.nanos {
+                ts_frac
+            } else {
+                ts_frac.wrapping_mul(1000)
+            },
+            orig_len,
+            data,
+        }))
+    }
+
+    /// Return the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_u32(&self, buf: &[u8]) -> u32 {
+        if self.big_endian {
+            BigEndian::read_u32(buf)
+        } else {
+            LittleEndian::read_u32(buf)
+        }
+    }
+}
+
+impl<R: Read> Iterator for PcapReader<R> {
+    type Item = io::Result<PcapRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+/// A writer of an ethernet pcap capture to `W`.
+///
+/// The capture has the nanosecond timestamps. The writes are not buffered, so
+/// `W` should be buffered if the packets are written one by one, e.g. with a
+/// `BufWriter`.
+pub struct PcapWriter<W: Write> {
+    writer: W,
+    snaplen: u32,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Write the file header to `writer`, the packets are truncated to
+    /// `snaplen`, which is capped at `MAX_SNAPLEN`.
+    pub fn new(mut writer: W, snaplen: u32) -> io::Result<Self> {
+        let snaplen = snaplen.min(MAX_SNAPLEN);
+        let mut header = [0; PCAP_HEADER_LEN];
+        LittleEndian::write_u32(&mut header[0..4], MAGIC_NANOS);
+        // version 2.4, the zone and the sigfigs are 0
+        LittleEndian::write_u16(&mut header[4..6], 2);
+        LittleEndian::write_u16(&mut header[6..8], 4);
+        LittleEndian::write_u32(&mut header[16..20], snaplen);
+        LittleEndian::write_u32(&mut header[20..24], LINKTYPE_ETHERNET);
+        writer.write_all(&header[..])?;
+
+        Ok(Self { writer, snaplen })
+    }
+
+    /// Return the snapshot length of the capture.
+    pub fn snaplen(&self) -> u32 {
+        self.snaplen
+    }
+
+    /// Write a packet captured at `ts_sec` and `ts_nsec`, which is truncated to
+    /// the snapshot length.
+    pub fn write_packet(&mut self, ts_sec: u32, ts_nsec: u32, data: &[u8]) -> io::Result<()> {
+        let orig_len = u32::try_from(data.len()).unwrap_or(u32::MAX);
+        self.write(ts_sec, ts_nsec, orig_len, data)
+    }
+
+    /// Write `record`, keeping its original length.
+    pub fn write_record(&mut self, record: &PcapRecord) -> io::Result<()> {
+        self.write(
+            record.ts_sec,
+            record.ts_nsec,
+            record.orig_len,
+            &record.data[..],
+        )
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Return the wrapped writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write(&mut self, ts_sec: u32, ts_nsec: u32, orig_len: u32, data: &[u8]) -> io::Result<()> {
+        let incl_len = data.len().min(self.snaplen as usize);
+
+        let mut header = [0; RECORD_HEADER_LEN];
+        LittleEndian::write_u32(&mut header[0..4], ts_sec);
+        LittleEndian::write_u32(&mut header[4..8], ts_nsec);
+        LittleEndian::write_u32(&mut header[8..12], incl_len as u32);
+        LittleEndian::write_u32(&mut header[12..16], orig_len);
+        self.writer.write_all(&header[..])?;
+        self.writer.write_all(&data[..incl_len])
+    }
+}
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_pcap(magic: u32, big_endian: bool, records: &[(u32, u32, &[u8])]) -> Vec<u8> {
+        let write_u32 = |bytes: &mut Vec<u8>, val: u32| {
+            if big_endian {
+                bytes.extend_from_slice(&val.to_be_bytes());
+            } else {
+                bytes.extend_from_slice(&val.to_le_bytes());
+            }
+        };
+
+        let mut bytes = Vec::new();
+        write_u32(&mut bytes, magic);
+        // version 2.4, the zone and the sigfigs are 0
+        write_u32(
+            &mut bytes,
+            if big_endian { 0x0002_0004 } else { 0x0004_0002 },
+        );
+        write_u32(&mut bytes, 0);
+        write_u32(&mut bytes, 0);
+        write_u32(&mut bytes, 65535);
+        write_u32(&mut bytes, LINKTYPE_ETHERNET);
+        for (ts_sec, ts_frac, data) in records {
+            write_u32(&mut bytes, *ts_sec);
+            write_u32(&mut bytes, *ts_frac);
+            write_u32(&mut bytes, data.len() as u32);
+            write_u32(&mut bytes, data.len() as u32 + 4);
+            bytes.extend_from_slice(data);
+        }
+        bytes
+    }
+
+    #[test]
+    fn read_records() {
+        let records: [(u32, u32, &[u8]); 3] = [(1, 2, &[0xaa; 60]), (3, 4, &[]), (5, 6, &[1, 2])];
+        for (magic, big_endian) in [
+            (MAGIC_MICROS, false),
+            (MAGIC_MICROS, true),
+            (MAGIC_NANOS, false),
+            (MAGIC_NANOS, true),
+        ] {
+            let bytes = build_pcap(magic, big_endian, &records[..]);
+            let pcap = PcapReader::new(&bytes[..]).unwrap();
+            assert_eq!(pcap.linktype(), LINKTYPE_ETHERNET);
+            assert_eq!(pcap.snaplen(), 65535);
+
+            let read: Vec<_> = pcap.map(|record| record.unwrap()).collect();
+            assert_eq!(read.len(), 3);
+            for (record, (ts_sec, ts_frac, data)) in read.iter().zip(records.iter()) {
+                assert_eq!(record.ts_sec, *ts_sec);
+                let ts_nsec = if magic == MAGIC_NANOS {
+                    *ts_frac
+                } else {
+                    *ts_frac * 1000
+                };
+                assert_eq!(record.ts_nsec, ts_nsec);
+                assert_eq!(record.orig_len, data.len() as u32 + 4);
+                assert_eq!(&record.data[..], *data);
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_pcap() {
+        assert!(PcapReader::new(&[0; 24][..]).is_err());
+        assert!(PcapReader::new(&[0xd4, 0xc3, 0xb2, 0xa1][..]).is_err());
+
+        // the last record is cut
+        let bytes = build_pcap(MAGIC_MICROS, false, &[(1, 2, &[0xaa; 60])]);
+        let mut pcap = PcapReader::new(&bytes[..bytes.len() - 1]).unwrap();
+        assert!(pcap.next_record().is_err());
+        let mut pcap = PcapReader::new(&bytes[..30]).unwrap();
+        assert!(pcap.next_record().is_err());
+
+        // the length of the record is corrupted
+        let mut bytes = bytes;
+        bytes[32..36].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut pcap = PcapReader::new(&bytes[..]).unwrap();
+        assert!(pcap.next_record().is_err());
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let mut pcap = PcapWriter::new(Vec::new(), 64).unwrap();
+        assert_eq!(pcap.snaplen(), 64);
+        pcap.write_packet(1, 2, &[0xaa; 60][..]).unwrap();
+        // truncated to the snapshot length
+        pcap.write_packet(3, 999_999_999, &[0xbb; 100][..]).unwrap();
+        let record = PcapRecord {
+            ts_sec: 5,
+            ts_nsec: 6,
+            orig_len: 1514,
+            data: vec![0xcc; 20],
+        };
+        pcap.write_record(&record).unwrap();
+        pcap.flush().unwrap();
+        let bytes = pcap.into_inner();
+
+        let pcap = PcapReader::new(&bytes[..]).unwrap();
+        assert_eq!(pcap.linktype(), LINKTYPE_ETHERNET);
+        assert_eq!(pcap.snaplen(), 64);
+        let read: Vec<_> = pcap.map(|record| record.unwrap()).collect();
+        assert_eq!(read.len(), 3);
+        assert_eq!(
+            (read[0].ts_sec, read[0].ts_nsec, read[0].orig_len),
+            (1, 2, 60)
+        );
+        assert_eq!(&read[0].data[..], &[0xaa; 60][..]);
+        assert_eq!(read[1].ts_nsec, 999_999_999);
+        assert_eq!(read[1].orig_len, 100);
+        assert_eq!(&read[1].data[..], &[0xbb; 64][..]);
+        assert_eq!(read[2], record);
+    }
+}
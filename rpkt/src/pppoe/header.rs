@@ -0,0 +1,117 @@
+use byteorder::{ByteOrder, NetworkEndian};
+
+header_field_val_accessors! {
+    (ver_type, ver_type_mut, 0),
+    (code, code_mut, 1),
+}
+
+header_field_range_accessors! {
+    (session_id, session_id_mut, 2..4),
+    (length, length_mut, 4..6),
+}
+
+pub const PPPOE_HEADER_LEN: usize = 6;
+
+/// A pppoe session header of version 1 and type 1.
+pub const PPPOE_HEADER_TEMPLATE: PppoeHeader<[u8; 6]> = PppoeHeader {
+    buf: [0x11, 0x00, 0x00, 0x00, 0x00, 0x00],
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct PppoeHeader<T> {
+    buf: T,
+}
+
+impl<T: AsRef<[u8]>> PppoeHeader<T> {
+    #[inline]
+    pub fn new(buf: T) -> Result<Self, T> {
+        if buf.as_ref().len() >= PPPOE_HEADER_LEN {
+            Ok(Self { buf })
+        } else {
+            Err(buf)
+        }
+    }
+
+    #[inline]
+    pub fn new_unchecked(buf: T) -> Self {
+        Self { buf }
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf.as_ref()[0..PPPOE_HEADER_LEN]
+    }
+
+    #[inline]
+    pub fn to_owned(&self) -> PppoeHeader<[u8; PPPOE_HEADER_LEN]> {
+        let mut buf = [0; PPPOE_HEADER_LEN];
+        buf.copy_from_slice(self.as_bytes());
+        PppoeHeader { buf }
+    }
+
+    #[inline]
+    pub fn version(&self) -> u8 {
+        let data = ver_type(self.buf.as_ref());
+        *data >> 4
+    }
+
+    #[inline]
+    pub fn pppoe_type(&self) -> u8 {
+        let data = ver_type(self.buf.as_ref());
+        *data & 0x0f
+    }
+
+    /// The code is 0 for the session data.
+    #[inline]
+    pub fn code(&self) -> u8 {
+        let data = code(self.buf.as_ref());
+        *data
+    }
+
+    #[inline]
+    pub fn session_id(&self) -> u16 {
+        let data = session_id(self.buf.as_ref());
+        NetworkEndian::read_u16(data)
+    }
+
+    /// The length of the pppoe payload, excluding the pppoe header.
+    #[inline]
+    pub fn payload_len(&self) -> u16 {
+        let data = length(self.buf.as_ref());
+        NetworkEndian::read_u16(data)
+    }
+}
+
+impl<T: AsMut<[u8]>> PppoeHeader<T> {
+    #[inline]
+    pub fn set_version(&mut self, value: u8) {
+        assert!(value <= 0x0f);
+        let data = ver_type_mut(self.buf.as_mut());
+        *data = (*data & 0x0f) | (value << 4);
+    }
+
+    #[inline]
+    pub fn set_pppoe_type(&mut self, value: u8) {
+        assert!(value <= 0x0f);
+        let data = ver_type_mut(self.buf.as_mut());
+        *data = (*data & 0xf0) | value;
+    }
+
+    #[inline]
+    pub fn set_code(&mut self, value: u8) {
+        let data = code_mut(self.buf.as_mut());
+        *data = value;
+    }
+
+    #[inline]
+    pub fn set_session_id(&mut self, value: u16) {
+        let data = session_id_mut(self.buf.as_mut());
+        NetworkEndian::write_u16(data, value);
+    }
+
+    #[inline]
+    pub fn set_payload_len(&mut self, value: u16) {
+        let data = length_mut(self.buf.as_mut());
+        NetworkEndian::write_u16(data, value);
+    }
+}
@@ -0,0 +1,18 @@
+enum_sim! {
+    /// The protocol field of a ppp header.
+    pub struct PppProtocol (u16) {
+        IPV4 = 0x0021,
+        IPV6 = 0x0057,
+        IPCP = 0x8021,
+        IPV6CP = 0x8057,
+        LCP = 0xc021,
+        PAP = 0xc023,
+        CHAP = 0xc223,
+    }
+}
+
+mod header;
+pub use header::{PppoeHeader, PPPOE_HEADER_LEN, PPPOE_HEADER_TEMPLATE};
+
+mod packet;
+pub use self::packet::{PppPacket, PppoePacket};
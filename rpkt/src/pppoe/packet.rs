@@ -0,0 +1,322 @@
+use bytes::Buf;
+
+use crate::{Cursor, CursorMut, Header, ParseError};
+use crate::{PktBuf, PktMut};
+
+use super::header::{PppoeHeader, PPPOE_HEADER_LEN};
+use super::PppProtocol;
+
+packet_base! {
+    pub struct PppoePacket: PppoeHeader {
+        header_len: PPPOE_HEADER_LEN,
+        get_methods: [
+            (version, u8),
+            (pppoe_type, u8),
+            (code, u8),
+            (session_id, u16),
+            (payload_len, u16),
+        ],
+        set_methods: [
+            (set_version, value: u8),
+            (set_pppoe_type, value: u8),
+            (set_code, value: u8),
+            (set_session_id, value: u16),
+        ],
+        unchecked_set_methods:[
+            (set_payload_len_unchecked, set_payload_len, value: u16)
+        ]
+    }
+}
+
+impl<T: Buf> PppoePacket<T> {
+    #[inline]
+    pub fn parse(buf: T) -> Result<PppoePacket<T>, T> {
+        if buf.chunk().len() < PPPOE_HEADER_LEN {
+            return Err(buf);
+        }
+
+        let packet = PppoePacket::parse_unchecked(buf);
+
+        if PPPOE_HEADER_LEN + usize::from(packet.payload_len()) <= packet.buf.remaining() {
+            Ok(packet)
+        } else {
+            Err(packet.release())
+        }
+    }
+}
+
+impl<T: PktBuf> PppoePacket<T> {
+    /// Return the ppp packet, with the ethernet padding trimmed off.
+    #[inline]
+    pub fn payload(self) -> T {
+        let packet_len = PPPOE_HEADER_LEN + usize::from(self.payload_len());
+        assert!(packet_len <= self.buf.remaining());
+        let trim_size = self.buf.remaining() - packet_len;
+
+        let mut buf = self.release();
+        if trim_size > 0 {
+            buf.trim_off(trim_size);
+        }
+
+        buf.advance(PPPOE_HEADER_LEN);
+
+        buf
+    }
+}
+
+impl<T: PktMut> PppoePacket<T> {
+    #[inline]
+    pub fn prepend_header<TH: AsRef<[u8]>>(mut buf: T, header: &PppoeHeader<TH>) -> PppoePacket<T> {
+        assert!(buf.chunk_headroom() >= PPPOE_HEADER_LEN);
+        let payload_len = u16::try_from(buf.remaining()).unwrap();
+        buf.move_back(PPPOE_HEADER_LEN);
+
+        let data = &mut buf.chunk_mut()[0..PPPOE_HEADER_LEN];
+        data.copy_from_slice(header.as_bytes());
+
+        let mut pppoepkt = PppoePacket::parse_unchecked(buf);
+        pppoepkt.set_payload_len_unchecked(payload_len);
+        pppoepkt
+    }
+}
+
+impl<'a> PppoePacket<Cursor<'a>> {
+    #[inline]
+    pub fn cursor_header(&self) -> PppoeHeader<&'a [u8]> {
+        let data = &self.buf.chunk_shared_lifetime()[..PPPOE_HEADER_LEN];
+        PppoeHeader::new_unchecked(data)
+    }
+
+    #[inline]
+    pub fn cursor_payload(&self) -> Cursor<'a> {
+        let packet_len = PPPOE_HEADER_LEN + usize::from(self.payload_len());
+        Cursor::new(&self.buf.chunk_shared_lifetime()[PPPOE_HEADER_LEN..packet_len])
+    }
+}
+
+impl<'a> Header<'a> for PppoePacket<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        PppoePacket::parse(Cursor::new(buf)).map_err(|_| ParseError::Pppoe)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        PPPOE_HEADER_LEN
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        self.cursor_payload().chunk_shared_lifetime()
+    }
+}
+
+impl<'a> PppoePacket<CursorMut<'a>> {
+    #[inline]
+    pub fn split(self) -> (PppoeHeader<&'a mut [u8]>, CursorMut<'a>) {
+        let packet_len = PPPOE_HEADER_LEN + usize::from(self.payload_len());
+
+        let (buf_mut, _) = self
+            .buf
+            .chunk_mut_shared_lifetime()
+            .split_at_mut(packet_len);
+        let (hdr, payload) = buf_mut.split_at_mut(PPPOE_HEADER_LEN);
+
+        (PppoeHeader::new_unchecked(hdr), CursorMut::new(payload))
+    }
+}
+
+/// A ppp packet, which starts with a 1-byte or 2-byte protocol field.
+///
+/// The protocol field is compressed to 1 byte if the peers negotiate the
+/// protocol field compression. A protocol value always has an odd low byte and
+/// an even high byte, so a 1-byte field is recognized by its odd value.
+#[derive(Debug)]
+pub struct PppPacket<T> {
+    buf: T,
+}
+
+impl<T: Buf> PppPacket<T> {
+    #[inline]
+    pub fn parse(buf: T) -> Result<PppPacket<T>, T> {
+        let chunk = buf.chunk();
+        let valid = match chunk.len() {
+            0 => false,
+            1 => chunk[0] & 0x01 == 1,
+            _ => chunk[0] & 0x01 == 1 || chunk[1] & 0x01 == 1,
+        };
+        if valid {
+            Ok(PppPacket { buf })
+        } else {
+            Err(buf)
+        }
+    }
+
+    #[inline]
+    pub fn parse_unchecked(buf: T) -> Self {
+        Self { buf }
+    }
+
+    #[inline]
+    pub fn buf(&self) -> &T {
+        &self.buf
+    }
+
+    #[inline]
+    pub fn release(self) -> T {
+        self.buf
+    }
+
+    /// Return the length of the protocol field, which is 1 or 2.
+    #[inline]
+    pub fn header_len(&self) -> usize {
+        if self.buf.chunk()[0] & 0x01 == 1 {
+            1
+        } else {
+            2
+        }
+    }
+
+    #[inline]
+    pub fn protocol(&self) -> PppProtocol {
+        let chunk = self.buf.chunk();
+        if self.header_len() == 1 {
+            u16::from(chunk[0]).into()
+        } else {
+            u16::from_be_bytes([chunk[0], chunk[1]]).into()
+        }
+    }
+
+    #[inline]
+    pub fn payload(self) -> T {
+        let header_len = self.header_len();
+
+        let mut buf = self.release();
+        buf.advance(header_len);
+
+        buf
+    }
+}
+
+impl<'a> Header<'a> for PppPacket<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        PppPacket::parse(Cursor::new(buf)).map_err(|_| ParseError::Pppoe)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        self.header_len()
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        &self.buf.chunk_shared_lifetime()[self.header_len()..]
+    }
+}
+
+impl<T: PktMut> PppPacket<T> {
+    /// Prepend an uncompressed 2-byte protocol field to `buf`.
+    #[inline]
+    pub fn prepend_header(mut buf: T, protocol: PppProtocol) -> PppPacket<T> {
+        assert!(buf.chunk_headroom() >= 2);
+        buf.move_back(2);
+
+        let data = &mut buf.chunk_mut()[0..2];
+        data.copy_from_slice(&u16::from(protocol).to_be_bytes());
+
+        PppPacket::parse_unchecked(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ether::*;
+    use crate::ipv4::*;
+    use crate::pppoe::PPPOE_HEADER_TEMPLATE;
+
+    // An ethernet frame carrying a pppoe session packet of an icmp echo request
+    // from 10.0.0.1 to 10.0.0.2, followed by 4 bytes of ethernet padding.
+    static FRAME_BYTES: [u8; 58] = [
+        0x00, 0x0b, 0x86, 0x64, 0x8b, 0xa0, 0x00, 0x50, 0x56, 0xae, 0x76, 0xf5, 0x88, 0x64, 0x11,
+        0x00, 0x12, 0x34, 0x00, 0x22, 0x00, 0x21, 0x45, 0x00, 0x00, 0x20, 0x00, 0x01, 0x00, 0x00,
+        0x40, 0x01, 0x66, 0xda, 0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x08, 0x00, 0xf7,
+        0xfc, 0x00, 0x01, 0x00, 0x02, 0x61, 0x62, 0x63, 0x64, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn packet_parse() {
+        let ethpkt = EtherPacket::parse(Cursor::new(&FRAME_BYTES[..])).unwrap();
+        assert_eq!(ethpkt.ethertype(), EtherType::PPPOE_SESSION);
+
+        let pppoepkt = PppoePacket::parse(ethpkt.payload()).unwrap();
+        assert_eq!(pppoepkt.version(), 1);
+        assert_eq!(pppoepkt.pppoe_type(), 1);
+        assert_eq!(pppoepkt.code(), 0);
+        assert_eq!(pppoepkt.session_id(), 0x1234);
+        assert_eq!(pppoepkt.payload_len(), 34);
+        assert_eq!(pppoepkt.cursor_payload().chunk().len(), 34);
+
+        let ppppkt = PppPacket::parse(pppoepkt.payload()).unwrap();
+        assert_eq!(ppppkt.header_len(), 2);
+        assert_eq!(ppppkt.protocol(), PppProtocol::IPV4);
+
+        let ippkt = Ipv4Packet::parse(ppppkt.payload()).unwrap();
+        assert!(ippkt.verify_checksum());
+        assert_eq!(ippkt.source_ip(), Ipv4Addr([10, 0, 0, 1]));
+        assert_eq!(ippkt.dest_ip(), Ipv4Addr([10, 0, 0, 2]));
+        assert_eq!(ippkt.protocol(), IpProtocol::ICMP);
+        // the ethernet padding is trimmed off
+        assert_eq!(ippkt.payload().chunk().len(), 12);
+    }
+
+    #[test]
+    fn ppp_protocol_field() {
+        // a compressed protocol field
+        let ppppkt = PppPacket::parse(Cursor::new(&[0x21, 0x45][..])).unwrap();
+        assert_eq!(ppppkt.header_len(), 1);
+        assert_eq!(ppppkt.protocol(), PppProtocol::IPV4);
+        assert_eq!(ppppkt.payload().chunk(), &[0x45][..]);
+
+        let ppppkt = PppPacket::parse(Cursor::new(&[0x57][..])).unwrap();
+        assert_eq!(ppppkt.protocol(), PppProtocol::IPV6);
+
+        let ppppkt = PppPacket::parse(Cursor::new(&[0xc0, 0x21][..])).unwrap();
+        assert_eq!(ppppkt.header_len(), 2);
+        assert_eq!(ppppkt.protocol(), PppProtocol::LCP);
+
+        // both bytes are even
+        assert!(PppPacket::parse(Cursor::new(&[0x00, 0x20][..])).is_err());
+        assert!(PppPacket::parse(Cursor::new(&[0x00][..])).is_err());
+        assert!(PppPacket::parse(Cursor::new(&[][..])).is_err());
+    }
+
+    #[test]
+    fn packet_parse_invalid() {
+        // the pppoe length exceeds the packet
+        let mut bytes = FRAME_BYTES;
+        bytes[18..20].copy_from_slice(&39_u16.to_be_bytes());
+        let ethpkt = EtherPacket::parse(Cursor::new(&bytes[..])).unwrap();
+        assert!(PppoePacket::parse(ethpkt.payload()).is_err());
+    }
+
+    #[test]
+    fn packet_build() {
+        let mut bytes = [0xff; 58];
+        bytes[22..54].copy_from_slice(&FRAME_BYTES[22..54]);
+
+        let mut buf = CursorMut::new(&mut bytes[..]);
+        buf.advance(22);
+        buf.trim_off(4);
+        let ppppkt = PppPacket::prepend_header(buf, PppProtocol::IPV4);
+        let mut pppoepkt = PppoePacket::prepend_header(ppppkt.release(), &PPPOE_HEADER_TEMPLATE);
+        pppoepkt.set_session_id(0x1234);
+        let mut ethpkt = EtherPacket::prepend_header(pppoepkt.release(), &ETHER_HEADER_TEMPLATE);
+        ethpkt.set_dest_mac(MacAddr([0x00, 0x0b, 0x86, 0x64, 0x8b, 0xa0]));
+        ethpkt.set_source_mac(MacAddr([0x00, 0x50, 0x56, 0xae, 0x76, 0xf5]));
+        ethpkt.set_ethertype(EtherType::PPPOE_SESSION);
+
+        assert_eq!(ethpkt.buf().chunk(), &FRAME_BYTES[..54]);
+    }
+}
@@ -0,0 +1,28 @@
+enum_sim! {
+    /// The message types of ptp version 2. The types below 8 are the event
+    /// messages, which are timestamped and sent to the event port.
+    pub struct PtpMessageType (u8) {
+        SYNC = 0x0,
+        DELAY_REQ = 0x1,
+        PDELAY_REQ = 0x2,
+        PDELAY_RESP = 0x3,
+        FOLLOW_UP = 0x8,
+        DELAY_RESP = 0x9,
+        PDELAY_RESP_FOLLOW_UP = 0xa,
+        ANNOUNCE = 0xb,
+        SIGNALING = 0xc,
+        MANAGEMENT = 0xd,
+    }
+}
+
+/// The udp port of the ptp event messages.
+pub const PTP_EVENT_UDP_PORT: u16 = 319;
+
+/// The udp port of the ptp general messages.
+pub const PTP_GENERAL_UDP_PORT: u16 = 320;
+
+/// The length of the common header of the ptp messages.
+pub const PTP_HEADER_LEN: usize = 34;
+
+mod packet;
+pub use packet::{PtpMessage, PtpPacket, PtpPortIdentity, PtpTimestamp};
@@ -0,0 +1,408 @@
+use std::time::Duration;
+
+use byteorder::{ByteOrder, NetworkEndian};
+use bytes::Buf;
+
+use crate::{Cursor, Header, ParseError, PktBuf};
+
+use super::{PtpMessageType, PTP_HEADER_LEN};
+
+// The length of a timestamp and of a port identity on the wire.
+const TIMESTAMP_LEN: usize = 10;
+const PORT_IDENTITY_LEN: usize = 10;
+
+// The two-step flag in the first byte of the flag field.
+const FLAG_TWO_STEP: u16 = 0x0200;
+
+/// A ptp timestamp, the time since the ptp epoch with a 48-bit seconds field.
+///
+/// The ptp epoch is 1970-01-01 in tai, so a ptp timestamp is ahead of the
+/// unix time by the tai-utc offset, 37 seconds since 2017.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PtpTimestamp {
+    pub seconds: u64,
+    /// Always below 10^9.
+    pub nanoseconds: u32,
+}
+
+impl PtpTimestamp {
+    fn parse(buf: &[u8]) -> Option<Self> {
+        let buf = buf.get(..TIMESTAMP_LEN)?;
+        let nanoseconds = NetworkEndian::read_u32(&buf[6..10]);
+        if nanoseconds >= 1_000_000_000 {
+            return None;
+        }
+        Some(Self {
+            seconds: NetworkEndian::read_u48(&buf[0..6]),
+            nanoseconds,
+        })
+    }
+
+    /// Return the time since the ptp epoch.
+    #[inline]
+    pub fn to_duration(&self) -> Duration {
+        Duration::new(self.seconds, self.nanoseconds)
+    }
+}
+
+/// The identity of a ptp port, i.e. the eui-64 of its clock and the port
+/// number within the clock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PtpPortIdentity {
+    pub clock_identity: [u8; 8],
+    pub port_number: u16,
+}
+
+impl PtpPortIdentity {
+    fn parse(buf: &[u8]) -> Option<Self> {
+        let buf = buf.get(..PORT_IDENTITY_LEN)?;
+        Some(Self {
+            clock_identity: buf[0..8].try_into().unwrap(),
+            port_number: NetworkEndian::read_u16(&buf[8..10]),
+        })
+    }
+}
+
+/// The body of a ptp message of the delay request-response mechanism,
+/// returned by `PtpPacket::message`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PtpMessage {
+    /// The origin timestamp of a two-step sync is an estimate, the precise
+    /// one is carried by the follow up with the same sequence id.
+    Sync {
+        origin_timestamp: PtpTimestamp,
+    },
+    DelayReq {
+        origin_timestamp: PtpTimestamp,
+    },
+    FollowUp {
+        precise_origin_timestamp: PtpTimestamp,
+    },
+    /// The master's receive timestamp of the delay request sent by
+    /// `requesting_port_identity`.
+    DelayResp {
+        receive_timestamp: PtpTimestamp,
+        requesting_port_identity: PtpPortIdentity,
+    },
+}
+
+/// A ptp version 2 message, over ethernet or at the front of a udp payload.
+///
+/// The message is bounded by the message length field, the bytes past it are
+/// not part of the message.
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct PtpPacket<T> {
+    buf: T,
+}
+
+impl<T: Buf> PtpPacket<T> {
+    #[inline]
+    pub fn parse_unchecked(buf: T) -> Self {
+        Self { buf }
+    }
+
+    #[inline]
+    pub fn buf(&self) -> &T {
+        &self.buf
+    }
+
+    #[inline]
+    pub fn release(self) -> T {
+        self.buf
+    }
+
+    /// Parse the ptp message at the front of `buf`.
+    ///
+    /// The parse fails if the version is not 2, or if the message length is
+    /// shorter than the header or longer than `buf`.
+    #[inline]
+    pub fn parse(buf: T) -> Result<PtpPacket<T>, T> {
+        let chunk_len = buf.chunk().len();
+        if chunk_len < PTP_HEADER_LEN {
+            return Err(buf);
+        }
+
+        let packet = PtpPacket { buf };
+        let len = usize::from(packet.message_length());
+        if packet.version() != 2 || len < PTP_HEADER_LEN || len > chunk_len {
+            return Err(packet.release());
+        }
+
+        Ok(packet)
+    }
+
+    #[inline]
+    pub fn message_type(&self) -> PtpMessageType {
+        PtpMessageType::from(self.buf.chunk()[0] & 0x0f)
+    }
+
+    /// Return the major sdo id, formerly the transport specific field.
+    #[inline]
+    pub fn sdo_id(&self) -> u8 {
+        self.buf.chunk()[0] >> 4
+    }
+
+    /// Return the version, which is always 2.
+    #[inline]
+    pub fn version(&self) -> u8 {
+        self.buf.chunk()[1] & 0x0f
+    }
+
+    #[inline]
+    pub fn minor_version(&self) -> u8 {
+        self.buf.chunk()[1] >> 4
+    }
+
+    #[inline]
+    pub fn message_length(&self) -> u16 {
+        NetworkEndian::read_u16(&self.buf.chunk()[2..4])
+    }
+
+    #[inline]
+    pub fn domain_number(&self) -> u8 {
+        self.buf.chunk()[4]
+    }
+
+    #[inline]
+    pub fn flags(&self) -> u16 {
+        NetworkEndian::read_u16(&self.buf.chunk()[6..8])
+    }
+
+    /// Return whether the sync is followed by a follow up that carries its
+    /// precise origin timestamp.
+    #[inline]
+    pub fn two_step(&self) -> bool {
+        self.flags() & FLAG_TWO_STEP != 0
+    }
+
+    /// Return the correction field in nanoseconds multiplied by 2^16.
+    #[inline]
+    pub fn correction(&self) -> i64 {
+        NetworkEndian::read_i64(&self.buf.chunk()[8..16])
+    }
+
+    #[inline]
+    pub fn source_port_identity(&self) -> PtpPortIdentity {
+        PtpPortIdentity::parse(&self.buf.chunk()[20..30]).unwrap()
+    }
+
+    #[inline]
+    pub fn sequence_id(&self) -> u16 {
+        NetworkEndian::read_u16(&self.buf.chunk()[30..32])
+    }
+
+    #[inline]
+    pub fn log_message_interval(&self) -> i8 {
+        self.buf.chunk()[33] as i8
+    }
+
+    /// Return the body between the header and the message length.
+    #[inline]
+    pub fn body(&self) -> &[u8] {
+        &self.buf.chunk()[PTP_HEADER_LEN..usize::from(self.message_length())]
+    }
+
+    /// Return the body, the bytes past the message length are trimmed off.
+    #[inline]
+    pub fn payload(self) -> T
+    where
+        T: PktBuf,
+    {
+        assert!(usize::from(self.message_length()) <= self.buf.remaining());
+        let trim_size = self.buf.remaining() - usize::from(self.message_length());
+
+        let mut buf = self.release();
+        if trim_size > 0 {
+            buf.trim_off(trim_size);
+        }
+        buf.advance(PTP_HEADER_LEN);
+
+        buf
+    }
+
+    /// Return the typed body of a sync, delay request, follow up or delay
+    /// response message.
+    ///
+    /// Return `None` for the other message types, or if the body is truncated
+    /// or carries a nanoseconds field of 10^9 or more.
+    pub fn message(&self) -> Option<PtpMessage> {
+        let payload = self.body();
+        match self.message_type() {
+            PtpMessageType::SYNC => Some(PtpMessage::Sync {
+                origin_timestamp: PtpTimestamp::parse(payload)?,
+            }),
+            PtpMessageType::DELAY_REQ => Some(PtpMessage::DelayReq {
+                origin_timestamp: PtpTimestamp::parse(payload)?,
+            }),
+            PtpMessageType::FOLLOW_UP => Some(PtpMessage::FollowUp {
+                precise_origin_timestamp: PtpTimestamp::parse(payload)?,
+            }),
+            PtpMessageType::DELAY_RESP => Some(PtpMessage::DelayResp {
+                receive_timestamp: PtpTimestamp::parse(payload)?,
+                requesting_port_identity: PtpPortIdentity::parse(payload.get(TIMESTAMP_LEN..)?)?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> PtpPacket<Cursor<'a>> {
+    #[inline]
+    pub fn cursor_body(&self) -> &'a [u8] {
+        &self.buf.chunk_shared_lifetime()[PTP_HEADER_LEN..usize::from(self.message_length())]
+    }
+}
+
+impl<'a> Header<'a> for PtpPacket<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        PtpPacket::parse(Cursor::new(buf)).map_err(|_| ParseError::Ptp)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        PTP_HEADER_LEN
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        self.cursor_body()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A two-step sync and its follow up sent by a ptp4l master in domain 0,
+    // captured in an ethernet frame with the ptp multicast address.
+    static SYNC_FRAME: [u8; 60] = [
+        0x01, 0x1b, 0x19, 0x00, 0x00, 0x00, 0x00, 0x1b, 0x21, 0x6c, 0x2a, 0x10, 0x88, 0xf7, 0x00,
+        0x02, 0x00, 0x2c, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x1b, 0x21, 0xff, 0xfe, 0x6c, 0x2a, 0x10, 0x00, 0x01, 0x12,
+        0x34, 0x00, 0x00, 0x00, 0x00, 0x65, 0x53, 0xf1, 0x00, 0x07, 0x5b, 0xcd, 0x15, 0x00, 0x00,
+    ];
+    static FOLLOW_UP_FRAME: [u8; 60] = [
+        0x01, 0x1b, 0x19, 0x00, 0x00, 0x00, 0x00, 0x1b, 0x21, 0x6c, 0x2a, 0x10, 0x88, 0xf7, 0x08,
+        0x02, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x80, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x1b, 0x21, 0xff, 0xfe, 0x6c, 0x2a, 0x10, 0x00, 0x01, 0x12,
+        0x34, 0x02, 0x00, 0x00, 0x00, 0x65, 0x53, 0xf1, 0x00, 0x07, 0x5b, 0xcd, 0x8a, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn parse_sync_and_follow_up() {
+        let sync = PtpPacket::parse(Cursor::new(&SYNC_FRAME[14..])).unwrap();
+        assert_eq!(sync.message_type(), PtpMessageType::SYNC);
+        assert_eq!((sync.version(), sync.minor_version()), (2, 0));
+        assert_eq!(sync.message_length(), 44);
+        assert_eq!(sync.domain_number(), 0);
+        assert!(sync.two_step());
+        assert_eq!(sync.correction(), 0);
+        let source = PtpPortIdentity {
+            clock_identity: [0x00, 0x1b, 0x21, 0xff, 0xfe, 0x6c, 0x2a, 0x10],
+            port_number: 1,
+        };
+        assert_eq!(sync.source_port_identity(), source);
+        assert_eq!(sync.sequence_id(), 0x1234);
+        assert_eq!(sync.log_message_interval(), 0);
+        // the padding of the ethernet frame is not part of the message
+        assert_eq!(sync.body().len(), 10);
+        let ptppkt = PtpPacket::parse(Cursor::new(&SYNC_FRAME[14..])).unwrap();
+        assert_eq!(ptppkt.payload().chunk(), &SYNC_FRAME[48..58]);
+        let sync_ts = PtpTimestamp {
+            seconds: 1_700_000_000,
+            nanoseconds: 123_456_789,
+        };
+        assert_eq!(
+            sync.message(),
+            Some(PtpMessage::Sync {
+                origin_timestamp: sync_ts
+            })
+        );
+
+        let follow_up = PtpPacket::parse(Cursor::new(&FOLLOW_UP_FRAME[14..])).unwrap();
+        assert_eq!(follow_up.message_type(), PtpMessageType::FOLLOW_UP);
+        assert!(!follow_up.two_step());
+        assert_eq!(follow_up.sequence_id(), sync.sequence_id());
+        assert_eq!(follow_up.source_port_identity(), source);
+        // 1.5ns of residence time
+        assert_eq!(follow_up.correction(), 3 << 15);
+        let precise_ts = match follow_up.message() {
+            Some(PtpMessage::FollowUp {
+                precise_origin_timestamp,
+            }) => precise_origin_timestamp,
+            message => panic!("unexpected message {:?}", message),
+        };
+        assert_eq!(precise_ts.seconds, 1_700_000_000);
+        assert_eq!(precise_ts.nanoseconds, 123_456_906);
+        assert_eq!(
+            precise_ts.to_duration() - sync_ts.to_duration(),
+            Duration::from_nanos(117)
+        );
+
+        // a truncated message, and a message of another version
+        assert!(PtpPacket::parse(Cursor::new(&FOLLOW_UP_FRAME[14..57])).is_err());
+        assert!(PtpPacket::parse(Cursor::new(&FOLLOW_UP_FRAME[14..47])).is_err());
+        let mut invalid = FOLLOW_UP_FRAME;
+        invalid[15] = 0x01;
+        assert!(PtpPacket::parse(Cursor::new(&invalid[14..])).is_err());
+        // an invalid nanoseconds field
+        invalid[15] = 0x02;
+        invalid[54..58].copy_from_slice(&1_000_000_000_u32.to_be_bytes());
+        assert_eq!(
+            PtpPacket::parse(Cursor::new(&invalid[14..]))
+                .unwrap()
+                .message(),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_delay_messages() {
+        let mut delay_req = SYNC_FRAME[14..58].to_vec();
+        delay_req[0] = 0x01;
+        delay_req[6] = 0x00;
+        delay_req[40..44].fill(0);
+        let ptppkt = PtpPacket::parse(Cursor::new(&delay_req[..])).unwrap();
+        let origin_timestamp = PtpTimestamp {
+            seconds: 1_700_000_000,
+            nanoseconds: 0,
+        };
+        assert_eq!(
+            ptppkt.message(),
+            Some(PtpMessage::DelayReq { origin_timestamp })
+        );
+
+        // the delay response carries the identity of the requesting port
+        let mut delay_resp = delay_req.clone();
+        delay_resp[0] = 0x09;
+        delay_resp[2..4].copy_from_slice(&54_u16.to_be_bytes());
+        delay_resp.extend_from_slice(&[0x02, 0x00, 0x00, 0xff, 0xfe, 0x00, 0x00, 0x07, 0x00, 0x02]);
+        let ptppkt = PtpPacket::parse(Cursor::new(&delay_resp[..])).unwrap();
+        assert_eq!(
+            ptppkt.message(),
+            Some(PtpMessage::DelayResp {
+                receive_timestamp: origin_timestamp,
+                requesting_port_identity: PtpPortIdentity {
+                    clock_identity: [0x02, 0x00, 0x00, 0xff, 0xfe, 0x00, 0x00, 0x07],
+                    port_number: 2,
+                },
+            })
+        );
+        // a delay response without the requesting port identity
+        delay_resp[2..4].copy_from_slice(&44_u16.to_be_bytes());
+        assert_eq!(
+            PtpPacket::parse(Cursor::new(&delay_resp[..]))
+                .unwrap()
+                .message(),
+            None
+        );
+
+        // the other message types have no typed body
+        delay_resp[0] = 0x0b;
+        let ptppkt = PtpPacket::parse(Cursor::new(&delay_resp[..])).unwrap();
+        assert_eq!(ptppkt.message_type(), PtpMessageType::ANNOUNCE);
+        assert_eq!(ptppkt.message(), None);
+    }
+}
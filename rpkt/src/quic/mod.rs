@@ -0,0 +1,37 @@
+enum_sim! {
+    /// The quic versions that are recognized by `QuicPacket`, see
+    /// https://www.iana.org/assignments/quic/quic.xhtml#quic-versions
+    pub struct QuicVersion (u32) {
+        V1 = 0x0000_0001,
+        V2 = 0x6b33_43cf,
+        /// The first of the ietf drafts that are deployed, draft-29.
+        DRAFT_29 = 0xff00_001d,
+        /// The last ietf draft, draft-34.
+        DRAFT_34 = 0xff00_0022,
+    }
+}
+
+enum_sim! {
+    /// The type of a long header packet, with the values of quic version 1.
+    ///
+    /// The types of quic version 2 are encoded differently on the wire, they
+    /// are converted to these values by `QuicPacket`.
+    pub struct QuicPacketType (u8) {
+        INITIAL = 0,
+        ZERO_RTT = 1,
+        HANDSHAKE = 2,
+        RETRY = 3,
+    }
+}
+
+/// The udp port of quic, which is shared with https over tcp.
+pub const QUIC_PORT: u16 = 443;
+
+/// The maximum length of a connection id in quic version 1 and 2.
+pub const QUIC_MAX_CID_LEN: usize = 20;
+
+/// The minimum length of the udp payload that carries a client initial packet.
+pub const QUIC_MIN_INITIAL_LEN: usize = 1200;
+
+mod packet;
+pub use packet::QuicPacket;
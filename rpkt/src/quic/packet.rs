@@ -0,0 +1,414 @@
+use std::ops::Range;
+
+use byteorder::{ByteOrder, NetworkEndian};
+use bytes::Buf;
+
+use crate::{Cursor, Header, ParseError, PktBuf};
+
+use super::{QuicPacketType, QuicVersion, QUIC_MAX_CID_LEN};
+
+// The length of the integrity tag of a retry packet.
+const RETRY_TAG_LEN: usize = 16;
+
+// The minimum length of a protected packet past the length field: the 1-byte
+// packet number is sampled for the header protection from 4 bytes past the
+// start of the packet number, and the sample is 16 bytes long.
+const MIN_PROTECTED_LEN: usize = 20;
+
+/// A long header quic packet at the front of a udp payload.
+///
+/// Only the header fields that are sent in the clear are parsed, i.e. the
+/// version, the connection ids and, for the initial packets, the token. The
+/// packet number and the payload are protected and left as an opaque blob.
+///
+/// The short header packets carry no version and no connection id length, and
+/// the version negotiation packets have no fixed bit, so neither of them can
+/// be told apart from arbitrary udp payloads and they are not recognized.
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct QuicPacket<T> {
+    buf: T,
+}
+
+impl<T: Buf> QuicPacket<T> {
+    #[inline]
+    pub fn parse_unchecked(buf: T) -> Self {
+        Self { buf }
+    }
+
+    #[inline]
+    pub fn buf(&self) -> &T {
+        &self.buf
+    }
+
+    #[inline]
+    pub fn release(self) -> T {
+        self.buf
+    }
+
+    /// Parse the long header packet at the front of the udp payload `buf`.
+    ///
+    /// The payload is recognized as quic only if the header form and the
+    /// fixed bits are set, the version is in `QuicVersion`, the connection ids
+    /// are at most 20 bytes long, and the length fields of the packet fit in
+    /// `buf`. A udp payload starting with random bytes passes these checks
+    /// with a negligible probability.
+    #[inline]
+    pub fn parse(buf: T) -> Result<QuicPacket<T>, T> {
+        if layout(buf.chunk()).is_none() {
+            return Err(buf);
+        }
+        Ok(QuicPacket { buf })
+    }
+
+    #[inline]
+    pub fn version(&self) -> QuicVersion {
+        NetworkEndian::read_u32(&self.buf.chunk()[1..5]).into()
+    }
+
+    #[inline]
+    pub fn packet_type(&self) -> QuicPacketType {
+        packet_type(self.buf.chunk()[0], self.version()).unwrap()
+    }
+
+    #[inline]
+    pub fn is_initial(&self) -> bool {
+        self.packet_type() == QuicPacketType::INITIAL
+    }
+
+    /// Return the destination connection id, which is chosen by the receiver
+    /// of the packet, except for the first initial packets of the client.
+    #[inline]
+    pub fn dcid(&self) -> &[u8] {
+        &self.buf.chunk()[self.layout().dcid]
+    }
+
+    /// Return the source connection id, which is chosen by the sender.
+    #[inline]
+    pub fn scid(&self) -> &[u8] {
+        &self.buf.chunk()[self.layout().scid]
+    }
+
+    /// Return the token of an initial or a retry packet, which is empty for
+    /// the other packet types.
+    #[inline]
+    pub fn token(&self) -> &[u8] {
+        &self.buf.chunk()[self.layout().token]
+    }
+
+    /// Return the length of the clear header, the protected packet number and
+    /// payload, or the integrity tag of a retry packet, start at this offset.
+    #[inline]
+    pub fn header_len(&self) -> usize {
+        self.layout().payload.start
+    }
+
+    /// Return the length of the packet, the following packets coalesced in
+    /// the same udp payload, if any, start at this offset.
+    #[inline]
+    pub fn packet_len(&self) -> usize {
+        self.layout().payload.end
+    }
+
+    /// Return the protected packet number and payload, or the integrity tag of
+    /// a retry packet.
+    #[inline]
+    pub fn protected_bytes(&self) -> &[u8] {
+        &self.buf.chunk()[self.layout().payload]
+    }
+
+    /// Return the protected bytes of the packet, the coalesced packets after
+    /// it are trimmed off.
+    #[inline]
+    pub fn payload(self) -> T
+    where
+        T: PktBuf,
+    {
+        let layout = self.layout();
+        let trim_size = self.buf.remaining() - layout.payload.end;
+
+        let mut buf = self.release();
+        if trim_size > 0 {
+            buf.trim_off(trim_size);
+        }
+        buf.advance(layout.payload.start);
+
+        buf
+    }
+
+    // The packet is checked by `parse`.
+    #[inline]
+    fn layout(&self) -> Layout {
+        layout(self.buf.chunk()).unwrap()
+    }
+}
+
+impl<'a> QuicPacket<Cursor<'a>> {
+    #[inline]
+    pub fn cursor_dcid(&self) -> &'a [u8] {
+        &self.buf.chunk_shared_lifetime()[self.layout().dcid]
+    }
+
+    #[inline]
+    pub fn cursor_scid(&self) -> &'a [u8] {
+        &self.buf.chunk_shared_lifetime()[self.layout().scid]
+    }
+
+    #[inline]
+    pub fn cursor_protected_bytes(&self) -> &'a [u8] {
+        &self.buf.chunk_shared_lifetime()[self.layout().payload]
+    }
+}
+
+impl<'a> Header<'a> for QuicPacket<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        QuicPacket::parse(Cursor::new(buf)).map_err(|_| ParseError::Quic)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        self.header_len()
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        self.cursor_protected_bytes()
+    }
+}
+
+// The offsets of the variable-length fields of a packet.
+struct Layout {
+    dcid: Range<usize>,
+    scid: Range<usize>,
+    token: Range<usize>,
+    payload: Range<usize>,
+}
+
+#[inline]
+fn packet_type(first: u8, version: QuicVersion) -> Option<QuicPacketType> {
+    if first & 0xc0 != 0xc0 {
+        return None;
+    }
+    match version {
+        QuicVersion::V1 => Some(QuicPacketType::from((first >> 4) & 0x03)),
+        // the types of version 2 are rotated by one
+        QuicVersion::V2 => Some(QuicPacketType::from(((first >> 4) + 3) & 0x03)),
+        version if version >= QuicVersion::DRAFT_29 && version <= QuicVersion::DRAFT_34 => {
+            Some(QuicPacketType::from((first >> 4) & 0x03))
+        }
+        _ => None,
+    }
+}
+
+// Check the header of the packet at the front of `buf` and locate its fields.
+fn layout(buf: &[u8]) -> Option<Layout> {
+    let mut cursor = buf;
+    let first = *take(&mut cursor, 1)?.first()?;
+    let version = QuicVersion::from(NetworkEndian::read_u32(take(&mut cursor, 4)?));
+    let packet_type = packet_type(first, version)?;
+
+    let offset = |cursor: &[u8]| buf.len() - cursor.len();
+    let dcid = take_cid(&mut cursor, offset)?;
+    let scid = take_cid(&mut cursor, offset)?;
+    let mut token = offset(cursor)..offset(cursor);
+    let payload_len = if packet_type == QuicPacketType::RETRY {
+        // the retry token fills the packet up to the integrity tag
+        let token_len = cursor.len().checked_sub(RETRY_TAG_LEN)?;
+        token = offset(cursor)..offset(cursor) + token_len;
+        take(&mut cursor, token_len)?;
+        RETRY_TAG_LEN
+    } else {
+        if packet_type == QuicPacketType::INITIAL {
+            let token_len = usize::try_from(take_varint(&mut cursor)?).ok()?;
+            let start = offset(cursor);
+            take(&mut cursor, token_len)?;
+            token = start..start + token_len;
+        }
+        let len = usize::try_from(take_varint(&mut cursor)?).ok()?;
+        if len < MIN_PROTECTED_LEN {
+            return None;
+        }
+        len
+    };
+    let start = offset(cursor);
+    take(&mut cursor, payload_len)?;
+
+    Some(Layout {
+        dcid,
+        scid,
+        token,
+        payload: start..start + payload_len,
+    })
+}
+
+// Split the first `len` bytes from `buf`.
+#[inline]
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if buf.len() < len {
+        return None;
+    }
+    let (head, tail) = buf.split_at(len);
+    *buf = tail;
+    Some(head)
+}
+
+// Split a connection id and its length, and return its range in the packet.
+#[inline]
+fn take_cid(buf: &mut &[u8], offset: impl Fn(&[u8]) -> usize) -> Option<Range<usize>> {
+    let len = usize::from(*take(buf, 1)?.first()?);
+    if len > QUIC_MAX_CID_LEN {
+        return None;
+    }
+    let start = offset(buf);
+    take(buf, len)?;
+    Some(start..start + len)
+}
+
+// Split a variable-length integer, whose length is encoded in the two most
+// significant bits of the first byte.
+#[inline]
+fn take_varint(buf: &mut &[u8]) -> Option<u64> {
+    let len = 1 << (buf.first()? >> 6);
+    let bytes = take(buf, len)?;
+    let value = bytes[1..]
+        .iter()
+        .fold(u64::from(bytes[0] & 0x3f), |value, byte| {
+            (value << 8) | u64::from(*byte)
+        });
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quic::QUIC_MIN_INITIAL_LEN;
+
+    // The server initial packet of the example handshake in RFC 9001, appendix
+    // A.3, with the header protection applied.
+    static SERVER_INITIAL_BYTES: [u8; 135] = [
+        0xcf, 0x00, 0x00, 0x00, 0x01, 0x00, 0x08, 0xf0, 0x67, 0xa5, 0x50, 0x2a, 0x42, 0x62, 0xb5,
+        0x00, 0x40, 0x75, 0xc0, 0xd9, 0x5a, 0x48, 0x2c, 0xd0, 0x99, 0x1c, 0xd2, 0x5b, 0x0a, 0xac,
+        0x40, 0x6a, 0x58, 0x16, 0xb6, 0x39, 0x41, 0x00, 0xf3, 0x7a, 0x1c, 0x69, 0x79, 0x75, 0x54,
+        0x78, 0x0b, 0xb3, 0x8c, 0xc5, 0xa9, 0x9f, 0x5e, 0xde, 0x4c, 0xf7, 0x3c, 0x3e, 0xc2, 0x49,
+        0x3a, 0x18, 0x39, 0xb3, 0xdb, 0xcb, 0xa3, 0xf6, 0xea, 0x46, 0xc5, 0xb7, 0x68, 0x4d, 0xf3,
+        0x54, 0x8e, 0x7d, 0xde, 0xb9, 0xc3, 0xbf, 0x9c, 0x73, 0xcc, 0x3f, 0x3b, 0xde, 0xd7, 0x4b,
+        0x56, 0x2b, 0xfb, 0x19, 0xfb, 0x84, 0x02, 0x2f, 0x8e, 0xf4, 0xcd, 0xd9, 0x37, 0x95, 0xd7,
+        0x7d, 0x06, 0xed, 0xbb, 0x7a, 0xaf, 0x2f, 0x58, 0x89, 0x18, 0x50, 0xab, 0xbd, 0xca, 0x3d,
+        0x20, 0x39, 0x8c, 0x27, 0x64, 0x56, 0xcb, 0xc4, 0x21, 0x58, 0x40, 0x7d, 0xd0, 0x74, 0xee,
+    ];
+
+    // The retry packet of RFC 9001, appendix A.4.
+    static RETRY_BYTES: [u8; 36] = [
+        0xff, 0x00, 0x00, 0x00, 0x01, 0x00, 0x08, 0xf0, 0x67, 0xa5, 0x50, 0x2a, 0x42, 0x62, 0xb5,
+        0x74, 0x6f, 0x6b, 0x65, 0x6e, 0x04, 0xa2, 0x65, 0xba, 0x2e, 0xff, 0x4d, 0x82, 0x90, 0x58,
+        0xfb, 0x3f, 0x0f, 0x24, 0x96, 0xba,
+    ];
+
+    // The header of the client initial packet of RFC 9001, appendix A.2, the
+    // protected payload is padded with zeros up to `QUIC_MIN_INITIAL_LEN`.
+    fn client_initial() -> Vec<u8> {
+        let mut buf = vec![
+            0xc0, 0x00, 0x00, 0x00, 0x01, 0x08, 0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08,
+            0x00, 0x00, 0x44, 0x9e,
+        ];
+        buf.resize(QUIC_MIN_INITIAL_LEN, 0);
+        buf
+    }
+
+    #[test]
+    fn parse_initial_packets() {
+        let header = QuicPacket::parse(Cursor::new(&SERVER_INITIAL_BYTES[..])).unwrap();
+        assert_eq!(header.version(), QuicVersion::V1);
+        assert!(header.is_initial());
+        assert_eq!(header.dcid(), &[] as &[u8]);
+        assert_eq!(
+            header.scid(),
+            &[0xf0, 0x67, 0xa5, 0x50, 0x2a, 0x42, 0x62, 0xb5]
+        );
+        assert!(header.token().is_empty());
+        assert_eq!(header.protected_bytes().len(), 0x75);
+        assert_eq!(header.packet_len(), 135);
+
+        let buf = client_initial();
+        let header = QuicPacket::parse(Cursor::new(&buf[..])).unwrap();
+        assert!(header.is_initial());
+        assert_eq!(
+            header.dcid(),
+            &[0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08]
+        );
+        assert!(header.scid().is_empty());
+        assert_eq!(header.packet_len(), QUIC_MIN_INITIAL_LEN);
+
+        // a handshake packet coalesced after the initial packet
+        let mut coalesced = SERVER_INITIAL_BYTES.to_vec();
+        coalesced.extend_from_slice(&[0xe0, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x14]);
+        coalesced.resize(135 + 8 + 20, 0);
+        let header = QuicPacket::parse(Cursor::new(&coalesced[..])).unwrap();
+        assert_eq!(header.packet_len(), 135);
+        assert_eq!(header.header_len(), 18);
+        // the following packets are trimmed off the payload
+        assert_eq!(header.payload().chunk(), &SERVER_INITIAL_BYTES[18..]);
+        let mut buf = &coalesced[..];
+        let header = QuicPacket::parse_next(&mut buf).unwrap();
+        assert_eq!(Header::header_len(&header), 18);
+        assert_eq!(buf.len(), 0x75);
+        let next = QuicPacket::parse(Cursor::new(&coalesced[header.packet_len()..])).unwrap();
+        assert_eq!(next.packet_type(), QuicPacketType::HANDSHAKE);
+        assert!(next.token().is_empty());
+
+        // the initial type of quic version 2
+        let mut v2 = SERVER_INITIAL_BYTES;
+        v2[0] = 0xdf;
+        v2[1..5].copy_from_slice(&[0x6b, 0x33, 0x43, 0xcf]);
+        let header = QuicPacket::parse(Cursor::new(&v2[..])).unwrap();
+        assert_eq!(header.version(), QuicVersion::V2);
+        assert!(header.is_initial());
+    }
+
+    #[test]
+    fn parse_retry_packet() {
+        let header = QuicPacket::parse(Cursor::new(&RETRY_BYTES[..])).unwrap();
+        assert_eq!(header.packet_type(), QuicPacketType::RETRY);
+        assert_eq!(header.token(), b"token");
+        assert_eq!(header.protected_bytes().len(), 16);
+        assert_eq!(header.packet_len(), 36);
+    }
+
+    #[test]
+    fn reject_non_quic_payloads() {
+        // the short header form, or no fixed bit
+        for first in [0x40, 0x80] {
+            let mut buf = SERVER_INITIAL_BYTES;
+            buf[0] = first;
+            assert!(QuicPacket::parse(Cursor::new(&buf[..])).is_err());
+        }
+        // a version negotiation packet, or an unknown version
+        for version in [[0, 0, 0, 0], [0x0a, 0x1a, 0x2a, 0x3a]] {
+            let mut buf = SERVER_INITIAL_BYTES;
+            buf[1..5].copy_from_slice(&version);
+            assert!(QuicPacket::parse(Cursor::new(&buf[..])).is_err());
+        }
+        // a connection id longer than 20 bytes
+        let mut buf = SERVER_INITIAL_BYTES;
+        buf[5] = 21;
+        assert!(QuicPacket::parse(Cursor::new(&buf[..])).is_err());
+        // the length of the packet exceeds the payload, or is too short
+        let mut buf = SERVER_INITIAL_BYTES;
+        buf[17] = 0x76;
+        assert!(QuicPacket::parse(Cursor::new(&buf[..])).is_err());
+        buf[16..18].copy_from_slice(&[0x40, 0x13]);
+        assert!(QuicPacket::parse(Cursor::new(&buf[..])).is_err());
+        // the truncated packets
+        for len in 0..SERVER_INITIAL_BYTES.len() {
+            assert!(QuicPacket::parse(Cursor::new(&SERVER_INITIAL_BYTES[..len])).is_err());
+        }
+        assert!(QuicPacket::parse(Cursor::new(&RETRY_BYTES[..15])).is_err());
+        // a dns query to port 443
+        let dns = [
+            0xc0, 0x01, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x77,
+            0x77, 0x77, 0x00, 0x00, 0x01, 0x00, 0x01,
+        ];
+        assert!(QuicPacket::parse(Cursor::new(&dns[..])).is_err());
+    }
+}
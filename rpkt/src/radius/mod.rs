@@ -0,0 +1,28 @@
+enum_sim! {
+    /// See https://www.iana.org/assignments/radius-types/radius-types.xhtml#radius-types-27
+    pub struct RadiusCode (u8) {
+        ACCESS_REQUEST = 1,
+        ACCESS_ACCEPT = 2,
+        ACCESS_REJECT = 3,
+        ACCOUNTING_REQUEST = 4,
+        ACCOUNTING_RESPONSE = 5,
+        ACCESS_CHALLENGE = 11,
+        STATUS_SERVER = 12,
+        STATUS_CLIENT = 13,
+    }
+}
+
+/// The length of the radius header, which precedes the attributes.
+pub const RADIUS_HEADER_LEN: usize = 20;
+
+/// The maximum length of a radius packet.
+pub const RADIUS_MAX_LEN: usize = 4096;
+
+/// The udp port assigned to the radius authentication.
+pub const RADIUS_AUTH_UDP_PORT: u16 = 1812;
+
+/// The udp port assigned to the radius accounting.
+pub const RADIUS_ACCT_UDP_PORT: u16 = 1813;
+
+mod packet;
+pub use packet::{RadiusAttr, RadiusAttrIter, RadiusPacket};
@@ -0,0 +1,258 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use bytes::Buf;
+
+use crate::{Cursor, Header, ParseError};
+
+use super::{RadiusCode, RADIUS_HEADER_LEN, RADIUS_MAX_LEN};
+
+/// A radius packet, the payload of a udp datagram on the radius ports.
+///
+/// The fixed header is followed by a list of attributes up to the length of the
+/// packet, the bytes after the length are padding.
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct RadiusPacket<T> {
+    buf: T,
+}
+
+impl<T: Buf> RadiusPacket<T> {
+    #[inline]
+    pub fn parse_unchecked(buf: T) -> Self {
+        Self { buf }
+    }
+
+    #[inline]
+    pub fn buf(&self) -> &T {
+        &self.buf
+    }
+
+    #[inline]
+    pub fn release(self) -> T {
+        self.buf
+    }
+
+    /// Parse a radius packet from `buf`, the length of the packet must fit in
+    /// `buf`.
+    ///
+    /// The attributes are checked by `check_attrs`.
+    #[inline]
+    pub fn parse(buf: T) -> Result<RadiusPacket<T>, T> {
+        let chunk_len = buf.chunk().len();
+        if chunk_len < RADIUS_HEADER_LEN {
+            return Err(buf);
+        }
+
+        let packet = RadiusPacket { buf };
+        let packet_len = usize::from(packet.packet_len());
+        if !(RADIUS_HEADER_LEN..=RADIUS_MAX_LEN).contains(&packet_len) || packet_len > chunk_len {
+            return Err(packet.release());
+        }
+
+        Ok(packet)
+    }
+
+    #[inline]
+    pub fn code(&self) -> RadiusCode {
+        self.buf.chunk()[0].into()
+    }
+
+    /// Return the identifier that matches a response to its request.
+    #[inline]
+    pub fn identifier(&self) -> u8 {
+        self.buf.chunk()[1]
+    }
+
+    #[inline]
+    pub fn packet_len(&self) -> u16 {
+        NetworkEndian::read_u16(&self.buf.chunk()[2..4])
+    }
+
+    #[inline]
+    pub fn authenticator(&self) -> &[u8] {
+        &self.buf.chunk()[4..RADIUS_HEADER_LEN]
+    }
+
+    #[inline]
+    pub fn attr_bytes(&self) -> &[u8] {
+        &self.buf.chunk()[RADIUS_HEADER_LEN..usize::from(self.packet_len())]
+    }
+
+    #[inline]
+    pub fn attrs(&self) -> RadiusAttrIter<'_> {
+        RadiusAttrIter::from_attr_bytes(self.attr_bytes())
+    }
+
+    /// Check that the attributes fill the packet exactly.
+    #[inline]
+    pub fn check_attrs(&self) -> bool {
+        RadiusAttrIter::check_attr_bytes(self.attr_bytes())
+    }
+}
+
+impl<'a> RadiusPacket<Cursor<'a>> {
+    #[inline]
+    pub fn cursor_attr_bytes(&self) -> &'a [u8] {
+        &self.buf.chunk_shared_lifetime()[RADIUS_HEADER_LEN..usize::from(self.packet_len())]
+    }
+}
+
+impl<'a> Header<'a> for RadiusPacket<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        RadiusPacket::parse(Cursor::new(buf)).map_err(|_| ParseError::Radius)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        RADIUS_HEADER_LEN
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        self.cursor_attr_bytes()
+    }
+}
+
+/// An attribute of a radius packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RadiusAttr<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> RadiusAttr<'a> {
+    /// See https://www.iana.org/assignments/radius-types/radius-types.xhtml#radius-types-2
+    #[inline]
+    pub fn attr_type(&self) -> u8 {
+        self.buf[0]
+    }
+
+    /// The length of the attribute, including the 2-byte type and length.
+    #[inline]
+    pub fn attr_len(&self) -> u8 {
+        self.buf[1]
+    }
+
+    #[inline]
+    pub fn value(&self) -> &'a [u8] {
+        &self.buf[2..]
+    }
+}
+
+pub struct RadiusAttrIter<'a> {
+    buf: &'a [u8],
+    valid: bool,
+}
+
+impl<'a> RadiusAttrIter<'a> {
+    #[inline]
+    pub fn from_attr_bytes(buf: &'a [u8]) -> RadiusAttrIter<'a> {
+        Self { buf, valid: true }
+    }
+
+    #[inline]
+    pub fn check_attr_bytes(buf: &'a [u8]) -> bool {
+        let mut reader = Self::from_attr_bytes(buf);
+        for _ in &mut reader {}
+        reader.valid
+    }
+}
+
+impl<'a> Iterator for RadiusAttrIter<'a> {
+    type Item = RadiusAttr<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.valid || self.buf.is_empty() {
+            return None;
+        }
+
+        // the length covers the 2-byte attribute header, so the iterator
+        // always advances
+        if self.buf.len() < 2 {
+            self.valid = false;
+            return None;
+        }
+        let attr_len = usize::from(self.buf[1]);
+        if attr_len < 2 || self.buf.len() < attr_len {
+            self.valid = false;
+            return None;
+        }
+
+        let attr = RadiusAttr {
+            buf: &self.buf[..attr_len],
+        };
+        self.buf = &self.buf[attr_len..];
+        Some(attr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The access-request of RFC 2865 section 7.1, for the user "nemo" on port 3
+    // of the nas 192.168.1.16.
+    static ACCESS_REQUEST_BYTES: [u8; 56] = [
+        0x01, 0x00, 0x00, 0x38, 0x0f, 0x40, 0x3f, 0x94, 0x73, 0x97, 0x80, 0x57, 0xbd, 0x83, 0xd5,
+        0xcb, 0x98, 0xf4, 0x22, 0x7a, 0x01, 0x06, 0x6e, 0x65, 0x6d, 0x6f, 0x02, 0x12, 0x0d, 0xbe,
+        0x70, 0x8d, 0x93, 0xd4, 0x13, 0xce, 0x31, 0x96, 0xe4, 0x3f, 0x78, 0x2a, 0x0a, 0xee, 0x04,
+        0x06, 0xc0, 0xa8, 0x01, 0x10, 0x05, 0x06, 0x00, 0x00, 0x00, 0x03,
+    ];
+
+    #[test]
+    fn parse_access_request() {
+        let pkt = RadiusPacket::parse(Cursor::new(&ACCESS_REQUEST_BYTES[..])).unwrap();
+        assert_eq!(pkt.code(), RadiusCode::ACCESS_REQUEST);
+        assert_eq!(pkt.identifier(), 0);
+        assert_eq!(pkt.packet_len(), 56);
+        assert_eq!(&pkt.authenticator()[..4], &[0x0f, 0x40, 0x3f, 0x94][..]);
+        assert!(pkt.check_attrs());
+
+        let attrs: Vec<_> = pkt
+            .attrs()
+            .map(|attr| (attr.attr_type(), attr.value()))
+            .collect();
+        assert_eq!(attrs.len(), 4);
+        assert_eq!(attrs[0], (1, &b"nemo"[..]));
+        assert_eq!(attrs[1].0, 2);
+        assert_eq!(attrs[1].1.len(), 16);
+        assert_eq!(attrs[2], (4, &[192, 168, 1, 16][..]));
+        assert_eq!(attrs[3], (5, &[0, 0, 0, 3][..]));
+
+        // the padding after the length is not parsed
+        let mut padded = ACCESS_REQUEST_BYTES.to_vec();
+        padded.extend_from_slice(&[0xff; 3]);
+        let pkt = RadiusPacket::parse(Cursor::new(&padded[..])).unwrap();
+        assert!(pkt.check_attrs());
+        assert_eq!(pkt.attrs().count(), 4);
+
+        // the length exceeds the packet
+        assert!(RadiusPacket::parse(Cursor::new(&ACCESS_REQUEST_BYTES[..55])).is_err());
+        let mut bytes = ACCESS_REQUEST_BYTES;
+        bytes[3] = 19;
+        assert!(RadiusPacket::parse(Cursor::new(&bytes[..])).is_err());
+    }
+
+    #[test]
+    fn malformed_attrs() {
+        // a zero-length attribute would not advance the iterator
+        let mut bytes = ACCESS_REQUEST_BYTES;
+        bytes[21] = 0;
+        let pkt = RadiusPacket::parse(Cursor::new(&bytes[..])).unwrap();
+        assert!(!pkt.check_attrs());
+        assert_eq!(pkt.attrs().count(), 0);
+
+        // the last attribute exceeds the length of the packet
+        let mut bytes = ACCESS_REQUEST_BYTES;
+        bytes[51] = 7;
+        let pkt = RadiusPacket::parse(Cursor::new(&bytes[..])).unwrap();
+        assert!(!pkt.check_attrs());
+        assert_eq!(pkt.attrs().count(), 3);
+
+        // a dangling byte after the last attribute
+        let mut bytes = ACCESS_REQUEST_BYTES.to_vec();
+        bytes[3] = 57;
+        bytes.push(0x01);
+        let pkt = RadiusPacket::parse(Cursor::new(&bytes[..])).unwrap();
+        assert!(!pkt.check_attrs());
+    }
+}
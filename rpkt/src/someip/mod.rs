@@ -0,0 +1,81 @@
+enum_sim! {
+    /// The message types of SOME/IP, the types with the 0x20 bit set carry the
+    /// segments of a message split by SOME/IP-TP.
+    pub struct SomeIpMessageType (u8) {
+        REQUEST = 0x00,
+        REQUEST_NO_RETURN = 0x01,
+        NOTIFICATION = 0x02,
+        RESPONSE = 0x80,
+        ERROR = 0x81,
+        TP_REQUEST = 0x20,
+        TP_REQUEST_NO_RETURN = 0x21,
+        TP_NOTIFICATION = 0x22,
+        TP_RESPONSE = 0xa0,
+        TP_ERROR = 0xa1,
+    }
+}
+
+enum_sim! {
+    /// The return codes of SOME/IP, the codes from 0x20 are defined by the
+    /// services.
+    pub struct SomeIpReturnCode (u8) {
+        E_OK = 0x00,
+        E_NOT_OK = 0x01,
+        E_UNKNOWN_SERVICE = 0x02,
+        E_UNKNOWN_METHOD = 0x03,
+        E_NOT_READY = 0x04,
+        E_NOT_REACHABLE = 0x05,
+        E_TIMEOUT = 0x06,
+        E_WRONG_PROTOCOL_VERSION = 0x07,
+        E_WRONG_INTERFACE_VERSION = 0x08,
+        E_MALFORMED_MESSAGE = 0x09,
+        E_WRONG_MESSAGE_TYPE = 0x0a,
+    }
+}
+
+enum_sim! {
+    /// The types of the service discovery entries. A stop offer and a stop
+    /// subscribe are an offer and a subscribe with a ttl of 0, and a nack is a
+    /// subscribe ack with a ttl of 0.
+    pub struct SdEntryType (u8) {
+        FIND_SERVICE = 0x00,
+        OFFER_SERVICE = 0x01,
+        SUBSCRIBE_EVENTGROUP = 0x06,
+        SUBSCRIBE_EVENTGROUP_ACK = 0x07,
+    }
+}
+
+enum_sim! {
+    /// The types of the service discovery options.
+    pub struct SdOptionType (u8) {
+        CONFIGURATION = 0x01,
+        LOAD_BALANCING = 0x02,
+        IPV4_ENDPOINT = 0x04,
+        IPV6_ENDPOINT = 0x06,
+        IPV4_MULTICAST = 0x14,
+        IPV6_MULTICAST = 0x16,
+        IPV4_SD_ENDPOINT = 0x24,
+        IPV6_SD_ENDPOINT = 0x26,
+    }
+}
+
+/// The length of the SOME/IP header, which precedes the payload.
+pub const SOMEIP_HEADER_LEN: usize = 16;
+
+/// The protocol version of SOME/IP, which is the only supported one.
+pub const SOMEIP_PROTOCOL_VERSION: u8 = 1;
+
+/// The udp and tcp port of the service discovery.
+pub const SOMEIP_SD_PORT: u16 = 30490;
+
+/// The service id of the service discovery messages.
+pub const SOMEIP_SD_SERVICE_ID: u16 = 0xffff;
+
+/// The method id of the service discovery messages.
+pub const SOMEIP_SD_METHOD_ID: u16 = 0x8100;
+
+/// The length of a service discovery entry.
+pub const SD_ENTRY_LEN: usize = 16;
+
+mod packet;
+pub use packet::{SdEntry, SdEntryIter, SdOption, SdOptionIter, SomeIpPacket, SomeIpSdMessage};
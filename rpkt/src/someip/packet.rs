@@ -0,0 +1,744 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use bytes::Buf;
+
+use super::{
+    SdEntryType, SdOptionType, SomeIpMessageType, SomeIpReturnCode, SD_ENTRY_LEN,
+    SOMEIP_HEADER_LEN, SOMEIP_PROTOCOL_VERSION, SOMEIP_SD_METHOD_ID, SOMEIP_SD_SERVICE_ID,
+};
+use crate::ipv4::{IpProtocol, Ipv4Addr};
+use crate::ipv6::Ipv6Addr;
+use crate::untrusted::{ParsedPacket, Transport};
+use crate::{Cursor, Header, ParseError, PktBuf, StreamError};
+
+/// A SOME/IP message at the front of a udp or tcp payload.
+///
+/// A payload may carry several messages back to back, the next message, if
+/// any, starts at `message_len` of the payload.
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct SomeIpPacket<T> {
+    buf: T,
+}
+
+impl<T: Buf> SomeIpPacket<T> {
+    #[inline]
+    pub fn parse_unchecked(buf: T) -> Self {
+        Self { buf }
+    }
+
+    #[inline]
+    pub fn buf(&self) -> &T {
+        &self.buf
+    }
+
+    #[inline]
+    pub fn release(self) -> T {
+        self.buf
+    }
+
+    /// Parse the message at the front of `buf`, the message must be complete,
+    /// see `check_message`.
+    #[inline]
+    pub fn parse(buf: T) -> Result<SomeIpPacket<T>, T> {
+        if Self::check_message(buf.chunk()).is_err() {
+            return Err(buf);
+        }
+        Ok(SomeIpPacket { buf })
+    }
+
+    /// Check the message at the front of `buf` and return its length.
+    ///
+    /// Return `NeedMoreData` if the message is not complete, so that the
+    /// caller can retry after appending the next tcp segment. The protocol
+    /// version is the only field with a fixed value, the payloads with another
+    /// version are rejected as malformed.
+    #[inline]
+    pub fn check_message(buf: &[u8]) -> Result<usize, StreamError> {
+        if buf
+            .get(12)
+            .is_some_and(|version| *version != SOMEIP_PROTOCOL_VERSION)
+        {
+            return Err(StreamError::Malformed);
+        }
+        if buf.len() < SOMEIP_HEADER_LEN {
+            return Err(StreamError::NeedMoreData(Some(SOMEIP_HEADER_LEN)));
+        }
+
+        // the length counts the bytes from the request id to the end
+        let len = NetworkEndian::read_u32(&buf[4..8]) as usize;
+        if len < SOMEIP_HEADER_LEN - 8 {
+            return Err(StreamError::Malformed);
+        }
+        let message_len = len.checked_add(8).ok_or(StreamError::Malformed)?;
+        if message_len > buf.len() {
+            return Err(StreamError::NeedMoreData(Some(message_len)));
+        }
+        Ok(message_len)
+    }
+
+    #[inline]
+    pub fn message_id(&self) -> u32 {
+        NetworkEndian::read_u32(&self.buf.chunk()[0..4])
+    }
+
+    #[inline]
+    pub fn service_id(&self) -> u16 {
+        NetworkEndian::read_u16(&self.buf.chunk()[0..2])
+    }
+
+    /// Return the method id, or the event id if `is_event` is true.
+    #[inline]
+    pub fn method_id(&self) -> u16 {
+        NetworkEndian::read_u16(&self.buf.chunk()[2..4])
+    }
+
+    /// Return whether the method id is an event id, whose highest bit is set.
+    #[inline]
+    pub fn is_event(&self) -> bool {
+        self.buf.chunk()[2] & 0x80 != 0
+    }
+
+    /// Return the length field, which counts the bytes from the request id to
+    /// the end of the message.
+    #[inline]
+    pub fn length(&self) -> u32 {
+        NetworkEndian::read_u32(&self.buf.chunk()[4..8])
+    }
+
+    #[inline]
+    pub fn request_id(&self) -> u32 {
+        NetworkEndian::read_u32(&self.buf.chunk()[8..12])
+    }
+
+    #[inline]
+    pub fn client_id(&self) -> u16 {
+        NetworkEndian::read_u16(&self.buf.chunk()[8..10])
+    }
+
+    #[inline]
+    pub fn session_id(&self) -> u16 {
+        NetworkEndian::read_u16(&self.buf.chunk()[10..12])
+    }
+
+    #[inline]
+    pub fn protocol_version(&self) -> u8 {
+        self.buf.chunk()[12]
+    }
+
+    #[inline]
+    pub fn interface_version(&self) -> u8 {
+        self.buf.chunk()[13]
+    }
+
+    #[inline]
+    pub fn message_type(&self) -> SomeIpMessageType {
+        self.buf.chunk()[14].into()
+    }
+
+    #[inline]
+    pub fn return_code(&self) -> SomeIpReturnCode {
+        self.buf.chunk()[15].into()
+    }
+
+    /// Return the length of the message, including the header.
+    #[inline]
+    pub fn message_len(&self) -> usize {
+        self.length() as usize + 8
+    }
+
+    /// Return the payload of the message, the following messages excluded.
+    #[inline]
+    pub fn body(&self) -> &[u8] {
+        &self.buf.chunk()[SOMEIP_HEADER_LEN..self.message_len()]
+    }
+
+    /// Return whether the message is a service discovery message.
+    #[inline]
+    pub fn is_sd(&self) -> bool {
+        self.service_id() == SOMEIP_SD_SERVICE_ID && self.method_id() == SOMEIP_SD_METHOD_ID
+    }
+
+    /// Parse the payload of a service discovery message.
+    pub fn sd(&self) -> Result<SomeIpSdMessage<Cursor<'_>>, StreamError> {
+        if !self.is_sd() {
+            return Err(StreamError::Malformed);
+        }
+        let body = self.body();
+        SomeIpSdMessage::<Cursor>::check_message(body)?;
+        Ok(SomeIpSdMessage::parse_unchecked(Cursor::new(body)))
+    }
+
+    /// Return the payload, the following messages are trimmed off.
+    #[inline]
+    pub fn payload(self) -> T
+    where
+        T: PktBuf,
+    {
+        let trim_size = self.buf.remaining() - self.message_len();
+
+        let mut buf = self.release();
+        if trim_size > 0 {
+            buf.trim_off(trim_size);
+        }
+        buf.advance(SOMEIP_HEADER_LEN);
+
+        buf
+    }
+}
+
+impl<'a> SomeIpPacket<Cursor<'a>> {
+    /// Parse the first SOME/IP message of the tcp or udp packet in `frame`,
+    /// whose layers are parsed by `parse_untrusted`.
+    ///
+    /// SOME/IP has no well-known port except for the service discovery, so
+    /// the message is only parsed if the source or the destination port is in
+    /// `ports`, which should include `SOMEIP_SD_PORT` for the service
+    /// discovery messages.
+    pub fn from_parsed(
+        frame: &'a [u8],
+        parsed: &ParsedPacket,
+        ports: &[u16],
+    ) -> Option<SomeIpPacket<Cursor<'a>>> {
+        let (src_port, dst_port) = match parsed.transport? {
+            Transport::Tcp { src_port, dst_port } | Transport::Udp { src_port, dst_port } => {
+                (src_port, dst_port)
+            }
+            _ => return None,
+        };
+        if !ports.contains(&src_port) && !ports.contains(&dst_port) {
+            return None;
+        }
+        Self::parse(Cursor::new(frame.get(parsed.payload_offset..)?)).ok()
+    }
+
+    #[inline]
+    pub fn cursor_body(&self) -> &'a [u8] {
+        &self.buf.chunk_shared_lifetime()[SOMEIP_HEADER_LEN..self.message_len()]
+    }
+}
+
+impl<'a> Header<'a> for SomeIpPacket<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        SomeIpPacket::parse(Cursor::new(buf)).map_err(|_| ParseError::SomeIp)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        SOMEIP_HEADER_LEN
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        self.cursor_body()
+    }
+}
+
+/// The payload of a service discovery message, which holds the entries and
+/// the options that are referenced by the entries.
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct SomeIpSdMessage<T> {
+    buf: T,
+}
+
+impl<T: Buf> SomeIpSdMessage<T> {
+    /// The reboot flag, which is set until the session id wraps around after
+    /// a reboot.
+    pub const REBOOT_FLAG: u8 = 0x80;
+    /// The unicast flag, which is always set by the current implementations.
+    pub const UNICAST_FLAG: u8 = 0x40;
+
+    #[inline]
+    pub fn parse_unchecked(buf: T) -> Self {
+        Self { buf }
+    }
+
+    #[inline]
+    pub fn buf(&self) -> &T {
+        &self.buf
+    }
+
+    #[inline]
+    pub fn release(self) -> T {
+        self.buf
+    }
+
+    /// Parse the service discovery payload in `buf`, see `check_message`.
+    #[inline]
+    pub fn parse(buf: T) -> Result<SomeIpSdMessage<T>, T> {
+        if Self::check_message(buf.chunk()).is_err() {
+            return Err(buf);
+        }
+        Ok(SomeIpSdMessage { buf })
+    }
+
+    /// Check the service discovery payload in `buf` and return its length.
+    ///
+    /// The length of the entry array must be a multiple of the entry length,
+    /// and the option array is checked to be a well-formed option list. The
+    /// payload is bounded by the length of the message, so a truncated
+    /// payload is malformed.
+    pub fn check_message(buf: &[u8]) -> Result<usize, StreamError> {
+        let mut cursor = buf;
+        take(&mut cursor, 4)?;
+        let entries_len = NetworkEndian::read_u32(take(&mut cursor, 4)?) as usize;
+        if entries_len % SD_ENTRY_LEN != 0 {
+            return Err(StreamError::Malformed);
+        }
+        take(&mut cursor, entries_len)?;
+        let options_len = NetworkEndian::read_u32(take(&mut cursor, 4)?) as usize;
+        let option_bytes = take(&mut cursor, options_len)?;
+        if !SdOptionIter::check_option_bytes(option_bytes) {
+            return Err(StreamError::Malformed);
+        }
+        Ok(buf.len() - cursor.len())
+    }
+
+    #[inline]
+    pub fn flags(&self) -> u8 {
+        self.buf.chunk()[0]
+    }
+
+    #[inline]
+    pub fn reboot(&self) -> bool {
+        self.flags() & Self::REBOOT_FLAG != 0
+    }
+
+    #[inline]
+    pub fn unicast(&self) -> bool {
+        self.flags() & Self::UNICAST_FLAG != 0
+    }
+
+    #[inline]
+    pub fn entry_bytes(&self) -> &[u8] {
+        &self.buf.chunk()[8..self.entries_end()]
+    }
+
+    #[inline]
+    pub fn option_bytes(&self) -> &[u8] {
+        let start = self.entries_end() + 4;
+        let options_len = NetworkEndian::read_u32(&self.buf.chunk()[start - 4..start]) as usize;
+        &self.buf.chunk()[start..start + options_len]
+    }
+
+    #[inline]
+    pub fn entries(&self) -> SdEntryIter<'_> {
+        SdEntryIter {
+            chunks: self.entry_bytes().chunks_exact(SD_ENTRY_LEN),
+        }
+    }
+
+    #[inline]
+    pub fn options(&self) -> SdOptionIter<'_> {
+        SdOptionIter::from_option_bytes(self.option_bytes())
+    }
+
+    /// Return the options referenced by `entry`, i.e. the first and the second
+    /// runs of the option array. The indexes out of the array are skipped.
+    pub fn entry_options(&self, entry: &SdEntry<'_>) -> impl Iterator<Item = SdOption<'_>> + '_ {
+        let options = self.options();
+        let first = usize::from(entry.first_option_index());
+        let second = usize::from(entry.second_option_index());
+        let first = first..first + usize::from(entry.nb_first_options());
+        let second = second..second + usize::from(entry.nb_second_options());
+        options
+            .enumerate()
+            .filter(move |(index, _)| first.contains(index) || second.contains(index))
+            .map(|(_, option)| option)
+    }
+
+    // The offset of the length of the option array.
+    #[inline]
+    fn entries_end(&self) -> usize {
+        8 + NetworkEndian::read_u32(&self.buf.chunk()[4..8]) as usize
+    }
+}
+
+// Split the first `len` bytes from `buf`, the input is malformed if `buf` is
+// shorter, as it is bounded by the length of the message.
+#[inline]
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8], StreamError> {
+    if buf.len() < len {
+        return Err(StreamError::Malformed);
+    }
+    let (head, tail) = buf.split_at(len);
+    *buf = tail;
+    Ok(head)
+}
+
+/// An entry of a service discovery message.
+///
+/// The service entries, i.e. find service and offer service, carry a minor
+/// version, and the eventgroup entries carry an eventgroup id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SdEntry<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> SdEntry<'a> {
+    #[inline]
+    pub fn entry_type(&self) -> SdEntryType {
+        self.buf[0].into()
+    }
+
+    #[inline]
+    pub fn first_option_index(&self) -> u8 {
+        self.buf[1]
+    }
+
+    #[inline]
+    pub fn second_option_index(&self) -> u8 {
+        self.buf[2]
+    }
+
+    #[inline]
+    pub fn nb_first_options(&self) -> u8 {
+        self.buf[3] >> 4
+    }
+
+    #[inline]
+    pub fn nb_second_options(&self) -> u8 {
+        self.buf[3] & 0x0f
+    }
+
+    #[inline]
+    pub fn service_id(&self) -> u16 {
+        NetworkEndian::read_u16(&self.buf[4..6])
+    }
+
+    #[inline]
+    pub fn instance_id(&self) -> u16 {
+        NetworkEndian::read_u16(&self.buf[6..8])
+    }
+
+    #[inline]
+    pub fn major_version(&self) -> u8 {
+        self.buf[8]
+    }
+
+    /// Return the ttl in seconds, which is stored in 24 bits.
+    #[inline]
+    pub fn ttl(&self) -> u32 {
+        NetworkEndian::read_u24(&self.buf[9..12])
+    }
+
+    /// Return whether the entry is a service entry, otherwise it is an
+    /// eventgroup entry.
+    #[inline]
+    pub fn is_service_entry(&self) -> bool {
+        self.buf[0] < SdEntryType::SUBSCRIBE_EVENTGROUP.into()
+    }
+
+    /// Return the minor version of a service entry.
+    #[inline]
+    pub fn minor_version(&self) -> u32 {
+        NetworkEndian::read_u32(&self.buf[12..16])
+    }
+
+    /// Return the counter of an eventgroup entry, which tells apart the
+    /// subscriptions of the same eventgroup.
+    #[inline]
+    pub fn counter(&self) -> u8 {
+        self.buf[13] & 0x0f
+    }
+
+    /// Return the eventgroup id of an eventgroup entry.
+    #[inline]
+    pub fn eventgroup_id(&self) -> u16 {
+        NetworkEndian::read_u16(&self.buf[14..16])
+    }
+}
+
+pub struct SdEntryIter<'a> {
+    chunks: std::slice::ChunksExact<'a, u8>,
+}
+
+impl<'a> Iterator for SdEntryIter<'a> {
+    type Item = SdEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.chunks.next().map(|buf| SdEntry { buf })
+    }
+}
+
+/// An option of a service discovery message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SdOption<'a> {
+    option_type: SdOptionType,
+    data: &'a [u8],
+}
+
+impl<'a> SdOption<'a> {
+    #[inline]
+    pub fn option_type(&self) -> SdOptionType {
+        self.option_type
+    }
+
+    /// Return the data of the option, past the reserved byte that follows the
+    /// type.
+    #[inline]
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Return the address, the transport protocol and the port of an ipv4
+    /// endpoint, multicast or sd endpoint option.
+    pub fn ipv4_endpoint(&self) -> Option<(Ipv4Addr, IpProtocol, u16)> {
+        let ipv4_types = [
+            SdOptionType::IPV4_ENDPOINT,
+            SdOptionType::IPV4_MULTICAST,
+            SdOptionType::IPV4_SD_ENDPOINT,
+        ];
+        if !ipv4_types.contains(&self.option_type) || self.data.len() != 8 {
+            return None;
+        }
+        Some((
+            Ipv4Addr::from_bytes(&self.data[0..4]),
+            self.data[5].into(),
+            NetworkEndian::read_u16(&self.data[6..8]),
+        ))
+    }
+
+    /// Return the address, the transport protocol and the port of an ipv6
+    /// endpoint, multicast or sd endpoint option.
+    pub fn ipv6_endpoint(&self) -> Option<(Ipv6Addr, IpProtocol, u16)> {
+        let ipv6_types = [
+            SdOptionType::IPV6_ENDPOINT,
+            SdOptionType::IPV6_MULTICAST,
+            SdOptionType::IPV6_SD_ENDPOINT,
+        ];
+        if !ipv6_types.contains(&self.option_type) || self.data.len() != 20 {
+            return None;
+        }
+        Some((
+            Ipv6Addr::from_bytes(&self.data[0..16]),
+            self.data[17].into(),
+            NetworkEndian::read_u16(&self.data[18..20]),
+        ))
+    }
+}
+
+pub struct SdOptionIter<'a> {
+    buf: &'a [u8],
+    valid: bool,
+}
+
+impl<'a> SdOptionIter<'a> {
+    #[inline]
+    pub fn from_option_bytes(buf: &'a [u8]) -> SdOptionIter<'a> {
+        Self { buf, valid: true }
+    }
+
+    #[inline]
+    pub fn check_option_bytes(buf: &'a [u8]) -> bool {
+        let mut reader = Self::from_option_bytes(buf);
+        for _ in &mut reader {}
+        reader.valid
+    }
+}
+
+impl<'a> Iterator for SdOptionIter<'a> {
+    type Item = SdOption<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.valid || self.buf.is_empty() {
+            return None;
+        }
+
+        // the length counts the bytes past the type, which start with the
+        // reserved byte, so the iterator advances by at least 4 bytes
+        if self.buf.len() < 4 {
+            self.valid = false;
+            return None;
+        }
+        let len = NetworkEndian::read_u16(&self.buf[0..2]) as usize;
+        if len == 0 || self.buf.len() < 3 + len {
+            self.valid = false;
+            return None;
+        }
+
+        let option = SdOption {
+            option_type: self.buf[2].into(),
+            data: &self.buf[4..3 + len],
+        };
+        self.buf = &self.buf[3 + len..];
+        Some(option)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_untrusted;
+    use crate::someip::SOMEIP_SD_PORT;
+
+    // A SOME/IP request of the method 0x0421 of the service 0x1234, from the
+    // client 0x0013 in the session 0x0001, with a 4-byte payload.
+    static REQUEST_BYTES: [u8; 20] = [
+        0x12, 0x34, 0x04, 0x21, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x13, 0x00, 0x01, 0x01, 0x02, 0x00,
+        0x00, 0xde, 0xad, 0xbe, 0xef,
+    ];
+
+    // A service discovery message with an offer of the instance 0x0001 of the
+    // service 0x1234 at the udp endpoint 192.168.0.10:30509, and a subscribe
+    // of the eventgroup 0x0005 of the service 0x5678.
+    static SD_BYTES: [u8; 72] = [
+        // the header of a notification from the client 0x0000 in the session 2
+        0xff, 0xff, 0x81, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x02, 0x01, 0x01, 0x02,
+        0x00, // the flags and the length of the entry array
+        0xc0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20,
+        // the offer service entry, which references the first option
+        0x01, 0x00, 0x00, 0x10, 0x12, 0x34, 0x00, 0x01, 0x01, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00,
+        0x0a, // the subscribe eventgroup entry
+        0x06, 0x00, 0x00, 0x00, 0x56, 0x78, 0x00, 0x01, 0x02, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00,
+        0x05, // the length of the option array and the ipv4 endpoint option
+        0x00, 0x00, 0x00, 0x0c, 0x00, 0x09, 0x04, 0x00, 0xc0, 0xa8, 0x00, 0x0a, 0x00, 0x11, 0x77,
+        0x2d,
+    ];
+
+    #[test]
+    fn parse_someip_request() {
+        let header = SomeIpPacket::parse(Cursor::new(&REQUEST_BYTES[..])).unwrap();
+        assert_eq!(header.message_id(), 0x12340421);
+        assert_eq!(header.service_id(), 0x1234);
+        assert_eq!(header.method_id(), 0x0421);
+        assert!(!header.is_event());
+        assert_eq!(header.length(), 12);
+        assert_eq!(header.request_id(), 0x00130001);
+        assert_eq!(header.client_id(), 0x0013);
+        assert_eq!(header.session_id(), 0x0001);
+        assert_eq!(header.protocol_version(), 1);
+        assert_eq!(header.interface_version(), 2);
+        assert_eq!(header.message_type(), SomeIpMessageType::REQUEST);
+        assert_eq!(header.return_code(), SomeIpReturnCode::E_OK);
+        assert_eq!(header.body(), &[0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(header.message_len(), 20);
+        assert!(!header.is_sd());
+        assert_eq!(header.sd().err(), Some(StreamError::Malformed));
+
+        // two messages in a tcp segment
+        let mut segment = REQUEST_BYTES.to_vec();
+        segment.extend_from_slice(&REQUEST_BYTES[..]);
+        segment[31] = 0x02;
+        let header = SomeIpPacket::parse(Cursor::new(&segment[..])).unwrap();
+        let next = SomeIpPacket::parse(Cursor::new(&segment[header.message_len()..])).unwrap();
+        assert_eq!(next.session_id(), 0x0002);
+
+        // the truncated messages
+        assert_eq!(
+            SomeIpPacket::<Cursor>::check_message(&REQUEST_BYTES[..10]),
+            Err(StreamError::NeedMoreData(Some(16)))
+        );
+        assert_eq!(
+            SomeIpPacket::<Cursor>::check_message(&REQUEST_BYTES[..19]),
+            Err(StreamError::NeedMoreData(Some(20)))
+        );
+        assert!(SomeIpPacket::parse(Cursor::new(&REQUEST_BYTES[..19])).is_err());
+        // the wrong protocol version, or a length below the header
+        let mut bytes = REQUEST_BYTES;
+        bytes[12] = 2;
+        assert_eq!(
+            SomeIpPacket::<Cursor>::check_message(&bytes[..]),
+            Err(StreamError::Malformed)
+        );
+        let mut bytes = REQUEST_BYTES;
+        bytes[7] = 7;
+        assert_eq!(
+            SomeIpPacket::<Cursor>::check_message(&bytes[..]),
+            Err(StreamError::Malformed)
+        );
+    }
+
+    #[test]
+    fn parse_sd_message() {
+        let header = SomeIpPacket::parse(Cursor::new(&SD_BYTES[..])).unwrap();
+        assert!(header.is_sd());
+        assert_eq!(header.message_type(), SomeIpMessageType::NOTIFICATION);
+        let sd = header.sd().unwrap();
+        assert!(sd.reboot());
+        assert!(sd.unicast());
+
+        let entries: Vec<_> = sd.entries().collect();
+        assert_eq!(entries.len(), 2);
+        let offer = &entries[0];
+        assert_eq!(offer.entry_type(), SdEntryType::OFFER_SERVICE);
+        assert!(offer.is_service_entry());
+        assert_eq!(offer.service_id(), 0x1234);
+        assert_eq!(offer.instance_id(), 0x0001);
+        assert_eq!(offer.major_version(), 1);
+        assert_eq!(offer.ttl(), 3);
+        assert_eq!(offer.minor_version(), 10);
+        let options: Vec<_> = sd.entry_options(offer).collect();
+        assert_eq!(options.len(), 1);
+        assert_eq!(
+            options[0].ipv4_endpoint(),
+            Some((Ipv4Addr([192, 168, 0, 10]), IpProtocol::UDP, 30509))
+        );
+        assert_eq!(options[0].ipv6_endpoint(), None);
+
+        let subscribe = &entries[1];
+        assert_eq!(subscribe.entry_type(), SdEntryType::SUBSCRIBE_EVENTGROUP);
+        assert!(!subscribe.is_service_entry());
+        assert_eq!(subscribe.service_id(), 0x5678);
+        assert_eq!(subscribe.ttl(), 0xffffff);
+        assert_eq!(subscribe.eventgroup_id(), 5);
+        assert_eq!(subscribe.counter(), 0);
+        assert_eq!(sd.entry_options(subscribe).count(), 0);
+
+        // the entry array is not a multiple of 16 bytes
+        let mut bytes = SD_BYTES;
+        bytes[23] = 0x1f;
+        assert_eq!(
+            SomeIpSdMessage::<Cursor>::check_message(&bytes[16..]),
+            Err(StreamError::Malformed)
+        );
+        // the option length exceeds the option array
+        let mut bytes = SD_BYTES;
+        bytes[61] = 0x0a;
+        assert_eq!(
+            SomeIpSdMessage::<Cursor>::check_message(&bytes[16..]),
+            Err(StreamError::Malformed)
+        );
+        // the option array exceeds the message
+        assert_eq!(
+            SomeIpSdMessage::<Cursor>::check_message(&SD_BYTES[16..71]),
+            Err(StreamError::Malformed)
+        );
+    }
+
+    // Wrap `payload` in an ethernet frame of an ipv4 udp packet from the port
+    // 50000 to `dst_port`.
+    fn build_udp_frame(dst_port: u16, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0; 14];
+        bytes[12..14].copy_from_slice(&[0x08, 0x00]);
+        bytes.extend_from_slice(&[0x45, 0x00]);
+        bytes.extend_from_slice(&(28 + payload.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&[0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00]);
+        bytes.extend_from_slice(&[192, 168, 0, 10, 192, 168, 0, 20]);
+        bytes.extend_from_slice(&50000_u16.to_be_bytes());
+        bytes.extend_from_slice(&dst_port.to_be_bytes());
+        bytes.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    #[test]
+    fn parse_someip_frames() {
+        let ports = [SOMEIP_SD_PORT, 30509];
+
+        let bytes = build_udp_frame(30509, &REQUEST_BYTES[..]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        let header = SomeIpPacket::from_parsed(&bytes[..], &parsed, &ports[..]).unwrap();
+        assert_eq!(header.service_id(), 0x1234);
+        assert_eq!(header.method_id(), 0x0421);
+        // a port out of the port set
+        assert!(SomeIpPacket::from_parsed(&bytes[..], &parsed, &[30490]).is_none());
+
+        let bytes = build_udp_frame(SOMEIP_SD_PORT, &SD_BYTES[..]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        let header = SomeIpPacket::from_parsed(&bytes[..], &parsed, &ports[..]).unwrap();
+        assert_eq!(header.sd().unwrap().entries().count(), 2);
+    }
+}
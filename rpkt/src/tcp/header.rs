@@ -1,5 +1,8 @@
 use byteorder::{ByteOrder, NetworkEndian};
 
+use crate::checksum_utils;
+use crate::ParseError;
+
 header_field_range_accessors! {
     (src_port, src_port_mut, 0..2),
     (dst_port, dst_port_mut, 2..4),
@@ -351,3 +354,68 @@ impl<T: AsMut<[u8]>> TcpHeader<T> {
         NetworkEndian::write_u16(data, value)
     }
 }
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> TcpHeader<T> {
+    /// Rewrite the value of the MSS option, if there is one. The buffer must
+    /// span the whole header, including the options.
+    ///
+    /// The checksum is updated incrementally following RFC 1624, so a packet
+    /// whose checksum was valid before the rewrite stays valid afterwards.
+    ///
+    /// Return `Ok(false)` if the options end without an MSS option, and
+    /// `Err(ParseError::TcpOptions)` if an option before the MSS option, or
+    /// the MSS option itself, is malformed. The header is left untouched in
+    /// both cases.
+    pub fn set_mss_option(&mut self, mss: u16) -> Result<bool, ParseError> {
+        let header_len = usize::from(self.header_len());
+        let options = self
+            .buf
+            .as_ref()
+            .get(TCP_HEADER_LEN..header_len)
+            .ok_or(ParseError::TcpOptions)?;
+        let mut offset = 0;
+        let mss_offset = loop {
+            if offset >= options.len() {
+                return Ok(false);
+            }
+            match options[offset] {
+                0 => return Ok(false),
+                1 => offset += 1,
+                kind => {
+                    if offset + 1 >= options.len() {
+                        return Err(ParseError::TcpOptions);
+                    }
+                    let opt_len = usize::from(options[offset + 1]);
+                    if opt_len < 2 || offset + opt_len > options.len() {
+                        return Err(ParseError::TcpOptions);
+                    }
+                    if kind == 2 {
+                        if opt_len != 4 {
+                            return Err(ParseError::TcpOptions);
+                        }
+                        break TCP_HEADER_LEN + offset + 2;
+                    }
+                    offset += opt_len;
+                }
+            }
+        };
+
+        let data = &mut self.buf.as_mut()[mss_offset..mss_offset + 2];
+        let old_mss = NetworkEndian::read_u16(data);
+        NetworkEndian::write_u16(data, mss);
+
+        // The checksum sums the 16-bit words from the start of the header. A
+        // value at an odd offset, e.g. behind a single NOP, straddles two words
+        // with its bytes swapped, and the byte-swapped sum is the swapped sum
+        // of the words.
+        let (old_word, new_word) = if mss_offset % 2 == 0 {
+            (old_mss, mss)
+        } else {
+            (old_mss.swap_bytes(), mss.swap_bytes())
+        };
+        let cksum = checksum_utils::incremental_update(self.checksum(), old_word, new_word);
+        self.set_checksum(cksum);
+
+        Ok(true)
+    }
+}
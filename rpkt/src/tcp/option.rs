@@ -256,7 +256,9 @@ impl<'a> Iterator for TcpOptionIter<'a> {
                     }
                     _ => {
                         let opt_len = usize::from(self.buf[1]);
-                        if self.buf.len() < opt_len {
+                        // the length covers the type and length bytes, a shorter
+                        // option would never advance the iterator
+                        if opt_len < 2 || self.buf.len() < opt_len {
                             self.valid = false;
                             None
                         } else {
@@ -3,6 +3,7 @@ use bytes::Buf;
 use crate::checksum_utils;
 use crate::ipv4::{Ipv4Addr, Ipv4PseudoHeader};
 use crate::{Cursor, CursorMut};
+use crate::{Header, ParseError};
 use crate::{PktBuf, PktMut};
 
 use super::{TcpHeader, TCP_HEADER_LEN};
@@ -115,6 +116,13 @@ impl<T: PktMut> TcpPacket<T> {
         &mut self.buf.chunk_mut()[TCP_HEADER_LEN..header_len]
     }
 
+    /// Rewrite the value of the MSS option, see `TcpHeader::set_mss_option`.
+    #[inline]
+    pub fn set_mss_option(&mut self, mss: u16) -> Result<bool, ParseError> {
+        let header_len = usize::from(self.header_len());
+        TcpHeader::new_unchecked(&mut self.buf.chunk_mut()[..header_len]).set_mss_option(mss)
+    }
+
     #[inline]
     pub fn adjust_ipv4_checksum(&mut self, src_addr: Ipv4Addr, dst_addr: Ipv4Addr) {
         self.set_checksum(0);
@@ -158,6 +166,23 @@ impl<'a> TcpPacket<Cursor<'a>> {
     }
 }
 
+impl<'a> Header<'a> for TcpPacket<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        TcpPacket::parse(Cursor::new(buf)).map_err(|_| ParseError::Tcp)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        usize::from(self.header_len())
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        self.cursor_payload().chunk_shared_lifetime()
+    }
+}
+
 impl<'a> TcpPacket<CursorMut<'a>> {
     #[inline]
     pub fn split(self) -> (TcpHeader<&'a mut [u8]>, &'a [u8], CursorMut<'a>) {
@@ -182,6 +207,7 @@ mod tests {
     use super::*;
     use crate::ether::*;
     use crate::ipv4::*;
+    use crate::malformed::TCP_FRAME;
     use crate::tcp::*;
     use crate::{Cursor, CursorMut};
 
@@ -272,10 +298,106 @@ mod tests {
         )
     }
 
+    #[test]
+    fn mss_clamping() {
+        let mut bytes = TCP_FRAME;
+        let buf = CursorMut::new(&mut bytes[..]);
+
+        let ethpkt = EtherPacket::parse(buf).unwrap();
+        let ippkt = Ipv4Packet::parse(ethpkt.payload()).unwrap();
+        let src_ip = ippkt.source_ip();
+        let dst_ip = ippkt.dest_ip();
+
+        let mut tcppkt = TcpPacket::parse(ippkt.payload()).unwrap();
+        assert!(tcppkt.syn());
+        assert!(tcppkt.verify_ipv4_checksum(src_ip, dst_ip));
+
+        let mut opt_reader = TcpOptionIter::from_option_bytes(tcppkt.option_bytes());
+        assert!(matches!(opt_reader.next(), Some(TcpOption::Mss(1460))));
+
+        assert_eq!(tcppkt.set_mss_option(1400), Ok(true));
+        assert!(tcppkt.verify_ipv4_checksum(src_ip, dst_ip));
+
+        let mut opt_reader = TcpOptionIter::from_option_bytes(tcppkt.option_bytes());
+        assert!(matches!(opt_reader.next(), Some(TcpOption::Mss(1400))));
+
+        // The checksum must match a full recomputation.
+        let cksum = tcppkt.checksum();
+        tcppkt.adjust_ipv4_checksum(src_ip, dst_ip);
+        assert_eq!(tcppkt.checksum(), cksum);
+    }
+
+    #[test]
+    fn mss_clamping_at_odd_offset() {
+        // move the MSS option behind a NOP, so that its value starts at an odd
+        // offset of the TCP header
+        let mut bytes = TCP_FRAME;
+        bytes[54] = 0x01;
+        bytes[55..59].copy_from_slice(&TCP_FRAME[54..58]);
+        bytes[59..70].copy_from_slice(&TCP_FRAME[58..69]);
+        let buf = CursorMut::new(&mut bytes[..]);
+
+        let ethpkt = EtherPacket::parse(buf).unwrap();
+        let ippkt = Ipv4Packet::parse(ethpkt.payload()).unwrap();
+        let src_ip = ippkt.source_ip();
+        let dst_ip = ippkt.dest_ip();
+        let mut tcppkt = TcpPacket::parse(ippkt.payload()).unwrap();
+        tcppkt.adjust_ipv4_checksum(src_ip, dst_ip);
+
+        assert_eq!(tcppkt.set_mss_option(1400), Ok(true));
+        assert!(tcppkt.verify_ipv4_checksum(src_ip, dst_ip));
+        let mut opt_reader = TcpOptionIter::from_option_bytes(tcppkt.option_bytes());
+        assert!(matches!(opt_reader.next(), Some(TcpOption::Nop)));
+        assert!(matches!(opt_reader.next(), Some(TcpOption::Mss(1400))));
+
+        let cksum = tcppkt.checksum();
+        tcppkt.adjust_ipv4_checksum(src_ip, dst_ip);
+        assert_eq!(tcppkt.checksum(), cksum);
+    }
+
+    #[test]
+    fn mss_clamping_with_malformed_options() {
+        // an option length that overruns the option region
+        let mut bytes = TCP_FRAME;
+        bytes[54..56].copy_from_slice(&[0x05, 0x30]);
+        let buf = CursorMut::new(&mut bytes[..]);
+        let ethpkt = EtherPacket::parse(buf).unwrap();
+        let ippkt = Ipv4Packet::parse(ethpkt.payload()).unwrap();
+        let mut tcppkt = TcpPacket::parse(ippkt.payload()).unwrap();
+        assert_eq!(tcppkt.set_mss_option(1400), Err(ParseError::TcpOptions));
+
+        // a MSS option with an invalid length
+        let mut bytes = TCP_FRAME;
+        bytes[55] = 0x03;
+        let buf = CursorMut::new(&mut bytes[..]);
+        let ethpkt = EtherPacket::parse(buf).unwrap();
+        let ippkt = Ipv4Packet::parse(ethpkt.payload()).unwrap();
+        let mut tcppkt = TcpPacket::parse(ippkt.payload()).unwrap();
+        assert_eq!(tcppkt.set_mss_option(1400), Err(ParseError::TcpOptions));
+        assert_eq!(tcppkt.release().chunk()[20..24], [0x02, 0x03, 0x05, 0xb4]);
+
+        // a header buffer that stops before the end of the options
+        let mut header = TcpHeader::new(&mut bytes[34..54]).unwrap();
+        assert_eq!(header.set_mss_option(1400), Err(ParseError::TcpOptions));
+    }
+
+    #[test]
+    fn mss_clamping_without_mss_option() {
+        let mut bytes = FRAME_BYTES;
+        let buf = CursorMut::new(&mut bytes[..]);
+
+        let ethpkt = EtherPacket::parse(buf).unwrap();
+        let ippkt = Ipv4Packet::parse(ethpkt.payload()).unwrap();
+        let mut tcppkt = TcpPacket::parse(ippkt.payload()).unwrap();
+
+        assert_eq!(tcppkt.set_mss_option(1400), Ok(false));
+        assert_eq!(tcppkt.release().chunk(), &FRAME_BYTES[34..]);
+    }
+
     #[test]
     fn packet_build() {
         let mut bytes = [0xff; 200];
-        (&mut bytes[ETHER_HEADER_LEN + IPV4_HEADER_LEN + TCP_HEADER_LEN + 12..]).copy_from_slice(
+        bytes[ETHER_HEADER_LEN + IPV4_HEADER_LEN + TCP_HEADER_LEN + 12..].copy_from_slice(
             &FRAME_BYTES[ETHER_HEADER_LEN + IPV4_HEADER_LEN + TCP_HEADER_LEN + 12..],
         );
 
@@ -0,0 +1,70 @@
+enum_sim! {
+    /// See https://www.iana.org/assignments/tls-parameters/tls-parameters.xhtml#tls-parameters-5
+    pub struct TlsContentType (u8) {
+        CHANGE_CIPHER_SPEC = 20,
+        ALERT = 21,
+        HANDSHAKE = 22,
+        APPLICATION_DATA = 23,
+        HEARTBEAT = 24,
+    }
+}
+
+enum_sim! {
+    /// The protocol version of a record or a hello message, the major version
+    /// is 3 for ssl 3.0 and all the tls versions.
+    pub struct TlsVersion (u16) {
+        SSL_3_0 = 0x0300,
+        TLS_1_0 = 0x0301,
+        TLS_1_1 = 0x0302,
+        TLS_1_2 = 0x0303,
+        TLS_1_3 = 0x0304,
+    }
+}
+
+enum_sim! {
+    /// See https://www.iana.org/assignments/tls-parameters/tls-parameters.xhtml#tls-parameters-7
+    pub struct TlsHandshakeType (u8) {
+        HELLO_REQUEST = 0,
+        CLIENT_HELLO = 1,
+        SERVER_HELLO = 2,
+        NEW_SESSION_TICKET = 4,
+        CERTIFICATE = 11,
+        SERVER_KEY_EXCHANGE = 12,
+        CERTIFICATE_REQUEST = 13,
+        SERVER_HELLO_DONE = 14,
+        CERTIFICATE_VERIFY = 15,
+        CLIENT_KEY_EXCHANGE = 16,
+        FINISHED = 20,
+    }
+}
+
+enum_sim! {
+    /// See https://www.iana.org/assignments/tls-extensiontype-values/tls-extensiontype-values.xhtml#tls-extensiontype-values-1
+    pub struct TlsExtensionType (u16) {
+        SERVER_NAME = 0,
+        SUPPORTED_GROUPS = 10,
+        EC_POINT_FORMATS = 11,
+        SIGNATURE_ALGORITHMS = 13,
+        ALPN = 16,
+        PADDING = 21,
+        SESSION_TICKET = 35,
+        SUPPORTED_VERSIONS = 43,
+        KEY_SHARE = 51,
+    }
+}
+
+/// The length of the tls record header, which precedes the fragment.
+pub const TLS_RECORD_HEADER_LEN: usize = 5;
+
+/// The maximum length of the fragment of a record, which is the limit of an
+/// encrypted record of tls 1.2.
+pub const TLS_MAX_FRAGMENT_LEN: usize = (1 << 14) + 2048;
+
+/// The length of the handshake header, which precedes the handshake message.
+pub const TLS_HANDSHAKE_HEADER_LEN: usize = 4;
+
+/// The tcp port assigned to https.
+pub const HTTPS_PORT: u16 = 443;
+
+mod packet;
+pub use packet::{ClientHello, TlsExtension, TlsExtensionIter, TlsRecord};
@@ -0,0 +1,573 @@
+use std::ops::Range;
+
+use byteorder::{ByteOrder, NetworkEndian};
+use bytes::Buf;
+
+use crate::{Cursor, Header, ParseError, PktBuf, StreamError};
+
+use super::{
+    TlsContentType, TlsExtensionType, TlsHandshakeType, TlsVersion, TLS_HANDSHAKE_HEADER_LEN,
+    TLS_MAX_FRAGMENT_LEN, TLS_RECORD_HEADER_LEN,
+};
+
+/// A tls record at the front of a tcp payload.
+///
+/// Only the records of ssl 3.0 and the tls versions are recognized, the ssl
+/// 2.0 compatible hellos are rejected as malformed. The following records, if
+/// any, start at `record_len` of the payload.
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct TlsRecord<T> {
+    buf: T,
+}
+
+impl<T: Buf> TlsRecord<T> {
+    #[inline]
+    pub fn parse_unchecked(buf: T) -> Self {
+        Self { buf }
+    }
+
+    #[inline]
+    pub fn buf(&self) -> &T {
+        &self.buf
+    }
+
+    #[inline]
+    pub fn release(self) -> T {
+        self.buf
+    }
+
+    /// Parse the record at the front of `buf`, the record must be complete,
+    /// see `check_record`.
+    #[inline]
+    pub fn parse(buf: T) -> Result<TlsRecord<T>, T> {
+        if Self::check_record(buf.chunk()).is_err() {
+            return Err(buf);
+        }
+        Ok(TlsRecord { buf })
+    }
+
+    /// Check the record at the front of `buf` and return its length.
+    ///
+    /// Return `NeedMoreData` if the record is not complete, so that the caller
+    /// can retry after appending the next tcp segment. The content type and
+    /// the major version are checked as soon as they are available, so that a
+    /// non-tls payload is rejected from its first bytes.
+    #[inline]
+    pub fn check_record(buf: &[u8]) -> Result<usize, StreamError> {
+        if buf.first().is_some_and(|t| !(20..=24).contains(t))
+            || buf.get(1).is_some_and(|major| *major != 3)
+        {
+            return Err(StreamError::Malformed);
+        }
+        if buf.len() < TLS_RECORD_HEADER_LEN {
+            return Err(StreamError::NeedMoreData(Some(TLS_RECORD_HEADER_LEN)));
+        }
+
+        let fragment_len = NetworkEndian::read_u16(&buf[3..5]) as usize;
+        if fragment_len > TLS_MAX_FRAGMENT_LEN {
+            return Err(StreamError::Malformed);
+        }
+        let record_len = TLS_RECORD_HEADER_LEN + fragment_len;
+        if record_len > buf.len() {
+            return Err(StreamError::NeedMoreData(Some(record_len)));
+        }
+        Ok(record_len)
+    }
+
+    #[inline]
+    pub fn content_type(&self) -> TlsContentType {
+        self.buf.chunk()[0].into()
+    }
+
+    /// Return the version of the record layer, which is 1.0 or 1.2 for the
+    /// hellos of all the modern clients, see `ClientHello::version`.
+    #[inline]
+    pub fn version(&self) -> TlsVersion {
+        NetworkEndian::read_u16(&self.buf.chunk()[1..3]).into()
+    }
+
+    #[inline]
+    pub fn fragment_len(&self) -> u16 {
+        NetworkEndian::read_u16(&self.buf.chunk()[3..5])
+    }
+
+    /// Return the length of the record, including the header.
+    #[inline]
+    pub fn record_len(&self) -> usize {
+        TLS_RECORD_HEADER_LEN + usize::from(self.fragment_len())
+    }
+
+    #[inline]
+    pub fn fragment(&self) -> &[u8] {
+        &self.buf.chunk()[TLS_RECORD_HEADER_LEN..self.record_len()]
+    }
+
+    /// Parse the fragment of a handshake record as a client hello, see
+    /// `ClientHello::check_hello`.
+    #[inline]
+    pub fn client_hello(&self) -> Result<ClientHello<Cursor<'_>>, StreamError> {
+        if self.content_type() != TlsContentType::HANDSHAKE {
+            return Err(StreamError::Malformed);
+        }
+        let fragment = self.fragment();
+        ClientHello::<Cursor>::check_hello(fragment)?;
+        Ok(ClientHello::parse_unchecked(Cursor::new(fragment)))
+    }
+
+    /// Return the fragment, the following records are trimmed off.
+    #[inline]
+    pub fn payload(self) -> T
+    where
+        T: PktBuf,
+    {
+        let trim_size = self.buf.remaining() - self.record_len();
+
+        let mut buf = self.release();
+        if trim_size > 0 {
+            buf.trim_off(trim_size);
+        }
+        buf.advance(TLS_RECORD_HEADER_LEN);
+
+        buf
+    }
+}
+
+impl<'a> TlsRecord<Cursor<'a>> {
+    #[inline]
+    pub fn cursor_fragment(&self) -> &'a [u8] {
+        &self.buf.chunk_shared_lifetime()[TLS_RECORD_HEADER_LEN..self.record_len()]
+    }
+}
+
+impl<'a> Header<'a> for TlsRecord<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        TlsRecord::parse(Cursor::new(buf)).map_err(|_| ParseError::Tls)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        TLS_RECORD_HEADER_LEN
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        self.cursor_fragment()
+    }
+}
+
+/// A client hello message, which is sent in the clear at the start of a tls
+/// connection.
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct ClientHello<T> {
+    buf: T,
+}
+
+impl<T: Buf> ClientHello<T> {
+    #[inline]
+    pub fn parse_unchecked(buf: T) -> Self {
+        Self { buf }
+    }
+
+    #[inline]
+    pub fn buf(&self) -> &T {
+        &self.buf
+    }
+
+    #[inline]
+    pub fn release(self) -> T {
+        self.buf
+    }
+
+    /// Parse the client hello at the front of the handshake messages in
+    /// `buf`, the hello must be complete, see `check_hello`.
+    #[inline]
+    pub fn parse(buf: T) -> Result<ClientHello<T>, T> {
+        if hello_layout(buf.chunk()).is_err() {
+            return Err(buf);
+        }
+        Ok(ClientHello { buf })
+    }
+
+    /// Check the client hello at the front of the handshake messages in
+    /// `buf`, which starts with the handshake header, and return its length.
+    ///
+    /// A hello that spans several records returns `NeedMoreData`, the caller
+    /// can retry with the fragments of the following handshake records
+    /// concatenated. Every length field is checked against the message, and
+    /// the extensions must fill the message exactly.
+    #[inline]
+    pub fn check_hello(buf: &[u8]) -> Result<usize, StreamError> {
+        hello_layout(buf).map(|layout| layout.msg_len)
+    }
+
+    /// Return the legacy version of the hello, which is 1.2 for a tls 1.3
+    /// client that lists its versions in the supported versions extension.
+    #[inline]
+    pub fn version(&self) -> TlsVersion {
+        NetworkEndian::read_u16(&self.buf.chunk()[4..6]).into()
+    }
+
+    #[inline]
+    pub fn random(&self) -> &[u8] {
+        &self.buf.chunk()[6..38]
+    }
+
+    #[inline]
+    pub fn session_id(&self) -> &[u8] {
+        &self.buf.chunk()[self.layout().session_id]
+    }
+
+    #[inline]
+    pub fn cipher_suites(&self) -> impl Iterator<Item = u16> + '_ {
+        self.buf.chunk()[self.layout().cipher_suites]
+            .chunks_exact(2)
+            .map(NetworkEndian::read_u16)
+    }
+
+    #[inline]
+    pub fn compression_methods(&self) -> &[u8] {
+        &self.buf.chunk()[self.layout().compression_methods]
+    }
+
+    #[inline]
+    pub fn extension_bytes(&self) -> &[u8] {
+        &self.buf.chunk()[self.layout().extensions]
+    }
+
+    #[inline]
+    pub fn extensions(&self) -> TlsExtensionIter<'_> {
+        TlsExtensionIter::from_extension_bytes(self.extension_bytes())
+    }
+
+    /// Return the first extension of `ext_type`.
+    #[inline]
+    pub fn extension(&self, ext_type: TlsExtensionType) -> Option<TlsExtension<'_>> {
+        self.extensions().find(|ext| ext.ext_type() == ext_type)
+    }
+
+    /// Return the host name of the server name extension.
+    ///
+    /// Return `None` if the extension is absent, malformed or carries no host
+    /// name, or if the host name is not utf-8.
+    pub fn server_name(&self) -> Option<&str> {
+        let data = self.extension(TlsExtensionType::SERVER_NAME)?.data();
+        if data.len() < 2 || NetworkEndian::read_u16(&data[..2]) as usize != data.len() - 2 {
+            return None;
+        }
+
+        let mut names = &data[2..];
+        while !names.is_empty() {
+            let name_type = take(&mut names, 1).ok()?[0];
+            let name_len = NetworkEndian::read_u16(take(&mut names, 2).ok()?) as usize;
+            let name = take(&mut names, name_len).ok()?;
+            // the host name is the only defined name type
+            if name_type == 0 {
+                return std::str::from_utf8(name).ok();
+            }
+        }
+        None
+    }
+
+    // The hello is checked by `parse`.
+    #[inline]
+    fn layout(&self) -> HelloLayout {
+        hello_layout(self.buf.chunk()).unwrap()
+    }
+}
+
+// The length of a client hello and the ranges of its variable-length fields.
+struct HelloLayout {
+    msg_len: usize,
+    session_id: Range<usize>,
+    cipher_suites: Range<usize>,
+    compression_methods: Range<usize>,
+    extensions: Range<usize>,
+}
+
+fn hello_layout(buf: &[u8]) -> Result<HelloLayout, StreamError> {
+    if buf
+        .first()
+        .is_some_and(|t| *t != TlsHandshakeType::CLIENT_HELLO.into())
+    {
+        return Err(StreamError::Malformed);
+    }
+    if buf.len() < TLS_HANDSHAKE_HEADER_LEN {
+        return Err(StreamError::NeedMoreData(Some(TLS_HANDSHAKE_HEADER_LEN)));
+    }
+    let msg_len = TLS_HANDSHAKE_HEADER_LEN + NetworkEndian::read_u24(&buf[1..4]) as usize;
+    if msg_len > buf.len() {
+        return Err(StreamError::NeedMoreData(Some(msg_len)));
+    }
+
+    let msg = &buf[..msg_len];
+    let mut body = &msg[TLS_HANDSHAKE_HEADER_LEN..];
+    // the version and the random
+    take(&mut body, 2 + 32)?;
+    let session_id_len = take(&mut body, 1)?[0] as usize;
+    if session_id_len > 32 {
+        return Err(StreamError::Malformed);
+    }
+    let session_id = take_range(msg, &mut body, session_id_len)?;
+    let cipher_suites_len = NetworkEndian::read_u16(take(&mut body, 2)?) as usize;
+    if cipher_suites_len == 0 || cipher_suites_len % 2 != 0 {
+        return Err(StreamError::Malformed);
+    }
+    let cipher_suites = take_range(msg, &mut body, cipher_suites_len)?;
+    let compression_methods_len = take(&mut body, 1)?[0] as usize;
+    if compression_methods_len == 0 {
+        return Err(StreamError::Malformed);
+    }
+    let compression_methods = take_range(msg, &mut body, compression_methods_len)?;
+
+    // the extensions may be absent in a hello of ssl 3.0
+    let extensions = if body.is_empty() {
+        msg_len..msg_len
+    } else {
+        let extensions_len = NetworkEndian::read_u16(take(&mut body, 2)?) as usize;
+        let extensions = take_range(msg, &mut body, extensions_len)?;
+        if !body.is_empty() || !TlsExtensionIter::check_extension_bytes(&msg[extensions.clone()]) {
+            return Err(StreamError::Malformed);
+        }
+        extensions
+    };
+
+    Ok(HelloLayout {
+        msg_len,
+        session_id,
+        cipher_suites,
+        compression_methods,
+        extensions,
+    })
+}
+
+// Split the first `len` bytes from `buf`, the input is malformed if `buf` is
+// shorter, as it is bounded by an enclosing length that is already checked.
+#[inline]
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8], StreamError> {
+    if buf.len() < len {
+        return Err(StreamError::Malformed);
+    }
+    let (head, tail) = buf.split_at(len);
+    *buf = tail;
+    Ok(head)
+}
+
+// Split the first `len` bytes from `buf`, the tail of `msg`, and return their
+// range in `msg`.
+#[inline]
+fn take_range(msg: &[u8], buf: &mut &[u8], len: usize) -> Result<Range<usize>, StreamError> {
+    let start = msg.len() - buf.len();
+    take(buf, len)?;
+    Ok(start..start + len)
+}
+
+/// An extension of a hello message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TlsExtension<'a> {
+    ext_type: TlsExtensionType,
+    data: &'a [u8],
+}
+
+impl<'a> TlsExtension<'a> {
+    #[inline]
+    pub fn ext_type(&self) -> TlsExtensionType {
+        self.ext_type
+    }
+
+    #[inline]
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+pub struct TlsExtensionIter<'a> {
+    buf: &'a [u8],
+    valid: bool,
+}
+
+impl<'a> TlsExtensionIter<'a> {
+    #[inline]
+    pub fn from_extension_bytes(buf: &'a [u8]) -> TlsExtensionIter<'a> {
+        Self { buf, valid: true }
+    }
+
+    #[inline]
+    pub fn check_extension_bytes(buf: &'a [u8]) -> bool {
+        let mut reader = Self::from_extension_bytes(buf);
+        for _ in &mut reader {}
+        reader.valid
+    }
+}
+
+impl<'a> Iterator for TlsExtensionIter<'a> {
+    type Item = TlsExtension<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.valid || self.buf.is_empty() {
+            return None;
+        }
+
+        // the 4-byte extension header is always consumed, so the iterator
+        // always advances
+        if self.buf.len() < 4 {
+            self.valid = false;
+            return None;
+        }
+        let data_len = NetworkEndian::read_u16(&self.buf[2..4]) as usize;
+        if self.buf.len() < 4 + data_len {
+            self.valid = false;
+            return None;
+        }
+
+        let ext = TlsExtension {
+            ext_type: NetworkEndian::read_u16(&self.buf[0..2]).into(),
+            data: &self.buf[4..4 + data_len],
+        };
+        self.buf = &self.buf[4 + data_len..];
+        Some(ext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A client hello of openssl 3 to www.example.com, including the record
+    // header.
+    static CLIENT_HELLO_BYTES: [u8; 517] = [
+        0x16, 0x03, 0x01, 0x02, 0x00, 0x01, 0x00, 0x01, 0xfc, 0x03, 0x03, 0xb5, 0xcf, 0x7c, 0x00,
+        0x8d, 0x83, 0x69, 0x58, 0xd6, 0xa5, 0x56, 0x8a, 0xfb, 0xb4, 0x43, 0x67, 0x46, 0x0f, 0x35,
+        0x48, 0x9c, 0xae, 0x61, 0x7f, 0x8d, 0xde, 0xe0, 0x81, 0x0a, 0x46, 0xda, 0x28, 0x20, 0x21,
+        0x6e, 0x3b, 0x0a, 0xf3, 0xc4, 0x4a, 0xb8, 0x34, 0x5b, 0x90, 0x0d, 0x80, 0x8e, 0x69, 0x93,
+        0x22, 0x8b, 0x96, 0x64, 0x87, 0xb8, 0x48, 0xf4, 0x9d, 0xff, 0x79, 0x69, 0xb0, 0x69, 0xed,
+        0xaf, 0x00, 0x24, 0x13, 0x02, 0x13, 0x03, 0x13, 0x01, 0xc0, 0x2c, 0xc0, 0x30, 0xc0, 0x2b,
+        0xc0, 0x2f, 0xcc, 0xa9, 0xcc, 0xa8, 0xc0, 0x24, 0xc0, 0x28, 0xc0, 0x23, 0xc0, 0x27, 0x00,
+        0x9f, 0x00, 0x9e, 0x00, 0x6b, 0x00, 0x67, 0x00, 0xff, 0x01, 0x00, 0x01, 0x8f, 0x00, 0x00,
+        0x00, 0x14, 0x00, 0x12, 0x00, 0x00, 0x0f, 0x77, 0x77, 0x77, 0x2e, 0x65, 0x78, 0x61, 0x6d,
+        0x70, 0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d, 0x00, 0x0b, 0x00, 0x04, 0x03, 0x00, 0x01, 0x02,
+        0x00, 0x0a, 0x00, 0x16, 0x00, 0x14, 0x00, 0x1d, 0x00, 0x17, 0x00, 0x1e, 0x00, 0x19, 0x00,
+        0x18, 0x01, 0x00, 0x01, 0x01, 0x01, 0x02, 0x01, 0x03, 0x01, 0x04, 0x00, 0x23, 0x00, 0x00,
+        0x00, 0x16, 0x00, 0x00, 0x00, 0x17, 0x00, 0x00, 0x00, 0x0d, 0x00, 0x2a, 0x00, 0x28, 0x04,
+        0x03, 0x05, 0x03, 0x06, 0x03, 0x08, 0x07, 0x08, 0x08, 0x08, 0x09, 0x08, 0x0a, 0x08, 0x0b,
+        0x08, 0x04, 0x08, 0x05, 0x08, 0x06, 0x04, 0x01, 0x05, 0x01, 0x06, 0x01, 0x03, 0x03, 0x03,
+        0x01, 0x03, 0x02, 0x04, 0x02, 0x05, 0x02, 0x06, 0x02, 0x00, 0x2b, 0x00, 0x05, 0x04, 0x03,
+        0x04, 0x03, 0x03, 0x00, 0x2d, 0x00, 0x02, 0x01, 0x01, 0x00, 0x33, 0x00, 0x26, 0x00, 0x24,
+        0x00, 0x1d, 0x00, 0x20, 0x7d, 0x06, 0x06, 0x1c, 0x72, 0x2a, 0xc3, 0x64, 0xdb, 0x4a, 0x62,
+        0x44, 0xcb, 0x21, 0x39, 0x01, 0x1e, 0x89, 0x05, 0xb1, 0x40, 0x74, 0x3a, 0xa1, 0x5b, 0x6c,
+        0xef, 0xbd, 0xca, 0x12, 0xc3, 0x64, 0x00, 0x15, 0x00, 0xde, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn parse_client_hello() {
+        let record = TlsRecord::parse(Cursor::new(&CLIENT_HELLO_BYTES[..])).unwrap();
+        assert_eq!(record.content_type(), TlsContentType::HANDSHAKE);
+        assert_eq!(record.version(), TlsVersion::TLS_1_0);
+        assert_eq!(record.fragment_len(), 512);
+        assert_eq!(record.record_len(), 517);
+
+        let hello = record.client_hello().unwrap();
+        assert_eq!(hello.version(), TlsVersion::TLS_1_2);
+        assert_eq!(hello.random(), &CLIENT_HELLO_BYTES[11..43]);
+        assert_eq!(hello.session_id().len(), 32);
+        assert_eq!(hello.cipher_suites().count(), 18);
+        // TLS_AES_256_GCM_SHA384
+        assert_eq!(hello.cipher_suites().next(), Some(0x1302));
+        assert_eq!(hello.compression_methods(), &[0][..]);
+        assert_eq!(hello.extensions().count(), 11);
+        let versions = hello.extension(TlsExtensionType::SUPPORTED_VERSIONS);
+        assert_eq!(versions.unwrap().data(), &[4, 3, 4, 3, 3][..]);
+        assert_eq!(hello.server_name(), Some("www.example.com"));
+
+        // the following record starts after the record length
+        let mut bytes = CLIENT_HELLO_BYTES.to_vec();
+        bytes.extend_from_slice(&[21, 3, 3, 0, 2, 1, 0]);
+        let record = TlsRecord::parse(Cursor::new(&bytes[..])).unwrap();
+        assert_eq!(record.record_len(), 517);
+        let alert = TlsRecord::parse(Cursor::new(&bytes[517..])).unwrap();
+        assert_eq!(alert.content_type(), TlsContentType::ALERT);
+        assert_eq!(alert.fragment(), &[1, 0][..]);
+        assert_eq!(alert.client_hello().err(), Some(StreamError::Malformed));
+    }
+
+    #[test]
+    fn parse_split_record() {
+        for len in 0..CLIENT_HELLO_BYTES.len() {
+            let res = TlsRecord::<Cursor>::check_record(&CLIENT_HELLO_BYTES[..len]);
+            let need = if len < 5 { 5 } else { 517 };
+            assert_eq!(res.err(), Some(StreamError::NeedMoreData(Some(need))));
+            assert!(TlsRecord::parse(Cursor::new(&CLIENT_HELLO_BYTES[..len])).is_err());
+        }
+
+        // a hello that spans two records
+        let fragment = &CLIENT_HELLO_BYTES[5..];
+        assert_eq!(
+            ClientHello::<Cursor>::check_hello(&fragment[..300]).err(),
+            Some(StreamError::NeedMoreData(Some(512)))
+        );
+        assert_eq!(
+            ClientHello::<Cursor>::check_hello(&fragment[..2]).err(),
+            Some(StreamError::NeedMoreData(Some(4)))
+        );
+        let hello = ClientHello::parse(Cursor::new(fragment)).unwrap();
+        assert_eq!(hello.server_name(), Some("www.example.com"));
+    }
+
+    #[test]
+    fn malformed_lengths() {
+        // not a tls record
+        let res = TlsRecord::<Cursor>::check_record(b"GET / HTTP/1.1\r\n");
+        assert_eq!(res.err(), Some(StreamError::Malformed));
+        let res = TlsRecord::<Cursor>::check_record(&[22, 2]);
+        assert_eq!(res.err(), Some(StreamError::Malformed));
+        // the fragment exceeds the maximum length
+        let res = TlsRecord::<Cursor>::check_record(&[23, 3, 3, 0xff, 0xff]);
+        assert_eq!(res.err(), Some(StreamError::Malformed));
+
+        let malformed = |offset: usize, val: &[u8]| {
+            let mut bytes = CLIENT_HELLO_BYTES.to_vec();
+            bytes[offset..offset + val.len()].copy_from_slice(val);
+            TlsRecord::parse(Cursor::new(&bytes[..]))
+                .unwrap()
+                .client_hello()
+                .err()
+        };
+        // the session id exceeds 32 bytes
+        assert_eq!(malformed(43, &[33]), Some(StreamError::Malformed));
+        // the cipher suites exceed the message
+        assert_eq!(malformed(76, &[0xf0, 0]), Some(StreamError::Malformed));
+        // the extensions exceed or do not fill the message
+        assert_eq!(malformed(116, &[1, 0x90]), Some(StreamError::Malformed));
+        assert_eq!(malformed(116, &[1, 0x8e]), Some(StreamError::Malformed));
+        // the server name extension exceeds the extensions
+        assert_eq!(malformed(120, &[0xff, 0xff]), Some(StreamError::Malformed));
+
+        // a malformed server name list is ignored
+        let mut bytes = CLIENT_HELLO_BYTES.to_vec();
+        bytes[122..124].copy_from_slice(&[0, 0x20]);
+        let record = TlsRecord::parse(Cursor::new(&bytes[..])).unwrap();
+        assert_eq!(record.client_hello().unwrap().server_name(), None);
+        bytes[122..124].copy_from_slice(&[0, 0x12]);
+        // the host name exceeds the list
+        bytes[125..127].copy_from_slice(&[0, 0x10]);
+        let record = TlsRecord::parse(Cursor::new(&bytes[..])).unwrap();
+        assert_eq!(record.client_hello().unwrap().server_name(), None);
+    }
+}
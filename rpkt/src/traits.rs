@@ -1,5 +1,7 @@
 pub use bytes::Buf;
 
+use crate::ParseError;
+
 pub trait PktBuf: Buf {
     fn move_back(&mut self, cnt: usize);
     fn trim_off(&mut self, cnt: usize);
@@ -33,3 +35,31 @@ impl<T: PktMut + ?Sized> PktMut for &mut T {
         (**self).chunk_headroom()
     }
 }
+
+/// A protocol header that is parsed from the front of a byte slice.
+///
+/// The headers of a packet can be walked generically: parse a header with
+/// `Header::parse_next`, and dispatch the next header by the protocol field of
+/// the parsed one.
+pub trait Header<'a>: Sized {
+    /// Parse the header at the front of `buf`, all the length fields are
+    /// checked against `buf`.
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError>;
+
+    /// Return the length of the header, including the options.
+    fn header_len(&self) -> usize;
+
+    /// Return the bytes after the header.
+    ///
+    /// If the header has a length field, the bytes after the length, e.g. the
+    /// ethernet padding, are not included.
+    fn payload(&self) -> &'a [u8];
+
+    /// Parse the header at the front of `buf`, and advance `buf` to the payload.
+    #[inline]
+    fn parse_next(buf: &mut &'a [u8]) -> Result<Self, ParseError> {
+        let header = Self::parse(buf)?;
+        *buf = header.payload();
+        Ok(header)
+    }
+}
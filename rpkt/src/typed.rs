@@ -0,0 +1,217 @@
+//! Packets with a stack of layers that is fixed at compile time.
+//!
+//! `parse_untrusted` dispatches each layer by the protocol field of the
+//! previous one. When the encapsulation is known in advance, e.g. always
+//! ethernet, ipv4 and udp, `TypedPacket` parses exactly those layers and
+//! rejects the packets with any other stack. The layers that can be stacked
+//! are given by the `Encap` impls, so an invalid stack does not compile.
+
+use crate::ether::{EtherPacket, EtherType};
+use crate::ipv4::{IpProtocol, Ipv4Packet};
+use crate::ipv6::Ipv6Packet;
+use crate::tcp::TcpPacket;
+use crate::udp::UdpPacket;
+use crate::{Cursor, Header, ParseError};
+
+pub type Ether<'a> = EtherPacket<Cursor<'a>>;
+pub type Ipv4<'a> = Ipv4Packet<Cursor<'a>>;
+pub type Ipv6<'a> = Ipv6Packet<Cursor<'a>>;
+pub type Tcp<'a> = TcpPacket<Cursor<'a>>;
+pub type Udp<'a> = UdpPacket<Cursor<'a>>;
+
+/// A header that encapsulates the header `H` in its payload.
+pub trait Encap<'a, H: Header<'a>>: Header<'a> {
+    /// Check by the protocol field of the header that the payload is a `H`
+    /// header, return the parse error of `H` otherwise.
+    fn check_encap(&self) -> Result<(), ParseError>;
+}
+
+impl<'a> Encap<'a, Ipv4<'a>> for Ether<'a> {
+    #[inline]
+    fn check_encap(&self) -> Result<(), ParseError> {
+        check(self.ethertype() == EtherType::IPV4, ParseError::Ipv4)
+    }
+}
+
+impl<'a> Encap<'a, Ipv6<'a>> for Ether<'a> {
+    #[inline]
+    fn check_encap(&self) -> Result<(), ParseError> {
+        check(self.ethertype() == EtherType::IPV6, ParseError::Ipv6)
+    }
+}
+
+// A fragment other than the first one does not start with the transport
+// header.
+impl<'a> Encap<'a, Tcp<'a>> for Ipv4<'a> {
+    #[inline]
+    fn check_encap(&self) -> Result<(), ParseError> {
+        check(
+            self.protocol() == IpProtocol::TCP && self.frag_offset() == 0,
+            ParseError::Tcp,
+        )
+    }
+}
+
+impl<'a> Encap<'a, Udp<'a>> for Ipv4<'a> {
+    #[inline]
+    fn check_encap(&self) -> Result<(), ParseError> {
+        check(
+            self.protocol() == IpProtocol::UDP && self.frag_offset() == 0,
+            ParseError::Udp,
+        )
+    }
+}
+
+// The packets with ipv6 extension headers do not match.
+impl<'a> Encap<'a, Tcp<'a>> for Ipv6<'a> {
+    #[inline]
+    fn check_encap(&self) -> Result<(), ParseError> {
+        check(self.next_header() == IpProtocol::TCP, ParseError::Tcp)
+    }
+}
+
+impl<'a> Encap<'a, Udp<'a>> for Ipv6<'a> {
+    #[inline]
+    fn check_encap(&self) -> Result<(), ParseError> {
+        check(self.next_header() == IpProtocol::UDP, ParseError::Udp)
+    }
+}
+
+#[inline]
+fn check(matched: bool, err: ParseError) -> Result<(), ParseError> {
+    if matched {
+        Ok(())
+    } else {
+        Err(err)
+    }
+}
+
+/// A packet of the layers `L2`, `L3` and `L4`, which are all validated by a
+/// single `parse`.
+///
+/// The accessors of the layers need no checks, e.g. the udp ports of a
+/// `TypedPacket::<Ether, Ipv4, Udp>` are read by `l4().dest_port()`.
+#[derive(Debug)]
+pub struct TypedPacket<L2, L3, L4> {
+    l2: L2,
+    l3: L3,
+    l4: L4,
+}
+
+impl<'a, L2, L3, L4> TypedPacket<L2, L3, L4>
+where
+    L2: Encap<'a, L3>,
+    L3: Encap<'a, L4>,
+    L4: Header<'a>,
+{
+    /// Parse the three layers at the front of `buf`.
+    ///
+    /// Return the error of the first layer that fails to parse, or of the
+    /// layer that is not encapsulated by the previous one.
+    #[inline]
+    pub fn parse(mut buf: &'a [u8]) -> Result<Self, ParseError> {
+        let l2 = L2::parse_next(&mut buf)?;
+        l2.check_encap()?;
+        let l3 = L3::parse_next(&mut buf)?;
+        l3.check_encap()?;
+        let l4 = L4::parse(buf)?;
+        Ok(Self { l2, l3, l4 })
+    }
+
+    #[inline]
+    pub fn l2(&self) -> &L2 {
+        &self.l2
+    }
+
+    #[inline]
+    pub fn l3(&self) -> &L3 {
+        &self.l3
+    }
+
+    #[inline]
+    pub fn l4(&self) -> &L4 {
+        &self.l4
+    }
+
+    /// Return the total length of the three headers.
+    #[inline]
+    pub fn headers_len(&self) -> usize {
+        self.l2.header_len() + self.l3.header_len() + self.l4.header_len()
+    }
+
+    /// Return the payload of the `L4` header.
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        self.l4.payload()
+    }
+
+    #[inline]
+    pub fn into_parts(self) -> (L2, L3, L4) {
+        (self.l2, self.l3, self.l4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::malformed::TCP_FRAME;
+
+    // Replace the tcp segment of `TCP_FRAME` with a udp datagram of
+    // `payload`.
+    fn build_udp_frame(payload: &[u8]) -> Vec<u8> {
+        let udp_len = 8 + payload.len();
+        let mut bytes = TCP_FRAME[..34].to_vec();
+        bytes[16..18].copy_from_slice(&(20 + udp_len as u16).to_be_bytes());
+        bytes[23] = 17;
+        bytes.extend_from_slice(&5353_u16.to_be_bytes());
+        bytes.extend_from_slice(&53_u16.to_be_bytes());
+        bytes.extend_from_slice(&(udp_len as u16).to_be_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    #[test]
+    fn parse_matching_stack() {
+        let bytes = build_udp_frame(b"hello");
+        let pkt = TypedPacket::<Ether, Ipv4, Udp>::parse(&bytes[..]).unwrap();
+        assert_eq!(pkt.l2().ethertype(), EtherType::IPV4);
+        assert_eq!(pkt.l3().protocol(), IpProtocol::UDP);
+        assert_eq!(pkt.l4().source_port(), 5353);
+        assert_eq!(pkt.l4().dest_port(), 53);
+        assert_eq!(pkt.headers_len(), 14 + 20 + 8);
+        assert_eq!(pkt.payload(), b"hello");
+
+        let pkt = TypedPacket::<Ether, Ipv4, Tcp>::parse(&TCP_FRAME[..]).unwrap();
+        let (_, ippkt, tcppkt) = pkt.into_parts();
+        assert_eq!(ippkt.time_to_live(), 64);
+        assert_eq!(tcppkt.dst_port(), 80);
+        assert_eq!(tcppkt.header_len(), 40);
+    }
+
+    #[test]
+    fn parse_mismatched_stack() {
+        // a tcp frame is not a udp frame
+        let res = TypedPacket::<Ether, Ipv4, Udp>::parse(&TCP_FRAME[..]);
+        assert_eq!(res.err(), Some(ParseError::Udp));
+        let bytes = build_udp_frame(b"hello");
+        let res = TypedPacket::<Ether, Ipv4, Tcp>::parse(&bytes[..]);
+        assert_eq!(res.err(), Some(ParseError::Tcp));
+
+        // an ipv4 frame is not an ipv6 frame
+        let res = TypedPacket::<Ether, Ipv6, Tcp>::parse(&TCP_FRAME[..]);
+        assert_eq!(res.err(), Some(ParseError::Ipv6));
+
+        // a non-first fragment does not carry the udp header
+        let mut fragment = bytes.clone();
+        fragment[20..22].copy_from_slice(&[0x00, 0x10]);
+        let res = TypedPacket::<Ether, Ipv4, Udp>::parse(&fragment[..]);
+        assert_eq!(res.err(), Some(ParseError::Udp));
+
+        // a truncated frame fails at the truncated layer
+        let res = TypedPacket::<Ether, Ipv4, Udp>::parse(&bytes[..30]);
+        assert_eq!(res.err(), Some(ParseError::Ipv4));
+        let res = TypedPacket::<Ether, Ipv4, Tcp>::parse(&TCP_FRAME[..10]);
+        assert_eq!(res.err(), Some(ParseError::Ether));
+    }
+}
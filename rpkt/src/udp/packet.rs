@@ -3,6 +3,7 @@ use bytes::Buf;
 use crate::checksum_utils;
 use crate::ipv4::{Ipv4Addr, Ipv4PseudoHeader};
 use crate::{Cursor, CursorMut};
+use crate::{Header, ParseError};
 use crate::{PktBuf, PktMut};
 
 use super::header::{UdpHeader, UDP_HEADER_LEN};
@@ -129,6 +130,23 @@ impl<'a> UdpPacket<Cursor<'a>> {
     }
 }
 
+impl<'a> Header<'a> for UdpPacket<Cursor<'a>> {
+    #[inline]
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseError> {
+        UdpPacket::parse(Cursor::new(buf)).map_err(|_| ParseError::Udp)
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        UDP_HEADER_LEN
+    }
+
+    #[inline]
+    fn payload(&self) -> &'a [u8] {
+        self.cursor_payload().chunk_shared_lifetime()
+    }
+}
+
 impl<'a> UdpPacket<CursorMut<'a>> {
     #[inline]
     pub fn split(self) -> (UdpHeader<&'a mut [u8]>, CursorMut<'a>) {
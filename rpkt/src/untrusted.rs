@@ -0,0 +1,1341 @@
+use std::collections::HashMap;
+
+use crate::arp::ArpPacket;
+use crate::diameter::{DiameterCommand, DiameterPacket, DIAMETER_HEADER_LEN, DIAMETER_PORT};
+use crate::ether::{EtherPacket, EtherType};
+use crate::geneve::{GeneveOptionIter, GenevePacket, GENEVE_UDP_PORT};
+use crate::icmpv4::{IcmpType, Icmpv4Packet};
+use crate::icmpv6::{Icmpv6MsgType, Icmpv6Packet};
+use crate::igmp::{IgmpMsgType, IgmpPacket};
+use crate::ipsec::{IpsecAuthHdrPacket, IpsecEspPacket};
+use crate::ipv4::{IpProtocol, Ipv4Addr, Ipv4OptionIter, Ipv4Packet};
+use crate::ipv6::extentions::{FragPacket, Ipv6OptionPacket, RoutingPacket};
+use crate::ipv6::{Ipv6Addr, Ipv6Packet};
+use crate::l2tp::{L2tpPacket, L2TP_UDP_PORT};
+use crate::pppoe::{PppPacket, PppProtocol, PppoePacket};
+use crate::ptp::{
+    PtpMessageType, PtpPacket, PTP_EVENT_UDP_PORT, PTP_GENERAL_UDP_PORT, PTP_HEADER_LEN,
+};
+use crate::quic::{QuicPacket, QuicPacketType, QuicVersion, QUIC_PORT};
+use crate::radius::{
+    RadiusCode, RadiusPacket, RADIUS_ACCT_UDP_PORT, RADIUS_AUTH_UDP_PORT, RADIUS_HEADER_LEN,
+};
+use crate::tcp::{TcpOptionIter, TcpPacket};
+use crate::udp::UdpPacket;
+use crate::Cursor;
+
+pub use crate::error::ParseError;
+
+/// The network layer of a packet parsed by `parse_untrusted`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    Arp,
+    Ipv4 {
+        src: Ipv4Addr,
+        dst: Ipv4Addr,
+        protocol: IpProtocol,
+    },
+    /// `next_header` is the protocol after the ipv6 extension headers.
+    Ipv6 {
+        src: Ipv6Addr,
+        dst: Ipv6Addr,
+        next_header: IpProtocol,
+    },
+    /// A ptp message over ethernet, the payload is the message body.
+    Ptp {
+        message_type: PtpMessageType,
+        sequence_id: u16,
+    },
+    /// An ethertype that is not parsed.
+    Other(EtherType),
+}
+
+/// The transport layer of a packet parsed by `parse_untrusted`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Tcp {
+        src_port: u16,
+        dst_port: u16,
+    },
+    Udp {
+        src_port: u16,
+        dst_port: u16,
+    },
+    /// A geneve tunnel over udp, the payload is the inner frame of
+    /// `protocol_type`.
+    Geneve {
+        src_port: u16,
+        vni: u32,
+        protocol_type: EtherType,
+    },
+    Icmpv4 {
+        icmp_type: IcmpType,
+        code: u8,
+    },
+    Icmpv6 {
+        msg_type: Icmpv6MsgType,
+        code: u8,
+    },
+    Igmp {
+        msg_type: IgmpMsgType,
+    },
+    /// An ipsec esp packet. The payload is encrypted, so it is left as an
+    /// opaque blob that starts past the esp header.
+    Esp {
+        spi: u32,
+        seq_num: u32,
+    },
+    /// An ipsec authentication header, the payload is the packet of
+    /// `next_header` that follows the icv.
+    Ah {
+        spi: u32,
+        seq_num: u32,
+        next_header: IpProtocol,
+    },
+    /// A radius packet over udp, the payload is the attribute list.
+    Radius {
+        src_port: u16,
+        dst_port: u16,
+        code: RadiusCode,
+        identifier: u8,
+    },
+    /// A udp datagram that starts with a quic long header packet, the payload
+    /// is the quic packet, whose connection ids are read with `QuicPacket`.
+    Quic {
+        src_port: u16,
+        dst_port: u16,
+        version: QuicVersion,
+        packet_type: QuicPacketType,
+    },
+    /// A tcp segment that starts with a diameter message, the payload is the
+    /// avp list of the message.
+    Diameter {
+        src_port: u16,
+        dst_port: u16,
+        command_code: DiameterCommand,
+        request: bool,
+        application_id: u32,
+    },
+    /// An l2tp message over udp or ip. The payload is the ppp frame of an
+    /// l2tpv2 data message, the pseudowire frame of an l2tpv3 data message, or
+    /// the avp list of a control message. The l2tpv3 sessions are assumed to
+    /// have no cookie.
+    L2tp {
+        version: u8,
+        tunnel_id: Option<u32>,
+        session_id: u32,
+        control: bool,
+    },
+    /// A ptp message over udp, the payload is the message body.
+    Ptp {
+        src_port: u16,
+        dst_port: u16,
+        message_type: PtpMessageType,
+        sequence_id: u16,
+    },
+    /// A protocol that is not parsed.
+    Other(IpProtocol),
+}
+
+/// The layers of a packet parsed by `parse_untrusted`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParsedPacket {
+    pub network: Network,
+    /// The transport layer, which is `None` for the non-ip packets and the
+    /// non-first fragments.
+    pub transport: Option<Transport>,
+    /// The offset of the payload of the innermost parsed layer.
+    pub payload_offset: usize,
+}
+
+pub type ParseResult = Result<ParsedPacket, ParseError>;
+
+/// The handler of a custom protocol, see `ProtocolRegistry`.
+pub type ProtocolHandler = Box<dyn Fn(&[u8]) -> Option<usize> + Send + Sync>;
+
+/// The handlers of the user-defined protocols, which `parse_untrusted_with`
+/// dispatches to.
+///
+/// A handler is passed the bytes from the start of the protocol header to the
+/// end of the packet, and returns the length of the header, or `None` if the
+/// header is malformed. The payload of the packet then starts past the header.
+/// A handler that is registered for a built-in protocol overrides it.
+#[derive(Default)]
+pub struct ProtocolRegistry {
+    ethertypes: HashMap<EtherType, ProtocolHandler>,
+    ip_protocols: HashMap<IpProtocol, ProtocolHandler>,
+}
+
+impl ProtocolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `handler` for the network protocol of `ethertype`, which is
+    /// reported as `Network::Other`.
+    pub fn register_ethertype<F>(&mut self, ethertype: EtherType, handler: F)
+    where
+        F: Fn(&[u8]) -> Option<usize> + Send + Sync + 'static,
+    {
+        self.ethertypes.insert(ethertype, Box::new(handler));
+    }
+
+    /// Register `handler` for the transport protocol of `protocol`, over both
+    /// ipv4 and ipv6, which is reported as `Transport::Other`.
+    pub fn register_ip_protocol<F>(&mut self, protocol: IpProtocol, handler: F)
+    where
+        F: Fn(&[u8]) -> Option<usize> + Send + Sync + 'static,
+    {
+        self.ip_protocols.insert(protocol, Box::new(handler));
+    }
+}
+
+// Return the payload offset of the custom protocol header at the start of
+// `buf`, the length returned by the handler is checked against the packet.
+fn parse_custom(handler: &ProtocolHandler, buf: Cursor) -> Result<usize, ParseError> {
+    let bytes = buf.chunk_shared_lifetime();
+    match handler(bytes) {
+        Some(header_len) if header_len <= bytes.len() => Ok(buf.cursor() + header_len),
+        _ => Err(ParseError::Custom),
+    }
+}
+
+/// Parse all the layers of the ethernet frame in `bytes`.
+///
+/// This is the entry point for the untrusted input, e.g. the target of a fuzz
+/// harness. It is total: it never panics and always terminates on any input.
+/// Every length field is checked against the input before it is used, and the
+/// option lists and the ipv6 extension header chain are walked with iterators
+/// that consume at least one byte per step.
+///
+/// The protocols over udp and tcp are guessed from the well-known ports of
+/// either side, so a payload that fails to parse as the protocol of its port
+/// is reported as plain `Transport::Udp` or `Transport::Tcp` instead of an
+/// error. The protocols that are identified by an ethertype or an ip protocol
+/// number must parse.
+pub fn parse_untrusted(bytes: &[u8]) -> ParseResult {
+    parse_frame(bytes, None)
+}
+
+/// Parse all the layers of the ethernet frame in `bytes` like
+/// `parse_untrusted`, dispatching the protocols of `registry` to their
+/// handlers.
+pub fn parse_untrusted_with(bytes: &[u8], registry: &ProtocolRegistry) -> ParseResult {
+    parse_frame(bytes, Some(registry))
+}
+
+fn parse_frame(bytes: &[u8], registry: Option<&ProtocolRegistry>) -> ParseResult {
+    let ethpkt = EtherPacket::parse(Cursor::new(bytes)).map_err(|_| ParseError::Ether)?;
+    let ethertype = ethpkt.ethertype();
+    if let Some(handler) = registry.and_then(|registry| registry.ethertypes.get(&ethertype)) {
+        return Ok(ParsedPacket {
+            network: Network::Other(ethertype),
+            transport: None,
+            payload_offset: parse_custom(handler, ethpkt.payload())?,
+        });
+    }
+    match ethertype {
+        EtherType::ARP => {
+            let arppkt = ArpPacket::parse(ethpkt.payload()).map_err(|_| ParseError::Arp)?;
+            Ok(ParsedPacket {
+                network: Network::Arp,
+                transport: None,
+                payload_offset: arppkt.buf().cursor(),
+            })
+        }
+        EtherType::IPV4 => parse_ipv4(ethpkt.payload(), registry),
+        EtherType::IPV6 => parse_ipv6(ethpkt.payload(), registry),
+        EtherType::PPPOE_SESSION => parse_pppoe(ethpkt.payload(), registry),
+        EtherType::PTP => {
+            let ptppkt = PtpPacket::parse(ethpkt.payload()).map_err(|_| ParseError::Ptp)?;
+            Ok(ParsedPacket {
+                network: Network::Ptp {
+                    message_type: ptppkt.message_type(),
+                    sequence_id: ptppkt.sequence_id(),
+                },
+                transport: None,
+                payload_offset: ptppkt.buf().cursor() + PTP_HEADER_LEN,
+            })
+        }
+        ethertype => Ok(ParsedPacket {
+            network: Network::Other(ethertype),
+            transport: None,
+            payload_offset: ethpkt.payload().cursor(),
+        }),
+    }
+}
+
+// The pppoe and ppp headers are transparent, the network layer is the ip
+// packet carried by the ppp session.
+fn parse_pppoe(buf: Cursor, registry: Option<&ProtocolRegistry>) -> ParseResult {
+    let pppoepkt = PppoePacket::parse(buf).map_err(|_| ParseError::Pppoe)?;
+    let ppppkt = PppPacket::parse(pppoepkt.payload()).map_err(|_| ParseError::Pppoe)?;
+    match ppppkt.protocol() {
+        PppProtocol::IPV4 => parse_ipv4(ppppkt.payload(), registry),
+        PppProtocol::IPV6 => parse_ipv6(ppppkt.payload(), registry),
+        _ => Ok(ParsedPacket {
+            network: Network::Other(EtherType::PPPOE_SESSION),
+            transport: None,
+            payload_offset: ppppkt.payload().cursor(),
+        }),
+    }
+}
+
+fn parse_ipv4(buf: Cursor, registry: Option<&ProtocolRegistry>) -> ParseResult {
+    let ippkt = Ipv4Packet::parse(buf).map_err(|_| ParseError::Ipv4)?;
+    if !Ipv4OptionIter::check_option_bytes(ippkt.option_bytes()) {
+        return Err(ParseError::Ipv4Options);
+    }
+
+    let network = Network::Ipv4 {
+        src: ippkt.source_ip(),
+        dst: ippkt.dest_ip(),
+        protocol: ippkt.protocol(),
+    };
+    let protocol = ippkt.protocol();
+    // the non-first fragments do not carry the transport header
+    let non_first_frag = ippkt.frag_offset() != 0;
+    let payload = ippkt.payload();
+    if non_first_frag {
+        return Ok(ParsedPacket {
+            network,
+            transport: None,
+            payload_offset: payload.cursor(),
+        });
+    }
+
+    if let Some(handler) = registry.and_then(|registry| registry.ip_protocols.get(&protocol)) {
+        return Ok(ParsedPacket {
+            network,
+            transport: Some(Transport::Other(protocol)),
+            payload_offset: parse_custom(handler, payload)?,
+        });
+    }
+
+    let (transport, payload_offset) = match protocol {
+        IpProtocol::ICMP => {
+            let icmppkt = Icmpv4Packet::parse(payload).map_err(|_| ParseError::Icmpv4)?;
+            let transport = Transport::Icmpv4 {
+                icmp_type: icmppkt.icmp_type(),
+                code: icmppkt.code(),
+            };
+            (transport, icmppkt.data().cursor())
+        }
+        IpProtocol::IGMP => {
+            let igmppkt = IgmpPacket::parse(payload).map_err(|_| ParseError::Igmp)?;
+            // only the igmpv3 reports carry the group records
+            if igmppkt.is_v3_report() && !igmppkt.check_group_records() {
+                return Err(ParseError::Igmp);
+            }
+            let msg_type = igmppkt.msg_type();
+            (Transport::Igmp { msg_type }, igmppkt.release().cursor())
+        }
+        protocol => parse_transport(protocol, payload)?,
+    };
+    Ok(ParsedPacket {
+        network,
+        transport: Some(transport),
+        payload_offset,
+    })
+}
+
+fn parse_ipv6(buf: Cursor, registry: Option<&ProtocolRegistry>) -> ParseResult {
+    let ippkt = Ipv6Packet::parse(buf).map_err(|_| ParseError::Ipv6)?;
+    let (src, dst) = (ippkt.source_ip(), ippkt.dest_ip());
+    let mut next_header = ippkt.next_header();
+    let mut payload = ippkt.payload();
+
+    // Each extension header is at least 8 bytes long, so the chain is bounded
+    // by the length of the input.
+    let mut non_first_frag = false;
+    loop {
+        match next_header {
+            IpProtocol::HOPOPT | IpProtocol::IPV6_OPTS => {
+                let optpkt =
+                    Ipv6OptionPacket::parse(payload).map_err(|_| ParseError::Ipv6ExtHeader)?;
+                next_header = optpkt.next_header();
+                payload = optpkt.payload();
+            }
+            IpProtocol::IPV6_ROUTE => {
+                let routingpkt =
+                    RoutingPacket::parse(payload).map_err(|_| ParseError::Ipv6ExtHeader)?;
+                next_header = routingpkt.next_header();
+                payload = routingpkt.payload();
+            }
+            IpProtocol::IPV6_FRAG => {
+                let fragpkt = FragPacket::parse(payload).map_err(|_| ParseError::Ipv6ExtHeader)?;
+                next_header = fragpkt.next_header();
+                non_first_frag = fragpkt.frag_off() != 0;
+                payload = fragpkt.payload();
+                if non_first_frag {
+                    break;
+                }
+            }
+            _ => break,
+        }
+    }
+
+    let network = Network::Ipv6 {
+        src,
+        dst,
+        next_header,
+    };
+    if non_first_frag || next_header == IpProtocol::IPV6_NO_NXT {
+        return Ok(ParsedPacket {
+            network,
+            transport: None,
+            payload_offset: payload.cursor(),
+        });
+    }
+
+    if let Some(handler) = registry.and_then(|registry| registry.ip_protocols.get(&next_header)) {
+        return Ok(ParsedPacket {
+            network,
+            transport: Some(Transport::Other(next_header)),
+            payload_offset: parse_custom(handler, payload)?,
+        });
+    }
+
+    let (transport, payload_offset) = match next_header {
+        IpProtocol::ICMPV6 => {
+            let icmppkt = Icmpv6Packet::parse(payload).map_err(|_| ParseError::Icmpv6)?;
+            let transport = Transport::Icmpv6 {
+                msg_type: icmppkt.msg_type(),
+                code: icmppkt.code(),
+            };
+            (transport, icmppkt.release().cursor())
+        }
+        protocol => parse_transport(protocol, payload)?,
+    };
+    Ok(ParsedPacket {
+        network,
+        transport: Some(transport),
+        payload_offset,
+    })
+}
+
+// Parse the transport layers that are shared by ipv4 and ipv6.
+fn parse_transport(protocol: IpProtocol, buf: Cursor) -> Result<(Transport, usize), ParseError> {
+    match protocol {
+        IpProtocol::TCP => {
+            let tcppkt = TcpPacket::parse(buf).map_err(|_| ParseError::Tcp)?;
+            if !TcpOptionIter::check_option_bytes(tcppkt.option_bytes()) {
+                return Err(ParseError::TcpOptions);
+            }
+            let (src_port, dst_port) = (tcppkt.src_port(), tcppkt.dst_port());
+            let mut payload = tcppkt.payload();
+            if src_port == DIAMETER_PORT || dst_port == DIAMETER_PORT {
+                match parse_diameter(src_port, dst_port, payload) {
+                    Ok(parsed) => return Ok(parsed),
+                    Err(buf) => payload = buf,
+                }
+            }
+            let transport = Transport::Tcp { src_port, dst_port };
+            Ok((transport, payload.cursor()))
+        }
+        IpProtocol::UDP => {
+            let udppkt = UdpPacket::parse(buf).map_err(|_| ParseError::Udp)?;
+            let (src_port, dst_port) = (udppkt.source_port(), udppkt.dest_port());
+            let either_port =
+                |ports: &[u16]| ports.contains(&src_port) || ports.contains(&dst_port);
+            let mut payload = udppkt.payload();
+            // the protocols are guessed from the ports, a payload that fails to
+            // parse is tried with the next protocol and is plain udp at last
+            if either_port(&[GENEVE_UDP_PORT]) {
+                match parse_geneve(src_port, payload) {
+                    Ok(parsed) => return Ok(parsed),
+                    Err(buf) => payload = buf,
+                }
+            }
+            if either_port(&[RADIUS_AUTH_UDP_PORT, RADIUS_ACCT_UDP_PORT]) {
+                match parse_radius(src_port, dst_port, payload) {
+                    Ok(parsed) => return Ok(parsed),
+                    Err(buf) => payload = buf,
+                }
+            }
+            if either_port(&[L2TP_UDP_PORT]) {
+                match L2tpPacket::parse(payload) {
+                    Ok(l2tppkt) => return Ok(l2tp_transport(&l2tppkt)),
+                    Err(buf) => payload = buf,
+                }
+            }
+            if either_port(&[PTP_EVENT_UDP_PORT, PTP_GENERAL_UDP_PORT]) {
+                match parse_ptp(src_port, dst_port, payload) {
+                    Ok(parsed) => return Ok(parsed),
+                    Err(buf) => payload = buf,
+                }
+            }
+            if either_port(&[QUIC_PORT]) {
+                match parse_quic(src_port, dst_port, payload) {
+                    Ok(parsed) => return Ok(parsed),
+                    Err(buf) => payload = buf,
+                }
+            }
+            let transport = Transport::Udp { src_port, dst_port };
+            Ok((transport, payload.cursor()))
+        }
+        IpProtocol::ESP => {
+            let esppkt = IpsecEspPacket::parse(buf).map_err(|_| ParseError::Esp)?;
+            let transport = Transport::Esp {
+                spi: esppkt.spi(),
+                seq_num: esppkt.seq_num(),
+            };
+            Ok((transport, esppkt.payload().cursor()))
+        }
+        IpProtocol::AH => {
+            let ahpkt = IpsecAuthHdrPacket::parse(buf).map_err(|_| ParseError::Ah)?;
+            let transport = Transport::Ah {
+                spi: ahpkt.spi(),
+                seq_num: ahpkt.seq_num(),
+                next_header: ahpkt.next_header(),
+            };
+            Ok((transport, ahpkt.payload().cursor()))
+        }
+        IpProtocol::L2TP => {
+            let l2tppkt = L2tpPacket::parse_ip(buf, 0).map_err(|_| ParseError::L2tp)?;
+            Ok(l2tp_transport(&l2tppkt))
+        }
+        protocol => Ok((Transport::Other(protocol), buf.cursor())),
+    }
+}
+
+// The payload starts past the l2tp header.
+fn l2tp_transport(l2tppkt: &L2tpPacket<Cursor>) -> (Transport, usize) {
+    let transport = Transport::L2tp {
+        version: l2tppkt.version(),
+        tunnel_id: l2tppkt.tunnel_id(),
+        session_id: l2tppkt.session_id(),
+        control: l2tppkt.is_control(),
+    };
+    (transport, l2tppkt.buf().cursor() + l2tppkt.header_len())
+}
+
+// The option length of geneve is counted in 4-byte units, the inner frame
+// starts past the options.
+fn parse_geneve(src_port: u16, buf: Cursor) -> Result<(Transport, usize), Cursor> {
+    let genevepkt = GenevePacket::parse(buf)?;
+    if !GeneveOptionIter::check_option_bytes(genevepkt.option_bytes()) {
+        return Err(genevepkt.release());
+    }
+    let transport = Transport::Geneve {
+        src_port,
+        vni: genevepkt.vni(),
+        protocol_type: genevepkt.protocol_type(),
+    };
+    Ok((transport, genevepkt.payload().cursor()))
+}
+
+// The radius packets are sent both to and from the radius ports.
+fn parse_radius(src_port: u16, dst_port: u16, buf: Cursor) -> Result<(Transport, usize), Cursor> {
+    let radiuspkt = RadiusPacket::parse(buf)?;
+    if !radiuspkt.check_attrs() {
+        return Err(radiuspkt.release());
+    }
+    let transport = Transport::Radius {
+        src_port,
+        dst_port,
+        code: radiuspkt.code(),
+        identifier: radiuspkt.identifier(),
+    };
+    Ok((transport, radiuspkt.buf().cursor() + RADIUS_HEADER_LEN))
+}
+
+// The payload starts past the common header of the ptp message.
+fn parse_ptp(src_port: u16, dst_port: u16, buf: Cursor) -> Result<(Transport, usize), Cursor> {
+    let ptppkt = PtpPacket::parse(buf)?;
+    let transport = Transport::Ptp {
+        src_port,
+        dst_port,
+        message_type: ptppkt.message_type(),
+        sequence_id: ptppkt.sequence_id(),
+    };
+    Ok((transport, ptppkt.buf().cursor() + PTP_HEADER_LEN))
+}
+
+// Only the long header packets are recognized, the short header packets carry
+// no version and look like random bytes.
+fn parse_quic(src_port: u16, dst_port: u16, buf: Cursor) -> Result<(Transport, usize), Cursor> {
+    let quicpkt = QuicPacket::parse(buf)?;
+    let transport = Transport::Quic {
+        src_port,
+        dst_port,
+        version: quicpkt.version(),
+        packet_type: quicpkt.packet_type(),
+    };
+    Ok((transport, quicpkt.buf().cursor()))
+}
+
+// Tcp is a byte stream, so a segment on the diameter port may carry the rest
+// of a message or no data at all. Only a segment that starts with a valid
+// diameter message is reported as diameter, the others are plain tcp.
+fn parse_diameter(src_port: u16, dst_port: u16, buf: Cursor) -> Result<(Transport, usize), Cursor> {
+    let diameterpkt = DiameterPacket::parse(buf)?;
+    if !diameterpkt.check_avps() {
+        return Err(diameterpkt.release());
+    }
+    let transport = Transport::Diameter {
+        src_port,
+        dst_port,
+        command_code: diameterpkt.command_code(),
+        request: diameterpkt.request(),
+        application_id: diameterpkt.application_id(),
+    };
+    Ok((transport, diameterpkt.buf().cursor() + DIAMETER_HEADER_LEN))
+}
+
+/// The entry point of the fuzz targets, which only checks that
+/// `parse_untrusted` returns.
+#[cfg(fuzzing)]
+pub fn fuzz_parse_untrusted(bytes: &[u8]) {
+    let _ = parse_untrusted(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::malformed::TCP_FRAME;
+    use crate::Header;
+
+    #[test]
+    fn parse_tcp_frame() {
+        let parsed = parse_untrusted(&TCP_FRAME[..]).unwrap();
+        assert_eq!(
+            parsed.network,
+            Network::Ipv4 {
+                src: Ipv4Addr([192, 168, 1, 140]),
+                dst: Ipv4Addr([174, 143, 213, 184]),
+                protocol: IpProtocol::TCP,
+            }
+        );
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::Tcp {
+                src_port: 57678,
+                dst_port: 80
+            })
+        );
+        assert_eq!(parsed.payload_offset, TCP_FRAME.len());
+
+        // every truncation of a valid frame is handled
+        for len in 0..TCP_FRAME.len() {
+            let _ = parse_untrusted(&TCP_FRAME[..len]);
+        }
+        assert_eq!(parse_untrusted(&[][..]), Err(ParseError::Ether));
+        assert_eq!(parse_untrusted(&TCP_FRAME[..20]), Err(ParseError::Ipv4));
+    }
+
+    // Wrap `TCP_FRAME` in a geneve tunnel with `options`.
+    fn build_geneve_frame(options: &[u8]) -> Vec<u8> {
+        let udp_len = 8 + 8 + options.len() + TCP_FRAME.len();
+        let mut bytes = TCP_FRAME[..34].to_vec();
+        bytes[16..18].copy_from_slice(&(20 + udp_len as u16).to_be_bytes());
+        bytes[23] = 17;
+        bytes.extend_from_slice(&50000_u16.to_be_bytes());
+        bytes.extend_from_slice(&GENEVE_UDP_PORT.to_be_bytes());
+        bytes.extend_from_slice(&(udp_len as u16).to_be_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(&[(options.len() / 4) as u8, 0, 0x65, 0x58, 0, 0, 42, 0]);
+        bytes.extend_from_slice(options);
+        bytes.extend_from_slice(&TCP_FRAME[..]);
+        bytes
+    }
+
+    #[test]
+    fn parse_geneve_frame() {
+        let geneve = Transport::Geneve {
+            src_port: 50000,
+            vni: 42,
+            protocol_type: EtherType::TEB,
+        };
+
+        // no options
+        let bytes = build_geneve_frame(&[]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(parsed.transport, Some(geneve));
+        assert_eq!(parsed.payload_offset, 50);
+        let inner = parse_untrusted(&bytes[parsed.payload_offset..]).unwrap();
+        assert_eq!(inner, parse_untrusted(&TCP_FRAME[..]).unwrap());
+
+        // two options, with 4 and 0 bytes of data
+        let options = [
+            0x01, 0x02, 0x80, 0x01, 0xaa, 0xbb, 0xcc, 0xdd, 0xff, 0xff, 0x03, 0x00,
+        ];
+        let bytes = build_geneve_frame(&options[..]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(parsed.transport, Some(geneve));
+        assert_eq!(parsed.payload_offset, 62);
+        let inner = parse_untrusted(&bytes[parsed.payload_offset..]).unwrap();
+        assert_eq!(inner, parse_untrusted(&TCP_FRAME[..]).unwrap());
+
+        // the port is only a guess, an invalid geneve header is plain udp: the
+        // last option exceeds the option length
+        let mut bytes = bytes;
+        bytes[61] = 0x01;
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::Udp {
+                src_port: 50000,
+                dst_port: GENEVE_UDP_PORT
+            })
+        );
+        assert_eq!(parsed.payload_offset, 42);
+        // the option length exceeds the packet
+        let mut bytes = build_geneve_frame(&[]);
+        bytes[42] = 0x3f;
+        bytes.truncate(100);
+        bytes[16..18].copy_from_slice(&86_u16.to_be_bytes());
+        bytes[38..40].copy_from_slice(&66_u16.to_be_bytes());
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::Udp {
+                src_port: 50000,
+                dst_port: GENEVE_UDP_PORT
+            })
+        );
+    }
+
+    // Wrap the ip packet of `TCP_FRAME` in a pppoe session, with a ppp
+    // protocol field of `protocol`.
+    fn build_pppoe_frame(protocol: &[u8]) -> Vec<u8> {
+        let ip_packet = &TCP_FRAME[14..];
+        let mut bytes = TCP_FRAME[..12].to_vec();
+        bytes.extend_from_slice(&[0x88, 0x64, 0x11, 0x00, 0x00, 0x07]);
+        bytes.extend_from_slice(&((protocol.len() + ip_packet.len()) as u16).to_be_bytes());
+        bytes.extend_from_slice(protocol);
+        bytes.extend_from_slice(ip_packet);
+        bytes
+    }
+
+    // Walk the headers generically with `Header`, return the header lengths and
+    // the payload of the innermost header.
+    fn walk_headers(mut buf: &[u8]) -> Result<(Vec<usize>, &[u8]), ParseError> {
+        fn next<'a, H: Header<'a>>(
+            buf: &mut &'a [u8],
+            lens: &mut Vec<usize>,
+        ) -> Result<H, ParseError> {
+            let header = H::parse_next(buf)?;
+            lens.push(header.header_len());
+            Ok(header)
+        }
+
+        let mut lens = Vec::new();
+        let ethpkt: EtherPacket<Cursor> = next(&mut buf, &mut lens)?;
+        let mut ethertype = ethpkt.ethertype();
+        if ethertype == EtherType::PPPOE_SESSION {
+            next::<PppoePacket<Cursor>>(&mut buf, &mut lens)?;
+            ethertype = match next::<PppPacket<Cursor>>(&mut buf, &mut lens)?.protocol() {
+                PppProtocol::IPV4 => EtherType::IPV4,
+                _ => return Ok((lens, buf)),
+            };
+        }
+        let protocol = match ethertype {
+            EtherType::IPV4 => next::<Ipv4Packet<Cursor>>(&mut buf, &mut lens)?.protocol(),
+            EtherType::IPV6 => next::<Ipv6Packet<Cursor>>(&mut buf, &mut lens)?.next_header(),
+            EtherType::ARP => {
+                next::<ArpPacket<Cursor>>(&mut buf, &mut lens)?;
+                return Ok((lens, buf));
+            }
+            _ => return Ok((lens, buf)),
+        };
+        match protocol {
+            IpProtocol::TCP => {
+                next::<TcpPacket<Cursor>>(&mut buf, &mut lens)?;
+            }
+            IpProtocol::UDP => {
+                let udppkt: UdpPacket<Cursor> = next(&mut buf, &mut lens)?;
+                if udppkt.dest_port() == GENEVE_UDP_PORT {
+                    next::<GenevePacket<Cursor>>(&mut buf, &mut lens)?;
+                }
+            }
+            IpProtocol::ICMP => {
+                next::<Icmpv4Packet<Cursor>>(&mut buf, &mut lens)?;
+            }
+            IpProtocol::ICMPV6 => {
+                next::<Icmpv6Packet<Cursor>>(&mut buf, &mut lens)?;
+            }
+            IpProtocol::IGMP => {
+                next::<IgmpPacket<Cursor>>(&mut buf, &mut lens)?;
+            }
+            _ => {}
+        }
+        Ok((lens, buf))
+    }
+
+    #[test]
+    fn generic_header_walk() {
+        let (lens, payload) = walk_headers(&TCP_FRAME[..]).unwrap();
+        assert_eq!(lens, [14, 20, 40]);
+        assert!(payload.is_empty());
+
+        // the geneve header with its options, followed by the inner frame
+        let options = [0x01, 0x02, 0x80, 0x01, 0xaa, 0xbb, 0xcc, 0xdd];
+        let bytes = build_geneve_frame(&options[..]);
+        let (lens, payload) = walk_headers(&bytes[..]).unwrap();
+        assert_eq!(lens, [14, 20, 8, 16]);
+        assert_eq!(payload, &TCP_FRAME[..]);
+        // the option exceeds the option length
+        let mut bytes = bytes;
+        bytes[53] = 0x02;
+        assert_eq!(walk_headers(&bytes[..]), Err(ParseError::Geneve));
+
+        let bytes = build_pppoe_frame(&[0x00, 0x21]);
+        let (lens, payload) = walk_headers(&bytes[..]).unwrap();
+        assert_eq!(lens, [14, 6, 2, 20, 40]);
+        assert!(payload.is_empty());
+
+        // an arp request, padded to the minimum frame length
+        let mut bytes = TCP_FRAME[..12].to_vec();
+        bytes.extend_from_slice(&[0x08, 0x06, 0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01]);
+        bytes.resize(60, 0);
+        let (lens, payload) = walk_headers(&bytes[..]).unwrap();
+        assert_eq!(lens, [14, 28]);
+        assert_eq!(payload, &[0; 18][..]);
+
+        // an echo request with 4 bytes of data, and an igmpv3 query with a
+        // single source
+        let echo = [
+            0x08, 0x00, 0xf7, 0xfd, 0x00, 0x01, 0x00, 0x01, 0xde, 0xad, 0xbe, 0xef,
+        ];
+        let bytes = build_ipv4_frame(1, &echo[..]);
+        let (lens, payload) = walk_headers(&bytes[..]).unwrap();
+        assert_eq!(lens, [14, 20, 8]);
+        assert_eq!(payload, &[0xde, 0xad, 0xbe, 0xef]);
+        let query = [
+            0x11, 0x64, 0x00, 0x00, 0xe0, 0x00, 0x00, 0xfb, 0x02, 0x7d, 0x00, 0x01, 0x0a, 0x00,
+            0x00, 0x01,
+        ];
+        let bytes = build_ipv4_frame(2, &query[..]);
+        let (lens, payload) = walk_headers(&bytes[..]).unwrap();
+        assert_eq!(lens, [14, 20, 16]);
+        assert!(payload.is_empty());
+
+        // the ethernet padding is not a part of the ip payload
+        let mut padded = TCP_FRAME.to_vec();
+        padded.extend_from_slice(&[0; 6]);
+        assert_eq!(walk_headers(&padded[..]).unwrap().1.len(), 0);
+
+        let mut truncated = TCP_FRAME.to_vec();
+        truncated.truncate(60);
+        assert_eq!(walk_headers(&truncated[..]), Err(ParseError::Ipv4));
+        // the tcp data offset exceeds the packet
+        let mut bytes = TCP_FRAME.to_vec();
+        bytes[46] = 0xf0;
+        assert_eq!(walk_headers(&bytes[..]), Err(ParseError::Tcp));
+    }
+
+    #[test]
+    fn parse_pppoe_frame() {
+        let plain = parse_untrusted(&TCP_FRAME[..]).unwrap();
+        let bytes = build_pppoe_frame(&[0x00, 0x21]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(
+            parsed.network,
+            Network::Ipv4 {
+                src: Ipv4Addr([192, 168, 1, 140]),
+                dst: Ipv4Addr([174, 143, 213, 184]),
+                protocol: IpProtocol::TCP,
+            }
+        );
+        assert_eq!(parsed.transport, plain.transport);
+        assert_eq!(parsed.payload_offset, bytes.len());
+
+        // a compressed ppp protocol field
+        let bytes = build_pppoe_frame(&[0x21]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(parsed.network, plain.network);
+        assert_eq!(parsed.payload_offset, bytes.len());
+
+        // the ethernet padding is not parsed as ip payload
+        let mut padded = bytes.clone();
+        padded.extend_from_slice(&[0; 6]);
+        assert_eq!(parse_untrusted(&padded[..]).unwrap(), parsed);
+
+        // a ppp control protocol
+        let bytes = build_pppoe_frame(&[0xc0, 0x21, 0x01, 0x01, 0x00, 0x04]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(parsed.network, Network::Other(EtherType::PPPOE_SESSION));
+        assert_eq!(parsed.payload_offset, 22);
+
+        // the pppoe length exceeds the frame
+        let mut bytes = build_pppoe_frame(&[0x00, 0x21]);
+        bytes.truncate(40);
+        assert_eq!(parse_untrusted(&bytes[..]), Err(ParseError::Pppoe));
+    }
+
+    // Replace the tcp segment of `TCP_FRAME` with `payload` of ip
+    // `protocol`.
+    fn build_ipv4_frame(protocol: u8, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = TCP_FRAME[..34].to_vec();
+        bytes[16..18].copy_from_slice(&(20 + payload.len() as u16).to_be_bytes());
+        bytes[23] = protocol;
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    #[test]
+    fn protocol_names() {
+        assert_eq!(EtherType::IPV4.name(), Some("IPv4"));
+        assert_eq!(EtherType::from(0x86dd).name(), Some("IPv6"));
+        assert_eq!(EtherType::from(0x8100), EtherType::VLAN);
+        assert_eq!(EtherType::QINQ.name(), Some("QinQ"));
+        assert_eq!(EtherType::MPLS.to_string(), "MPLS");
+        assert_eq!(EtherType::from(0x1234).name(), None);
+        assert_eq!(EtherType::from(0x1234).to_string(), "0x1234");
+
+        assert_eq!(IpProtocol::from(6).name(), Some("TCP"));
+        assert_eq!(IpProtocol::from(132), IpProtocol::SCTP);
+        assert_eq!(IpProtocol::GRE.name(), Some("GRE"));
+        assert_eq!(IpProtocol::ESP.to_string(), "ESP");
+        assert_eq!(IpProtocol::from(253).name(), None);
+        assert_eq!(IpProtocol::from(253).to_string(), "0xfd");
+
+        // the frames are dispatched by the named constants
+        let mut bytes = TCP_FRAME.to_vec();
+        bytes[12..14].copy_from_slice(&u16::from(EtherType::VLAN).to_be_bytes());
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(parsed.network, Network::Other(EtherType::VLAN));
+        assert_eq!(parsed.payload_offset, 14);
+
+        let bytes = build_ipv4_frame(IpProtocol::GRE.into(), &[0; 4]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(parsed.transport, Some(Transport::Other(IpProtocol::GRE)));
+    }
+
+    #[test]
+    fn parse_ipsec_frames() {
+        // an esp packet with 24 bytes of encrypted data
+        let mut esp = vec![0x12, 0x34, 0x56, 0x78, 0x00, 0x00, 0x00, 0x05];
+        esp.extend_from_slice(&[0xa5; 24]);
+        let bytes = build_ipv4_frame(50, &esp[..]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::Esp {
+                spi: 0x12345678,
+                seq_num: 5
+            })
+        );
+        // the encrypted data is not parsed
+        assert_eq!(parsed.payload_offset, 42);
+        assert_eq!(&bytes[parsed.payload_offset..], &[0xa5; 24][..]);
+
+        // an authentication header with a 12-byte icv, protecting the tcp
+        // segment of `TCP_FRAME`
+        let mut ah = vec![6, 4, 0, 0, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2a];
+        ah.extend_from_slice(&[0x5a; 12]);
+        ah.extend_from_slice(&TCP_FRAME[34..]);
+        let bytes = build_ipv4_frame(51, &ah[..]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::Ah {
+                spi: 0x100,
+                seq_num: 42,
+                next_header: IpProtocol::TCP
+            })
+        );
+        assert_eq!(parsed.payload_offset, 58);
+
+        // the headers can be walked generically past the icv
+        let mut buf = &bytes[34..];
+        let ahpkt = IpsecAuthHdrPacket::parse_next(&mut buf).unwrap();
+        assert_eq!(Header::header_len(&ahpkt), 24);
+        assert_eq!(ahpkt.cursor_icv_bytes(), &[0x5a; 12][..]);
+        let tcppkt = TcpPacket::parse_next(&mut buf).unwrap();
+        assert_eq!(tcppkt.dst_port(), 80);
+
+        // the esp header is truncated
+        let bytes = build_ipv4_frame(50, &esp[..4]);
+        assert_eq!(parse_untrusted(&bytes[..]), Err(ParseError::Esp));
+        // the ah payload length exceeds the packet
+        let bytes = build_ipv4_frame(51, &ah[..20]);
+        assert_eq!(parse_untrusted(&bytes[..]), Err(ParseError::Ah));
+    }
+
+    #[test]
+    fn parse_aaa_frames() {
+        // an access-request with a user-name attribute
+        let mut radius = vec![0x01, 0x07, 0x00, 0x1a];
+        radius.extend_from_slice(&[0xaa; 16]);
+        radius.extend_from_slice(&[0x01, 0x06]);
+        radius.extend_from_slice(b"nemo");
+        let mut udp = 50000_u16.to_be_bytes().to_vec();
+        udp.extend_from_slice(&RADIUS_AUTH_UDP_PORT.to_be_bytes());
+        udp.extend_from_slice(&(8 + radius.len() as u16).to_be_bytes());
+        udp.extend_from_slice(&[0, 0]);
+        udp.extend_from_slice(&radius[..]);
+        let bytes = build_ipv4_frame(17, &udp[..]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::Radius {
+                src_port: 50000,
+                dst_port: 1812,
+                code: RadiusCode::ACCESS_REQUEST,
+                identifier: 7
+            })
+        );
+        assert_eq!(parsed.payload_offset, 62);
+        // a malformed attribute list is plain udp
+        let mut bytes = bytes;
+        bytes[63] = 0;
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::Udp {
+                src_port: 50000,
+                dst_port: 1812
+            })
+        );
+        assert_eq!(parsed.payload_offset, 42);
+
+        // a device-watchdog request from the diameter port
+        let mut diameter = vec![0x01, 0x00, 0x00, 0x20, 0x80, 0x00, 0x01, 0x18];
+        diameter.extend_from_slice(&[0; 12]);
+        diameter.extend_from_slice(&[0x00, 0x00, 0x01, 0x08, 0x40, 0x00, 0x00, 0x0c]);
+        diameter.extend_from_slice(b"peer");
+        let mut tcp = TCP_FRAME[34..].to_vec();
+        tcp[0..2].copy_from_slice(&DIAMETER_PORT.to_be_bytes());
+        tcp.extend_from_slice(&diameter[..]);
+        let bytes = build_ipv4_frame(6, &tcp[..]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::Diameter {
+                src_port: 3868,
+                dst_port: 80,
+                command_code: DiameterCommand::DEVICE_WATCHDOG,
+                request: true,
+                application_id: 0
+            })
+        );
+        assert_eq!(parsed.payload_offset, 94);
+
+        // the rest of a message is plain tcp
+        let bytes = build_ipv4_frame(6, &tcp[..tcp.len() - 4]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::Tcp {
+                src_port: 3868,
+                dst_port: 80
+            })
+        );
+        assert_eq!(parsed.payload_offset, 74);
+    }
+
+    #[test]
+    fn parse_quic_frame() {
+        // a client initial packet with an 8-byte dcid
+        let mut quic = vec![0xc3, 0x00, 0x00, 0x00, 0x01, 0x08];
+        quic.extend_from_slice(&[0x11; 8]);
+        quic.extend_from_slice(&[0x00, 0x00, 0x40, 0x14]);
+        quic.extend_from_slice(&[0; 20]);
+        let mut udp = 50000_u16.to_be_bytes().to_vec();
+        udp.extend_from_slice(&QUIC_PORT.to_be_bytes());
+        udp.extend_from_slice(&(8 + quic.len() as u16).to_be_bytes());
+        udp.extend_from_slice(&[0, 0]);
+        udp.extend_from_slice(&quic[..]);
+        let bytes = build_ipv4_frame(17, &udp[..]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::Quic {
+                src_port: 50000,
+                dst_port: 443,
+                version: QuicVersion::V1,
+                packet_type: QuicPacketType::INITIAL
+            })
+        );
+        assert_eq!(parsed.payload_offset, 42);
+        let header = QuicPacket::parse(Cursor::new(&bytes[parsed.payload_offset..])).unwrap();
+        assert_eq!(header.dcid(), &[0x11; 8]);
+
+        // a short header packet, or quic on another port, is plain udp
+        for (offset, value) in [(42, 0x43), (36, 0x02)] {
+            let mut bytes = bytes.clone();
+            bytes[offset] = value;
+            let parsed = parse_untrusted(&bytes[..]).unwrap();
+            assert!(matches!(parsed.transport, Some(Transport::Udp { .. })));
+            assert_eq!(parsed.payload_offset, 42);
+        }
+    }
+
+    #[test]
+    fn parse_l2tp_frames() {
+        // an l2tpv2 data message whose ppp frame carries the inner ipv4 packet
+        let inner = build_ipv4_frame(17, &[0x00, 0x35, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00]);
+        let mut l2tp = vec![0x00, 0x02, 0x00, 0x07, 0x00, 0x09, 0xff, 0x03, 0x00, 0x21];
+        l2tp.extend_from_slice(&inner[14..]);
+        let mut udp = 50000_u16.to_be_bytes().to_vec();
+        udp.extend_from_slice(&L2TP_UDP_PORT.to_be_bytes());
+        udp.extend_from_slice(&(8 + l2tp.len() as u16).to_be_bytes());
+        udp.extend_from_slice(&[0, 0]);
+        udp.extend_from_slice(&l2tp[..]);
+        let bytes = build_ipv4_frame(17, &udp[..]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::L2tp {
+                version: 2,
+                tunnel_id: Some(7),
+                session_id: 9,
+                control: false
+            })
+        );
+        assert_eq!(parsed.payload_offset, 48);
+        assert_eq!(&bytes[parsed.payload_offset + 4..], &inner[14..]);
+
+        // an invalid l2tp header on the l2tp port is plain udp
+        let mut invalid = bytes.clone();
+        invalid[43] = 0x05;
+        let parsed = parse_untrusted(&invalid[..]).unwrap();
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::Udp {
+                src_port: 50000,
+                dst_port: L2TP_UDP_PORT
+            })
+        );
+        assert_eq!(parsed.payload_offset, 42);
+
+        // an l2tpv3 data message over ip whose pseudowire frame is ethernet
+        let mut l2tp = 0x0001e240_u32.to_be_bytes().to_vec();
+        l2tp.extend_from_slice(&TCP_FRAME[..]);
+        let bytes = build_ipv4_frame(IpProtocol::L2TP.into(), &l2tp[..]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::L2tp {
+                version: 3,
+                tunnel_id: None,
+                session_id: 123456,
+                control: false
+            })
+        );
+        assert_eq!(parsed.payload_offset, 38);
+        let inner = parse_untrusted(&bytes[parsed.payload_offset..]).unwrap();
+        assert!(matches!(inner.transport, Some(Transport::Tcp { .. })));
+    }
+
+    #[test]
+    fn parse_ptp_frames() {
+        // a sync over ethernet, with a 10-byte origin timestamp
+        let mut ptp = vec![0x00, 0x02, 0x00, 0x2c, 0x00, 0x00, 0x02, 0x00];
+        ptp.extend_from_slice(&[0; 22]);
+        ptp.extend_from_slice(&[0x12, 0x34, 0x00, 0x00]);
+        ptp.extend_from_slice(&[0x00, 0x00, 0x65, 0x53, 0xf1, 0x00, 0x07, 0x5b, 0xcd, 0x15]);
+        let mut bytes = TCP_FRAME[..14].to_vec();
+        bytes[12..14].copy_from_slice(&u16::from(EtherType::PTP).to_be_bytes());
+        bytes.extend_from_slice(&ptp[..]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(
+            parsed.network,
+            Network::Ptp {
+                message_type: PtpMessageType::SYNC,
+                sequence_id: 0x1234
+            }
+        );
+        assert_eq!(parsed.transport, None);
+        assert_eq!(parsed.payload_offset, 14 + PTP_HEADER_LEN);
+        assert_eq!(parse_untrusted(&bytes[..50]), Err(ParseError::Ptp));
+
+        // the follow up over udp to the general port
+        ptp[0] = 0x08;
+        let mut udp = PTP_GENERAL_UDP_PORT.to_be_bytes().to_vec();
+        udp.extend_from_slice(&PTP_GENERAL_UDP_PORT.to_be_bytes());
+        udp.extend_from_slice(&(8 + ptp.len() as u16).to_be_bytes());
+        udp.extend_from_slice(&[0, 0]);
+        udp.extend_from_slice(&ptp[..]);
+        let bytes = build_ipv4_frame(17, &udp[..]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::Ptp {
+                src_port: 320,
+                dst_port: 320,
+                message_type: PtpMessageType::FOLLOW_UP,
+                sequence_id: 0x1234
+            })
+        );
+        assert_eq!(parsed.payload_offset, 42 + PTP_HEADER_LEN);
+        let header = PtpPacket::parse(Cursor::new(&bytes[42..])).unwrap();
+        assert!(header.message().is_some());
+
+        // a message of another version on the ptp port is plain udp
+        let mut invalid = bytes.clone();
+        invalid[43] = 0x01;
+        let parsed = parse_untrusted(&invalid[..]).unwrap();
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::Udp {
+                src_port: PTP_GENERAL_UDP_PORT,
+                dst_port: PTP_GENERAL_UDP_PORT
+            })
+        );
+        assert_eq!(parsed.payload_offset, 42);
+    }
+
+    #[test]
+    fn parse_custom_protocols() {
+        // a 4-byte header with the header length in the second byte
+        let handler = |bytes: &[u8]| bytes.get(1).map(|len| usize::from(*len));
+        let mut registry = ProtocolRegistry::new();
+        registry.register_ethertype(EtherType::from(0x88b5), handler);
+
+        let mut bytes = TCP_FRAME[..14].to_vec();
+        bytes[12..14].copy_from_slice(&[0x88, 0xb5]);
+        bytes.extend_from_slice(&[0x01, 0x04, 0x00, 0x00, 0xaa, 0xbb]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(parsed.network, Network::Other(EtherType::from(0x88b5)));
+        assert_eq!(parsed.payload_offset, 14);
+        let parsed = parse_untrusted_with(&bytes[..], &registry).unwrap();
+        assert_eq!(parsed.network, Network::Other(EtherType::from(0x88b5)));
+        assert_eq!(parsed.payload_offset, 18);
+        // the header length exceeds the packet
+        bytes[15] = 7;
+        assert_eq!(
+            parse_untrusted_with(&bytes[..], &registry),
+            Err(ParseError::Custom)
+        );
+
+        // an experimental ip protocol, and an override of udp
+        registry.register_ip_protocol(IpProtocol::from(253), handler);
+        let bytes = build_ipv4_frame(253, &[0x01, 0x02, 0xcc]);
+        assert_eq!(parse_untrusted(&bytes[..]).unwrap().payload_offset, 34);
+        let parsed = parse_untrusted_with(&bytes[..], &registry).unwrap();
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::Other(IpProtocol::from(253)))
+        );
+        assert_eq!(parsed.payload_offset, 36);
+
+        let bytes = build_ipv4_frame(17, &[0x00, 0x35, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00]);
+        let parsed = parse_untrusted_with(&bytes[..], &registry).unwrap();
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::Udp {
+                src_port: 53,
+                dst_port: 53
+            })
+        );
+        registry.register_ip_protocol(IpProtocol::UDP, |_: &[u8]| Some(4));
+        let parsed = parse_untrusted_with(&bytes[..], &registry).unwrap();
+        assert_eq!(parsed.transport, Some(Transport::Other(IpProtocol::UDP)));
+        assert_eq!(parsed.payload_offset, 38);
+
+        // the tcp frame is untouched by the registry
+        assert_eq!(
+            parse_untrusted_with(&TCP_FRAME[..], &registry),
+            parse_untrusted(&TCP_FRAME[..])
+        );
+    }
+
+    #[test]
+    fn parse_igmp_frames() {
+        // a v1 report, a v2 report and a v2 query of 224.0.0.251, whose
+        // checksum field would be read as the number of group records of a
+        // v3 report
+        for msg in [
+            [0x12, 0x00, 0x0e, 0xfb, 0xe0, 0x00, 0x00, 0xfb],
+            [0x16, 0x00, 0x09, 0x04, 0xe0, 0x00, 0x00, 0xfb],
+            [0x11, 0x64, 0xee, 0x9b, 0xe0, 0x00, 0x00, 0xfb],
+        ] {
+            let bytes = build_ipv4_frame(2, &msg[..]);
+            let parsed = parse_untrusted(&bytes[..]).unwrap();
+            assert_eq!(
+                parsed.transport,
+                Some(Transport::Igmp {
+                    msg_type: IgmpMsgType::from(msg[0])
+                })
+            );
+            assert_eq!(parsed.payload_offset, 34);
+        }
+
+        // a v3 report with a single record of 224.0.0.251
+        let report = [
+            0x22, 0x00, 0xf9, 0x02, 0x00, 0x00, 0x00, 0x01, 0x04, 0x00, 0x00, 0x00, 0xe0, 0x00,
+            0x00, 0xfb,
+        ];
+        let bytes = build_ipv4_frame(2, &report[..]);
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::Igmp {
+                msg_type: IgmpMsgType::V3_MEMBERSHIP_REPORT
+            })
+        );
+
+        // the group record is truncated
+        let bytes = build_ipv4_frame(2, &report[..12]);
+        assert_eq!(parse_untrusted(&bytes[..]), Err(ParseError::Igmp));
+    }
+
+    #[test]
+    fn regression_inputs() {
+        // a zero-length unknown tcp option used to loop forever
+        let mut bytes = TCP_FRAME;
+        bytes[54..56].copy_from_slice(&[0xfe, 0x00]);
+        assert_eq!(parse_untrusted(&bytes[..]), Err(ParseError::TcpOptions));
+        bytes[55] = 1;
+        assert_eq!(parse_untrusted(&bytes[..]), Err(ParseError::TcpOptions));
+
+        // a zero-length unknown ipv4 option, with the ihl set to 6
+        let mut bytes = [0; 78];
+        bytes[..34].copy_from_slice(&TCP_FRAME[..34]);
+        bytes[14] = 0x46;
+        bytes[16..18].copy_from_slice(&64_u16.to_be_bytes());
+        bytes[34..38].copy_from_slice(&[0xfe, 0x00, 0x00, 0x00]);
+        bytes[38..78].copy_from_slice(&TCP_FRAME[34..74]);
+        assert_eq!(parse_untrusted(&bytes[..]), Err(ParseError::Ipv4Options));
+
+        // a tcp data offset that exceeds the packet
+        let mut bytes = TCP_FRAME;
+        bytes[46] = 0xf0;
+        assert_eq!(parse_untrusted(&bytes[..]), Err(ParseError::Tcp));
+
+        // an ipv4 header length below the minimum
+        let mut bytes = TCP_FRAME;
+        bytes[14] = 0x41;
+        assert_eq!(parse_untrusted(&bytes[..]), Err(ParseError::Ipv4));
+
+        // an ipv6 packet with a hop-by-hop header longer than the packet
+        let mut bytes = [0; 64];
+        bytes[12..14].copy_from_slice(&[0x86, 0xdd]);
+        bytes[14] = 0x60;
+        bytes[18..20].copy_from_slice(&10_u16.to_be_bytes());
+        bytes[20] = 0;
+        bytes[54..56].copy_from_slice(&[6, 0xff]);
+        assert_eq!(parse_untrusted(&bytes[..]), Err(ParseError::Ipv6ExtHeader));
+
+        // a chain of empty destination options ends with no next header
+        let mut bytes = [0; 94];
+        bytes[12..14].copy_from_slice(&[0x86, 0xdd]);
+        bytes[14] = 0x60;
+        bytes[18..20].copy_from_slice(&40_u16.to_be_bytes());
+        bytes[20] = 60;
+        for i in 0..5 {
+            let next_header = if i == 4 { 59 } else { 60 };
+            bytes[54 + i * 8] = next_header;
+        }
+        let parsed = parse_untrusted(&bytes[..]).unwrap();
+        assert_eq!(parsed.transport, None);
+        assert_eq!(parsed.payload_offset, 94);
+
+        // a dns question whose name is a pointer to itself, the dns payload
+        // is left to the caller
+        let mut builder = crate::malformed::MalformedPacketBuilder::dns_query();
+        builder.loop_dns_compression();
+        let parsed = parse_untrusted(builder.as_bytes()).unwrap();
+        assert_eq!(
+            parsed.transport,
+            Some(Transport::Udp {
+                src_port: 50000,
+                dst_port: 53
+            })
+        );
+        assert_eq!(parsed.payload_offset, 42);
+    }
+}